@@ -0,0 +1,124 @@
+//! An example of serving Gotham on the event loop of a larger tokio application.
+
+extern crate futures;
+extern crate gotham;
+extern crate hyper;
+extern crate mime;
+extern crate tokio;
+
+use std::net;
+
+use futures::{Future, Stream};
+use hyper::{Body, Response, StatusCode};
+use tokio::io;
+use tokio::net::TcpListener;
+use tokio::reactor::Handle;
+use tokio::runtime::current_thread::Runtime;
+
+use gotham::helpers::http::response::create_response;
+use gotham::state::State;
+
+/// The admin endpoint, which is served by Gotham.
+pub fn status(state: State) -> (State, Response<Body>) {
+    let res = create_response(
+        &state,
+        StatusCode::OK,
+        Some((String::from("OK").into_bytes(), mime::TEXT_PLAIN)),
+    );
+
+    (state, res)
+}
+
+/// The rest of the application, which greets each client that connects to it.
+pub fn greet(listener: TcpListener) -> impl Future<Item = (), Error = ()> {
+    listener
+        .incoming()
+        .map_err(|e| eprintln!("accept error: {}", e))
+        .for_each(|socket| {
+            let greeting = io::write_all(socket, "Hello from the application!\n")
+                .map(|_| ())
+                .map_err(|e| eprintln!("write error: {}", e));
+
+            tokio::executor::current_thread::spawn(greeting);
+            Ok(())
+        })
+}
+
+/// Spawns the admin endpoint and the rest of the application onto the same `runtime`.
+///
+/// Gotham doesn't start any threads of its own; each connection runs on the event loop of
+/// `runtime`, which the application drives.
+pub fn spawn_onto(runtime: &mut Runtime, admin: net::TcpListener, app: TcpListener) {
+    runtime.spawn(gotham::bind_server(&Handle::default(), admin, || {
+        Ok(status)
+    }));
+    runtime.spawn(greet(app));
+}
+
+/// Start the application, serving both listeners on a single thread.
+pub fn main() {
+    let admin = net::TcpListener::bind("127.0.0.1:7878").unwrap();
+    let app = TcpListener::bind(&"127.0.0.1:7879".parse().unwrap()).unwrap();
+
+    println!("Listening for admin requests at http://127.0.0.1:7878");
+    println!("Listening for application connections at 127.0.0.1:7879");
+
+    let mut runtime = Runtime::new().unwrap();
+    spawn_onto(&mut runtime, admin, app);
+    runtime.run().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+    use std::net::SocketAddr;
+    use std::thread;
+
+    use futures::sync::oneshot;
+
+    /// Sends `request` from a client thread, resolving to everything the server sends back.
+    fn request(addr: SocketAddr, request: &'static [u8]) -> oneshot::Receiver<String> {
+        let (tx, rx) = oneshot::channel();
+
+        thread::spawn(move || {
+            let mut stream = net::TcpStream::connect(addr).unwrap();
+            stream.write_all(request).unwrap();
+
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            tx.send(response).unwrap();
+        });
+
+        rx
+    }
+
+    #[test]
+    fn serves_admin_endpoint_beside_application() {
+        let admin = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let admin_addr = admin.local_addr().unwrap();
+        let app = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let app_addr = app.local_addr().unwrap();
+
+        let mut runtime = Runtime::new().unwrap();
+        spawn_onto(&mut runtime, admin, app);
+
+        let admin_response = request(
+            admin_addr,
+            b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        );
+        let app_response = request(app_addr, b"");
+
+        // Both listeners are served by this thread, only while it drives the event loop.
+        let (admin_response, app_response) =
+            runtime.block_on(admin_response.join(app_response)).unwrap();
+
+        assert!(
+            admin_response.starts_with("HTTP/1.1 200 OK"),
+            admin_response
+        );
+        assert!(admin_response.ends_with("OK"), admin_response);
+        assert_eq!(app_response, "Hello from the application!\n");
+    }
+}
@@ -0,0 +1,104 @@
+//! Measures the cost of routing requests against a large generated route table, as found in
+//! applications with several hundred routes: deep static paths, paths with many dynamic
+//! segments, and requests which match no route at all.
+//!
+//! Each request is sent via `TestServer`, so the timings include the cost of the request and
+//! response as well as route resolution, which dominates as the table grows.
+//!
+//! Requires a nightly compiler:
+//!
+//! ```text
+//! cargo +nightly bench --features bench --bench router
+//! ```
+#![cfg(feature = "bench")]
+#![cfg_attr(feature = "bench", feature(test))]
+
+extern crate gotham;
+extern crate hyper;
+extern crate test;
+
+use hyper::{Body, Response, StatusCode};
+use test::{black_box, Bencher};
+
+use gotham::router::builder::*;
+use gotham::router::Router;
+use gotham::state::State;
+use gotham::test::TestServer;
+
+/// The number of resources in the generated table. Each resource has eight routes.
+const RESOURCES: usize = 100;
+
+fn handler(state: State) -> (State, Response<Body>) {
+    (state, Response::new(Body::empty()))
+}
+
+/// Builds a router resembling a large REST API, with 800 routes.
+fn router() -> Router {
+    build_simple_router(|route| {
+        for i in 0..RESOURCES {
+            let resource = format!("/api/v1/resource{}", i);
+
+            route.get(&resource).to(handler);
+            route.post(&resource).to(handler);
+            route.get(&format!("{}/:id", resource)).to(handler);
+            route.put(&format!("{}/:id", resource)).to(handler);
+            route.delete(&format!("{}/:id", resource)).to(handler);
+            route
+                .get(&format!("{}/:id/children/:child/items/:item", resource))
+                .to(handler);
+            route
+                .get(&format!("{}/settings/general/display/theme", resource))
+                .to(handler);
+            route.get(&format!("/static/resource{}/*", i)).to(handler);
+        }
+    })
+}
+
+fn bench_path(b: &mut Bencher, path: &str, status: StatusCode) {
+    let test_server = TestServer::new(router()).unwrap();
+    let uri = format!("http://localhost{}", path);
+
+    b.iter(|| {
+        let response = test_server.client().get(&uri).perform().unwrap();
+        assert_eq!(response.status(), status);
+        black_box(response);
+    })
+}
+
+#[bench]
+fn deep_static_path(b: &mut Bencher) {
+    bench_path(
+        b,
+        "/api/v1/resource99/settings/general/display/theme",
+        StatusCode::OK,
+    );
+}
+
+#[bench]
+fn dynamic_heavy_path(b: &mut Bencher) {
+    bench_path(
+        b,
+        "/api/v1/resource99/42/children/7/items/1001",
+        StatusCode::OK,
+    );
+}
+
+#[bench]
+fn glob_path(b: &mut Bencher) {
+    bench_path(b, "/static/resource99/css/vendor/site.css", StatusCode::OK);
+}
+
+#[bench]
+fn non_matching_deep_path(b: &mut Bencher) {
+    // Every resource branch is tried, down to the deepest dynamic route, before failing.
+    bench_path(
+        b,
+        "/api/v1/resource99/42/children/7/items/1001/missing",
+        StatusCode::NOT_FOUND,
+    );
+}
+
+#[bench]
+fn non_matching_path(b: &mut Bencher) {
+    bench_path(b, "/api/v1/missing/42", StatusCode::NOT_FOUND);
+}
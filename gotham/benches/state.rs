@@ -0,0 +1,123 @@
+//! Compares the cost of `State::put` and `State::borrow` against the `HashMap` storage which
+//! `State` previously used, for a fixture resembling the values stored during a typical request.
+//!
+//! Requires a nightly compiler:
+//!
+//! ```text
+//! cargo +nightly bench --features bench --bench state
+//! ```
+#![cfg(feature = "bench")]
+#![cfg_attr(feature = "bench", feature(test))]
+
+extern crate gotham;
+extern crate hyper;
+extern crate test;
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use hyper::header::HeaderMap;
+use hyper::{Body, Method, Uri, Version};
+use test::{black_box, Bencher};
+
+use gotham::state::{State, StateData};
+
+#[derive(Clone)]
+struct SessionId(u64);
+
+impl StateData for SessionId {}
+
+#[derive(Clone)]
+struct CurrentUser(String);
+
+impl StateData for CurrentUser {}
+
+#[derive(Clone)]
+struct PathExtractor(String);
+
+impl StateData for PathExtractor {}
+
+/// The storage used by `State` before the inline representation was introduced.
+struct Baseline {
+    data: HashMap<TypeId, Box<Any + Send>>,
+}
+
+impl Baseline {
+    fn new() -> Baseline {
+        Baseline {
+            data: HashMap::new(),
+        }
+    }
+
+    fn put<T: Any + Send>(&mut self, t: T) {
+        self.data.insert(TypeId::of::<T>(), Box::new(t));
+    }
+
+    fn borrow<T: Any + Send>(&self) -> &T {
+        self.data
+            .get(&TypeId::of::<T>())
+            .and_then(|b| b.downcast_ref::<T>())
+            .unwrap()
+    }
+}
+
+macro_rules! fixture {
+    ($state:ident) => {{
+        let addr: SocketAddr = "127.0.0.1:10000".parse().unwrap();
+        $state.put(addr);
+        $state.put(Method::GET);
+        $state.put("http://localhost/users/1".parse::<Uri>().unwrap());
+        $state.put(Version::HTTP_11);
+        $state.put(HeaderMap::new());
+        $state.put(Body::empty());
+        $state.put(SessionId(1));
+        $state.put(CurrentUser("alice".to_owned()));
+        $state.put(PathExtractor("1".to_owned()));
+    }};
+}
+
+macro_rules! borrow_all {
+    ($state:ident) => {{
+        black_box($state.borrow::<Method>());
+        black_box($state.borrow::<Uri>());
+        black_box($state.borrow::<HeaderMap>());
+        black_box($state.borrow::<SessionId>());
+        black_box($state.borrow::<CurrentUser>());
+        black_box($state.borrow::<PathExtractor>());
+    }};
+}
+
+#[bench]
+fn put_request_fixture(b: &mut Bencher) {
+    b.iter(|| {
+        State::with_new(|state| {
+            fixture!(state);
+            black_box(&state);
+        })
+    })
+}
+
+#[bench]
+fn put_request_fixture_baseline(b: &mut Bencher) {
+    b.iter(|| {
+        let mut state = Baseline::new();
+        fixture!(state);
+        black_box(&state);
+    })
+}
+
+#[bench]
+fn borrow_request_fixture(b: &mut Bencher) {
+    State::with_new(|state| {
+        fixture!(state);
+        b.iter(|| borrow_all!(state))
+    })
+}
+
+#[bench]
+fn borrow_request_fixture_baseline(b: &mut Bencher) {
+    let mut state = Baseline::new();
+    fixture!(state);
+    b.iter(|| borrow_all!(state))
+}
@@ -0,0 +1,55 @@
+use extractor::internal::ExtractorError;
+use state::StateData;
+
+/// Identifies the extractor of a route which failed to extract the request data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtractorKind {
+    /// The `PathExtractor`, deserializing the segments of the request path.
+    Path,
+
+    /// The `QueryStringExtractor`, deserializing the query string of the request.
+    QueryString,
+}
+
+/// Describes why an extractor failed, so that the `Response` to the request can explain the
+/// problem to the client.
+///
+/// When an extractor fails, the `ExtractionFailure` is placed into `State` before the `Router`
+/// creates the `Response`. It's passed to the `ExtractionFailureMapper` of the `Router`, when one
+/// is set, and is otherwise available to the `StaticResponseExtender` of the extractor, via
+/// `ExtractionFailure::try_borrow_from(state)`.
+#[derive(Clone, Debug)]
+pub struct ExtractionFailure {
+    kind: ExtractorKind,
+    field: Option<String>,
+    message: String,
+}
+
+impl ExtractionFailure {
+    pub(crate) fn new(kind: ExtractorKind, error: &ExtractorError) -> ExtractionFailure {
+        ExtractionFailure {
+            kind,
+            field: error.field(),
+            message: error.message(),
+        }
+    }
+
+    /// The extractor which failed.
+    pub fn kind(&self) -> ExtractorKind {
+        self.kind
+    }
+
+    /// The name of the field which couldn't be extracted, if the failure is specific to one field.
+    /// The field of a nested struct is given in the bracketed form used in the query string, e.g.
+    /// `page[size]`.
+    pub fn field(&self) -> Option<&str> {
+        self.field.as_ref().map(String::as_str)
+    }
+
+    /// Describes the failure, e.g. the error from converting the value of the field.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl StateData for ExtractionFailure {}
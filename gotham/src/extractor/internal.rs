@@ -12,7 +12,9 @@ use serde::de::{
     VariantAccess, Visitor,
 };
 
+use extractor::QueryStringOptions;
 use helpers::http::request::query_string::QueryStringMapping;
+use helpers::http::FormUrlDecoded;
 use router::tree::segment::SegmentMapping;
 
 /// Describes the error cases which can result from deserializing a `ExtractorDeserializer` into a
@@ -81,11 +83,81 @@ pub(crate) enum ExtractorError {
     /// informative error messages.
     Custom(String),
 
+    /// A query string key was used both for a value and as the prefix of nested keys, such as in
+    /// `filter=open&filter[status]=closed`. The conflicting key is provided.
+    AmbiguousKey(String),
+
+    /// Nested keys were provided in the query string (e.g. `page[size]=20`), but the target type
+    /// of the field expected a single value or sequence of values. The key is provided.
+    UnexpectedNestedKeys(String),
+
+    /// Keys were provided in the query string which don't correspond to any field of the target
+    /// struct, when `QueryStringOptions::deny_unknown_keys` is set. The keys are provided.
+    UnknownKeys(Vec<String>),
+
+    /// The value for a field of the target struct failed to deserialize. The key of the field is
+    /// provided, along with the error from its value, which may itself be an `InvalidField` error
+    /// for a field of a nested struct.
+    InvalidField(String, Box<ExtractorError>),
+
+    /// No value was provided for a required field of the target struct. The field is provided.
+    MissingField(&'static str),
+
     // Variants may be added in future, and it will not be considered a breaking change.
     #[doc(hidden)]
     __NonExhaustive,
 }
 
+impl ExtractorError {
+    /// Attributes an error from deserializing the value of `key` to that field. Errors which
+    /// already identify the keys involved are left as they are.
+    fn within_field(self, key: &str) -> ExtractorError {
+        match self {
+            ExtractorError::AmbiguousKey(_)
+            | ExtractorError::UnexpectedNestedKeys(_)
+            | ExtractorError::UnknownKeys(_) => self,
+            _ => ExtractorError::InvalidField(key.to_owned(), Box::new(self)),
+        }
+    }
+
+    /// The key of the field which failed to deserialize, if known. The field of a nested struct
+    /// is given in the bracketed form used in the query string, e.g. `page[size]`.
+    pub(crate) fn field(&self) -> Option<String> {
+        match *self {
+            ExtractorError::InvalidField(ref key, ref error) => match error.field() {
+                Some(ref inner) => {
+                    let split = inner.find('[').unwrap_or(inner.len());
+                    Some(format!("{}[{}]{}", key, &inner[..split], &inner[split..]))
+                }
+                None => Some(key.clone()),
+            },
+            ExtractorError::MissingField(field) => Some(field.to_owned()),
+            _ => None,
+        }
+    }
+
+    /// Describes the error, without the field which it occurred in.
+    pub(crate) fn message(&self) -> String {
+        match *self {
+            ExtractorError::InvalidField(_, ref error) => error.message(),
+            ExtractorError::ParseError(ref message) | ExtractorError::Custom(ref message) => {
+                message.clone()
+            }
+            ExtractorError::MissingField(_) => "no value was provided".to_owned(),
+            ExtractorError::NoValues => "no value was provided".to_owned(),
+            ExtractorError::MultipleValues => "multiple values were provided".to_owned(),
+            ExtractorError::UnknownKeys(ref keys) => format!("unknown keys: {}", keys.join(", ")),
+            ExtractorError::AmbiguousKey(ref key) => {
+                format!("`{}` was given both a value and nested keys", key)
+            }
+            ExtractorError::UnexpectedNestedKeys(ref key) => {
+                format!("`{}` was given nested keys, but accepts a value", key)
+            }
+            _ => format!("{:?}", self),
+        }
+    }
+}
+
 impl Display for ExtractorError {
     fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
         out.write_fmt(format_args!("{:?}", self))
@@ -105,6 +177,10 @@ impl de::Error for ExtractorError {
     {
         ExtractorError::Custom(format!("{}", t))
     }
+
+    fn missing_field(field: &'static str) -> ExtractorError {
+        ExtractorError::MissingField(field)
+    }
 }
 
 /// Implements one `Deserializer` function (`$trait_fn`) to parse a single value using the
@@ -179,7 +255,7 @@ macro_rules! reject_value_type {
 /// primarily giving us one place to deal with the type structure and expose the `next` function
 /// that we require.
 ///
-/// See `from_segment_mapping` and `from_query_string` for how the values are constructed.
+/// See `from_segment_mapping` for how the values are constructed.
 trait ExtractorDataSource<'a> {
     type Iterator: Iterator<Item = (&'a str, Self::ValueIterator)>;
     type ValueIterator: IntoIterator<Item = &'a Self::Value>;
@@ -189,8 +265,8 @@ trait ExtractorDataSource<'a> {
     fn next(&mut self) -> Option<(&'a str, Self::ValueIterator)>;
 }
 
-/// Concrete type which implements `ExtractorDataSource`. See `from_segment_mapping` and
-/// `from_query_string` for how this is constructed and used.
+/// Concrete type which implements `ExtractorDataSource`. See `from_segment_mapping` for how this is
+/// constructed and used.
 struct IteratorAdaptor<'a, I, VI, V>
 where
     I: Iterator<Item = (&'a str, VI)>,
@@ -250,14 +326,28 @@ where
 }
 
 /// Deserializes a value of type `T` from a set of query parameters.
+///
+/// Keys with a bracketed path, such as `filter[status]`, are grouped by their prefix and
+/// deserialized as a nested struct or map. Keys which don't correspond to a field of a struct are
+/// ignored, unless `options` deny unknown keys.
 pub(crate) fn from_query_string_mapping<'de, T>(
     qsm: &'de QueryStringMapping,
+    options: QueryStringOptions,
 ) -> Result<T, ExtractorError>
 where
     T: Deserialize<'de>,
 {
-    let iter = qsm.iter().map(|(k, v)| (k.as_str(), v));
-    from_data_source(IteratorAdaptor { iter })
+    let mut entries = Vec::new();
+
+    for (key, values) in qsm {
+        insert_query_string_node(&mut entries, &query_string_key_path(key), 0, values)?;
+    }
+
+    T::deserialize(QueryStringMap {
+        prefix: None,
+        entries,
+        deny_unknown_keys: options.deny_unknown_keys(),
+    })
 }
 
 /// Implements a `Deserializer` for the full set of extracted path segments. This is the top level
@@ -406,18 +496,21 @@ where
         V: DeserializeSeed<'de>,
     {
         match self.current.take() {
-            Some((_k, values)) => {
+            Some((key, values)) => {
                 let deserializer = DeserializeValues {
                     values: values.into_iter().map(convert_to_string_ref),
+                    joined: true,
                 };
                 seed.deserialize(deserializer)
+                    .map_err(|e| e.within_field(key))
             }
             None => Err(ExtractorError::NoCurrentItem),
         }
     }
 }
 
-/// Deserializes an identifier string into an identifier. Just serde boilerplate.
+/// Deserializes an identifier string into an identifier, or into a string for the keys of a map
+/// (e.g. `HashMap<String, String>`). Just serde boilerplate.
 struct DeserializeKey<'de> {
     key: &'de str,
 }
@@ -432,6 +525,20 @@ impl<'de> Deserializer<'de> for DeserializeKey<'de> {
         visitor.visit_str(self.key)
     }
 
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_borrowed_str(self.key)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_borrowed_str(self.key)
+    }
+
     fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
@@ -441,7 +548,7 @@ impl<'de> Deserializer<'de> for DeserializeKey<'de> {
     }
 
     forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char bytes
         byte_buf option unit unit_struct newtype_struct seq tuple
         tuple_struct map struct enum ignored_any
     }
@@ -454,6 +561,10 @@ where
     I: Iterator<Item = &'de str>,
 {
     values: I,
+    // Whether the values are joined by `/` when deserialized into a string, rather than requiring
+    // a single value. This is the case for path segments, where only a glob has any number of
+    // values other than one.
+    joined: bool,
 }
 
 /// Convert the value from a single-item list of percent-decoded strings by using
@@ -500,7 +611,6 @@ where
     single_value_type!(deserialize_u64, visit_u64);
     single_value_type!(deserialize_f32, visit_f32);
     single_value_type!(deserialize_f64, visit_f64);
-    single_value_type!(deserialize_string, visit_string);
     single_value_type!(deserialize_byte_buf, visit_string);
     single_value_type!(deserialize_char, visit_char);
 
@@ -541,15 +651,45 @@ where
     where
         V: Visitor<'de>,
     {
+        if self.joined {
+            let values = self.values.collect::<Vec<_>>();
+            return match values.len() {
+                1 => visitor.visit_borrowed_str(values[0]),
+                _ => visitor.visit_string(values.join("/")),
+            };
+        }
+
         let val = extract_single_value(self.values)?;
         visitor.visit_borrowed_str(val)
     }
 
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.joined {
+            return visitor.visit_string(self.values.collect::<Vec<_>>().join("/"));
+        }
+
+        let val = parse_single_value(self.values)?;
+        visitor.visit_string(val)
+    }
+
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_some(self)
+        // An empty query string value (e.g. `?page=`) is treated in the same way as an absent
+        // key, rather than failing to parse, or giving an empty string.
+        let values = self.values.collect::<Vec<_>>();
+        if !self.joined && values.iter().all(|value| value.is_empty()) {
+            return visitor.visit_none();
+        }
+
+        visitor.visit_some(DeserializeValues {
+            values: values.into_iter(),
+            joined: self.joined,
+        })
     }
 
     fn deserialize_enum<V>(
@@ -633,6 +773,7 @@ where
             Some(val) => {
                 let val = seed.deserialize(DeserializeValues {
                     values: vec![val].into_iter(),
+                    joined: false,
                 })?;
                 Ok(Some(val))
             }
@@ -699,10 +840,262 @@ impl<'de> VariantAccess<'de> for UnitVariant {
     }
 }
 
+/// A query string key within a `QueryStringMap`, which holds either values or a group of nested
+/// keys.
+enum QueryStringNode<'de> {
+    Values(&'de [FormUrlDecoded]),
+    Map(QueryStringMap<'de>),
+}
+
+/// Holds a group of query string keys which share a prefix, or the full set of keys at the top
+/// level, for deserializing into a struct or map.
+struct QueryStringMap<'de> {
+    prefix: Option<String>,
+    entries: Vec<(&'de str, QueryStringNode<'de>)>,
+    // Whether keys which don't correspond to a field of a struct are rejected. This is set on
+    // the nested groups of a struct as it's deserialized.
+    deny_unknown_keys: bool,
+}
+
+/// Splits a query string key into its path, e.g. `filter[status]` becomes `["filter", "status"]`.
+/// Keys which aren't a well-formed bracketed path are used as a single segment.
+fn query_string_key_path(key: &str) -> Vec<&str> {
+    if let Some(start) = key.find('[') {
+        if start > 0 && key.ends_with(']') {
+            let segments: Vec<&str> = key[start + 1..key.len() - 1].split("][").collect();
+
+            if segments
+                .iter()
+                .all(|seg| !seg.is_empty() && !seg.contains(|c: char| c == '[' || c == ']'))
+            {
+                let mut path = vec![&key[..start]];
+                path.extend(segments);
+                return path;
+            }
+        }
+    }
+
+    vec![key]
+}
+
+/// Formats a key path in the bracketed form used in the query string.
+fn display_key_path(path: &[&str]) -> String {
+    let mut key = path[0].to_owned();
+
+    for seg in &path[1..] {
+        key.push('[');
+        key.push_str(seg);
+        key.push(']');
+    }
+
+    key
+}
+
+/// Inserts the values for a key path into the group, beginning at the segment given by `depth`.
+/// Fails if the key path conflicts with a key for which values were already inserted.
+fn insert_query_string_node<'de>(
+    entries: &mut Vec<(&'de str, QueryStringNode<'de>)>,
+    path: &[&'de str],
+    depth: usize,
+    values: &'de [FormUrlDecoded],
+) -> Result<(), ExtractorError> {
+    let name = path[depth];
+    let position = entries.iter().position(|&(key, _)| key == name);
+
+    if depth + 1 == path.len() {
+        return match position {
+            Some(_) => Err(ExtractorError::AmbiguousKey(display_key_path(path))),
+            None => {
+                entries.push((name, QueryStringNode::Values(values)));
+                Ok(())
+            }
+        };
+    }
+
+    let index = match position {
+        Some(index) => index,
+        None => {
+            let map = QueryStringMap {
+                prefix: Some(display_key_path(&path[..=depth])),
+                entries: Vec::new(),
+                deny_unknown_keys: false,
+            };
+            entries.push((name, QueryStringNode::Map(map)));
+            entries.len() - 1
+        }
+    };
+
+    match entries[index].1 {
+        QueryStringNode::Map(ref mut map) => {
+            insert_query_string_node(&mut map.entries, path, depth + 1, values)
+        }
+        QueryStringNode::Values(_) => Err(ExtractorError::AmbiguousKey(display_key_path(
+            &path[..=depth],
+        ))),
+    }
+}
+
+/// Implements a `Deserializer` for a group of query string keys. Only structured types (i.e.
+/// structs and maps) can be deserialized from the group.
+impl<'de> Deserializer<'de> for QueryStringMap<'de> {
+    type Error = ExtractorError;
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(QueryStringMapAccess {
+            entries: self.entries.into_iter(),
+            current: None,
+        })
+    }
+
+    fn deserialize_struct<V>(
+        mut self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.deny_unknown_keys {
+            let unknown = self
+                .entries
+                .iter()
+                .map(|&(key, _)| key)
+                .filter(|key| !fields.iter().any(|field| field == key))
+                .map(|key| match self.prefix {
+                    Some(ref prefix) => format!("{}[{}]", prefix, key),
+                    None => key.to_owned(),
+                })
+                .collect::<Vec<_>>();
+
+            if !unknown.is_empty() {
+                return Err(ExtractorError::UnknownKeys(unknown));
+            }
+
+            for &mut (_, ref mut node) in &mut self.entries {
+                if let QueryStringNode::Map(ref mut map) = *node {
+                    map.deny_unknown_keys = true;
+                }
+            }
+        }
+
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.prefix {
+            Some(prefix) => Err(ExtractorError::UnexpectedNestedKeys(prefix)),
+            None => Err(ExtractorError::UnexpectedTargetType(
+                "unsupported target type for query string extractor",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
+        byte_buf seq tuple tuple_struct enum identifier
+    }
+}
+
+/// Iterates through a group of query string keys, yielding each key and its values or nested
+/// group.
+struct QueryStringMapAccess<'de> {
+    entries: ::std::vec::IntoIter<(&'de str, QueryStringNode<'de>)>,
+    current: Option<(&'de str, QueryStringNode<'de>)>,
+}
+
+impl<'de> MapAccess<'de> for QueryStringMapAccess<'de> {
+    type Error = ExtractorError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.entries.next() {
+            Some((key, node)) => {
+                self.current = Some((key, node));
+                let key = seed.deserialize(DeserializeKey { key })?;
+                Ok(Some(key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.current.take() {
+            Some((key, QueryStringNode::Values(values))) => seed
+                .deserialize(DeserializeValues {
+                    values: values.iter().map(convert_to_string_ref),
+                    joined: false,
+                })
+                .map_err(|e| e.within_field(key)),
+            Some((key, QueryStringNode::Map(map))) => {
+                seed.deserialize(map).map_err(|e| e.within_field(key))
+            }
+            None => Err(ExtractorError::NoCurrentItem),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use helpers::http::{FormUrlDecoded, PercentDecoded};
+    use extractor::QueryStringOptions;
+    use std::collections::HashMap;
+    use helpers::http::request::query_string::split;
+    use helpers::http::PercentDecoded;
     use std;
 
     #[derive(Deserialize)]
@@ -836,7 +1229,7 @@ mod tests {
             vec![FormUrlDecoded::new("this is optional").unwrap()],
         );
 
-        let p = from_query_string_mapping::<SimpleValues>(&qsm).unwrap();
+        let p = from_query_string_mapping::<SimpleValues>(&qsm, QueryStringOptions::new()).unwrap();
 
         assert_eq!(p.bool_val, true);
         assert_eq!(p.i8_val, 15);
@@ -890,6 +1283,63 @@ mod tests {
         }
     }
 
+    #[derive(Deserialize)]
+    struct GlobSegments {
+        #[serde(rename = "*")]
+        ids: Vec<u32>,
+    }
+
+    #[derive(Deserialize)]
+    struct JoinedGlob {
+        path: String,
+    }
+
+    #[test]
+    fn glob_values_path_tests() {
+        let one = PercentDecoded::new("1").unwrap();
+        let two = PercentDecoded::new("2").unwrap();
+        let invalid = PercentDecoded::new("two").unwrap();
+
+        let cases: Vec<(Vec<&PercentDecoded>, Vec<u32>, &str)> = vec![
+            (vec![&one], vec![1], "1"),
+            (vec![&one, &two], vec![1, 2], "1/2"),
+            (vec![], vec![], ""),
+        ];
+
+        for (values, ids, path) in cases {
+            let mut sm = SegmentMapping::new();
+            sm.insert("*", values.clone());
+            let p = from_segment_mapping::<GlobSegments>(sm).unwrap();
+            assert_eq!(p.ids, ids);
+
+            let mut sm = SegmentMapping::new();
+            sm.insert("path", values);
+            let p = from_segment_mapping::<JoinedGlob>(sm).unwrap();
+            assert_eq!(p.path, path);
+        }
+
+        let mut sm = SegmentMapping::new();
+        sm.insert("*", vec![&one, &invalid]);
+        assert!(from_segment_mapping::<GlobSegments>(sm).is_err());
+    }
+
+    #[test]
+    fn map_values_path_tests() {
+        let one = PercentDecoded::new("1").unwrap();
+        let two = PercentDecoded::new("2").unwrap();
+        let slug = PercentDecoded::new("a b").unwrap();
+
+        let mut sm = SegmentMapping::new();
+        sm.insert("*", vec![&one, &two]);
+        sm.insert("slug", vec![&slug]);
+
+        let p = from_segment_mapping::<HashMap<String, Vec<String>>>(sm).unwrap();
+
+        assert_eq!(p.len(), 2);
+        assert_eq!(p["*"], vec!["1", "2"]);
+        assert_eq!(p["slug"], vec!["a b"]);
+    }
+
     #[test]
     fn byte_buf_values_path_tests() {
         let bytes_val = PercentDecoded::new("bytes").unwrap();
@@ -910,7 +1360,7 @@ mod tests {
             vec![FormUrlDecoded::new("bytes").unwrap()],
         );
 
-        let p = from_query_string_mapping::<WithByteBuf>(&qsm).unwrap();
+        let p = from_query_string_mapping::<WithByteBuf>(&qsm, QueryStringOptions::new()).unwrap();
 
         assert_eq!(&p.bytes_val[..], b"bytes");
     }
@@ -973,7 +1423,8 @@ mod tests {
             vec![FormUrlDecoded::new("borrowed_bytes").unwrap()],
         );
 
-        let p = from_query_string_mapping::<WithBorrowedBytes>(&qsm).unwrap();
+        let p = from_query_string_mapping::<WithBorrowedBytes>(&qsm, QueryStringOptions::new())
+            .unwrap();
 
         assert_eq!(&p.bytes_val[..], b"borrowed_bytes");
     }
@@ -1033,7 +1484,8 @@ mod tests {
             vec![FormUrlDecoded::new("borrowed_str").unwrap()],
         );
 
-        let p = from_query_string_mapping::<WithBorrowedString>(&qsm).unwrap();
+        let p = from_query_string_mapping::<WithBorrowedString>(&qsm, QueryStringOptions::new())
+            .unwrap();
 
         assert_eq!(p.str_val, "borrowed_str");
     }
@@ -1071,7 +1523,7 @@ mod tests {
             vec![FormUrlDecoded::new("b").unwrap()],
         );
 
-        let p = from_query_string_mapping::<WithEnum>(&qsm).unwrap();
+        let p = from_query_string_mapping::<WithEnum>(&qsm, QueryStringOptions::new()).unwrap();
 
         assert_eq!(p.enum_val, MyEnumType::B);
     }
@@ -1114,11 +1566,264 @@ mod tests {
             ],
         );
 
-        let p = from_query_string_mapping::<WithSeq>(&qsm).unwrap();
+        let p = from_query_string_mapping::<WithSeq>(&qsm, QueryStringOptions::new()).unwrap();
 
         assert_eq!(p.seq_val, vec![15, 16, 17, 18, 19]);
     }
 
+    #[derive(Deserialize)]
+    struct WithRepeatedKeys {
+        #[serde(default)]
+        tag: Vec<String>,
+        id: Option<Vec<i32>>,
+        page: Option<u32>,
+    }
+
+    fn split_query(query: &str, bracket_suffix: bool) -> QueryStringMapping {
+        let options = QueryStringOptions::new().with_bracket_suffix(bracket_suffix);
+        split(Some(query), options)
+    }
+
+    #[test]
+    fn repeated_keys_query_tests() {
+        let qsm = split_query("", false);
+        let p =
+            from_query_string_mapping::<WithRepeatedKeys>(&qsm, QueryStringOptions::new()).unwrap();
+        assert!(p.tag.is_empty());
+        assert!(p.id.is_none());
+        assert!(p.page.is_none());
+
+        let qsm = split_query("tag=a&id=1&page=2", false);
+        let p =
+            from_query_string_mapping::<WithRepeatedKeys>(&qsm, QueryStringOptions::new()).unwrap();
+        assert_eq!(p.tag, vec!["a"]);
+        assert_eq!(p.id, Some(vec![1]));
+        assert_eq!(p.page, Some(2));
+
+        let qsm = split_query("tag=a&id=3&tag=b&id=1&tag=c", false);
+        let p =
+            from_query_string_mapping::<WithRepeatedKeys>(&qsm, QueryStringOptions::new()).unwrap();
+        assert_eq!(p.tag, vec!["a", "b", "c"]);
+        assert_eq!(p.id, Some(vec![3, 1]));
+
+        // A scalar field doesn't accept a repeated key.
+        let qsm = split_query("page=1&page=2", false);
+        match from_query_string_mapping::<WithRepeatedKeys>(&qsm, QueryStringOptions::new()) {
+            Err(ExtractorError::InvalidField(ref key, ref e)) if key == "page" => match **e {
+                ExtractorError::MultipleValues => (),
+                ref e => panic!("unexpected error: {}", e),
+            },
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("expected repeated scalar key to be rejected"),
+        }
+    }
+
+    #[test]
+    fn bracket_suffix_query_tests() {
+        let qsm = split_query("tag[]=a&tag[]=b&id[]=4&page=5", true);
+        let p =
+            from_query_string_mapping::<WithRepeatedKeys>(&qsm, QueryStringOptions::new()).unwrap();
+        assert_eq!(p.tag, vec!["a", "b"]);
+        assert_eq!(p.id, Some(vec![4]));
+        assert_eq!(p.page, Some(5));
+
+        // Without the option, the suffixed keys don't match any field.
+        let qsm = split_query("tag[]=a&tag[]=b&id[]=4&page=5", false);
+        let p =
+            from_query_string_mapping::<WithRepeatedKeys>(&qsm, QueryStringOptions::new()).unwrap();
+        assert!(p.tag.is_empty());
+        assert!(p.id.is_none());
+        assert_eq!(p.page, Some(5));
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Filter {
+        status: String,
+        assignee: Option<String>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Page {
+        size: u32,
+        number: Option<u32>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct WithNestedGroups {
+        filter: Filter,
+        page: Option<Page>,
+        sort: Option<String>,
+    }
+
+    #[test]
+    fn nested_groups_query_tests() {
+        let qsm = split_query(
+            "filter[status]=open&filter[assignee]=me&page[size]=20",
+            false,
+        );
+        let p =
+            from_query_string_mapping::<WithNestedGroups>(&qsm, QueryStringOptions::new()).unwrap();
+        assert_eq!(
+            p.filter,
+            Filter {
+                status: "open".to_owned(),
+                assignee: Some("me".to_owned()),
+            }
+        );
+        assert_eq!(
+            p.page,
+            Some(Page {
+                size: 20,
+                number: None,
+            })
+        );
+        assert!(p.sort.is_none());
+
+        // Missing optional group.
+        let qsm = split_query("filter[status]=closed&sort=created", false);
+        let p =
+            from_query_string_mapping::<WithNestedGroups>(&qsm, QueryStringOptions::new()).unwrap();
+        assert_eq!(p.filter.status, "closed");
+        assert!(p.filter.assignee.is_none());
+        assert!(p.page.is_none());
+        assert_eq!(p.sort, Some("created".to_owned()));
+
+        // Missing required field inside a group.
+        let qsm = split_query("filter[assignee]=me", false);
+        assert!(
+            from_query_string_mapping::<WithNestedGroups>(&qsm, QueryStringOptions::new()).is_err()
+        );
+    }
+
+    #[test]
+    fn nested_groups_type_mismatch_query_tests() {
+        let qsm = split_query("filter[status]=open&page[size]=large", false);
+        match from_query_string_mapping::<WithNestedGroups>(&qsm, QueryStringOptions::new()) {
+            Err(ref e) if e.field() == Some("page[size]".to_owned()) => {
+                assert_eq!(e.message(), "invalid digit found in string");
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("expected nested type mismatch to be rejected"),
+        }
+
+        let qsm = split_query("filter[status]=open&sort[field]=created", false);
+        match from_query_string_mapping::<WithNestedGroups>(&qsm, QueryStringOptions::new()) {
+            Err(ExtractorError::UnexpectedNestedKeys(ref key)) if key == "sort" => (),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("expected nested keys for a scalar to be rejected"),
+        }
+
+        let qsm = split_query("filter=open", false);
+        assert!(
+            from_query_string_mapping::<WithNestedGroups>(&qsm, QueryStringOptions::new()).is_err()
+        );
+    }
+
+    #[test]
+    fn nested_groups_ambiguous_key_query_tests() {
+        for query in &[
+            "filter=open&filter[status]=open",
+            "filter[status]=open&filter[status][name]=open",
+        ] {
+            let qsm = split_query(query, false);
+            match from_query_string_mapping::<WithNestedGroups>(&qsm, QueryStringOptions::new()) {
+                Err(ExtractorError::AmbiguousKey(_)) => (),
+                Err(e) => panic!("unexpected error for {}: {}", query, e),
+                Ok(_) => panic!("expected ambiguous key to be rejected for {}", query),
+            }
+        }
+    }
+
+    fn default_per_page() -> u32 {
+        20
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct WithOptionalParams {
+        q: String,
+        name: Option<String>,
+        page: Option<u32>,
+        #[serde(default)]
+        sort: String,
+        #[serde(default = "default_per_page")]
+        per_page: u32,
+    }
+
+    #[test]
+    fn optional_and_default_values_query_tests() {
+        let extract = |query: &str| {
+            let qsm = split_query(query, false);
+            from_query_string_mapping::<WithOptionalParams>(&qsm, QueryStringOptions::new())
+        };
+
+        let absent = WithOptionalParams {
+            q: "rust".to_owned(),
+            name: None,
+            page: None,
+            sort: "".to_owned(),
+            per_page: 20,
+        };
+        assert_eq!(extract("q=rust").unwrap(), absent);
+        assert_eq!(extract("q=rust&name&page&sort&per_page").unwrap(), absent);
+
+        let empty = WithOptionalParams {
+            q: "".to_owned(),
+            ..absent
+        };
+        assert_eq!(extract("q=&name=&page=&sort=").unwrap(), empty);
+
+        let present = WithOptionalParams {
+            q: "rust".to_owned(),
+            name: Some("ann".to_owned()),
+            page: Some(2),
+            sort: "created".to_owned(),
+            per_page: 50,
+        };
+        let query = "q=rust&name=ann&page=2&sort=created&per_page=50";
+        assert_eq!(extract(query).unwrap(), present);
+
+        assert!(extract("q=rust&per_page=").is_err());
+        assert!(extract("q=rust&page=two").is_err());
+        assert!(extract("name=ann").is_err());
+        assert!(extract("q=rust&utm_source=feed").is_ok());
+    }
+
+    #[test]
+    fn unknown_keys_query_tests() {
+        let options = QueryStringOptions::new().with_deny_unknown_keys(true);
+
+        let qsm = split_query("q=rust&page=2", false);
+        assert!(from_query_string_mapping::<WithOptionalParams>(&qsm, options).is_ok());
+
+        let qsm = split_query("q=rust&utm_source=feed&ref=home", false);
+        match from_query_string_mapping::<WithOptionalParams>(&qsm, options) {
+            Err(ExtractorError::UnknownKeys(mut keys)) => {
+                keys.sort();
+                assert_eq!(keys, vec!["ref", "utm_source"]);
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("expected unknown keys to be rejected"),
+        }
+
+        let qsm = split_query("filter[status]=open&filter[label]=bug", false);
+        match from_query_string_mapping::<WithNestedGroups>(&qsm, options) {
+            Err(ExtractorError::UnknownKeys(keys)) => assert_eq!(keys, vec!["filter[label]"]),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("expected unknown nested keys to be rejected"),
+        }
+    }
+
+    #[test]
+    fn query_string_key_path_tests() {
+        assert_eq!(query_string_key_path("filter"), vec!["filter"]);
+        assert_eq!(query_string_key_path("page[size]"), vec!["page", "size"]);
+        assert_eq!(query_string_key_path("a[b][c]"), vec!["a", "b", "c"]);
+        assert_eq!(query_string_key_path("tag[]"), vec!["tag[]"]);
+        assert_eq!(query_string_key_path("[size]"), vec!["[size]"]);
+        assert_eq!(query_string_key_path("a[b]c]"), vec!["a[b]c]"]);
+        assert_eq!(query_string_key_path("a[b"), vec!["a[b"]);
+    }
+
     #[derive(Deserialize, Eq, PartialEq, Debug)]
     struct IntWrapper(i32);
 
@@ -1147,7 +1852,8 @@ mod tests {
             vec![FormUrlDecoded::new("100").unwrap()],
         );
 
-        let p = from_query_string_mapping::<WithNewtypeStruct>(&qsm).unwrap();
+        let p = from_query_string_mapping::<WithNewtypeStruct>(&qsm, QueryStringOptions::new())
+            .unwrap();
 
         assert_eq!(p.wrapped_int_val, IntWrapper(100));
     }
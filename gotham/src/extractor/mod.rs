@@ -8,9 +8,13 @@
 //! the data and store it within the request `State` before the request is dispatched to the
 //! `Handler`.
 
+mod failure;
 pub(crate) mod internal;
+mod parse;
 mod path;
 mod query_string;
 
+pub use self::failure::*;
+pub use self::parse::*;
 pub use self::path::*;
 pub use self::query_string::*;
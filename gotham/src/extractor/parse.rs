@@ -0,0 +1,206 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::de::{self, Deserialize, Deserializer};
+
+/// Deserializes a field of an extractor from a `String`, via `FromStr`. This allows a type which
+/// implements `FromStr`, but not `Deserialize`, to be extracted from a path segment or query
+/// string value directly, using `#[serde(deserialize_with = "...")]`.
+///
+/// When the value can't be parsed, the extractor fails in the same way as when any other value
+/// can't be deserialized, so its `StaticResponseExtender` determines the response (which is
+/// `400 Bad Request` when derived).
+///
+/// Types which implement `Deserialize` by deserializing a string, such as `uuid::Uuid` and, with
+/// the `chrono` feature of Gotham enabled, the `chrono` date and time types, don't need this
+/// function and can be used as the type of a field directly.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # #[macro_use]
+/// # extern crate gotham_derive;
+/// # extern crate hyper;
+/// # extern crate serde;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// #
+/// # use std::str::FromStr;
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::state::{FromState, State};
+/// # use gotham::helpers::http::response::create_text_response;
+/// # use gotham::router::Router;
+/// # use gotham::router::builder::*;
+/// # use gotham::test::TestServer;
+/// #
+/// struct Sku(String);
+///
+/// impl FromStr for Sku {
+///     type Err = String;
+///
+///     fn from_str(s: &str) -> Result<Sku, String> {
+///         if s.len() == 8 && s.chars().all(|c| c.is_ascii_alphanumeric()) {
+///             Ok(Sku(s.to_uppercase()))
+///         } else {
+///             Err(format!("invalid SKU: {}", s))
+///         }
+///     }
+/// }
+///
+/// #[derive(Deserialize, StateData, StaticResponseExtender)]
+/// struct ProductPath {
+///     #[serde(deserialize_with = "gotham::extractor::deserialize_from_str")]
+///     sku: Sku,
+/// }
+///
+/// fn handler(mut state: State) -> (State, Response<Body>) {
+///     let ProductPath { sku } = ProductPath::take_from(&mut state);
+///     let res = create_text_response(&state, StatusCode::OK, sku.0);
+///     (state, res)
+/// }
+///
+/// fn router() -> Router {
+///     build_simple_router(|route| {
+///         route
+///             .get("/products/:sku")
+///             .with_path_extractor::<ProductPath>()
+///             .to(handler);
+///     })
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://example.com/products/ab12cd34")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "AB12CD34");
+/// #
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://example.com/products/ab12")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+/// # }
+/// ```
+pub fn deserialize_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, Response, StatusCode};
+    use uuid::Uuid;
+
+    use helpers::http::response::create_text_response;
+    use router::builder::*;
+    use router::response::extender::StaticResponseExtender;
+    use state::{State, StateData};
+    use test::TestServer;
+
+    struct Version(u32, u32);
+
+    impl FromStr for Version {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Version, String> {
+            let mut parts = s.splitn(2, '.').map(|part| part.parse::<u32>().ok());
+            match (parts.next(), parts.next()) {
+                (Some(Some(major)), Some(Some(minor))) => Ok(Version(major, minor)),
+                _ => Err(format!("invalid version: {}", s)),
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct ReleasePath {
+        id: Uuid,
+        #[serde(deserialize_with = "deserialize_from_str")]
+        version: Version,
+    }
+
+    impl StateData for ReleasePath {}
+
+    impl StaticResponseExtender for ReleasePath {
+        type ResBody = Body;
+        fn extend(_: &mut State, res: &mut Response<Body>) {
+            *res.status_mut() = StatusCode::BAD_REQUEST;
+        }
+    }
+
+    fn release_handler(mut state: State) -> (State, Response<Body>) {
+        let ReleasePath { id, version } = state.take();
+        let body = format!("{} {}.{}", id, version.0, version.1);
+        let res = create_text_response(&state, StatusCode::OK, body);
+        (state, res)
+    }
+
+    #[test]
+    fn typed_path_segments() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/releases/:id/:version")
+                .with_path_extractor::<ReleasePath>()
+                .to(release_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let get = |path: &str| {
+            test_server
+                .client()
+                .get(&format!("http://localhost{}", path))
+                .perform()
+                .unwrap()
+        };
+
+        let response = get("/releases/67e55044-10b1-426f-9247-bb680e5fe0c8/1.2");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            "67e55044-10b1-426f-9247-bb680e5fe0c8 1.2"
+        );
+
+        let response = get("/releases/67e55044-10b1-426f-9247/1.2");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = get("/releases/67e55044-10b1-426f-9247-bb680e5fe0c8/1");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_path_segments() {
+        use chrono::NaiveDate;
+        use extractor::internal::from_segment_mapping;
+        use helpers::http::PercentDecoded;
+        use router::tree::segment::SegmentMapping;
+
+        #[derive(Deserialize)]
+        struct DayPath {
+            day: NaiveDate,
+        }
+
+        let day = PercentDecoded::new("2018-06-21").unwrap();
+        let mut sm = SegmentMapping::new();
+        sm.insert("day", vec![&day]);
+        let p = from_segment_mapping::<DayPath>(sm).unwrap();
+        assert_eq!(p.day, NaiveDate::from_ymd(2018, 6, 21));
+
+        let day = PercentDecoded::new("2018-06-31").unwrap();
+        let mut sm = SegmentMapping::new();
+        sm.insert("day", vec![&day]);
+        assert!(from_segment_mapping::<DayPath>(sm).is_err());
+    }
+}
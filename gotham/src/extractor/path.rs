@@ -16,6 +16,19 @@ use state::{State, StateData};
 /// behaviour from Serde, and result in a `400 Bad Request` HTTP response if the path segments are
 /// not able to be deserialized.
 ///
+/// The segments matched by a glob (`*name` or `**name`) are extracted into the field of the same
+/// name, either as a `Vec` with an element for each segment, parsed in the same way as any other
+/// segment (e.g. `Vec<u32>`), or as a `String` of the segments joined by `/`. An optional glob
+/// which matched no segments is extracted as an empty `Vec` or `String`, and a glob without a name
+/// is extracted into the field renamed to `*` (via `#[serde(rename = "*")]`). As each segment is
+/// percent-decoded, a joined `String` doesn't distinguish a segment which contained an encoded
+/// `/`, so use a `Vec<String>` (or `GlobPath`) when that matters.
+///
+/// A field can be of any type which deserializes from a string, such as `uuid::Uuid` (or the
+/// `chrono` date and time types, with the `chrono` feature enabled), or of any type which
+/// implements `FromStr`, via `deserialize_from_str`. A segment which can't be parsed fails the
+/// extraction in the same way as any other.
+///
 /// # Examples
 ///
 /// ```rust
@@ -16,6 +16,26 @@ use state::{State, StateData};
 /// behaviour from Serde, and result in a `400 Bad Request` HTTP response if the query string is
 /// not able to be deserialized.
 ///
+/// A key which is repeated in the query string (e.g. `?tag=a&tag=b`) can be deserialized into a
+/// `Vec<T>` or `Option<Vec<T>>` field, with the values in the order they appear. A key which is
+/// absent leaves an `Option<Vec<T>>` field as `None`, while a `Vec<T>` field requires
+/// `#[serde(default)]` to be deserialized as empty. A field which holds a single value is
+/// rejected when its key is repeated, rather than silently keeping one of the values. The
+/// PHP-style `?tag[]=a&tag[]=b` form is accepted when enabled via
+/// `DefineSingleRoute::with_query_string_options`.
+///
+/// Keys with a bracketed path, such as `?filter[status]=open&page[size]=20`, are deserialized into
+/// nested structs or maps, so that a `filter: Filter` field receives the `status` value. A key
+/// which is given both a value and nested keys (e.g. `?filter=open&filter[status]=open`) fails
+/// deserialization, as do nested keys for a field which holds a single value.
+///
+/// A key which is absent leaves an `Option<T>` field as `None`, and the field of a struct can be
+/// given a value for an absent key via `#[serde(default)]` or `#[serde(default = "path")]` (a key
+/// without `=`, as in `?page`, is absent). A key with an empty value (`?page=`) leaves an
+/// `Option<T>` field as `None` too, while a `String` field receives the empty string, and any
+/// other field fails to parse the value. Keys which don't correspond to any field are ignored,
+/// unless rejected via `QueryStringOptions::with_deny_unknown_keys`.
+///
 /// # Examples
 ///
 /// ```rust
@@ -117,6 +137,52 @@ impl<'de> Deserialize<'de> for NoopQueryStringExtractor {
 
 impl StateData for NoopQueryStringExtractor {}
 
+/// Options which control how the query string is parsed before it is deserialized into a
+/// `QueryStringExtractor`, applied to a route via `DefineSingleRoute::with_query_string_options`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueryStringOptions {
+    bracket_suffix: bool,
+    deny_unknown_keys: bool,
+}
+
+impl QueryStringOptions {
+    /// Creates the default `QueryStringOptions`, which treat each key literally, and ignore keys
+    /// which don't correspond to a field of the `QueryStringExtractor`.
+    pub fn new() -> QueryStringOptions {
+        QueryStringOptions::default()
+    }
+
+    /// Sets whether a `[]` suffix on a key is ignored, so that `?tag[]=a&tag[]=b` is
+    /// deserialized into a `tag` field in the same way as `?tag=a&tag=b`.
+    pub fn with_bracket_suffix(self, bracket_suffix: bool) -> QueryStringOptions {
+        QueryStringOptions {
+            bracket_suffix,
+            ..self
+        }
+    }
+
+    /// Returns whether a `[]` suffix on a key is ignored.
+    pub fn bracket_suffix(&self) -> bool {
+        self.bracket_suffix
+    }
+
+    /// Sets whether keys which don't correspond to a field of the `QueryStringExtractor` (or of a
+    /// nested struct) are rejected. When they are, the request receives `400 Bad Request`, with a
+    /// body listing the unknown keys, before the `StaticResponseExtender` of the extractor is
+    /// applied.
+    pub fn with_deny_unknown_keys(self, deny_unknown_keys: bool) -> QueryStringOptions {
+        QueryStringOptions {
+            deny_unknown_keys,
+            ..self
+        }
+    }
+
+    /// Returns whether keys which don't correspond to a field are rejected.
+    pub fn deny_unknown_keys(&self) -> bool {
+        self.deny_unknown_keys
+    }
+}
+
 impl StaticResponseExtender for NoopQueryStringExtractor {
     type ResBody = Body;
     fn extend(_state: &mut State, _res: &mut Response<Body>) {}
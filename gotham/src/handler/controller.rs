@@ -0,0 +1,141 @@
+//! Defines a `NewHandler` which routes to a method of a shared controller, so that related
+//! handlers can be grouped on a struct holding their dependencies.
+
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+
+use error::Result;
+use handler::{Handler, HandlerFuture, IntoHandlerFuture, NewHandler};
+use state::State;
+
+/// A `NewHandler` which calls a method of a controller, shared via `Arc` by every route and
+/// request directed to it.
+///
+/// The controller is constructed once, by the application, before the `Router` is built. Each
+/// `Handler` created for a request holds a clone of the `Arc`, so the controller isn't
+/// constructed or copied per request. The method may return anything which a function `Handler`
+/// can, i.e. a `(State, Response<Body>)` pair or a `Box<HandlerFuture>`.
+///
+/// This is usually created via `DefineSingleRoute::to_controller`.
+pub struct ControllerHandler<C, R>
+where
+    C: Send + Sync + RefUnwindSafe + 'static,
+    R: IntoHandlerFuture + 'static,
+{
+    controller: Arc<C>,
+    method: fn(&C, State) -> R,
+}
+
+impl<C, R> ControllerHandler<C, R>
+where
+    C: Send + Sync + RefUnwindSafe + 'static,
+    R: IntoHandlerFuture + 'static,
+{
+    /// Creates a `ControllerHandler` which calls `method` on `controller` for each request.
+    pub fn new(controller: Arc<C>, method: fn(&C, State) -> R) -> Self {
+        ControllerHandler { controller, method }
+    }
+}
+
+impl<C, R> Clone for ControllerHandler<C, R>
+where
+    C: Send + Sync + RefUnwindSafe + 'static,
+    R: IntoHandlerFuture + 'static,
+{
+    fn clone(&self) -> Self {
+        ControllerHandler {
+            controller: self.controller.clone(),
+            method: self.method,
+        }
+    }
+}
+
+impl<C, R> NewHandler for ControllerHandler<C, R>
+where
+    C: Send + Sync + RefUnwindSafe + 'static,
+    R: IntoHandlerFuture + 'static,
+{
+    type Instance = Self;
+
+    fn new_handler(&self) -> Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl<C, R> Handler for ControllerHandler<C, R>
+where
+    C: Send + Sync + RefUnwindSafe + 'static,
+    R: IntoHandlerFuture + 'static,
+{
+    fn handle(self, state: State) -> Box<HandlerFuture> {
+        (self.method)(&self.controller, state).into_handler_future()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::future;
+    use hyper::{Body, Response, StatusCode};
+
+    use helpers::http::response::create_text_response;
+    use router::builder::*;
+    use test::TestServer;
+
+    struct UsersController {
+        greeting: String,
+        requests: AtomicUsize,
+    }
+
+    impl UsersController {
+        fn index(&self, state: State) -> (State, Response<Body>) {
+            let n = self.requests.fetch_add(1, Ordering::SeqCst) + 1;
+            let body = format!("{}, index ({})", self.greeting, n);
+            let res = create_text_response(&state, StatusCode::OK, body);
+            (state, res)
+        }
+
+        fn show(&self, state: State) -> Box<HandlerFuture> {
+            let n = self.requests.fetch_add(1, Ordering::SeqCst) + 1;
+            let body = format!("{}, show ({})", self.greeting, n);
+            let res = create_text_response(&state, StatusCode::OK, body);
+            Box::new(future::ok((state, res)))
+        }
+    }
+
+    #[test]
+    fn controller_methods_share_controller() {
+        let controller = Arc::new(UsersController {
+            greeting: "Hello".to_owned(),
+            requests: AtomicUsize::new(0),
+        });
+
+        let router = build_simple_router(|route| {
+            route
+                .get("/users")
+                .to_controller(controller.clone(), UsersController::index);
+            route
+                .get("/users/:id")
+                .to_controller(controller.clone(), UsersController::show);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let get = |path: &str| {
+            test_server
+                .client()
+                .get(&format!("http://localhost{}", path))
+                .perform()
+                .unwrap()
+                .read_utf8_body()
+                .unwrap()
+        };
+
+        assert_eq!(get("/users"), "Hello, index (1)");
+        assert_eq!(get("/users/1"), "Hello, show (2)");
+        assert_eq!(get("/users"), "Hello, index (3)");
+        assert_eq!(controller.requests.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,604 @@
+//! Defines a `Handler` which serves the files beneath a directory, such as the static assets of a
+//! web application.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::future::{self, Loop};
+use futures::Future;
+use hyper::header::{HeaderMap, HeaderValue, CACHE_CONTROL, CONTENT_ENCODING, ETAG};
+use hyper::{Body, Response, StatusCode};
+use mime::{self, Mime};
+use tokio;
+
+use error::Result;
+use handler::{Handler, HandlerError, HandlerFuture, NewHandler};
+use helpers::http::accept::{negotiate_encoding, vary_on_accept_encoding, IDENTITY};
+use helpers::http::conditional::{
+    check_if_modified_since, is_not_modified, not_modified_response, set_last_modified, EntityTag,
+};
+use helpers::http::range::{range_response, FileSource, RangeOptions};
+use helpers::http::request::path::GlobPath;
+use helpers::http::response::create_response;
+use router::response::extender::StaticResponseExtender;
+use state::{request_id, FromState, State, StateData};
+
+/// Options which control how a `DirHandler` serves the files beneath its root directory.
+///
+/// By default, a request for a directory is answered with its `index.html` file, and each file
+/// is sent with `Cache-Control: public, max-age=0, must-revalidate`, so that clients revalidate
+/// their copy via the `ETag` and `Last-Modified` headers which are always sent.
+///
+/// A pre-compressed copy of a file beside it (such as `app.js.br` or `app.js.gz`) is sent instead
+/// when preferred by the `Accept-Encoding` header of the request, with the `Content-Encoding` of
+/// the copy. This can be disabled with `DirOptions::without_precompressed`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// #
+/// # use gotham::handler::dir::DirOptions;
+/// # use gotham::router::builder::*;
+/// #
+/// # fn main() {
+/// let assets = DirOptions::new("public/")
+///     .with_index_files(vec!["index.html", "index.htm"])
+///     .with_cache_control("public, max-age=31536000, immutable");
+///
+/// let _router = build_simple_router(|route| {
+///     route.get("/assets/*").to_dir_with(assets);
+///     route.get("/docs/**").to_dir("docs/");
+/// });
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct DirOptions {
+    root: PathBuf,
+    index_files: Vec<String>,
+    cache_control: Option<String>,
+    precompressed: bool,
+}
+
+impl DirOptions {
+    /// Creates `DirOptions` for serving the files beneath `root`.
+    pub fn new<P: Into<PathBuf>>(root: P) -> DirOptions {
+        DirOptions {
+            root: root.into(),
+            index_files: vec!["index.html".to_owned()],
+            cache_control: Some("public, max-age=0, must-revalidate".to_owned()),
+            precompressed: true,
+        }
+    }
+
+    /// Sets the files which are tried, in order, when a request is for a directory. With no index
+    /// files, a request for a directory receives a `404 Not Found` response.
+    pub fn with_index_files<I, S>(self, index_files: I) -> DirOptions
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        DirOptions {
+            index_files: index_files.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Sets the value of the `Cache-Control` header sent with each file.
+    pub fn with_cache_control<S: Into<String>>(self, cache_control: S) -> DirOptions {
+        DirOptions {
+            cache_control: Some(cache_control.into()),
+            ..self
+        }
+    }
+
+    /// Sends files without a `Cache-Control` header.
+    pub fn without_cache_control(self) -> DirOptions {
+        DirOptions {
+            cache_control: None,
+            ..self
+        }
+    }
+
+    /// Always sends files as they are, rather than looking for pre-compressed copies of them.
+    pub fn without_precompressed(self) -> DirOptions {
+        DirOptions {
+            precompressed: false,
+            ..self
+        }
+    }
+
+    /// The directory which files are served from.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+/// A `Handler` which serves the file beneath the root directory of its `DirOptions` at the path
+/// matched by the glob segment of the route.
+///
+/// This is usually created via `DefineSingleRoute::to_dir`, which also extracts the glob segment
+/// of the request path. Segments of `.` and `..`, and those containing a path separator, are
+/// rejected when extracted, so a request can't reach a file outside the root directory, other
+/// than through a symbolic link beneath it.
+///
+/// Each file is sent with a `Content-Type` determined from its extension, along with `ETag` and
+/// `Last-Modified` headers, and conditional and `Range` requests are answered as appropriate.
+/// Files are accessed via `tokio::fs`, and read as the body of the response is sent.
+#[derive(Clone)]
+pub struct DirHandler {
+    options: Arc<DirOptions>,
+}
+
+impl DirHandler {
+    /// Creates a `DirHandler` which serves files according to the given `DirOptions`.
+    pub fn new(options: DirOptions) -> DirHandler {
+        DirHandler {
+            options: Arc::new(options),
+        }
+    }
+
+    /// Responds with the best of the `variants` of the file for the request, reading it via
+    /// `tokio::fs` as the body is sent.
+    fn respond(&self, state: State, variants: Vec<Variant>) -> Box<HandlerFuture> {
+        let mime = file_mime(&variants[0].path);
+        let codings: Vec<&'static str> = variants[1..].iter().map(|v| v.coding).collect();
+        let vary = !codings.is_empty();
+
+        let variant = match negotiate_encoding(&state, &codings)
+            .and_then(|coding| variants.into_iter().find(|v| v.coding == coding))
+        {
+            Some(variant) => variant,
+            None => {
+                trace!("[{}] no acceptable encoding", request_id(&state));
+                let mut res = create_response(&state, StatusCode::NOT_ACCEPTABLE, None);
+                vary_on_accept_encoding(&mut res);
+                return Box::new(future::ok((state, res)));
+            }
+        };
+
+        let modified = variant.metadata.modified().ok();
+        let etag = file_etag(variant.metadata.len(), modified, variant.coding);
+
+        let mut headers = HeaderMap::new();
+        if let Some(ref cache_control) = self.options.cache_control {
+            if let Ok(value) = HeaderValue::from_str(cache_control) {
+                headers.insert(CACHE_CONTROL, value);
+            }
+        }
+
+        let fresh = is_not_modified(&state, &etag)
+            || modified.map_or(false, |modified| !check_if_modified_since(&state, modified));
+
+        if fresh {
+            let mut res = not_modified_response(&state, &etag, headers);
+            finish_response(&mut res, modified, vary);
+            return Box::new(future::ok((state, res)));
+        }
+
+        let Variant {
+            path,
+            metadata,
+            coding,
+        } = variant;
+
+        let f = tokio::fs::File::open(path.clone()).then(move |result| {
+            let res = match result {
+                Ok(file) => {
+                    let source = FileSource::new(file, metadata.len());
+                    let mut res = range_response(&state, source, mime, &RangeOptions::new());
+
+                    let etag = HeaderValue::from_str(&etag.to_string())
+                        .expect("EntityTag is always a valid header value");
+                    res.headers_mut().insert(ETAG, etag);
+                    if coding != IDENTITY {
+                        res.headers_mut()
+                            .insert(CONTENT_ENCODING, HeaderValue::from_static(coding));
+                    }
+                    res.headers_mut().extend(headers);
+
+                    finish_response(&mut res, modified, vary);
+                    res
+                }
+                Err(e) => {
+                    error!("[{}] failed to open {:?}: {}", request_id(&state), path, e);
+                    create_response(&state, StatusCode::INTERNAL_SERVER_ERROR, None)
+                }
+            };
+
+            Ok::<_, (State, HandlerError)>((state, res))
+        });
+
+        Box::new(f)
+    }
+}
+
+impl NewHandler for DirHandler {
+    type Instance = Self;
+
+    fn new_handler(&self) -> Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl Handler for DirHandler {
+    fn handle(self, state: State) -> Box<HandlerFuture> {
+        let relative = match DirPath::try_borrow_from(&state) {
+            Some(path) => path.path.as_path().to_path_buf(),
+            None => PathBuf::new(),
+        };
+
+        let options = self.options.clone();
+        let f = find_file(&self.options, &relative)
+            .and_then(move |found| match found {
+                Some((path, metadata)) => {
+                    future::Either::A(find_variants(&options, path, metadata).map(Some))
+                }
+                None => future::Either::B(future::ok(None)),
+            })
+            .then(move |result| -> Box<HandlerFuture> {
+                match result {
+                    Ok(Some(variants)) => self.respond(state, variants),
+                    Ok(None) => {
+                        trace!("[{}] no file at {:?}", request_id(&state), relative);
+                        let res = create_response(&state, StatusCode::NOT_FOUND, None);
+                        Box::new(future::ok((state, res)))
+                    }
+                    Err(e) => {
+                        error!(
+                            "[{}] failed to find {:?}: {}",
+                            request_id(&state),
+                            relative,
+                            e
+                        );
+                        let res = create_response(&state, StatusCode::INTERNAL_SERVER_ERROR, None);
+                        Box::new(future::ok((state, res)))
+                    }
+                }
+            });
+
+        Box::new(f)
+    }
+}
+
+/// The `PathExtractor` used by `DefineSingleRoute::to_dir`, extracting the anonymous glob segment
+/// which ends the path of the route.
+#[derive(Deserialize)]
+pub(crate) struct DirPath {
+    #[serde(rename = "*")]
+    path: GlobPath,
+}
+
+impl StateData for DirPath {}
+
+impl StaticResponseExtender for DirPath {
+    type ResBody = Body;
+
+    fn extend(_state: &mut State, res: &mut Response<Body>) {
+        *res.status_mut() = StatusCode::NOT_FOUND;
+    }
+}
+
+/// The content codings of the pre-compressed files which may be found next to a file, with the
+/// extension appended to its name. Earlier codings are preferred when equally acceptable.
+const PRECOMPRESSED: [(&'static str, &'static str); 2] = [("br", "br"), ("gzip", "gz")];
+
+/// A file which may be sent for a request path: either the file itself, with a coding of
+/// `IDENTITY`, or a pre-compressed copy of it.
+struct Variant {
+    path: PathBuf,
+    metadata: fs::Metadata,
+    coding: &'static str,
+}
+
+/// Finds the file to send for the request path, trying the index files for a directory.
+fn find_file(
+    options: &DirOptions,
+    relative: &Path,
+) -> Box<Future<Item = Option<(PathBuf, fs::Metadata)>, Error = io::Error> + Send> {
+    let file = options.root.join(relative);
+    let mut candidates: Vec<PathBuf> = options
+        .index_files
+        .iter()
+        .map(|index| file.join(index))
+        .collect();
+    candidates.insert(0, file);
+
+    let f = future::loop_fn(candidates.into_iter(), |mut candidates| {
+        let candidate = match candidates.next() {
+            Some(candidate) => candidate,
+            None => return future::Either::A(future::ok(Loop::Break(None))),
+        };
+
+        future::Either::B(
+            file_metadata(candidate.clone()).map(move |metadata| match metadata {
+                Some(metadata) => Loop::Break(Some((candidate, metadata))),
+                None => Loop::Continue(candidates),
+            }),
+        )
+    });
+
+    Box::new(f)
+}
+
+/// Lists the file at `path` and each pre-compressed copy of it which is present, unless disabled
+/// with `DirOptions::without_precompressed`.
+fn find_variants(
+    options: &DirOptions,
+    path: PathBuf,
+    metadata: fs::Metadata,
+) -> Box<Future<Item = Vec<Variant>, Error = io::Error> + Send> {
+    let mut variants = vec![Variant {
+        path: path.clone(),
+        metadata,
+        coding: IDENTITY,
+    }];
+
+    if !options.precompressed {
+        return Box::new(future::ok(variants));
+    }
+
+    let copies = PRECOMPRESSED.iter().map(move |&(coding, extension)| {
+        let mut copy = path.clone().into_os_string();
+        copy.push(".");
+        copy.push(extension);
+        let copy = PathBuf::from(copy);
+
+        file_metadata(copy.clone()).map(move |metadata| {
+            metadata.map(|metadata| Variant {
+                path: copy,
+                metadata,
+                coding,
+            })
+        })
+    });
+
+    let f = future::join_all(copies).map(move |copies| {
+        variants.extend(copies.into_iter().filter_map(|copy| copy));
+        variants
+    });
+
+    Box::new(f)
+}
+
+/// Reads the metadata of the file at `path` via `tokio::fs`, returning `None` unless it exists
+/// and is a regular file.
+fn file_metadata(
+    path: PathBuf,
+) -> Box<Future<Item = Option<fs::Metadata>, Error = io::Error> + Send> {
+    let f = tokio::fs::metadata(path).then(|result| match result {
+        Ok(ref metadata) if metadata.is_file() => Ok(Some(metadata.clone())),
+        // As well as a missing file, this includes a path beneath a file, such as
+        // `index.html/index.html`, which fails with `ENOTDIR` instead.
+        _ => Ok(None),
+    });
+
+    Box::new(f)
+}
+
+/// Adds the headers which are sent both with a file and with `304 Not Modified`.
+fn finish_response(res: &mut Response<Body>, modified: Option<SystemTime>, vary: bool) {
+    if let Some(modified) = modified {
+        set_last_modified(res, modified);
+    }
+
+    if vary {
+        vary_on_accept_encoding(res);
+    }
+}
+
+/// Creates a weak `EntityTag` from the length and modification time of a file, which changes
+/// whenever the file is replaced or rewritten. The content coding of a pre-compressed copy is
+/// included, so that each copy has a distinct tag.
+fn file_etag(len: u64, modified: Option<SystemTime>, coding: &str) -> EntityTag {
+    let modified = modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_secs());
+
+    if coding == IDENTITY {
+        EntityTag::weak(format!("{:x}-{:x}", len, modified))
+    } else {
+        EntityTag::weak(format!("{:x}-{:x}-{}", len, modified, coding))
+    }
+}
+
+/// Determines the `Content-Type` of a file from its extension.
+fn file_mime(file: &Path) -> Mime {
+    let extension = file
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+
+    let mime = match extension.as_ref().map(String::as_str) {
+        Some("html") | Some("htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") | Some("mjs") => "application/javascript; charset=utf-8",
+        Some("json") | Some("map") => "application/json",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("xml") => "text/xml; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("wasm") => "application/wasm",
+        Some("pdf") => "application/pdf",
+        _ => return mime::APPLICATION_OCTET_STREAM,
+    };
+
+    mime.parse().expect("valid media type for file extension")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::{ACCEPT_ENCODING, CONTENT_RANGE, CONTENT_TYPE, IF_NONE_MATCH, RANGE, VARY};
+    use uuid::Uuid;
+
+    use router::builder::*;
+    use test::TestServer;
+
+    fn asset_dir() -> PathBuf {
+        let dir = ::std::env::temp_dir().join(format!("gotham-dir-{}", Uuid::new_v4().simple()));
+        let public = dir.join("public");
+        fs::create_dir_all(public.join("css/vendor")).unwrap();
+        fs::write(public.join("index.html"), "<h1>home</h1>").unwrap();
+        fs::write(public.join("css/vendor/reset.css"), "* { margin: 0 }").unwrap();
+        fs::write(dir.join("secret.txt"), "secret").unwrap();
+        dir
+    }
+
+    #[test]
+    fn serves_directory() {
+        let dir = asset_dir();
+        let public = dir.join("public");
+
+        let router = build_simple_router(|route| {
+            route.get("/static/*").to_dir(public.clone());
+            route.get("/site/**").to_dir_with(
+                DirOptions::new(public.clone()).with_cache_control("public, max-age=60"),
+            );
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/static/css/vendor/reset.css")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "text/css; charset=utf-8"
+        );
+        assert_eq!(
+            response.headers().get(CACHE_CONTROL).unwrap(),
+            "public, max-age=0, must-revalidate"
+        );
+        let etag = response.headers().get(ETAG).unwrap().clone();
+        assert_eq!(response.read_utf8_body().unwrap(), "* { margin: 0 }");
+
+        let response = test_server
+            .client()
+            .head("http://localhost/static/css/vendor/reset.css")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_body().unwrap(), b"");
+
+        let response = test_server
+            .client()
+            .get("http://localhost/static/css/vendor/reset.css")
+            .with_header(IF_NONE_MATCH, etag)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        for path in &[
+            "/static/../secret.txt",
+            "/static/css/%2e%2e/%2e%2e/%2e%2e/secret.txt",
+            "/static/missing.css",
+            "/static/css",
+        ] {
+            let response = test_server
+                .client()
+                .get(&format!("http://localhost{}", path))
+                .perform()
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+        }
+
+        let response = test_server
+            .client()
+            .get("http://localhost/site")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(CACHE_CONTROL).unwrap(),
+            "public, max-age=60"
+        );
+        assert_eq!(response.read_utf8_body().unwrap(), "<h1>home</h1>");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn serves_precompressed_copies_and_ranges() {
+        let dir = asset_dir();
+        let public = dir.join("public");
+        fs::write(public.join("app.js"), "console.log(1)").unwrap();
+        fs::write(public.join("app.js.br"), "br-bytes").unwrap();
+        fs::write(public.join("app.js.gz"), "gzip-bytes").unwrap();
+
+        let router = build_simple_router(|route| {
+            route.get("/static/*").to_dir(public.clone());
+            route
+                .get("/plain/*")
+                .to_dir_with(DirOptions::new(public.clone()).without_precompressed());
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let get = |path: &str, name, value: &str| {
+            test_server
+                .client()
+                .get(&format!("http://localhost{}", path))
+                .with_header(name, value.parse().unwrap())
+                .perform()
+                .unwrap()
+        };
+
+        let response = get("/static/app.js", ACCEPT_ENCODING, "gzip, br;q=0.5");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+        assert_eq!(response.headers().get(VARY).unwrap(), "Accept-Encoding");
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "application/javascript; charset=utf-8"
+        );
+        let gzip_etag = response.headers().get(ETAG).unwrap().clone();
+        assert_eq!(response.read_utf8_body().unwrap(), "gzip-bytes");
+
+        let response = get("/static/app.js", ACCEPT_ENCODING, "gzip, br");
+        assert_eq!(response.headers().get(CONTENT_ENCODING).unwrap(), "br");
+        assert_eq!(response.read_utf8_body().unwrap(), "br-bytes");
+
+        let response = get("/static/app.js", ACCEPT_ENCODING, "deflate");
+        assert!(response.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!(response.headers().get(VARY).unwrap(), "Accept-Encoding");
+        assert_ne!(response.headers().get(ETAG).unwrap(), &gzip_etag);
+        assert_eq!(response.read_utf8_body().unwrap(), "console.log(1)");
+
+        let response = get("/static/app.js", IF_NONE_MATCH, gzip_etag.to_str().unwrap());
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get(
+            "/static/css/vendor/reset.css",
+            ACCEPT_ENCODING,
+            "identity;q=0",
+        );
+        assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+
+        let response = get("/plain/app.js", ACCEPT_ENCODING, "gzip");
+        assert!(response.headers().get(CONTENT_ENCODING).is_none());
+        assert!(response.headers().get(VARY).is_none());
+        assert_eq!(response.read_utf8_body().unwrap(), "console.log(1)");
+
+        let response = get("/plain/app.js", RANGE, "bytes=8-");
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers().get(CONTENT_RANGE).unwrap(),
+            "bytes 8-13/14"
+        );
+        assert_eq!(response.read_utf8_body().unwrap(), "log(1)");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -125,6 +125,12 @@ impl HandlerError {
             ..self
         }
     }
+
+    /// Returns the HTTP status code of the response which is generated by the `IntoResponse`
+    /// implementation.
+    pub fn status(&self) -> StatusCode {
+        self.status_code
+    }
 }
 
 impl IntoResponse<Body> for HandlerError {
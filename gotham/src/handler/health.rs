@@ -0,0 +1,368 @@
+//! Defines a `Handler` which aggregates a set of health checks, for use as a liveness or
+//! readiness endpoint (e.g. `/healthz` and `/readyz`).
+
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Either};
+use futures::{Future, IntoFuture};
+use hyper::StatusCode;
+use mime;
+use tokio::timer::Delay;
+
+use error::{Error, Result};
+use handler::{Handler, HandlerFuture, NewHandler};
+use helpers::http::response::create_response;
+use router::response::body::escape_json;
+use state::{request_id, State};
+
+/// A type alias for the trait objects returned by a `HealthCheck`.
+///
+/// Resolving to an error marks the check as failed, using the error as the message.
+pub type HealthCheckFuture = Future<Item = CheckResult, Error = Error> + Send;
+
+/// The outcome of an individual health check.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckResult {
+    healthy: bool,
+    message: Option<String>,
+}
+
+impl CheckResult {
+    /// Creates a passing `CheckResult`.
+    pub fn pass() -> Self {
+        CheckResult {
+            healthy: true,
+            message: None,
+        }
+    }
+
+    /// Creates a failing `CheckResult`, described by the given message.
+    pub fn fail<M: Into<String>>(message: M) -> Self {
+        CheckResult {
+            healthy: false,
+            message: Some(message.into()),
+        }
+    }
+
+    /// Attaches a message to the `CheckResult`, replacing any existing message.
+    pub fn with_message<M: Into<String>>(self, message: M) -> Self {
+        CheckResult {
+            message: Some(message.into()),
+            ..self
+        }
+    }
+
+    /// Determines if the check passed.
+    pub fn is_healthy(&self) -> bool {
+        self.healthy
+    }
+
+    /// Returns the message describing the check, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_ref().map(|m| m.as_str())
+    }
+}
+
+/// A single named check which is run by a `HealthCheckHandler`, such as pinging a database.
+///
+/// This is implemented for closures which return a value convertible into a future of
+/// `CheckResult`, so checks are usually registered as closures:
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::StatusCode;
+/// # use gotham::error::Error;
+/// # use gotham::handler::health::{CheckResult, HealthCheckHandler};
+/// # use gotham::router::Router;
+/// # use gotham::router::builder::*;
+/// # use gotham::test::TestServer;
+/// #
+/// fn router() -> Router {
+///     let readiness = HealthCheckHandler::new()
+///         .add("database", || -> Result<CheckResult, Error> {
+///             // Ping the database, omitted for brevity.
+///             Ok(CheckResult::pass().with_message("3ms"))
+///         })
+///         .add("sessions", || -> Result<CheckResult, Error> {
+///             Ok(CheckResult::fail("backend unreachable"))
+///         });
+///
+///     build_simple_router(|route| {
+///         route.get("/healthz").to_new_handler(HealthCheckHandler::new());
+///         route.get("/readyz").to_new_handler(readiness);
+///     })
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server.client().get("http://localhost/healthz").perform().unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #
+/// #   let response = test_server.client().get("http://localhost/readyz").perform().unwrap();
+/// #   assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+/// # }
+/// ```
+pub trait HealthCheck: Send + Sync + RefUnwindSafe {
+    /// Runs the check.
+    fn check(&self) -> Box<HealthCheckFuture>;
+}
+
+impl<F, R> HealthCheck for F
+where
+    F: Fn() -> R + Send + Sync + RefUnwindSafe,
+    R: IntoFuture<Item = CheckResult, Error = Error>,
+    R::Future: Send + 'static,
+{
+    fn check(&self) -> Box<HealthCheckFuture> {
+        Box::new(self().into_future())
+    }
+}
+
+/// A `Handler` which runs each registered `HealthCheck` concurrently, and responds with a JSON
+/// summary of the results.
+///
+/// The response is `200 OK` when all checks pass, and `503 Service Unavailable` when any check
+/// fails or does not complete within the timeout. The body lists every check, for example:
+///
+/// ```json
+/// {"status":"fail","checks":[{"name":"database","status":"fail","message":"timed out after 5000ms"}]}
+/// ```
+///
+/// The set of checks is shared between all instances created via `NewHandler`.
+#[derive(Clone)]
+pub struct HealthCheckHandler {
+    checks: Arc<Vec<(String, Box<HealthCheck>)>>,
+    timeout: Duration,
+}
+
+impl HealthCheckHandler {
+    /// Creates a new `HealthCheckHandler` with no checks, and a per-check timeout of 5 seconds.
+    ///
+    /// With no checks registered, the handler always responds with `200 OK`, which is suitable
+    /// as a liveness endpoint.
+    pub fn new() -> Self {
+        HealthCheckHandler {
+            checks: Arc::new(Vec::new()),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Registers a named `HealthCheck`. Checks are reported in the order they were added.
+    ///
+    /// # Panics
+    ///
+    /// If called after the handler has been cloned.
+    pub fn add<N, C>(mut self, name: N, check: C) -> Self
+    where
+        N: Into<String>,
+        C: HealthCheck + 'static,
+    {
+        Arc::get_mut(&mut self.checks)
+            .expect("HealthCheckHandler checks must be added before it is shared")
+            .push((name.into(), Box::new(check)));
+        self
+    }
+
+    /// Sets the time each check is allowed to run before it is considered to have failed.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        HealthCheckHandler { timeout, ..self }
+    }
+}
+
+impl Default for HealthCheckHandler {
+    fn default() -> Self {
+        HealthCheckHandler::new()
+    }
+}
+
+impl NewHandler for HealthCheckHandler {
+    type Instance = Self;
+
+    fn new_handler(&self) -> Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl Handler for HealthCheckHandler {
+    fn handle(self, state: State) -> Box<HandlerFuture> {
+        trace!(
+            "[{}] running {} health checks",
+            request_id(&state),
+            self.checks.len()
+        );
+
+        let timeout = self.timeout;
+        let checks = self
+            .checks
+            .iter()
+            .map(|&(ref name, ref check)| {
+                let name = name.clone();
+                let deadline = Delay::new(Instant::now() + timeout);
+
+                check.check().select2(deadline).then(move |r| {
+                    let result = match r {
+                        Ok(Either::A((result, _))) => result,
+                        Ok(Either::B(_)) => CheckResult::fail(format!(
+                            "timed out after {}ms",
+                            timeout.as_secs() * 1000 + u64::from(timeout.subsec_nanos() / 1_000_000)
+                        )),
+                        Err(Either::A((e, _))) => CheckResult::fail(e.to_string()),
+                        Err(Either::B((e, _))) => CheckResult::fail(format!("timer error: {}", e)),
+                    };
+
+                    Ok::<_, ()>((name, result))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let f = future::join_all(checks).then(move |results: ::std::result::Result<_, ()>| {
+            let results: Vec<(String, CheckResult)> = results.unwrap_or_default();
+            let healthy = results.iter().all(|&(_, ref result)| result.is_healthy());
+
+            for &(ref name, ref result) in results.iter().filter(|&&(_, ref r)| !r.is_healthy()) {
+                warn!(
+                    "[{}] health check {} failed: {}",
+                    request_id(&state),
+                    name,
+                    result.message().unwrap_or("(no message)")
+                );
+            }
+
+            let status = if healthy {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+
+            let body = summarize(healthy, &results).into_bytes();
+            let res = create_response(&state, status, Some((body, mime::APPLICATION_JSON)));
+            future::ok((state, res))
+        });
+
+        Box::new(f)
+    }
+}
+
+fn summarize(healthy: bool, results: &[(String, CheckResult)]) -> String {
+    let checks = results
+        .iter()
+        .map(|&(ref name, ref result)| {
+            let message = match result.message() {
+                Some(message) => format!("\"{}\"", escape_json(message)),
+                None => "null".to_owned(),
+            };
+
+            format!(
+                "{{\"name\":\"{}\",\"status\":\"{}\",\"message\":{}}}",
+                escape_json(name),
+                status_label(result.is_healthy()),
+                message
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!(
+        "{{\"status\":\"{}\",\"checks\":[{}]}}",
+        status_label(healthy),
+        checks
+    )
+}
+
+fn status_label(healthy: bool) -> &'static str {
+    if healthy {
+        "pass"
+    } else {
+        "fail"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use failure::err_msg;
+
+    use router::builder::*;
+    use router::Router;
+    use test::TestServer;
+
+    fn router(handler: HealthCheckHandler) -> Router {
+        build_simple_router(|route| {
+            route.get("/health").to_new_handler(handler);
+        })
+    }
+
+    #[test]
+    fn no_checks_is_healthy() {
+        let test_server = TestServer::new(router(HealthCheckHandler::new())).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/health")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            "{\"status\":\"pass\",\"checks\":[]}"
+        );
+    }
+
+    #[test]
+    fn all_checks_passing_is_healthy() {
+        let handler = HealthCheckHandler::new()
+            .add("first", || -> Result<CheckResult> { Ok(CheckResult::pass()) })
+            .add("second", || -> Result<CheckResult> {
+                Ok(CheckResult::pass().with_message("fine"))
+            });
+
+        let test_server = TestServer::new(router(handler)).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/health")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            "{\"status\":\"pass\",\"checks\":[\
+             {\"name\":\"first\",\"status\":\"pass\",\"message\":null},\
+             {\"name\":\"second\",\"status\":\"pass\",\"message\":\"fine\"}]}"
+        );
+    }
+
+    #[test]
+    fn failing_and_slow_checks_are_unavailable() {
+        let handler = HealthCheckHandler::new()
+            .add("ok", || -> Result<CheckResult> { Ok(CheckResult::pass()) })
+            .add("broken", || -> Result<CheckResult> { Err(err_msg("connection refused")) })
+            .add("slow", || {
+                Delay::new(Instant::now() + Duration::from_secs(5))
+                    .map(|_| CheckResult::pass())
+                    .map_err(Error::from)
+            })
+            .with_timeout(Duration::from_millis(50));
+
+        let test_server = TestServer::new(router(handler)).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/health")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            "{\"status\":\"fail\",\"checks\":[\
+             {\"name\":\"ok\",\"status\":\"pass\",\"message\":null},\
+             {\"name\":\"broken\",\"status\":\"fail\",\"message\":\"connection refused\"},\
+             {\"name\":\"slow\",\"status\":\"fail\",\"message\":\"timed out after 50ms\"}]}"
+        );
+    }
+}
@@ -10,7 +10,12 @@ use hyper::{Body, Response};
 
 use state::State;
 
+pub mod controller;
+pub mod dir;
 mod error;
+pub mod health;
+pub mod redirect;
+pub mod timeout;
 use error::*;
 
 pub use self::error::{HandlerError, IntoHandlerError};
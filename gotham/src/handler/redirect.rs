@@ -0,0 +1,424 @@
+//! Defines a `Handler` which redirects requests to another location, such as the new path of a
+//! page which has moved.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future;
+use hyper::{Body, Response, StatusCode, Uri};
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+
+use error::Result;
+use handler::{Handler, HandlerFuture, NewHandler};
+use helpers::http::response::set_redirect_headers;
+use router::response::extender::StaticResponseExtender;
+use state::{FromState, State, StateData};
+
+/// The kind of redirect sent by a `RedirectHandler`, which determines the status of the response.
+///
+/// When deserialized, e.g. as part of a `RouteSpec`, the kind is named in snake case, such as
+/// `permanent` or `moved_permanently`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedirectKind {
+    /// `308 Permanent Redirect`, which clients follow with the same method and body, and may
+    /// remember in place of the original location.
+    Permanent,
+    /// `307 Temporary Redirect`, which clients follow with the same method and body.
+    Temporary,
+    /// `301 Moved Permanently`, as understood by older clients, which may follow it with `GET`
+    /// in place of the original method.
+    MovedPermanently,
+    /// `302 Found`, as understood by older clients, which may follow it with `GET` in place of
+    /// the original method.
+    Found,
+}
+
+impl RedirectKind {
+    /// The status of a response sending this kind of redirect.
+    pub fn status(self) -> StatusCode {
+        match self {
+            RedirectKind::Permanent => StatusCode::PERMANENT_REDIRECT,
+            RedirectKind::Temporary => StatusCode::TEMPORARY_REDIRECT,
+            RedirectKind::MovedPermanently => StatusCode::MOVED_PERMANENTLY,
+            RedirectKind::Found => StatusCode::FOUND,
+        }
+    }
+}
+
+/// Options which control the location a `RedirectHandler` sends, and the kind of redirect.
+///
+/// The target may contain placeholders using the syntax of route paths, which are replaced by
+/// the values matched by the dynamic segments of the same name: `:name` for a dynamic segment,
+/// and `*name` (or `*` for an anonymous glob) for the segments matched by a glob. Each value is
+/// percent-encoded as a path segment. A placeholder for an optional segment which wasn't present
+/// in the request is omitted from the location, along with its `/`.
+///
+/// The query string of the request is appended to the location by default, after any query
+/// string of the target.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// #
+/// # use gotham::handler::redirect::{RedirectKind, RedirectOptions};
+/// # use gotham::router::builder::*;
+/// #
+/// # fn main() {
+/// let _router = build_simple_router(|route| {
+///     route
+///         .get("/old-blog/:slug")
+///         .redirect_to("/blog/:slug", RedirectKind::Permanent);
+///
+///     route.get("/search").redirect_to_with(
+///         RedirectOptions::new("/", RedirectKind::Found).with_query_string(false),
+///     );
+/// });
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RedirectOptions {
+    target: String,
+    kind: RedirectKind,
+    query_string: bool,
+}
+
+impl RedirectOptions {
+    /// Creates `RedirectOptions` for redirecting to `target` with the given kind of redirect.
+    pub fn new<T: Into<String>>(target: T, kind: RedirectKind) -> RedirectOptions {
+        RedirectOptions {
+            target: target.into(),
+            kind,
+            query_string: true,
+        }
+    }
+
+    /// Sets whether the query string of the request is appended to the location.
+    pub fn with_query_string(self, query_string: bool) -> RedirectOptions {
+        RedirectOptions {
+            query_string,
+            ..self
+        }
+    }
+
+    /// The location which requests are redirected to, before placeholders are replaced.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// The kind of redirect which is sent.
+    pub fn kind(&self) -> RedirectKind {
+        self.kind
+    }
+
+    /// Finds the first placeholder of the target which doesn't name a dynamic segment of the
+    /// route path `source`, returning its position among the segments of the target (counting
+    /// from zero after the leading `/`) and the placeholder itself.
+    pub(crate) fn missing_placeholder(&self, source: &str) -> Option<(usize, &str)> {
+        let names: Vec<&str> = source.split('/').filter_map(placeholder).collect();
+
+        let path = self.target.split(|c| c == '?' || c == '#').next().unwrap();
+        let path = if path.starts_with('/') {
+            &path[1..]
+        } else {
+            path
+        };
+
+        path.split('/')
+            .enumerate()
+            .find(|&(_, segment)| placeholder(segment).map_or(false, |name| !names.contains(&name)))
+    }
+}
+
+/// A `Handler` which redirects each request to the target of its `RedirectOptions`, replacing
+/// the placeholders of the target with the values matched by the route.
+///
+/// This is usually created via `DefineSingleRoute::redirect_to`, which also extracts the dynamic
+/// segments of the request path, and checks that each placeholder names one of them.
+#[derive(Clone)]
+pub struct RedirectHandler {
+    target: Arc<Target>,
+    kind: RedirectKind,
+    query_string: bool,
+}
+
+impl RedirectHandler {
+    /// Creates a `RedirectHandler` which redirects according to the given `RedirectOptions`.
+    pub fn new(options: RedirectOptions) -> RedirectHandler {
+        RedirectHandler {
+            target: Arc::new(Target::parse(&options.target)),
+            kind: options.kind,
+            query_string: options.query_string,
+        }
+    }
+
+    /// Builds the location for the request, from the target and the extracted segments.
+    fn location(&self, state: &State) -> String {
+        let empty = HashMap::new();
+        let params = RedirectParams::try_borrow_from(state).map_or(&empty, |params| &params.0);
+
+        let mut segments: Vec<String> = Vec::new();
+        for part in &self.target.path {
+            match *part {
+                TargetPart::Literal(ref segment) => segments.push(segment.clone()),
+                TargetPart::Placeholder(ref name) => {
+                    // An optional segment which wasn't present is omitted, along with its `/`.
+                    if let Some(values) = params.get(name) {
+                        segments.extend(
+                            values
+                                .iter()
+                                .map(|value| utf8_percent_encode(value, PATH_SEGMENT_ENCODE_SET))
+                                .map(|value| value.to_string()),
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut location = segments.join("/");
+        if location.is_empty() {
+            location.push('/');
+        }
+
+        let mut query = self.target.query.clone();
+
+        if self.query_string {
+            if let Some(request_query) = Uri::borrow_from(state).query() {
+                query = match query {
+                    Some(query) => Some(format!("{}&{}", query, request_query)),
+                    None => Some(request_query.to_owned()),
+                };
+            }
+        }
+
+        if let Some(query) = query {
+            location.push('?');
+            location.push_str(&query);
+        }
+
+        if let Some(ref fragment) = self.target.fragment {
+            location.push('#');
+            location.push_str(fragment);
+        }
+
+        location
+    }
+}
+
+impl NewHandler for RedirectHandler {
+    type Instance = Self;
+
+    fn new_handler(&self) -> Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl Handler for RedirectHandler {
+    fn handle(self, state: State) -> Box<HandlerFuture> {
+        let mut res = Response::builder()
+            .status(self.kind.status())
+            .body(Body::empty())
+            .expect("Response built from constant values");
+
+        let location = self.location(&state);
+        set_redirect_headers(&state, &mut res, location);
+        Box::new(future::ok((state, res)))
+    }
+}
+
+/// The `PathExtractor` used by `DefineSingleRoute::redirect_to`, extracting every dynamic segment
+/// of the route path by name.
+#[derive(Deserialize)]
+pub(crate) struct RedirectParams(HashMap<String, Vec<String>>);
+
+impl StateData for RedirectParams {}
+
+impl StaticResponseExtender for RedirectParams {
+    type ResBody = Body;
+
+    fn extend(_state: &mut State, res: &mut Response<Body>) {
+        *res.status_mut() = StatusCode::BAD_REQUEST;
+    }
+}
+
+/// The target of a `RedirectHandler`, parsed once when the handler is created.
+struct Target {
+    path: Vec<TargetPart>,
+    query: Option<String>,
+    fragment: Option<String>,
+}
+
+/// A segment of the path of a `Target`.
+enum TargetPart {
+    Literal(String),
+    Placeholder(String),
+}
+
+impl Target {
+    fn parse(target: &str) -> Target {
+        let (target, fragment) = match target.find('#') {
+            Some(n) => (&target[..n], Some(target[n + 1..].to_owned())),
+            None => (target, None),
+        };
+
+        let (path, query) = match target.find('?') {
+            Some(n) => (&target[..n], Some(target[n + 1..].to_owned())),
+            None => (target, None),
+        };
+
+        let path = path
+            .split('/')
+            .map(|segment| match placeholder(segment) {
+                Some(name) => TargetPart::Placeholder(name.to_owned()),
+                None => TargetPart::Literal(segment.to_owned()),
+            })
+            .collect();
+
+        Target {
+            path,
+            query,
+            fragment,
+        }
+    }
+}
+
+/// The name of a placeholder or dynamic segment, in the forms accepted in route paths: `:name`,
+/// `:name?` and `:name:regex` for a dynamic segment, or `*name` and `**name` for a glob, with
+/// `*` naming an anonymous glob.
+fn placeholder(segment: &str) -> Option<&str> {
+    match segment.chars().next() {
+        Some(':') => {
+            let name = segment[1..].split(':').next().unwrap();
+            Some(name.trim_right_matches('?'))
+        }
+        Some('*') => match segment.trim_left_matches('*') {
+            "" => Some("*"),
+            name => Some(name),
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::LOCATION;
+
+    use pipeline::set::{finalize_pipeline_set, new_pipeline_set};
+    use router::builder::*;
+    use test::TestServer;
+
+    fn redirect(test_server: &TestServer, path: &str) -> (StatusCode, String) {
+        let response = test_server
+            .client()
+            .get(&format!("http://localhost{}", path))
+            .perform()
+            .unwrap();
+
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .map(|location| location.to_str().unwrap().to_owned())
+            .unwrap_or_default();
+
+        (response.status(), location)
+    }
+
+    #[test]
+    fn redirects_with_placeholders() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/old-blog/:slug")
+                .redirect_to("/blog/:slug", RedirectKind::Permanent);
+            route
+                .get("/users/:id/files/*")
+                .redirect_to("/files/:id/*", RedirectKind::Temporary);
+            route
+                .get("/archive/:year/:month?")
+                .redirect_to("/blog/:year/:month", RedirectKind::MovedPermanently);
+            route
+                .get("/home")
+                .redirect_to("https://example.com/?from=home#top", RedirectKind::Found);
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let permanent = |location: &str| (StatusCode::PERMANENT_REDIRECT, location.to_owned());
+
+        assert_eq!(
+            redirect(&test_server, "/old-blog/hello-world"),
+            permanent("/blog/hello-world")
+        );
+        assert_eq!(
+            redirect(&test_server, "/old-blog/a%20b"),
+            permanent("/blog/a%20b")
+        );
+        assert_eq!(
+            redirect(&test_server, "/users/42/files/docs/a%20b.txt"),
+            (
+                StatusCode::TEMPORARY_REDIRECT,
+                "/files/42/docs/a%20b.txt".to_owned()
+            )
+        );
+        assert_eq!(
+            redirect(&test_server, "/archive/2018/06"),
+            (StatusCode::MOVED_PERMANENTLY, "/blog/2018/06".to_owned())
+        );
+        assert_eq!(
+            redirect(&test_server, "/archive/2018"),
+            (StatusCode::MOVED_PERMANENTLY, "/blog/2018".to_owned())
+        );
+        assert_eq!(
+            redirect(&test_server, "/home"),
+            (
+                StatusCode::FOUND,
+                "https://example.com/?from=home#top".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn redirects_with_query_string() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/old-blog/:slug")
+                .redirect_to("/blog/:slug", RedirectKind::Permanent);
+            route
+                .get("/home")
+                .redirect_to("/?from=home", RedirectKind::Permanent);
+            route.get("/search").redirect_to_with(
+                RedirectOptions::new("/find", RedirectKind::Permanent).with_query_string(false),
+            );
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        assert_eq!(
+            redirect(&test_server, "/old-blog/hello?page=2&sort=new").1,
+            "/blog/hello?page=2&sort=new"
+        );
+        assert_eq!(redirect(&test_server, "/home?a=1").1, "/?from=home&a=1");
+        assert_eq!(redirect(&test_server, "/home").1, "/?from=home");
+        assert_eq!(redirect(&test_server, "/search?q=gotham").1, "/find");
+    }
+
+    #[test]
+    fn reports_missing_placeholder() {
+        let result = build_router_result((), finalize_pipeline_set(new_pipeline_set()), |route| {
+            route
+                .get("/old-blog/:slug")
+                .redirect_to("/blog/:year/:slug", RedirectKind::Permanent);
+        });
+
+        assert_eq!(
+            result.err(),
+            Some(RouterBuildError::InvalidPattern {
+                path: "/blog/:year/:slug".to_owned(),
+                segment: 1,
+                message: "redirect target `/blog/:year/:slug` uses `:year`, which isn't a \
+                          dynamic segment of route path `/old-blog/:slug`"
+                    .to_owned(),
+            })
+        );
+    }
+}
@@ -0,0 +1,338 @@
+//! Defines functionality for enforcing a deadline on the execution of a `Handler`.
+
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Either};
+use futures::Future;
+use hyper::StatusCode;
+use tokio::timer::Delay;
+
+use error::Result;
+use handler::{Handler, HandlerFuture, IntoHandlerError, NewHandler};
+use helpers::http::response::create_response;
+use state::deadline::Deadline;
+use state::{request_id, State};
+
+/// Runs the future returned by `f`, and responds with `status` if it has not completed within
+/// `timeout`, or by an earlier `Deadline` which is already present in `State`.
+///
+/// When the deadline passes, the pending future is dropped so that no further work is performed
+/// on behalf of the request. Since the `State` is owned by the dropped future, the `Response` is
+/// created from a copy of the request data which Gotham stores in `State` (method, URI, version,
+/// headers, request ID, client address, request times and deadline), taken before `f` is invoked.
+/// Any other data added to `State` is not available in the timeout `Response`.
+///
+/// The `Deadline` in `State` is tightened to the end of `timeout` before `f` is invoked, so that
+/// the remaining time is available to the `Handler`.
+///
+/// This is used by `TimeoutHandler`, and is available for implementing other kinds of deadline,
+/// e.g. in `Middleware` which wraps the remainder of the pipeline.
+pub fn call_with_timeout<F>(
+    mut state: State,
+    timeout: Duration,
+    status: StatusCode,
+    f: F,
+) -> Box<HandlerFuture>
+where
+    F: FnOnce(State) -> Box<HandlerFuture>,
+{
+    let start = Instant::now();
+    let deadline = Deadline::tighten(&mut state, start + timeout);
+
+    let snapshot = state.request_snapshot();
+    let delay = Delay::new(deadline.at());
+
+    let f = f(state).select2(delay).then(move |result| match result {
+        Ok(Either::A((completed, _))) => future::ok(completed),
+        Err(Either::A((failed, _))) => future::err(failed),
+        Ok(Either::B((_, pending))) => {
+            let elapsed = start.elapsed();
+            warn!(
+                "[{}] handler timed out after {}ms, responding with {}",
+                request_id(&snapshot),
+                elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_nanos() / 1_000_000),
+                status
+            );
+
+            drop(pending);
+            let res = create_response(&snapshot, status, None);
+            future::ok((snapshot, res))
+        }
+        Err(Either::B((e, pending))) => {
+            error!(
+                "[{}] timer failure while enforcing handler timeout: {}",
+                request_id(&snapshot),
+                e
+            );
+
+            drop(pending);
+            future::err((snapshot, e.into_handler_error()))
+        }
+    });
+
+    Box::new(f)
+}
+
+/// A `NewHandler` which creates `TimeoutHandler` values, enforcing a deadline on each `Handler`
+/// created by the wrapped `NewHandler`.
+///
+/// This is usually created via `DefineSingleRoute::with_timeout`.
+pub struct TimeoutNewHandler<NH>
+where
+    NH: NewHandler,
+{
+    new_handler: NH,
+    timeout: Duration,
+    status: StatusCode,
+}
+
+impl<NH> TimeoutNewHandler<NH>
+where
+    NH: NewHandler,
+{
+    /// Wraps the `NewHandler`, responding with `503 Service Unavailable` when a `Handler` does not
+    /// complete within `timeout`.
+    pub fn new(new_handler: NH, timeout: Duration) -> Self {
+        TimeoutNewHandler {
+            new_handler,
+            timeout,
+            status: StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Sets the status of the `Response` sent when the deadline passes.
+    pub fn with_status(self, status: StatusCode) -> Self {
+        TimeoutNewHandler { status, ..self }
+    }
+}
+
+impl<NH> NewHandler for TimeoutNewHandler<NH>
+where
+    NH: NewHandler,
+{
+    type Instance = TimeoutHandler<NH::Instance>;
+
+    fn new_handler(&self) -> Result<Self::Instance> {
+        Ok(TimeoutHandler {
+            handler: self.new_handler.new_handler()?,
+            timeout: self.timeout,
+            status: self.status,
+        })
+    }
+}
+
+/// A `Handler` which responds with an error status when the wrapped `Handler` does not complete
+/// within a deadline. See `call_with_timeout` for details.
+pub struct TimeoutHandler<H>
+where
+    H: Handler,
+{
+    handler: H,
+    timeout: Duration,
+    status: StatusCode,
+}
+
+impl<H> Handler for TimeoutHandler<H>
+where
+    H: Handler,
+{
+    fn handle(self, state: State) -> Box<HandlerFuture> {
+        let handler = self.handler;
+        call_with_timeout(state, self.timeout, self.status, move |state| {
+            handler.handle(state)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use hyper::header::HeaderMap;
+    use hyper::{Body, Method, Response};
+    use tokio::runtime::Runtime;
+
+    use helpers::http::header::X_REQUEST_ID;
+    use router::builder::*;
+    use router::Router;
+    use state::{set_request_id, FromState};
+    use test::TestServer;
+
+    fn fast(state: State) -> (State, Response<Body>) {
+        (state, Response::new(Body::empty()))
+    }
+
+    fn remaining_ms(state: &State) -> u64 {
+        let remaining = Deadline::borrow_from(state).remaining();
+        remaining.as_secs() * 1000 + u64::from(remaining.subsec_nanos() / 1_000_000)
+    }
+
+    fn slow(state: State) -> Box<HandlerFuture> {
+        let f = Delay::new(Instant::now() + Duration::from_secs(5)).then(move |_| {
+            let res = Response::new(Body::empty());
+            future::ok((state, res))
+        });
+
+        Box::new(f)
+    }
+
+    fn router() -> Router {
+        build_simple_router(|route| {
+            route
+                .get("/fast")
+                .with_timeout(Duration::from_millis(500))
+                .to(fast);
+
+            route
+                .get("/slow")
+                .with_timeout(Duration::from_millis(50))
+                .to(slow);
+
+            route
+                .get("/gateway")
+                .with_timeout(Duration::from_millis(50))
+                .with_timeout_status(StatusCode::GATEWAY_TIMEOUT)
+                .to(slow);
+        })
+    }
+
+    #[test]
+    fn handler_within_deadline_responds() {
+        let test_server = TestServer::new(router()).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/fast")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn handler_past_deadline_is_unavailable() {
+        let test_server = TestServer::new(router()).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/slow")
+            .with_header(X_REQUEST_ID, "slow-id".parse().unwrap())
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers().get(X_REQUEST_ID).unwrap(), "slow-id");
+    }
+
+    #[test]
+    fn timeout_status_is_configurable() {
+        let test_server = TestServer::new(router()).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/gateway")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[test]
+    fn deadline_is_tightened_for_handler() {
+        fn run(state: State, timeout: Duration) -> u64 {
+            let f = call_with_timeout(state, timeout, StatusCode::SERVICE_UNAVAILABLE, |state| {
+                Box::new(future::ok((state, Response::new(Body::empty()))))
+            });
+
+            let mut runtime = Runtime::new().unwrap();
+            match runtime.block_on(f) {
+                Ok((state, _res)) => remaining_ms(&state),
+                Err(_) => panic!("handler should respond"),
+            }
+        }
+
+        let mut state = State::new();
+        set_request_id(&mut state);
+        let remaining = run(state, Duration::from_secs(2));
+        assert!(remaining > 1000 && remaining <= 2000);
+
+        // An outer deadline which is tighter than the route timeout is left in place.
+        let mut state = State::new();
+        set_request_id(&mut state);
+        Deadline::tighten(&mut state, Instant::now() + Duration::from_millis(500));
+        assert!(run(state, Duration::from_secs(10)) <= 500);
+
+        // A tighter route timeout replaces a looser outer deadline.
+        let mut state = State::new();
+        set_request_id(&mut state);
+        Deadline::tighten(&mut state, Instant::now() + Duration::from_secs(10));
+        assert!(run(state, Duration::from_millis(500)) <= 500);
+    }
+
+    #[test]
+    fn pending_handler_is_dropped() {
+        struct Flag(Arc<AtomicBool>);
+
+        impl Drop for Flag {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = Flag(dropped.clone());
+
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        state.put(Method::GET);
+        set_request_id(&mut state);
+
+        let f = call_with_timeout(
+            state,
+            Duration::from_millis(10),
+            StatusCode::SERVICE_UNAVAILABLE,
+            move |state| {
+                let f = Delay::new(Instant::now() + Duration::from_secs(5)).then(move |_| {
+                    drop(flag);
+                    future::ok((state, Response::new(Body::empty())))
+                });
+                Box::new(f)
+            },
+        );
+
+        let mut runtime = Runtime::new().unwrap();
+        let status = match runtime.block_on(f) {
+            Ok((_state, res)) => res.status(),
+            Err(_) => panic!("timeout should produce a response"),
+        };
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn outer_deadline_is_enforced() {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        state.put(Method::GET);
+        set_request_id(&mut state);
+        Deadline::tighten(&mut state, Instant::now() + Duration::from_millis(10));
+
+        let start = Instant::now();
+        let f = call_with_timeout(
+            state,
+            Duration::from_secs(10),
+            StatusCode::SERVICE_UNAVAILABLE,
+            slow,
+        );
+
+        let mut runtime = Runtime::new().unwrap();
+        let status = match runtime.block_on(f) {
+            Ok((_state, res)) => res.status(),
+            Err(_) => panic!("timeout should produce a response"),
+        };
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,503 @@
+//! Helpers for content negotiation via the `Accept` and `Accept-Encoding` request headers.
+
+use hyper::header::{HeaderMap, HeaderValue, ACCEPT, ACCEPT_ENCODING};
+use hyper::Response;
+use mime::{self, Mime};
+
+use helpers::http::response::add_vary;
+use state::{FromState, State};
+
+/// Chooses the best of the `supported` media types according to the `Accept` header of the
+/// request, as described by [RFC 7231, section 5.3.2][rfc].
+///
+/// Each supported type is assigned the quality value of the most specific media range which
+/// matches it, where `type/subtype` (with any parameters) takes precedence over `type/*`, which in
+/// turn takes precedence over `*/*`. The type with the highest quality value is returned, with
+/// ties resolved by the order of `supported`. A missing `Accept` header accepts any media type, so
+/// the first supported type is returned.
+///
+/// When `None` is returned, no supported type is acceptable and the caller should respond with
+/// `406 Not Acceptable`.
+///
+/// [rfc]: https://tools.ietf.org/html/rfc7231#section-5.3.2
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::{ACCEPT, VARY};
+/// # use gotham::helpers::http::accept::{negotiate, vary_on_accept};
+/// # use gotham::helpers::http::response::{create_html_response, create_response};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let supported = [mime::TEXT_HTML, mime::APPLICATION_JSON];
+///
+///     let mut res = match negotiate(&state, &supported) {
+///         Some(ref m) if *m == mime::APPLICATION_JSON => create_response(
+///             &state,
+///             StatusCode::OK,
+///             Some((b"{\"hello\":\"world\"}".to_vec(), mime::APPLICATION_JSON)),
+///         ),
+///         Some(_) => create_html_response(&state, StatusCode::OK, "<p>Hello, world!</p>"),
+///         None => create_response(&state, StatusCode::NOT_ACCEPTABLE, None),
+///     };
+///
+///     vary_on_accept(&mut res);
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .with_header(ACCEPT, "text/html;q=0.5, application/json".parse().unwrap())
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::OK);
+/// #     assert_eq!(response.headers().get(VARY).unwrap(), "Accept");
+/// #     assert_eq!(response.read_utf8_body().unwrap(), "{\"hello\":\"world\"}");
+/// #
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .with_header(ACCEPT, "image/png".parse().unwrap())
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+/// # }
+/// ```
+pub fn negotiate(state: &State, supported: &[Mime]) -> Option<Mime> {
+    let accept = HeaderMap::try_borrow_from(state).and_then(|headers| headers.get(ACCEPT));
+    negotiate_header(accept, supported)
+}
+
+/// Adds `Accept` to the `Vary` header of the `Response`, so that caches store a separate copy of
+/// the `Response` for each `Accept` header value. This should be used whenever the `Response`
+/// was chosen via `negotiate`.
+pub fn vary_on_accept<B>(res: &mut Response<B>) {
+    add_vary(res, "Accept")
+}
+
+/// Adds `Accept-Encoding` to the `Vary` header of the `Response`. This should be used whenever
+/// the `Content-Encoding` of the `Response` was chosen via `negotiate_encoding`.
+pub fn vary_on_accept_encoding<B>(res: &mut Response<B>) {
+    add_vary(res, "Accept-Encoding")
+}
+
+/// The `identity` content coding, which indicates that no encoding is applied.
+pub const IDENTITY: &'static str = "identity";
+
+/// Chooses the best of the `supported` content codings (e.g. `br` or `gzip`) according to the
+/// `Accept-Encoding` header of the request, as described by [RFC 7231, section 5.3.4][rfc].
+///
+/// `identity` (i.e. sending the representation unencoded) is always considered, after the
+/// `supported` codings, and is returned as `IDENTITY`. It is acceptable unless excluded via
+/// `identity;q=0`, or via `*;q=0` without `identity` being listed separately. In that case the
+/// client will accept a compressed representation or nothing, and `None` is returned if none of
+/// the `supported` codings is acceptable, so the caller should respond with `406 Not Acceptable`.
+///
+/// Although RFC 7231 permits any coding when the header is missing, `IDENTITY` is returned in
+/// that case, as such clients frequently cannot decode compressed responses.
+///
+/// [rfc]: https://tools.ietf.org/html/rfc7231#section-5.3.4
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::{ACCEPT_ENCODING, CONTENT_ENCODING, VARY};
+/// # use gotham::helpers::http::accept::{negotiate_encoding, vary_on_accept_encoding, IDENTITY};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let mut res = Response::new(Body::empty());
+///
+///     match negotiate_encoding(&state, &["br", "gzip"]) {
+///         Some(IDENTITY) => {}
+///         Some(coding) => {
+///             // Serve the pre-compressed file, e.g. `app.js.br`.
+///             res.headers_mut().insert(CONTENT_ENCODING, coding.parse().unwrap());
+///         }
+///         None => *res.status_mut() = StatusCode::NOT_ACCEPTABLE,
+///     }
+///
+///     vary_on_accept_encoding(&mut res);
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/app.js")
+/// #         .with_header(ACCEPT_ENCODING, "gzip, br;q=0.9".parse().unwrap())
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+/// #     assert_eq!(response.headers().get(VARY).unwrap(), "Accept-Encoding");
+/// # }
+/// ```
+pub fn negotiate_encoding<'a>(state: &State, supported: &[&'a str]) -> Option<&'a str> {
+    let accept_encoding =
+        HeaderMap::try_borrow_from(state).and_then(|headers| headers.get(ACCEPT_ENCODING));
+    negotiate_encoding_header(accept_encoding, supported)
+}
+
+/// Chooses the best of the `supported` content codings for the given `Accept-Encoding` header
+/// value. See `negotiate_encoding`.
+pub(crate) fn negotiate_encoding_header<'a>(
+    accept_encoding: Option<&HeaderValue>,
+    supported: &[&'a str],
+) -> Option<&'a str> {
+    let codings = match accept_encoding.map(|value| value.to_str()) {
+        Some(Ok(value)) => parse_codings(value),
+        // An unreadable header is treated in the same way as a missing one.
+        Some(Err(_)) | None => return Some(IDENTITY),
+    };
+
+    let quality = |name: &str| {
+        let find = |target: &str| {
+            codings
+                .iter()
+                .find(|&&(ref coding, _)| coding.eq_ignore_ascii_case(target))
+                .map(|&(_, q)| q)
+        };
+
+        find(name).or_else(|| find("*")).unwrap_or_else(|| {
+            if name.eq_ignore_ascii_case(IDENTITY) {
+                1.0
+            } else {
+                0.0
+            }
+        })
+    };
+
+    let mut best: Option<(&'a str, f32)> = None;
+
+    for &candidate in supported.iter().chain(Some(&IDENTITY)) {
+        let q = quality(candidate);
+        let better = match best {
+            Some((_, best_q)) => q > best_q,
+            None => q > 0.0,
+        };
+
+        if better {
+            best = Some((candidate, q));
+        }
+    }
+
+    best.map(|(candidate, _)| candidate)
+}
+
+/// Parses the codings of an `Accept-Encoding` header value. Codings with an invalid quality value
+/// are ignored.
+fn parse_codings(value: &str) -> Vec<(String, f32)> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let coding = parts.next().unwrap_or("").trim();
+
+            if coding.is_empty() {
+                return None;
+            }
+
+            let mut quality = 1.0;
+            for param in parts {
+                let mut pair = param.splitn(2, '=');
+                let name = pair.next().unwrap_or("").trim();
+                let value = pair.next().unwrap_or("").trim();
+
+                if name.eq_ignore_ascii_case("q") {
+                    match value.parse::<f32>() {
+                        Ok(q) if (0.0..=1.0).contains(&q) => quality = q,
+                        _ => return None,
+                    }
+                }
+            }
+
+            Some((coding.to_owned(), quality))
+        }).collect()
+}
+
+/// Chooses the best of the `supported` media types for the given `Accept` header value. See
+/// `negotiate`.
+pub(crate) fn negotiate_header(
+    accept: Option<&HeaderValue>,
+    supported: &[Mime],
+) -> Option<Mime> {
+    let ranges = match accept.and_then(|accept| accept.to_str().ok()) {
+        Some(accept) => parse_ranges(accept),
+        None => Vec::new(),
+    };
+
+    if ranges.is_empty() {
+        return supported.first().cloned();
+    }
+
+    let mut best: Option<(&Mime, f32)> = None;
+
+    for candidate in supported {
+        let quality = ranges
+            .iter()
+            .filter(|range| range.matches(candidate))
+            .max_by_key(|range| range.specificity())
+            .map(|range| range.quality)
+            .unwrap_or(0.0);
+
+        let better = match best {
+            Some((_, q)) => quality > q,
+            None => quality > 0.0,
+        };
+
+        if better {
+            best = Some((candidate, quality));
+        }
+    }
+
+    best.map(|(candidate, _)| candidate.clone())
+}
+
+/// A single media range from an `Accept` header, with its quality value.
+struct MediaRange {
+    mime: Mime,
+    quality: f32,
+}
+
+impl MediaRange {
+    fn matches(&self, candidate: &Mime) -> bool {
+        if self.mime.type_() == mime::STAR {
+            return true;
+        }
+
+        if self.mime.type_() != candidate.type_() {
+            return false;
+        }
+
+        if self.mime.subtype() == mime::STAR {
+            return true;
+        }
+
+        self.mime.subtype() == candidate.subtype() && self.params().all(|(name, value)| {
+            candidate
+                .get_param(name.as_str())
+                .map(|v| v.as_str() == value.as_str())
+                .unwrap_or(false)
+        })
+    }
+
+    /// Ranks `*/*` below `type/*`, which is below `type/subtype`. Ranges with more parameters are
+    /// more specific.
+    fn specificity(&self) -> usize {
+        if self.mime.type_() == mime::STAR {
+            0
+        } else if self.mime.subtype() == mime::STAR {
+            1
+        } else {
+            2 + self.params().count()
+        }
+    }
+
+    /// The parameters of the media range, excluding the quality value.
+    fn params<'a>(&'a self) -> Box<Iterator<Item = (mime::Name<'a>, mime::Name<'a>)> + 'a> {
+        Box::new(self.mime.params().filter(|&(ref name, _)| *name != "q"))
+    }
+}
+
+/// Parses the media ranges of an `Accept` header value. Ranges which cannot be parsed, or which
+/// have an invalid quality value, are ignored.
+fn parse_ranges(accept: &str) -> Vec<MediaRange> {
+    accept
+        .split(',')
+        .filter_map(|range| range.trim().parse::<Mime>().ok())
+        .filter_map(|mime| {
+            let quality = match mime.get_param("q") {
+                Some(q) => match q.as_str().parse::<f32>() {
+                    Ok(q) if (0.0..=1.0).contains(&q) => q,
+                    _ => return None,
+                },
+                None => 1.0,
+            };
+
+            Some(MediaRange { mime, quality })
+        }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::VARY;
+    use hyper::{Body, StatusCode};
+
+    fn best(accept: Option<&str>, supported: &[&str]) -> Option<String> {
+        let accept = accept.map(|accept| HeaderValue::from_str(accept).unwrap());
+        let supported = supported
+            .iter()
+            .map(|s| s.parse::<Mime>().unwrap())
+            .collect::<Vec<_>>();
+
+        negotiate_header(accept.as_ref(), &supported).map(|m| m.to_string())
+    }
+
+    #[test]
+    fn rfc7231_examples() {
+        let rfc_levels = "text/*;q=0.3, text/html;q=0.7, text/html;level=1, \
+                          text/html;level=2;q=0.4, */*;q=0.5";
+
+        let table: Vec<(Option<&str>, Vec<&str>, Option<&str>)> = vec![
+            // A missing header accepts anything, so the server's first preference wins.
+            (None, vec!["application/json", "text/html"], Some("application/json")),
+            // "audio/* with a quality of 0.2, but audio/basic is preferred"
+            (
+                Some("audio/*; q=0.2, audio/basic"),
+                vec!["audio/mpeg", "audio/basic"],
+                Some("audio/basic"),
+            ),
+            (
+                Some("audio/*; q=0.2, audio/basic"),
+                vec!["audio/mpeg"],
+                Some("audio/mpeg"),
+            ),
+            (Some("audio/*; q=0.2, audio/basic"), vec!["text/html"], None),
+            // "text/html and text/x-c are the equally preferred media types, but if they do not
+            // exist, then send the text/x-dvi representation, and if that does not exist, send
+            // the text/plain representation"
+            (
+                Some("text/plain; q=0.5, text/html, text/x-dvi; q=0.8, text/x-c"),
+                vec!["text/plain", "text/x-dvi", "text/html"],
+                Some("text/html"),
+            ),
+            (
+                Some("text/plain; q=0.5, text/html, text/x-dvi; q=0.8, text/x-c"),
+                vec!["text/plain", "text/x-dvi"],
+                Some("text/x-dvi"),
+            ),
+            (
+                Some("text/plain; q=0.5, text/html, text/x-dvi; q=0.8, text/x-c"),
+                vec!["text/plain"],
+                Some("text/plain"),
+            ),
+            // Quality values are taken from the most specific matching range.
+            (Some(rfc_levels), vec!["text/plain", "image/jpeg"], Some("image/jpeg")),
+            (
+                Some(rfc_levels),
+                vec!["text/html;level=2", "text/plain"],
+                Some("text/html;level=2"),
+            ),
+            (Some(rfc_levels), vec!["text/plain", "text/html"], Some("text/html")),
+            (
+                Some(rfc_levels),
+                vec!["text/html", "text/html;level=1"],
+                Some("text/html;level=1"),
+            ),
+            (
+                Some(rfc_levels),
+                vec!["text/html;level=2", "text/html;level=3"],
+                Some("text/html;level=3"),
+            ),
+            // A quality of zero means "not acceptable".
+            (Some("text/html;q=0, */*"), vec!["text/html"], None),
+            (
+                Some("text/html;q=0, */*"),
+                vec!["text/html", "application/json"],
+                Some("application/json"),
+            ),
+            // Unparseable ranges are ignored.
+            (Some("nonsense, text/html"), vec!["text/html"], Some("text/html")),
+            (Some("text/html;q=2, text/plain"), vec!["text/html"], None),
+        ];
+
+        for (accept, supported, expected) in table {
+            assert_eq!(
+                best(accept, &supported),
+                expected.map(|e| e.to_owned()),
+                "Accept: {:?}, supported: {:?}",
+                accept,
+                supported
+            );
+        }
+    }
+
+    fn best_encoding(
+        accept_encoding: Option<&str>,
+        supported: &[&'static str],
+    ) -> Option<&'static str> {
+        let accept_encoding = accept_encoding.map(|value| HeaderValue::from_str(value).unwrap());
+        negotiate_encoding_header(accept_encoding.as_ref(), supported)
+    }
+
+    #[test]
+    fn encoding_quality_values() {
+        let table: Vec<(Option<&str>, Vec<&str>, Option<&str>)> = vec![
+            // Missing or empty headers only accept identity.
+            (None, vec!["br", "gzip"], Some("identity")),
+            (Some(""), vec!["br", "gzip"], Some("identity")),
+            // The server's preference breaks ties.
+            (Some("gzip, br"), vec!["br", "gzip"], Some("br")),
+            (Some("gzip, br;q=0.9"), vec!["br", "gzip"], Some("gzip")),
+            // Codings are case-insensitive.
+            (Some("GZIP"), vec!["gzip"], Some("gzip")),
+            // Unsupported codings fall back to identity.
+            (Some("deflate"), vec!["br", "gzip"], Some("identity")),
+            // Identity may be preferred over a supported coding.
+            (Some("gzip;q=0.5, identity"), vec!["gzip"], Some("identity")),
+            // A quality of zero excludes the coding.
+            (Some("br;q=0, gzip"), vec!["br", "gzip"], Some("gzip")),
+            (Some("gzip;q=0"), vec!["gzip"], Some("identity")),
+            // The wildcard applies to anything not listed, including identity.
+            (Some("*"), vec!["br", "gzip"], Some("br")),
+            (Some("*;q=0.1, gzip"), vec!["br", "gzip"], Some("gzip")),
+            (Some("br;q=0, *"), vec!["br", "gzip"], Some("gzip")),
+            // Excluding identity means "compressed or nothing".
+            (Some("identity;q=0, gzip"), vec!["br", "gzip"], Some("gzip")),
+            (Some("identity;q=0, deflate"), vec!["br", "gzip"], None),
+            (Some("*;q=0"), vec!["br", "gzip"], None),
+            (Some("*;q=0, identity"), vec!["br"], Some("identity")),
+            (Some("*;q=0, br;q=0.001"), vec!["br"], Some("br")),
+            // Invalid quality values cause the entry to be ignored.
+            (Some("gzip;q=5"), vec!["gzip"], Some("identity")),
+            (Some("gzip;q=high, br"), vec!["gzip", "br"], Some("br")),
+        ];
+
+        for (accept_encoding, supported, expected) in table {
+            assert_eq!(
+                best_encoding(accept_encoding, &supported),
+                expected,
+                "Accept-Encoding: {:?}, supported: {:?}",
+                accept_encoding,
+                supported
+            );
+        }
+    }
+
+    #[test]
+    fn vary_is_added_once() {
+        let mut res = Response::builder()
+            .status(StatusCode::OK)
+            .header(VARY, "Accept-Encoding")
+            .body(Body::empty())
+            .unwrap();
+
+        vary_on_accept(&mut res);
+        vary_on_accept(&mut res);
+        vary_on_accept_encoding(&mut res);
+
+        let vary = res.headers().get_all(VARY).iter().collect::<Vec<_>>();
+        assert_eq!(vary, vec!["Accept-Encoding, Accept"]);
+    }
+}
@@ -0,0 +1,646 @@
+//! Helpers for conditional requests via entity tags, as described by [RFC 7232][rfc].
+//!
+//! [rfc]: https://tools.ietf.org/html/rfc7232
+
+use std::fmt::{self, Display, Formatter};
+use std::time::SystemTime;
+
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, ETAG, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    IF_UNMODIFIED_SINCE, LAST_MODIFIED,
+};
+use hyper::{Body, Method, Response, StatusCode};
+
+use helpers::http::date::{format_http_date, parse_http_date, whole_seconds};
+use helpers::http::response::response;
+use state::{FromState, State};
+
+/// An entity tag, used to identify a specific representation of a resource in the `ETag`,
+/// `If-Match` and `If-None-Match` headers.
+///
+/// A weak entity tag (`W/"xyz"`) identifies a representation which is semantically equivalent
+/// to others with the same tag, where a strong tag (`"xyz"`) identifies the exact bytes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EntityTag {
+    weak: bool,
+    tag: String,
+}
+
+impl EntityTag {
+    /// Creates a strong `EntityTag`. The `tag` is the opaque value, without the surrounding
+    /// quotes.
+    ///
+    /// # Panics
+    ///
+    /// When `tag` contains characters which are not valid in an entity tag, such as `"`.
+    pub fn strong<T: Into<String>>(tag: T) -> EntityTag {
+        EntityTag::new(false, tag.into())
+    }
+
+    /// Creates a weak `EntityTag`. The `tag` is the opaque value, without the surrounding quotes
+    /// or `W/` prefix.
+    ///
+    /// # Panics
+    ///
+    /// When `tag` contains characters which are not valid in an entity tag, such as `"`.
+    pub fn weak<T: Into<String>>(tag: T) -> EntityTag {
+        EntityTag::new(true, tag.into())
+    }
+
+    fn new(weak: bool, tag: String) -> EntityTag {
+        assert!(
+            tag.chars().all(is_etagc),
+            "invalid characters in entity tag: {:?}",
+            tag
+        );
+
+        EntityTag { weak, tag }
+    }
+
+    /// Parses an entity tag in its header representation, such as `W/"xyz"`.
+    pub fn parse(value: &str) -> Option<EntityTag> {
+        match parse_entity_tag(value.trim()) {
+            Some((etag, "")) => Some(etag),
+            _ => None,
+        }
+    }
+
+    /// Determines if this is a weak entity tag.
+    pub fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    /// The opaque value of the entity tag, without the surrounding quotes or `W/` prefix.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Compares two entity tags using the strong comparison function, where both must be strong
+    /// and have the same opaque value.
+    pub fn strong_eq(&self, other: &EntityTag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Compares two entity tags using the weak comparison function, where the opaque values must
+    /// match regardless of whether either tag is weak.
+    pub fn weak_eq(&self, other: &EntityTag) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl Display for EntityTag {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.weak {
+            f.write_str("W/")?;
+        }
+
+        write!(f, "\"{}\"", self.tag)
+    }
+}
+
+/// The value of an `If-Match` or `If-None-Match` header, which is either `*` or a list of entity
+/// tags.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EntityTagList {
+    /// `*`, which matches any current representation of the resource.
+    Any,
+    /// A list of entity tags, any one of which may match.
+    Tags(Vec<EntityTag>),
+}
+
+impl EntityTagList {
+    /// Parses a single header value, such as `"xyz", W/"abc"` or `*`.
+    pub fn parse(value: &str) -> Option<EntityTagList> {
+        let mut rest = value.trim();
+
+        if rest == "*" {
+            return Some(EntityTagList::Any);
+        }
+
+        let mut tags = Vec::new();
+
+        loop {
+            rest = rest.trim_left_matches(|c: char| c == ',' || c == ' ' || c == '\t');
+
+            if rest.is_empty() {
+                break;
+            }
+
+            let (etag, remaining) = parse_entity_tag(rest)?;
+            tags.push(etag);
+
+            rest = remaining.trim_left_matches(|c: char| c == ' ' || c == '\t');
+            if !rest.is_empty() && !rest.starts_with(',') {
+                return None;
+            }
+        }
+
+        if tags.is_empty() {
+            None
+        } else {
+            Some(EntityTagList::Tags(tags))
+        }
+    }
+
+    /// Parses every value of the named header into a single list. `None` is returned when the
+    /// header is absent or any value is malformed, in which case the precondition is ignored.
+    pub fn from_headers(headers: &HeaderMap, name: &str) -> Option<EntityTagList> {
+        let mut combined: Option<EntityTagList> = None;
+
+        for value in headers.get_all(name) {
+            let list = EntityTagList::parse(value.to_str().ok()?)?;
+
+            combined = Some(match (combined, list) {
+                (None, list) => list,
+                (Some(EntityTagList::Tags(mut tags)), EntityTagList::Tags(more)) => {
+                    tags.extend(more);
+                    EntityTagList::Tags(tags)
+                }
+                _ => return None,
+            });
+        }
+
+        combined
+    }
+
+    /// Determines if any entity tag in the list matches `etag` using the strong comparison
+    /// function, as required by `If-Match`.
+    pub fn matches_strong(&self, etag: &EntityTag) -> bool {
+        match *self {
+            EntityTagList::Any => true,
+            EntityTagList::Tags(ref tags) => tags.iter().any(|t| t.strong_eq(etag)),
+        }
+    }
+
+    /// Determines if any entity tag in the list matches `etag` using the weak comparison
+    /// function, as required by `If-None-Match`.
+    pub fn matches_weak(&self, etag: &EntityTag) -> bool {
+        match *self {
+            EntityTagList::Any => true,
+            EntityTagList::Tags(ref tags) => tags.iter().any(|t| t.weak_eq(etag)),
+        }
+    }
+}
+
+/// Determines if the client already has the representation identified by `etag`, according to
+/// the `If-None-Match` header of the request. When `true`, a `GET` or `HEAD` request should be
+/// answered with `not_modified_response`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::{Body, HeaderMap, Response, StatusCode};
+/// # use hyper::header::{ETAG, IF_NONE_MATCH};
+/// # use gotham::helpers::http::conditional::{is_not_modified, not_modified_response, EntityTag};
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let etag = EntityTag::strong("v42");
+///
+///     let res = if is_not_modified(&state, &etag) {
+///         not_modified_response(&state, &etag, HeaderMap::new())
+///     } else {
+///         let mut res = create_response(
+///             &state,
+///             StatusCode::OK,
+///             Some((b"content".to_vec(), mime::TEXT_PLAIN)),
+///         );
+///         res.headers_mut()
+///             .insert(ETAG, etag.to_string().parse().unwrap());
+///         res
+///     };
+///
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .with_header(IF_NONE_MATCH, "W/\"v41\", W/\"v42\"".parse().unwrap())
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+/// #     assert_eq!(response.headers().get(ETAG).unwrap(), "\"v42\"");
+/// # }
+/// ```
+pub fn is_not_modified(state: &State, etag: &EntityTag) -> bool {
+    EntityTagList::from_headers(HeaderMap::borrow_from(state), IF_NONE_MATCH.as_str())
+        .map_or(false, |list| list.matches_weak(etag))
+}
+
+/// Determines if the `If-Match` header of the request fails against the current representation
+/// of the resource, identified by `etag` (or `None` when the resource has no current
+/// representation). When `true`, the request should be answered with `412 Precondition Failed`
+/// rather than being performed.
+pub fn is_precondition_failed(state: &State, etag: Option<&EntityTag>) -> bool {
+    match EntityTagList::from_headers(HeaderMap::borrow_from(state), IF_MATCH.as_str()) {
+        None => false,
+        Some(list) => match etag {
+            Some(etag) => !list.matches_strong(etag),
+            None => true,
+        },
+    }
+}
+
+/// Creates a `304 Not Modified` response for the representation identified by `etag`.
+///
+/// The response has no body, and includes the `ETag` header. The `extra_headers` should include
+/// any of `Cache-Control`, `Content-Location`, `Date`, `Expires` and `Vary` which would have been
+/// sent in a `200 OK` response, as required by [RFC 7232, section 4.1][rfc].
+///
+/// [rfc]: https://tools.ietf.org/html/rfc7232#section-4.1
+pub fn not_modified_response(
+    state: &State,
+    etag: &EntityTag,
+    extra_headers: HeaderMap,
+) -> Response<Body> {
+    let etag =
+        HeaderValue::from_str(&etag.to_string()).expect("EntityTag is always a valid header value");
+
+    response(state)
+        .status(StatusCode::NOT_MODIFIED)
+        .header(ETAG, etag)
+        .headers(extra_headers)
+        .build()
+        .expect("Response built from valid headers")
+}
+
+/// Determines if the representation, last modified at `last_modified`, has been modified since
+/// the `If-Modified-Since` date of the request, at the one second precision of an HTTP date.
+///
+/// When `false`, the client's copy is current and the request should be answered with
+/// `not_modified_response`. The header is ignored (and `true` returned) when it is malformed,
+/// when the request also has an `If-None-Match` header, which takes precedence, or when the
+/// request method is not `GET` or `HEAD`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// # use hyper::{Body, HeaderMap, Response, StatusCode};
+/// # use hyper::header::{IF_MODIFIED_SINCE, LAST_MODIFIED};
+/// # use gotham::helpers::http::conditional::{
+/// #     check_if_modified_since, not_modified_response, set_last_modified, EntityTag,
+/// # };
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let last_modified = UNIX_EPOCH + Duration::from_secs(784_111_777);
+///     let etag = EntityTag::weak("784111777");
+///
+///     let mut res = if check_if_modified_since(&state, last_modified) {
+///         create_response(
+///             &state,
+///             StatusCode::OK,
+///             Some((b"content".to_vec(), mime::TEXT_PLAIN)),
+///         )
+///     } else {
+///         not_modified_response(&state, &etag, HeaderMap::new())
+///     };
+///
+///     set_last_modified(&mut res, last_modified);
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .with_header(IF_MODIFIED_SINCE, "Sunday, 06-Nov-94 08:49:37 GMT".parse().unwrap())
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+/// #     assert_eq!(
+/// #         response.headers().get(LAST_MODIFIED).unwrap(),
+/// #         "Sun, 06 Nov 1994 08:49:37 GMT"
+/// #     );
+/// # }
+/// ```
+pub fn check_if_modified_since(state: &State, last_modified: SystemTime) -> bool {
+    let method = Method::borrow_from(state);
+    let headers = HeaderMap::borrow_from(state);
+
+    if (*method != Method::GET && *method != Method::HEAD) || headers.contains_key(IF_NONE_MATCH) {
+        return true;
+    }
+
+    match date_header(headers, IF_MODIFIED_SINCE) {
+        Some(since) => whole_seconds(last_modified) > whole_seconds(since),
+        None => true,
+    }
+}
+
+/// Determines if the representation, last modified at `last_modified`, satisfies the
+/// `If-Unmodified-Since` date of the request. When `false`, the request should be answered with
+/// `412 Precondition Failed` rather than being performed.
+///
+/// The header is ignored (and `true` returned) when it is malformed, or when the request also has
+/// an `If-Match` header, which takes precedence.
+pub fn check_if_unmodified_since(state: &State, last_modified: SystemTime) -> bool {
+    let headers = HeaderMap::borrow_from(state);
+
+    if headers.contains_key(IF_MATCH) {
+        return true;
+    }
+
+    match date_header(headers, IF_UNMODIFIED_SINCE) {
+        Some(since) => whole_seconds(last_modified) <= whole_seconds(since),
+        None => true,
+    }
+}
+
+/// Sets the `Last-Modified` header of the `Response`, formatted as an IMF-fixdate.
+pub fn set_last_modified<B>(res: &mut Response<B>, last_modified: SystemTime) {
+    let value = HeaderValue::from_str(&format_http_date(last_modified))
+        .expect("HTTP date is always a valid header value");
+
+    res.headers_mut().insert(LAST_MODIFIED, value);
+}
+
+/// Parses the named header as an HTTP date, if it has a single valid value.
+fn date_header(headers: &HeaderMap, name: HeaderName) -> Option<SystemTime> {
+    let mut values = headers.get_all(&name).iter();
+
+    match (values.next(), values.next()) {
+        (Some(value), None) => parse_http_date(value.to_str().ok()?),
+        _ => None,
+    }
+}
+
+/// Parses an entity tag from the start of `value`, returning the remainder of the string.
+fn parse_entity_tag(value: &str) -> Option<(EntityTag, &str)> {
+    let (weak, rest) = if value.starts_with("W/") {
+        (true, &value[2..])
+    } else {
+        (false, value)
+    };
+
+    if !rest.starts_with('"') {
+        return None;
+    }
+
+    let end = rest[1..].find('"')? + 1;
+    let tag = &rest[1..end];
+
+    if !tag.chars().all(is_etagc) {
+        return None;
+    }
+
+    let etag = EntityTag {
+        weak,
+        tag: tag.to_owned(),
+    };
+
+    Some((etag, &rest[end + 1..]))
+}
+
+/// Determines if `c` is permitted within the quotes of an entity tag.
+fn is_etagc(c: char) -> bool {
+    c == '!' || (c >= '#' && c <= '~') || c >= '\u{80}'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use futures::{Future, Stream};
+    use hyper::header::{CACHE_CONTROL, CONTENT_TYPE};
+
+    use state::set_request_id;
+
+    fn state_with(headers: &[(&'static str, &'static str)]) -> State {
+        let mut map = HeaderMap::new();
+        for &(name, value) in headers {
+            map.append(name, value.parse().unwrap());
+        }
+
+        let mut state = State::new();
+        state.put(Method::GET);
+        state.put(map);
+        set_request_id(&mut state);
+        state
+    }
+
+    #[test]
+    fn comparison_table() {
+        // RFC 7232, section 2.3.2.
+        let cases = vec![
+            ("W/\"1\"", "W/\"1\"", false, true),
+            ("W/\"1\"", "W/\"2\"", false, false),
+            ("W/\"1\"", "\"1\"", false, true),
+            ("\"1\"", "\"1\"", true, true),
+        ];
+
+        for (a, b, strong, weak) in cases {
+            let a = EntityTag::parse(a).unwrap();
+            let b = EntityTag::parse(b).unwrap();
+
+            assert_eq!(
+                a.strong_eq(&b),
+                strong,
+                "strong comparison of {} and {}",
+                a,
+                b
+            );
+            assert_eq!(
+                b.strong_eq(&a),
+                strong,
+                "strong comparison of {} and {}",
+                b,
+                a
+            );
+            assert_eq!(a.weak_eq(&b), weak, "weak comparison of {} and {}", a, b);
+            assert_eq!(b.weak_eq(&a), weak, "weak comparison of {} and {}", b, a);
+        }
+    }
+
+    #[test]
+    fn entity_tag_parsing() {
+        assert_eq!(
+            EntityTag::parse("\"xyzzy\""),
+            Some(EntityTag::strong("xyzzy"))
+        );
+        assert_eq!(
+            EntityTag::parse("W/\"xyzzy\""),
+            Some(EntityTag::weak("xyzzy"))
+        );
+        assert_eq!(EntityTag::parse("\"\""), Some(EntityTag::strong("")));
+        assert_eq!(EntityTag::parse("xyzzy"), None);
+        assert_eq!(EntityTag::parse("w/\"xyzzy\""), None);
+        assert_eq!(EntityTag::parse("\"xyz\"zy\""), None);
+        assert_eq!(EntityTag::parse("\"xyz zy\""), None);
+
+        assert_eq!(EntityTag::weak("xyzzy").to_string(), "W/\"xyzzy\"");
+        assert_eq!(EntityTag::strong("xyzzy").to_string(), "\"xyzzy\"");
+    }
+
+    #[test]
+    fn entity_tag_list_parsing() {
+        assert_eq!(EntityTagList::parse("*"), Some(EntityTagList::Any));
+        assert_eq!(
+            EntityTagList::parse("\"a,b\", W/\"c\" ,\"d\""),
+            Some(EntityTagList::Tags(vec![
+                EntityTag::strong("a,b"),
+                EntityTag::weak("c"),
+                EntityTag::strong("d"),
+            ]))
+        );
+        assert_eq!(EntityTagList::parse(""), None);
+        assert_eq!(EntityTagList::parse("\"a\" \"b\""), None);
+        assert_eq!(EntityTagList::parse("\"a\", *"), None);
+
+        let state = state_with(&[("if-none-match", "\"a\""), ("if-none-match", "W/\"b\"")]);
+        assert_eq!(
+            EntityTagList::from_headers(HeaderMap::borrow_from(&state), "if-none-match"),
+            Some(EntityTagList::Tags(vec![
+                EntityTag::strong("a"),
+                EntityTag::weak("b"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn if_none_match_uses_weak_comparison() {
+        let etag = EntityTag::strong("v1");
+
+        assert!(!is_not_modified(&state_with(&[]), &etag));
+        assert!(is_not_modified(
+            &state_with(&[("if-none-match", "*")]),
+            &etag
+        ));
+        assert!(is_not_modified(
+            &state_with(&[("if-none-match", "W/\"v0\", W/\"v1\"")]),
+            &etag
+        ));
+        assert!(!is_not_modified(
+            &state_with(&[("if-none-match", "\"v0\"")]),
+            &etag
+        ));
+        assert!(!is_not_modified(
+            &state_with(&[("if-none-match", "v1")]),
+            &etag
+        ));
+    }
+
+    #[test]
+    fn if_match_uses_strong_comparison() {
+        let strong = EntityTag::strong("v1");
+        let weak = EntityTag::weak("v1");
+
+        assert!(!is_precondition_failed(&state_with(&[]), None));
+        assert!(!is_precondition_failed(
+            &state_with(&[("if-match", "\"v1\"")]),
+            Some(&strong)
+        ));
+        assert!(is_precondition_failed(
+            &state_with(&[("if-match", "\"v1\"")]),
+            Some(&weak)
+        ));
+        assert!(is_precondition_failed(
+            &state_with(&[("if-match", "W/\"v1\"")]),
+            Some(&strong)
+        ));
+        assert!(!is_precondition_failed(
+            &state_with(&[("if-match", "*")]),
+            Some(&weak)
+        ));
+        assert!(is_precondition_failed(
+            &state_with(&[("if-match", "*")]),
+            None
+        ));
+    }
+
+    #[test]
+    fn if_modified_since() {
+        let since = "Sun, 06 Nov 1994 08:49:37 GMT";
+        let at = UNIX_EPOCH + Duration::from_secs(784_111_777);
+
+        assert!(check_if_modified_since(&state_with(&[]), at));
+        assert!(!check_if_modified_since(
+            &state_with(&[("if-modified-since", since)]),
+            at + Duration::from_millis(999)
+        ));
+        assert!(!check_if_modified_since(
+            &state_with(&[("if-modified-since", since)]),
+            at - Duration::from_secs(1)
+        ));
+        assert!(check_if_modified_since(
+            &state_with(&[("if-modified-since", since)]),
+            at + Duration::from_secs(1)
+        ));
+        assert!(!check_if_modified_since(
+            &state_with(&[("if-modified-since", "Sun Nov  6 08:49:37 1994")]),
+            at
+        ));
+        assert!(check_if_modified_since(
+            &state_with(&[("if-modified-since", "yesterday")]),
+            at
+        ));
+
+        // If-None-Match takes precedence, even when it would not match.
+        assert!(check_if_modified_since(
+            &state_with(&[("if-modified-since", since), ("if-none-match", "\"v0\"")]),
+            at
+        ));
+
+        let mut state = state_with(&[("if-modified-since", since)]);
+        state.put(Method::POST);
+        assert!(check_if_modified_since(&state, at));
+    }
+
+    #[test]
+    fn if_unmodified_since() {
+        let since = "Sunday, 06-Nov-94 08:49:37 GMT";
+        let at = UNIX_EPOCH + Duration::from_secs(784_111_777);
+
+        assert!(check_if_unmodified_since(&state_with(&[]), at));
+        assert!(check_if_unmodified_since(
+            &state_with(&[("if-unmodified-since", since)]),
+            at
+        ));
+        assert!(!check_if_unmodified_since(
+            &state_with(&[("if-unmodified-since", since)]),
+            at + Duration::from_secs(1)
+        ));
+        assert!(check_if_unmodified_since(
+            &state_with(&[("if-unmodified-since", since), ("if-match", "*")]),
+            at + Duration::from_secs(1)
+        ));
+    }
+
+    #[test]
+    fn not_modified_response_has_no_body() {
+        let state = state_with(&[]);
+
+        let mut extra = HeaderMap::new();
+        extra.insert(CACHE_CONTROL, "max-age=60".parse().unwrap());
+
+        let res = not_modified_response(&state, &EntityTag::weak("v1"), extra);
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers().get(ETAG).unwrap(), "W/\"v1\"");
+        assert_eq!(res.headers().get(CACHE_CONTROL).unwrap(), "max-age=60");
+        assert!(res.headers().get(CONTENT_TYPE).is_none());
+
+        let body = res.into_body().concat2().wait().unwrap();
+        assert!(body.is_empty());
+    }
+}
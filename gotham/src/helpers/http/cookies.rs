@@ -0,0 +1,390 @@
+//! Helpers for setting and removing cookies via the `Set-Cookie` response header.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hyper::header::{HeaderValue, SET_COOKIE};
+use hyper::Response;
+
+use helpers::http::date::format_http_date;
+
+/// The `SameSite` attribute of a cookie, which restricts whether it is sent with cross-site
+/// requests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    /// The cookie is only sent with same-site requests.
+    Strict,
+    /// The cookie is also sent with top-level cross-site navigations using a safe method.
+    Lax,
+}
+
+/// A cookie and its attributes, serialized as a `Set-Cookie` header value as described by
+/// [RFC 6265, section 4.1][rfc].
+///
+/// Attributes are omitted unless they are set.
+///
+/// [rfc]: https://tools.ietf.org/html/rfc6265#section-4.1
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// #
+/// # use std::time::Duration;
+/// # use gotham::helpers::http::cookies::{SameSite, SetCookie};
+/// #
+/// # fn main() {
+/// let cookie = SetCookie::new("theme", "dark")
+///     .unwrap()
+///     .max_age(Duration::from_secs(3600))
+///     .path("/")
+///     .secure(true)
+///     .http_only(true)
+///     .same_site(SameSite::Lax);
+///
+/// assert_eq!(
+///     cookie.to_string(),
+///     "theme=dark; Secure; HttpOnly; SameSite=Lax; Path=/; Max-Age=3600"
+/// );
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SetCookie {
+    name: String,
+    value: String,
+    expires: Option<SystemTime>,
+    max_age: Option<Duration>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+    domain: Option<String>,
+    path: Option<String>,
+}
+
+impl SetCookie {
+    /// Creates a `SetCookie` with the given name and value, and no attributes.
+    ///
+    /// The name must be a `token` and the value must consist of `cookie-octet`s (optionally
+    /// wrapped in double quotes), as defined by RFC 6265. Otherwise, the cookie could add
+    /// attributes of its own (e.g. `a; Domain=evil.com`), and `InvalidCookie` is returned.
+    pub fn new<N, V>(name: N, value: V) -> Result<SetCookie, InvalidCookie>
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        let name = name.into();
+        if !is_token(&name) {
+            return Err(InvalidCookie::Name(name));
+        }
+
+        let value = value.into();
+        if !is_cookie_value(&value) {
+            return Err(InvalidCookie::Value(value));
+        }
+
+        Ok(SetCookie {
+            name,
+            value,
+            expires: None,
+            max_age: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+            domain: None,
+            path: None,
+        })
+    }
+
+    /// Sets the `Expires` attribute, after which the client discards the cookie.
+    pub fn expires(self, expires: SystemTime) -> SetCookie {
+        SetCookie {
+            expires: Some(expires),
+            ..self
+        }
+    }
+
+    /// Sets the `Max-Age` attribute, after which the client discards the cookie. This takes
+    /// precedence over `Expires` in clients which support both.
+    pub fn max_age(self, max_age: Duration) -> SetCookie {
+        SetCookie {
+            max_age: Some(max_age),
+            ..self
+        }
+    }
+
+    /// Sets the `Path` attribute.
+    pub fn path<P: Into<String>>(self, path: P) -> SetCookie {
+        SetCookie {
+            path: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Sets the `Domain` attribute.
+    pub fn domain<D: Into<String>>(self, domain: D) -> SetCookie {
+        SetCookie {
+            domain: Some(domain.into()),
+            ..self
+        }
+    }
+
+    /// Sets whether the `Secure` attribute is included, restricting the cookie to secure
+    /// connections.
+    pub fn secure(self, secure: bool) -> SetCookie {
+        SetCookie { secure, ..self }
+    }
+
+    /// Sets whether the `HttpOnly` attribute is included, hiding the cookie from scripts.
+    pub fn http_only(self, http_only: bool) -> SetCookie {
+        SetCookie { http_only, ..self }
+    }
+
+    /// Sets the `SameSite` attribute.
+    pub fn same_site(self, same_site: SameSite) -> SetCookie {
+        SetCookie {
+            same_site: Some(same_site),
+            ..self
+        }
+    }
+
+    /// The name of the cookie.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value of the cookie.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// The error returned by `SetCookie::new` when the name or value of a cookie contains characters
+/// which RFC 6265 does not permit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvalidCookie {
+    /// The name, which is not a `token`.
+    Name(String),
+    /// The value, which contains characters other than `cookie-octet`s.
+    Value(String),
+}
+
+impl Display for InvalidCookie {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            InvalidCookie::Name(ref name) => write!(f, "invalid cookie name: {:?}", name),
+            InvalidCookie::Value(ref value) => write!(f, "invalid cookie value: {:?}", value),
+        }
+    }
+}
+
+impl Error for InvalidCookie {
+    fn description(&self) -> &str {
+        match *self {
+            InvalidCookie::Name(_) => "invalid cookie name",
+            InvalidCookie::Value(_) => "invalid cookie value",
+        }
+    }
+}
+
+/// Whether `name` is a `token` as defined by RFC 2616, section 2.2.
+fn is_token(name: &str) -> bool {
+    !name.is_empty()
+        && name.bytes().all(|b| match b {
+            b'(' | b')' | b'<' | b'>' | b'@' | b',' | b';' | b':' | b'\\' | b'"' | b'/' | b'['
+            | b']' | b'?' | b'=' | b'{' | b'}' => false,
+            0x21...0x7e => true,
+            _ => false,
+        })
+}
+
+/// Whether `value` is a `cookie-value` as defined by RFC 6265, section 4.1.1.
+fn is_cookie_value(value: &str) -> bool {
+    let octets = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    };
+
+    octets.bytes().all(|b| match b {
+        b'"' | b',' | b';' | b'\\' => false,
+        0x21...0x7e => true,
+        _ => false,
+    })
+}
+
+impl Display for SetCookie {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+
+        if self.secure {
+            f.write_str("; Secure")?;
+        }
+
+        if self.http_only {
+            f.write_str("; HttpOnly")?;
+        }
+
+        match self.same_site {
+            Some(SameSite::Strict) => f.write_str("; SameSite=Strict")?,
+            Some(SameSite::Lax) => f.write_str("; SameSite=Lax")?,
+            None => (),
+        }
+
+        if let Some(ref domain) = self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+
+        if let Some(ref path) = self.path {
+            write!(f, "; Path={}", path)?;
+        }
+
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", format_http_date(expires))?;
+        }
+
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Adds a `Set-Cookie` header to the `Response`. Any `Set-Cookie` headers which are already
+/// present (e.g. from the session middleware) are retained.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::SET_COOKIE;
+/// # use gotham::helpers::http::cookies::{set_cookie, SetCookie};
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let mut res = create_response(&state, StatusCode::OK, None);
+///     set_cookie(&mut res, &SetCookie::new("theme", "dark").unwrap().path("/"));
+///     set_cookie(&mut res, &SetCookie::new("lang", "en").unwrap().path("/"));
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     let cookies = response.headers().get_all(SET_COOKIE).iter().collect::<Vec<_>>();
+/// #     assert_eq!(cookies, vec!["theme=dark; Path=/", "lang=en; Path=/"]);
+/// # }
+/// ```
+pub fn set_cookie<B>(response: &mut Response<B>, cookie: &SetCookie) {
+    match HeaderValue::from_str(&cookie.to_string()) {
+        Ok(value) => {
+            response.headers_mut().append(SET_COOKIE, value);
+        }
+        Err(_) => error!(
+            "[ERROR][Cookie `{}` contains characters which are invalid in a header]",
+            cookie.name
+        ),
+    }
+}
+
+/// Adds a `Set-Cookie` header to the `Response` which instructs the client to discard the cookie
+/// immediately, by setting an `Expires` in the past and a `Max-Age` of zero.
+///
+/// The `Path` and `Domain` of `cookie` must match those used when the cookie was set, or the
+/// client will not discard it.
+pub fn remove_cookie<B>(response: &mut Response<B>, cookie: SetCookie) {
+    let cookie = cookie.expires(UNIX_EPOCH).max_age(Duration::from_secs(0));
+    set_cookie(response, &cookie)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::Body;
+
+    #[test]
+    fn attributes_are_formatted_per_rfc6265() {
+        let cookie = SetCookie::new("SID", "31d4d96e407aad42")
+            .unwrap()
+            .path("/")
+            .domain("example.com")
+            .secure(true)
+            .http_only(true)
+            .same_site(SameSite::Strict)
+            .expires(UNIX_EPOCH + Duration::from_secs(784_111_777))
+            .max_age(Duration::from_secs(86_400));
+
+        assert_eq!(
+            cookie.to_string(),
+            "SID=31d4d96e407aad42; Secure; HttpOnly; SameSite=Strict; Domain=example.com; \
+             Path=/; Expires=Sun, 06 Nov 1994 08:49:37 GMT; Max-Age=86400"
+        );
+
+        assert_eq!(
+            SetCookie::new("lang", "en-US").unwrap().to_string(),
+            "lang=en-US"
+        );
+    }
+
+    #[test]
+    fn multiple_cookies_survive() {
+        let mut res = Response::new(Body::empty());
+        res.headers_mut()
+            .append(SET_COOKIE, "_gotham_session=abc; Path=/".parse().unwrap());
+
+        set_cookie(&mut res, &SetCookie::new("theme", "dark").unwrap());
+        set_cookie(&mut res, &SetCookie::new("lang", "en").unwrap());
+        remove_cookie(&mut res, SetCookie::new("stale", "").unwrap().path("/app"));
+
+        let cookies = res.headers().get_all(SET_COOKIE).iter().collect::<Vec<_>>();
+        assert_eq!(
+            cookies,
+            vec![
+                "_gotham_session=abc; Path=/",
+                "theme=dark",
+                "lang=en",
+                "stale=; Path=/app; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Max-Age=0",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_names_and_values_are_rejected() {
+        assert_eq!(
+            SetCookie::new("a; Domain=evil", "x").unwrap_err(),
+            InvalidCookie::Name("a; Domain=evil".to_owned())
+        );
+        assert_eq!(
+            SetCookie::new("a", "b; Domain=evil").unwrap_err(),
+            InvalidCookie::Value("b; Domain=evil".to_owned())
+        );
+        assert!(SetCookie::new("", "x").is_err());
+        assert!(SetCookie::new("bad", "line\nbreak").is_err());
+        assert!(SetCookie::new("bad", "a b").is_err());
+
+        assert!(SetCookie::new("quoted", "\"abc\"").is_ok());
+        assert!(SetCookie::new("token", "YWJj-_=.~").is_ok());
+    }
+
+    #[test]
+    fn invalid_cookie_is_not_set() {
+        let mut res = Response::new(Body::empty());
+        set_cookie(
+            &mut res,
+            &SetCookie::new("bad", "x").unwrap().path("/line\nbreak"),
+        );
+        assert!(res.headers().get(SET_COOKIE).is_none());
+    }
+}
@@ -0,0 +1,245 @@
+//! Helpers for parsing and formatting HTTP dates, as described by
+//! [RFC 7231, section 7.1.1.1][rfc].
+//!
+//! [rfc]: https://tools.ietf.org/html/rfc7231#section-7.1.1.1
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const LONG_DAYS: [&str; 7] = [
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Formats the time as an IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`, which is the
+/// format that must be used when sending an HTTP date. Fractions of a second are discarded, and
+/// times before the Unix epoch are formatted as the epoch.
+pub fn format_http_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let days = secs / 86_400;
+    let secs_of_day = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Parses an HTTP date in any of the three formats which a recipient must accept:
+///
+/// ```plain
+/// Sun, 06 Nov 1994 08:49:37 GMT    ; IMF-fixdate
+/// Sunday, 06-Nov-94 08:49:37 GMT   ; obsolete RFC 850 format
+/// Sun Nov  6 08:49:37 1994         ; ANSI C's asctime() format
+/// ```
+///
+/// A two digit year in the RFC 850 format is taken to be in the range 1970 to 2069. The day of
+/// the week is checked for the correct name, but not against the date. `None` is returned for
+/// a malformed date or one before the Unix epoch.
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+
+    let (year, month, day, time) = if let Some(comma) = value.find(',') {
+        let (weekday, rest) = (&value[..comma], value[comma + 1..].trim_left());
+
+        if DAYS.contains(&weekday) {
+            // IMF-fixdate
+            let parts: Vec<&str> = rest.split(' ').collect();
+            match parts.as_slice() {
+                [day, month, year, time, "GMT"] if day.len() == 2 && year.len() == 4 => {
+                    (year.parse().ok()?, *month, day.parse().ok()?, *time)
+                }
+                _ => return None,
+            }
+        } else if LONG_DAYS.contains(&weekday) {
+            // RFC 850
+            let parts: Vec<&str> = rest.split(' ').collect();
+            match parts.as_slice() {
+                [date, time, "GMT"] => {
+                    let date: Vec<&str> = date.split('-').collect();
+                    match date.as_slice() {
+                        [day, month, year] if day.len() == 2 && year.len() == 2 => {
+                            let year: u64 = year.parse().ok()?;
+                            let year = if year < 70 { 2000 + year } else { 1900 + year };
+                            (year, *month, day.parse().ok()?, *time)
+                        }
+                        _ => return None,
+                    }
+                }
+                _ => return None,
+            }
+        } else {
+            return None;
+        }
+    } else {
+        // asctime, where a single digit day is padded with a space.
+        let parts: Vec<&str> = value.split(' ').filter(|s| !s.is_empty()).collect();
+        match parts.as_slice() {
+            [weekday, month, day, time, year] if DAYS.contains(weekday) && year.len() == 4 => {
+                (year.parse().ok()?, *month, day.parse().ok()?, *time)
+            }
+            _ => return None,
+        }
+    };
+
+    let month = MONTHS.iter().position(|m| *m == month)? as u64 + 1;
+    let secs_of_day = parse_time(time)?;
+
+    if year < 1970 || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let secs = days_from_civil(year, month, day) * 86_400 + secs_of_day;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Truncates the time to a whole number of seconds since the Unix epoch, which is the precision
+/// of an HTTP date.
+pub(crate) fn whole_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Parses `hh:mm:ss` into seconds since midnight.
+fn parse_time(time: &str) -> Option<u64> {
+    let parts: Vec<&str> = time.split(':').collect();
+
+    match parts.as_slice() {
+        [h, m, s] if h.len() == 2 && m.len() == 2 && s.len() == 2 => {
+            let (h, m, s): (u64, u64, u64) = (h.parse().ok()?, m.parse().ok()?, s.parse().ok()?);
+
+            // A leap second is permitted by the grammar, and treated as the following second.
+            if h > 23 || m > 59 || s > 60 {
+                return None;
+            }
+
+            Some(h * 3600 + m * 60 + s)
+        }
+        _ => None,
+    }
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Converts days since the epoch into a civil date, per Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+/// Converts a civil date into days since the epoch, per Howard Hinnant's `days_from_civil`.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn http_dates() {
+        assert_eq!(
+            format_http_date(UNIX_EPOCH),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+        assert_eq!(
+            format_http_date(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "Tue, 29 Feb 2000 00:00:00 GMT"
+        );
+        assert_eq!(
+            format_http_date(UNIX_EPOCH + Duration::from_millis(1_546_300_799_999)),
+            "Mon, 31 Dec 2018 23:59:59 GMT"
+        );
+    }
+
+    #[test]
+    fn parse_all_formats() {
+        let expected = Some(UNIX_EPOCH + Duration::from_secs(784_111_777));
+
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), expected);
+        assert_eq!(parse_http_date("Sun Nov 06 08:49:37 1994"), expected);
+
+        assert_eq!(
+            parse_http_date("Wednesday, 01-Jan-20 00:00:00 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(1_577_836_800))
+        );
+        assert_eq!(
+            parse_http_date("Tue, 29 Feb 2000 00:00:00 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(951_782_400))
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for secs in vec![0, 68_169_599, 951_868_799, 1_546_300_799, 4_102_444_800] {
+            let time = UNIX_EPOCH + Duration::from_secs(secs);
+            assert_eq!(parse_http_date(&format_http_date(time)), Some(time));
+        }
+    }
+
+    #[test]
+    fn parse_invalid_dates() {
+        for value in vec![
+            "",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 6 Nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 94 08:49:37 GMT",
+            "Sun, 06 Nox 1994 08:49:37 GMT",
+            "Sun, 30 Feb 2000 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:49:37 GMT",
+            "Sun, 06 Nov 1994 8:49:37 GMT",
+            "Sunday, 06 Nov 1994 08:49:37 GMT",
+            "Sun, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 94",
+            "Thu, 01 Jan 1969 23:59:59 GMT",
+            "1994-11-06T08:49:37Z",
+        ] {
+            assert_eq!(parse_http_date(value), None, "parsing {:?}", value);
+        }
+    }
+}
@@ -6,3 +6,12 @@ pub const X_REQUEST_ID: &'static str = "x-request-id";
 
 /// Marks the execution time of a Gotham request in microseconds.
 pub const X_RUNTIME_MICROSECONDS: &'static str = "x-runtime-microseconds";
+
+/// Identifies the protocol used by the client to connect to a proxy, e.g. `https`.
+pub const X_FORWARDED_PROTO: &'static str = "x-forwarded-proto";
+
+/// Identifies the host requested by the client from a proxy.
+pub const X_FORWARDED_HOST: &'static str = "x-forwarded-host";
+
+/// Identifies the port used by the client to connect to a proxy.
+pub const X_FORWARDED_PORT: &'static str = "x-forwarded-port";
@@ -1,6 +1,11 @@
 //! Helpers for HTTP request handling and response generation
 
+pub mod accept;
+pub mod conditional;
+pub mod cookies;
+pub mod date;
 pub mod header;
+pub mod range;
 pub mod request;
 pub mod response;
 
@@ -11,6 +16,9 @@ use url::percent_encoding::percent_decode;
 #[derive(Clone, PartialEq, Debug)]
 pub struct PercentDecoded {
     val: String,
+    // The form which the static segments of routes are compared against. This is the segment as
+    // received, unless the `Router` is set to match static segments after decoding.
+    static_form: String,
 }
 
 impl PercentDecoded {
@@ -26,6 +34,7 @@ impl PercentDecoded {
                 trace!(" percent_decode: {}, src: {}", pd, raw);
                 Some(PercentDecoded {
                     val: pd.into_owned(),
+                    static_form: raw.to_owned(),
                 })
             }
             Err(_) => {
@@ -34,6 +43,12 @@ impl PercentDecoded {
             }
         }
     }
+
+    /// The form of the segment which the static segments of routes are compared against. See
+    /// `StaticSegments`.
+    pub(crate) fn static_form(&self) -> &str {
+        &self.static_form
+    }
 }
 
 impl AsRef<str> for PercentDecoded {
@@ -59,7 +74,7 @@ fn form_url_decode(raw: &str) -> Result<String, std::str::Utf8Error> {
 
 /// Represents data that has been successfully decoded from a form-urlencoded source and is
 /// valid UTF-8
-#[derive(PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct FormUrlDecoded {
     val: String,
 }
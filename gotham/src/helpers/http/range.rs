@@ -0,0 +1,868 @@
+//! Defines helpers for answering `Range` requests, as described by [RFC 7233][rfc].
+//!
+//! [rfc]: https://tools.ietf.org/html/rfc7233
+
+use std::cmp;
+use std::collections::VecDeque;
+use std::io::{self, SeekFrom};
+use std::path::Path;
+
+use futures::{future, stream, Future, Stream};
+use hyper::header::{HeaderMap, HeaderValue, ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+use hyper::{Body, Chunk, Method, Response, StatusCode};
+use mime::Mime;
+use tokio;
+use uuid::Uuid;
+
+use helpers::http::response::response;
+use state::{request_id, FromState, State};
+
+/// A range of bytes within a representation, with both `start` and `end` inclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+impl ByteRange {
+    /// The offset of the first byte in the range.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// The offset of the last byte in the range.
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// The value of the `Content-Range` header for this range of a representation which is
+    /// `length` bytes long.
+    pub fn content_range(&self, length: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, length)
+    }
+}
+
+/// The response to send when a `Range` header lists more ranges than `RangeOptions::max_ranges`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExcessRanges {
+    /// Respond with `416 Range Not Satisfiable`.
+    NotSatisfiable,
+    /// Ignore the `Range` header, and respond with the full representation.
+    FullContent,
+}
+
+/// Options which control how `select_ranges` and `range_response` answer a `Range` header.
+#[derive(Clone, Debug)]
+pub struct RangeOptions {
+    max_ranges: usize,
+    excess: ExcessRanges,
+}
+
+impl RangeOptions {
+    /// Creates `RangeOptions` which accept up to 16 ranges, and respond with `416 Range Not
+    /// Satisfiable` when more are requested.
+    pub fn new() -> RangeOptions {
+        RangeOptions {
+            max_ranges: 16,
+            excess: ExcessRanges::NotSatisfiable,
+        }
+    }
+
+    /// Sets the maximum number of ranges which may be listed in a single `Range` header. This
+    /// prevents a client from requesting many small or overlapping ranges in order to amplify the
+    /// size of the response.
+    pub fn max_ranges(self, max_ranges: usize) -> RangeOptions {
+        RangeOptions { max_ranges, ..self }
+    }
+
+    /// Sets the response to send when more than `max_ranges` ranges are requested.
+    pub fn on_excess(self, excess: ExcessRanges) -> RangeOptions {
+        RangeOptions { excess, ..self }
+    }
+}
+
+impl Default for RangeOptions {
+    fn default() -> RangeOptions {
+        RangeOptions::new()
+    }
+}
+
+/// The outcome of evaluating a `Range` header against a representation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RangeSelection {
+    /// The full representation should be sent with `200 OK`, either because no valid `Range`
+    /// header was provided, or because too many ranges were requested.
+    Full,
+    /// The listed ranges should be sent with `206 Partial Content`. Overlapping and adjacent
+    /// ranges have been merged, and the ranges are in ascending order.
+    Partial(Vec<ByteRange>),
+    /// None of the requested ranges overlap the representation, so `416 Range Not Satisfiable`
+    /// should be sent.
+    NotSatisfiable,
+}
+
+/// The first and last byte positions of a single range, before they are resolved against the
+/// length of the representation.
+enum RangeSpec {
+    FromTo(u64, u64),
+    From(u64),
+    Suffix(u64),
+}
+
+/// Evaluates the value of a `Range` header against a representation which is `length` bytes
+/// long.
+///
+/// A header which is not a syntactically valid list of byte ranges is ignored, as required by
+/// RFC 7233. Ranges which begin beyond the end of the representation are discarded, and the
+/// remainder are merged where they overlap or are adjacent.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// #
+/// # use gotham::helpers::http::range::{select_ranges, RangeOptions, RangeSelection};
+/// #
+/// # fn main() {
+/// let options = RangeOptions::new();
+///
+/// match select_ranges(Some("bytes=0-99,50-149,-10"), 1000, &options) {
+///     RangeSelection::Partial(ranges) => {
+///         let ranges: Vec<_> = ranges.iter().map(|r| (r.start(), r.end())).collect();
+///         assert_eq!(ranges, vec![(0, 149), (990, 999)]);
+///     }
+///     _ => panic!("expected partial content"),
+/// }
+///
+/// assert_eq!(
+///     select_ranges(Some("bytes=1000-"), 1000, &options),
+///     RangeSelection::NotSatisfiable
+/// );
+/// assert_eq!(select_ranges(Some("lines=1-2"), 1000, &options), RangeSelection::Full);
+/// # }
+/// ```
+pub fn select_ranges(range: Option<&str>, length: u64, options: &RangeOptions) -> RangeSelection {
+    let specs = match range.and_then(parse_range) {
+        Some(specs) => specs,
+        None => return RangeSelection::Full,
+    };
+
+    if specs.len() > options.max_ranges {
+        return match options.excess {
+            ExcessRanges::NotSatisfiable => RangeSelection::NotSatisfiable,
+            ExcessRanges::FullContent => RangeSelection::Full,
+        };
+    }
+
+    let mut ranges: Vec<ByteRange> = specs
+        .into_iter()
+        .filter_map(|spec| resolve(spec, length))
+        .collect();
+
+    if ranges.is_empty() {
+        return RangeSelection::NotSatisfiable;
+    }
+
+    ranges.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        if let Some(last) = merged.last_mut() {
+            if range.start <= last.end + 1 {
+                last.end = cmp::max(last.end, range.end);
+                continue;
+            }
+        }
+
+        merged.push(range);
+    }
+
+    RangeSelection::Partial(merged)
+}
+
+/// The size of the chunks in which a `RangeSource` is read into the body of a response.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A representation which `range_response` reads the requested ranges from as the body of the
+/// response is sent, so that it needn't be held in memory.
+///
+/// This is implemented for in-memory data, and for files via `FileSource`.
+pub trait RangeSource: Send + Sized + 'static {
+    /// The length of the representation, in bytes.
+    fn length(&self) -> u64;
+
+    /// Reads at least one and at most `max` bytes beginning at `offset`, which is always less
+    /// than `length`.
+    fn read_at(
+        self,
+        offset: u64,
+        max: usize,
+    ) -> Box<Future<Item = (Self, Chunk), Error = io::Error> + Send>;
+}
+
+impl RangeSource for Vec<u8> {
+    fn length(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read_at(
+        self,
+        offset: u64,
+        max: usize,
+    ) -> Box<Future<Item = (Self, Chunk), Error = io::Error> + Send> {
+        let start = offset as usize;
+        let end = cmp::min(start + max, self.len());
+        let chunk = Chunk::from(self[start..end].to_vec());
+        Box::new(future::ok((self, chunk)))
+    }
+}
+
+impl RangeSource for &'static [u8] {
+    fn length(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read_at(
+        self,
+        offset: u64,
+        max: usize,
+    ) -> Box<Future<Item = (Self, Chunk), Error = io::Error> + Send> {
+        let start = offset as usize;
+        let end = cmp::min(start + max, self.len());
+        Box::new(future::ok((self, Chunk::from(&self[start..end]))))
+    }
+}
+
+/// A `RangeSource` which reads from a file via `tokio::fs`, seeking to each range in turn.
+pub struct FileSource {
+    file: tokio::fs::File,
+    length: u64,
+    position: u64,
+}
+
+impl FileSource {
+    /// Creates a `FileSource` which reads the first `length` bytes of `file`. The file must be
+    /// positioned at its start.
+    pub fn new(file: tokio::fs::File, length: u64) -> FileSource {
+        FileSource {
+            file,
+            length,
+            position: 0,
+        }
+    }
+
+    /// Opens the file at `path`, taking its length from the file's metadata.
+    pub fn open<P>(path: P) -> Box<Future<Item = FileSource, Error = io::Error> + Send>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let f = tokio::fs::File::open(path)
+            .and_then(|file| file.metadata())
+            .map(|(file, metadata)| FileSource::new(file, metadata.len()));
+
+        Box::new(f)
+    }
+}
+
+impl RangeSource for FileSource {
+    fn length(&self) -> u64 {
+        self.length
+    }
+
+    fn read_at(
+        self,
+        offset: u64,
+        max: usize,
+    ) -> Box<Future<Item = (Self, Chunk), Error = io::Error> + Send> {
+        let FileSource {
+            file,
+            length,
+            position,
+        } = self;
+
+        let seek = if position == offset {
+            future::Either::A(future::ok(file))
+        } else {
+            future::Either::B(file.seek(SeekFrom::Start(offset)).map(|(file, _)| file))
+        };
+
+        let f = seek
+            .and_then(move |file| tokio::io::read(file, vec![0; max]))
+            .and_then(move |(file, mut buf, read)| {
+                if read == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "file is shorter than its length",
+                    ));
+                }
+
+                buf.truncate(read);
+                let source = FileSource {
+                    file,
+                    length,
+                    position: offset + read as u64,
+                };
+                Ok((source, Chunk::from(buf)))
+            });
+
+        Box::new(f)
+    }
+}
+
+/// A part of the body of a range response, which is either framing or a range of the source.
+enum Part {
+    Bytes(Vec<u8>),
+    Range(ByteRange),
+}
+
+impl Part {
+    fn len(&self) -> u64 {
+        match *self {
+            Part::Bytes(ref bytes) => bytes.len() as u64,
+            Part::Range(range) => range.end - range.start + 1,
+        }
+    }
+}
+
+/// Creates a `Response` holding the parts of `source` requested by the `Range` header of a `GET`
+/// request, or all of `source` otherwise.
+///
+/// A single range is sent as the body of a `206 Partial Content` response, with a
+/// `Content-Range` header. When more than one range remains after merging, the body is a
+/// `multipart/byteranges` document with a generated boundary, in which each part has its own
+/// `Content-Type` and `Content-Range` headers. An `Accept-Ranges: bytes` header is always added.
+///
+/// The body is a stream which reads each range from `source` as it is sent, so a `FileSource`
+/// is never read into memory in full.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::{CONTENT_RANGE, RANGE};
+/// # use gotham::helpers::http::range::{range_response, RangeOptions};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let data = b"0123456789".to_vec();
+///     let res = range_response(&state, data, mime::TEXT_PLAIN, &RangeOptions::new());
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://localhost/")
+/// #       .with_header(RANGE, "bytes=2-4".parse().unwrap())
+/// #       .perform()
+/// #       .unwrap();
+/// #
+/// #   assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+/// #   assert_eq!(response.headers().get(CONTENT_RANGE).unwrap(), "bytes 2-4/10");
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "234");
+/// # }
+/// ```
+pub fn range_response<S>(
+    state: &State,
+    source: S,
+    mime: Mime,
+    options: &RangeOptions,
+) -> Response<Body>
+where
+    S: RangeSource,
+{
+    let length = source.length();
+
+    let selection = if *Method::borrow_from(state) == Method::GET {
+        let range = HeaderMap::try_borrow_from(state)
+            .and_then(|headers| headers.get(RANGE))
+            .and_then(|range| range.to_str().ok());
+
+        select_ranges(range, length, options)
+    } else {
+        RangeSelection::Full
+    };
+
+    let builder = response(state).header(ACCEPT_RANGES, "bytes");
+
+    let (builder, parts) = match selection {
+        RangeSelection::Full => {
+            let parts = if length == 0 {
+                vec![]
+            } else {
+                vec![Part::Range(ByteRange {
+                    start: 0,
+                    end: length - 1,
+                })]
+            };
+
+            (builder, Some((parts, mime)))
+        }
+        RangeSelection::NotSatisfiable => {
+            debug!("[{}] unsatisfiable range request", request_id(state));
+            let builder = builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(CONTENT_RANGE, format!("bytes */{}", length).as_str());
+
+            (builder, None)
+        }
+        RangeSelection::Partial(ref ranges) if ranges.len() == 1 => {
+            let range = ranges[0];
+            let builder = builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(CONTENT_RANGE, range.content_range(length).as_str());
+
+            (builder, Some((vec![Part::Range(range)], mime)))
+        }
+        RangeSelection::Partial(ranges) => {
+            let boundary = Uuid::new_v4().simple().to_string();
+            let multipart = format!("multipart/byteranges; boundary={}", boundary)
+                .parse()
+                .expect("multipart/byteranges with a hexadecimal boundary");
+
+            let parts = byteranges(length, &ranges, &mime, &boundary);
+            (
+                builder.status(StatusCode::PARTIAL_CONTENT),
+                Some((parts, multipart)),
+            )
+        }
+    };
+
+    let (builder, content_length) = match parts {
+        Some((parts, mime)) => {
+            let content_length = parts.iter().map(Part::len).sum::<u64>();
+            let body = Body::wrap_stream(part_stream(source, parts));
+            (builder.body(body, mime), Some(content_length))
+        }
+        None => (builder, None),
+    };
+
+    let mut res = builder
+        .build()
+        .expect("Response built from a valid Content-Range");
+
+    // The length of the stream is known in advance, so it's sent rather than chunked encoding.
+    if let Some(content_length) = content_length {
+        res.headers_mut()
+            .insert(CONTENT_LENGTH, HeaderValue::from(content_length));
+    }
+
+    res
+}
+
+/// Parses the value of a `Range` header, returning `None` when it is not a valid list of byte
+/// ranges.
+fn parse_range(range: &str) -> Option<Vec<RangeSpec>> {
+    let mut parts = range.splitn(2, '=');
+    let unit = parts.next()?.trim();
+    let ranges = parts.next()?;
+
+    if !unit.eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+
+    let mut specs = Vec::new();
+
+    // Empty list elements are permitted, so "bytes=0-1,,2-3" is valid.
+    for spec in ranges.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let mut bounds = spec.splitn(2, '-');
+        let first = bounds.next()?.trim();
+        let last = bounds.next()?.trim();
+
+        let spec = match (first.is_empty(), last.is_empty()) {
+            (true, true) => return None,
+            (true, false) => RangeSpec::Suffix(parse_position(last)?),
+            (false, true) => RangeSpec::From(parse_position(first)?),
+            (false, false) => {
+                let (first, last) = (parse_position(first)?, parse_position(last)?);
+                if last < first {
+                    return None;
+                }
+                RangeSpec::FromTo(first, last)
+            }
+        };
+
+        specs.push(spec);
+    }
+
+    if specs.is_empty() {
+        None
+    } else {
+        Some(specs)
+    }
+}
+
+fn parse_position(position: &str) -> Option<u64> {
+    if position.bytes().all(|b| b.is_ascii_digit()) {
+        position.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Resolves a range against the length of the representation, returning `None` when the range
+/// does not overlap it.
+fn resolve(spec: RangeSpec, length: u64) -> Option<ByteRange> {
+    if length == 0 {
+        return None;
+    }
+
+    let (start, end) = match spec {
+        RangeSpec::FromTo(first, last) => (first, cmp::min(last, length - 1)),
+        RangeSpec::From(first) => (first, length - 1),
+        RangeSpec::Suffix(0) => return None,
+        RangeSpec::Suffix(suffix) => (length.saturating_sub(suffix), length - 1),
+    };
+
+    if start >= length {
+        None
+    } else {
+        Some(ByteRange { start, end })
+    }
+}
+
+/// Lists the parts of a `multipart/byteranges` body holding each of `ranges` of a representation
+/// which is `length` bytes long.
+fn byteranges(length: u64, ranges: &[ByteRange], mime: &Mime, boundary: &str) -> Vec<Part> {
+    let mut parts = Vec::with_capacity(ranges.len() * 3 + 1);
+
+    for range in ranges {
+        let head = format!(
+            "--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+            boundary,
+            mime,
+            range.content_range(length)
+        );
+
+        parts.push(Part::Bytes(head.into_bytes()));
+        parts.push(Part::Range(*range));
+        parts.push(Part::Bytes(b"\r\n".to_vec()));
+    }
+
+    parts.push(Part::Bytes(format!("--{}--\r\n", boundary).into_bytes()));
+    parts
+}
+
+/// Streams each of `parts`, reading ranges from `source` in chunks of at most `CHUNK_SIZE` bytes.
+fn part_stream<S>(
+    source: S,
+    parts: Vec<Part>,
+) -> Box<Stream<Item = Chunk, Error = io::Error> + Send>
+where
+    S: RangeSource,
+{
+    let parts: VecDeque<Part> = parts.into();
+
+    let stream = stream::unfold((source, parts), |(source, mut parts)| {
+        let range = match parts.pop_front()? {
+            Part::Bytes(bytes) => {
+                return Some(future::Either::A(future::ok((
+                    Chunk::from(bytes),
+                    (source, parts),
+                ))));
+            }
+            Part::Range(range) => range,
+        };
+
+        let max = cmp::min(range.end - range.start + 1, CHUNK_SIZE as u64) as usize;
+
+        let f = source
+            .read_at(range.start, max)
+            .and_then(move |(source, chunk)| {
+                let read = chunk.len() as u64;
+                if read == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "range source is shorter than its length",
+                    ));
+                }
+
+                if range.start + read <= range.end {
+                    parts.push_front(Part::Range(ByteRange {
+                        start: range.start + read,
+                        end: range.end,
+                    }));
+                }
+
+                Ok((chunk, (source, parts)))
+            });
+
+        Some(future::Either::B(f))
+    });
+
+    Box::new(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use hyper::header::CONTENT_TYPE;
+    use mime;
+    use tokio::runtime::Runtime;
+
+    use test::TestServer;
+
+    const FIXTURE: &'static [u8] = include_bytes!("fixtures/range.txt");
+
+    fn collect<S: RangeSource>(source: S, parts: Vec<Part>) -> Vec<u8> {
+        part_stream(source, parts)
+            .concat2()
+            .wait()
+            .unwrap()
+            .to_vec()
+    }
+
+    fn partial(range: &str, length: u64, options: &RangeOptions) -> Vec<(u64, u64)> {
+        match select_ranges(Some(range), length, options) {
+            RangeSelection::Partial(ranges) => ranges.iter().map(|r| (r.start, r.end)).collect(),
+            selection => panic!("{}: unexpected {:?}", range, selection),
+        }
+    }
+
+    #[test]
+    fn ranges_are_resolved_and_merged() {
+        let options = RangeOptions::new();
+
+        assert_eq!(partial("bytes=0-99", 1000, &options), vec![(0, 99)]);
+        assert_eq!(partial("bytes=900-", 1000, &options), vec![(900, 999)]);
+        assert_eq!(partial("bytes=-100", 1000, &options), vec![(900, 999)]);
+        assert_eq!(partial("bytes=-2000", 1000, &options), vec![(0, 999)]);
+        assert_eq!(partial("bytes=990-2000", 1000, &options), vec![(990, 999)]);
+        assert_eq!(
+            partial("bytes=200-299, 0-99", 1000, &options),
+            vec![(0, 99), (200, 299)]
+        );
+        assert_eq!(
+            partial("bytes=0-99,50-149,150-199,,-10", 1000, &options),
+            vec![(0, 199), (990, 999)]
+        );
+        assert_eq!(partial("Bytes=0-9,2000-3000", 1000, &options), vec![(0, 9)]);
+    }
+
+    #[test]
+    fn invalid_headers_are_ignored() {
+        let options = RangeOptions::new();
+
+        for range in vec![
+            "",
+            "bytes",
+            "bytes=",
+            "bytes=-",
+            "bytes=5-1",
+            "bytes=a-b",
+            "bytes=+1-2",
+            "bytes=0-1,x",
+            "items=0-1",
+        ] {
+            assert_eq!(
+                select_ranges(Some(range), 1000, &options),
+                RangeSelection::Full,
+                "{}",
+                range
+            );
+        }
+
+        assert_eq!(select_ranges(None, 1000, &options), RangeSelection::Full);
+    }
+
+    #[test]
+    fn unsatisfiable_ranges() {
+        let options = RangeOptions::new();
+
+        for range in vec!["bytes=1000-", "bytes=-0", "bytes=1000-1100,2000-"] {
+            assert_eq!(
+                select_ranges(Some(range), 1000, &options),
+                RangeSelection::NotSatisfiable,
+                "{}",
+                range
+            );
+        }
+
+        assert_eq!(
+            select_ranges(Some("bytes=0-"), 0, &options),
+            RangeSelection::NotSatisfiable
+        );
+    }
+
+    #[test]
+    fn excess_ranges() {
+        let range = Some("bytes=0-0,2-2,4-4");
+
+        let options = RangeOptions::new().max_ranges(2);
+        assert_eq!(
+            select_ranges(range, 1000, &options),
+            RangeSelection::NotSatisfiable
+        );
+
+        let options = options.on_excess(ExcessRanges::FullContent);
+        assert_eq!(select_ranges(range, 1000, &options), RangeSelection::Full);
+
+        let options = options.max_ranges(3);
+        assert_eq!(
+            partial(range.unwrap(), 1000, &options),
+            vec![(0, 0), (2, 2), (4, 4)]
+        );
+    }
+
+    #[test]
+    fn byteranges_framing() {
+        let ranges = vec![
+            ByteRange { start: 0, end: 9 },
+            ByteRange { start: 40, end: 52 },
+        ];
+
+        let parts = byteranges(FIXTURE.len() as u64, &ranges, &mime::TEXT_PLAIN, "BOUNDARY");
+        let body = collect(FIXTURE, parts);
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"--BOUNDARY\r\n");
+        expected.extend_from_slice(b"Content-Type: text/plain\r\n");
+        expected.extend_from_slice(
+            format!("Content-Range: bytes 0-9/{}\r\n", FIXTURE.len()).as_bytes(),
+        );
+        expected.extend_from_slice(b"\r\n");
+        expected.extend_from_slice(&FIXTURE[0..10]);
+        expected.extend_from_slice(b"\r\n--BOUNDARY\r\n");
+        expected.extend_from_slice(b"Content-Type: text/plain\r\n");
+        expected.extend_from_slice(
+            format!("Content-Range: bytes 40-52/{}\r\n", FIXTURE.len()).as_bytes(),
+        );
+        expected.extend_from_slice(b"\r\n");
+        expected.extend_from_slice(&FIXTURE[40..53]);
+        expected.extend_from_slice(b"\r\n--BOUNDARY--\r\n");
+
+        assert_eq!(body, expected);
+    }
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let options = RangeOptions::new().max_ranges(4);
+        let res = range_response(&state, FIXTURE.to_vec(), mime::TEXT_PLAIN, &options);
+        (state, res)
+    }
+
+    #[test]
+    fn multipart_response() {
+        let test_server = TestServer::new(|| Ok(handler)).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .with_header(RANGE, "bytes=40-52,0-4,5-9".parse().unwrap())
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers().get(ACCEPT_RANGES).unwrap(), "bytes");
+        assert!(response.headers().get(CONTENT_RANGE).is_none());
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned();
+
+        let prefix = "multipart/byteranges; boundary=";
+        assert!(content_type.starts_with(prefix), "{}", content_type);
+        let boundary = &content_type[prefix.len()..];
+
+        let ranges = vec![
+            ByteRange { start: 0, end: 9 },
+            ByteRange { start: 40, end: 52 },
+        ];
+        assert_eq!(
+            response.read_body().unwrap(),
+            collect(
+                FIXTURE,
+                byteranges(FIXTURE.len() as u64, &ranges, &mime::TEXT_PLAIN, boundary)
+            )
+        );
+    }
+
+    #[test]
+    fn single_range_and_unsatisfiable_responses() {
+        let test_server = TestServer::new(|| Ok(handler)).unwrap();
+        let length = FIXTURE.len();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .with_header(RANGE, "bytes=-5".parse().unwrap())
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers().get(CONTENT_RANGE).unwrap(),
+            format!("bytes {}-{}/{}", length - 5, length - 1, length).as_str()
+        );
+        assert_eq!(response.read_body().unwrap(), &FIXTURE[length - 5..]);
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .with_header(RANGE, format!("bytes={}-", length).parse().unwrap())
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            response.headers().get(CONTENT_RANGE).unwrap(),
+            format!("bytes */{}", length).as_str()
+        );
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .with_header(RANGE, "bytes=0-0,2-2,4-4,6-6,8-8".parse().unwrap())
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_body().unwrap(), FIXTURE);
+    }
+
+    #[test]
+    fn file_source_is_streamed_in_chunks() {
+        let data: Vec<u8> = (0..CHUNK_SIZE * 3).map(|i| (i % 251) as u8).collect();
+        let path = ::std::env::temp_dir().join(format!("gotham-range-{}", Uuid::new_v4().simple()));
+        fs::write(&path, &data).unwrap();
+
+        let ranges = vec![
+            ByteRange {
+                start: 10,
+                end: CHUNK_SIZE as u64 * 2 + 10,
+            },
+            ByteRange {
+                start: CHUNK_SIZE as u64 * 3 - 5,
+                end: CHUNK_SIZE as u64 * 3 - 1,
+            },
+        ];
+        let length = data.len() as u64;
+        let parts = byteranges(length, &ranges, &mime::TEXT_PLAIN, "BOUNDARY");
+        let expected = collect(data, parts);
+
+        let f = FileSource::open(path.clone()).and_then(move |source| {
+            assert_eq!(source.length(), length);
+            let parts = byteranges(length, &ranges, &mime::TEXT_PLAIN, "BOUNDARY");
+            part_stream(source, parts).concat2()
+        });
+        let body = Runtime::new().unwrap().block_on(f).unwrap();
+        assert_eq!(body.to_vec(), expected);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
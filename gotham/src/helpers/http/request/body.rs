@@ -0,0 +1,1030 @@
+//! Defines helper functions for consuming the request body
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use futures::{future, Async, Future, Poll, Stream};
+use hyper::header::{HeaderMap, CONTENT_LENGTH};
+use hyper::{Body, Chunk, StatusCode};
+use sha2::{Digest, Sha256};
+use tokio;
+use tokio::executor::{DefaultExecutor, Executor};
+use tokio::timer::{self, Delay};
+use uuid::Uuid;
+
+use handler::{HandlerError, IntoHandlerError};
+use state::connection::ConnectionInfo;
+use state::{request_id, FromState, State};
+
+/// The complete request body, stored in `State` by `buffer_body` so that it can be read any number
+/// of times.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BufferedBody {
+    bytes: Vec<u8>,
+}
+
+impl BufferedBody {
+    /// The bytes of the request body.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Describes a request body which has been saved to disk by `save_body_to_file`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileInfo {
+    path: PathBuf,
+    bytes: u64,
+    sha256: Option<String>,
+}
+
+impl FileInfo {
+    /// The path of the saved file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The number of bytes written to the file.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The SHA-256 digest of the file as lowercase hex, when the body was saved by
+    /// `save_body_to_file_with_sha256`.
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_ref().map(|digest| digest.as_str())
+    }
+}
+
+/// Reads the complete request body into memory, storing it in `State` as a `BufferedBody`.
+///
+/// Once buffered, the body can be read any number of times via `BufferedBody::borrow_from`, such as
+/// verifying a signature before the body is parsed. A `Body` containing the same bytes is also put
+/// back into `State`, so consumers which take the `Body` continue to work. When a `BufferedBody` is
+/// already present, the returned future completes immediately.
+///
+/// Failures are reported as a `HandlerError` with the status:
+///
+/// * `413 Payload Too Large` when the body exceeds `limit` bytes;
+/// * `408 Request Timeout` when the body isn't received within the body timeout of the
+///   `ReadTimeouts` of the application;
+/// * `400 Bad Request` when the body could not be read from the connection;
+/// * `500 Internal Server Error` when the `Body` has already been taken from `State`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate futures;
+/// # extern crate mime;
+/// #
+/// # use futures::Future;
+/// # use hyper::{Body, StatusCode};
+/// # use gotham::handler::HandlerFuture;
+/// # use gotham::helpers::http::request::body::{buffer_body, BufferedBody};
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> Box<HandlerFuture> {
+///     let f = buffer_body(state, 1024).map(|state| {
+///         let length = BufferedBody::borrow_from(&state).bytes().len();
+///         let body = format!("received {} bytes", length);
+///         let res = create_response(
+///             &state,
+///             StatusCode::OK,
+///             Some((body.into_bytes(), mime::TEXT_PLAIN)),
+///         );
+///         (state, res)
+///     });
+///
+///     Box::new(f)
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .post("http://localhost/", Body::from("data"), mime::TEXT_PLAIN)
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "received 4 bytes");
+/// # }
+/// ```
+pub fn buffer_body(
+    state: State,
+    limit: u64,
+) -> Box<Future<Item = State, Error = (State, HandlerError)> + Send> {
+    buffer_body_with(state, limit, None)
+}
+
+/// Reads the complete request body into memory in the same way as `buffer_body`, failing with
+/// `408 Request Timeout` if no data is received from the client for `idle` at any point.
+///
+/// The timeout restarts whenever a chunk of the body arrives, so a large body sent at a steady
+/// rate is not affected. This protects against a client which sends the body very slowly in order
+/// to hold the connection open, and is distinct from a deadline on the whole request such as that
+/// enforced by `TimeoutHandler`.
+pub fn buffer_body_with_idle_timeout(
+    state: State,
+    limit: u64,
+    idle: Duration,
+) -> Box<Future<Item = State, Error = (State, HandlerError)> + Send> {
+    buffer_body_with(state, limit, Some(idle))
+}
+
+fn buffer_body_with(
+    mut state: State,
+    limit: u64,
+    idle: Option<Duration>,
+) -> Box<Future<Item = State, Error = (State, HandlerError)> + Send> {
+    if state.has::<BufferedBody>() {
+        return Box::new(future::ok(state));
+    }
+
+    if let Some(length) = declared_length(&state) {
+        if length > limit {
+            debug!(
+                "[{}] declared body length {} exceeds limit of {} bytes",
+                request_id(&state),
+                length,
+                limit
+            );
+            return Box::new(future::err((state, too_large(limit))));
+        }
+    }
+
+    let body = match state.try_take::<Body>() {
+        Some(body) => body,
+        None => {
+            let err = io::Error::new(io::ErrorKind::Other, "request body already consumed");
+            return Box::new(future::err((state, err.into_handler_error())));
+        }
+    };
+
+    let f = Chunks::new(body, idle, body_deadline(&state))
+        .fold(Vec::new(), move |mut bytes, chunk| {
+            if (bytes.len() + chunk.len()) as u64 > limit {
+                return Err(BodyError::TooLarge);
+            }
+
+            bytes.extend_from_slice(&chunk);
+            Ok(bytes)
+        })
+        .then(move |result| match result {
+            Ok(bytes) => {
+                trace!("[{}] buffered {} byte body", request_id(&state), bytes.len());
+                state.put(Body::from(bytes.clone()));
+                state.put(BufferedBody { bytes });
+                future::ok(state)
+            }
+            Err(e) => {
+                let err = e.into_handler_error(&state, limit);
+                future::err((state, err))
+            }
+        });
+
+    Box::new(f)
+}
+
+/// Streams the request body to the file at `path`, without buffering the whole body in memory.
+///
+/// The body is written to a temporary file alongside `path`, which is renamed into place once the
+/// body has been completely received. The temporary file is removed if the upload fails, or if the
+/// returned future is dropped before completion (e.g. the client disconnected).
+///
+/// Disk access is performed via `tokio::fs`, which runs it on a blocking section of the Tokio
+/// thread pool rather than on the reactor. The future must therefore be run on the thread pool,
+/// as it is when served by `gotham::start` or the `TestServer`.
+///
+/// Failures are reported as a `HandlerError` with the status:
+///
+/// * `413 Payload Too Large` when the body exceeds `limit` bytes;
+/// * `408 Request Timeout` when the body isn't received within the body timeout of the
+///   `ReadTimeouts` of the application;
+/// * `400 Bad Request` when the body could not be read from the connection;
+/// * `500 Internal Server Error` when writing to disk fails, with the `io::Error` as the cause.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate futures;
+/// # extern crate mime;
+/// #
+/// # use futures::Future;
+/// # use hyper::{Body, StatusCode};
+/// # use gotham::handler::HandlerFuture;
+/// # use gotham::helpers::http::request::body::save_body_to_file;
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn upload(state: State) -> Box<HandlerFuture> {
+///     let path = std::env::temp_dir().join("gotham-upload-doctest.bin");
+///
+///     let f = save_body_to_file(state, path, 1024 * 1024).map(|(state, info)| {
+///         let body = format!("saved {} bytes", info.bytes());
+///         let res = create_response(
+///             &state,
+///             StatusCode::CREATED,
+///             Some((body.into_bytes(), mime::TEXT_PLAIN)),
+///         );
+///         (state, res)
+///     });
+///
+///     Box::new(f)
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(upload)).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .post("http://localhost/", Body::from("data"), mime::APPLICATION_OCTET_STREAM)
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::CREATED);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "saved 4 bytes");
+/// # }
+/// ```
+pub fn save_body_to_file<P>(
+    state: State,
+    path: P,
+    limit: u64,
+) -> Box<Future<Item = (State, FileInfo), Error = (State, HandlerError)> + Send>
+where
+    P: AsRef<Path>,
+{
+    save_body_to_file_with(state, path.as_ref(), limit, None, false)
+}
+
+/// Streams the request body to the file at `path` in the same way as `save_body_to_file`, also
+/// computing the SHA-256 digest of the body as it is received. The digest is available from
+/// `FileInfo::sha256`.
+pub fn save_body_to_file_with_sha256<P>(
+    state: State,
+    path: P,
+    limit: u64,
+) -> Box<Future<Item = (State, FileInfo), Error = (State, HandlerError)> + Send>
+where
+    P: AsRef<Path>,
+{
+    save_body_to_file_with(state, path.as_ref(), limit, None, true)
+}
+
+/// Streams the request body to the file at `path` in the same way as `save_body_to_file`, failing
+/// with `408 Request Timeout` if no data is received from the client for `idle` at any point. The
+/// temporary file is removed when the timeout passes.
+///
+/// See `buffer_body_with_idle_timeout` for the behaviour of the timeout.
+pub fn save_body_to_file_with_idle_timeout<P>(
+    state: State,
+    path: P,
+    limit: u64,
+    idle: Duration,
+) -> Box<Future<Item = (State, FileInfo), Error = (State, HandlerError)> + Send>
+where
+    P: AsRef<Path>,
+{
+    save_body_to_file_with(state, path.as_ref(), limit, Some(idle), false)
+}
+
+fn save_body_to_file_with(
+    mut state: State,
+    path: &Path,
+    limit: u64,
+    idle: Option<Duration>,
+    digest: bool,
+) -> Box<Future<Item = (State, FileInfo), Error = (State, HandlerError)> + Send> {
+    let target = path.to_path_buf();
+
+    if let Some(length) = declared_length(&state) {
+        if length > limit {
+            debug!(
+                "[{}] declared body length {} exceeds limit of {} bytes",
+                request_id(&state),
+                length,
+                limit
+            );
+            return Box::new(future::err((state, too_large(limit))));
+        }
+    }
+
+    let body = match state.try_take::<Body>() {
+        Some(body) => body,
+        None => {
+            let err = io::Error::new(io::ErrorKind::Other, "request body already consumed");
+            return Box::new(future::err((state, err.into_handler_error())));
+        }
+    };
+
+    let chunks = Chunks::new(body, idle, body_deadline(&state));
+
+    let f = PartialFile::create(&target, digest)
+        .map_err(BodyError::Io)
+        .and_then(move |partial| {
+            chunks.fold((partial, 0u64), move |(partial, written), chunk| {
+                let written = written + chunk.len() as u64;
+                if written > limit {
+                    return future::Either::A(future::err(BodyError::TooLarge));
+                }
+
+                let write = partial
+                    .write_all(chunk)
+                    .map(move |partial| (partial, written))
+                    .map_err(BodyError::Io);
+
+                future::Either::B(write)
+            })
+        })
+        .and_then(move |(partial, written)| {
+            partial
+                .persist(target.clone())
+                .map(move |sha256| FileInfo {
+                    path: target,
+                    bytes: written,
+                    sha256,
+                })
+                .map_err(BodyError::Io)
+        })
+        .then(move |result| match result {
+            Ok(info) => {
+                trace!(
+                    "[{}] saved {} bytes to {}",
+                    request_id(&state),
+                    info.bytes,
+                    info.path.display()
+                );
+                future::ok((state, info))
+            }
+            Err(e) => {
+                let err = e.into_handler_error(&state, limit);
+                future::err((state, err))
+            }
+        });
+
+    Box::new(f)
+}
+
+fn body_deadline(state: &State) -> Option<Instant> {
+    ConnectionInfo::try_borrow_from(state).and_then(|connection| connection.body_deadline())
+}
+
+fn declared_length(state: &State) -> Option<u64> {
+    HeaderMap::try_borrow_from(state)
+        .and_then(|headers| headers.get(CONTENT_LENGTH))
+        .and_then(|len| len.to_str().ok())
+        .and_then(|len| len.parse::<u64>().ok())
+}
+
+/// The chunks of a request body, failing with `BodyError::Idle` if the next chunk does not arrive
+/// within the idle timeout, or with `BodyError::Deadline` once the body deadline set by the
+/// `ReadTimeouts` of the application has passed.
+struct Chunks {
+    body: Body,
+    idle: Option<(Duration, Delay)>,
+    deadline: Option<Instant>,
+}
+
+impl Chunks {
+    fn new(body: Body, idle: Option<Duration>, deadline: Option<Instant>) -> Chunks {
+        Chunks {
+            body,
+            idle: idle.map(|idle| (idle, Delay::new(Instant::now() + idle))),
+            deadline,
+        }
+    }
+
+    fn body_error(&self, e: ::hyper::Error) -> BodyError {
+        // The body itself fails once the deadline has passed.
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => BodyError::Deadline,
+            _ => BodyError::Body(e),
+        }
+    }
+}
+
+impl Stream for Chunks {
+    type Item = Chunk;
+    type Error = BodyError;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, BodyError> {
+        let poll = match self.body.poll() {
+            Ok(poll) => poll,
+            Err(e) => return Err(self.body_error(e)),
+        };
+
+        if let Async::Ready(chunk) = poll {
+            if let Some((idle, ref mut delay)) = self.idle {
+                delay.reset(Instant::now() + idle);
+            }
+
+            return Ok(Async::Ready(chunk));
+        }
+
+        match self.idle {
+            Some((idle, ref mut delay)) => match delay.poll().map_err(BodyError::Timer)? {
+                Async::Ready(()) => Err(BodyError::Idle(idle)),
+                Async::NotReady => Ok(Async::NotReady),
+            },
+            None => Ok(Async::NotReady),
+        }
+    }
+}
+
+enum BodyError {
+    TooLarge,
+    Idle(Duration),
+    Deadline,
+    Body(::hyper::Error),
+    Io(io::Error),
+    Timer(timer::Error),
+}
+
+impl BodyError {
+    fn into_handler_error(self, state: &State, limit: u64) -> HandlerError {
+        match self {
+            BodyError::TooLarge => {
+                debug!(
+                    "[{}] body exceeded limit of {} bytes",
+                    request_id(state),
+                    limit
+                );
+                too_large(limit)
+            }
+            BodyError::Idle(idle) => {
+                debug!(
+                    "[{}] no body received for {}ms",
+                    request_id(state),
+                    idle.as_secs() * 1000 + u64::from(idle.subsec_nanos() / 1_000_000)
+                );
+                io::Error::new(io::ErrorKind::TimedOut, "timed out reading request body")
+                    .into_handler_error()
+                    .with_status(StatusCode::REQUEST_TIMEOUT)
+            }
+            BodyError::Deadline => {
+                debug!(
+                    "[{}] body not received within the body timeout",
+                    request_id(state)
+                );
+                io::Error::new(io::ErrorKind::TimedOut, "timed out reading request body")
+                    .into_handler_error()
+                    .with_status(StatusCode::REQUEST_TIMEOUT)
+            }
+            BodyError::Body(e) => {
+                debug!("[{}] failed to read body: {}", request_id(state), e);
+                e.into_handler_error().with_status(StatusCode::BAD_REQUEST)
+            }
+            BodyError::Io(e) => {
+                error!("[{}] failed to write body: {}", request_id(state), e);
+                e.into_handler_error()
+            }
+            BodyError::Timer(e) => {
+                error!(
+                    "[{}] timer failure while reading body: {}",
+                    request_id(state),
+                    e
+                );
+                e.into_handler_error()
+            }
+        }
+    }
+}
+
+fn too_large(limit: u64) -> HandlerError {
+    io::Error::new(
+        io::ErrorKind::Other,
+        format!("request body exceeds limit of {} bytes", limit),
+    ).into_handler_error()
+        .with_status(StatusCode::PAYLOAD_TOO_LARGE)
+}
+
+/// A temporary file which is removed when dropped, unless it has been persisted.
+struct PartialFile {
+    file: Option<tokio::fs::File>,
+    path: PathBuf,
+    digest: Option<Sha256>,
+}
+
+impl PartialFile {
+    fn create(
+        target: &Path,
+        digest: bool,
+    ) -> Box<Future<Item = PartialFile, Error = io::Error> + Send> {
+        let name = target
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "upload".to_owned());
+
+        let path = target.with_file_name(format!(".{}.{}.part", name, Uuid::new_v4().simple()));
+
+        let f = tokio::fs::File::create(path.clone()).map(move |file| PartialFile {
+            file: Some(file),
+            path,
+            digest: if digest { Some(Sha256::new()) } else { None },
+        });
+
+        Box::new(f)
+    }
+
+    fn write_all(
+        mut self,
+        chunk: Chunk,
+    ) -> Box<Future<Item = PartialFile, Error = io::Error> + Send> {
+        if let Some(ref mut digest) = self.digest {
+            digest.input(&chunk);
+        }
+
+        let write = match self.file.take() {
+            Some(file) => future::Either::A(tokio::io::write_all(file, chunk)),
+            None => {
+                let err = io::Error::new(io::ErrorKind::Other, "file already closed");
+                future::Either::B(future::err(err))
+            }
+        };
+
+        Box::new(write.map(move |(file, _)| {
+            self.file = Some(file);
+            self
+        }))
+    }
+
+    fn persist(
+        mut self,
+        target: PathBuf,
+    ) -> Box<Future<Item = Option<String>, Error = io::Error> + Send> {
+        let mut file = self.file.take();
+
+        // The file is closed once synced, before it is renamed into place.
+        let f = future::poll_fn(move || match file {
+            Some(ref mut file) => file.poll_sync_all(),
+            None => Ok(Async::Ready(())),
+        }).and_then(move |()| {
+            tokio::fs::rename(self.path.clone(), target).map(move |()| {
+                // Nothing remains at the temporary path, so there is nothing for `Drop` to clean up.
+                self.path = PathBuf::new();
+                self.digest.take().map(|digest| {
+                    digest
+                        .result()
+                        .iter()
+                        .map(|b| format!("{:02x}", b))
+                        .collect()
+                })
+            })
+        });
+
+        Box::new(f)
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        self.file.take();
+
+        if self.path != PathBuf::new() {
+            let path = ::std::mem::replace(&mut self.path, PathBuf::new());
+
+            // The file is removed on the thread pool when there is one, which is the case unless
+            // the upload future is dropped outside of the runtime.
+            let remove = tokio::fs::remove_file(path.clone()).map_err(|_| ());
+            if DefaultExecutor::current().spawn(Box::new(remove)).is_err() {
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::stream;
+    use hyper::Response;
+    use mime;
+
+    use extractor::QueryStringOptions;
+    use handler::HandlerFuture;
+    use helpers::http::request::query_string::split;
+    use helpers::http::response::create_response;
+    use test::TestServer;
+    use tokio::runtime::Runtime;
+
+    fn upload_dir() -> PathBuf {
+        let dir = ::std::env::temp_dir().join(format!("gotham-upload-{}", Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn server(target: PathBuf, limit: u64) -> TestServer {
+        TestServer::new(move || {
+            let target = target.clone();
+            Ok(move |state: State| -> Box<HandlerFuture> {
+                let f = save_body_to_file(state, target, limit).map(|(state, info)| {
+                    let body = info.bytes().to_string().into_bytes();
+                    let res: Response<Body> =
+                        create_response(&state, StatusCode::OK, Some((body, mime::TEXT_PLAIN)));
+                    (state, res)
+                });
+                Box::new(f)
+            })
+        }).unwrap()
+    }
+
+    fn chunked_body() -> Body {
+        let chunks: Vec<Result<Vec<u8>, io::Error>> = vec![
+            Ok(b"first chunk, ".to_vec()),
+            Ok(b"second chunk, ".to_vec()),
+            Ok(b"third chunk".to_vec()),
+        ];
+        Body::wrap_stream(stream::iter_result(chunks))
+    }
+
+    fn remaining_files(dir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect()
+    }
+
+    /// Temporary files are removed on the thread pool, so may outlive the response briefly.
+    fn removed_eventually(dir: &Path) -> bool {
+        for _ in 0..100 {
+            if remaining_files(dir).is_empty() {
+                return true;
+            }
+            ::std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn saves_multi_chunk_body() {
+        let dir = upload_dir();
+        let target = dir.join("upload.txt");
+        let test_server = server(target.clone(), 1024);
+
+        let response = test_server
+            .client()
+            .post("http://localhost/", chunked_body(), mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "38");
+        assert_eq!(
+            fs::read(&target).unwrap(),
+            b"first chunk, second chunk, third chunk".to_vec()
+        );
+        assert_eq!(remaining_files(&dir), vec![target]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saves_body_with_sha256() {
+        let dir = upload_dir();
+        let target = dir.join("upload.txt");
+
+        let test_server = TestServer::new(move || {
+            let target = target.clone();
+            Ok(move |state: State| -> Box<HandlerFuture> {
+                let f = save_body_to_file_with_sha256(state, target, 1024).map(|(state, info)| {
+                    let body = info.sha256().unwrap().to_owned().into_bytes();
+                    let res =
+                        create_response(&state, StatusCode::OK, Some((body, mime::TEXT_PLAIN)));
+                    (state, res)
+                });
+                Box::new(f)
+            })
+        }).unwrap();
+
+        let response = test_server
+            .client()
+            .post("http://localhost/", "abc", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_body_over_limit() {
+        let dir = upload_dir();
+        let test_server = server(dir.join("upload.txt"), 20);
+
+        let response = test_server
+            .client()
+            .post("http://localhost/", chunked_body(), mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(removed_eventually(&dir));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_declared_length_over_limit() {
+        let dir = upload_dir();
+        let test_server = server(dir.join("upload.txt"), 4);
+
+        let response = test_server
+            .client()
+            .post("http://localhost/", "too long", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(removed_eventually(&dir));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn disk_errors_are_server_errors() {
+        let dir = upload_dir();
+        let target = dir.join("missing").join("upload.txt");
+        let test_server = server(target, 1024);
+
+        let response = test_server
+            .client()
+            .post("http://localhost/", chunked_body(), mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Stands in for an HMAC, comparing a checksum of the body to the `x-signature` header.
+    fn verify_signature(
+        state: State,
+    ) -> Box<Future<Item = State, Error = (State, HandlerError)> + Send> {
+        let f = buffer_body(state, 1024).and_then(|state| {
+            let checksum = BufferedBody::borrow_from(&state)
+                .bytes()
+                .iter()
+                .fold(0u8, |sum, b| sum.wrapping_add(*b));
+
+            let valid = HeaderMap::borrow_from(&state)
+                .get("x-signature")
+                .map(|sig| sig == checksum.to_string().as_str())
+                .unwrap_or(false);
+
+            if valid {
+                future::ok(state)
+            } else {
+                let err = io::Error::new(io::ErrorKind::Other, "invalid signature")
+                    .into_handler_error()
+                    .with_status(StatusCode::UNAUTHORIZED);
+                future::err((state, err))
+            }
+        });
+
+        Box::new(f)
+    }
+
+    fn signed_form_handler(state: State) -> Box<HandlerFuture> {
+        let f = verify_signature(state)
+            .and_then(|state| buffer_body(state, 1024))
+            .map(|state| {
+                let body = {
+                    let form = ::std::str::from_utf8(BufferedBody::borrow_from(&state).bytes())
+                        .unwrap()
+                        .to_owned();
+                    let fields = split(Some(&form), QueryStringOptions::default());
+                    fields["name"][0].as_ref().to_owned()
+                };
+
+                let res = create_response(
+                    &state,
+                    StatusCode::OK,
+                    Some((body.into_bytes(), mime::TEXT_PLAIN)),
+                );
+                (state, res)
+            });
+
+        Box::new(f)
+    }
+
+    fn signature(body: &str) -> String {
+        body.bytes().fold(0u8, |sum, b| sum.wrapping_add(b)).to_string()
+    }
+
+    #[test]
+    fn buffered_body_is_read_by_chained_consumers() {
+        let test_server = TestServer::new(|| Ok(signed_form_handler)).unwrap();
+        let body = "name=gotham&kind=framework";
+
+        let response = test_server
+            .client()
+            .post("http://localhost/", body, mime::APPLICATION_WWW_FORM_URLENCODED)
+            .with_header("x-signature", signature(body).parse().unwrap())
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "gotham");
+
+        let response = test_server
+            .client()
+            .post("http://localhost/", body, mime::APPLICATION_WWW_FORM_URLENCODED)
+            .with_header("x-signature", "0".parse().unwrap())
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn buffered_body_remains_available_as_body() {
+        let dir = upload_dir();
+        let target = dir.join("upload.txt");
+
+        let test_server = TestServer::new(move || {
+            let target = target.clone();
+            Ok(move |state: State| -> Box<HandlerFuture> {
+                let f = buffer_body(state, 1024)
+                    .and_then(move |state| save_body_to_file(state, target, 1024))
+                    .map(|(state, info)| {
+                        let same = BufferedBody::borrow_from(&state).bytes().len() as u64
+                            == info.bytes();
+                        let res = create_response(
+                            &state,
+                            StatusCode::OK,
+                            Some((same.to_string().into_bytes(), mime::TEXT_PLAIN)),
+                        );
+                        (state, res)
+                    });
+                Box::new(f)
+            })
+        }).unwrap();
+
+        let response = test_server
+            .client()
+            .post("http://localhost/", chunked_body(), mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "true");
+        assert_eq!(
+            fs::read(dir.join("upload.txt")).unwrap(),
+            b"first chunk, second chunk, third chunk".to_vec()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn buffer_rejects_body_over_limit() {
+        let test_server = TestServer::new(|| {
+            Ok(|state: State| -> Box<HandlerFuture> {
+                let f = buffer_body(state, 20).map(|state| {
+                    let res = create_response(&state, StatusCode::OK, None);
+                    (state, res)
+                });
+                Box::new(f)
+            })
+        }).unwrap();
+
+        let response = test_server
+            .client()
+            .post("http://localhost/", chunked_body(), mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    /// A body which sends one chunk, and then stalls for `stall` before sending the next.
+    fn stalled_body(stall: Duration) -> Body {
+        let first = stream::once(Ok(b"first chunk, ".to_vec()));
+        let second = future::lazy(move || Delay::new(Instant::now() + stall))
+            .map(|()| b"second chunk".to_vec())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            .into_stream();
+
+        Body::wrap_stream(first.chain(second))
+    }
+
+    fn idle_timeout_server(idle: Duration) -> TestServer {
+        TestServer::new(move || {
+            Ok(move |state: State| -> Box<HandlerFuture> {
+                let f = buffer_body_with_idle_timeout(state, 1024, idle).map(|state| {
+                    let length = BufferedBody::borrow_from(&state).bytes().len();
+                    let res = create_response(
+                        &state,
+                        StatusCode::OK,
+                        Some((length.to_string().into_bytes(), mime::TEXT_PLAIN)),
+                    );
+                    (state, res)
+                });
+                Box::new(f)
+            })
+        }).unwrap()
+    }
+
+    #[test]
+    fn stalled_body_times_out() {
+        let test_server = idle_timeout_server(Duration::from_millis(100));
+
+        let response = test_server
+            .client()
+            .post(
+                "http://localhost/",
+                stalled_body(Duration::from_secs(2)),
+                mime::TEXT_PLAIN,
+            )
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+    }
+
+    #[test]
+    fn idle_timeout_restarts_with_each_chunk() {
+        let test_server = idle_timeout_server(Duration::from_millis(500));
+
+        let response = test_server
+            .client()
+            .post(
+                "http://localhost/",
+                stalled_body(Duration::from_millis(50)),
+                mime::TEXT_PLAIN,
+            )
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "25");
+    }
+
+    #[test]
+    fn stalled_upload_is_removed() {
+        let dir = upload_dir();
+        let target = dir.join("upload.txt");
+
+        let test_server = TestServer::new(move || {
+            let target = target.clone();
+            Ok(move |state: State| -> Box<HandlerFuture> {
+                let idle = Duration::from_millis(100);
+                let f = save_body_to_file_with_idle_timeout(state, target, 1024, idle).map(
+                    |(state, _)| {
+                        let res = create_response(&state, StatusCode::OK, None);
+                        (state, res)
+                    },
+                );
+                Box::new(f)
+            })
+        }).unwrap();
+
+        let response = test_server
+            .client()
+            .post(
+                "http://localhost/",
+                stalled_body(Duration::from_secs(2)),
+                mime::TEXT_PLAIN,
+            )
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        assert!(removed_eventually(&dir));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn partial_file_removed_when_dropped() {
+        let dir = upload_dir();
+        let target = dir.join("upload.txt");
+
+        {
+            let mut runtime = Runtime::new().unwrap();
+            let _partial = runtime
+                .block_on(
+                    PartialFile::create(&target, false)
+                        .and_then(|partial| partial.write_all(Chunk::from("partial"))),
+                )
+                .unwrap();
+            assert_eq!(remaining_files(&dir).len(), 1);
+        }
+
+        assert!(removed_eventually(&dir));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
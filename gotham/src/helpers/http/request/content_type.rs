@@ -0,0 +1,231 @@
+//! Defines the parsed `Content-Type` of the request.
+
+use hyper::header::{HeaderMap, CONTENT_TYPE};
+use mime::{self, Mime};
+
+use state::{request_id, FromState, State};
+
+/// The media type of the request body, parsed from the `Content-Type` header and available via
+/// `RequestContentType::borrow_from(&state)`.
+///
+/// This is stored by `GothamService` before any `Router`, `Pipeline` or `Middleware` is invoked.
+/// A missing or unparseable header does not fail the request; `mime` is `None` in both cases, and
+/// the raw value of an unparseable header is kept for diagnostics. Multiple `Content-Type`
+/// headers are treated as unparseable.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::request::RequestContentType;
+/// # use gotham::helpers::http::response::create_text_response;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let message = {
+///         let content_type = RequestContentType::borrow_from(&state);
+///
+///         match content_type.mime() {
+///             Some(mime) => format!(
+///                 "{}/{} ({:?})",
+///                 mime.type_(),
+///                 mime.subtype(),
+///                 content_type.charset()
+///             ),
+///             None => format!("unknown: {:?}", content_type.raw()),
+///         }
+///     };
+///
+///     let res = create_text_response(&state, StatusCode::OK, message);
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .post("http://localhost/", "text", mime::TEXT_PLAIN_UTF_8)
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "text/plain (Some(\"utf-8\"))");
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct RequestContentType {
+    mime: Option<Mime>,
+    raw: Option<String>,
+}
+
+impl RequestContentType {
+    /// Parses the `Content-Type` header from the given request headers.
+    pub fn from_headers(headers: &HeaderMap) -> RequestContentType {
+        let values: Vec<_> = headers
+            .get_all(CONTENT_TYPE)
+            .iter()
+            .map(|value| String::from_utf8_lossy(value.as_bytes()))
+            .collect();
+
+        let raw = match values.len() {
+            0 => None,
+            _ => Some(values.join(", ")),
+        };
+
+        let mime = match values.len() {
+            1 => values[0].parse().ok(),
+            _ => None,
+        };
+
+        RequestContentType { mime, raw }
+    }
+
+    /// The media type of the request body, or `None` when the `Content-Type` header was missing
+    /// or could not be parsed.
+    pub fn mime(&self) -> Option<&Mime> {
+        self.mime.as_ref()
+    }
+
+    /// The `charset` parameter of the media type, if present.
+    pub fn charset(&self) -> Option<&str> {
+        self.mime
+            .as_ref()
+            .and_then(|mime| mime.get_param(mime::CHARSET))
+            .map(|charset| charset.as_str())
+    }
+
+    /// The `boundary` parameter of a `multipart/*` media type, if present.
+    pub fn boundary(&self) -> Option<&str> {
+        self.mime
+            .as_ref()
+            .filter(|mime| mime.type_() == mime::MULTIPART)
+            .and_then(|mime| mime.get_param(mime::BOUNDARY))
+            .map(|boundary| boundary.as_str())
+    }
+
+    /// The raw value of the `Content-Type` header, if present, whether or not it could be parsed.
+    pub fn raw(&self) -> Option<&str> {
+        self.raw.as_ref().map(String::as_str)
+    }
+
+    /// Determines if a `Content-Type` header was provided but could not be parsed.
+    pub fn is_malformed(&self) -> bool {
+        self.raw.is_some() && self.mime.is_none()
+    }
+}
+
+/// Stores a `RequestContentType` parsed from the request headers already placed in `State`.
+pub(crate) fn put_request_content_type(state: &mut State) {
+    let content_type = RequestContentType::from_headers(HeaderMap::borrow_from(state));
+
+    if content_type.is_malformed() {
+        debug!(
+            "[DEBUG][{}][Unparseable Content-Type: {:?}]",
+            request_id(state),
+            content_type.raw().unwrap_or("")
+        );
+    }
+
+    state.put(content_type);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, Response, StatusCode};
+
+    use helpers::http::response::create_text_response;
+    use router::builder::*;
+    use router::route::matcher::content_type::ContentTypeHeaderRouteMatcher;
+    use test::TestServer;
+
+    fn parse(values: &[&'static str]) -> RequestContentType {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(CONTENT_TYPE, value.parse().unwrap());
+        }
+
+        RequestContentType::from_headers(&headers)
+    }
+
+    #[test]
+    fn parses_parameters() {
+        let ct = parse(&["application/json; charset=utf-8"]);
+        assert_eq!(ct.mime().map(Mime::type_), Some(mime::APPLICATION));
+        assert_eq!(ct.mime().map(Mime::subtype), Some(mime::JSON));
+        assert_eq!(ct.charset(), Some("utf-8"));
+        assert_eq!(ct.boundary(), None);
+        assert!(!ct.is_malformed());
+
+        let ct = parse(&["multipart/form-data; boundary=\"----abc\""]);
+        assert_eq!(ct.boundary(), Some("----abc"));
+
+        let ct = parse(&["text/plain; boundary=abc"]);
+        assert_eq!(ct.boundary(), None);
+    }
+
+    #[test]
+    fn missing_and_malformed() {
+        let ct = parse(&[]);
+        assert_eq!(ct.mime(), None);
+        assert_eq!(ct.raw(), None);
+        assert!(!ct.is_malformed());
+
+        let ct = parse(&["not a media type"]);
+        assert_eq!(ct.mime(), None);
+        assert_eq!(ct.raw(), Some("not a media type"));
+        assert!(ct.is_malformed());
+
+        let ct = parse(&["text/plain", "application/json"]);
+        assert_eq!(ct.mime(), None);
+        assert_eq!(ct.raw(), Some("text/plain, application/json"));
+        assert!(ct.is_malformed());
+    }
+
+    #[test]
+    fn malformed_header_does_not_fail_request() {
+        fn handler(state: State) -> (State, Response<Body>) {
+            let raw = RequestContentType::borrow_from(&state)
+                .raw()
+                .unwrap_or("")
+                .to_owned();
+
+            let res = create_text_response(&state, StatusCode::OK, raw);
+            (state, res)
+        }
+
+        let router = build_simple_router(|route| {
+            route.post("/any").to(handler);
+            route
+                .post("/json")
+                .add_route_matcher(ContentTypeHeaderRouteMatcher::new(vec![
+                    mime::APPLICATION_JSON,
+                ]))
+                .to(handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let malformed = "not a media type";
+
+        let response = test_server
+            .client()
+            .post("http://localhost/any", "data", mime::TEXT_PLAIN)
+            .with_header(CONTENT_TYPE, malformed.parse().unwrap())
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), malformed);
+
+        let response = test_server
+            .client()
+            .post("http://localhost/json", "data", mime::TEXT_PLAIN)
+            .with_header(CONTENT_TYPE, malformed.parse().unwrap())
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
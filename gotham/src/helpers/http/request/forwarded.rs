@@ -0,0 +1,387 @@
+//! Defines helpers for building absolute URLs to the application, as seen by the client when
+//! requests pass through a reverse proxy.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::net::{IpAddr, SocketAddr};
+
+use http::uri::InvalidUri;
+use hyper::header::{HeaderMap, FORWARDED, HOST};
+use hyper::Uri;
+
+use helpers::http::header::{X_FORWARDED_HOST, X_FORWARDED_PORT, X_FORWARDED_PROTO};
+use state::connection::ConnectionInfo;
+use state::{client_addr, FromState, State};
+
+/// The proxies which are trusted to report the scheme and host requested by the client, via the
+/// `Forwarded` or `X-Forwarded-*` headers.
+///
+/// These headers can be set by any client, so they are only honoured by `absolute_url` when the
+/// immediate peer of the connection is a trusted proxy. A `TrustedProxies` value is stored in
+/// `State` (e.g. by a middleware) for `absolute_url` to consult; when absent, no proxy is trusted.
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies {
+    addrs: Vec<IpAddr>,
+    any: bool,
+}
+
+impl TrustedProxies {
+    /// Creates a `TrustedProxies` which trusts no proxy.
+    pub fn new() -> TrustedProxies {
+        TrustedProxies::default()
+    }
+
+    /// Trusts the proxy connecting from the given address.
+    pub fn with_proxy(mut self, addr: IpAddr) -> TrustedProxies {
+        self.addrs.push(addr);
+        self
+    }
+
+    /// Sets whether every peer is trusted as a proxy. This is only appropriate when the
+    /// application cannot be reached except through a proxy which sets the headers itself.
+    pub fn trust_any(self, any: bool) -> TrustedProxies {
+        TrustedProxies { any, ..self }
+    }
+
+    fn trusts(&self, peer: Option<SocketAddr>) -> bool {
+        self.any || peer.map_or(false, |peer| self.addrs.contains(&peer.ip()))
+    }
+}
+
+/// The reason an absolute URL could not be built by `absolute_url`.
+#[derive(Debug)]
+pub enum AbsoluteUrlError {
+    /// No host was provided by the request, either in the `Host` header or the request URI.
+    MissingHost,
+
+    /// The host provided by the request contained invalid characters.
+    InvalidHost,
+
+    /// The URL could not be parsed, likely due to an invalid path.
+    InvalidUri(InvalidUri),
+}
+
+impl Display for AbsoluteUrlError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            AbsoluteUrlError::MissingHost => f.write_str("request has no host"),
+            AbsoluteUrlError::InvalidHost => f.write_str("request host is invalid"),
+            AbsoluteUrlError::InvalidUri(ref e) => write!(f, "invalid URL: {}", e),
+        }
+    }
+}
+
+impl Error for AbsoluteUrlError {
+    fn description(&self) -> &str {
+        match *self {
+            AbsoluteUrlError::MissingHost => "request has no host",
+            AbsoluteUrlError::InvalidHost => "request host is invalid",
+            AbsoluteUrlError::InvalidUri(_) => "invalid URL",
+        }
+    }
+}
+
+/// Builds an absolute URL for `path_and_query`, using the scheme and host that the client used to
+/// make the current request.
+///
+/// When the peer is trusted according to the `TrustedProxies` in `State`, the `Forwarded`
+/// header ([RFC 7239][rfc]) is preferred, followed by the `X-Forwarded-Proto`,
+/// `X-Forwarded-Host` and `X-Forwarded-Port` headers. Where a header has several values, the one
+/// added by the nearest proxy (the last) is used. In all other cases the `Host` header is used,
+/// falling back to the request URI, and the scheme is that of the request URI if it has one, or
+/// otherwise `https` for a request received over TLS (see `ConnectionInfo::tls`) and `http` for
+/// any other request.
+///
+/// [rfc]: https://tools.ietf.org/html/rfc7239
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::header::{HeaderMap, HOST};
+/// # use hyper::Uri;
+/// # use gotham::helpers::http::request::{absolute_url, TrustedProxies};
+/// # use gotham::state::State;
+/// #
+/// # fn main() {
+/// #   State::with_new(|state| {
+/// #       let mut headers = HeaderMap::new();
+/// #       headers.insert(HOST, "10.0.0.5:7878".parse().unwrap());
+/// #       headers.insert("x-forwarded-proto", "https".parse().unwrap());
+/// #       headers.insert("x-forwarded-host", "example.com".parse().unwrap());
+/// #       state.put(headers);
+/// #       state.put("/".parse::<Uri>().unwrap());
+/// #       state.put("10.0.0.1:50123".parse::<std::net::SocketAddr>().unwrap());
+/// state.put(TrustedProxies::new().with_proxy("10.0.0.1".parse().unwrap()));
+///
+/// let url = absolute_url(state, "/login?next=%2F").unwrap();
+/// assert_eq!(url.to_string(), "https://example.com/login?next=%2F");
+/// #   });
+/// # }
+/// ```
+pub fn absolute_url(state: &State, path_and_query: &str) -> Result<Uri, AbsoluteUrlError> {
+    let headers = HeaderMap::borrow_from(state);
+    let uri = Uri::borrow_from(state);
+
+    let trusted = TrustedProxies::try_borrow_from(state)
+        .map_or(false, |proxies| proxies.trusts(client_addr(state)));
+
+    let mut scheme = None;
+    let mut host = None;
+    let mut port = None;
+
+    if trusted {
+        if let Some(forwarded) = last_forwarded_element(headers) {
+            scheme = forwarded.proto;
+            host = forwarded.host;
+        }
+
+        scheme = scheme.or_else(|| last_value(headers, X_FORWARDED_PROTO));
+        host = host.or_else(|| last_value(headers, X_FORWARDED_HOST));
+        port = last_value(headers, X_FORWARDED_PORT);
+    }
+
+    let scheme = scheme
+        .map(|s| s.to_lowercase())
+        .filter(|s| s == "http" || s == "https")
+        .or_else(|| uri.scheme_part().map(|s| s.as_str().to_owned()))
+        .unwrap_or_else(|| {
+            let tls = ConnectionInfo::try_borrow_from(state).map_or(false, ConnectionInfo::tls);
+            if tls { "https" } else { "http" }.to_owned()
+        });
+
+    let mut host = host
+        .or_else(|| {
+            headers
+                .get(HOST)
+                .and_then(|h| h.to_str().ok())
+                .map(str::to_owned)
+        })
+        .or_else(|| uri.authority_part().map(|a| a.as_str().to_owned()))
+        .ok_or(AbsoluteUrlError::MissingHost)?;
+
+    if host.is_empty() {
+        return Err(AbsoluteUrlError::MissingHost);
+    }
+
+    if !host
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-._:[]".contains(c))
+    {
+        return Err(AbsoluteUrlError::InvalidHost);
+    }
+
+    if let Some(port) = port {
+        let default = if scheme == "https" { "443" } else { "80" };
+
+        if !has_port(&host) && port != default && port.chars().all(|c| c.is_ascii_digit()) {
+            host = format!("{}:{}", host, port);
+        }
+    }
+
+    let separator = if path_and_query.starts_with('/') {
+        ""
+    } else {
+        "/"
+    };
+
+    format!("{}://{}{}{}", scheme, host, separator, path_and_query)
+        .parse()
+        .map_err(AbsoluteUrlError::InvalidUri)
+}
+
+/// The parameters of a single `Forwarded` element which are used by `absolute_url`.
+#[derive(Debug, Default, PartialEq)]
+struct ForwardedElement {
+    proto: Option<String>,
+    host: Option<String>,
+}
+
+/// Parses the last element of the `Forwarded` header(s), which was added by the nearest proxy.
+fn last_forwarded_element(headers: &HeaderMap) -> Option<ForwardedElement> {
+    let element = headers
+        .get_all(FORWARDED)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .last()?;
+
+    let mut forwarded = ForwardedElement::default();
+
+    for pair in element.split(';') {
+        let mut parts = pair.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim().to_lowercase();
+        let value = match parts.next() {
+            Some(value) => value.trim().trim_matches('"').to_owned(),
+            None => continue,
+        };
+
+        match key.as_str() {
+            "proto" => forwarded.proto = Some(value),
+            "host" => forwarded.host = Some(value),
+            _ => (),
+        }
+    }
+
+    Some(forwarded)
+}
+
+/// The last comma-separated value of the named header(s), which was added by the nearest proxy.
+fn last_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .last()
+        .map(|v| v.trim().to_owned())
+        .filter(|v| !v.is_empty())
+}
+
+fn has_port(host: &str) -> bool {
+    if host.starts_with('[') {
+        host.contains("]:")
+    } else {
+        host.contains(':')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use keep_alive::KeepAliveConfig;
+    use state::connection::put_connection_info;
+
+    const PROXY: &'static str = "10.0.0.1:50123";
+
+    fn url(headers: &[(&'static str, &'static str)], peer: &str) -> Result<String, String> {
+        let mut state = State::new();
+
+        let mut map = HeaderMap::new();
+        for &(name, value) in headers {
+            map.append(name, value.parse().unwrap());
+        }
+
+        state.put(map);
+        state.put("/".parse::<Uri>().unwrap());
+        state.put(peer.parse::<SocketAddr>().unwrap());
+        state.put(TrustedProxies::new().with_proxy("10.0.0.1".parse().unwrap()));
+
+        absolute_url(&state, "/login?next=%2F")
+            .map(|uri| uri.to_string())
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn direct_request_uses_host() {
+        assert_eq!(
+            url(&[("host", "example.com:7878")], "192.0.2.7:41000"),
+            Ok("http://example.com:7878/login?next=%2F".to_owned())
+        );
+    }
+
+    #[test]
+    fn tls_request_uses_https() {
+        let mut state = State::new();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(HOST, "example.com".parse().unwrap());
+        state.put(headers);
+        state.put("/".parse::<Uri>().unwrap());
+        put_connection_info(
+            &mut state,
+            None,
+            true,
+            None,
+            &KeepAliveConfig::new(),
+            false,
+            None,
+        );
+
+        assert_eq!(
+            absolute_url(&state, "/login").unwrap().to_string(),
+            "https://example.com/login"
+        );
+    }
+
+    #[test]
+    fn trusted_proxy_x_forwarded_headers() {
+        let headers = [
+            ("host", "10.0.0.5:7878"),
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "example.com"),
+            ("x-forwarded-port", "443"),
+        ];
+        assert_eq!(
+            url(&headers, PROXY),
+            Ok("https://example.com/login?next=%2F".to_owned())
+        );
+
+        let headers = [
+            ("host", "10.0.0.5:7878"),
+            ("x-forwarded-proto", "http, https"),
+            ("x-forwarded-host", "example.com"),
+            ("x-forwarded-port", "8443"),
+        ];
+        assert_eq!(
+            url(&headers, PROXY),
+            Ok("https://example.com:8443/login?next=%2F".to_owned())
+        );
+    }
+
+    #[test]
+    fn trusted_proxy_forwarded_header() {
+        let headers = [
+            ("host", "10.0.0.5:7878"),
+            ("x-forwarded-host", "ignored.example.com"),
+            (
+                "forwarded",
+                "for=192.0.2.60;proto=http;host=spoofed.example.com",
+            ),
+            (
+                "forwarded",
+                "for=192.0.2.43; Proto=https; Host=\"example.com\"",
+            ),
+        ];
+        assert_eq!(
+            url(&headers, PROXY),
+            Ok("https://example.com/login?next=%2F".to_owned())
+        );
+    }
+
+    #[test]
+    fn untrusted_peer_headers_are_ignored() {
+        let headers = [
+            ("host", "example.com"),
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "evil.example.com"),
+            ("forwarded", "proto=https;host=evil.example.com"),
+        ];
+        assert_eq!(
+            url(&headers, "192.0.2.7:41000"),
+            Ok("http://example.com/login?next=%2F".to_owned())
+        );
+    }
+
+    #[test]
+    fn invalid_inputs() {
+        assert_eq!(
+            url(&[("x-forwarded-proto", "javascript")], PROXY),
+            Err("request has no host".to_owned())
+        );
+        assert_eq!(
+            url(&[("host", "example.com/evil")], "192.0.2.7:41000"),
+            Err("request host is invalid".to_owned())
+        );
+        assert_eq!(
+            url(
+                &[("host", "example.com"), ("x-forwarded-proto", "ftp")],
+                PROXY
+            ),
+            Ok("http://example.com/login?next=%2F".to_owned())
+        );
+    }
+}
@@ -1,4 +1,11 @@
 //! Helpers for HTTP request handling
 
+pub mod body;
+pub mod content_type;
+pub mod forwarded;
 pub mod path;
 pub mod query_string;
+
+pub use self::content_type::RequestContentType;
+pub use self::forwarded::{absolute_url, AbsoluteUrlError, TrustedProxies};
+pub use self::query_string::QueryParams;
@@ -1,15 +1,48 @@
 //! Defines helper functions for processing the request path
 
+use std::cmp;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Component, Path, PathBuf};
+
+use hyper::{StatusCode, Uri};
+use serde::de::{self, Deserialize, Deserializer};
+
 use helpers::http::PercentDecoded;
+use state::{FromState, State};
 
 const EXCLUDED_SEGMENTS: [&str; 1] = [""];
 
+/// Stores the `RequestPathSegments` split from the `Uri` already placed in `State`. The `Router`
+/// normalizes them according to its `PathOptions` before matching, so this is used both for
+/// requests received by `GothamService` and for those forwarded by `forward_to`.
+pub(crate) fn put_request_path_segments(state: &mut State) {
+    let segments = RequestPathSegments::new(Uri::borrow_from(state).path());
+    state.put(segments);
+}
+
 /// Holder for `Request` URI path segments that have been split into individual segments.
 ///
+/// Each segment is percent-decoded once, when the path is split, so that `PathExtractor` values
+/// receive the decoded form (e.g. `dotted name` for a segment of `dotted%20name`). The segment as
+/// received is also kept, for matching static segments (see `StaticSegments`).
+///
 /// Used internally by the `Router` when traversing its internal `Tree`.
 #[derive(Clone, Debug, PartialEq)]
 pub struct RequestPathSegments {
     segments: Vec<PercentDecoded>,
+    invalid_utf8: bool,
+    empty_segments: bool,
+    trailing_slash: Option<bool>,
+    lengths: PathLengths,
+}
+
+/// The sizes of the whole request path, measured when it's split, and compared against the
+/// `PathLimits` of the `Router`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PathLengths {
+    path: usize,
+    segments: usize,
+    longest_segment: usize,
 }
 
 impl RequestPathSegments {
@@ -22,22 +55,169 @@ impl RequestPathSegments {
     /// ```plain
     /// ["/", "some", "path", "to", "my", "handler"]
     /// ```
+    ///
+    /// The path is split before decoding, so an encoded slash (`%2F`) remains within a single
+    /// segment until `normalize` applies the `EncodedSlashes` policy. A segment which isn't valid
+    /// UTF-8 after decoding is skipped, and recorded so that the `Router` can reject the request.
     pub(crate) fn new(path: &str) -> Self {
-        let segments = path
-            .split('/')
+        let mut invalid_utf8 = false;
+        let mut lengths = PathLengths {
+            path: path.len(),
+            segments: 0,
+            longest_segment: 0,
+        };
+
+        let pieces: Vec<&str> = path.split('/').collect();
+        let empty_segments =
+            pieces.len() > 2 && pieces[1..pieces.len() - 1].iter().any(|s| s.is_empty());
+
+        let segments: Vec<PercentDecoded> = pieces
+            .into_iter()
             .filter(|s| !EXCLUDED_SEGMENTS.contains(s))
-            .filter_map(PercentDecoded::new)
+            .filter_map(|s| {
+                lengths.segments += 1;
+                lengths.longest_segment = cmp::max(lengths.longest_segment, s.len());
+
+                let decoded = PercentDecoded::new(s);
+                invalid_utf8 |= decoded.is_none();
+                decoded
+            })
             .collect();
 
-        RequestPathSegments { segments }
+        let trailing_slash = if segments.is_empty() {
+            None
+        } else {
+            Some(path.ends_with('/'))
+        };
+
+        RequestPathSegments {
+            segments,
+            invalid_utf8,
+            empty_segments,
+            trailing_slash,
+            lengths,
+        }
     }
 
     pub(crate) fn into_subsegments(&self, offset: usize) -> Self {
         RequestPathSegments {
             segments: self.segments.split_at(offset).1.to_vec(),
+            invalid_utf8: self.invalid_utf8,
+            empty_segments: self.empty_segments,
+            trailing_slash: self.trailing_slash,
+            lengths: self.lengths,
         }
     }
 
+    /// Determines if a segment of the path was not valid UTF-8 after percent-decoding.
+    pub(crate) fn has_invalid_utf8(&self) -> bool {
+        self.invalid_utf8
+    }
+
+    /// Determines if a segment contains an encoded slash (`%2F`), which decodes to a `/` within
+    /// the segment instead of separating two segments.
+    pub(crate) fn has_encoded_slash(&self) -> bool {
+        self.segments.iter().any(|s| s.as_ref().contains('/'))
+    }
+
+    /// Determines if the path contained an empty segment other than a trailing one, such as in
+    /// `/users//42`.
+    pub(crate) fn has_empty_segments(&self) -> bool {
+        self.empty_segments
+    }
+
+    /// Determines if the path ended with a slash, such as `/users/`. This is `None` for the root
+    /// path, which has no segments and so no canonical form (even when delegated to by another
+    /// `Router`, this describes the whole request path).
+    pub(crate) fn trailing_slash(&self) -> Option<bool> {
+        self.trailing_slash
+    }
+
+    /// Applies the `PathOptions` to the segments, producing the segments which are matched
+    /// against routes and provided to the `PathExtractor`.
+    pub(crate) fn normalize(&self, options: &PathOptions) -> Result<Self, PathRejection> {
+        options.limits.check(&self.lengths)?;
+
+        if self.has_invalid_utf8() {
+            return Err(PathRejection::InvalidUtf8);
+        }
+
+        let split = match options.encoded_slashes {
+            EncodedSlashes::Reject if self.has_encoded_slash() => {
+                return Err(PathRejection::EncodedSlash);
+            }
+            EncodedSlashes::Split => self.has_encoded_slash(),
+            _ => false,
+        };
+
+        if self.has_empty_segments() && !options.collapse_slashes {
+            return Err(PathRejection::EmptySegment);
+        }
+
+        let mut pieces = Vec::with_capacity(self.segments.len());
+
+        for segment in &self.segments {
+            if !split || !segment.as_ref().contains('/') {
+                pieces.push(segment.clone());
+                continue;
+            }
+
+            // A decoded slash separates segments, as though it had been sent unencoded. Every
+            // slash in the decoded segment was encoded, so its pieces match those of the encoded
+            // segment split at each `%2F`.
+            let encoded = split_encoded_slashes(segment.static_form());
+            for (piece, static_form) in segment.as_ref().split('/').zip(encoded) {
+                if !piece.is_empty() {
+                    pieces.push(PercentDecoded {
+                        val: piece.to_owned(),
+                        static_form: static_form.to_owned(),
+                    });
+                } else if !options.collapse_slashes {
+                    return Err(PathRejection::EmptySegment);
+                }
+            }
+        }
+
+        let mut segments = Vec::with_capacity(pieces.len());
+
+        for segment in pieces {
+            if options.resolve_dot_segments {
+                match segment.as_ref() {
+                    "." => continue,
+                    ".." => match segments.pop() {
+                        Some(_) => continue,
+                        None => return Err(PathRejection::EscapesRoot),
+                    },
+                    _ => (),
+                }
+            }
+
+            let PercentDecoded {
+                mut val,
+                mut static_form,
+            } = segment;
+
+            if options.static_segments == StaticSegments::Decoded {
+                static_form = val.clone();
+            }
+
+            if options.lowercase {
+                val = val.to_lowercase();
+                static_form = static_form.to_lowercase();
+            }
+
+            segments.push(PercentDecoded { val, static_form });
+        }
+
+        Ok(RequestPathSegments {
+            segments,
+            invalid_utf8: false,
+            empty_segments: false,
+            trailing_slash: self.trailing_slash,
+            lengths: self.lengths,
+        })
+    }
+
     /// Provide segments that still need to be processed.
     ///
     /// This will always include a "/" node to represent the root as well as all segments
@@ -50,10 +230,459 @@ impl RequestPathSegments {
     }
 }
 
+/// Splits an encoded segment at each encoded slash (`%2F` or `%2f`).
+fn split_encoded_slashes(segment: &str) -> Vec<&str> {
+    let bytes = segment.as_bytes();
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+
+    while i + 3 <= bytes.len() {
+        if bytes[i] == b'%' && bytes[i + 1] == b'2' && (bytes[i + 2] | 0x20) == b'f' {
+            pieces.push(&segment[start..i]);
+            i += 3;
+            start = i;
+        } else {
+            i += 1;
+        }
+    }
+
+    pieces.push(&segment[start..]);
+    pieces
+}
+
+/// The segments matched by a glob in a route path, such as `*path` in `/static/*path`, joined into
+/// a relative path which is safe to resolve beneath a directory.
+///
+/// This is used as the type of a `PathExtractor` field, in place of `Vec<String>`. Extraction
+/// fails (and the request is rejected with `400 Bad Request`) when a segment is `.` or `..`, or
+/// contains a path separator or NUL character, so the path can never refer to a location outside
+/// the directory it's joined to.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # #[macro_use]
+/// # extern crate gotham_derive;
+/// # extern crate hyper;
+/// # extern crate serde;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::request::path::GlobPath;
+/// # use gotham::helpers::http::response::create_text_response;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// #[derive(Deserialize, StateData, StaticResponseExtender)]
+/// struct StaticPath {
+///     path: GlobPath,
+/// }
+///
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let file = {
+///         let path = StaticPath::borrow_from(&state);
+///         std::path::Path::new("assets").join(path.path.as_path())
+///     };
+///
+///     let res = create_text_response(&state, StatusCode::OK, file.display().to_string());
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #   let router = build_simple_router(|route| {
+/// #       route
+/// #           .get("/static/*path")
+/// #           .with_path_extractor::<StaticPath>()
+/// #           .to(handler);
+/// #   });
+/// #
+/// #   let test_server = TestServer::new(router).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://localhost/static/css/site.css")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(
+/// #       response.read_utf8_body().unwrap(),
+/// #       std::path::Path::new("assets/css/site.css").display().to_string()
+/// #   );
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlobPath {
+    segments: Vec<String>,
+    path: PathBuf,
+}
+
+impl GlobPath {
+    /// Joins the segments into a relative path, failing when a segment is unsafe.
+    pub fn new(segments: Vec<String>) -> Result<GlobPath, String> {
+        let mut path = PathBuf::new();
+
+        for segment in &segments {
+            let mut components = Path::new(segment).components();
+            let safe = !segment.contains(|c| c == '/' || c == '\\' || c == '\0')
+                && match (components.next(), components.next()) {
+                    (Some(Component::Normal(_)), None) => true,
+                    _ => false,
+                };
+
+            if !safe {
+                return Err(format!("unsafe path segment: {:?}", segment));
+            }
+
+            path.push(segment);
+        }
+
+        Ok(GlobPath { segments, path })
+    }
+
+    /// The matched segments, in order.
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    /// The segments joined into a relative path.
+    pub fn as_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The segments joined by `/`.
+    pub fn joined(&self) -> String {
+        self.segments.join("/")
+    }
+}
+
+impl<'de> Deserialize<'de> for GlobPath {
+    fn deserialize<D>(deserializer: D) -> Result<GlobPath, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let segments = Vec::<String>::deserialize(deserializer)?;
+        GlobPath::new(segments).map_err(de::Error::custom)
+    }
+}
+
+/// Controls how the `Router` treats the request path before matching it against routes. Each
+/// option is set via `RouterBuilder`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PathOptions {
+    pub(crate) encoded_slashes: EncodedSlashes,
+    pub(crate) static_segments: StaticSegments,
+    pub(crate) collapse_slashes: bool,
+    pub(crate) resolve_dot_segments: bool,
+    pub(crate) lowercase: bool,
+    pub(crate) case: PathCase,
+    pub(crate) trailing_slash: TrailingSlash,
+    pub(crate) canonical_trailing_slash: bool,
+    pub(crate) redirect_all_methods: bool,
+    pub(crate) limits: PathLimits,
+}
+
+impl Default for PathOptions {
+    fn default() -> PathOptions {
+        PathOptions {
+            encoded_slashes: EncodedSlashes::Reject,
+            static_segments: StaticSegments::Encoded,
+            collapse_slashes: true,
+            resolve_dot_segments: false,
+            lowercase: false,
+            case: PathCase::Sensitive,
+            trailing_slash: TrailingSlash::Normalize,
+            canonical_trailing_slash: false,
+            redirect_all_methods: false,
+            limits: PathLimits::default(),
+        }
+    }
+}
+
+/// Limits on the size of the request path, which the `Router` enforces before matching the path
+/// against its routes. This is set via `RouterBuilder::set_path_limits`.
+///
+/// A request path which exceeds a limit receives a `414 URI Too Long` response, or the status
+/// set with `with_status` (e.g. `404 Not Found`, to avoid revealing the limits). The sizes are
+/// measured while the path is split into segments, before percent-decoding, so checking them
+/// adds no further work to each request.
+///
+/// The default limits, of 16384 bytes for the path, 1024 segments, and 4096 bytes for a single
+/// segment, are generous enough not to affect applications, while preventing a request from
+/// making the `Router` process an enormous path.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::StatusCode;
+/// # use gotham::helpers::http::request::path::PathLimits;
+/// # use gotham::router::builder::*;
+/// #
+/// # fn main() {
+/// let _router = build_simple_router(|route| {
+///     route.set_path_limits(
+///         PathLimits::default()
+///             .with_max_segments(32)
+///             .with_status(StatusCode::NOT_FOUND),
+///     );
+/// });
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathLimits {
+    max_path_length: usize,
+    max_segments: usize,
+    max_segment_length: usize,
+    status: StatusCode,
+}
+
+impl Default for PathLimits {
+    fn default() -> PathLimits {
+        PathLimits {
+            max_path_length: 16384,
+            max_segments: 1024,
+            max_segment_length: 4096,
+            status: StatusCode::URI_TOO_LONG,
+        }
+    }
+}
+
+impl PathLimits {
+    /// Sets the maximum length of the request path in bytes, excluding the query string.
+    pub fn with_max_path_length(self, max_path_length: usize) -> PathLimits {
+        PathLimits {
+            max_path_length,
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of segments in the request path. Empty segments, such as those
+    /// between duplicate slashes, aren't counted.
+    pub fn with_max_segments(self, max_segments: usize) -> PathLimits {
+        PathLimits {
+            max_segments,
+            ..self
+        }
+    }
+
+    /// Sets the maximum length of a single segment of the request path in bytes, before
+    /// percent-decoding.
+    pub fn with_max_segment_length(self, max_segment_length: usize) -> PathLimits {
+        PathLimits {
+            max_segment_length,
+            ..self
+        }
+    }
+
+    /// Sets the status of the response sent for a request path which exceeds a limit.
+    pub fn with_status(self, status: StatusCode) -> PathLimits {
+        PathLimits { status, ..self }
+    }
+
+    fn check(&self, lengths: &PathLengths) -> Result<(), PathRejection> {
+        if lengths.path > self.max_path_length {
+            Err(PathRejection::PathTooLong(self.status))
+        } else if lengths.segments > self.max_segments {
+            Err(PathRejection::TooManySegments(self.status))
+        } else if lengths.longest_segment > self.max_segment_length {
+            Err(PathRejection::SegmentTooLong(self.status))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Controls how the `Router` treats an encoded slash (`%2F`) within a segment of the request
+/// path, such as in `/packages/@scope%2Fname`. This is set via
+/// `RouterBuilder::set_encoded_slashes`.
+///
+/// The same policy applies to matching static segments, the segments captured by globs and the
+/// values provided to the `PathExtractor`, since each of them receives the normalized segments.
+/// Only a single level of encoding is decoded, so a doubly-encoded slash (`%252F`) is never
+/// treated as a slash: it's provided to the `PathExtractor` as the text `%2F` in every mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EncodedSlashes {
+    /// A request path containing an encoded slash receives a `400 Bad Request` response. This is
+    /// the default, since the slash would otherwise be indistinguishable from a segment separator
+    /// to a handler which joins the segments back together.
+    Reject,
+
+    /// An encoded slash separates segments as though it had been sent unencoded, so a request
+    /// for `/files/a%2Fb` matches `/files/a/b`, and a glob captures `a` and `b` separately. Empty
+    /// segments this produces are treated as duplicate slashes.
+    Split,
+
+    /// An encoded slash is kept within its decoded segment, so a request for `/files/a%2Fb`
+    /// matches `/files/:name` with a `name` of `a/b`. A glob captures `a/b` as a single segment,
+    /// which `GlobPath` rejects.
+    Preserve,
+}
+
+/// Controls whether the static segments of routes are matched against the request path as it was
+/// received, or after percent-decoding. This is set via `RouterBuilder::set_static_segments`.
+///
+/// Dynamic and glob segments are provided to the `PathExtractor` in their decoded form in either
+/// mode, and the `EncodedSlashes` policy applies in either mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StaticSegments {
+    /// Static segments only match a request path segment which is byte for byte the same as it
+    /// was received, still percent-encoded. A route for `/users` isn't matched by a request for
+    /// `/%75sers`, and a static segment containing other characters is defined in its encoded
+    /// form, such as `/dotted%20name`. This is the default, and agrees with proxies and other
+    /// software which match the path as it appears on the wire.
+    Encoded,
+
+    /// Static segments match the percent-decoded request path segment, so a route for
+    /// `/dotted name` is matched by a request for `/dotted%20name`, and a route for `/users` by
+    /// a request for `/%75sers`.
+    Decoded,
+}
+
+/// Controls how the `Router` treats a request path which ends with a slash, such as `/users/`,
+/// compared to the same path without one. This is set via `RouterBuilder::set_trailing_slash`.
+///
+/// The canonical form of a path is without a trailing slash, unless its route was defined with
+/// one (such as `/teams/`) or this is changed with `RouterBuilder::set_canonical_trailing_slash`.
+/// The root path `/` is always canonical.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrailingSlash {
+    /// Only the canonical form of a path matches its route, and a request for the other form
+    /// receives a `404 Not Found` response.
+    Strict,
+
+    /// Both forms of a path match the same route. This is the default.
+    Normalize,
+
+    /// A `GET` or `HEAD` request for the non-canonical form of a path which matches a route
+    /// receives a `301 Moved Permanently` response, redirecting to the canonical form with the
+    /// same query string.
+    ///
+    /// Requests with other methods are matched as with `Normalize`, since clients may not repeat
+    /// the request body after a redirect. They're redirected with `308 Permanent Redirect`
+    /// instead when enabled with `RouterBuilder::set_redirect_trailing_slash_all_methods`.
+    Redirect,
+}
+
+/// Controls whether the static segments of routes match request path segments which differ only
+/// in case, such as `/Signup` for a route of `/signup`. This is set via
+/// `RouterBuilder::set_path_case`.
+///
+/// Unlike `RouterBuilder::set_lowercase_path`, the request path isn't modified, so the values of
+/// dynamic and glob segments are provided to the `PathExtractor` with their original case.
+/// Routes whose paths differ only in the case of a static segment shouldn't be defined together
+/// with the modes which ignore case, since only the first of them would ever match.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathCase {
+    /// Static segments only match when spelled exactly as in the route path. This is the
+    /// default.
+    Sensitive,
+
+    /// Static segments match regardless of case.
+    Insensitive,
+
+    /// A `GET` or `HEAD` request for a path which only matches a route by ignoring the case of
+    /// its static segments receives a `301 Moved Permanently` response, redirecting to the
+    /// canonical form of the path with the same query string. The canonical form spells each
+    /// static segment as in the route path, and keeps the values of the other segments as they
+    /// were requested.
+    ///
+    /// Requests with other methods are matched as with `Insensitive`, since clients may not
+    /// repeat the request body after a redirect.
+    ///
+    /// The canonical form is built from the normalized request path, so this mode should be set
+    /// on the `Router` which receives requests from the server, rather than on one which is
+    /// delegated to.
+    Redirect,
+}
+
+/// The reason a request path was rejected by `RequestPathSegments::normalize`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum PathRejection {
+    /// A segment was not valid UTF-8 after percent-decoding.
+    InvalidUtf8,
+    /// A segment contained an encoded slash, and `EncodedSlashes::Reject` is set.
+    EncodedSlash,
+    /// The path contained an empty segment, and duplicate slashes are not collapsed.
+    EmptySegment,
+    /// A `..` segment would have moved above the root of the path.
+    EscapesRoot,
+    /// The path was longer than allowed by the `PathLimits`, whose status is given.
+    PathTooLong(StatusCode),
+    /// The path had more segments than allowed by the `PathLimits`, whose status is given.
+    TooManySegments(StatusCode),
+    /// A segment was longer than allowed by the `PathLimits`, whose status is given.
+    SegmentTooLong(StatusCode),
+}
+
+impl PathRejection {
+    /// The status of the response sent for the rejected request.
+    pub(crate) fn status(self) -> StatusCode {
+        match self {
+            PathRejection::EmptySegment => StatusCode::NOT_FOUND,
+            PathRejection::PathTooLong(status)
+            | PathRejection::TooManySegments(status)
+            | PathRejection::SegmentTooLong(status) => status,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// Determines if the path was rejected for exceeding the `PathLimits`.
+    pub(crate) fn exceeds_limits(self) -> bool {
+        match self {
+            PathRejection::PathTooLong(_)
+            | PathRejection::TooManySegments(_)
+            | PathRejection::SegmentTooLong(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Display for PathRejection {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            PathRejection::InvalidUtf8 => "request path is not valid UTF-8 after percent decoding",
+            PathRejection::EncodedSlash => "request path contains an encoded slash",
+            PathRejection::EmptySegment => "request path contains an empty segment",
+            PathRejection::EscapesRoot => "request path escapes the root via a `..` segment",
+            PathRejection::PathTooLong(_) => "request path exceeds the maximum length",
+            PathRejection::TooManySegments(_) => "request path exceeds the maximum segment count",
+            PathRejection::SegmentTooLong(_) => "request path segment exceeds the maximum length",
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn glob_path_tests() {
+        let segments = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let path = GlobPath::new(segments(&["css", "site.css"])).unwrap();
+        assert_eq!(
+            path.segments(),
+            &["css".to_owned(), "site.css".to_owned()][..]
+        );
+        assert_eq!(path.as_path(), Path::new("css").join("site.css").as_path());
+        assert_eq!(path.joined(), "css/site.css");
+
+        assert!(GlobPath::new(vec![]).unwrap().joined().is_empty());
+
+        for unsafe_segment in &["..", ".", "", "a/b", "a\\b", "a\0b", "/etc"] {
+            assert!(
+                GlobPath::new(segments(&["css", unsafe_segment])).is_err(),
+                "{:?}",
+                unsafe_segment
+            );
+        }
+    }
+
     #[test]
     fn request_path_segments_tests() {
         // Validate the claim made in the doc comment above.
@@ -64,4 +693,207 @@ mod tests {
             vec!["some", "path", "to", "my", "handler"]
         );
     }
+
+    #[test]
+    fn request_path_segments_decoding_tests() {
+        let rps = RequestPathSegments::new("/files/dotted%20name.txt");
+        assert_eq!(
+            rps.segments.iter().map(|s| s.as_ref()).collect::<Vec<_>>(),
+            vec!["files", "dotted name.txt"]
+        );
+        assert!(!rps.has_invalid_utf8());
+        assert!(!rps.has_encoded_slash());
+
+        let rps = RequestPathSegments::new("/files/a%2Fb");
+        assert_eq!(
+            rps.segments.iter().map(|s| s.as_ref()).collect::<Vec<_>>(),
+            vec!["files", "a/b"]
+        );
+        assert!(rps.has_encoded_slash());
+
+        let rps = RequestPathSegments::new("/files/%FF%FE");
+        assert_eq!(
+            rps.segments.iter().map(|s| s.as_ref()).collect::<Vec<_>>(),
+            vec!["files"]
+        );
+        assert!(rps.has_invalid_utf8());
+    }
+
+    #[test]
+    fn static_form_tests() {
+        let static_forms = |path: &str, options: PathOptions| {
+            RequestPathSegments::new(path)
+                .normalize(&options)
+                .unwrap()
+                .segments()
+                .iter()
+                .map(|s| s.static_form().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let encoded = PathOptions::default();
+        let decoded = PathOptions {
+            static_segments: StaticSegments::Decoded,
+            ..encoded
+        };
+        let split = PathOptions {
+            encoded_slashes: EncodedSlashes::Split,
+            ..encoded
+        };
+        let lowercase = PathOptions {
+            lowercase: true,
+            ..decoded
+        };
+
+        assert_eq!(
+            static_forms("/%75sers/dotted%20name", encoded),
+            vec!["%75sers", "dotted%20name"]
+        );
+        assert_eq!(
+            static_forms("/%75sers/dotted%20name", decoded),
+            vec!["users", "dotted name"]
+        );
+        assert_eq!(
+            static_forms("/files/a%20b%2fc%2F%2Fd", split),
+            vec!["files", "a%20b", "c", "d"]
+        );
+        assert_eq!(static_forms("/%55SERS", lowercase), vec!["users"]);
+    }
+
+    #[test]
+    fn normalize_tests() {
+        let defaults = PathOptions::default();
+        let dots = PathOptions {
+            resolve_dot_segments: true,
+            ..defaults
+        };
+        let strict = PathOptions {
+            collapse_slashes: false,
+            ..defaults
+        };
+        let lowercase = PathOptions {
+            lowercase: true,
+            ..dots
+        };
+        let split = PathOptions {
+            encoded_slashes: EncodedSlashes::Split,
+            ..dots
+        };
+        let split_strict = PathOptions {
+            encoded_slashes: EncodedSlashes::Split,
+            ..strict
+        };
+        let preserve = PathOptions {
+            encoded_slashes: EncodedSlashes::Preserve,
+            ..defaults
+        };
+
+        let cases: Vec<(&str, PathOptions, Result<Vec<&str>, PathRejection>)> = vec![
+            ("/users/42", defaults, Ok(vec!["users", "42"])),
+            ("//users///42/", defaults, Ok(vec!["users", "42"])),
+            (
+                "/users/./42/../42",
+                defaults,
+                Ok(vec!["users", ".", "42", "..", "42"]),
+            ),
+            ("//users/./42/../42", dots, Ok(vec!["users", "42"])),
+            ("/users/%2E%2E/admin", dots, Ok(vec!["admin"])),
+            ("/users/../..", dots, Err(PathRejection::EscapesRoot)),
+            ("/../etc/passwd", dots, Err(PathRejection::EscapesRoot)),
+            ("/./.", dots, Ok(vec![])),
+            ("/users/42/", strict, Ok(vec!["users", "42"])),
+            ("/users//42", strict, Err(PathRejection::EmptySegment)),
+            ("/Users/./ABC", lowercase, Ok(vec!["users", "abc"])),
+            ("/Users/ABC", defaults, Ok(vec!["Users", "ABC"])),
+            ("/files/a%2Fb", defaults, Err(PathRejection::EncodedSlash)),
+            ("/files/a%252Fb", defaults, Ok(vec!["files", "a%2Fb"])),
+            ("/files/a%2Fb", split, Ok(vec!["files", "a", "b"])),
+            ("/files/a%2F%2Fb%2F", split, Ok(vec!["files", "a", "b"])),
+            ("/files/a%2F..%2Fb", split, Ok(vec!["files", "b"])),
+            ("/files/a%252Fb", split, Ok(vec!["files", "a%2Fb"])),
+            (
+                "/files/a%2F%2Fb",
+                split_strict,
+                Err(PathRejection::EmptySegment),
+            ),
+            ("/files/a%2Fb", preserve, Ok(vec!["files", "a/b"])),
+            ("/files/a%252Fb", preserve, Ok(vec!["files", "a%2Fb"])),
+            ("/files/%FF", lowercase, Err(PathRejection::InvalidUtf8)),
+        ];
+
+        for (path, options, expected) in cases {
+            let normalized = RequestPathSegments::new(path)
+                .normalize(&options)
+                .map(|rps| {
+                    rps.segments()
+                        .iter()
+                        .map(|s| s.as_ref().to_owned())
+                        .collect::<Vec<_>>()
+                });
+
+            let expected = expected.map(|v| v.into_iter().map(str::to_owned).collect());
+            assert_eq!(normalized, expected, "normalizing {}", path);
+        }
+    }
+
+    #[test]
+    fn path_limits_tests() {
+        let limits = PathLimits::default()
+            .with_max_path_length(16)
+            .with_max_segments(3)
+            .with_max_segment_length(7);
+        let options = PathOptions {
+            limits,
+            ..PathOptions::default()
+        };
+        let not_found = PathOptions {
+            limits: limits.with_status(StatusCode::NOT_FOUND),
+            ..PathOptions::default()
+        };
+
+        let cases = vec![
+            ("/abcdefg/hijklmn", options, Ok(())),
+            (
+                "/abcdefg/hijklmno",
+                options,
+                Err(PathRejection::PathTooLong(StatusCode::URI_TOO_LONG)),
+            ),
+            ("/a/b/c", options, Ok(())),
+            ("//a//b//c//", options, Ok(())),
+            (
+                "/a/b/c/d",
+                options,
+                Err(PathRejection::TooManySegments(StatusCode::URI_TOO_LONG)),
+            ),
+            ("/abcdefg", options, Ok(())),
+            (
+                "/abcdefgh",
+                options,
+                Err(PathRejection::SegmentTooLong(StatusCode::URI_TOO_LONG)),
+            ),
+            ("/%41%42", options, Ok(())),
+            (
+                "/%41%42%43",
+                options,
+                Err(PathRejection::SegmentTooLong(StatusCode::URI_TOO_LONG)),
+            ),
+            (
+                "/a/b/c/d",
+                not_found,
+                Err(PathRejection::TooManySegments(StatusCode::NOT_FOUND)),
+            ),
+        ];
+
+        for (path, options, expected) in cases {
+            let result = RequestPathSegments::new(path)
+                .normalize(&options)
+                .map(|_| ());
+            assert_eq!(result, expected, "normalizing {}", path);
+        }
+
+        let rejection = PathRejection::TooManySegments(StatusCode::NOT_FOUND);
+        assert_eq!(rejection.status(), StatusCode::NOT_FOUND);
+        assert!(rejection.exceeds_limits());
+        assert!(!PathRejection::EncodedSlash.exceeds_limits());
+    }
 }
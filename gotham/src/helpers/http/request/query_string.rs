@@ -2,37 +2,155 @@
 
 use std::collections::HashMap;
 
+use hyper::Uri;
+
+use extractor::QueryStringOptions;
 use helpers::http::{form_url_decode, FormUrlDecoded};
+use state::{FromState, State};
 
 /// Provides a mapping of keys from `Request` query string to their supplied values
 pub(crate) type QueryStringMapping = HashMap<String, Vec<FormUrlDecoded>>;
 
-/// Splits a query string into pairs and provides a mapping of keys to values.
+/// The pairs of the `Request` query string, decoded in the order they appear and available via
+/// `QueryParams::borrow_from(&state)`.
 ///
-/// For keys which are represented 1..n times in the query string the mapped `Vec` will be
-/// populated with each value provided.
+/// The query string is parsed when first needed by a route matcher or by the
+/// `QueryStringExtractor` of the matched route, which stores the `QueryParams` before the
+/// `Pipeline` and `Handler` of the route are invoked. Keys which cannot be decoded are skipped. A
+/// key which is provided without a value (e.g. `?debug`), or whose value cannot be decoded, is
+/// only reported by `contains_key`.
 ///
-/// Keys that are provided but with no value associated are skipped.
-pub(crate) fn split<'r>(query: Option<&'r str>) -> QueryStringMapping {
-    let mut query_string_mapping = QueryStringMapping::new();
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::request::QueryParams;
+/// # use gotham::helpers::http::response::create_text_response;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let message = {
+///         let params = QueryParams::borrow_from(&state);
+///         format!("{:?} {:?}", params.get("type"), params.get_all("tag"))
+///     };
+///
+///     let res = create_text_response(&state, StatusCode::OK, message);
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #   let router = build_simple_router(|route| route.get("/").to(handler));
+/// #   let test_server = TestServer::new(router).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://localhost/?type=push&tag=a&tag=b+c")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "Some(\"push\") [\"a\", \"b c\"]");
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct QueryParams {
+    pairs: Vec<(String, Option<FormUrlDecoded>)>,
+    // Keys which were provided without a value, and so aren't mapped for extraction.
+    bare_keys: Vec<String>,
+}
 
-    if let Some(query) = query {
-        let pairs = query.split(is_separator).filter(|pair| pair.contains("="));
+impl QueryParams {
+    /// Parses the pairs of a query string.
+    pub fn parse(query: Option<&str>) -> QueryParams {
+        let mut pairs = Vec::new();
+        let mut bare_keys = Vec::new();
+
+        if let Some(query) = query {
+            for p in query.split(is_separator).filter(|pair| !pair.is_empty()) {
+                let mut sp = p.splitn(2, '=');
+                let (k, v) = (sp.next().unwrap(), sp.next());
+
+                if let Ok(k) = form_url_decode(k) {
+                    match v {
+                        Some(v) => pairs.push((k, FormUrlDecoded::new(v))),
+                        None => bare_keys.push(k),
+                    }
+                }
+            }
+        }
+
+        QueryParams { pairs, bare_keys }
+    }
 
-        for p in pairs {
-            let mut sp = p.splitn(2, '=');
-            let (k, v) = (sp.next().unwrap(), sp.next().unwrap());
+    /// Determines if the query string contains the key, with or without a value.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.pairs.iter().any(|&(ref k, _)| k == key) || self.bare_keys.iter().any(|k| k == key)
+    }
 
-            if let Ok(k) = form_url_decode(k) {
-                let vec = query_string_mapping.entry(k).or_insert_with(Vec::new);
-                if let Some(dv) = FormUrlDecoded::new(v) {
-                    vec.push(dv);
-                }
-            };
+    /// The first value provided for the key, if any.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_all(key).into_iter().next()
+    }
+
+    /// Each value provided for the key, in the order they appear.
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.iter()
+            .filter(|&(k, _)| k == key)
+            .map(|(_, v)| v)
+            .collect()
+    }
+
+    /// Iterates over the decoded key and value of each pair, in the order they appear.
+    pub fn iter<'a>(&'a self) -> Box<Iterator<Item = (&'a str, &'a str)> + 'a> {
+        Box::new(
+            self.pairs
+                .iter()
+                .filter_map(|&(ref k, ref v)| v.as_ref().map(|v| (k.as_str(), v.as_ref()))),
+        )
+    }
+
+    /// Provides a mapping of keys to values, as described by `split`.
+    pub(crate) fn mapping(&self, options: QueryStringOptions) -> QueryStringMapping {
+        let mut query_string_mapping = QueryStringMapping::new();
+
+        for &(ref k, ref v) in &self.pairs {
+            let mut k = k.clone();
+            if options.bracket_suffix() && k.ends_with("[]") {
+                let len = k.len() - 2;
+                k.truncate(len);
+            }
+
+            let vec = query_string_mapping.entry(k).or_insert_with(Vec::new);
+            if let Some(ref dv) = *v {
+                vec.push(dv.clone());
+            }
         }
+
+        query_string_mapping
+    }
+}
+
+/// Stores the `QueryParams` parsed from the `Uri` in `State`, unless they're already present.
+pub(crate) fn put_query_params(state: &mut State) {
+    if !state.has::<QueryParams>() {
+        let params = QueryParams::parse(Uri::borrow_from(state).query());
+        state.put(params);
     }
+}
 
-    query_string_mapping
+/// Splits a query string into pairs and provides a mapping of keys to values.
+///
+/// For keys which are represented 1..n times in the query string the mapped `Vec` will be
+/// populated with each value provided, in the order they appear. When
+/// `QueryStringOptions::bracket_suffix` is set, a `[]` suffix is removed from each key, so that
+/// values for `tag[]` and `tag` are mapped together.
+///
+/// Keys that are provided but with no value associated are skipped.
+#[cfg(test)]
+pub(crate) fn split<'r>(query: Option<&'r str>, options: QueryStringOptions) -> QueryStringMapping {
+    QueryParams::parse(query).mapping(options)
 }
 
 fn is_separator(c: char) -> bool {
@@ -58,30 +176,80 @@ mod tests {
         pairs
     }
 
+    fn split_default(query: Option<&str>) -> QueryStringMapping {
+        split(query, QueryStringOptions::default())
+    }
+
     #[test]
     fn query_string_mapping_tests() {
-        let qsm = split(Some("a=b&c=d&e=f"));
+        let qsm = split_default(Some("a=b&c=d&e=f"));
         assert_eq!(
             to_pairs(&qsm),
             vec![("a", vec!["b"]), ("c", vec!["d"]), ("e", vec!["f"])],
         );
 
-        let qsm = split(Some("a=b&a=d&e=f"));
+        let qsm = split_default(Some("a=b&a=d&e=f"));
         assert_eq!(
             to_pairs(&qsm),
             vec![("a", vec!["b", "d"]), ("e", vec!["f"])],
         );
 
-        let qsm = split(Some("a&b"));
+        let qsm = split_default(Some("a&b"));
         assert_eq!(to_pairs(&qsm), vec![],);
 
-        let qsm = split(Some("a=b;c=d&e=f"));
+        let qsm = split_default(Some("a=b;c=d&e=f"));
         assert_eq!(
             to_pairs(&qsm),
             vec![("a", vec!["b"]), ("c", vec!["d"]), ("e", vec!["f"])],
         );
 
-        let qsm = split(Some("a=b=c&d=e"));
+        let qsm = split_default(Some("a=b=c&d=e"));
         assert_eq!(to_pairs(&qsm), vec![("a", vec!["b=c"]), ("d", vec!["e"])],);
     }
+
+    #[test]
+    fn bracket_suffix_tests() {
+        let query = Some("tag[]=a&tag=b&tag%5B%5D=c&other[]=d");
+
+        let qsm = split_default(query);
+        assert_eq!(
+            to_pairs(&qsm),
+            vec![
+                ("other[]", vec!["d"]),
+                ("tag", vec!["b"]),
+                ("tag[]", vec!["a", "c"]),
+            ],
+        );
+
+        let qsm = split(query, QueryStringOptions::new().with_bracket_suffix(true));
+        assert_eq!(
+            to_pairs(&qsm),
+            vec![("other", vec!["d"]), ("tag", vec!["a", "b", "c"])],
+        );
+
+        // Order of appearance is retained for deserializing into a `Vec`.
+        let values: Vec<&str> = qsm["tag"].iter().map(|v| v.as_ref()).collect();
+        assert_eq!(values, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn query_params_tests() {
+        let params = QueryParams::parse(Some("type=push&debug&tag=a&tag=b+c&bad=%FF&%FF=x"));
+
+        assert!(params.contains_key("type"));
+        assert!(params.contains_key("bad"));
+        assert!(params.contains_key("debug"));
+        assert!(!params.contains_key("missing"));
+
+        assert_eq!(params.get("type"), Some("push"));
+        assert_eq!(params.get("bad"), None);
+        assert_eq!(params.get("debug"), None);
+        assert_eq!(params.get_all("tag"), vec!["a", "b c"]);
+        assert_eq!(
+            params.iter().collect::<Vec<_>>(),
+            vec![("type", "push"), ("tag", "a"), ("tag", "b c")]
+        );
+
+        assert!(!QueryParams::parse(None).contains_key("type"));
+    }
 }
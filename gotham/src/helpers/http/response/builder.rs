@@ -0,0 +1,406 @@
+//! Defines a chainable builder for `Response` values.
+
+use http::{self, HttpTryFrom};
+use hyper::body::Payload;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH};
+use hyper::{Body, Method, Response, StatusCode};
+use mime::{self, Mime};
+
+#[cfg(feature = "serde_json")]
+use serde::Serialize;
+#[cfg(feature = "serde_json")]
+use serde_json;
+
+use helpers::http::response::{check_charset, extend_response, merge_headers};
+use state::{FromState, State};
+
+/// Begins building a `Response` to the request represented by `state`.
+///
+/// The `Response` receives the same standard headers as one created by `create_response`, and
+/// has a status of `200 OK` unless `ResponseBuilder::status` is called.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::{CONTENT_TYPE, LOCATION};
+/// # use gotham::helpers::http::header::X_REQUEST_ID;
+/// # use gotham::helpers::http::response::response;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let res = response(&state)
+///         .status(StatusCode::CREATED)
+///         .bytes(b"{\"id\":1}".to_vec(), mime::APPLICATION_JSON)
+///         .header(LOCATION, "/widgets/1")
+///         .build()
+///         .unwrap();
+///
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::CREATED);
+/// #     assert!(response.headers().get(X_REQUEST_ID).is_some());
+/// #     assert_eq!(response.headers().get(LOCATION).unwrap(), "/widgets/1");
+/// #     assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "application/json");
+/// #     assert_eq!(response.read_body().unwrap(), b"{\"id\":1}");
+/// # }
+/// ```
+///
+/// A plain text `Response` with no further customization:
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::CONTENT_TYPE;
+/// # use gotham::helpers::http::response::response;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let res = response(&state).string("Hello, world!").build().unwrap();
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::OK);
+/// #     assert_eq!(
+/// #         response.headers().get(CONTENT_TYPE).unwrap(),
+/// #         "text/plain; charset=utf-8"
+/// #     );
+/// #     assert_eq!(response.read_utf8_body().unwrap(), "Hello, world!");
+/// # }
+/// ```
+pub fn response(state: &State) -> ResponseBuilder {
+    ResponseBuilder {
+        state,
+        status: StatusCode::OK,
+        body: None,
+        headers: HeaderMap::new(),
+        error: None,
+    }
+}
+
+/// A chainable builder for `Response` values, created by `response`.
+pub struct ResponseBuilder<'a> {
+    state: &'a State,
+    status: StatusCode,
+    body: Option<(Body, Mime)>,
+    headers: HeaderMap,
+    error: Option<http::Error>,
+}
+
+impl<'a> ResponseBuilder<'a> {
+    /// Sets the status of the `Response`.
+    pub fn status(self, status: StatusCode) -> Self {
+        ResponseBuilder { status, ..self }
+    }
+
+    /// Sets the body of the `Response`, along with its `Content-Type`.
+    ///
+    /// A warning is logged when a `text/*` type without a `charset` is used with a non-ASCII
+    /// body. Prefer `ResponseBuilder::string` or `ResponseBuilder::html` for text.
+    pub fn bytes<B>(self, data: B, mime: Mime) -> Self
+    where
+        B: Into<Vec<u8>>,
+    {
+        let data = data.into();
+        check_charset(self.state, &mime, &data);
+        self.body(Body::from(data), mime)
+    }
+
+    /// Sets the body of the `Response` to the given text, with a `Content-Type` of
+    /// `text/plain; charset=utf-8`.
+    pub fn string<S>(self, text: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.body(Body::from(text.into()), mime::TEXT_PLAIN_UTF_8)
+    }
+
+    /// Sets the body of the `Response` to the given HTML, with a `Content-Type` of
+    /// `text/html; charset=utf-8`.
+    pub fn html<S>(self, html: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.body(Body::from(html.into()), mime::TEXT_HTML_UTF_8)
+    }
+
+    /// Sets the body of the `Response` to the JSON representation of `value`, with a
+    /// `Content-Type` of `application/json`.
+    #[cfg(feature = "serde_json")]
+    pub fn json<T>(self, value: &T) -> Result<Self, serde_json::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let data = serde_json::to_vec(value)?;
+        Ok(self.bytes(data, mime::APPLICATION_JSON))
+    }
+
+    /// Sets the body of the `Response`, along with its `Content-Type`. The `Body` may be a stream
+    /// which produces data after the `Response` is returned.
+    ///
+    /// HTTP/1.1 clients receive a stream using chunked encoding. HTTP/1.0 clients do not support
+    /// chunked encoding, so the stream is instead delimited by closing the connection.
+    pub fn body(self, body: Body, mime: Mime) -> Self {
+        ResponseBuilder {
+            body: Some((body, mime)),
+            ..self
+        }
+    }
+
+    /// Adds a header to the `Response`. Headers added here replace any header of the same name
+    /// set by Gotham, except for `Content-Length` which is ignored so that it always agrees with
+    /// the body. Adding the same header more than once retains each value.
+    ///
+    /// An invalid name or value is reported by `ResponseBuilder::build`.
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        HeaderName: HttpTryFrom<K>,
+        HeaderValue: HttpTryFrom<V>,
+    {
+        if self.error.is_none() {
+            let name: Result<HeaderName, http::Error> =
+                HeaderName::try_from(key).map_err(Into::into);
+            let value: Result<HeaderValue, http::Error> =
+                HeaderValue::try_from(value).map_err(Into::into);
+
+            match (name, value) {
+                (Ok(name), Ok(value)) => {
+                    self.headers.append(name, value);
+                }
+                (Err(e), _) | (_, Err(e)) => self.error = Some(e),
+            }
+        }
+
+        self
+    }
+
+    /// Adds each of the headers to the `Response`, in the same way as `ResponseBuilder::header`.
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        for (name, value) in headers.iter() {
+            self.headers.append(name.clone(), value.clone());
+        }
+
+        self
+    }
+
+    /// Builds the `Response`, failing if an invalid header was provided.
+    ///
+    /// The body is omitted when responding to a `HEAD` request, while its length is still sent
+    /// as the `Content-Length` header.
+    pub fn build(self) -> Result<Response<Body>, http::Error> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        let (body, mime) = match self.body {
+            Some((body, mime)) => (Some(body), Some(mime)),
+            None => (None, None),
+        };
+
+        let mut builder = Response::builder();
+        extend_response(self.state, self.status, &mut builder, mime);
+
+        let (body, head_length) = match body {
+            Some(body) if *Method::borrow_from(self.state) != Method::HEAD => (body, None),
+            Some(body) => (Body::empty(), body.content_length()),
+            None => (Body::empty(), None),
+        };
+
+        let mut res = builder.body(body)?;
+        merge_headers(res.headers_mut(), &self.headers);
+
+        if let Some(len) = head_length {
+            res.headers_mut()
+                .insert(CONTENT_LENGTH, HeaderValue::from(len));
+        }
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io;
+
+    use futures::{stream, Future, Stream};
+    use hyper::header::{CONTENT_TYPE, LOCATION, TRANSFER_ENCODING};
+    use hyper::Version;
+
+    use helpers::http::header::X_REQUEST_ID;
+    use helpers::http::response::create_response;
+    use state::set_request_id;
+    use test::TestServer;
+
+    fn response_state(method: Method) -> State {
+        let mut state = State::new();
+        state.put(method);
+        state.put(HeaderMap::new());
+        set_request_id(&mut state);
+        state
+    }
+
+    fn into_parts(res: Response<Body>) -> (StatusCode, HeaderMap, Vec<u8>) {
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.into_body().concat2().wait().unwrap().to_vec();
+        (status, headers, body)
+    }
+
+    #[test]
+    fn parity_with_create_response() {
+        for method in vec![Method::GET, Method::HEAD] {
+            let state = response_state(method);
+
+            let created = create_response(
+                &state,
+                StatusCode::ACCEPTED,
+                Some((b"data".to_vec(), mime::TEXT_PLAIN)),
+            );
+            let built = response(&state)
+                .status(StatusCode::ACCEPTED)
+                .bytes(b"data".to_vec(), mime::TEXT_PLAIN)
+                .build()
+                .unwrap();
+            assert_eq!(into_parts(created), into_parts(built));
+
+            let created = create_response(&state, StatusCode::NO_CONTENT, None);
+            let built = response(&state)
+                .status(StatusCode::NO_CONTENT)
+                .build()
+                .unwrap();
+            assert_eq!(into_parts(created), into_parts(built));
+        }
+    }
+
+    #[test]
+    fn head_responses_keep_content_length() {
+        let state = response_state(Method::HEAD);
+        let res = response(&state)
+            .bytes(b"data".to_vec(), mime::TEXT_PLAIN)
+            .build()
+            .unwrap();
+
+        let (_, headers, body) = into_parts(res);
+        assert_eq!(headers.get(CONTENT_LENGTH).unwrap(), "4");
+        assert!(body.is_empty());
+    }
+
+    #[test]
+    fn headers_follow_standard_headers() {
+        let state = response_state(Method::GET);
+
+        let res = response(&state)
+            .string("created")
+            .header(LOCATION, "/widgets/1")
+            .header(X_REQUEST_ID, "overridden")
+            .build()
+            .unwrap();
+
+        let (status, headers, body) = into_parts(res);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers.get(LOCATION).unwrap(), "/widgets/1");
+        assert_eq!(headers.get(X_REQUEST_ID).unwrap(), "overridden");
+        assert_eq!(
+            headers.get(CONTENT_TYPE).unwrap(),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(body, b"created");
+    }
+
+    #[test]
+    fn invalid_header_is_reported() {
+        let state = response_state(Method::GET);
+
+        let result = response(&state)
+            .header("bad header", "value")
+            .header(LOCATION, "/valid")
+            .build();
+        assert!(result.is_err());
+    }
+
+    fn streaming_handler(state: State) -> (State, Response<Body>) {
+        let chunks: Vec<Result<&'static str, io::Error>> = vec![Ok("first, "), Ok("second")];
+        let res = response(&state)
+            .body(
+                Body::wrap_stream(stream::iter_result(chunks)),
+                mime::TEXT_PLAIN,
+            )
+            .build()
+            .unwrap();
+
+        (state, res)
+    }
+
+    #[test]
+    fn streamed_body_framing_follows_version() {
+        let test_server = TestServer::new(|| Ok(streaming_handler)).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .with_version(Version::HTTP_10)
+            .perform()
+            .unwrap();
+        // The stream is delimited by closing the connection, as hyper does for HTTP/1.0.
+        assert!(response.headers().get(TRANSFER_ENCODING).is_none());
+        assert_eq!(response.read_utf8_body().unwrap(), "first, second");
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+        assert_eq!(
+            response.headers().get(TRANSFER_ENCODING).unwrap(),
+            "chunked"
+        );
+        assert_eq!(response.read_utf8_body().unwrap(), "first, second");
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json_body() {
+        #[derive(Serialize)]
+        struct Widget {
+            id: u32,
+        }
+
+        let state = response_state(Method::GET);
+        let res = response(&state)
+            .json(&Widget { id: 1 })
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let (_, headers, body) = into_parts(res);
+        assert_eq!(headers.get(CONTENT_TYPE).unwrap(), "application/json");
+        assert_eq!(body, b"{\"id\":1}");
+    }
+}
@@ -0,0 +1,404 @@
+//! Defines a builder for `Link` header values, as described by [RFC 5988][rfc].
+//!
+//! [rfc]: https://tools.ietf.org/html/rfc5988
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use hyper::header::{HeaderValue, InvalidHeaderValue};
+use hyper::Uri;
+use url::form_urlencoded::byte_serialize;
+
+use helpers::http::form_url_decode;
+use helpers::http::request::{absolute_url, AbsoluteUrlError};
+use state::{FromState, State};
+
+/// Builds a `Link` header value whose links refer to the current request URI, each with some of
+/// its query parameters replaced. This is typically used to link to other pages of a paginated
+/// collection.
+///
+/// Each link retains the path and query string of the request, with the given parameters replacing
+/// any of the same name (or being added, when not already present). Keys and values are
+/// percent-encoded as required.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::LINK;
+/// # use gotham::helpers::http::response::{response, LinkHeader};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let link = LinkHeader::new(&state)
+///         .paginate(2, 20, 95)
+///         .build()
+///         .unwrap();
+///
+///     let res = response(&state).header(LINK, link).build().unwrap();
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/widgets?q=a%26b&page=2")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::OK);
+/// #     assert_eq!(
+/// #         response.headers().get(LINK).unwrap(),
+/// #         "</widgets?q=a%26b&page=1&per_page=20>; rel=\"first\", \
+/// #          </widgets?q=a%26b&page=1&per_page=20>; rel=\"prev\", \
+/// #          </widgets?q=a%26b&page=3&per_page=20>; rel=\"next\", \
+/// #          </widgets?q=a%26b&page=5&per_page=20>; rel=\"last\""
+/// #     );
+/// # }
+/// ```
+pub struct LinkHeader<'a> {
+    state: &'a State,
+    links: Vec<(String, Vec<(String, String)>)>,
+    absolute: bool,
+    error: Option<LinkError>,
+}
+
+/// The reason a `Link` header value could not be built by `LinkHeader::build`.
+#[derive(Debug)]
+pub enum LinkError {
+    /// `LinkHeader::paginate` was given a `per_page` of zero.
+    InvalidPerPage,
+
+    /// The relation type of a link was empty, or contained characters which can't appear in a
+    /// quoted `rel` parameter (such as `"` or a line break).
+    InvalidRelation(String),
+
+    /// An absolute link could not be built, since the host of the request is missing or invalid.
+    AbsoluteUrl(AbsoluteUrlError),
+
+    /// The links could not be represented as a header value.
+    InvalidHeaderValue(InvalidHeaderValue),
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LinkError::InvalidPerPage => f.write_str("per_page must be greater than zero"),
+            LinkError::InvalidRelation(ref rel) => write!(f, "invalid relation type: {:?}", rel),
+            LinkError::AbsoluteUrl(ref e) => write!(f, "unable to build absolute link: {}", e),
+            LinkError::InvalidHeaderValue(ref e) => write!(f, "invalid Link header: {}", e),
+        }
+    }
+}
+
+impl Error for LinkError {
+    fn description(&self) -> &str {
+        match *self {
+            LinkError::InvalidPerPage => "per_page must be greater than zero",
+            LinkError::InvalidRelation(_) => "invalid relation type",
+            LinkError::AbsoluteUrl(_) => "unable to build absolute link",
+            LinkError::InvalidHeaderValue(_) => "invalid Link header",
+        }
+    }
+}
+
+impl From<AbsoluteUrlError> for LinkError {
+    fn from(e: AbsoluteUrlError) -> LinkError {
+        LinkError::AbsoluteUrl(e)
+    }
+}
+
+impl<'a> LinkHeader<'a> {
+    /// Begins building a `Link` header value for the request represented by `state`.
+    pub fn new(state: &'a State) -> LinkHeader<'a> {
+        LinkHeader {
+            state,
+            links: Vec::new(),
+            absolute: false,
+            error: None,
+        }
+    }
+
+    /// Adds a link with the given relation type, replacing the named query parameters of the
+    /// request URI. The query parameters are percent-encoded, while the relation type is
+    /// validated by `build`.
+    pub fn link<R, I, K, V>(mut self, rel: R, params: I) -> Self
+    where
+        R: Into<String>,
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: ToString,
+    {
+        let params = params
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.to_string()))
+            .collect();
+
+        self.links.push((rel.into(), params));
+        self
+    }
+
+    /// Adds the `first`, `prev`, `next` and `last` links for a collection of `total` items, where
+    /// the request is for the 1-based `page` holding `per_page` items. The `prev` and `next`
+    /// links are omitted on the first and last pages respectively.
+    ///
+    /// Each link sets the `page` and `per_page` query parameters. A `per_page` of zero causes
+    /// `build` to fail with `LinkError::InvalidPerPage`.
+    pub fn paginate(mut self, page: u64, per_page: u64, total: u64) -> Self {
+        if per_page == 0 {
+            self.error.get_or_insert(LinkError::InvalidPerPage);
+            return self;
+        }
+
+        let last = (total / per_page + (total % per_page != 0) as u64).max(1);
+        let page = page.max(1).min(last);
+
+        let params = |page: u64| vec![("page", page), ("per_page", per_page)];
+
+        let mut link = self.link("first", params(1));
+
+        if page > 1 {
+            link = link.link("prev", params(page - 1));
+        }
+
+        if page < last {
+            link = link.link("next", params(page + 1));
+        }
+
+        link.link("last", params(last))
+    }
+
+    /// Sets whether each link is an absolute URL, built by `absolute_url` so that the scheme and
+    /// host are those used by the client. Links are relative to the host by default.
+    pub fn absolute(self, absolute: bool) -> Self {
+        LinkHeader { absolute, ..self }
+    }
+
+    /// Builds the `Link` header value, failing when `paginate` was given a `per_page` of zero, a
+    /// relation type is invalid, or absolute links were requested and the host of the request is
+    /// missing or invalid.
+    pub fn build(self) -> Result<HeaderValue, LinkError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        let uri = Uri::borrow_from(self.state);
+
+        let mut links = Vec::with_capacity(self.links.len());
+
+        for (rel, params) in self.links {
+            if !is_relation_type(&rel) {
+                return Err(LinkError::InvalidRelation(rel));
+            }
+
+            let target = with_params(uri.path(), uri.query(), &params);
+
+            let target = if self.absolute {
+                absolute_url(self.state, &target)?.to_string()
+            } else {
+                target
+            };
+
+            links.push(format!("<{}>; rel=\"{}\"", target, rel));
+        }
+
+        HeaderValue::from_str(&links.join(", ")).map_err(LinkError::InvalidHeaderValue)
+    }
+}
+
+/// Determines if `rel` can be sent as the quoted `rel` parameter of a link: one or more relation
+/// types (e.g. `next`, or a URI for an extension type) separated by single spaces.
+fn is_relation_type(rel: &str) -> bool {
+    rel.split(' ').all(|rel| {
+        !rel.is_empty()
+            && rel.bytes().all(|b| match b {
+                b'"' | b'\\' => false,
+                0x21...0x7e => true,
+                _ => false,
+            })
+    })
+}
+
+/// Replaces or adds each of `params` in the query string, retaining the order and encoding of
+/// the parameters which are not replaced.
+fn with_params(path: &str, query: Option<&str>, params: &[(String, String)]) -> String {
+    let mut pairs: Vec<String> = Vec::new();
+    let mut replaced = vec![false; params.len()];
+
+    for pair in query.unwrap_or("").split('&').filter(|p| !p.is_empty()) {
+        let key = pair.splitn(2, '=').next().unwrap_or("");
+        let key = form_url_decode(key).unwrap_or_else(|_| key.to_owned());
+
+        match params.iter().position(|&(ref k, _)| *k == key) {
+            Some(i) if replaced[i] => (),
+            Some(i) => {
+                replaced[i] = true;
+                pairs.push(encode_pair(&params[i]));
+            }
+            None => pairs.push(pair.to_owned()),
+        }
+    }
+
+    for (i, param) in params.iter().enumerate() {
+        if !replaced[i] {
+            pairs.push(encode_pair(param));
+        }
+    }
+
+    if pairs.is_empty() {
+        path.to_owned()
+    } else {
+        format!("{}?{}", path, pairs.join("&"))
+    }
+}
+
+fn encode_pair(&(ref key, ref value): &(String, String)) -> String {
+    format!(
+        "{}={}",
+        byte_serialize(key.as_bytes()).collect::<String>(),
+        byte_serialize(value.as_bytes()).collect::<String>()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::HeaderMap;
+
+    fn state(uri: &str) -> State {
+        let mut headers = HeaderMap::new();
+        headers.insert("host", "example.com".parse().unwrap());
+
+        let mut state = State::new();
+        state.put(uri.parse::<Uri>().unwrap());
+        state.put(headers);
+        state
+    }
+
+    #[test]
+    fn parameters_are_replaced() {
+        let state = state("/widgets?sort=name&page=2&tag=a&page=3");
+        let link = LinkHeader::new(&state)
+            .link("next", vec![("page", 4)])
+            .link("sorted", vec![("sort", "size"), ("order", "desc")])
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            link,
+            "</widgets?sort=name&page=4&tag=a>; rel=\"next\", \
+             </widgets?sort=size&page=2&tag=a&page=3&order=desc>; rel=\"sorted\""
+        );
+    }
+
+    #[test]
+    fn reserved_characters_are_encoded() {
+        let state = state("/search?q=caf%C3%A9+au+lait&f%5Bx%5D=1");
+        let link = LinkHeader::new(&state)
+            .link("alternate", vec![("q", "a&b=c d/é"), ("f[x]", "#2")])
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            link,
+            "</search?q=a%26b%3Dc+d%2F%C3%A9&f%5Bx%5D=%232>; rel=\"alternate\""
+        );
+    }
+
+    #[test]
+    fn pagination() {
+        let state = state("/widgets");
+
+        let first_page = LinkHeader::new(&state).paginate(1, 10, 25).build().unwrap();
+        assert_eq!(
+            first_page,
+            "</widgets?page=1&per_page=10>; rel=\"first\", \
+             </widgets?page=2&per_page=10>; rel=\"next\", \
+             </widgets?page=3&per_page=10>; rel=\"last\""
+        );
+
+        let last_page = LinkHeader::new(&state).paginate(3, 10, 25).build().unwrap();
+        assert_eq!(
+            last_page,
+            "</widgets?page=1&per_page=10>; rel=\"first\", \
+             </widgets?page=2&per_page=10>; rel=\"prev\", \
+             </widgets?page=3&per_page=10>; rel=\"last\""
+        );
+
+        let empty = LinkHeader::new(&state).paginate(1, 10, 0).build().unwrap();
+        assert_eq!(
+            empty,
+            "</widgets?page=1&per_page=10>; rel=\"first\", \
+             </widgets?page=1&per_page=10>; rel=\"last\""
+        );
+    }
+
+    #[test]
+    fn pagination_of_large_collections() {
+        let state = state("/widgets");
+
+        let link = LinkHeader::new(&state)
+            .paginate(2, 10, u64::max_value())
+            .build()
+            .unwrap();
+        assert_eq!(
+            link,
+            "</widgets?page=1&per_page=10>; rel=\"first\", \
+             </widgets?page=1&per_page=10>; rel=\"prev\", \
+             </widgets?page=3&per_page=10>; rel=\"next\", \
+             </widgets?page=1844674407370955162&per_page=10>; rel=\"last\""
+        );
+
+        match LinkHeader::new(&state).paginate(1, 0, 25).build() {
+            Err(LinkError::InvalidPerPage) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn invalid_relation_types_are_rejected() {
+        let state = state("/widgets");
+
+        for rel in &[
+            "",
+            "next\r\nSet-Cookie: a=b",
+            "next\"; title=\"x",
+            "next  prev",
+        ] {
+            match LinkHeader::new(&state)
+                .link(*rel, vec![("page", 2)])
+                .build()
+            {
+                Err(LinkError::InvalidRelation(ref invalid)) if invalid == rel => (),
+                other => panic!("unexpected result for {:?}: {:?}", rel, other),
+            }
+        }
+
+        let link = LinkHeader::new(&state)
+            .link("next http://example.com/rel/more", vec![("page", 2)])
+            .build()
+            .unwrap();
+        assert_eq!(
+            link,
+            "</widgets?page=2>; rel=\"next http://example.com/rel/more\""
+        );
+    }
+
+    #[test]
+    fn absolute_links() {
+        let state = state("/widgets?page=1");
+        let link = LinkHeader::new(&state)
+            .absolute(true)
+            .link("next", vec![("page", 2)])
+            .build()
+            .unwrap();
+
+        assert_eq!(link, "<http://example.com/widgets?page=2>; rel=\"next\"");
+    }
+}
@@ -0,0 +1,290 @@
+//! Defines helpers for building `Location` header values which are safe to use in redirects.
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use hyper::Uri;
+use url::form_urlencoded::byte_serialize;
+use url::percent_encoding::{utf8_percent_encode, SIMPLE_ENCODE_SET};
+
+/// A `Location` value which has been checked to contain no control characters, and so cannot be
+/// used to inject headers into a response. Created by `redirect_location` or `safe_next_url`, and
+/// accepted by `create_permanent_redirect`, `create_temporary_redirect` and
+/// `set_redirect_headers`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RedirectLocation {
+    location: String,
+}
+
+impl RedirectLocation {
+    /// The `Location` value.
+    pub fn as_str(&self) -> &str {
+        &self.location
+    }
+}
+
+impl Display for RedirectLocation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(&self.location)
+    }
+}
+
+impl From<RedirectLocation> for Cow<'static, str> {
+    fn from(location: RedirectLocation) -> Cow<'static, str> {
+        Cow::Owned(location.location)
+    }
+}
+
+/// The error returned when a `RedirectLocation` could not be built, because the base path
+/// contained a control character.
+#[derive(Debug)]
+pub struct InvalidLocation {
+    _priv: (),
+}
+
+impl Display for InvalidLocation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl Error for InvalidLocation {
+    fn description(&self) -> &str {
+        "redirect location contains a control character"
+    }
+}
+
+/// Begins building a `RedirectLocation` from the given base path (or URL), to which query
+/// parameters can be added.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::LOCATION;
+/// # use gotham::helpers::http::response::{create_temporary_redirect, redirect_location};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let location = redirect_location("/login")
+///         .query("next", "/cart?items=3&coupon=a b")
+///         .build()
+///         .unwrap();
+///
+///     let res = create_temporary_redirect(&state, location);
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://localhost/")
+/// #       .perform()
+/// #       .unwrap();
+/// #
+/// #   assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+/// #   assert_eq!(
+/// #       response.headers().get(LOCATION).unwrap(),
+/// #       "/login?next=%2Fcart%3Fitems%3D3%26coupon%3Da+b"
+/// #   );
+/// # }
+/// ```
+pub fn redirect_location<P: Into<String>>(base: P) -> RedirectLocationBuilder {
+    RedirectLocationBuilder {
+        base: base.into(),
+        query: Vec::new(),
+    }
+}
+
+/// A builder for `RedirectLocation` values, created by `redirect_location`.
+#[derive(Clone, Debug)]
+pub struct RedirectLocationBuilder {
+    base: String,
+    query: Vec<String>,
+}
+
+impl RedirectLocationBuilder {
+    /// Adds a query parameter. The key and value are percent-encoded, so they may contain any
+    /// characters.
+    pub fn query<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.query.push(format!(
+            "{}={}",
+            byte_serialize(key.as_ref().as_bytes()).collect::<String>(),
+            byte_serialize(value.as_ref().as_bytes()).collect::<String>()
+        ));
+        self
+    }
+
+    /// Builds the `RedirectLocation`, failing when the base path contains a control character
+    /// (such as a CR or LF). Spaces and non-ASCII characters in the base path are percent-encoded.
+    pub fn build(self) -> Result<RedirectLocation, InvalidLocation> {
+        if self.base.chars().any(|c| c.is_control()) {
+            return Err(InvalidLocation { _priv: () });
+        }
+
+        let mut location = utf8_percent_encode(&self.base, SIMPLE_ENCODE_SET)
+            .to_string()
+            .replace(' ', "%20");
+
+        if !self.query.is_empty() {
+            location.push(if location.contains('?') { '&' } else { '?' });
+            location.push_str(&self.query.join("&"));
+        }
+
+        Ok(RedirectLocation { location })
+    }
+}
+
+/// Validates a user-supplied redirect target, such as the `next` parameter used to return to a
+/// page after signing in, so that it cannot redirect the user to another site.
+///
+/// A relative path must begin with a single `/`; protocol-relative references such as
+/// `//example.com` (or `/\example.com`, which browsers treat the same way) are rejected. An
+/// absolute URL must use `http` or `https`, and its host must be one of `allowed_hosts` (compared
+/// without regard to case). `None` is returned for any other value, or one containing a control
+/// character.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// #
+/// # use gotham::helpers::http::response::safe_next_url;
+/// #
+/// # fn main() {
+/// let allowed = ["example.com"];
+///
+/// assert!(safe_next_url("/account?tab=2", &allowed).is_some());
+/// assert!(safe_next_url("https://example.com/account", &allowed).is_some());
+/// assert!(safe_next_url("https://evil.example.net/", &allowed).is_none());
+/// assert!(safe_next_url("//evil.example.net/", &allowed).is_none());
+/// # }
+/// ```
+pub fn safe_next_url(next: &str, allowed_hosts: &[&str]) -> Option<RedirectLocation> {
+    if next.chars().any(|c| c.is_control() || c == '\\') {
+        return None;
+    }
+
+    if next.starts_with('/') {
+        if next.starts_with("//") {
+            return None;
+        }
+    } else {
+        let uri: Uri = next.parse().ok()?;
+
+        match uri.scheme_part().map(|s| s.as_str()) {
+            Some("http") | Some("https") => (),
+            _ => return None,
+        }
+
+        let host = uri.host()?;
+        if !allowed_hosts.iter().any(|h| h.eq_ignore_ascii_case(host)) {
+            return None;
+        }
+    }
+
+    redirect_location(next).build().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::{HeaderMap, LOCATION, SET_COOKIE};
+    use hyper::{Body, Method, Response};
+
+    use helpers::http::response::create_permanent_redirect;
+    use state::{set_request_id, State};
+
+    #[test]
+    fn query_pairs_are_encoded() {
+        let location = redirect_location("/search?page=2")
+            .query("q", "a&b=c\r\nSet-Cookie: x=1")
+            .query("tag[]", "ü")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            location.as_str(),
+            "/search?page=2&q=a%26b%3Dc%0D%0ASet-Cookie%3A+x%3D1&tag%5B%5D=%C3%BC"
+        );
+
+        let location = redirect_location("/caf\u{e9} menu").build().unwrap();
+        assert_eq!(location.as_str(), "/caf%C3%A9%20menu");
+    }
+
+    #[test]
+    fn injection_attempts_are_rejected() {
+        // A `next` value of `%0d%0aSet-Cookie:...` after the query string has been decoded.
+        for base in vec![
+            "/\r\nSet-Cookie: session=stolen",
+            "/home\nLocation: https://evil.example.net/",
+            "/tab\there",
+        ] {
+            assert!(redirect_location(base).build().is_err(), "{:?}", base);
+            assert!(
+                safe_next_url(base, &["example.com"]).is_none(),
+                "{:?}",
+                base
+            );
+        }
+
+        // An encoded CRLF remains encoded, and is harmless in the header.
+        let location = safe_next_url("/%0d%0aSet-Cookie:x=1", &[]).unwrap();
+        assert_eq!(location.as_str(), "/%0d%0aSet-Cookie:x=1");
+
+        let mut state = State::new();
+        state.put(Method::GET);
+        state.put(HeaderMap::new());
+        set_request_id(&mut state);
+
+        let res: Response<Body> = create_permanent_redirect(&state, location);
+        assert_eq!(res.headers().get_all(LOCATION).iter().count(), 1);
+        assert!(res.headers().get(SET_COOKIE).is_none());
+    }
+
+    #[test]
+    fn open_redirects_are_rejected() {
+        let allowed = ["example.com", "accounts.example.com"];
+
+        for next in vec![
+            "/",
+            "/account?tab=2#billing",
+            "http://example.com/",
+            "https://ACCOUNTS.example.com/settings",
+        ] {
+            assert_eq!(
+                safe_next_url(next, &allowed).map(|l| l.to_string()),
+                Some(next.to_owned()),
+                "{}",
+                next
+            );
+        }
+
+        for next in vec![
+            "",
+            "//evil.example.net/",
+            "/\\evil.example.net/",
+            "\\\\evil.example.net",
+            "https://evil.example.net/",
+            "https://example.com.evil.example.net/",
+            "https://example.com@evil.example.net/",
+            "javascript:alert(1)",
+            "ftp://example.com/",
+            "evil.example.net/path",
+            "account",
+        ] {
+            assert!(safe_next_url(next, &allowed).is_none(), "{}", next);
+        }
+    }
+}
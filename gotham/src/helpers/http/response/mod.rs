@@ -2,15 +2,25 @@
 
 use http::response;
 use hyper::header::{
-    HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, LOCATION, X_CONTENT_TYPE_OPTIONS,
-    X_FRAME_OPTIONS, X_XSS_PROTECTION,
+    HeaderMap, HeaderValue, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE, LOCATION, VARY,
+    X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS, X_XSS_PROTECTION,
 };
-use hyper::{Body, Method, Response, StatusCode};
-use mime::Mime;
+use hyper::{Body, Response, StatusCode};
+use mime::{self, Mime};
 use std::borrow::Cow;
 
 use helpers::http::header::X_REQUEST_ID;
-use state::{request_id, FromState, State};
+use state::{request_id, State};
+
+mod builder;
+mod link;
+mod location;
+
+pub use self::builder::{response, ResponseBuilder};
+pub use self::link::{LinkError, LinkHeader};
+pub use self::location::{
+    redirect_location, safe_next_url, InvalidLocation, RedirectLocation, RedirectLocationBuilder,
+};
 
 // constant strings to be used as header values
 const XFO_VALUE: &'static str = "DENY";
@@ -23,6 +33,9 @@ const XCTO_VALUE: &'static str = "nosniff";
 /// `create_response` utilises `extend_response`, which delegates to `set_headers` for setting
 /// security headers. See `set_headers` for information about the headers which are populated.
 ///
+/// This is a shorthand for `response`, which should be preferred when the `Response` needs
+/// additional customization.
+///
 /// # Examples
 ///
 /// ```rust
@@ -76,22 +89,203 @@ pub fn create_response(
     status: StatusCode,
     body: Option<(Vec<u8>, Mime)>,
 ) -> Response<Body> {
-    let mut builder = Response::builder();
+    response_with_body(state, status, body)
+        .build()
+        .expect("Response built from a compatible byte vector (Vec<u8>)")
+}
 
-    let (data, mime) = body
-        .map(|(data, mime)| (Some(data), Some(mime)))
-        .unwrap_or_else(|| (None, None));
+/// Creates a `Response` with a text body, in the same way as `create_response`. The
+/// `Content-Type` is always `text/plain; charset=utf-8`, so that clients do not need to guess the
+/// encoding.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+/// # use gotham::state::State;
+/// # use gotham::helpers::http::response::create_text_response;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let response = create_text_response(&state, StatusCode::OK, "Grüße");
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::OK);
+/// #     assert_eq!(
+/// #         response.headers().get(CONTENT_TYPE).unwrap(),
+/// #         "text/plain; charset=utf-8"
+/// #     );
+/// #     assert_eq!(response.headers().get(CONTENT_LENGTH).unwrap(), "7");
+/// # }
+/// ```
+pub fn create_text_response<B>(state: &State, status: StatusCode, body: B) -> Response<Body>
+where
+    B: Into<String>,
+{
+    response(state)
+        .status(status)
+        .string(body)
+        .build()
+        .expect("Response built from a compatible string")
+}
 
-    extend_response(state, status, &mut builder, mime);
+/// Creates a `Response` with an HTML body, in the same way as `create_response`. The
+/// `Content-Type` is always `text/html; charset=utf-8`, so that browsers do not need to sniff the
+/// encoding.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::CONTENT_TYPE;
+/// # use gotham::state::State;
+/// # use gotham::helpers::http::response::create_html_response;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let response = create_html_response(&state, StatusCode::OK, "<h1>Café</h1>");
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::OK);
+/// #     assert_eq!(
+/// #         response.headers().get(CONTENT_TYPE).unwrap(),
+/// #         "text/html; charset=utf-8"
+/// #     );
+/// # }
+/// ```
+pub fn create_html_response<B>(state: &State, status: StatusCode, body: B) -> Response<Body>
+where
+    B: Into<String>,
+{
+    response(state)
+        .status(status)
+        .html(body)
+        .build()
+        .expect("Response built from a compatible string")
+}
 
-    let method = Method::borrow_from(state);
-    let built = if data.is_some() && *method != Method::HEAD {
-        builder.body(data.unwrap().into())
-    } else {
-        builder.body(Body::empty())
-    };
+/// Logs a warning when a `text/*` body containing non-ASCII bytes is sent without a `charset`
+/// parameter, as clients will then guess the encoding (often incorrectly).
+pub(crate) fn check_charset(state: &State, mime: &Mime, body: &[u8]) {
+    if mime.type_() == mime::TEXT
+        && mime.get_param(mime::CHARSET).is_none()
+        && !body.is_ascii()
+    {
+        warn!(
+            "[{}] non-ASCII body sent as `{}` without a charset parameter",
+            request_id(state),
+            mime
+        );
+    }
+}
 
-    built.expect("Response built from a compatible byte vector (Vec<u8>)")
+/// Starts a `ResponseBuilder` with the arguments accepted by `create_response`.
+fn response_with_body(
+    state: &State,
+    status: StatusCode,
+    body: Option<(Vec<u8>, Mime)>,
+) -> ResponseBuilder {
+    let builder = response(state).status(status);
+
+    match body {
+        Some((data, mime)) => builder.bytes(data, mime),
+        None => builder,
+    }
+}
+
+/// Creates a `Response` in the same way as `create_response`, and then merges the provided
+/// headers into it.
+///
+/// Each header in `headers` replaces any value of the same name set by `create_response`, and
+/// headers with multiple values are retained in full. The exception is `Content-Length`, which is
+/// ignored so that it always agrees with the body.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::{HeaderMap, CACHE_CONTROL, CONTENT_TYPE};
+/// # use gotham::state::State;
+/// # use gotham::helpers::http::response::create_response_with_headers;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let mut headers = HeaderMap::new();
+///     headers.insert(CACHE_CONTROL, "max-age=3600".parse().unwrap());
+///
+///     let response = create_response_with_headers(
+///         &state,
+///         StatusCode::OK,
+///         Some((b"Hello, world!".to_vec(), mime::TEXT_PLAIN)),
+///         headers,
+///     );
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::OK);
+/// #     assert_eq!(response.headers().get(CACHE_CONTROL).unwrap(), "max-age=3600");
+/// #     assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "text/plain");
+/// # }
+/// ```
+pub fn create_response_with_headers(
+    state: &State,
+    status: StatusCode,
+    body: Option<(Vec<u8>, Mime)>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    response_with_body(state, status, body)
+        .headers(headers)
+        .build()
+        .expect("Response built from a compatible byte vector (Vec<u8>)")
+}
+
+/// Replaces each header in `target` which is named in `source`, except for `Content-Length`.
+fn merge_headers(target: &mut HeaderMap, source: &HeaderMap) {
+    for name in source.keys().filter(|name| *name != CONTENT_LENGTH) {
+        target.remove(name);
+
+        for value in source.get_all(name) {
+            target.append(name.clone(), value.clone());
+        }
+    }
 }
 
 /// Produces a simple empty `Response` with a `Location` header and a 301
@@ -186,6 +380,137 @@ pub fn create_temporary_redirect<B: Default, L: Into<Cow<'static, str>>>(
     res
 }
 
+/// Creates a `Response` which instructs the client to save the body as a file with the given
+/// name, by setting `Content-Disposition: attachment` along with `Content-Type` and
+/// `Content-Length`.
+///
+/// Any directory components of `filename` are discarded, and characters which are unsafe within a
+/// quoted header value are replaced. Names containing non-ASCII characters are additionally
+/// supplied in the RFC 5987 `filename*` form, so that clients supporting it receive the original
+/// name.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::{CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE};
+/// # use gotham::state::State;
+/// # use gotham::helpers::http::response::create_download_response;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let report = b"id,total\n1,100\n".to_vec();
+///
+///     let response = create_download_response(
+///         &state,
+///         StatusCode::OK,
+///         "text/csv".parse().unwrap(),
+///         report,
+///         "Übersicht 2018.csv",
+///     );
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::OK);
+/// #     assert_eq!(
+/// #         response.headers().get(CONTENT_DISPOSITION).unwrap(),
+/// #         "attachment; filename=\"_bersicht 2018.csv\"; filename*=UTF-8''%C3%9Cbersicht%202018.csv"
+/// #     );
+/// #     assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "text/csv");
+/// #     assert_eq!(response.headers().get(CONTENT_LENGTH).unwrap(), "15");
+/// # }
+/// ```
+pub fn create_download_response<F: AsRef<str>>(
+    state: &State,
+    status: StatusCode,
+    mime: Mime,
+    body: Vec<u8>,
+    filename: F,
+) -> Response<Body> {
+    let length = body.len();
+    let mut res = create_response(state, status, Some((body, mime)));
+
+    {
+        let headers = res.headers_mut();
+        headers.insert(CONTENT_LENGTH, length.to_string().parse().unwrap());
+        headers.insert(
+            CONTENT_DISPOSITION,
+            attachment_disposition(filename.as_ref()),
+        );
+    }
+
+    res
+}
+
+/// Builds the value of a `Content-Disposition: attachment` header for the given file name.
+fn attachment_disposition(filename: &str) -> HeaderValue {
+    // Only the final path component is meaningful to the client, and accepting separators would
+    // allow a crafted name to suggest a location outside of the download directory.
+    let name = filename
+        .rsplit(|c| c == '/' || c == '\\')
+        .next()
+        .unwrap_or("")
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>();
+
+    let name = match name.trim() {
+        "" | "." | ".." => "download".to_owned(),
+        trimmed => trimmed.to_owned(),
+    };
+
+    let fallback = name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii() => c,
+            _ => '_',
+        })
+        .collect::<String>();
+
+    let value = if fallback == name {
+        format!("attachment; filename=\"{}\"", fallback)
+    } else {
+        format!(
+            "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+            fallback,
+            encode_rfc5987(&name)
+        )
+    };
+
+    HeaderValue::from_str(&value).expect("Content-Disposition built from visible ASCII")
+}
+
+/// Percent-encodes a value for use in an RFC 5987 extended header parameter.
+fn encode_rfc5987(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' => encoded.push(byte as char),
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
 /// Extends a `response::Builder` struct with an optional body and set of default headers that help to
 /// improve security and conformance to best practice.
 ///
@@ -437,6 +762,10 @@ pub fn set_headers<B>(
 
 /// Sets redirect headers on a given `Response`.
 ///
+/// When the location is derived from the request (such as a `next` query parameter), build it
+/// with `redirect_location` or `safe_next_url` rather than passing the raw string, so that it
+/// cannot inject headers or redirect the client to another site.
+///
 /// # Examples
 ///
 /// ```rust
@@ -490,7 +819,314 @@ pub fn set_redirect_headers<B, L: Into<Cow<'static, str>>>(
     headers.insert(LOCATION, location.into().to_string().parse().unwrap());
 }
 
+/// Adds `header` to the `Vary` header of the `Response`, retaining any names which are already
+/// present, so that caches store a separate copy of the `Response` for each value of `header`.
+///
+/// The names in every existing `Vary` value are merged into a single value, without duplicates
+/// (regardless of case) and in the order they were added. A `Vary` of `*` absorbs every other
+/// name, as the `Response` varies on more than request headers.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response};
+/// # use hyper::header::VARY;
+/// # use gotham::helpers::http::response::add_vary;
+/// #
+/// # fn main() {
+/// let mut res = Response::builder()
+///     .header(VARY, "accept-encoding")
+///     .body(Body::empty())
+///     .unwrap();
+///
+/// add_vary(&mut res, "Accept-Language");
+/// add_vary(&mut res, "Accept-Encoding");
+///
+/// assert_eq!(
+///     res.headers().get(VARY).unwrap(),
+///     "accept-encoding, Accept-Language"
+/// );
+/// # }
+/// ```
+pub fn add_vary<B>(res: &mut Response<B>, header: &str) {
+    let mut names: Vec<String> = Vec::new();
+
+    {
+        let existing = res
+            .headers()
+            .get_all(VARY)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+
+        for name in existing.chain(Some(header)).map(str::trim) {
+            if name.is_empty() || names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+
+            names.push(name.to_owned());
+        }
+    }
+
+    let value = if names.iter().any(|n| n == "*") {
+        HeaderValue::from_static("*")
+    } else {
+        match HeaderValue::from_str(&names.join(", ")) {
+            Ok(value) => value,
+            Err(_) => {
+                error!("[ERROR][`{}` is not a valid Vary header name]", header);
+                return;
+            }
+        }
+    };
+
+    res.headers_mut().insert(VARY, value);
+}
+
 /// Sets the request id inside a given `HeaderMap`.
 fn set_request_id(state: &State, headers: &mut HeaderMap) {
     headers.insert(X_REQUEST_ID, request_id(state).parse().unwrap());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::{CACHE_CONTROL, SET_COOKIE};
+    use hyper::Method;
+    use mime;
+
+    use state::set_request_id;
+    use test::TestServer;
+
+    fn response_state() -> State {
+        let mut state = State::new();
+        state.put(Method::GET);
+        state.put(HeaderMap::new());
+        set_request_id(&mut state);
+        state
+    }
+
+    #[test]
+    fn extra_headers_are_added() {
+        let state = response_state();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CACHE_CONTROL, "no-store".parse().unwrap());
+
+        let res = create_response_with_headers(&state, StatusCode::OK, None, headers);
+        assert_eq!(res.headers().get(CACHE_CONTROL).unwrap(), "no-store");
+        assert_eq!(
+            res.headers().get(X_REQUEST_ID).unwrap(),
+            request_id(&state)
+        );
+    }
+
+    #[test]
+    fn extra_headers_replace_standard_headers() {
+        let state = response_state();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "text/csv".parse().unwrap());
+
+        let res = create_response_with_headers(
+            &state,
+            StatusCode::OK,
+            Some((b"a,b".to_vec(), mime::TEXT_PLAIN)),
+            headers,
+        );
+
+        let content_types = res.headers().get_all(CONTENT_TYPE).iter().collect::<Vec<_>>();
+        assert_eq!(content_types, vec!["text/csv"]);
+    }
+
+    #[test]
+    fn extra_headers_keep_every_value() {
+        let state = response_state();
+
+        let mut headers = HeaderMap::new();
+        headers.append(SET_COOKIE, "a=1".parse().unwrap());
+        headers.append(SET_COOKIE, "b=2".parse().unwrap());
+
+        let res = create_response_with_headers(&state, StatusCode::OK, None, headers);
+
+        let cookies = res.headers().get_all(SET_COOKIE).iter().collect::<Vec<_>>();
+        assert_eq!(cookies, vec!["a=1", "b=2"]);
+    }
+
+    #[test]
+    fn text_responses_declare_utf8() {
+        let state = response_state();
+
+        let res = create_text_response(&state, StatusCode::OK, "plain");
+        assert_eq!(
+            res.headers().get(CONTENT_TYPE).unwrap(),
+            "text/plain; charset=utf-8"
+        );
+
+        let res = create_html_response(&state, StatusCode::OK, "<p>html</p>");
+        assert_eq!(
+            res.headers().get(CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+    }
+
+    #[test]
+    fn content_length_is_measured_in_bytes() {
+        let test_server = TestServer::new(|| {
+            Ok(|state| {
+                let res = create_text_response(&state, StatusCode::OK, "Grüße, 世界");
+                (state, res)
+            })
+        }).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        let expected = "Grüße, 世界".len();
+        assert!(expected > "Grüße, 世界".chars().count());
+        assert_eq!(
+            response.headers().get(CONTENT_LENGTH).unwrap(),
+            expected.to_string().as_str()
+        );
+        assert_eq!(response.read_utf8_body().unwrap(), "Grüße, 世界");
+    }
+
+    #[test]
+    fn content_length_is_not_overridden() {
+        let mut target = HeaderMap::new();
+        target.insert(CONTENT_LENGTH, "3".parse().unwrap());
+
+        let mut source = HeaderMap::new();
+        source.insert(CONTENT_LENGTH, "1000".parse().unwrap());
+        merge_headers(&mut target, &source);
+
+        assert_eq!(target.get(CONTENT_LENGTH).unwrap(), "3");
+
+        let state = response_state();
+        let res = create_response_with_headers(
+            &state,
+            StatusCode::OK,
+            Some((b"abc".to_vec(), mime::TEXT_PLAIN)),
+            source,
+        );
+        assert!(res.headers().get(CONTENT_LENGTH).is_none());
+    }
+
+    fn disposition(filename: &str) -> String {
+        attachment_disposition(filename).to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn plain_filenames_are_quoted() {
+        assert_eq!(
+            disposition("report.csv"),
+            "attachment; filename=\"report.csv\""
+        );
+        assert_eq!(
+            disposition("monthly report.pdf"),
+            "attachment; filename=\"monthly report.pdf\""
+        );
+    }
+
+    #[test]
+    fn quotes_and_backslashes_are_replaced() {
+        assert_eq!(
+            disposition("say \"hello\".txt"),
+            "attachment; filename=\"say _hello_.txt\"; \
+             filename*=UTF-8''say%20%22hello%22.txt"
+        );
+    }
+
+    #[test]
+    fn utf8_filenames_use_extended_form() {
+        assert_eq!(
+            disposition("résumé.pdf"),
+            "attachment; filename=\"r_sum_.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf"
+        );
+        assert_eq!(
+            disposition("日本.txt"),
+            "attachment; filename=\"__.txt\"; filename*=UTF-8''%E6%97%A5%E6%9C%AC.txt"
+        );
+    }
+
+    #[test]
+    fn path_separators_and_control_characters_are_stripped() {
+        assert_eq!(
+            disposition("../../etc/passwd"),
+            "attachment; filename=\"passwd\""
+        );
+        assert_eq!(
+            disposition("C:\\Users\\me\\data.bin"),
+            "attachment; filename=\"data.bin\""
+        );
+        assert_eq!(
+            disposition("evil\r\nSet-Cookie: a=b.txt"),
+            "attachment; filename=\"evilSet-Cookie: a=b.txt\""
+        );
+    }
+
+    #[test]
+    fn empty_filenames_use_default() {
+        assert_eq!(disposition(""), "attachment; filename=\"download\"");
+        assert_eq!(disposition("exports/"), "attachment; filename=\"download\"");
+        assert_eq!(disposition(".."), "attachment; filename=\"download\"");
+    }
+
+    fn vary(existing: &[&'static str], added: &[&str]) -> Vec<String> {
+        let mut res = Response::new(Body::empty());
+        for value in existing {
+            res.headers_mut()
+                .append(VARY, HeaderValue::from_static(*value));
+        }
+
+        for header in added {
+            add_vary(&mut res, header);
+        }
+
+        res.headers()
+            .get_all(VARY)
+            .iter()
+            .map(|v| v.to_str().unwrap().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn vary_names_are_merged() {
+        assert_eq!(vary(&[], &["Accept"]), vec!["Accept"]);
+        assert_eq!(
+            vary(&["Accept-Encoding"], &["Accept", "Origin"]),
+            vec!["Accept-Encoding, Accept, Origin"]
+        );
+        assert_eq!(
+            vary(
+                &["Origin", "accept-encoding,  Accept"],
+                &["ACCEPT", "Accept-Language"]
+            ),
+            vec!["Origin, accept-encoding, Accept, Accept-Language"]
+        );
+        assert_eq!(
+            vary(&[], &["Accept", "Origin", "accept"]),
+            vec!["Accept, Origin"]
+        );
+    }
+
+    #[test]
+    fn vary_star_absorbs_names() {
+        assert_eq!(vary(&["*"], &["Accept"]), vec!["*"]);
+        assert_eq!(vary(&["Accept", "Origin"], &["*"]), vec!["*"]);
+        assert_eq!(vary(&["Accept, *"], &["Origin"]), vec!["*"]);
+        assert_eq!(vary(&["Accept"], &["*", "Origin"]), vec!["*"]);
+    }
+
+    #[test]
+    fn invalid_vary_name_is_ignored() {
+        assert_eq!(vary(&["Accept"], &["Bad\nName"]), vec!["Accept"]);
+    }
+}
@@ -0,0 +1,424 @@
+//! Defines `KeepAliveConfig`, which determines how long the connections of an application started
+//! via `gotham::start_with_keep_alive` are kept open between requests.
+
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::timer::Delay;
+
+/// The keep-alive behaviour of the connections of an application.
+///
+/// By default, connections are kept open for as long as the client wishes, and serve any number of
+/// requests. A load balancer in front of the application usually expects idle connections to be
+/// closed sooner than it closes them itself, which is described by `with_idle_timeout`.
+///
+/// The configuration is available via `ConnectionInfo`, along with whether the connection will be
+/// kept open after the current response.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::time::Duration;
+/// # use hyper::{Body, Response};
+/// # use gotham::keep_alive::KeepAliveConfig;
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let keep_alive = KeepAliveConfig::new()
+///     .with_idle_timeout(Duration::from_secs(55))
+///     .with_max_requests(1000);
+///
+/// gotham::start_with_keep_alive("127.0.0.1:7878", || Ok(handler), keep_alive);
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeepAliveConfig {
+    enabled: bool,
+    idle_timeout: Option<Duration>,
+    max_requests: Option<usize>,
+}
+
+impl KeepAliveConfig {
+    /// Creates a `KeepAliveConfig` which keeps connections open, without an idle timeout or a
+    /// limit on the number of requests per connection.
+    pub fn new() -> KeepAliveConfig {
+        KeepAliveConfig {
+            enabled: true,
+            idle_timeout: None,
+            max_requests: None,
+        }
+    }
+
+    /// Sets whether connections are kept open after a response. When disabled, each connection
+    /// serves a single request.
+    pub fn with_keep_alive(self, enabled: bool) -> KeepAliveConfig {
+        KeepAliveConfig { enabled, ..self }
+    }
+
+    /// Closes a connection when no new request starts within `idle_timeout` of the previous
+    /// response, or of the connection being accepted.
+    pub fn with_idle_timeout(self, idle_timeout: Duration) -> KeepAliveConfig {
+        KeepAliveConfig {
+            idle_timeout: Some(idle_timeout),
+            ..self
+        }
+    }
+
+    /// Closes a connection once it has served `max_requests` requests, which must be at least
+    /// one. The last response is sent with a `Connection: close` header.
+    pub fn with_max_requests(self, max_requests: usize) -> KeepAliveConfig {
+        assert!(
+            max_requests > 0,
+            "at least one request per connection is required"
+        );
+        KeepAliveConfig {
+            max_requests: Some(max_requests),
+            ..self
+        }
+    }
+
+    /// Determines if connections are kept open after a response.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The time a connection is kept open without a new request, if limited.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+
+    /// The number of requests a connection serves before it's closed, if limited.
+    pub fn max_requests(&self) -> Option<usize> {
+        self.max_requests
+    }
+}
+
+impl Default for KeepAliveConfig {
+    fn default() -> KeepAliveConfig {
+        KeepAliveConfig::new()
+    }
+}
+
+/// Tracks the requests in flight on a connection, and when it was last active, which is shared
+/// between the `ConnectedGothamService` and the `IdleTimeout` and `HeadTimeout` of the connection.
+pub(crate) struct Activity {
+    in_flight: AtomicUsize,
+    requests: AtomicUsize,
+    closing: AtomicBool,
+    last_active: Mutex<Instant>,
+}
+
+impl Activity {
+    pub(crate) fn new() -> Activity {
+        Activity {
+            in_flight: AtomicUsize::new(0),
+            requests: AtomicUsize::new(0),
+            closing: AtomicBool::new(false),
+            last_active: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Records that a request has started.
+    pub(crate) fn begin(&self) {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        self.touch();
+    }
+
+    /// Records that the response to a request has been produced.
+    pub(crate) fn end(&self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.touch();
+    }
+
+    /// The number of requests in flight.
+    pub(crate) fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// The number of requests which have started on the connection.
+    pub(crate) fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+
+    /// Marks the connection to be closed after the responses in flight.
+    pub(crate) fn close(&self) {
+        self.closing.store(true, Ordering::SeqCst);
+    }
+
+    /// Determines if the connection has been marked to be closed.
+    pub(crate) fn closing(&self) -> bool {
+        self.closing.load(Ordering::SeqCst)
+    }
+
+    fn touch(&self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+    }
+
+    fn idle_since(&self) -> Option<Instant> {
+        if self.in_flight() > 0 {
+            None
+        } else {
+            Some(*self.last_active.lock().unwrap())
+        }
+    }
+}
+
+/// Wraps the I/O of a connection, failing the next read once the connection has been idle for
+/// longer than the idle timeout, which closes the connection.
+pub(crate) struct IdleTimeout<I> {
+    io: I,
+    idle_timeout: Option<Duration>,
+    activity: Arc<Activity>,
+    delay: Option<Delay>,
+}
+
+impl<I> IdleTimeout<I> {
+    pub(crate) fn new(
+        io: I,
+        idle_timeout: Option<Duration>,
+        activity: Arc<Activity>,
+    ) -> IdleTimeout<I> {
+        IdleTimeout {
+            io,
+            idle_timeout,
+            activity,
+            delay: None,
+        }
+    }
+
+    /// Checks the idle timeout while a read is pending, registering the task to be woken when it
+    /// next passes.
+    fn poll_idle(&mut self) -> io::Result<()> {
+        let idle_timeout = match self.idle_timeout {
+            Some(idle_timeout) => idle_timeout,
+            None => return Ok(()),
+        };
+
+        let deadline = match self.activity.idle_since() {
+            Some(idle_since) => idle_since + idle_timeout,
+            None => {
+                self.delay = None;
+                return Ok(());
+            }
+        };
+
+        let delay = self.delay.get_or_insert_with(|| Delay::new(deadline));
+        if delay.deadline() != deadline {
+            delay.reset(deadline);
+        }
+
+        match delay.poll() {
+            Ok(Async::NotReady) => Ok(()),
+            Ok(Async::Ready(())) => {
+                debug!(
+                    "closing connection after idle timeout of {:?}",
+                    idle_timeout
+                );
+                Err(io::Error::new(io::ErrorKind::TimedOut, "connection idle"))
+            }
+            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+        }
+    }
+}
+
+impl<I> Read for IdleTimeout<I>
+where
+    I: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.io.read(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                self.poll_idle()?;
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+            result => result,
+        }
+    }
+}
+
+impl<I> Write for IdleTimeout<I>
+where
+    I: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.io.write(buf)?;
+
+        // A large response may take longer than the idle timeout to write.
+        self.activity.touch();
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl<I> AsyncRead for IdleTimeout<I> where I: AsyncRead {}
+
+impl<I> AsyncWrite for IdleTimeout<I>
+where
+    I: AsyncWrite,
+{
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::{self, SocketAddr};
+    use std::thread;
+
+    use hyper::{Body, Response, StatusCode};
+    use tokio::net::TcpListener;
+    use tokio::runtime::Runtime;
+
+    use helpers::http::response::create_text_response;
+    use service::GothamService;
+    use state::connection::ConnectionInfo;
+    use state::{FromState, State};
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let message = {
+            let connection = ConnectionInfo::borrow_from(&state);
+            format!(
+                "keep-alive={} idle-timeout={:?} max-requests={:?}",
+                connection.keep_alive(),
+                connection.idle_timeout(),
+                connection.max_requests()
+            )
+        };
+
+        let res = create_text_response(&state, StatusCode::OK, message);
+        (state, res)
+    }
+
+    fn start(runtime: &mut Runtime, config: KeepAliveConfig) -> SocketAddr {
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let service = GothamService::new(|| Ok(handler)).with_keep_alive(config);
+        runtime.spawn(::serve(listener, service));
+        addr
+    }
+
+    fn connect(addr: SocketAddr) -> net::TcpStream {
+        let stream = net::TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+    }
+
+    /// Sends a request on `stream`, and reads its response, leaving the connection open.
+    fn request(stream: &mut net::TcpStream) -> String {
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+
+        let mut response = Vec::new();
+        let mut buf = [0; 1024];
+        loop {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0, "connection closed before the response was complete");
+            response.extend_from_slice(&buf[..n]);
+
+            let text = String::from_utf8_lossy(&response).into_owned();
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text
+                    .lines()
+                    .find(|line| line.to_lowercase().starts_with("content-length:"))
+                    .and_then(|line| line[15..].trim().parse::<usize>().ok())
+                    .unwrap();
+
+                if response.len() >= end + 4 + length {
+                    return text;
+                }
+            }
+        }
+    }
+
+    fn is_closed(stream: &mut net::TcpStream) -> bool {
+        let mut buf = [0; 1];
+        match stream.read(&mut buf) {
+            Ok(0) => true,
+            Ok(_) => false,
+            Err(e) => e.kind() != io::ErrorKind::WouldBlock && e.kind() != io::ErrorKind::TimedOut,
+        }
+    }
+
+    #[test]
+    fn closes_idle_connection() {
+        let mut runtime = Runtime::new().unwrap();
+        let config = KeepAliveConfig::new().with_idle_timeout(Duration::from_millis(200));
+        let addr = start(&mut runtime, config);
+
+        let mut stream = connect(addr);
+        let response = request(&mut stream);
+        assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+        assert!(response.ends_with("keep-alive=true idle-timeout=Some(200ms) max-requests=None"));
+
+        let started = Instant::now();
+        assert!(is_closed(&mut stream));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn serves_requests_within_idle_timeout() {
+        let mut runtime = Runtime::new().unwrap();
+        let config = KeepAliveConfig::new().with_idle_timeout(Duration::from_millis(500));
+        let addr = start(&mut runtime, config);
+
+        let mut stream = connect(addr);
+        for _ in 0..3 {
+            let response = request(&mut stream);
+            assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+            thread::sleep(Duration::from_millis(200));
+        }
+    }
+
+    #[test]
+    fn closes_after_max_requests() {
+        let mut runtime = Runtime::new().unwrap();
+        let config = KeepAliveConfig::new().with_max_requests(2);
+        let addr = start(&mut runtime, config);
+
+        let mut stream = connect(addr);
+        let response = request(&mut stream);
+        assert!(response.ends_with("keep-alive=true idle-timeout=None max-requests=Some(2)"));
+
+        let response = request(&mut stream);
+        assert!(
+            response.to_lowercase().contains("connection: close"),
+            response
+        );
+        assert!(response.ends_with("keep-alive=false idle-timeout=None max-requests=Some(2)"));
+        assert!(is_closed(&mut stream));
+    }
+
+    #[test]
+    fn serves_single_request_without_keep_alive() {
+        let mut runtime = Runtime::new().unwrap();
+        let config = KeepAliveConfig::new().with_keep_alive(false);
+        let addr = start(&mut runtime, config);
+
+        let mut stream = connect(addr);
+        let response = request(&mut stream);
+        assert!(response.contains("keep-alive=false"), response);
+        assert!(is_closed(&mut stream));
+    }
+}
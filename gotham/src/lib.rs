@@ -15,10 +15,13 @@
 extern crate base64;
 extern crate bincode;
 extern crate borrow_bag;
+#[cfg(all(test, feature = "chrono"))]
 extern crate chrono;
 extern crate cookie;
 extern crate failure;
 extern crate futures;
+#[cfg(feature = "handlebars")]
+extern crate handlebars;
 extern crate http;
 extern crate hyper;
 extern crate linked_hash_map;
@@ -31,11 +34,15 @@ extern crate rand;
 extern crate regex;
 #[macro_use]
 extern crate serde;
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
+extern crate sha2;
 extern crate tokio;
+#[cfg(feature = "tls")]
+extern crate tokio_rustls;
 extern crate url;
 extern crate uuid;
 
-#[cfg(test)]
 #[macro_use]
 extern crate serde_derive;
 
@@ -43,26 +50,74 @@ pub mod error;
 pub mod extractor;
 pub mod handler;
 pub mod helpers;
+pub mod keep_alive;
+pub mod limits;
+pub mod listeners;
 pub mod middleware;
 pub mod pipeline;
+pub mod read_timeout;
 pub mod router;
+pub mod server;
 mod service;
+pub mod shutdown;
 pub mod state;
+pub mod template;
 pub mod test;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod workers;
+
+pub use server::{ServerBuildError, ServerBuilder};
 
+#[cfg(unix)]
+use std::fs;
+use std::io;
 use std::net::{SocketAddr, ToSocketAddrs};
+#[cfg(unix)]
+use std::path::Path;
 use std::sync::Arc;
 
-use futures::{Future, Stream};
-use hyper::server::conn::Http;
+#[cfg(unix)]
+use failure::err_msg;
+use futures::{future, Future, Stream};
+use hyper::server::conn::{Connection, Http};
 use tokio::executor::{self, thread_pool};
-use tokio::net::TcpListener;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::reactor::Handle;
 use tokio::runtime::{self, Runtime, TaskExecutor};
 
 use handler::NewHandler;
-use service::GothamService;
+use keep_alive::{IdleTimeout, KeepAliveConfig};
+use limits::{ConnectionGuard, ConnectionLimits};
+use listeners::Listeners;
+use middleware::validation::RequestValidation;
+use read_timeout::{HeadTimeout, ReadTimeouts};
+use service::{ConnectedGothamService, GothamService};
+use shutdown::{ShutdownHandle, ShutdownSignal};
+#[cfg(unix)]
+use state::connection::PeerCredentials;
+use state::request_id::RequestIdConfig;
+#[cfg(feature = "tls")]
+use tls::TlsConfig;
+#[cfg(feature = "tls")]
+use tokio::timer::Timeout;
+#[cfg(feature = "tls")]
+use tokio_rustls::rustls::Session;
+#[cfg(feature = "tls")]
+use tokio_rustls::TlsAcceptor;
+use workers::{register, WorkerConfig};
 
-/// Starts a Gotham application with the default number of threads.
+/// Starts a Gotham application with the default number of threads, which is the number of
+/// logical CPUs.
+///
+/// This is the simplest configuration of a `ServerBuilder`, which supports many more.
+///
+/// # Panics
+///
+/// If the application can't listen on `addr`.
 pub fn start<NH, A>(addr: A, new_handler: NH)
 where
     NH: NewHandler + 'static,
@@ -71,15 +126,388 @@ where
     start_with_num_threads(addr, new_handler, num_cpus::get())
 }
 
-/// Starts a Gotham application with a designated number of threads.
+/// Starts a Gotham application with a designated number of threads, which share a single
+/// listener as described by `ListenerLayout::Shared`.
+///
+/// # Panics
+///
+/// If the application can't listen on `addr`, or `threads` is zero.
 pub fn start_with_num_threads<NH, A>(addr: A, new_handler: NH, threads: usize)
 where
     NH: NewHandler + 'static,
     A: ToSocketAddrs + 'static,
 {
-    let runtime = new_runtime(threads);
-    start_on_executor(addr, new_handler, runtime.executor());
-    runtime.shutdown_on_idle().wait().unwrap();
+    ServerBuilder::new(new_handler)
+        .bind(addr)
+        .threads(threads)
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application with the default number of threads, invoking `on_bind` with the
+/// address which the application is listening on before any connection is accepted.
+///
+/// This allows the application to be bound to port 0, leaving the operating system to choose the
+/// port, and to register the port wherever it's needed, e.g. with a service registry.
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response};
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// gotham::start_with_on_bind("127.0.0.1:0", || Ok(handler), |addr| {
+///     println!("listening on port {}", addr.port());
+/// });
+/// # }
+/// ```
+pub fn start_with_on_bind<NH, A, F>(addr: A, new_handler: NH, on_bind: F)
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static,
+    F: FnOnce(SocketAddr),
+{
+    let listener = std::net::TcpListener::bind(addr).expect("unable to open TCP listener");
+    on_bind(
+        listener
+            .local_addr()
+            .expect("unable to determine listener address"),
+    );
+
+    ServerBuilder::new(new_handler)
+        .listener(listener)
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application with the default number of threads, keeping its connections open
+/// between requests as described by the `KeepAliveConfig`. See `KeepAliveConfig` for an example.
+pub fn start_with_keep_alive<NH, A>(addr: A, new_handler: NH, keep_alive: KeepAliveConfig)
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static,
+{
+    ServerBuilder::new(new_handler)
+        .bind(addr)
+        .keep_alive_config(keep_alive)
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application with the default number of threads, limiting the connections it
+/// serves at once as described by the `ConnectionLimits`. See `ConnectionLimits` for an example.
+pub fn start_with_connection_limits<NH, A>(addr: A, new_handler: NH, limits: ConnectionLimits)
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static,
+{
+    ServerBuilder::new(new_handler)
+        .bind(addr)
+        .connection_limits(limits)
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application with the default number of threads, bounding the time its
+/// connections wait for each request as described by the `ReadTimeouts`. See `ReadTimeouts` for
+/// an example.
+pub fn start_with_read_timeouts<NH, A>(addr: A, new_handler: NH, read_timeouts: ReadTimeouts)
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static,
+{
+    ServerBuilder::new(new_handler)
+        .bind(addr)
+        .read_timeouts(read_timeouts)
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application with the number of worker threads, and the layout of the listener
+/// between them, described by the `WorkerConfig`. See `ListenerLayout` for the trade-offs between
+/// the layouts.
+pub fn start_with_workers<NH, A>(addr: A, new_handler: NH, workers: WorkerConfig)
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static,
+{
+    ServerBuilder::new(new_handler)
+        .bind(addr)
+        .workers(workers)
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application with the default number of threads, accepting connections from a
+/// `listener` which is already bound, e.g. one passed in by systemd socket activation, or bound to
+/// port 0 so that the port is known before the application starts.
+///
+/// The listener keeps the socket options it was created with. It's switched to non-blocking mode
+/// by Gotham, so it needn't be beforehand.
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::net::TcpListener;
+/// # use hyper::{Body, Response};
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// println!("listening on port {}", listener.local_addr().unwrap().port());
+///
+/// gotham::start_on_listener(listener, || Ok(handler));
+/// # }
+/// ```
+pub fn start_on_listener<NH>(listener: std::net::TcpListener, new_handler: NH)
+where
+    NH: NewHandler + 'static,
+{
+    ServerBuilder::new(new_handler)
+        .listener(listener)
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application accepting connections from a `listener` which is already bound,
+/// as `start_on_listener` does, on the worker threads described by the `WorkerConfig`.
+pub fn start_on_listener_with_workers<NH>(
+    listener: std::net::TcpListener,
+    new_handler: NH,
+    workers: WorkerConfig,
+) where
+    NH: NewHandler + 'static,
+{
+    ServerBuilder::new(new_handler)
+        .listener(listener)
+        .workers(workers)
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application with the default number of threads, assigning request identifiers
+/// as described by the `RequestIdConfig`.
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::sync::atomic::{AtomicUsize, Ordering};
+/// # use hyper::{Body, Response};
+/// # use gotham::state::State;
+/// # use gotham::state::request_id::RequestIdConfig;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let counter = AtomicUsize::new(0);
+/// let config = RequestIdConfig::new()
+///     .with_generator(move || format!("req-{}", counter.fetch_add(1, Ordering::Relaxed)));
+///
+/// gotham::start_with_request_ids("127.0.0.1:7878", || Ok(handler), config);
+/// # }
+/// ```
+pub fn start_with_request_ids<NH, A>(addr: A, new_handler: NH, request_ids: RequestIdConfig)
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static,
+{
+    ServerBuilder::new(new_handler)
+        .bind(addr)
+        .request_ids(request_ids)
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application with the default number of threads, rejecting requests which fail
+/// the `RequestValidation` before any `Router`, `Pipeline` or `Middleware` is invoked.
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response};
+/// # use gotham::middleware::validation::RequestValidation;
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let validation = RequestValidation::new()
+///     .require_host(true)
+///     .max_uri_length(8192)
+///     .max_header_count(100)
+///     .max_header_value_length(8192)
+///     .reject_unknown_transfer_encoding(true);
+///
+/// gotham::start_with_request_validation("127.0.0.1:7878", || Ok(handler), validation);
+/// # }
+/// ```
+pub fn start_with_request_validation<NH, A>(addr: A, new_handler: NH, validation: RequestValidation)
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static,
+{
+    ServerBuilder::new(new_handler)
+        .bind(addr)
+        .request_validation(validation)
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application with the default number of threads in the background, returning
+/// a `ShutdownHandle` which stops it, allowing the requests in flight to complete. See
+/// `ShutdownHandle` for details.
+///
+/// ```rust,no_run
+/// # extern crate futures;
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::time::Duration;
+/// # use futures::Future;
+/// # use hyper::{Body, Response};
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let server = gotham::start_with_shutdown("127.0.0.1:7878", || Ok(handler));
+///
+/// // Serve requests until it's time to stop, e.g. when a signal is received.
+/// server.shutdown(Duration::from_secs(30)).wait().unwrap();
+/// # }
+/// ```
+pub fn start_with_shutdown<NH, A>(addr: A, new_handler: NH) -> ShutdownHandle
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static,
+{
+    ServerBuilder::new(new_handler)
+        .bind(addr)
+        .spawn()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application with the default number of threads, accepting connections over
+/// TLS with the certificate chain and private key of the `TlsConfig`. Requires the `tls` feature.
+///
+/// Each request received is marked as secure in `ConnectionInfo`. See `TlsConfig` for an example.
+#[cfg(feature = "tls")]
+pub fn start_with_tls<NH, A>(addr: A, new_handler: NH, tls_config: TlsConfig)
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static,
+{
+    ServerBuilder::new(new_handler)
+        .bind_tls(addr, tls_config)
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application with the default number of threads, listening on the Unix domain
+/// socket at `path` rather than a TCP port, e.g. for a reverse proxy on the same host.
+///
+/// A stale socket file left at `path` by an earlier application is removed, but the application
+/// panics if another application is still listening on it, or if `path` is some other kind of
+/// file. The permissions of the socket file are determined by the umask of the process; see
+/// `start_unix_with_mode` to set them.
+///
+/// Requests received over the socket have no client address, so `state::client_addr` returns
+/// `None` for them. The credentials of the peer process are available via `PeerCredentials`.
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response};
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// gotham::start_unix("/run/myapp/http.sock", || Ok(handler));
+/// # }
+/// ```
+#[cfg(unix)]
+pub fn start_unix<NH, P>(path: P, new_handler: NH)
+where
+    NH: NewHandler + 'static,
+    P: AsRef<Path>,
+{
+    ServerBuilder::new(new_handler)
+        .bind_unix(path, None)
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application listening on the Unix domain socket at `path`, as `start_unix`
+/// does, and sets the permissions of the socket file to `mode` (e.g. `0o660`, allowing the
+/// group of the process to connect).
+#[cfg(unix)]
+pub fn start_unix_with_mode<NH, P>(path: P, new_handler: NH, mode: u32)
+where
+    NH: NewHandler + 'static,
+    P: AsRef<Path>,
+{
+    ServerBuilder::new(new_handler)
+        .bind_unix(path, Some(mode))
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application listening on the Unix domain socket at `path` in the background,
+/// as `start_with_shutdown` does for a TCP port, returning a `ShutdownHandle` which stops it. The
+/// socket file is removed once the application has stopped. When `mode` is given, the
+/// permissions of the socket file are set as `start_unix_with_mode` does.
+#[cfg(unix)]
+pub fn start_unix_with_shutdown<NH, P>(
+    path: P,
+    new_handler: NH,
+    mode: Option<u32>,
+) -> ShutdownHandle
+where
+    NH: NewHandler + 'static,
+    P: AsRef<Path>,
+{
+    ServerBuilder::new(new_handler)
+        .bind_unix(path, mode)
+        .spawn()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
+}
+
+/// Starts a Gotham application with the default number of threads, serving the same handler on
+/// each of the `Listeners`. See `Listeners` for an example.
+///
+/// The name of the listener which each request arrived on is available via
+/// `ConnectionInfo::listener`.
+pub fn start_on_listeners<NH>(listeners: Listeners, new_handler: NH)
+where
+    NH: NewHandler + 'static,
+{
+    ServerBuilder::new(new_handler)
+        .listeners(listeners)
+        .run()
+        .unwrap_or_else(|e| panic!("unable to start Gotham: {}", e))
 }
 
 /// Starts a Gotham application with a designated backing `TaskExecutor`.
@@ -111,22 +539,75 @@ where
         addr
     );
 
-    bind_server(listener, new_handler)
+    serve(listener, GothamService::new(new_handler))
+}
+
+/// Returns a `Future` which serves a Gotham application on the connections accepted by
+/// `listener`, registering it with the reactor of `handle` rather than starting a runtime of its
+/// own.
+///
+/// This embeds the application within a larger tokio application, e.g. as an admin endpoint: the
+/// future is spawned onto, or composed with other futures on, the caller's event loop, which
+/// remains responsible for its threads and shutdown. Requests are handled in the same way as by
+/// `gotham::start`.
+///
+/// Each connection is spawned onto the executor which is running the future, so it must be run
+/// by an executor, e.g. a `current_thread::Runtime`, rather than waited on. Dropping the future
+/// stops accepting connections, while those already accepted are served until they close.
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate tokio;
+/// #
+/// # use std::net::TcpListener;
+/// # use hyper::{Body, Response};
+/// # use tokio::reactor::Handle;
+/// # use tokio::runtime::current_thread::Runtime;
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let listener = TcpListener::bind("127.0.0.1:7878").unwrap();
+/// let mut runtime = Runtime::new().unwrap();
+///
+/// // The default `Handle` refers to the reactor of the runtime which runs the future.
+/// runtime.spawn(gotham::bind_server(&Handle::default(), listener, || Ok(handler)));
+///
+/// // ... spawn the rest of the application onto `runtime`.
+///
+/// runtime.run().unwrap();
+/// # }
+/// ```
+pub fn bind_server<NH>(
+    handle: &Handle,
+    listener: std::net::TcpListener,
+    new_handler: NH,
+) -> impl Future<Item = (), Error = ()>
+where
+    NH: NewHandler + 'static,
+{
+    let listener = register(listener, handle);
+    serve(listener, GothamService::new(new_handler))
 }
 
-fn bind_server<NH>(listener: TcpListener, new_handler: NH) -> impl Future<Item = (), Error = ()>
+fn serve<NH>(
+    listener: TcpListener,
+    gotham_service: GothamService<NH>,
+) -> impl Future<Item = (), Error = ()>
 where
     NH: NewHandler + 'static,
 {
-    let protocol = Arc::new(Http::new());
-    let gotham_service = GothamService::new(new_handler);
+    let protocol = Arc::new(gotham_service.protocol());
 
-    listener
-        .incoming()
+    gotham_service
+        .limit(listener.incoming())
         .map_err(|e| panic!("socket error = {:?}", e))
-        .for_each(move |socket| {
-            let service = gotham_service.connect(socket.peer_addr().unwrap());
-            let handler = protocol.serve_connection(socket, service).then(|_| Ok(()));
+        .for_each(move |(socket, guard)| {
+            let handler = connection(&protocol, &gotham_service, socket, guard).then(|_| Ok(()));
 
             executor::spawn(handler);
 
@@ -134,6 +615,248 @@ where
         })
 }
 
+/// Accepts connections as `serve` does, until shutdown begins.
+fn serve_until_shutdown<NH>(
+    listener: TcpListener,
+    gotham_service: GothamService<NH>,
+    signal: ShutdownSignal,
+) -> impl Future<Item = (), Error = ()>
+where
+    NH: NewHandler + 'static,
+{
+    let protocol = Arc::new(gotham_service.protocol());
+
+    let connections = gotham_service
+        .limit(listener.incoming())
+        .map(move |(socket, guard)| connection(&protocol, &gotham_service, socket, guard));
+
+    accept_until_shutdown(connections, signal)
+}
+
+/// Spawns each of the `connections` until shutdown begins. Each connection is then closed once
+/// its request in flight has completed, or dropped when the deadline passes.
+fn accept_until_shutdown<S, I, NH>(
+    connections: S,
+    signal: ShutdownSignal,
+) -> impl Future<Item = (), Error = ()>
+where
+    S: Stream<Item = Connection<I, ConnectedGothamService<NH>>, Error = io::Error>,
+    I: AsyncRead + AsyncWrite + Send + 'static,
+    NH: NewHandler + 'static,
+{
+    let watcher = signal.clone();
+
+    let accept = connections
+        .map_err(|e| panic!("socket error = {:?}", e))
+        .for_each(move |connection| {
+            executor::spawn(watcher.watch(connection));
+
+            Ok(())
+        });
+
+    until_shutdown(accept, signal)
+}
+
+/// Runs `accept` until shutdown begins, marking the application as ready once it has started.
+fn until_shutdown<F>(accept: F, signal: ShutdownSignal) -> impl Future<Item = (), Error = ()>
+where
+    F: Future<Item = (), Error = ()>,
+{
+    let triggered = signal.triggered();
+
+    // The listener is dropped along with `accept`, so that new connections are refused.
+    future::lazy(move || {
+        signal.ready();
+        accept.select2(triggered)
+    })
+    .then(|_| Ok(()))
+}
+
+/// The I/O of a connection, which is closed when idle, or when it's slow to send a request head.
+type Watched<I> = HeadTimeout<IdleTimeout<I>>;
+
+fn connection<NH>(
+    protocol: &Http,
+    gotham_service: &GothamService<NH>,
+    socket: TcpStream,
+    guard: ConnectionGuard,
+) -> Connection<Watched<TcpStream>, ConnectedGothamService<NH>>
+where
+    NH: NewHandler + 'static,
+{
+    let service = gotham_service
+        .connect(socket.peer_addr().unwrap())
+        .with_local_addr(socket.local_addr().ok())
+        .with_guard(guard);
+
+    let io = service.watch_head(service.watch_idle(socket));
+    protocol.serve_connection(io, service)
+}
+
+/// Accepts connections as `serve` does, completing a TLS handshake on each connection before
+/// serving requests from it.
+#[cfg(feature = "tls")]
+fn serve_tls<NH>(
+    listener: TcpListener,
+    gotham_service: GothamService<NH>,
+    acceptor: TlsAcceptor,
+) -> impl Future<Item = (), Error = ()>
+where
+    NH: NewHandler + 'static,
+{
+    let protocol = Arc::new(gotham_service.protocol());
+
+    gotham_service
+        .limit(listener.incoming())
+        .map_err(|e| panic!("socket error = {:?}", e))
+        .for_each(move |(socket, guard)| {
+            let handler =
+                tls_connection(&protocol, &gotham_service, &acceptor, socket, guard, None);
+
+            executor::spawn(handler);
+
+            Ok(())
+        })
+}
+
+/// Accepts connections as `serve_tls` does, until shutdown begins.
+#[cfg(feature = "tls")]
+fn serve_tls_until_shutdown<NH>(
+    listener: TcpListener,
+    gotham_service: GothamService<NH>,
+    acceptor: TlsAcceptor,
+    signal: ShutdownSignal,
+) -> impl Future<Item = (), Error = ()>
+where
+    NH: NewHandler + 'static,
+{
+    let protocol = Arc::new(gotham_service.protocol());
+    let watcher = signal.clone();
+
+    let accept = gotham_service
+        .limit(listener.incoming())
+        .map_err(|e| panic!("socket error = {:?}", e))
+        .for_each(move |(socket, guard)| {
+            let watcher = Some(watcher.clone());
+            let handler = tls_connection(
+                &protocol,
+                &gotham_service,
+                &acceptor,
+                socket,
+                guard,
+                watcher,
+            );
+
+            executor::spawn(handler);
+
+            Ok(())
+        });
+
+    until_shutdown(accept, signal)
+}
+
+/// Completes a TLS handshake on `socket`, within the handshake timeout if one is set, then serves
+/// requests from it, until shutdown begins when a `ShutdownSignal` is given. A connection which
+/// negotiates `h2` via ALPN is served over HTTP/2.
+#[cfg(feature = "tls")]
+fn tls_connection<NH>(
+    protocol: &Http,
+    gotham_service: &GothamService<NH>,
+    acceptor: &TlsAcceptor,
+    socket: TcpStream,
+    guard: ConnectionGuard,
+    signal: Option<ShutdownSignal>,
+) -> impl Future<Item = (), Error = ()>
+where
+    NH: NewHandler + 'static,
+{
+    let client_addr = socket.peer_addr().unwrap();
+    let service = gotham_service
+        .connect(client_addr)
+        .with_local_addr(socket.local_addr().ok())
+        .with_tls(true)
+        .with_guard(guard);
+
+    let handshake = acceptor
+        .accept(service.watch_idle(socket))
+        .map_err(|e| e.to_string());
+
+    let handshake = match gotham_service.tls_handshake_timeout() {
+        Some(timeout) => future::Either::A(
+            Timeout::new(handshake, timeout)
+                .map_err(|e| e.into_inner().unwrap_or_else(|| "timed out".to_owned())),
+        ),
+        None => future::Either::B(handshake),
+    };
+
+    let mut protocol = protocol.clone();
+    handshake
+        .map_err(move |e| info!("[{}] TLS handshake failed: {}", client_addr, e))
+        .and_then(move |stream| {
+            if stream.get_ref().1.get_alpn_protocol() == Some("h2") {
+                protocol.http2_only(true);
+            }
+
+            // Above TLS, so that a `408 Request Timeout` is written within the session.
+            let io = service.watch_head(stream);
+            let connection = protocol.serve_connection(io, service);
+
+            match signal {
+                Some(signal) => future::Either::A(signal.watch(connection)),
+                None => future::Either::B(connection.then(|_| Ok(()))),
+            }
+        })
+}
+
+#[cfg(unix)]
+type UnixConnection<NH> = Connection<Watched<UnixStream>, ConnectedGothamService<NH>>;
+
+/// Serves connections accepted by `listener`, as `serve` does for a TCP listener.
+#[cfg(unix)]
+fn serve_unix<NH>(
+    listener: UnixListener,
+    gotham_service: GothamService<NH>,
+) -> impl Future<Item = (), Error = ()>
+where
+    NH: NewHandler + 'static,
+{
+    unix_connections(listener, gotham_service)
+        .map_err(|e| panic!("socket error = {:?}", e))
+        .for_each(|connection| {
+            executor::spawn(connection.then(|_| Ok(())));
+
+            Ok(())
+        })
+}
+
+/// Serves each connection accepted by `listener`, recording the credentials of the peer process
+/// in place of a client address.
+#[cfg(unix)]
+fn unix_connections<NH>(
+    listener: UnixListener,
+    gotham_service: GothamService<NH>,
+) -> impl Stream<Item = UnixConnection<NH>, Error = io::Error>
+where
+    NH: NewHandler + 'static,
+{
+    let protocol = Arc::new(gotham_service.protocol());
+
+    let incoming = gotham_service.limit(listener.incoming());
+
+    incoming.map(move |(socket, guard)| {
+        let peer_credentials = socket
+            .peer_cred()
+            .ok()
+            .map(|cred| PeerCredentials::new(cred.uid, cred.gid));
+        let service = gotham_service
+            .connect_unix(peer_credentials)
+            .with_guard(guard);
+
+        let io = service.watch_head(service.watch_idle(socket));
+        protocol.serve_connection(io, service)
+    })
+}
+
 fn new_runtime(threads: usize) -> Runtime {
     let mut pool_builder = thread_pool::Builder::new();
 
@@ -159,5 +882,273 @@ where
 
     let listener = TcpListener::bind(&addr).expect("unable to open TCP listener");
 
+    // The port which was bound, when port 0 was requested.
+    let addr = listener.local_addr().unwrap_or(addr);
+
     (listener, addr)
 }
+
+/// Binds a Unix domain socket at `path`, replacing a stale socket file left by an earlier
+/// application, and sets the permissions of the socket file to `mode` when given.
+#[cfg(unix)]
+fn unix_listener(path: &Path, mode: Option<u32>) -> error::Result<UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::os::unix::net;
+
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(err_msg(format!(
+                "unable to replace {}, which isn't a socket",
+                path.display()
+            )));
+        }
+
+        if net::UnixStream::connect(path).is_ok() {
+            return Err(err_msg(format!(
+                "another application is listening on {}",
+                path.display()
+            )));
+        }
+
+        fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+
+    if let Some(mode) = mode {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+
+    Ok(listener)
+}
+
+#[cfg(all(test, unix))]
+mod unix_tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use std::os::unix::net;
+    use std::time::Duration;
+
+    use hyper::{Body, Response, StatusCode};
+    use uuid::Uuid;
+
+    use helpers::http::response::create_text_response;
+    use state::{client_addr, FromState, State};
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let body = format!(
+            "client_addr={:?} uid={:?}",
+            client_addr(&state),
+            PeerCredentials::try_borrow_from(&state).map(PeerCredentials::uid)
+        );
+
+        let res = create_text_response(&state, StatusCode::OK, body);
+        (state, res)
+    }
+
+    #[test]
+    fn serves_over_unix_socket() {
+        let path = ::std::env::temp_dir().join(format!("gotham-{}.sock", Uuid::new_v4().simple()));
+
+        // Leaves a stale socket file, as an application which was killed would.
+        drop(net::UnixListener::bind(&path).unwrap());
+
+        let server = start_unix_with_shutdown(&path, || Ok(handler), Some(0o600));
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        assert_eq!(server.addr(), None);
+
+        let mut stream = net::UnixStream::connect(&path).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+
+        let expected = format!("client_addr=None uid=Some({})", metadata.uid());
+        assert!(response.ends_with(&expected), response);
+
+        server.shutdown(Duration::from_secs(1)).wait().unwrap();
+        assert!(!path.exists());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{self, Read, Write};
+    use std::net;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use futures::sync::oneshot;
+    use futures::{future, stream};
+    use hyper::client::HttpConnector;
+    use hyper::{Body, Chunk, Client, Error, Response, StatusCode, Uri, Version};
+    use tokio::timer::Delay;
+
+    use handler::HandlerFuture;
+    use helpers::http::response::create_text_response;
+    use state::{client_addr, FromState, State};
+
+    fn slow(state: State) -> Box<HandlerFuture> {
+        let f = Delay::new(Instant::now() + Duration::from_millis(200)).then(move |_| {
+            let body = format!(
+                "{:?} port={}",
+                Version::borrow_from(&state),
+                client_addr(&state).unwrap().port()
+            );
+
+            let res = create_text_response(&state, StatusCode::OK, body);
+            future::ok((state, res))
+        });
+
+        Box::new(f)
+    }
+
+    fn streaming(state: State) -> (State, Response<Body>) {
+        let chunks = (0..100).map(|i| Chunk::from(format!("{};", i)));
+        let chunks = stream::iter_ok::<_, io::Error>(chunks);
+        let res = Response::new(Body::wrap_stream(chunks));
+        (state, res)
+    }
+
+    /// Starts an application in the background, returning the address of its cleartext listener.
+    fn start<NH>(runtime: &mut Runtime, new_handler: NH) -> Uri
+    where
+        NH: NewHandler + 'static,
+    {
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        runtime.spawn(serve(listener, GothamService::new(new_handler)));
+        format!("http://{}/", addr).parse().unwrap()
+    }
+
+    fn get(client: &Client<HttpConnector>, uri: Uri) -> impl Future<Item = String, Error = Error> {
+        client
+            .get(uri)
+            .and_then(|response| {
+                assert_eq!(response.version(), Version::HTTP_2);
+                response.into_body().concat2()
+            })
+            .map(|body| String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn hello(state: State) -> (State, Response<Body>) {
+        let res = create_text_response(&state, StatusCode::OK, "hello");
+        (state, res)
+    }
+
+    #[test]
+    fn reports_bound_address() {
+        let (tx, rx) = mpsc::channel();
+        let on_bind = move |addr| tx.send(addr).unwrap();
+        thread::spawn(move || start_with_on_bind("127.0.0.1:0", || Ok(hello), on_bind));
+
+        let addr = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(addr.port() != 0);
+
+        let mut stream = net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+        assert!(response.ends_with("hello"), response);
+    }
+
+    fn thread_name(state: State) -> (State, Response<Body>) {
+        let name = thread::current().name().unwrap_or("unnamed").to_owned();
+        let res = create_text_response(&state, StatusCode::OK, name);
+        (state, res)
+    }
+
+    #[test]
+    fn serves_on_callers_event_loop() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = Handle::default();
+        let mut runtime = runtime::current_thread::Runtime::new().unwrap();
+        runtime.spawn(bind_server(&handle, listener, || Ok(thread_name)));
+
+        let (tx, rx) = oneshot::channel();
+        thread::spawn(move || {
+            let mut stream = net::TcpStream::connect(addr).unwrap();
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+                .unwrap();
+
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            tx.send(response).unwrap();
+        });
+
+        // The event loop only runs while this thread drives it, so the request is handled here.
+        let response = runtime.block_on(rx).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+        assert!(response.to_lowercase().contains("x-request-id: "), response);
+
+        let name = thread::current().name().unwrap_or("unnamed").to_owned();
+        assert!(response.ends_with(&name), response);
+    }
+
+    #[test]
+    fn serves_concurrent_streams_with_prior_knowledge() {
+        let mut runtime = Runtime::new().unwrap();
+        let uri = start(&mut runtime, || Ok(slow));
+
+        // The client multiplexes every request as a stream on a single connection.
+        let client = Client::builder().http2_only(true).build_http();
+        let requests: Vec<_> = (0..10).map(|_| get(&client, uri.clone())).collect();
+
+        let started = Instant::now();
+        let bodies = runtime.block_on(future::join_all(requests)).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        assert!(bodies[0].starts_with("HTTP/2.0 port="), bodies[0].clone());
+        assert!(bodies.iter().all(|body| *body == bodies[0]));
+    }
+
+    #[test]
+    fn streams_body_as_data_frames() {
+        let mut runtime = Runtime::new().unwrap();
+        let uri = start(&mut runtime, || Ok(streaming));
+
+        let client = Client::builder().http2_only(true).build_http();
+        let body = runtime.block_on(get(&client, uri)).unwrap();
+
+        let expected: String = (0..100).map(|i| format!("{};", i)).collect();
+        assert_eq!(body, expected);
+    }
+    #[test]
+    fn serves_h2c_upgrade_request_over_http1() {
+        let mut runtime = Runtime::new().unwrap();
+        let uri = start(&mut runtime, || Ok(slow));
+        let addr = uri.authority_part().unwrap().as_str().to_owned();
+
+        // The upgrade isn't supported, so the request is answered without switching protocols.
+        let mut stream = net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(
+                b"GET / HTTP/1.1\r\nHost: localhost\r\n\
+                  Connection: Upgrade, HTTP2-Settings, close\r\n\
+                  Upgrade: h2c\r\nHTTP2-Settings: AAMAAABkAAQAAP__\r\n\r\n",
+            )
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+        assert!(response.contains("\r\n\r\nHTTP/1.1 port="), response);
+    }
+}
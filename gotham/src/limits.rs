@@ -0,0 +1,411 @@
+//! Defines `ConnectionLimits`, which bounds the connections and requests which an application
+//! started via `gotham::start_with_connection_limits` serves at once.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures::task::{self, Task};
+use futures::{Async, Poll, Stream};
+
+/// What happens to connections beyond the limit set by `ConnectionLimits::with_max_connections`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExcessConnections {
+    /// Connections beyond the limit are accepted and closed straight away, so that the client
+    /// learns of it rather than waiting. This is the default.
+    Close,
+
+    /// Connections beyond the limit are left unaccepted until an open connection closes. They
+    /// wait in the listen backlog of the operating system, which refuses connections once the
+    /// backlog is full.
+    Queue,
+}
+
+/// Limits on the connections of an application, which are enforced as connections are accepted
+/// and requests are received, before any `Middleware` is invoked.
+///
+/// By default, there are no limits. A `ConnectionLimits` also counts the open connections and the
+/// connections closed for exceeding the limit, which are shared between its clones, so that a
+/// clone kept by the application observes the counts.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::thread;
+/// # use std::time::Duration;
+/// # use hyper::{Body, Response};
+/// # use gotham::limits::ConnectionLimits;
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let limits = ConnectionLimits::new()
+///     .with_max_connections(10_000)
+///     .with_max_concurrent_requests(100);
+///
+/// let observed = limits.clone();
+/// thread::spawn(move || loop {
+///     println!("open connections: {}", observed.open_connections());
+///     thread::sleep(Duration::from_secs(60));
+/// });
+///
+/// gotham::start_with_connection_limits("127.0.0.1:7878", || Ok(handler), limits);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ConnectionLimits {
+    max_connections: Option<usize>,
+    excess_connections: ExcessConnections,
+    max_concurrent_requests: Option<usize>,
+    counters: Arc<Counters>,
+}
+
+struct Counters {
+    open: AtomicUsize,
+    refused: AtomicUsize,
+    // The task accepting connections, while it waits for an open connection to close.
+    acceptor: Mutex<Option<Task>>,
+}
+
+impl ConnectionLimits {
+    /// Creates a `ConnectionLimits` without any limits.
+    pub fn new() -> ConnectionLimits {
+        ConnectionLimits {
+            max_connections: None,
+            excess_connections: ExcessConnections::Close,
+            max_concurrent_requests: None,
+            counters: Arc::new(Counters {
+                open: AtomicUsize::new(0),
+                refused: AtomicUsize::new(0),
+                acceptor: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Sets the maximum number of connections open at once, which must be at least one.
+    pub fn with_max_connections(self, max_connections: usize) -> ConnectionLimits {
+        assert!(max_connections > 0, "at least one connection is required");
+        ConnectionLimits {
+            max_connections: Some(max_connections),
+            ..self
+        }
+    }
+
+    /// Sets what happens to connections beyond the maximum number of connections.
+    pub fn with_excess_connections(
+        self,
+        excess_connections: ExcessConnections,
+    ) -> ConnectionLimits {
+        ConnectionLimits {
+            excess_connections,
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of requests in flight at once on a single connection, which must
+    /// be at least one. This only affects HTTP/2 connections, which multiplex requests. Requests
+    /// beyond the limit are answered with `503 Service Unavailable`.
+    pub fn with_max_concurrent_requests(self, max_concurrent_requests: usize) -> ConnectionLimits {
+        assert!(
+            max_concurrent_requests > 0,
+            "at least one request is required"
+        );
+        ConnectionLimits {
+            max_concurrent_requests: Some(max_concurrent_requests),
+            ..self
+        }
+    }
+
+    /// The maximum number of connections open at once, if limited.
+    pub fn max_connections(&self) -> Option<usize> {
+        self.max_connections
+    }
+
+    /// What happens to connections beyond the maximum number of connections.
+    pub fn excess_connections(&self) -> ExcessConnections {
+        self.excess_connections
+    }
+
+    /// The maximum number of requests in flight at once on a single connection, if limited.
+    pub fn max_concurrent_requests(&self) -> Option<usize> {
+        self.max_concurrent_requests
+    }
+
+    /// The number of connections which are currently open.
+    pub fn open_connections(&self) -> usize {
+        self.counters.open.load(Ordering::SeqCst)
+    }
+
+    /// The number of connections which have been closed for exceeding the maximum number of
+    /// connections, when `ExcessConnections::Close` is set.
+    pub fn refused_connections(&self) -> usize {
+        self.counters.refused.load(Ordering::SeqCst)
+    }
+
+    /// Applies the limits to the connections accepted from `incoming`, pairing each with a
+    /// `ConnectionGuard` which frees its place when dropped.
+    pub(crate) fn limit<S>(&self, incoming: S) -> LimitedIncoming<S> {
+        LimitedIncoming {
+            incoming,
+            limits: self.clone(),
+        }
+    }
+
+    fn at_limit(&self) -> bool {
+        self.max_connections.map_or(false, |max_connections| {
+            self.open_connections() >= max_connections
+        })
+    }
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> ConnectionLimits {
+        ConnectionLimits::new()
+    }
+}
+
+/// Holds the place of an open connection, counted by `ConnectionLimits`.
+pub(crate) struct ConnectionGuard {
+    counters: Arc<Counters>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.counters.open.fetch_sub(1, Ordering::SeqCst);
+
+        if let Some(acceptor) = self.counters.acceptor.lock().unwrap().take() {
+            acceptor.notify();
+        }
+    }
+}
+
+/// A stream of connections, limited by `ConnectionLimits`, as returned by
+/// `ConnectionLimits::limit`.
+pub(crate) struct LimitedIncoming<S> {
+    incoming: S,
+    limits: ConnectionLimits,
+}
+
+impl<S> Stream for LimitedIncoming<S>
+where
+    S: Stream,
+{
+    type Item = (S::Item, ConnectionGuard);
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, S::Error> {
+        let queue = self.limits.excess_connections == ExcessConnections::Queue;
+
+        loop {
+            if queue && self.limits.at_limit() {
+                *self.limits.counters.acceptor.lock().unwrap() = Some(task::current());
+
+                // A connection may have closed before the task was stored.
+                if self.limits.at_limit() {
+                    return Ok(Async::NotReady);
+                }
+            }
+
+            let socket = match self.incoming.poll()? {
+                Async::Ready(Some(socket)) => socket,
+                Async::Ready(None) => return Ok(Async::Ready(None)),
+                Async::NotReady => return Ok(Async::NotReady),
+            };
+
+            if self.limits.at_limit() {
+                self.limits.counters.refused.fetch_add(1, Ordering::SeqCst);
+                debug!(
+                    "closing connection beyond the limit of {:?}",
+                    self.limits.max_connections
+                );
+                continue;
+            }
+
+            self.limits.counters.open.fetch_add(1, Ordering::SeqCst);
+
+            let guard = ConnectionGuard {
+                counters: self.limits.counters.clone(),
+            };
+
+            return Ok(Async::Ready(Some((socket, guard))));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{self, Read, Write};
+    use std::net::{self, SocketAddr};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use futures::{future, Future};
+    use hyper::{Body, Client, Response, StatusCode};
+    use tokio::net::TcpListener;
+    use tokio::runtime::Runtime;
+    use tokio::timer::Delay;
+
+    use handler::{HandlerFuture, NewHandler};
+    use helpers::http::response::create_text_response;
+    use service::GothamService;
+    use state::State;
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let res = create_text_response(&state, StatusCode::OK, "served");
+        (state, res)
+    }
+
+    fn slow(state: State) -> Box<HandlerFuture> {
+        let f = Delay::new(Instant::now() + Duration::from_millis(200)).then(move |_| {
+            let res = create_text_response(&state, StatusCode::OK, "served");
+            future::ok((state, res))
+        });
+
+        Box::new(f)
+    }
+
+    fn start<NH>(runtime: &mut Runtime, new_handler: NH, limits: ConnectionLimits) -> SocketAddr
+    where
+        NH: NewHandler + 'static,
+    {
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let service = GothamService::new(new_handler).with_connection_limits(limits);
+        runtime.spawn(::serve(listener, service));
+        addr
+    }
+
+    fn connect(addr: SocketAddr) -> net::TcpStream {
+        let stream = net::TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+    }
+
+    /// Sends a request on `stream`, returning `None` if the connection is closed without a
+    /// response. The response body is `served`, which ends the response.
+    fn request(stream: &mut net::TcpStream) -> Option<String> {
+        if stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .is_err()
+        {
+            return None;
+        }
+
+        let mut response = Vec::new();
+        let mut buf = [0; 1024];
+        while !response.ends_with(b"served") {
+            match stream.read(&mut buf) {
+                Ok(0) | Err(_) => return None,
+                Ok(n) => response.extend_from_slice(&buf[..n]),
+            }
+        }
+
+        Some(String::from_utf8(response).unwrap())
+    }
+
+    fn wait_for(condition: &Fn() -> bool) {
+        for _ in 0..100 {
+            if condition() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        panic!("condition not met in time");
+    }
+
+    #[test]
+    fn closes_excess_connections() {
+        let mut runtime = Runtime::new().unwrap();
+        let limits = ConnectionLimits::new().with_max_connections(2);
+        let addr = start(&mut runtime, || Ok(handler), limits.clone());
+
+        let mut first = connect(addr);
+        let mut second = connect(addr);
+        assert!(request(&mut first).is_some());
+        assert!(request(&mut second).is_some());
+        assert_eq!(limits.open_connections(), 2);
+
+        let mut excess = connect(addr);
+        assert_eq!(request(&mut excess), None);
+        assert_eq!(limits.refused_connections(), 1);
+
+        // The open connections continue to be served.
+        assert!(request(&mut first).is_some());
+        assert!(request(&mut second).is_some());
+
+        // Once a connection closes, another is accepted in its place.
+        drop(first);
+        wait_for(&|| limits.open_connections() == 1);
+
+        let mut replacement = connect(addr);
+        assert!(request(&mut replacement).is_some());
+    }
+
+    #[test]
+    fn queues_excess_connections() {
+        let mut runtime = Runtime::new().unwrap();
+        let limits = ConnectionLimits::new()
+            .with_max_connections(1)
+            .with_excess_connections(ExcessConnections::Queue);
+        let addr = start(&mut runtime, || Ok(handler), limits.clone());
+
+        let mut first = connect(addr);
+        assert!(request(&mut first).is_some());
+
+        let mut queued = connect(addr);
+        queued
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        queued
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+
+        let mut buf = [0; 1];
+        let err = queued.read(&mut buf).unwrap_err();
+        assert!(err.kind() == io::ErrorKind::WouldBlock || err.kind() == io::ErrorKind::TimedOut);
+
+        drop(first);
+
+        let mut response = String::new();
+        queued
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        while !response.ends_with("served") {
+            let mut buf = [0; 1024];
+            let n = queued.read(&mut buf).unwrap();
+            assert!(n > 0);
+            response.push_str(&String::from_utf8_lossy(&buf[..n]));
+        }
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+        assert_eq!(limits.refused_connections(), 0);
+    }
+
+    #[test]
+    fn limits_concurrent_requests_per_connection() {
+        let mut runtime = Runtime::new().unwrap();
+        let limits = ConnectionLimits::new().with_max_concurrent_requests(1);
+        let addr = start(&mut runtime, || Ok(slow), limits);
+
+        let client = Client::builder().http2_only(true).build_http::<Body>();
+        let uri: ::hyper::Uri = format!("http://{}/", addr).parse().unwrap();
+        let requests: Vec<_> = (0..3)
+            .map(|_| client.get(uri.clone()).map(|response| response.status()))
+            .collect();
+
+        let statuses = runtime.block_on(future::join_all(requests)).unwrap();
+        assert!(statuses.contains(&StatusCode::OK));
+        assert!(statuses.contains(&StatusCode::SERVICE_UNAVAILABLE));
+    }
+}
@@ -0,0 +1,435 @@
+//! Defines `Listeners`, the set of named listeners which an application started via
+//! `gotham::start_on_listeners` accepts connections from.
+
+use std::fmt;
+use std::io;
+use std::net::{self, SocketAddr, ToSocketAddrs};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+
+use futures::{future, Future};
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::runtime::TaskExecutor;
+
+use error::Result;
+use handler::NewHandler;
+use service::GothamService;
+use shutdown::ShutdownSignal;
+#[cfg(feature = "tls")]
+use tls::TlsConfig;
+use workers::from_std;
+
+/// A set of named listeners, which an application started via `gotham::start_on_listeners`
+/// serves the same `Router` on, e.g. an IPv4 and an IPv6 address, or an internal port alongside
+/// an external one.
+///
+/// Each listener is bound as it's added, so that an address which can't be bound is reported
+/// before the application starts. The listeners already bound are closed along with the
+/// `Listeners`, when it's dropped.
+///
+/// The name of the listener which a request arrived on is available via
+/// `ConnectionInfo::listener`. `Listeners` can also be added to a `ServerBuilder`, alongside its
+/// other options.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response};
+/// # use gotham::listeners::Listeners;
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let listeners = Listeners::new()
+///     .bind("v4", "0.0.0.0:8080")
+///     .and_then(|listeners| listeners.bind("v6", "[::]:8080"))
+///     .and_then(|listeners| listeners.bind("internal", "127.0.0.1:9090"))
+///     .expect("unable to bind listeners");
+///
+/// gotham::start_on_listeners(listeners, || Ok(handler));
+/// # }
+/// ```
+pub struct Listeners {
+    listeners: Vec<Listener>,
+}
+
+struct Listener {
+    name: Option<String>,
+    kind: ListenerKind,
+}
+
+pub(crate) enum ListenerKind {
+    Tcp(net::TcpListener),
+    #[cfg(feature = "tls")]
+    Tls(net::TcpListener, TlsConfig),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listeners {
+    /// Creates an empty set of listeners.
+    pub fn new() -> Listeners {
+        Listeners {
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Binds a TCP listener named `name` to `addr`.
+    pub fn bind<A>(self, name: &str, addr: A) -> Result<Listeners>
+    where
+        A: ToSocketAddrs,
+    {
+        let listener = net::TcpListener::bind(addr)?;
+        Ok(self.with_listener(name, listener))
+    }
+
+    /// Adds a TCP listener named `name` which is already bound, as `gotham::start_on_listener`
+    /// accepts.
+    pub fn with_listener(mut self, name: &str, listener: net::TcpListener) -> Listeners {
+        self.push(Some(name), ListenerKind::Tcp(listener));
+        self
+    }
+
+    /// Binds a TCP listener named `name` to `addr`, accepting connections over TLS as
+    /// `gotham::start_with_tls` does. Requires the `tls` feature.
+    #[cfg(feature = "tls")]
+    pub fn bind_tls<A>(mut self, name: &str, addr: A, tls_config: TlsConfig) -> Result<Listeners>
+    where
+        A: ToSocketAddrs,
+    {
+        let listener = net::TcpListener::bind(addr)?;
+        self.push(Some(name), ListenerKind::Tls(listener, tls_config));
+        Ok(self)
+    }
+
+    /// Binds a Unix domain socket named `name` at `path`, as `gotham::start_unix_with_mode` does
+    /// when `mode` is given, or `gotham::start_unix` otherwise. Unix only.
+    #[cfg(unix)]
+    pub fn bind_unix<P>(mut self, name: &str, path: P, mode: Option<u32>) -> Result<Listeners>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let listener = ::unix_listener(path, mode)?;
+        self.push(Some(name), ListenerKind::Unix(listener, path.to_path_buf()));
+        Ok(self)
+    }
+
+    /// Adds a listener, which is unnamed in `ConnectionInfo` when `name` is `None`.
+    pub(crate) fn push(&mut self, name: Option<&str>, kind: ListenerKind) {
+        self.listeners.push(Listener {
+            name: name.map(str::to_owned),
+            kind,
+        });
+    }
+
+    /// Adds each of the `other` listeners, after those already added.
+    pub(crate) fn append(&mut self, other: Listeners) {
+        self.listeners.extend(other.listeners);
+    }
+
+    /// The number of listeners.
+    pub fn len(&self) -> usize {
+        self.listeners.len()
+    }
+
+    /// Determines if there are no listeners.
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+
+    /// The addresses of the TCP listeners, including those accepting connections over TLS.
+    pub(crate) fn addrs(&self) -> Vec<SocketAddr> {
+        self.listeners
+            .iter()
+            .filter_map(|listener| match listener.kind {
+                ListenerKind::Tcp(ref listener) => listener.local_addr().ok(),
+                #[cfg(feature = "tls")]
+                ListenerKind::Tls(ref listener, _) => listener.local_addr().ok(),
+                #[cfg(unix)]
+                ListenerKind::Unix(..) => None,
+            })
+            .collect()
+    }
+
+    /// The paths of the Unix domain sockets.
+    #[cfg(unix)]
+    pub(crate) fn socket_paths(&self) -> Vec<PathBuf> {
+        self.listeners
+            .iter()
+            .filter_map(|listener| match listener.kind {
+                ListenerKind::Unix(_, ref path) => Some(path.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Determines if any listener accepts connections over TLS.
+    #[cfg(feature = "tls")]
+    pub(crate) fn has_tls(&self) -> bool {
+        self.listeners.iter().any(|listener| match listener.kind {
+            ListenerKind::Tls(..) => true,
+            _ => false,
+        })
+    }
+
+    /// Determines if any listener is a Unix domain socket.
+    pub(crate) fn has_unix(&self) -> bool {
+        self.listeners.iter().any(|listener| match listener.kind {
+            #[cfg(unix)]
+            ListenerKind::Unix(..) => true,
+            _ => false,
+        })
+    }
+
+    /// Duplicates the TCP listeners, including those accepting connections over TLS, so that
+    /// each worker of a `ListenerLayout::PerWorker` application accepts from its own copy. Unix
+    /// domain sockets can't be duplicated.
+    pub(crate) fn try_clone(&self) -> io::Result<Listeners> {
+        let mut listeners = Listeners::new();
+
+        for listener in &self.listeners {
+            let kind = match listener.kind {
+                ListenerKind::Tcp(ref tcp) => ListenerKind::Tcp(tcp.try_clone()?),
+                #[cfg(feature = "tls")]
+                ListenerKind::Tls(ref tcp, ref tls_config) => {
+                    ListenerKind::Tls(tcp.try_clone()?, tls_config.clone())
+                }
+                #[cfg(unix)]
+                ListenerKind::Unix(..) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "unable to duplicate a Unix domain socket",
+                    ))
+                }
+            };
+
+            listeners.push(listener.name.as_ref().map(String::as_str), kind);
+        }
+
+        Ok(listeners)
+    }
+
+    /// Spawns a task accepting connections from each listener onto `executor`, each serving
+    /// requests via a copy of `service` which names the listener.
+    pub(crate) fn spawn<NH>(self, service: GothamService<NH>, executor: &TaskExecutor)
+    where
+        NH: NewHandler + 'static,
+    {
+        for accept in self.serve(service, true) {
+            executor.spawn(accept);
+        }
+    }
+
+    /// Returns a future accepting connections from each listener, each serving requests via a
+    /// copy of `service` which names the listener. The TCP listeners are registered with the
+    /// reactor of the runtime which first polls their future. When `announce` is set, each
+    /// listener is logged as it starts.
+    pub(crate) fn serve<NH>(
+        self,
+        service: GothamService<NH>,
+        announce: bool,
+    ) -> Vec<Box<Future<Item = (), Error = ()> + Send>>
+    where
+        NH: NewHandler + 'static,
+    {
+        self.listeners
+            .into_iter()
+            .map(|Listener { name, kind }| {
+                let service = if announce {
+                    listening(name, &kind, &service)
+                } else {
+                    named(name, &service)
+                };
+
+                let accept: Box<Future<Item = (), Error = ()> + Send> = match kind {
+                    ListenerKind::Tcp(listener) => {
+                        Box::new(future::lazy(move || ::serve(from_std(listener), service)))
+                    }
+                    #[cfg(feature = "tls")]
+                    ListenerKind::Tls(listener, tls_config) => Box::new(future::lazy(move || {
+                        ::serve_tls(from_std(listener), service, tls_config.acceptor())
+                    })),
+                    #[cfg(unix)]
+                    ListenerKind::Unix(listener, _) => Box::new(::serve_unix(listener, service)),
+                };
+
+                accept
+            })
+            .collect()
+    }
+
+    /// Spawns a task accepting connections from each listener onto `executor`, as `spawn` does,
+    /// until shutdown begins.
+    pub(crate) fn spawn_until_shutdown<NH>(
+        self,
+        service: GothamService<NH>,
+        executor: &TaskExecutor,
+        signal: ShutdownSignal,
+    ) where
+        NH: NewHandler + 'static,
+    {
+        for Listener { name, kind } in self.listeners {
+            let service = listening(name, &kind, &service);
+            let signal = signal.clone();
+
+            match kind {
+                ListenerKind::Tcp(listener) => {
+                    executor.spawn(future::lazy(move || {
+                        ::serve_until_shutdown(from_std(listener), service, signal)
+                    }));
+                }
+                #[cfg(feature = "tls")]
+                ListenerKind::Tls(listener, tls_config) => {
+                    executor.spawn(future::lazy(move || {
+                        let acceptor = tls_config.acceptor();
+                        ::serve_tls_until_shutdown(from_std(listener), service, acceptor, signal)
+                    }));
+                }
+                #[cfg(unix)]
+                ListenerKind::Unix(listener, _) => {
+                    let connections = ::unix_connections(listener, service);
+                    executor.spawn(::accept_until_shutdown(connections, signal));
+                }
+            }
+        }
+    }
+}
+
+/// Logs that the listener has started, returning a copy of `service` which names it.
+fn listening<NH>(
+    name: Option<String>,
+    kind: &ListenerKind,
+    service: &GothamService<NH>,
+) -> GothamService<NH>
+where
+    NH: NewHandler + 'static,
+{
+    match name {
+        Some(ref name) => {
+            info!(target: "gotham::start", " Gotham listening on {} ({})", kind, name)
+        }
+        None => info!(target: "gotham::start", " Gotham listening on {}", kind),
+    }
+
+    named(name, service)
+}
+
+/// Returns a copy of `service` which names the listener.
+fn named<NH>(name: Option<String>, service: &GothamService<NH>) -> GothamService<NH>
+where
+    NH: NewHandler + 'static,
+{
+    match name {
+        Some(name) => service.clone().with_listener(&name),
+        None => service.clone(),
+    }
+}
+
+impl Default for Listeners {
+    fn default() -> Listeners {
+        Listeners::new()
+    }
+}
+
+impl fmt::Display for ListenerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ListenerKind::Tcp(ref listener) => write_addr(f, "http", listener),
+            #[cfg(feature = "tls")]
+            ListenerKind::Tls(ref listener, _) => write_addr(f, "https", listener),
+            #[cfg(unix)]
+            ListenerKind::Unix(_, ref path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+fn write_addr(f: &mut fmt::Formatter, scheme: &str, listener: &net::TcpListener) -> fmt::Result {
+    match listener.local_addr() {
+        Ok(addr) => write!(f, "{}://{}", scheme, addr),
+        Err(_) => write!(f, "{}://<unknown>", scheme),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+    use std::net::SocketAddr;
+    use std::thread;
+
+    use hyper::{Body, Response, StatusCode};
+
+    use helpers::http::response::create_text_response;
+    use state::connection::ConnectionInfo;
+    use state::{FromState, State};
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let listener = ConnectionInfo::borrow_from(&state)
+            .listener()
+            .unwrap_or("none")
+            .to_owned();
+
+        let res = create_text_response(&state, StatusCode::OK, listener);
+        (state, res)
+    }
+
+    fn get(addr: SocketAddr) -> String {
+        let mut stream = net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn loopback() -> (net::TcpListener, SocketAddr) {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        (listener, addr)
+    }
+
+    #[test]
+    fn serves_on_every_listener() {
+        let (internal, internal_addr) = loopback();
+        let (external, external_addr) = loopback();
+
+        let listeners = Listeners::new()
+            .with_listener("internal", internal)
+            .with_listener("external", external);
+        assert_eq!(listeners.len(), 2);
+
+        thread::spawn(move || ::start_on_listeners(listeners, || Ok(handler)));
+
+        let response = get(internal_addr);
+        assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+        assert!(response.ends_with("internal"), response);
+
+        let response = get(external_addr);
+        assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+        assert!(response.ends_with("external"), response);
+    }
+
+    #[test]
+    fn failed_bind_closes_other_listeners() {
+        let (first, first_addr) = loopback();
+        let (_taken, taken_addr) = loopback();
+
+        let result = Listeners::new()
+            .with_listener("first", first)
+            .bind("second", taken_addr);
+
+        assert!(result.is_err());
+        assert!(net::TcpStream::connect(first_addr).is_err());
+    }
+}
@@ -10,6 +10,7 @@ use state::State;
 pub mod chain;
 pub mod session;
 pub mod state;
+pub mod validation;
 
 /// `Middleware` has the opportunity to provide additional behaviour to the `Request` / `Response`
 /// interaction. For example:
@@ -13,13 +13,14 @@ use futures::{
     future::{self, FutureResult},
     Future,
 };
-use hyper::header::{HeaderMap, COOKIE, SET_COOKIE};
+use hyper::header::{HeaderMap, COOKIE};
 use hyper::{Body, Response, StatusCode};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 
 use super::{Middleware, NewMiddleware};
 use handler::{HandlerError, HandlerFuture, IntoHandlerError};
+use helpers::http::cookies::{remove_cookie, set_cookie, InvalidCookie, SameSite, SetCookie};
 use helpers::http::response::extend_response;
 use state::{self, FromState, State, StateData};
 
@@ -98,37 +99,23 @@ impl Default for SessionCookieConfig {
 }
 
 impl SessionCookieConfig {
-    fn to_cookie_string(&self, value: &str) -> String {
-        // Ensure this is always enough to prevent re-allocs
-        let mut cookie_value = String::with_capacity(255);
-
-        cookie_value.push_str(&self.name);
-        cookie_value.push('=');
-        cookie_value.push_str(value);
-
-        if self.secure {
-            cookie_value.push_str("; Secure")
-        }
-
-        if self.http_only {
-            cookie_value.push_str("; HttpOnly")
-        }
-
-        match self.same_site {
-            SameSiteEnforcement::Strict => cookie_value.push_str("; SameSite=Strict"),
-            SameSiteEnforcement::Lax => cookie_value.push_str("; SameSite=Lax"),
-            SameSiteEnforcement::Disabled => (),
-        }
-
-        if let Some(ref domain) = self.domain {
-            cookie_value.push_str("; Domain=");
-            cookie_value.push_str(domain);
-        }
+    fn to_set_cookie(&self, value: &str) -> Result<SetCookie, InvalidCookie> {
+        let cookie = SetCookie::new(self.name.as_str(), value)?
+            .secure(self.secure)
+            .http_only(self.http_only);
+
+        let cookie = match self.same_site {
+            SameSiteEnforcement::Strict => cookie.same_site(SameSite::Strict),
+            SameSiteEnforcement::Lax => cookie.same_site(SameSite::Lax),
+            SameSiteEnforcement::Disabled => cookie,
+        };
 
-        cookie_value.push_str("; Path=");
-        cookie_value.push_str(&self.path);
+        let cookie = match self.domain {
+            Some(ref domain) => cookie.domain(domain.as_str()),
+            None => cookie,
+        };
 
-        cookie_value
+        Ok(cookie.path(self.path.as_str()))
     }
 
     /// Validates cookie attributes if the name includes a Cookie Prefix.
@@ -912,27 +899,20 @@ fn send_cookie<B, T>(response: &mut Response<B>, session_data: &SessionData<T>)
 where
     T: Default + Serialize + for<'de> Deserialize<'de> + Send + 'static,
 {
-    let cookie_string = session_data
+    match session_data
         .cookie_config
-        .to_cookie_string(&session_data.identifier.value);
-    write_cookie(cookie_string, response);
+        .to_set_cookie(&session_data.identifier.value)
+    {
+        Ok(cookie) => set_cookie(response, &cookie),
+        Err(e) => error!("[ERROR][Unable to send session cookie: {}]", e),
+    }
 }
 
 fn reset_cookie<B>(response: &mut Response<B>, session_drop_data: &SessionDropData) {
-    let cookie_string = session_drop_data
-        .cookie_config
-        .to_cookie_string("discarded");
-    let cookie_string = format!(
-        "{}; expires=Thu, 01 Jan 1970 00:00:00 GMT; max-age=0",
-        cookie_string
-    );
-    write_cookie(cookie_string, response);
-}
-
-fn write_cookie<B>(cookie: String, response: &mut Response<B>) {
-    response
-        .headers_mut()
-        .append(SET_COOKIE, cookie.parse().unwrap());
+    match session_drop_data.cookie_config.to_set_cookie("discarded") {
+        Ok(cookie) => remove_cookie(response, cookie),
+        Err(e) => error!("[ERROR][Unable to remove session cookie: {}]", e),
+    }
 }
 
 fn write_session<B, T>(
@@ -1092,7 +1072,10 @@ mod tests {
         assert!(m.cookie_config.domain.is_none());
 
         assert_eq!(
-            m.cookie_config.to_cookie_string(&identifier.value),
+            m.cookie_config
+                .to_set_cookie(&identifier.value)
+                .unwrap()
+                .to_string(),
             format!(
                 "_gotham_session={}; Secure; HttpOnly; SameSite=Lax; Path=/",
                 &identifier.value
@@ -1144,7 +1127,10 @@ mod tests {
         assert_eq!(identifier.value.len(), 86);
 
         assert_eq!(
-            m.cookie_config.to_cookie_string(&identifier.value),
+            m.cookie_config
+                .to_set_cookie(&identifier.value)
+                .unwrap()
+                .to_string(),
             format!(
                 "_my_session={}; HttpOnly; SameSite=Strict; Domain=example.com; Path=/myapp",
                 &identifier.value
@@ -1162,7 +1148,10 @@ mod tests {
         assert_eq!(identifier.value.len(), 86);
 
         assert_eq!(
-            m.cookie_config.to_cookie_string(&identifier.value),
+            m.cookie_config
+                .to_set_cookie(&identifier.value)
+                .unwrap()
+                .to_string(),
             format!(
                 "x_session={}; Secure; HttpOnly; Path=/xapp",
                 &identifier.value
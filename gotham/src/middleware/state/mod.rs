@@ -18,6 +18,63 @@ use std::panic::RefUnwindSafe;
 ///
 /// The generic types inside this struct can (and will) be cloned
 /// often, so wrap your expensive types in reference counts as needed.
+/// A common pattern is a cheap handle holding an `Arc` of the shared
+/// data, such that each clone only increments a reference count:
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::collections::HashMap;
+/// # use std::sync::Arc;
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::middleware::state::StateMiddleware;
+/// # use gotham::pipeline::single_middleware;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::{FromState, State, StateData};
+/// # use gotham::test::TestServer;
+/// #
+/// struct Inner {
+///     settings: HashMap<String, String>,
+/// }
+///
+/// #[derive(Clone)]
+/// struct Config {
+///     inner: Arc<Inner>,
+/// }
+///
+/// impl StateData for Config {}
+///
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let greeting = Config::borrow_from(&state).inner.settings["greeting"].clone();
+///     (state, Response::new(greeting.into()))
+/// }
+///
+/// # fn main() {
+/// let mut settings = HashMap::new();
+/// settings.insert("greeting".to_owned(), "Hello!".to_owned());
+///
+/// let config = Config {
+///     inner: Arc::new(Inner { settings }),
+/// };
+///
+/// let (chain, pipelines) = single_pipeline(single_middleware(StateMiddleware::new(config)));
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/").to(handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server.client().get("http://localhost/").perform().unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert_eq!(response.read_utf8_body().unwrap(), "Hello!");
+/// # }
+/// ```
+///
+/// Only a single `StateMiddleware` should be registered for each type `T`
+/// within a pipeline, as a later value would silently replace an earlier
+/// one. In debug builds this mistake is caught by an assertion when the
+/// first request is processed.
 #[derive(Clone)]
 pub struct StateMiddleware<T>
 where
@@ -49,6 +106,12 @@ where
     where
         Chain: FnOnce(State) -> Box<HandlerFuture>,
     {
+        debug_assert!(
+            !state.has::<T>(),
+            "StateMiddleware attempted to put a value into State which is already present, is \
+             the same type registered in multiple StateMiddleware instances?"
+        );
+
         state.put(self.t);
         chain(state)
     }
@@ -66,3 +129,61 @@ where
         Ok(self.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::future;
+    use hyper::{Body, Response, StatusCode};
+
+    use pipeline::new_pipeline;
+    use pipeline::single::single_pipeline;
+    use router::builder::*;
+    use state::FromState;
+    use test::TestServer;
+
+    #[derive(Clone)]
+    struct Value(usize);
+
+    impl StateData for Value {}
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let body = Value::borrow_from(&state).0.to_string();
+        (state, Response::new(body.into()))
+    }
+
+    #[test]
+    fn puts_value_into_state() {
+        let (chain, pipelines) =
+            single_pipeline(new_pipeline().add(StateMiddleware::new(Value(42))).build());
+
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/").to(handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "42");
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "already present")]
+    fn duplicate_registration_is_detected() {
+        let middleware = StateMiddleware::new(Value(1));
+
+        let mut state = State::new();
+        state.put(Value(0));
+
+        middleware.call(state, |state| -> Box<HandlerFuture> {
+            Box::new(future::ok((state, Response::new(Body::empty()))))
+        });
+    }
+}
@@ -0,0 +1,395 @@
+//! Defines `RequestValidation`, which rejects malformed or abusive requests before they are
+//! routed.
+
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+use futures::future;
+use hyper::header::{HeaderMap, HOST, TRANSFER_ENCODING};
+use hyper::{Body, Response, StatusCode, Uri};
+
+use handler::HandlerFuture;
+use helpers::http::response::create_response;
+use middleware::{Middleware, NewMiddleware};
+use state::{request_id, FromState, State};
+
+/// The transfer codings registered by RFC 7230, including the `x-` aliases which recipients are
+/// expected to accept.
+const KNOWN_TRANSFER_CODINGS: [&str; 6] = [
+    "chunked",
+    "compress",
+    "deflate",
+    "gzip",
+    "x-compress",
+    "x-gzip",
+];
+
+/// A rule applied by `RequestValidation`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationRule {
+    /// The request must have exactly one `Host` header. Responds with `400 Bad Request` by
+    /// default.
+    Host,
+    /// The request URI must not exceed a length in bytes. Responds with `414 URI Too Long` by
+    /// default.
+    UriLength,
+    /// The request must not have more than a number of headers. Responds with
+    /// `431 Request Header Fields Too Large` by default.
+    HeaderCount,
+    /// No request header value may exceed a length in bytes. Responds with
+    /// `431 Request Header Fields Too Large` by default.
+    HeaderValueLength,
+    /// Each coding in the `Transfer-Encoding` header must be one registered by RFC 7230. Responds
+    /// with `501 Not Implemented` by default.
+    TransferEncoding,
+}
+
+impl ValidationRule {
+    /// The name of the rule, as used when logging a rejected request.
+    pub fn name(self) -> &'static str {
+        match self {
+            ValidationRule::Host => "host",
+            ValidationRule::UriLength => "uri_length",
+            ValidationRule::HeaderCount => "header_count",
+            ValidationRule::HeaderValueLength => "header_value_length",
+            ValidationRule::TransferEncoding => "transfer_encoding",
+        }
+    }
+
+    fn default_status(self) -> StatusCode {
+        match self {
+            ValidationRule::Host => StatusCode::BAD_REQUEST,
+            ValidationRule::UriLength => StatusCode::URI_TOO_LONG,
+            ValidationRule::HeaderCount | ValidationRule::HeaderValueLength => {
+                StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+            }
+            ValidationRule::TransferEncoding => StatusCode::NOT_IMPLEMENTED,
+        }
+    }
+}
+
+impl Display for ValidationRule {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Rejects requests which break any of the enabled `ValidationRule`s, responding with the status
+/// configured for the first rule which fails. Every rule is disabled by default.
+///
+/// `RequestValidation` is used either for every request handled by the server, via
+/// `gotham::start_with_request_validation`, or as a `Middleware` in a `Pipeline`. At the server
+/// level, the checks are made before any `Router`, `Pipeline` or `Middleware` is invoked. Each
+/// rejection is logged at the `info` level, with the request id and the name of the rule.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::middleware::validation::{RequestValidation, ValidationRule};
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::pipeline::single_middleware;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let validation = RequestValidation::new()
+///     .require_host(true)
+///     .max_uri_length(2048)
+///     .max_header_count(64)
+///     .with_status(ValidationRule::HeaderCount, StatusCode::BAD_REQUEST);
+///
+/// let (chain, pipelines) = single_pipeline(single_middleware(validation));
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/:name").to(handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let long = format!("http://localhost/{}", "a".repeat(2048));
+/// # let response = test_server.client().get(&long).perform().unwrap();
+/// # assert_eq!(response.status(), StatusCode::URI_TOO_LONG);
+/// #
+/// # let response = test_server.client().get("http://localhost/a").perform().unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RequestValidation {
+    host: bool,
+    uri_length: Option<usize>,
+    header_count: Option<usize>,
+    header_value_length: Option<usize>,
+    transfer_encoding: bool,
+    statuses: Vec<(ValidationRule, StatusCode)>,
+}
+
+impl RequestValidation {
+    /// Creates a `RequestValidation` with every rule disabled.
+    pub fn new() -> RequestValidation {
+        RequestValidation::default()
+    }
+
+    /// Sets whether a request must have exactly one `Host` header.
+    pub fn require_host(self, host: bool) -> RequestValidation {
+        RequestValidation { host, ..self }
+    }
+
+    /// Limits the length of the request URI, in bytes.
+    pub fn max_uri_length(self, limit: usize) -> RequestValidation {
+        RequestValidation {
+            uri_length: Some(limit),
+            ..self
+        }
+    }
+
+    /// Limits the number of request headers.
+    pub fn max_header_count(self, limit: usize) -> RequestValidation {
+        RequestValidation {
+            header_count: Some(limit),
+            ..self
+        }
+    }
+
+    /// Limits the length of each request header value, in bytes.
+    pub fn max_header_value_length(self, limit: usize) -> RequestValidation {
+        RequestValidation {
+            header_value_length: Some(limit),
+            ..self
+        }
+    }
+
+    /// Sets whether a request with a `Transfer-Encoding` coding which isn't registered by
+    /// RFC 7230 is rejected.
+    pub fn reject_unknown_transfer_encoding(self, reject: bool) -> RequestValidation {
+        RequestValidation {
+            transfer_encoding: reject,
+            ..self
+        }
+    }
+
+    /// Sets the status of the response sent when `rule` fails, replacing its default.
+    pub fn with_status(mut self, rule: ValidationRule, status: StatusCode) -> RequestValidation {
+        self.statuses.retain(|&(r, _)| r != rule);
+        self.statuses.push((rule, status));
+        self
+    }
+
+    /// The status of the response sent when `rule` fails.
+    pub fn status(&self, rule: ValidationRule) -> StatusCode {
+        self.statuses
+            .iter()
+            .find(|&&(r, _)| r == rule)
+            .map(|&(_, status)| status)
+            .unwrap_or_else(|| rule.default_status())
+    }
+
+    /// Applies the enabled rules to the request in `state`, returning the first which fails.
+    pub fn check(&self, state: &State) -> Result<(), ValidationRule> {
+        let headers = HeaderMap::borrow_from(state);
+
+        if self.host && headers.get_all(HOST).iter().count() != 1 {
+            return Err(ValidationRule::Host);
+        }
+
+        if let Some(limit) = self.uri_length {
+            if Uri::borrow_from(state).to_string().len() > limit {
+                return Err(ValidationRule::UriLength);
+            }
+        }
+
+        if let Some(limit) = self.header_count {
+            if headers.len() > limit {
+                return Err(ValidationRule::HeaderCount);
+            }
+        }
+
+        if let Some(limit) = self.header_value_length {
+            if headers.values().any(|value| value.len() > limit) {
+                return Err(ValidationRule::HeaderValueLength);
+            }
+        }
+
+        if self.transfer_encoding {
+            let unknown = headers
+                .get_all(TRANSFER_ENCODING)
+                .iter()
+                .flat_map(|value| {
+                    value
+                        .to_str()
+                        .unwrap_or("\u{0}")
+                        .split(',')
+                        .map(|coding| coding.split(';').next().unwrap_or("").trim())
+                        .collect::<Vec<_>>()
+                })
+                .any(|coding| {
+                    !KNOWN_TRANSFER_CODINGS
+                        .iter()
+                        .any(|known| known.eq_ignore_ascii_case(coding))
+                });
+
+            if unknown {
+                return Err(ValidationRule::TransferEncoding);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Logs the rejection of the request by `rule`, and creates the response to send.
+    pub(crate) fn reject(&self, state: &State, rule: ValidationRule) -> Response<Body> {
+        info!(
+            "[{}] request rejected by the `{}` validation rule",
+            request_id(state),
+            rule
+        );
+
+        create_response(state, self.status(rule), None)
+    }
+}
+
+impl Middleware for RequestValidation {
+    fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+    where
+        Chain: FnOnce(State) -> Box<HandlerFuture>,
+    {
+        match self.check(&state) {
+            Ok(()) => chain(state),
+            Err(rule) => {
+                let res = self.reject(&state, rule);
+                Box::new(future::ok((state, res)))
+            }
+        }
+    }
+}
+
+impl NewMiddleware for RequestValidation {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::Method;
+
+    use state::set_request_id;
+
+    fn state_with(uri: &str, headers: &[(&'static str, &str)]) -> State {
+        let mut map = HeaderMap::new();
+        for &(name, value) in headers {
+            map.append(name, value.parse().unwrap());
+        }
+
+        let mut state = State::new();
+        state.put(Method::GET);
+        state.put(uri.parse::<Uri>().unwrap());
+        state.put(map);
+        set_request_id(&mut state);
+        state
+    }
+
+    #[test]
+    fn rules_are_disabled_by_default() {
+        let long = format!("/{}", "a".repeat(10_000));
+        let state = state_with(&long, &[("transfer-encoding", "rot13")]);
+        assert_eq!(RequestValidation::new().check(&state), Ok(()));
+    }
+
+    #[test]
+    fn host_rule() {
+        let validation = RequestValidation::new().require_host(true);
+
+        let cases: Vec<(&[(&'static str, &str)], Result<(), ValidationRule>)> = vec![
+            (&[("host", "example.com")], Ok(())),
+            (&[], Err(ValidationRule::Host)),
+            (
+                &[("host", "example.com"), ("host", "evil.example.com")],
+                Err(ValidationRule::Host),
+            ),
+        ];
+
+        for (headers, expected) in cases {
+            assert_eq!(validation.check(&state_with("/", headers)), expected);
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let validation = RequestValidation::new()
+            .max_uri_length(16)
+            .max_header_count(2)
+            .max_header_value_length(8);
+
+        assert_eq!(
+            validation.check(&state_with("/123456789012345", &[("a", "12345678")])),
+            Ok(())
+        );
+        assert_eq!(
+            validation.check(&state_with("/12345678?q=1234", &[])),
+            Ok(())
+        );
+        assert_eq!(
+            validation.check(&state_with("/12345678?q=12345", &[])),
+            Err(ValidationRule::UriLength)
+        );
+        assert_eq!(
+            validation.check(&state_with("/", &[("a", "1"), ("b", "2"), ("c", "3")])),
+            Err(ValidationRule::HeaderCount)
+        );
+        assert_eq!(
+            validation.check(&state_with("/", &[("a", "123456789")])),
+            Err(ValidationRule::HeaderValueLength)
+        );
+    }
+
+    #[test]
+    fn transfer_encoding_rule() {
+        let validation = RequestValidation::new().reject_unknown_transfer_encoding(true);
+
+        for value in vec!["chunked", "gzip, Chunked", "x-gzip,chunked"] {
+            let state = state_with("/", &[("transfer-encoding", value)]);
+            assert_eq!(validation.check(&state), Ok(()), "{}", value);
+        }
+
+        for value in vec!["rot13", "gzip, rot13, chunked", "chunked, "] {
+            let state = state_with("/", &[("transfer-encoding", value)]);
+            assert_eq!(
+                validation.check(&state),
+                Err(ValidationRule::TransferEncoding),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn rejection_status() {
+        let validation = RequestValidation::new()
+            .max_header_count(0)
+            .with_status(ValidationRule::HeaderCount, StatusCode::NOT_FOUND)
+            .with_status(ValidationRule::HeaderCount, StatusCode::BAD_REQUEST);
+
+        let state = state_with("/", &[("a", "1")]);
+        let rule = validation.check(&state).unwrap_err();
+        assert_eq!(rule.name(), "header_count");
+
+        let res = validation.reject(&state, rule);
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            validation.status(ValidationRule::UriLength),
+            StatusCode::URI_TOO_LONG
+        );
+    }
+}
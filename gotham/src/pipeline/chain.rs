@@ -9,7 +9,7 @@ use handler::{HandlerFuture, IntoHandlerError};
 use middleware::chain::NewMiddlewareChain;
 use pipeline::set::PipelineSet;
 use pipeline::Pipeline;
-use state::{request_id, State};
+use state::{request_id, State, StateData};
 
 /// A heterogeneous list of `Handle<P, _>` values, where `P` is a pipeline type. The pipelines are
 /// borrowed and invoked in order to serve a request.
@@ -32,6 +32,31 @@ pub trait PipelineHandleChain<P>: RefUnwindSafe {
         F: FnOnce(State) -> Box<HandlerFuture> + Send + 'static;
 }
 
+/// The pipelines which have already been invoked for a request, identified by their address
+/// within the `PipelineSet`.
+///
+/// A request forwarded by `forward_to` is dispatched to another route while the pipelines of the
+/// original route are still running, so any which the two routes share (such as the pipelines
+/// given to `build_router`) are skipped rather than invoked a second time.
+#[derive(Default)]
+struct InvokedPipelines {
+    pipelines: Vec<usize>,
+}
+
+impl StateData for InvokedPipelines {}
+
+/// Records that the `Pipeline` at `address` is being invoked, returning `false` if it already
+/// was.
+fn mark_invoked(state: &mut State, address: usize) -> bool {
+    let invoked = state.get_or_default::<InvokedPipelines>();
+    if invoked.pipelines.contains(&address) {
+        false
+    } else {
+        invoked.pipelines.push(address);
+        true
+    }
+}
+
 /// Part of a `PipelineHandleChain` which references a `Pipeline` and continues with a tail element.
 impl<'a, P, T, N, U> PipelineHandleChain<P> for (Handle<Pipeline<T>, N>, U)
 where
@@ -46,8 +71,18 @@ where
         F: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
     {
         let (handle, ref chain) = *self;
-        match pipelines.borrow(handle).construct() {
-            Ok(p) => chain.call(pipelines, state, move |state| p.call(state, f)),
+        let pipeline = pipelines.borrow(handle);
+        let address = pipeline as *const Pipeline<T> as usize;
+
+        match pipeline.construct() {
+            Ok(p) => chain.call(pipelines, state, move |mut state| {
+                if mark_invoked(&mut state, address) {
+                    p.call(state, f)
+                } else {
+                    trace!("[{}] pipeline already invoked", request_id(&state));
+                    f(state)
+                }
+            }),
             Err(e) => {
                 trace!("[{}] error borrowing pipeline", request_id(&state));
                 Box::new(future::err((state, e.into_handler_error())))
@@ -0,0 +1,515 @@
+//! Defines `ReadTimeouts`, which bound the time the connections of an application started via
+//! `gotham::start_with_read_timeouts` wait for the client to send each request.
+
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll, Stream};
+use hyper::{Body, Chunk};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::timer::Delay;
+
+use keep_alive::Activity;
+
+const REQUEST_TIMEOUT: &'static [u8] =
+    b"HTTP/1.1 408 Request Timeout\r\nConnection: close\r\nContent-Length: 0\r\n\r\n";
+
+const HTTP2_PREFACE: &'static [u8] = b"PRI ";
+
+/// Bounds on the time taken by a client to send a request, which protect the connections of an
+/// application from a client which sends its request very slowly in order to hold a connection
+/// open.
+///
+/// By default, the complete request head (the request line and headers) must be received within
+/// 30 seconds of the connection being accepted, or of the first byte of the request arriving on a
+/// connection which is kept open between requests. When the head timeout passes, a
+/// `408 Request Timeout` response is written if any of the request has been received, and the
+/// connection is closed. The head timeout applies to HTTP/1 connections; HTTP/2 connections send
+/// frames between requests, and are bounded by the idle timeout of the `KeepAliveConfig` instead.
+///
+/// The request body is unbounded by default, as the time needed for an upload varies between
+/// routes. When a body timeout is set, the body fails with a timeout if it's still being received
+/// once the body timeout has passed since the head was received. `buffer_body` and
+/// `save_body_to_file` respond to this with `408 Request Timeout`, and the connection is closed
+/// after the response. A route which accepts large uploads can instead bound the time between
+/// chunks via `buffer_body_with_idle_timeout`.
+///
+/// A `ReadTimeouts` counts the connections closed by either timeout, which is shared between its
+/// clones, so that a clone kept by the application observes the count.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::thread;
+/// # use std::time::Duration;
+/// # use hyper::{Body, Response};
+/// # use gotham::read_timeout::ReadTimeouts;
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let timeouts = ReadTimeouts::new()
+///     .with_head_timeout(Duration::from_secs(10))
+///     .with_body_timeout(Duration::from_secs(60));
+///
+/// let observed = timeouts.clone();
+/// thread::spawn(move || loop {
+///     println!("timed out connections: {}", observed.timed_out_connections());
+///     thread::sleep(Duration::from_secs(60));
+/// });
+///
+/// gotham::start_with_read_timeouts("127.0.0.1:7878", || Ok(handler), timeouts);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ReadTimeouts {
+    head_timeout: Duration,
+    body_timeout: Option<Duration>,
+    timed_out: Arc<AtomicUsize>,
+}
+
+impl ReadTimeouts {
+    /// Creates a `ReadTimeouts` with a head timeout of 30 seconds, and no body timeout.
+    pub fn new() -> ReadTimeouts {
+        ReadTimeouts {
+            head_timeout: Duration::from_secs(30),
+            body_timeout: None,
+            timed_out: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Sets the time within which the complete request head must be received.
+    pub fn with_head_timeout(self, head_timeout: Duration) -> ReadTimeouts {
+        ReadTimeouts {
+            head_timeout,
+            ..self
+        }
+    }
+
+    /// Sets the time within which the complete request body must be received, measured from when
+    /// the head was received.
+    pub fn with_body_timeout(self, body_timeout: Duration) -> ReadTimeouts {
+        ReadTimeouts {
+            body_timeout: Some(body_timeout),
+            ..self
+        }
+    }
+
+    /// The time within which the complete request head must be received.
+    pub fn head_timeout(&self) -> Duration {
+        self.head_timeout
+    }
+
+    /// The time within which the complete request body must be received, if limited.
+    pub fn body_timeout(&self) -> Option<Duration> {
+        self.body_timeout
+    }
+
+    /// The number of connections which have been closed for exceeding the head or body timeout.
+    pub fn timed_out_connections(&self) -> usize {
+        self.timed_out.load(Ordering::SeqCst)
+    }
+
+    fn record_timeout(&self) {
+        self.timed_out.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Default for ReadTimeouts {
+    fn default() -> ReadTimeouts {
+        ReadTimeouts::new()
+    }
+}
+
+/// Wraps the I/O of a connection, failing the next read once a request head has taken longer
+/// than the head timeout to arrive, which closes the connection.
+///
+/// The head is complete once the `ConnectedGothamService` has been called for it, which is
+/// observed via the number of requests recorded by the `Activity` of the connection.
+pub(crate) struct HeadTimeout<I> {
+    io: I,
+    timeouts: ReadTimeouts,
+    activity: Arc<Activity>,
+    // When the pending head started, and the number of requests which had begun by then.
+    started: Option<(Instant, usize)>,
+    // The bytes of the pending head which have been received, up to the length of the HTTP/2
+    // preface.
+    received: Vec<u8>,
+    http2: bool,
+    delay: Option<Delay>,
+}
+
+impl<I> HeadTimeout<I> {
+    pub(crate) fn new(io: I, timeouts: ReadTimeouts, activity: Arc<Activity>) -> HeadTimeout<I> {
+        let requests = activity.requests();
+
+        HeadTimeout {
+            io,
+            timeouts,
+            activity,
+            // A client which connects without sending anything holds the connection open as well.
+            started: Some((Instant::now(), requests)),
+            received: Vec::new(),
+            http2: false,
+            delay: None,
+        }
+    }
+
+    /// Records bytes received from the client, starting the head timeout when they begin a new
+    /// request while none is in flight.
+    fn record(&mut self, buf: &[u8]) {
+        if self.http2 || buf.is_empty() {
+            return;
+        }
+
+        let requests = self.activity.requests();
+        let pending = self.started.map_or(false, |(_, begun)| begun == requests);
+        if !pending && self.activity.in_flight() == 0 {
+            self.started = Some((Instant::now(), requests));
+            self.received.clear();
+        }
+
+        if self.started.is_some() && self.received.len() < HTTP2_PREFACE.len() {
+            let n = (HTTP2_PREFACE.len() - self.received.len()).min(buf.len());
+            self.received.extend_from_slice(&buf[..n]);
+            self.http2 = self.received[..] == HTTP2_PREFACE[..] && requests == 0;
+        }
+    }
+
+    /// Checks the head timeout while a read is pending, registering the task to be woken when it
+    /// next passes.
+    fn poll_head(&mut self) -> io::Result<()>
+    where
+        I: Write,
+    {
+        let started = match self.started {
+            Some((started, requests)) if !self.http2 && self.activity.requests() == requests => {
+                started
+            }
+            _ => {
+                self.started = None;
+                self.delay = None;
+                return Ok(());
+            }
+        };
+
+        let deadline = started + self.timeouts.head_timeout();
+        let delay = self.delay.get_or_insert_with(|| Delay::new(deadline));
+        if delay.deadline() != deadline {
+            delay.reset(deadline);
+        }
+
+        match delay.poll() {
+            Ok(Async::NotReady) => Ok(()),
+            Ok(Async::Ready(())) => {
+                debug!(
+                    "closing connection after head timeout of {:?}",
+                    self.timeouts.head_timeout()
+                );
+                self.timeouts.record_timeout();
+
+                // The response is written if it fits in the socket buffer, which is all but
+                // certain as the client isn't reading anything else.
+                if !self.received.is_empty() && !HTTP2_PREFACE.starts_with(&self.received) {
+                    let _ = self.io.write(REQUEST_TIMEOUT);
+                }
+
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "timed out reading request head",
+                ))
+            }
+            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+        }
+    }
+}
+
+impl<I> Read for HeadTimeout<I>
+where
+    I: Read + Write,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.io.read(buf) {
+            Ok(n) => {
+                self.record(&buf[..n]);
+                Ok(n)
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                self.poll_head()?;
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<I> Write for HeadTimeout<I>
+where
+    I: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.io.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl<I> AsyncRead for HeadTimeout<I> where I: AsyncRead + AsyncWrite {}
+
+impl<I> AsyncWrite for HeadTimeout<I>
+where
+    I: AsyncWrite,
+{
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
+
+/// Wraps a request body, failing once it hasn't been completely received by the deadline set by
+/// the body timeout. The connection is then closed after the response.
+pub(crate) struct BodyTimeout {
+    body: Body,
+    delay: Delay,
+    timeouts: ReadTimeouts,
+    activity: Arc<Activity>,
+    expired: bool,
+}
+
+impl BodyTimeout {
+    pub(crate) fn new(
+        body: Body,
+        deadline: Instant,
+        timeouts: ReadTimeouts,
+        activity: Arc<Activity>,
+    ) -> BodyTimeout {
+        BodyTimeout {
+            body,
+            delay: Delay::new(deadline),
+            timeouts,
+            activity,
+            expired: false,
+        }
+    }
+
+    fn timed_out() -> io::Error {
+        io::Error::new(io::ErrorKind::TimedOut, "timed out reading request body")
+    }
+}
+
+impl Stream for BodyTimeout {
+    type Item = Chunk;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, io::Error> {
+        if self.expired {
+            return Err(BodyTimeout::timed_out());
+        }
+
+        let poll = self.body.poll();
+        if let Async::Ready(chunk) = poll.map_err(|e| io::Error::new(io::ErrorKind::Other, e))? {
+            return Ok(Async::Ready(chunk));
+        }
+
+        match self.delay.poll() {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Ok(Async::Ready(())) => {
+                debug!(
+                    "request body not received within body timeout of {:?}",
+                    self.timeouts.body_timeout()
+                );
+                self.expired = true;
+                self.timeouts.record_timeout();
+                self.activity.close();
+                Err(BodyTimeout::timed_out())
+            }
+            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::{self, SocketAddr};
+    use std::thread;
+
+    use hyper::{Response, StatusCode};
+    use tokio::net::TcpListener;
+    use tokio::runtime::Runtime;
+
+    use handler::{HandlerFuture, NewHandler};
+    use helpers::http::request::body::buffer_body;
+    use helpers::http::response::create_text_response;
+    use service::GothamService;
+    use state::State;
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let res = create_text_response(&state, StatusCode::OK, "served");
+        (state, res)
+    }
+
+    fn buffered(state: State) -> Box<HandlerFuture> {
+        let f = buffer_body(state, 1024).map(|state| {
+            let res = create_text_response(&state, StatusCode::OK, "buffered");
+            (state, res)
+        });
+
+        Box::new(f)
+    }
+
+    fn start<NH>(runtime: &mut Runtime, new_handler: NH, timeouts: ReadTimeouts) -> SocketAddr
+    where
+        NH: NewHandler + 'static,
+    {
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let service = GothamService::new(new_handler).with_read_timeouts(timeouts);
+        runtime.spawn(::serve(listener, service));
+        addr
+    }
+
+    fn connect(addr: SocketAddr) -> net::TcpStream {
+        let stream = net::TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+    }
+
+    /// Sends `data` a byte at a time, until the connection is closed.
+    fn trickle(stream: &net::TcpStream, data: &'static [u8]) {
+        let mut stream = stream.try_clone().unwrap();
+        thread::spawn(move || {
+            for byte in data.iter().cycle() {
+                if stream.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+        });
+    }
+
+    fn read_to_close(stream: &mut net::TcpStream) -> String {
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response);
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    /// Reads a response which has a `Content-Length`, leaving the connection open.
+    fn read_response(stream: &mut net::TcpStream) -> String {
+        let mut response = Vec::new();
+        let mut buf = [0; 1024];
+        loop {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0, "connection closed mid-response");
+            response.extend_from_slice(&buf[..n]);
+
+            let text = String::from_utf8_lossy(&response).into_owned();
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text[..end]
+                    .lines()
+                    .filter_map(|line| {
+                        let mut parts = line.splitn(2, ':');
+                        match (parts.next(), parts.next()) {
+                            (Some(name), Some(value))
+                                if name.eq_ignore_ascii_case("content-length") =>
+                            {
+                                value.trim().parse::<usize>().ok()
+                            }
+                            _ => None,
+                        }
+                    })
+                    .next()
+                    .unwrap_or(0);
+
+                if response.len() >= end + 4 + length {
+                    return text;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn closes_connection_with_slow_head() {
+        let mut runtime = Runtime::new().unwrap();
+        let timeouts = ReadTimeouts::new().with_head_timeout(Duration::from_millis(300));
+        let addr = start(&mut runtime, || Ok(handler), timeouts.clone());
+
+        let started = Instant::now();
+        let mut stream = connect(addr);
+        trickle(&stream, b"GET / HTTP/1.1\r\nX-Padding: ");
+
+        let response = read_to_close(&mut stream);
+        let elapsed = started.elapsed();
+        assert!(
+            response.starts_with("HTTP/1.1 408 Request Timeout"),
+            response
+        );
+        assert!(elapsed >= Duration::from_millis(300), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+        assert_eq!(timeouts.timed_out_connections(), 1);
+    }
+
+    #[test]
+    fn head_timeout_restarts_for_each_request() {
+        let mut runtime = Runtime::new().unwrap();
+        let timeouts = ReadTimeouts::new().with_head_timeout(Duration::from_millis(300));
+        let addr = start(&mut runtime, || Ok(handler), timeouts.clone());
+
+        let mut stream = connect(addr);
+        for _ in 0..2 {
+            // Waiting between requests is bounded by the idle timeout instead.
+            thread::sleep(Duration::from_millis(200));
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .unwrap();
+
+            let response = read_response(&mut stream);
+            assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+            assert!(response.ends_with("served"), response);
+            thread::sleep(Duration::from_millis(200));
+        }
+
+        assert_eq!(timeouts.timed_out_connections(), 0);
+    }
+
+    #[test]
+    fn fails_slow_body() {
+        let mut runtime = Runtime::new().unwrap();
+        let timeouts = ReadTimeouts::new().with_body_timeout(Duration::from_millis(300));
+        let addr = start(&mut runtime, || Ok(buffered), timeouts.clone());
+
+        let started = Instant::now();
+        let mut stream = connect(addr);
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 1000\r\n\r\n")
+            .unwrap();
+        trickle(&stream, b"data");
+
+        let response = read_to_close(&mut stream);
+        let elapsed = started.elapsed();
+        assert!(
+            response.starts_with("HTTP/1.1 408 Request Timeout"),
+            response
+        );
+        assert!(
+            response.to_lowercase().contains("connection: close"),
+            response
+        );
+        assert!(elapsed >= Duration::from_millis(300), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+        assert_eq!(timeouts.timed_out_connections(), 1);
+    }
+}
@@ -3,10 +3,12 @@ use std::panic::RefUnwindSafe;
 
 use hyper::{Body, Method};
 
-use extractor::{PathExtractor, QueryStringExtractor};
+use extractor::{PathExtractor, QueryStringExtractor, QueryStringOptions};
 use pipeline::chain::PipelineHandleChain;
 use pipeline::set::PipelineSet;
 use router::builder::SingleRouteBuilder;
+use router::response::finalizer::ResponseFinalizerBuilder;
+use router::route::extensions::RouteExtensions;
 use router::route::matcher::{
     AndRouteMatcher, AnyRouteMatcher, MethodOnlyRouteMatcher, RouteMatcher,
 };
@@ -327,9 +329,18 @@ where
 
         SingleRouteBuilder {
             node_builder: *node_builder,
+            path: String::new(),
+            segment_constraints: Vec::new(),
+            automatic_head: true,
+            priority: 0,
+            fallback: false,
+            not_found: false,
+            response_finalizer_builder: ResponseFinalizerBuilder::internal_new(),
             matcher: AndRouteMatcher::new(MethodOnlyRouteMatcher::new(methods), matcher.clone()),
             pipeline_chain: *pipeline_chain,
             pipelines: pipelines.clone(),
+            extensions: RouteExtensions::new(),
+            query_string_options: QueryStringOptions::default(),
             phantom,
         }
     }
@@ -3,12 +3,16 @@ use std::panic::RefUnwindSafe;
 
 use hyper::Method;
 
-use extractor::{NoopPathExtractor, NoopQueryStringExtractor};
+use extractor::{NoopPathExtractor, NoopQueryStringExtractor, QueryStringOptions};
 use pipeline::chain::PipelineHandleChain;
 use pipeline::set::PipelineSet;
+use router::builder::error::report;
 use router::builder::{
-    AssociatedRouteBuilder, DelegateRouteBuilder, RouterBuilder, ScopeBuilder, SingleRouteBuilder,
+    AssociatedRouteBuilder, DelegateRouteBuilder, RouterBuildError, RouterBuilder, ScopeBuilder,
+    SingleRouteBuilder,
 };
+use router::response::finalizer::ResponseFinalizerBuilder;
+use router::route::extensions::RouteExtensions;
 use router::route::matcher::{
     AnyRouteMatcher, IntoRouteMatcher, MethodOnlyRouteMatcher, RouteMatcher,
 };
@@ -360,6 +364,38 @@ where
     ///
     /// * `"/hello/world"` - a static path, matching only a request for exactly `"/hello/world"`
     /// * `"/hello/:name"` - a dynamic path, matching requests for `"/hello/any_value_here"`
+    /// * `"/static/*path"` - a glob, matching one or more remaining segments (such as
+    ///   `"/static/css/site.css"`), which are extracted as `path`
+    /// * `"/static/**path"` - an optional glob, which also matches `"/static"` itself
+    /// * `"/archive/:year/:month?/:day?"` - optional dynamic segments, matching requests for
+    ///   `"/archive/2018"`, `"/archive/2018/06"` and `"/archive/2018/06/21"`
+    ///
+    /// A glob without a name (`*` or `**`) is extracted as `*`. When the same request could be
+    /// matched by more than one route, the most specific segment is preferred: static, then
+    /// constrained (`:name:regex`, or see `DefineSingleRoute::with_segment_regex` and
+    /// `DefineSingleRoute::with_segment_type`), then dynamic, then glob, then optional glob.
+    ///
+    /// Optional segments (`:name?`, which can also be constrained, as `:name?:regex`) can be
+    /// omitted from the end of a request path, and are then left out of the `PathExtractor`, so
+    /// they're extracted into `Option` fields, or fields with a `#[serde(default)]`. A route for
+    /// the shorter path takes precedence over optional segments: given routes for `/archive/:year`
+    /// and `/archive/:year/:month?`, a request for `/archive/2018` is matched only against the
+    /// first route, while a request for `/archive/2018/06` is matched against the second.
+    ///
+    /// The handler, extractors and matchers of the route are shared by all of the `methods`. A
+    /// request for the path with another method receives `405 Method Not Allowed`, with each of
+    /// the `methods` listed in the `Allow` header (along with `HEAD` when `GET` is listed, and
+    /// `OPTIONS` when `RouterBuilder::set_automatic_options` is enabled).
+    ///
+    /// # Panics
+    ///
+    /// When a glob is followed by another segment, since globs must be the final segment of a path.
+    ///
+    /// When an optional segment is followed by a required segment, since only the end of a
+    /// request path can be omitted.
+    ///
+    /// When the route is restricted only by method, and an earlier route for the same path already
+    /// matches all of the `methods`, since the route could never be matched.
     ///
     /// # Examples
     ///
@@ -462,20 +498,200 @@ where
         M: RouteMatcher + Send + Sync + 'static,
     {
         let (node_builder, pipeline_chain, pipelines) = self.component_refs();
-        let node_builder = descend(node_builder, path);
+        let matcher = matcher.into_route_matcher();
+        validate_path(path);
+
+        SingleRouteBuilder {
+            matcher,
+            node_builder,
+            path: path.to_owned(),
+            segment_constraints: Vec::new(),
+            automatic_head: true,
+            priority: 0,
+            fallback: false,
+            not_found: false,
+            response_finalizer_builder: ResponseFinalizerBuilder::internal_new(),
+            pipeline_chain: *pipeline_chain,
+            pipelines: pipelines.clone(),
+            extensions: RouteExtensions::new(),
+            query_string_options: QueryStringOptions::default(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Creates a fallback route, which receives requests to any path at or beneath the current
+    /// location which no other route matches, instead of the `Router` responding with
+    /// `404 Not Found`. The route is further restricted by `matcher`, so a fallback which only
+    /// accepts `GET` requests leaves the `404 Not Found` response in place for other methods.
+    /// A request which matches the path of another route, but not its method, still receives
+    /// `405 Method Not Allowed`.
+    ///
+    /// The fallback route of a scope takes precedence over that of its parent, and the fallback
+    /// routes of a `Router` don't apply to paths delegated to another `Router`. The handler can
+    /// find the original request path in the `Uri` of the request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::{Body, Method, Response, StatusCode, Uri};
+    /// # use gotham::helpers::http::response::create_text_response;
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn users(state: State) -> (State, Response<Body>) {
+    /// #   let response = create_text_response(&state, StatusCode::OK, "users");
+    /// #   (state, response)
+    /// # }
+    /// #
+    /// // Serves the single-page application, which routes on the client by the request path.
+    /// fn index(state: State) -> (State, Response<Body>) {
+    ///     let body = format!("index of {}", Uri::borrow_from(&state).path());
+    ///     let response = create_text_response(&state, StatusCode::OK, body);
+    ///     (state, response)
+    /// }
+    ///
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route.scope("/api", |route| {
+    ///         route.get("/users").to(users);
+    ///     });
+    ///
+    ///     route.fallback(vec![Method::GET, Method::HEAD]).to(index);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/account/settings")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "index of /account/settings");
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .post("https://example.com/account/settings", "", mime::TEXT_PLAIN)
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    /// # }
+    /// ```
+    fn fallback<'b, IRM, M>(&'b mut self, matcher: IRM) -> ExplicitSingleRouteBuilder<'b, M, C, P>
+    where
+        IRM: IntoRouteMatcher<Output = M>,
+        M: RouteMatcher + Send + Sync + 'static,
+    {
+        let (node_builder, pipeline_chain, pipelines) = self.component_refs();
         let matcher = matcher.into_route_matcher();
 
         SingleRouteBuilder {
             matcher,
             node_builder,
+            path: "/".to_owned(),
+            segment_constraints: Vec::new(),
+            automatic_head: false,
+            priority: 0,
+            fallback: true,
+            not_found: false,
+            response_finalizer_builder: ResponseFinalizerBuilder::internal_new(),
             pipeline_chain: *pipeline_chain,
             pipelines: pipelines.clone(),
+            extensions: RouteExtensions::new(),
+            query_string_options: QueryStringOptions::default(),
             phantom: PhantomData,
         }
     }
 
+    /// Creates a not-found route, which receives requests with any method to any path at or
+    /// beneath the current location which no other route matches, instead of the `Router`
+    /// responding with `404 Not Found`. This is a fallback route (see `fallback`) which is not
+    /// restricted by method.
+    ///
+    /// The request is dispatched through the pipelines of this builder, as for any other route,
+    /// and the handler may respond with any status (for example, redirecting a request for moved
+    /// content). A request which matches the path of another route, but not its method, still
+    /// receives `405 Method Not Allowed`.
+    ///
+    /// The not-found route of the innermost scope containing the request path is used. A
+    /// `Router` delegated to by `delegate` uses its own not-found routes first, and when it has
+    /// none for the request path, the not-found route of the nearest enclosing scope is used
+    /// instead. A request for which no not-found route is registered receives `404 Not Found`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::header::LOCATION;
+    /// # use hyper::{Body, Response, StatusCode, Uri};
+    /// # use gotham::helpers::http::response::{create_response, create_text_response};
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn not_found(state: State) -> (State, Response<Body>) {
+    ///     let response = if Uri::borrow_from(&state).path() == "/blog" {
+    ///         let mut response = create_response(&state, StatusCode::MOVED_PERMANENTLY, None);
+    ///         response
+    ///             .headers_mut()
+    ///             .insert(LOCATION, "/articles".parse().unwrap());
+    ///         response
+    ///     } else {
+    ///         create_text_response(&state, StatusCode::NOT_FOUND, "Nothing here")
+    ///     };
+    ///
+    ///     (state, response)
+    /// }
+    ///
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route.not_found().to(not_found);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/blog")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    /// #   assert_eq!(response.headers().get(LOCATION).unwrap(), "/articles");
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .post("https://example.com/missing", "", mime::TEXT_PLAIN)
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "Nothing here");
+    /// # }
+    /// ```
+    fn not_found<'b>(&'b mut self) -> ExplicitSingleRouteBuilder<'b, AnyRouteMatcher, C, P> {
+        SingleRouteBuilder {
+            not_found: true,
+            ..self.fallback(AnyRouteMatcher::new())
+        }
+    }
+
     /// Begins defining a new scope, based on a given `path` prefix.
     ///
+    /// Routes defined within the scope are added to the tree as though the prefix were written at
+    /// the start of their own paths, so scopes can be nested, and the dynamic segments of the
+    /// prefix are provided to the `PathExtractor` of each route along with its own. Segment
+    /// constraints (e.g. `with_segment_regex`) only apply to the segments of the route path, and
+    /// not to those of the prefix.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -520,13 +736,9 @@ where
         let (node_builder, pipeline_chain, pipelines) = self.component_refs();
         let node_builder = descend(node_builder, path);
 
-        let mut scope_builder = ScopeBuilder {
-            node_builder,
-            pipeline_chain: *pipeline_chain,
-            pipelines: pipelines.clone(),
-        };
-
-        f(&mut scope_builder)
+        let mut scope_builder = ScopeBuilder::new(node_builder, *pipeline_chain, pipelines.clone());
+        f(&mut scope_builder);
+        scope_builder.finish();
     }
 
     /// Begins a new scope at the current location, with an alternate pipeline chain.
@@ -637,17 +849,24 @@ where
     {
         let (node_builder, _pipeline_chain, pipelines) = self.component_refs();
 
-        let mut scope_builder = ScopeBuilder {
-            node_builder,
-            pipeline_chain,
-            pipelines: pipelines.clone(),
-        };
-
-        f(&mut scope_builder)
+        let mut scope_builder = ScopeBuilder::new(node_builder, pipeline_chain, pipelines.clone());
+        f(&mut scope_builder);
+        scope_builder.finish();
     }
 
     /// Begins delegating a subpath of the tree.
     ///
+    /// The delegated `Router` matches only the remainder of the request path after `path`, so
+    /// its routes and `PathExtractor` values are relative to the prefix (a request for
+    /// `/admin/users/42` is matched by an `/users/:id` route in the delegated `Router`). The
+    /// `Uri` in `State` is not modified. Delegated routers may themselves delegate.
+    ///
+    /// Requests are dispatched through the pipelines of this builder, and then those of the
+    /// delegated `Router`. When the delegated `Router` has no matching route, its response (such
+    /// as `404 Not Found`) is finalized by its own response extenders and then by those of this
+    /// `Router`, so an extender for the whole application also applies to delegated paths unless
+    /// the delegated `Router` has already provided a body.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -851,6 +1070,9 @@ where
     {
         let (node_builder, pipeline_chain, pipelines) = self.component_refs();
         let node_builder = descend(node_builder, path);
+        if has_trailing_slash(path) {
+            node_builder.set_trailing_slash();
+        }
 
         let mut builder =
             AssociatedRouteBuilder::new(node_builder, *pipeline_chain, pipelines.clone());
@@ -864,52 +1086,206 @@ where
 }
 
 fn descend<'n>(node_builder: &'n mut Node, path: &str) -> &'n mut Node {
+    if let Some((segment, optional)) = route_segments(path)
+        .split("/")
+        .enumerate()
+        .find(|&(_, s)| is_optional(s))
+    {
+        report(RouterBuildError::InvalidPattern {
+            path: path.to_owned(),
+            segment,
+            message: format!(
+                "optional segment `{}` in path `{}` is only supported in the path of a route",
+                optional, path
+            ),
+        });
+    }
+
+    descend_with_constraints(node_builder, path, &[])
+}
+
+/// Walks to the node for `path` beneath `node_builder`, creating nodes as required. Dynamic
+/// segments named in `constraints` are created with the given `SegmentType` instead.
+pub(super) fn descend_with_constraints<'n>(
+    node_builder: &'n mut Node,
+    path: &str,
+    constraints: &[(String, SegmentType)],
+) -> &'n mut Node {
     trace!("[walking to: {}]", path);
+    validate_path(path);
+
+    let segments = route_segments(path);
+    if segments.is_empty() {
+        node_builder
+    } else {
+        build_subtree(
+            node_builder,
+            path,
+            segments.split("/").enumerate(),
+            constraints,
+        )
+    }
+}
 
+/// Strips a leading and trailing slash from a route path, since a request path is matched
+/// regardless of its trailing slash.
+fn route_segments(path: &str) -> &str {
     let path = if path.starts_with("/") {
         &path[1..]
     } else {
         path
     };
 
-    if path.is_empty() {
-        node_builder
+    if path.ends_with("/") {
+        &path[..path.len() - 1]
     } else {
-        build_subtree(node_builder, path.split("/"))
+        path
+    }
+}
+
+/// Determines if a route path other than `/` ends with a slash, such as `/teams/`.
+pub(super) fn has_trailing_slash(path: &str) -> bool {
+    path.ends_with('/') && !route_segments(path).is_empty()
+}
+
+/// Reports a `RouterBuildError::InvalidPattern` for each segment which can't be drawn into the
+/// tree in its position.
+fn validate_path(given: &str) {
+    let path = route_segments(given);
+    let segments: Vec<&str> = path.split("/").collect();
+    let invalid = |segment: usize, message: String| {
+        report(RouterBuildError::InvalidPattern {
+            path: given.to_owned(),
+            segment,
+            message,
+        })
+    };
+
+    if let Some((i, glob)) = segments[..segments.len() - 1]
+        .iter()
+        .enumerate()
+        .find(|&(_, segment)| segment.starts_with('*'))
+    {
+        invalid(
+            i,
+            format!(
+                "glob segment `{}` in route path `/{}` must be the final segment",
+                glob, path
+            ),
+        );
+    }
+
+    // A segment can only be omitted from the end of a request path, so an optional segment can
+    // only be followed by other optional segments.
+    if let Some(first) = segments.iter().position(|segment| is_optional(segment)) {
+        if let Some((i, required)) = segments
+            .iter()
+            .enumerate()
+            .skip(first)
+            .find(|&(_, s)| !is_optional(s))
+        {
+            invalid(
+                i,
+                format!(
+                    "segment `{}` in route path `/{}` follows the optional segment `{}`, and \
+                     must also be optional",
+                    required, path, segments[first]
+                ),
+            );
+        }
     }
 }
 
-fn build_subtree<'n, 's, I>(node: &'n mut Node, mut i: I) -> &'n mut Node
+/// Determines if a segment of a route path may be omitted from the end of a request path, which
+/// is the case for a dynamic segment with a name ending in `?` (`:name?` or `:name?:regex`), and
+/// for an optional glob.
+fn is_optional(segment: &str) -> bool {
+    segment.starts_with("**")
+        || (segment.starts_with(':') && segment[1..].split(':').next().unwrap().ends_with('?'))
+}
+
+fn build_subtree<'n, 's, I>(
+    node: &'n mut Node,
+    path: &str,
+    mut i: I,
+    constraints: &[(String, SegmentType)],
+) -> &'n mut Node
 where
-    I: Iterator<Item = &'s str>,
+    I: Iterator<Item = (usize, &'s str)>,
 {
     match i.next() {
-        Some(segment) => {
+        Some((position, segment)) => {
             trace!("[descending into {}]", segment);
 
+            let mut optional = false;
             let (segment, segment_type) = match segment.chars().next() {
                 Some(':') => {
                     let segment = &segment[1..];
-                    match segment.find(":") {
-                        Some(n) => {
-                            let (segment, pattern) = segment.split_at(n);
-                            let regex = ConstrainedSegmentRegex::new(&pattern[1..]);
-                            (segment, SegmentType::Constrained { regex })
+                    let (segment, pattern) = match segment.find(":") {
+                        Some(n) => (&segment[..n], Some(&segment[n + 1..])),
+                        None => (segment, None),
+                    };
+
+                    let segment = if segment.ends_with('?') {
+                        optional = true;
+                        &segment[..segment.len() - 1]
+                    } else {
+                        segment
+                    };
+
+                    match pattern {
+                        Some(pattern) => match ConstrainedSegmentRegex::try_new(pattern) {
+                            Ok(regex) => (segment, SegmentType::Constrained { regex }),
+                            Err(e) => {
+                                report(RouterBuildError::InvalidPattern {
+                                    path: path.to_owned(),
+                                    segment: position,
+                                    message: format!(
+                                        "invalid regex for segment `:{}` in route path `{}`: {}",
+                                        segment, path, e
+                                    ),
+                                });
+                                (segment, SegmentType::Dynamic)
+                            }
+                        },
+                        None => {
+                            let segment_type = constraints
+                                .iter()
+                                .find(|&&(ref name, _)| name == segment)
+                                .map(|&(_, ref segment_type)| segment_type.clone())
+                                .unwrap_or(SegmentType::Dynamic);
+
+                            (segment, segment_type)
                         }
-                        None => (segment, SegmentType::Dynamic),
                     }
                 }
-                Some('*') if segment.len() == 1 => (segment, SegmentType::Glob),
+                Some('*') => {
+                    let (name, segment_type) = if segment.starts_with("**") {
+                        (&segment[2..], SegmentType::OptionalGlob)
+                    } else {
+                        (&segment[1..], SegmentType::Glob)
+                    };
+
+                    match name {
+                        "" => ("*", segment_type),
+                        name => (name, segment_type),
+                    }
+                }
                 Some('\\') => (&segment[1..], SegmentType::Static),
                 _ => (segment, SegmentType::Static),
             };
 
-            if !node.has_child(segment, segment_type.clone()) {
-                node.add_child(Node::new(segment, segment_type.clone()));
-            }
+            let child = if optional {
+                node.optional_child_mut(segment, segment_type)
+            } else {
+                if !node.has_child(segment, segment_type.clone()) {
+                    node.add_child(Node::new(segment, segment_type.clone()));
+                }
+
+                node.borrow_child_mut(segment, segment_type).unwrap()
+            };
 
-            let child = node.borrow_child_mut(segment, segment_type).unwrap();
-            build_subtree(child, i)
+            build_subtree(child, path, i, constraints)
         }
         None => {
             trace!("[reached node]");
@@ -950,18 +1326,48 @@ where
 mod tests {
     use std::io;
 
-    use futures::future;
-    use hyper::{Body, Response, StatusCode};
+    use futures::{future, Future};
+    use hyper::header::{HeaderValue, WARNING};
+    use hyper::{Body, Method, Response, StatusCode, Uri};
+    use mime;
 
     use handler::HandlerFuture;
-    use helpers::http::response::create_response;
+    use helpers::http::request::path::RequestPathSegments;
+    use helpers::http::response::{create_response, create_text_response};
     use middleware::{Middleware, NewMiddleware};
     use pipeline::single::*;
     use pipeline::*;
     use router::builder::*;
-    use state::State;
+    use router::response::extender::StaticResponseExtender;
+    use router::route::matcher::AnyRouteMatcher;
+    use router::Router;
+    use state::{FromState, State, StateData};
     use test::TestServer;
 
+    #[derive(Deserialize)]
+    struct GlobParams {
+        path: Vec<String>,
+    }
+
+    impl StateData for GlobParams {}
+
+    impl StaticResponseExtender for GlobParams {
+        type ResBody = Body;
+        fn extend(_: &mut State, _: &mut Response<Body>) {}
+    }
+
+    #[derive(Deserialize)]
+    struct NameParams {
+        name: String,
+    }
+
+    impl StateData for NameParams {}
+
+    impl StaticResponseExtender for NameParams {
+        type ResBody = Body;
+        fn extend(_: &mut State, _: &mut Response<Body>) {}
+    }
+
     #[derive(Clone, Copy)]
     struct QuickExitMiddleware;
 
@@ -990,6 +1396,33 @@ mod tests {
         }
     }
 
+    #[derive(Clone, Copy)]
+    struct OuterMiddleware;
+
+    impl NewMiddleware for OuterMiddleware {
+        type Instance = Self;
+
+        fn new_middleware(&self) -> io::Result<Self> {
+            Ok(*self)
+        }
+    }
+
+    impl Middleware for OuterMiddleware {
+        fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+        where
+            Chain: FnOnce(State) -> Box<HandlerFuture> + 'static,
+        {
+            let f = chain(state).map(|(state, mut response)| {
+                response
+                    .headers_mut()
+                    .insert("x-outer", HeaderValue::from_static("true"));
+                (state, response)
+            });
+
+            Box::new(f)
+        }
+    }
+
     fn test_handler(state: State) -> (State, Response<Body>) {
         let response = create_response(&state, StatusCode::ACCEPTED, None);
         (state, response)
@@ -1039,4 +1472,611 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::ACCEPTED);
     }
+
+    fn glob_response(mut state: State, label: &str) -> (State, Response<Body>) {
+        let params = state.take::<GlobParams>();
+        let body = format!("{} {}", label, params.path.join("/"));
+        let response = create_text_response(&state, StatusCode::OK, body);
+        (state, response)
+    }
+
+    fn glob_handler(state: State) -> (State, Response<Body>) {
+        glob_response(state, "glob")
+    }
+
+    fn optional_glob_handler(state: State) -> (State, Response<Body>) {
+        glob_response(state, "optional")
+    }
+
+    fn static_handler(state: State) -> (State, Response<Body>) {
+        let response = create_text_response(&state, StatusCode::OK, "static");
+        (state, response)
+    }
+
+    fn dynamic_handler(state: State) -> (State, Response<Body>) {
+        let response = create_text_response(&state, StatusCode::OK, "dynamic");
+        (state, response)
+    }
+
+    fn glob_router() -> Router {
+        build_simple_router(|route| {
+            route.get("/static/index.html").to(static_handler);
+            route.get("/static/:file").to(dynamic_handler);
+            route
+                .get("/static/*path")
+                .with_path_extractor::<GlobParams>()
+                .to(glob_handler);
+            route
+                .get("/opt/**path")
+                .with_path_extractor::<GlobParams>()
+                .to(optional_glob_handler);
+        })
+    }
+
+    fn get(test_server: &TestServer, path: &str) -> (StatusCode, String) {
+        let response = test_server
+            .client()
+            .get(&format!("http://localhost{}", path))
+            .perform()
+            .unwrap();
+
+        (response.status(), response.read_utf8_body().unwrap())
+    }
+
+    #[test]
+    fn glob_segments_have_lowest_precedence() {
+        let test_server = TestServer::new(glob_router()).unwrap();
+
+        assert_eq!(
+            get(&test_server, "/static/index.html"),
+            (StatusCode::OK, "static".to_owned())
+        );
+        assert_eq!(
+            get(&test_server, "/static/site.css"),
+            (StatusCode::OK, "dynamic".to_owned())
+        );
+        assert_eq!(
+            get(&test_server, "/static/css/site.css"),
+            (StatusCode::OK, "glob css/site.css".to_owned())
+        );
+        assert_eq!(get(&test_server, "/static").0, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn optional_glob_segments_match_empty_remainder() {
+        let test_server = TestServer::new(glob_router()).unwrap();
+
+        assert_eq!(
+            get(&test_server, "/opt"),
+            (StatusCode::OK, "optional ".to_owned())
+        );
+        assert_eq!(
+            get(&test_server, "/opt/a/b"),
+            (StatusCode::OK, "optional a/b".to_owned())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "must be the final segment")]
+    fn glob_segments_must_be_final() {
+        build_simple_router(|route| {
+            route.get("/a/*path/b").to(test_handler);
+        });
+    }
+
+    fn regex_handler(state: State) -> (State, Response<Body>) {
+        let response = create_text_response(&state, StatusCode::OK, "regex");
+        (state, response)
+    }
+
+    fn typed_handler(state: State) -> (State, Response<Body>) {
+        let response = create_text_response(&state, StatusCode::OK, "typed");
+        (state, response)
+    }
+
+    #[test]
+    fn constrained_segments_fall_through() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/orders/:id")
+                .with_segment_regex("id", r"\d+")
+                .to(regex_handler);
+            route
+                .get("/orders/:id")
+                .with_segment_type::<i64>("id")
+                .to(typed_handler);
+            route.get("/orders/0").to(static_handler);
+            route
+                .get("/items/:id")
+                .with_segment_type::<u8>("id")
+                .to(typed_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+
+        let cases = vec![
+            ("/orders/0", Some("static")),
+            ("/orders/42", Some("regex")),
+            ("/orders/-1", Some("typed")),
+            ("/orders/pending", None),
+            ("/items/255", Some("typed")),
+            ("/items/256", None),
+        ];
+
+        for (path, expected) in cases {
+            let (status, body) = get(&test_server, path);
+            match expected {
+                Some(expected) => assert_eq!((status, body.as_str()), (StatusCode::OK, expected)),
+                None => assert_eq!(status, StatusCode::NOT_FOUND, "{}", path),
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "has no dynamic segment `:id`")]
+    fn constrained_segments_must_exist() {
+        build_simple_router(|route| {
+            route
+                .get("/orders/:order_id")
+                .with_segment_regex("id", r"\d+")
+                .to(test_handler);
+        });
+    }
+
+    #[test]
+    fn route_precedence() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/pages/*path")
+                .with_path_extractor::<GlobParams>()
+                .to(glob_handler);
+            route.get("/pages/:page").to(dynamic_handler);
+            route
+                .get("/pages/:page")
+                .with_segment_regex("page", r"\d+")
+                .to(regex_handler);
+            route.get("/pages/about").to(static_handler);
+
+            route.get("/files/:name").to(dynamic_handler);
+            route
+                .get("/files/*path")
+                .with_path_extractor::<GlobParams>()
+                .with_priority(1)
+                .to(glob_handler);
+            route.get("/files/index").to(static_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+
+        let cases = vec![
+            ("/pages/about", "static"),
+            ("/pages/1", "regex"),
+            ("/pages/contact", "dynamic"),
+            ("/pages/team/jo", "glob team/jo"),
+            ("/files/index", "glob index"),
+            ("/files/report", "glob report"),
+        ];
+
+        for (path, expected) in cases {
+            let (status, body) = get(&test_server, path);
+            assert_eq!((status, body.as_str()), (StatusCode::OK, expected));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "`GET /users/:user_id` is already defined by `GET /users/:id`")]
+    fn duplicate_routes_are_rejected() {
+        build_simple_router(|route| {
+            route.get("/users/:id").to(test_handler);
+            route.get("/users/:user_id").to(test_handler);
+        });
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "duplicate route: `PUT /api/users/:user_id:[0-9]+` is already \
+                               defined by `PATCH, PUT /api/users/:id:[0-9]+`"
+    )]
+    fn duplicate_routes_in_scopes_are_rejected() {
+        build_simple_router(|route| {
+            route.scope("/api", |route| {
+                route
+                    .request(vec![Method::PUT, Method::PATCH], "/users/:id:[0-9]+")
+                    .to(test_handler);
+            });
+
+            route
+                .put("/api/users/:user_id")
+                .with_segment_regex("user_id", "[0-9]+")
+                .with_path_extractor::<NameParams>()
+                .to(test_handler);
+        });
+    }
+
+    #[test]
+    fn overlapping_routes_are_accepted() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/report")
+                .with_accept(vec![mime::TEXT_HTML])
+                .to(static_handler);
+            route
+                .get("/report")
+                .with_accept(vec![mime::APPLICATION_JSON])
+                .to(dynamic_handler);
+
+            route.get("/files/index").to(static_handler);
+            route
+                .get("/files/*path")
+                .with_path_extractor::<GlobParams>()
+                .to(glob_handler);
+
+            route.get("/pages/:page").to(dynamic_handler);
+            route
+                .get("/pages/:name")
+                .with_priority(1)
+                .to(static_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        assert_eq!(
+            get(&test_server, "/files/index"),
+            (StatusCode::OK, "static".to_owned())
+        );
+        assert_eq!(
+            get(&test_server, "/files/index.html"),
+            (StatusCode::OK, "glob index.html".to_owned())
+        );
+        assert_eq!(
+            get(&test_server, "/pages/about"),
+            (StatusCode::OK, "static".to_owned())
+        );
+    }
+
+    fn fallback_handler(state: State) -> (State, Response<Body>) {
+        let body = format!("fallback {}", Uri::borrow_from(&state).path());
+        let response = create_text_response(&state, StatusCode::OK, body);
+        (state, response)
+    }
+
+    fn api_fallback_handler(state: State) -> (State, Response<Body>) {
+        let response = create_text_response(&state, StatusCode::OK, "api fallback");
+        (state, response)
+    }
+
+    #[test]
+    fn fallback_routes() {
+        let admin_router = build_simple_router(|route| {
+            route.get("/").to(static_handler);
+        });
+
+        let router = build_simple_router(|route| {
+            route.get("/assets/app.js").to(static_handler);
+            route.post("/sessions").to(dynamic_handler);
+
+            route.scope("/api", |route| {
+                route.get("/users").to(dynamic_handler);
+                route
+                    .fallback(AnyRouteMatcher::new())
+                    .to(api_fallback_handler);
+            });
+
+            route.delegate("/admin").to_router(admin_router);
+            route.fallback(vec![Method::GET]).to(fallback_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let call = |method: Method, path: &str| {
+            let response = test_server
+                .client()
+                .build_request(method, &format!("http://localhost{}", path))
+                .perform()
+                .unwrap();
+            (response.status(), response.read_utf8_body().unwrap())
+        };
+
+        let ok = |body: &str| (StatusCode::OK, body.to_owned());
+        let not_found = (StatusCode::NOT_FOUND, String::new());
+
+        assert_eq!(call(Method::GET, "/assets/app.js"), ok("static"));
+        assert_eq!(
+            call(Method::GET, "/assets/app.css"),
+            ok("fallback /assets/app.css")
+        );
+        assert_eq!(call(Method::GET, "/"), ok("fallback /"));
+        assert_eq!(
+            call(Method::GET, "/account/settings"),
+            ok("fallback /account/settings")
+        );
+        assert_eq!(call(Method::POST, "/account/settings"), not_found);
+        assert_eq!(
+            call(Method::DELETE, "/assets/app.js").0,
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            call(Method::GET, "/sessions").0,
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+
+        assert_eq!(call(Method::GET, "/api/users"), ok("dynamic"));
+        assert_eq!(call(Method::GET, "/api/groups"), ok("api fallback"));
+        assert_eq!(call(Method::POST, "/api/groups/1"), ok("api fallback"));
+
+        assert_eq!(call(Method::GET, "/admin"), ok("static"));
+        assert_eq!(call(Method::GET, "/admin/missing"), not_found);
+    }
+
+    fn not_found_handler(state: State) -> (State, Response<Body>) {
+        let response = if Uri::borrow_from(&state).path() == "/old" {
+            create_text_response(&state, StatusCode::MOVED_PERMANENTLY, "moved")
+        } else {
+            create_text_response(&state, StatusCode::NOT_FOUND, "not found")
+        };
+        (state, response)
+    }
+
+    fn api_not_found_handler(state: State) -> (State, Response<Body>) {
+        let response = create_text_response(&state, StatusCode::NOT_FOUND, "api not found");
+        (state, response)
+    }
+
+    #[test]
+    fn not_found_routes() {
+        let docs_router = build_simple_router(|route| {
+            route.get("/").to(static_handler);
+        });
+
+        let admin_router = build_simple_router(|route| {
+            route.get("/").to(static_handler);
+            route.not_found().to(api_not_found_handler);
+        });
+
+        let (chain, pipelines) = single_pipeline(new_pipeline().add(OuterMiddleware).build());
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/users").to(dynamic_handler);
+
+            route.scope("/api", |route| {
+                route.not_found().to(api_not_found_handler);
+            });
+
+            route.delegate("/docs").to_router(docs_router);
+            route.delegate("/admin").to_router(admin_router);
+            route.not_found().to(not_found_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let call = |method: Method, path: &str| {
+            test_server
+                .client()
+                .build_request(method, &format!("http://localhost{}", path))
+                .perform()
+                .unwrap()
+        };
+
+        let cases = vec![
+            (Method::GET, "/old", 301, "moved"),
+            (Method::GET, "/missing", 404, "not found"),
+            (Method::DELETE, "/missing", 404, "not found"),
+            (Method::POST, "/api/users", 404, "api not found"),
+            (Method::GET, "/admin/missing", 404, "api not found"),
+            (Method::GET, "/docs/missing", 404, "not found"),
+        ];
+
+        for (method, path, status, body) in cases {
+            let response = call(method, path);
+            assert_eq!(response.status().as_u16(), status);
+            assert_eq!(response.headers().get("x-outer").unwrap(), "true");
+            assert_eq!(response.read_utf8_body().unwrap(), body);
+        }
+
+        let response = call(Method::POST, "/users");
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[derive(Deserialize)]
+    struct MemberParams {
+        org: String,
+        id: u64,
+    }
+
+    impl StateData for MemberParams {}
+
+    impl StaticResponseExtender for MemberParams {
+        type ResBody = Body;
+        fn extend(_: &mut State, _: &mut Response<Body>) {}
+    }
+
+    fn member_handler(mut state: State) -> (State, Response<Body>) {
+        let params = state.take::<MemberParams>();
+        let body = format!("{} {}", params.org, params.id);
+        let response = create_text_response(&state, StatusCode::OK, body);
+        (state, response)
+    }
+
+    #[test]
+    fn nested_scopes_share_path_prefix() {
+        let scoped = build_simple_router(|route| {
+            route.scope("/api", |route| {
+                route.scope("/v1/:org", |route| {
+                    route.get("/").to(static_handler);
+                    route
+                        .get("/members/:id")
+                        .with_path_extractor::<MemberParams>()
+                        .to(member_handler);
+                });
+            });
+        });
+
+        let flat = build_simple_router(|route| {
+            route.get("/api/v1/:org").to(static_handler);
+            route
+                .get("/api/v1/:org/members/:id")
+                .with_path_extractor::<MemberParams>()
+                .to(member_handler);
+        });
+
+        for router in &[&scoped, &flat] {
+            let segments = RequestPathSegments::new("/api/v1/acme/members/42");
+            let (node, params, processed) = router.data.tree.traverse(segments.segments()).unwrap();
+            assert!(node.is_routable());
+            assert_eq!(node.segment(), "id");
+            assert_eq!(processed, 5);
+            assert_eq!(params["org"][0].as_ref(), "acme");
+            assert_eq!(params["id"][0].as_ref(), "42");
+
+            let segments = RequestPathSegments::new("/api/v1");
+            assert!(router.data.tree.traverse(segments.segments()).is_none());
+        }
+
+        let test_server = TestServer::new(scoped).unwrap();
+        assert_eq!(
+            get(&test_server, "/api/v1/acme/members/42"),
+            (StatusCode::OK, "acme 42".to_owned())
+        );
+        assert_eq!(
+            get(&test_server, "/api/v1/acme"),
+            (StatusCode::OK, "static".to_owned())
+        );
+        assert_eq!(get(&test_server, "/members/42").0, StatusCode::NOT_FOUND);
+        assert_eq!(
+            get(&test_server, "/api/members/42").0,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    fn name_handler(mut state: State) -> (State, Response<Body>) {
+        let params = state.take::<NameParams>();
+        let response = create_text_response(&state, StatusCode::OK, params.name);
+        (state, response)
+    }
+
+    #[test]
+    fn delegated_routers_match_relative_paths() {
+        let reports_router = build_simple_router(|route| {
+            route.get("/").to(static_handler);
+        });
+
+        let admin_router = build_simple_router(|route| {
+            route
+                .get("/users/:name")
+                .with_path_extractor::<NameParams>()
+                .to(name_handler);
+            route.delegate("/reports").to_router(reports_router);
+        });
+
+        let (chain, pipelines) = single_pipeline(new_pipeline().add(OuterMiddleware).build());
+        let router = build_router(chain, pipelines, |route| {
+            route.add_response_extender(
+                StatusCode::NOT_FOUND,
+                |_state: &mut State, res: &mut Response<Body>| {
+                    res.headers_mut()
+                        .insert(WARNING, HeaderValue::from_static("199 - \"not found\""));
+                },
+            );
+
+            route.delegate("/admin").to_router(admin_router);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let call = |path: &str| {
+            test_server
+                .client()
+                .get(&format!("http://localhost{}", path))
+                .perform()
+                .unwrap()
+        };
+
+        let response = call("/admin/users/ann");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("x-outer").unwrap(), "true");
+        assert_eq!(response.read_utf8_body().unwrap(), "ann");
+
+        let response = call("/admin/reports");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "static");
+
+        let response = call("/admin/missing");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers().get("x-outer").unwrap(), "true");
+        assert!(response.headers().get(WARNING).is_some());
+
+        let response = call("/admin/reports/missing");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(response.headers().get(WARNING).is_some());
+
+        let response = call("/users/ann");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(response.headers().get("x-outer").is_none());
+        assert!(response.headers().get(WARNING).is_some());
+    }
+
+    #[derive(Deserialize)]
+    struct ArchiveParams {
+        year: u32,
+        month: Option<u32>,
+        #[serde(default)]
+        day: u32,
+    }
+
+    impl StateData for ArchiveParams {}
+
+    impl StaticResponseExtender for ArchiveParams {
+        type ResBody = Body;
+        fn extend(_: &mut State, _: &mut Response<Body>) {}
+    }
+
+    fn archive_handler(mut state: State) -> (State, Response<Body>) {
+        let params = state.take::<ArchiveParams>();
+        let body = format!("{} {:?} {}", params.year, params.month, params.day);
+        let response = create_text_response(&state, StatusCode::OK, body);
+        (state, response)
+    }
+
+    #[test]
+    fn optional_segments() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/archive/:year/:month?/:day?")
+                .with_segment_type::<u32>("month")
+                .with_path_extractor::<ArchiveParams>()
+                .to(archive_handler);
+
+            route.get("/news/:year").to(static_handler);
+            route.get("/news/:year/:month?").to(dynamic_handler);
+            route.get("/news/:year/:month/comments").to(test_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+
+        let cases = [
+            ("/archive/2018", 200, "2018 None 0"),
+            ("/archive/2018/6", 200, "2018 Some(6) 0"),
+            ("/archive/2018/6/21/", 200, "2018 Some(6) 21"),
+            ("/archive/2018/june", 404, ""),
+            ("/archive/2018/6/21/1", 404, ""),
+            ("/archive", 404, ""),
+            ("/news/2018", 200, "static"),
+            ("/news/2018/6", 200, "dynamic"),
+            ("/news/2018/6/comments", 202, ""),
+        ];
+
+        for &(path, status, body) in cases.iter() {
+            let (actual_status, actual_body) = get(&test_server, path);
+            assert_eq!(actual_status.as_u16(), status, "status of {}", path);
+            if status != 404 {
+                assert_eq!(actual_body, body, "body of {}", path);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "segment `:month` in route path `/archive/:year?/:month` follows \
+                               the optional segment `:year?`, and must also be optional"
+    )]
+    fn required_segments_after_optional_segments_are_rejected() {
+        build_simple_router(|route| {
+            route.get("/archive/:year?/:month").to(static_handler);
+        });
+    }
 }
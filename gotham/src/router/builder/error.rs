@@ -0,0 +1,134 @@
+//! Defines `RouterBuildError`, which describes a problem with the routes given to the builder.
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+
+/// A problem with the routes defined while building a `Router`. `build_router_result` returns the
+/// first problem found, and `build_router` panics with its `Display` form.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RouterBuildError {
+    /// A route path which can't be drawn into the tree, such as a glob which isn't the final
+    /// segment, or a regex constraint which doesn't compile.
+    InvalidPattern {
+        /// The path, as given to the builder.
+        path: String,
+        /// The position of the invalid segment, counting from zero after the leading `/`.
+        segment: usize,
+        /// Describes the problem.
+        message: String,
+    },
+
+    /// A route which could never be matched, because an earlier route matches the same paths and
+    /// all of its methods. Routes are described by their methods and path, e.g. `GET /users/:id`.
+    Conflict {
+        /// The route which could never be matched.
+        route: String,
+        /// The earlier route which matches its requests.
+        existing: String,
+    },
+
+    /// A builder method which was given a configuration that doesn't fit the route, such as a
+    /// constraint for a dynamic segment which isn't in the route path.
+    InvalidMatcher {
+        /// The path of the route being defined.
+        path: String,
+        /// Describes the problem.
+        message: String,
+    },
+
+    /// A route described by a `RouteSpec` whose handler isn't in the `HandlerRegistry` given to
+    /// `build_router_from_spec`.
+    UnknownHandler {
+        /// The route, described by its methods and path, e.g. `GET /users/:id`.
+        route: String,
+        /// The name of the handler.
+        handler: String,
+    },
+}
+
+impl Display for RouterBuildError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            RouterBuildError::InvalidPattern { ref message, .. }
+            | RouterBuildError::InvalidMatcher { ref message, .. } => f.write_str(message),
+            RouterBuildError::Conflict {
+                ref route,
+                ref existing,
+            } => write!(
+                f,
+                "duplicate route: `{}` is already defined by `{}`",
+                route, existing
+            ),
+            RouterBuildError::UnknownHandler {
+                ref route,
+                ref handler,
+            } => write!(
+                f,
+                "unknown handler: `{}` is directed to `{}`, which isn't registered",
+                route, handler
+            ),
+        }
+    }
+}
+
+impl Error for RouterBuildError {
+    fn description(&self) -> &str {
+        match *self {
+            RouterBuildError::InvalidPattern { .. } => "invalid route path",
+            RouterBuildError::Conflict { .. } => "conflicting route",
+            RouterBuildError::InvalidMatcher { .. } => "invalid route configuration",
+            RouterBuildError::UnknownHandler { .. } => "unknown handler",
+        }
+    }
+}
+
+thread_local! {
+    /// The errors reported while the closure given to `build_router_result` runs on this thread.
+    static ERRORS: RefCell<Option<Vec<RouterBuildError>>> = RefCell::new(None);
+}
+
+/// Reports a problem with the routes being built, to be returned by `build_router_result` once
+/// the routes are complete. Outside of `collect_errors` this panics immediately.
+pub(super) fn report(error: RouterBuildError) {
+    let unreported = ERRORS.with(|errors| match *errors.borrow_mut() {
+        Some(ref mut errors) => {
+            errors.push(error);
+            None
+        }
+        None => Some(error),
+    });
+
+    if let Some(error) = unreported {
+        panic!("{}", error);
+    }
+}
+
+/// Runs `f`, collecting the errors reported while it runs. A `Router` built within `f` (e.g. to
+/// be delegated to) collects its own errors, which aren't reported to the outer `Router`.
+pub(super) fn collect_errors<F, T>(f: F) -> (T, Vec<RouterBuildError>)
+where
+    F: FnOnce() -> T,
+{
+    let previous = ERRORS.with(|errors| mem::replace(&mut *errors.borrow_mut(), Some(Vec::new())));
+    let restore = Restore { previous };
+
+    let value = f();
+    let errors = ERRORS.with(|errors| errors.borrow_mut().take());
+
+    drop(restore);
+    (value, errors.unwrap_or_default())
+}
+
+/// Restores the errors of an enclosing `collect_errors` call, including when `f` panics.
+struct Restore {
+    previous: Option<Vec<RouterBuildError>>,
+}
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        ERRORS.with(|errors| *errors.borrow_mut() = previous);
+    }
+}
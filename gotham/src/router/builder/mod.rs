@@ -2,34 +2,61 @@
 
 mod associated;
 mod draw;
+mod error;
 mod modify;
 mod single;
+mod spec;
 
 use std::marker::PhantomData;
 use std::panic::RefUnwindSafe;
+use std::time::Duration;
 
 use hyper::{Body, StatusCode};
 
-use extractor::{NoopPathExtractor, NoopQueryStringExtractor, PathExtractor, QueryStringExtractor};
+use extractor::{
+    NoopPathExtractor, NoopQueryStringExtractor, PathExtractor, QueryStringExtractor,
+    QueryStringOptions,
+};
+use handler::timeout::TimeoutNewHandler;
+use handler::{Handler, NewHandler};
+use helpers::http::request::path::{
+    EncodedSlashes, PathCase, PathLimits, PathOptions, StaticSegments, TrailingSlash,
+};
 use pipeline::chain::PipelineHandleChain;
 use pipeline::set::{finalize_pipeline_set, new_pipeline_set, PipelineSet};
+use router::response::error::HandlerErrorMapper;
 use router::response::extender::ResponseExtender;
-use router::response::finalizer::ResponseFinalizerBuilder;
+use router::response::extraction::ExtractionFailureMapper;
+use router::response::finalizer::{ResponseFinalizerBuilder, StatusMatcher};
 use router::route::dispatch::DispatcherImpl;
+use router::route::extensions::RouteExtensions;
 use router::route::matcher::{AnyRouteMatcher, RouteMatcher};
 use router::route::{Delegation, Extractors, RouteImpl};
+use router::table::RouteRecord;
 use router::tree::node::Node;
+use router::tree::segment::SegmentType;
 use router::tree::Tree;
 use router::Router;
 
+use self::error::{collect_errors, report};
+
 pub use self::associated::{AssociatedRouteBuilder, AssociatedSingleRouteBuilder};
 pub use self::draw::DrawRoutes;
+pub use self::error::RouterBuildError;
 pub use self::modify::{ExtendRouteMatcher, ReplacePathExtractor, ReplaceQueryStringExtractor};
 pub use self::single::DefineSingleRoute;
+pub use self::spec::{
+    build_router_from_spec, HandlerRegistry, RedirectSpec, RouteSpec, RouteSpecExtensions,
+    RouterSpec,
+};
 
 /// Builds a `Router` using the provided closure. Routes are defined using the `RouterBuilder`
 /// value passed to the closure, and the `Router` is constructed before returning.
 ///
+/// Panics with a description of the problem when the routes are invalid, e.g. when a route path
+/// can't be parsed or a route conflicts with an earlier one. `build_router_result` returns the
+/// problem as a `RouterBuildError` instead.
+///
 /// ```rust
 /// # extern crate gotham;
 /// # extern crate hyper;
@@ -75,6 +102,67 @@ pub use self::single::DefineSingleRoute;
 /// # }
 /// ```
 pub fn build_router<C, P, F>(pipeline_chain: C, pipelines: PipelineSet<P>, f: F) -> Router
+where
+    C: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
+    P: Send + Sync + 'static,
+    F: FnOnce(&mut RouterBuilder<C, P>),
+{
+    match build_router_result(pipeline_chain, pipelines, f) {
+        Ok(router) => router,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Builds a `Router` using the provided closure, as `build_router` does, but returns an error
+/// rather than panicking when the routes are invalid. This suits applications which build part
+/// of their routes from configuration or a database at startup.
+///
+/// When several problems are found, the error describes the first of them: problems with route
+/// paths and builder configuration in the order the routes were defined, followed by
+/// conflicting routes.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::state::State;
+/// # use gotham::router::Router;
+/// # use gotham::router::builder::*;
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::*;
+/// #
+/// # fn my_handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+/// # }
+/// #
+/// fn router(paths: &[&str]) -> Result<Router, RouterBuildError> {
+///     let (chain, pipelines) = single_pipeline(new_pipeline().build());
+///
+///     build_router_result(chain, pipelines, |route| {
+///         for path in paths {
+///             route.get(path).to(my_handler);
+///         }
+///     })
+/// }
+/// #
+/// # fn main() {
+/// assert!(router(&["/users/:id", "/files/*"]).is_ok());
+///
+/// match router(&["/users/:id", "/files/*/raw"]) {
+///     Err(RouterBuildError::InvalidPattern { path, segment, .. }) => {
+///         assert_eq!(path, "/files/*/raw");
+///         assert_eq!(segment, 1);
+///     }
+///     _ => panic!("expected an invalid pattern"),
+/// }
+/// # }
+/// ```
+pub fn build_router_result<C, P, F>(
+    pipeline_chain: C,
+    pipelines: PipelineSet<P>,
+    f: F,
+) -> Result<Router, RouterBuildError>
 where
     C: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
     P: Send + Sync + 'static,
@@ -82,20 +170,58 @@ where
 {
     let mut tree = Tree::new();
 
-    let response_finalizer = {
+    let (
+        (
+            response_finalizer,
+            error_mapper,
+            extraction_failure_mapper,
+            path_options,
+            automatic_head,
+            automatic_options,
+        ),
+        mut errors,
+    ) = collect_errors(|| {
         let mut builder = RouterBuilder {
             node_builder: tree.borrow_root_mut(),
             pipeline_chain,
             pipelines,
             response_finalizer_builder: ResponseFinalizerBuilder::internal_new(),
+            error_mapper: None,
+            extraction_failure_mapper: None,
+            path_options: PathOptions::default(),
+            automatic_head: true,
+            automatic_options: false,
         };
 
         f(&mut builder);
 
-        builder.response_finalizer_builder.finalize()
-    };
+        (
+            builder.response_finalizer_builder.finalize(),
+            builder.error_mapper,
+            builder.extraction_failure_mapper,
+            builder.path_options,
+            builder.automatic_head,
+            builder.automatic_options,
+        )
+    });
+
+    tree.sort_by_priority();
+    tree.compute_depths();
+    errors.extend(tree.check_conflicts());
+
+    if let Some(error) = errors.into_iter().next() {
+        return Err(error);
+    }
 
-    Router::internal_new(tree, response_finalizer)
+    Ok(Router::internal_new(
+        tree,
+        response_finalizer,
+        error_mapper,
+        extraction_failure_mapper,
+        path_options,
+        automatic_head,
+        automatic_options,
+    ))
 }
 
 /// Builds a `Router` with **no** middleware using the provided closure. Routes are defined using
@@ -152,6 +278,11 @@ where
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
     response_finalizer_builder: ResponseFinalizerBuilder,
+    error_mapper: Option<Box<HandlerErrorMapper + Send + Sync>>,
+    extraction_failure_mapper: Option<Box<ExtractionFailureMapper + Send + Sync>>,
+    path_options: PathOptions,
+    automatic_head: bool,
+    automatic_options: bool,
 }
 
 impl<'a, C, P> RouterBuilder<'a, C, P>
@@ -161,6 +292,12 @@ where
 {
     /// Adds a `ResponseExtender` to the `ResponseFinalizer` in the `Router`.
     ///
+    /// The extender is invoked for responses which have no body, and a status matched by
+    /// `statuses`. This is usually a single `StatusCode`, but may be a `StatusClass` (e.g. every
+    /// `5xx` status) or any other `StatusMatcher`. When several extenders match, the most specific
+    /// is used, as described by `StatusMatcher`. Extenders added to a scope or route are used in
+    /// preference to these, as described by `ScopeBuilder::add_response_extender`.
+    ///
     /// ```rust
     /// # extern crate gotham;
     /// # extern crate hyper;
@@ -209,12 +346,425 @@ where
     /// #   }
     /// # }
     /// ```
-    pub fn add_response_extender<E>(&mut self, status_code: StatusCode, extender: E)
+    pub fn add_response_extender<S, E>(&mut self, statuses: S, extender: E)
     where
+        S: Into<StatusMatcher>,
         E: ResponseExtender<Body> + Send + Sync + 'static,
     {
         self.response_finalizer_builder
-            .add(status_code, Box::new(extender))
+            .add_matching(statuses.into(), Box::new(extender))
+    }
+
+    /// Sets the `HandlerErrorMapper` used by the `Router` to create a `Response` from any
+    /// `HandlerError` raised by a `Handler` or `Middleware`. Subsequent calls replace the
+    /// previously set mapper.
+    ///
+    /// Delegated routers apply their own mapper (if any) before the response is returned to this
+    /// `Router`.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// # extern crate futures;
+    /// #
+    /// # use std::io;
+    /// # use futures::future;
+    /// # use hyper::StatusCode;
+    /// # use gotham::handler::{HandlerError, HandlerFuture, IntoHandlerError};
+    /// # use gotham::helpers::http::response::create_response;
+    /// # use gotham::state::{request_id, State};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn my_handler(state: State) -> Box<HandlerFuture> {
+    ///     let err = io::Error::new(io::ErrorKind::Other, "failed")
+    ///         .into_handler_error()
+    ///         .with_status(StatusCode::SERVICE_UNAVAILABLE);
+    ///     Box::new(future::err((state, err)))
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.set_error_mapper(|state: &State, err: &HandlerError| {
+    ///             let body = format!(
+    ///                 r#"{{"status":{},"request_id":"{}"}}"#,
+    ///                 err.status().as_u16(),
+    ///                 request_id(state)
+    ///             );
+    ///             Some(create_response(
+    ///                 state,
+    ///                 err.status(),
+    ///                 Some((body.into_bytes(), mime::APPLICATION_JSON)),
+    ///             ))
+    ///         });
+    ///
+    ///         route.get("/").to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    /// #   let body = response.read_utf8_body().unwrap();
+    /// #   assert!(body.starts_with(r#"{"status":503,"request_id":""#));
+    /// # }
+    /// ```
+    pub fn set_error_mapper<M>(&mut self, mapper: M)
+    where
+        M: HandlerErrorMapper + Send + Sync + 'static,
+    {
+        self.error_mapper = Some(Box::new(mapper));
+    }
+
+    /// Sets the `ExtractionFailureMapper` which creates the `Response` when the `PathExtractor` or
+    /// `QueryStringExtractor` of a route fails, in place of the `StaticResponseExtender` of the
+    /// extractor. The mapper receives an `ExtractionFailure`, identifying the extractor and the
+    /// field which couldn't be extracted.
+    ///
+    /// The `json_extraction_failure` function is a mapper which describes the failure with a JSON
+    /// body.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// # extern crate hyper;
+    /// # extern crate serde;
+    /// # #[macro_use]
+    /// # extern crate serde_derive;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::response::create_text_response;
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::router::response::extraction::json_extraction_failure;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// #[derive(Deserialize, StateData, StaticResponseExtender)]
+    /// struct UserPath {
+    ///     id: u32,
+    /// }
+    ///
+    /// fn handler(mut state: State) -> (State, Response<Body>) {
+    ///     let UserPath { id } = UserPath::take_from(&mut state);
+    ///     let res = create_text_response(&state, StatusCode::OK, format!("user {}", id));
+    ///     (state, res)
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.set_extraction_failure_mapper(json_extraction_failure);
+    ///
+    ///         route
+    ///             .get("/users/:id")
+    ///             .with_path_extractor::<UserPath>()
+    ///             .to(handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/users/abc")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    /// #   let body = response.read_utf8_body().unwrap();
+    /// #   assert!(body.contains(r#""field":"id""#));
+    /// # }
+    /// ```
+    pub fn set_extraction_failure_mapper<M>(&mut self, mapper: M)
+    where
+        M: ExtractionFailureMapper + Send + Sync + 'static,
+    {
+        self.extraction_failure_mapper = Some(Box::new(mapper));
+    }
+
+    /// Sets how an encoded slash (`%2F`) in a segment of the request path is treated when
+    /// matching routes and extracting the path. By default, such a request receives a `400 Bad
+    /// Request` response. See `EncodedSlashes` for the available policies.
+    ///
+    /// Identifiers which legitimately contain a slash, such as a package name of `@scope/name`,
+    /// can be extracted into a single dynamic segment with `EncodedSlashes::Preserve`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// # extern crate hyper;
+    /// # extern crate serde;
+    /// # #[macro_use]
+    /// # extern crate serde_derive;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::request::path::EncodedSlashes;
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// #[derive(Deserialize, StateData, StaticResponseExtender)]
+    /// struct FileParams {
+    ///     name: String,
+    /// }
+    ///
+    /// fn my_handler(state: State) -> (State, Response<Body>) {
+    ///     let name = &FileParams::borrow_from(&state).name;
+    ///
+    ///     // Handler implementation elided.
+    /// #   assert_eq!(name, "a/b");
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.set_encoded_slashes(EncodedSlashes::Preserve);
+    ///
+    ///         route
+    ///             .get("/files/:name")
+    ///             .with_path_extractor::<FileParams>()
+    ///             .to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/files/a%2Fb")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// # }
+    /// ```
+    pub fn set_encoded_slashes(&mut self, encoded_slashes: EncodedSlashes) {
+        self.path_options.encoded_slashes = encoded_slashes;
+    }
+
+    /// Sets whether a request path may contain an encoded slash (`%2F`), which is then retained
+    /// within its decoded segment. This is equivalent to `set_encoded_slashes` with
+    /// `EncodedSlashes::Preserve` when `allow` is true, and `EncodedSlashes::Reject` otherwise.
+    pub fn set_allow_encoded_slashes(&mut self, allow: bool) {
+        self.set_encoded_slashes(if allow {
+            EncodedSlashes::Preserve
+        } else {
+            EncodedSlashes::Reject
+        });
+    }
+
+    /// Sets whether the static segments of routes are matched against the request path as it was
+    /// received, or after percent-decoding. The default is `StaticSegments::Encoded`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::request::path::StaticSegments;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn my_handler(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.set_static_segments(StaticSegments::Decoded);
+    ///         route.get("/release notes").to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/release%20notes")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// # }
+    /// ```
+    pub fn set_static_segments(&mut self, static_segments: StaticSegments) {
+        self.path_options.static_segments = static_segments;
+    }
+
+    /// Sets whether duplicate slashes in the request path are collapsed before matching routes,
+    /// so that `//users///42` matches `/users/42`. This is enabled by default. When disabled,
+    /// such a request receives a `404 Not Found` response.
+    pub fn set_collapse_slashes(&mut self, collapse: bool) {
+        self.path_options.collapse_slashes = collapse;
+    }
+
+    /// Sets whether `.` and `..` segments in the request path are resolved before matching
+    /// routes, so that `/users/./42/../43` matches `/users/43`. This is disabled by default, and
+    /// the segments are matched literally.
+    ///
+    /// When enabled, a request with a `..` segment which would move above the root receives a
+    /// `400 Bad Request` response.
+    pub fn set_resolve_dot_segments(&mut self, resolve: bool) {
+        self.path_options.resolve_dot_segments = resolve;
+    }
+
+    /// Sets whether the request path is lowercased before matching routes, for case-insensitive
+    /// routing. This is disabled by default.
+    ///
+    /// Routes should be defined in lowercase when enabled, and the `PathExtractor` receives the
+    /// lowercased segment values. Use `set_path_case` to match routes regardless of case while
+    /// keeping the original segment values.
+    pub fn set_lowercase_path(&mut self, lowercase: bool) {
+        self.path_options.lowercase = lowercase;
+    }
+
+    /// Sets the limits on the size of the request path, which is rejected before matching routes
+    /// when it exceeds one of them. See `PathLimits` for the defaults.
+    pub fn set_path_limits(&mut self, limits: PathLimits) {
+        self.path_options.limits = limits;
+    }
+
+    /// Sets whether the static segments of routes match request path segments which differ only
+    /// in case. The default is `PathCase::Sensitive`.
+    ///
+    /// The request path is matched as it was received, so the `PathExtractor` receives the
+    /// values of dynamic and glob segments with their original case.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::header::LOCATION;
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::request::path::PathCase;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn my_handler(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.set_path_case(PathCase::Redirect);
+    ///         route.get("/signup/:code").to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/SignUp/SPRING?ref=print")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    /// #   assert_eq!(response.headers().get(LOCATION).unwrap(), "/signup/SPRING?ref=print");
+    /// # }
+    /// ```
+    pub fn set_path_case(&mut self, case: PathCase) {
+        self.path_options.case = case;
+    }
+
+    /// Sets how a request path which ends with a slash (such as `/users/`) is matched against
+    /// routes, compared to the same path without one. The default is `TrailingSlash::Normalize`,
+    /// which matches both forms to the same route.
+    ///
+    /// The canonical form of a route defined with a trailing slash, such as `/teams/`, ends with
+    /// a slash. Otherwise, the canonical form is chosen with `set_canonical_trailing_slash`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::header::LOCATION;
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::request::path::TrailingSlash;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn my_handler(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.set_trailing_slash(TrailingSlash::Redirect);
+    ///         route.get("/users").to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/users/?page=2")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    /// #   assert_eq!(response.headers().get(LOCATION).unwrap(), "/users?page=2");
+    /// # }
+    /// ```
+    pub fn set_trailing_slash(&mut self, trailing_slash: TrailingSlash) {
+        self.path_options.trailing_slash = trailing_slash;
+    }
+
+    /// Sets whether the canonical form of a request path ends with a slash, for the
+    /// `TrailingSlash` modes which distinguish the two forms. This is disabled by default, so
+    /// `/users` is canonical rather than `/users/`.
+    pub fn set_canonical_trailing_slash(&mut self, trailing_slash: bool) {
+        self.path_options.canonical_trailing_slash = trailing_slash;
+    }
+
+    /// Sets whether `TrailingSlash::Redirect` applies to requests with methods other than `GET`
+    /// and `HEAD`, which are then redirected with `308 Permanent Redirect` so that the method and
+    /// body are repeated. This is disabled by default, and such requests are matched regardless
+    /// of the trailing slash.
+    pub fn set_redirect_trailing_slash_all_methods(&mut self, redirect: bool) {
+        self.path_options.redirect_all_methods = redirect;
+    }
+
+    /// Sets whether a `HEAD` request which matches no route is dispatched to the route which it
+    /// would match as a `GET` request. The body of the `Response` is then dropped without being
+    /// read, while its headers (including `Content-Length`) are sent. This is enabled by default.
+    ///
+    /// A route which matches the `HEAD` request, such as one added with `DrawRoutes::head` or
+    /// `DrawRoutes::get_or_head`, always takes precedence. Individual routes can opt out with
+    /// `DefineSingleRoute::without_automatic_head`.
+    pub fn set_automatic_head(&mut self, automatic_head: bool) {
+        self.automatic_head = automatic_head;
+    }
+
+    /// Sets whether an `OPTIONS` request which matches no route receives `204 No Content`, with
+    /// an `Allow` header listing the methods of the routes defined for the request path. A
+    /// server-wide `OPTIONS *` request then also receives `204 No Content`. This is disabled by
+    /// default.
+    ///
+    /// A route which matches the `OPTIONS` request, such as one added with `DrawRoutes::options`,
+    /// always takes precedence, so preflight requests continue to be answered by the routes (or
+    /// middleware) which handle CORS.
+    pub fn set_automatic_options(&mut self, automatic_options: bool) {
+        self.automatic_options = automatic_options;
     }
 }
 
@@ -228,6 +778,94 @@ where
     node_builder: &'a mut Node,
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
+    response_finalizer_builder: ResponseFinalizerBuilder,
+}
+
+impl<'a, C, P> ScopeBuilder<'a, C, P>
+where
+    C: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
+    P: Send + Sync + 'static,
+{
+    fn new(node_builder: &'a mut Node, pipeline_chain: C, pipelines: PipelineSet<P>) -> Self {
+        ScopeBuilder {
+            node_builder,
+            pipeline_chain,
+            pipelines,
+            response_finalizer_builder: ResponseFinalizerBuilder::internal_new(),
+        }
+    }
+
+    /// Adds a `ResponseExtender` for requests within this scope, which is used in preference to
+    /// those added with `RouterBuilder::add_response_extender`.
+    ///
+    /// A request is within the scope when its path begins with the path of the scope, whether or
+    /// not a route within the scope matches it, so this can replace the `404 Not Found` response
+    /// for missing paths beneath the scope (including those of a `Router` delegated to within
+    /// the scope). The extenders of a route (see `DefineSingleRoute::add_response_extender`) are
+    /// used in preference to those of its scope, and those of a scope in preference to those of
+    /// any enclosing scope. When none of them has an extender for the status code, the
+    /// extenders of the `Router` are used.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn not_found_page(_state: &mut State, res: &mut Response<Body>) {
+    ///     *res.body_mut() = "<h1>Page not found</h1>".into();
+    /// }
+    ///
+    /// fn not_found_json(_state: &mut State, res: &mut Response<Body>) {
+    ///     *res.body_mut() = r#"{"error":"not found"}"#.into();
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.add_response_extender(StatusCode::NOT_FOUND, not_found_page);
+    ///
+    ///         route.scope("/api", |route| {
+    ///             route.add_response_extender(StatusCode::NOT_FOUND, not_found_json);
+    ///         });
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/api/users")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), r#"{"error":"not found"}"#);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/users")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "<h1>Page not found</h1>");
+    /// # }
+    /// ```
+    pub fn add_response_extender<S, E>(&mut self, statuses: S, extender: E)
+    where
+        S: Into<StatusMatcher>,
+        E: ResponseExtender<Body> + Send + Sync + 'static,
+    {
+        self.response_finalizer_builder
+            .add_matching(statuses.into(), Box::new(extender))
+    }
+
+    /// Adds the response extenders of the scope to its node, once the scope has been defined.
+    fn finish(self) {
+        if !self.response_finalizer_builder.is_empty() {
+            let finalizer = self.response_finalizer_builder.finalize();
+            self.node_builder.add_response_finalizer(finalizer);
+        }
+    }
 }
 
 /// A delegated builder, which is created by `DrawRoutes::delegate` and returned. The `DrawRoutes`
@@ -251,6 +889,9 @@ where
 {
     /// Directs the delegated route to the given `Router`.
     pub fn to_router(self, router: Router) {
+        self.node_builder
+            .record_route(RouteRecord::Delegated(router.routes().clone()));
+
         let dispatcher = DispatcherImpl::new(router, self.pipeline_chain, self.pipelines);
         let route: DelegatedRoute = DelegatedRoute::new(
             AnyRouteMatcher::new(),
@@ -273,10 +914,24 @@ where
     PE: PathExtractor<Body> + Send + Sync + 'static,
     QSE: QueryStringExtractor<Body> + Send + Sync + 'static,
 {
+    // The route is added beneath this node, at `path`, once its handler is known. This allows
+    // segment constraints to be applied when the path is drawn into the tree.
     node_builder: &'a mut Node,
+    path: String,
+    segment_constraints: Vec<(String, SegmentType)>,
+    automatic_head: bool,
+    priority: i32,
+    // Whether the route is added as a fallback of `node_builder`, rather than at `path`.
+    fallback: bool,
+    // Whether the fallback route was defined by `not_found`, so that it also applies beneath
+    // delegated routers.
+    not_found: bool,
+    response_finalizer_builder: ResponseFinalizerBuilder,
     matcher: M,
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
+    extensions: RouteExtensions,
+    query_string_options: QueryStringOptions,
     phantom: PhantomData<(PE, QSE)>,
 }
 
@@ -298,12 +953,98 @@ where
     {
         SingleRouteBuilder {
             node_builder: self.node_builder,
+            path: self.path,
+            segment_constraints: self.segment_constraints,
+            automatic_head: self.automatic_head,
+            priority: self.priority,
+            fallback: self.fallback,
+            not_found: self.not_found,
+            response_finalizer_builder: self.response_finalizer_builder,
             matcher: self.matcher,
             pipeline_chain: self.pipeline_chain,
             pipelines: self.pipelines,
+            extensions: self.extensions,
+            query_string_options: self.query_string_options,
             phantom: PhantomData,
         }
     }
+
+    /// Constrains the dynamic segment `:name` of the route path, replacing its `SegmentType`
+    /// when the path is drawn into the tree.
+    fn constrain_segment(mut self, name: &str, segment_type: SegmentType) -> Self {
+        let dynamic = format!(":{}", name);
+        let optional = format!(":{}?", name);
+        if !self
+            .path
+            .split('/')
+            .any(|segment| segment == dynamic || segment == optional)
+        {
+            report(RouterBuildError::InvalidMatcher {
+                message: format!(
+                    "route path `{}` has no dynamic segment `{}` to constrain",
+                    self.path, dynamic
+                ),
+                path: self.path.clone(),
+            });
+            return self;
+        }
+
+        self.segment_constraints
+            .retain(|&(ref constrained, _)| constrained != name);
+        self.segment_constraints
+            .push((name.to_owned(), segment_type));
+        self
+    }
+}
+
+/// Wraps the `Handler` of a single route with a deadline, after `DefineSingleRoute::with_timeout`
+/// has been called. The `DefineSingleRoute` trait has documentation for using this type.
+pub struct TimeoutRouteBuilder<B>
+where
+    B: DefineSingleRoute,
+{
+    route_builder: B,
+    timeout: Duration,
+    status: StatusCode,
+}
+
+impl<B> TimeoutRouteBuilder<B>
+where
+    B: DefineSingleRoute,
+{
+    fn new(route_builder: B, timeout: Duration) -> Self {
+        TimeoutRouteBuilder {
+            route_builder,
+            timeout,
+            status: StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Sets the status of the `Response` sent when the deadline passes, e.g. `504 Gateway
+    /// Timeout` for routes which proxy to another service.
+    pub fn with_timeout_status(self, status: StatusCode) -> Self {
+        TimeoutRouteBuilder { status, ..self }
+    }
+
+    /// Directs the route to the given `Handler`, enforcing the deadline. See
+    /// `DefineSingleRoute::to`.
+    pub fn to<H>(self, handler: H)
+    where
+        H: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+    {
+        self.to_new_handler(move || Ok(handler))
+    }
+
+    /// Directs the route to the given `NewHandler`, enforcing the deadline on each `Handler` it
+    /// creates. See `DefineSingleRoute::to_new_handler`.
+    pub fn to_new_handler<NH>(self, new_handler: NH)
+    where
+        NH: NewHandler + 'static,
+    {
+        let new_handler =
+            TimeoutNewHandler::new(new_handler, self.timeout).with_status(self.status);
+        self.route_builder.to_new_handler(new_handler)
+    }
 }
 
 #[cfg(test)]
@@ -311,14 +1052,25 @@ mod tests {
     use super::*;
 
     use futures::{Future, Stream};
+    use hyper::header::LOCATION;
     use hyper::service::Service;
-    use hyper::{Body, Request, Response, StatusCode};
+    use hyper::{Body, HeaderMap, Method, Request, Response, StatusCode};
+
+    use std::io;
 
+    use futures::future;
+
+    use handler::{HandlerError, HandlerFuture, IntoHandlerError};
+    use helpers::http::header::X_REQUEST_ID;
+    use helpers::http::response::create_response;
     use middleware::session::NewSessionMiddleware;
+    use middleware::{Middleware, NewMiddleware};
     use pipeline::new_pipeline;
+    use pipeline::single::single_pipeline;
     use router::response::extender::StaticResponseExtender;
+    use router::response::finalizer::StatusClass;
     use service::GothamService;
-    use state::{State, StateData};
+    use state::{request_id, FromState, State, StateData};
 
     #[derive(Deserialize)]
     struct SalutationParams {
@@ -584,4 +1336,867 @@ mod tests {
         let response_bytes = response.into_body().concat2().wait().unwrap().to_vec();
         assert_eq!(&response_bytes[..], b"It's a resource.");
     }
+
+    #[derive(Clone)]
+    struct FailingMiddleware;
+
+    impl NewMiddleware for FailingMiddleware {
+        type Instance = Self;
+
+        fn new_middleware(&self) -> io::Result<Self::Instance> {
+            Ok(self.clone())
+        }
+    }
+
+    impl Middleware for FailingMiddleware {
+        fn call<Chain>(self, state: State, _chain: Chain) -> Box<HandlerFuture>
+        where
+            Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+        {
+            let err = io::Error::new(io::ErrorKind::Other, "middleware failed")
+                .into_handler_error()
+                .with_status(StatusCode::BAD_GATEWAY);
+            Box::new(future::err((state, err)))
+        }
+    }
+
+    fn failing_handler(state: State) -> Box<HandlerFuture> {
+        let err = io::Error::new(io::ErrorKind::Other, "handler failed")
+            .into_handler_error()
+            .with_status(StatusCode::SERVICE_UNAVAILABLE);
+        Box::new(future::err((state, err)))
+    }
+
+    fn unmapped_handler(state: State) -> Box<HandlerFuture> {
+        let err = io::Error::new(io::ErrorKind::Other, "not mapped")
+            .into_handler_error()
+            .with_status(StatusCode::NOT_IMPLEMENTED);
+        Box::new(future::err((state, err)))
+    }
+
+    #[test]
+    fn error_mapper_test() {
+        let pipelines = new_pipeline_set();
+        let (pipelines, failing) = pipelines.add(new_pipeline().add(FailingMiddleware).build());
+        let pipelines = finalize_pipeline_set(pipelines);
+
+        let router = build_router((), pipelines, |route| {
+            route.set_error_mapper(|state: &State, err: &HandlerError| {
+                if err.status() == StatusCode::NOT_IMPLEMENTED {
+                    return None;
+                }
+
+                let body = format!(
+                    "{{\"status\":{},\"request_id\":\"{}\"}}",
+                    err.status().as_u16(),
+                    request_id(state)
+                );
+                Some(
+                    Response::builder()
+                        .status(err.status())
+                        .body(body.into())
+                        .unwrap(),
+                )
+            });
+
+            route.get("/handler").to(failing_handler);
+
+            route.get("/unmapped").to(unmapped_handler);
+
+            route.with_pipeline_chain((failing, ()), |route| {
+                route.get("/middleware").to(welcome::index);
+            });
+        });
+
+        let new_service = GothamService::new(router);
+
+        let call = move |req| {
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            service.call(req).wait().unwrap()
+        };
+
+        let response = call(
+            Request::get("/handler")
+                .header(X_REQUEST_ID, "handler-id")
+                .body(Body::empty())
+                .unwrap(),
+        );
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let response_bytes = response.into_body().concat2().wait().unwrap().to_vec();
+        assert_eq!(
+            &String::from_utf8(response_bytes).unwrap(),
+            "{\"status\":503,\"request_id\":\"handler-id\"}"
+        );
+
+        let response = call(
+            Request::get("/middleware")
+                .header(X_REQUEST_ID, "middleware-id")
+                .body(Body::empty())
+                .unwrap(),
+        );
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        let response_bytes = response.into_body().concat2().wait().unwrap().to_vec();
+        assert_eq!(
+            &String::from_utf8(response_bytes).unwrap(),
+            "{\"status\":502,\"request_id\":\"middleware-id\"}"
+        );
+
+        let response = call(Request::get("/unmapped").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+        let response_bytes = response.into_body().concat2().wait().unwrap().to_vec();
+        assert!(response_bytes.is_empty());
+    }
+
+    #[derive(Clone)]
+    struct Permission(&'static str);
+
+    impl StateData for Permission {}
+
+    #[derive(Clone)]
+    struct PermissionMiddleware;
+
+    impl NewMiddleware for PermissionMiddleware {
+        type Instance = Self;
+
+        fn new_middleware(&self) -> io::Result<Self::Instance> {
+            Ok(self.clone())
+        }
+    }
+
+    impl Middleware for PermissionMiddleware {
+        fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+        where
+            Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+        {
+            let permitted = match Permission::try_borrow_from(&state) {
+                Some(&Permission(required)) => HeaderMap::borrow_from(&state)
+                    .get("x-role")
+                    .map(|role| role == required)
+                    .unwrap_or(false),
+                None => true,
+            };
+
+            if permitted {
+                chain(state)
+            } else {
+                let err = io::Error::new(io::ErrorKind::Other, "permission denied")
+                    .into_handler_error()
+                    .with_status(StatusCode::FORBIDDEN);
+                Box::new(future::err((state, err)))
+            }
+        }
+    }
+
+    fn call_path(router: Router, path: &str) -> (StatusCode, String) {
+        let new_service = GothamService::new(router);
+        let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+        let response = service
+            .call(Request::get(path).body(Body::empty()).unwrap())
+            .wait()
+            .unwrap();
+        let status = response.status();
+        let response_bytes = response.into_body().concat2().wait().unwrap().to_vec();
+        (status, String::from_utf8(response_bytes).unwrap())
+    }
+
+    #[test]
+    fn encoded_path_segments_test() {
+        let router = |allow_encoded_slashes| {
+            build_simple_router(|route| {
+                route.set_allow_encoded_slashes(allow_encoded_slashes);
+
+                route
+                    .get("/hello/:name")
+                    .with_path_extractor::<SalutationParams>()
+                    .to(welcome::hello);
+
+                route.get("/dotted%20name").to(welcome::index);
+                route.get("/users").to(welcome::literal);
+            })
+        };
+
+        let (status, body) = call_path(router(false), "/hello/dotted%20name");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "Hello, dotted name!");
+
+        // Static segments match the encoded form, byte for byte.
+        let (status, _) = call_path(router(false), "/dotted%20name");
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = call_path(router(false), "/dotted%20Name");
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = call_path(router(false), "/%75sers");
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = call_path(router(false), "/hello/%FF%FE");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = call_path(router(false), "/hello/a%2Fb");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, body) = call_path(router(true), "/hello/a%2Fb");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "Hello, a/b!");
+    }
+
+    #[test]
+    fn decoded_static_segments_test() {
+        let router = |static_segments| {
+            build_simple_router(|route| {
+                route.set_static_segments(static_segments);
+
+                route.get("/dotted name").to(welcome::index);
+                route.get("/users").to(welcome::literal);
+            })
+        };
+
+        use helpers::http::request::path::StaticSegments::{Decoded, Encoded};
+        let cases = vec![
+            (Encoded, "/dotted%20name", 404),
+            (Encoded, "/users", 201),
+            (Encoded, "/%75sers", 404),
+            (Decoded, "/dotted%20name", 200),
+            (Decoded, "/users", 201),
+            (Decoded, "/%75sers", 201),
+        ];
+
+        for (static_segments, path, status) in cases {
+            let (actual_status, _) = call_path(router(static_segments), path);
+            assert_eq!(
+                actual_status.as_u16(),
+                status,
+                "{:?} {}",
+                static_segments,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn encoded_slashes_test() {
+        #[derive(Deserialize)]
+        struct GlobParams {
+            #[serde(rename = "*")]
+            segments: Vec<String>,
+        }
+
+        impl StateData for GlobParams {}
+
+        impl StaticResponseExtender for GlobParams {
+            type ResBody = Body;
+            fn extend(_: &mut State, _: &mut Response<Body>) {}
+        }
+
+        fn glob_handler(mut state: State) -> (State, Response<Body>) {
+            let params = state.take::<GlobParams>();
+            (state, Response::new(params.segments.join(" | ").into()))
+        }
+
+        let router = |encoded_slashes| {
+            build_simple_router(|route| {
+                route.set_encoded_slashes(encoded_slashes);
+
+                route
+                    .get("/packages/:name")
+                    .with_path_extractor::<SalutationParams>()
+                    .to(welcome::hello);
+                route.get("/static/a/b").to(welcome::literal);
+                route
+                    .get("/files/**")
+                    .with_path_extractor::<GlobParams>()
+                    .to(glob_handler);
+            })
+        };
+
+        use helpers::http::request::path::EncodedSlashes::{Preserve, Reject, Split};
+        let cases = vec![
+            (Reject, "/packages/@a%2Fb", 400, ""),
+            (Reject, "/packages/@a%252Fb", 200, "Hello, @a%2Fb!"),
+            (Reject, "/static/a%2Fb", 400, ""),
+            (Reject, "/files/a%2Fb", 400, ""),
+            (Reject, "/files/a%252Fb", 200, "a%2Fb"),
+            (Split, "/packages/@a%2Fb", 404, ""),
+            (Split, "/packages/@a%252Fb", 200, "Hello, @a%2Fb!"),
+            (Split, "/static/a%2Fb", 201, ""),
+            (Split, "/files/a%2Fb/c", 200, "a | b | c"),
+            (Split, "/files/a%252Fb", 200, "a%2Fb"),
+            (Preserve, "/packages/@a%2Fb", 200, "Hello, @a/b!"),
+            (Preserve, "/packages/@a%252Fb", 200, "Hello, @a%2Fb!"),
+            (Preserve, "/static/a%2Fb", 404, ""),
+            (Preserve, "/files/a%2Fb/c", 200, "a/b | c"),
+            (Preserve, "/files/a%252Fb", 200, "a%2Fb"),
+        ];
+
+        for (encoded_slashes, path, status, body) in cases {
+            let (actual_status, actual_body) = call_path(router(encoded_slashes), path);
+            assert_eq!(
+                actual_status.as_u16(),
+                status,
+                "{:?} {}",
+                encoded_slashes,
+                path
+            );
+            assert_eq!(actual_body, body, "{:?} {}", encoded_slashes, path);
+        }
+    }
+
+    #[test]
+    fn path_limits_test() {
+        let router = |limits: Option<PathLimits>| {
+            build_simple_router(|route| {
+                if let Some(limits) = limits {
+                    route.set_path_limits(limits);
+                }
+
+                route
+                    .get("/hello/:name")
+                    .with_path_extractor::<SalutationParams>()
+                    .to(welcome::hello);
+                route.get("/files/*").to(welcome::globbed);
+            })
+        };
+
+        let many_segments = format!("/files{}", "/a".repeat(10_000));
+        let (status, _) = call_path(router(None), &many_segments);
+        assert_eq!(status, StatusCode::URI_TOO_LONG);
+
+        let (status, body) = call_path(router(None), &format!("/hello/{}", "a".repeat(4096)));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.len(), "Hello, !".len() + 4096);
+
+        let (status, _) = call_path(router(None), &format!("/hello/{}", "a".repeat(4097)));
+        assert_eq!(status, StatusCode::URI_TOO_LONG);
+
+        let limits = PathLimits::default()
+            .with_max_segments(4)
+            .with_status(StatusCode::NOT_FOUND);
+        let (status, body) = call_path(router(Some(limits)), "/files/a/b/c");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "Globbed");
+
+        let (status, _) = call_path(router(Some(limits)), "/files/a/b/c/d");
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn path_normalization_test() {
+        let router = |collapse, resolve, lowercase| {
+            build_simple_router(|route| {
+                route.set_collapse_slashes(collapse);
+                route.set_resolve_dot_segments(resolve);
+                route.set_lowercase_path(lowercase);
+
+                route
+                    .get("/users/:name")
+                    .with_path_extractor::<SalutationParams>()
+                    .to(welcome::hello);
+            })
+        };
+
+        let defaults = (true, false, false);
+        let strict = (false, false, false);
+        let dots = (true, true, false);
+        let lowercase = (true, false, true);
+
+        let cases = vec![
+            ("/users/42", defaults, StatusCode::OK, "42"),
+            ("//users///42", defaults, StatusCode::OK, "42"),
+            ("//users///42", strict, StatusCode::NOT_FOUND, ""),
+            ("/users/42/", strict, StatusCode::OK, "42"),
+            ("/users/./42/../42", defaults, StatusCode::NOT_FOUND, ""),
+            ("//users/./42/../42", dots, StatusCode::OK, "42"),
+            ("/users/41/../42", dots, StatusCode::OK, "42"),
+            ("/users/../../42", dots, StatusCode::BAD_REQUEST, ""),
+            ("/Users/Ann", defaults, StatusCode::NOT_FOUND, ""),
+            ("/Users/Ann", lowercase, StatusCode::OK, "ann"),
+        ];
+
+        for (path, (collapse, resolve, lowercase), status, name) in cases {
+            let (actual_status, body) = call_path(router(collapse, resolve, lowercase), path);
+            assert_eq!(actual_status, status, "requesting {}", path);
+            if status == StatusCode::OK {
+                assert_eq!(body, format!("Hello, {}!", name), "requesting {}", path);
+            }
+        }
+    }
+
+    #[test]
+    fn trailing_slash_test() {
+        let router = |mode, canonical_slash, all_methods| {
+            build_simple_router(|route| {
+                route.set_trailing_slash(mode);
+                route.set_canonical_trailing_slash(canonical_slash);
+                route.set_redirect_trailing_slash_all_methods(all_methods);
+
+                route.get("/").to(welcome::index);
+                route.get("/users").to(welcome::index);
+                route.post("/users").to(welcome::literal);
+                route.get("/teams/").to(welcome::index);
+                route
+                    .get("/users/:name")
+                    .with_path_extractor::<SalutationParams>()
+                    .to(welcome::hello);
+            })
+        };
+
+        let call = |router: Router, method: Method, path: &str| {
+            let new_service = GothamService::new(router);
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            let mut request = Request::new(Body::empty());
+            *request.method_mut() = method;
+            *request.uri_mut() = path.parse().unwrap();
+
+            let response = service.call(request).wait().unwrap();
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .map(|location| location.to_str().unwrap().to_owned());
+            (response.status(), location)
+        };
+
+        let normalize = (TrailingSlash::Normalize, false, false);
+        let strict = (TrailingSlash::Strict, false, false);
+        let strict_slash = (TrailingSlash::Strict, true, false);
+        let redirect = (TrailingSlash::Redirect, false, false);
+        let redirect_slash = (TrailingSlash::Redirect, true, false);
+        let redirect_all = (TrailingSlash::Redirect, false, true);
+
+        let cases = vec![
+            (normalize, Method::GET, "/users", StatusCode::OK, ""),
+            (normalize, Method::GET, "/users/", StatusCode::OK, ""),
+            (normalize, Method::GET, "/teams", StatusCode::OK, ""),
+            (normalize, Method::GET, "/users/ann/", StatusCode::OK, ""),
+            (strict, Method::GET, "/", StatusCode::OK, ""),
+            (strict, Method::GET, "/users", StatusCode::OK, ""),
+            (strict, Method::GET, "/users/", StatusCode::NOT_FOUND, ""),
+            (strict, Method::GET, "/teams/", StatusCode::OK, ""),
+            (strict, Method::GET, "/teams", StatusCode::NOT_FOUND, ""),
+            (strict_slash, Method::GET, "/", StatusCode::OK, ""),
+            (
+                strict_slash,
+                Method::GET,
+                "/users",
+                StatusCode::NOT_FOUND,
+                "",
+            ),
+            (strict_slash, Method::GET, "/users/ann/", StatusCode::OK, ""),
+            (
+                strict_slash,
+                Method::GET,
+                "/users/ann",
+                StatusCode::NOT_FOUND,
+                "",
+            ),
+            (redirect, Method::GET, "/users", StatusCode::OK, ""),
+            (
+                redirect,
+                Method::GET,
+                "/missing/",
+                StatusCode::NOT_FOUND,
+                "",
+            ),
+            (redirect, Method::POST, "/users/", StatusCode::CREATED, ""),
+            (
+                redirect,
+                Method::HEAD,
+                "/users/",
+                StatusCode::MOVED_PERMANENTLY,
+                "/users",
+            ),
+            (
+                redirect,
+                Method::GET,
+                "/users/?page=2",
+                StatusCode::MOVED_PERMANENTLY,
+                "/users?page=2",
+            ),
+            (
+                redirect,
+                Method::GET,
+                "/users/ann/",
+                StatusCode::MOVED_PERMANENTLY,
+                "/users/ann",
+            ),
+            (
+                redirect,
+                Method::GET,
+                "//users/",
+                StatusCode::MOVED_PERMANENTLY,
+                "/users",
+            ),
+            (
+                redirect,
+                Method::GET,
+                "/teams?page=2",
+                StatusCode::MOVED_PERMANENTLY,
+                "/teams/?page=2",
+            ),
+            (
+                redirect_slash,
+                Method::GET,
+                "/users/ann",
+                StatusCode::MOVED_PERMANENTLY,
+                "/users/ann/",
+            ),
+            (
+                redirect_all,
+                Method::POST,
+                "/users/",
+                StatusCode::PERMANENT_REDIRECT,
+                "/users",
+            ),
+        ];
+
+        for ((mode, canonical_slash, all_methods), method, path, status, location) in cases {
+            let description = format!("{:?} {} {} {}", mode, canonical_slash, method, path);
+            let (actual_status, actual_location) =
+                call(router(mode, canonical_slash, all_methods), method, path);
+
+            assert_eq!(actual_status, status, "{}", description);
+            assert_eq!(
+                actual_location.unwrap_or_default(),
+                location,
+                "{}",
+                description
+            );
+        }
+    }
+
+    #[test]
+    fn path_case_test() {
+        #[derive(Deserialize)]
+        struct FileParams {
+            #[serde(rename = "*")]
+            path: String,
+        }
+
+        impl StateData for FileParams {}
+
+        impl StaticResponseExtender for FileParams {
+            type ResBody = Body;
+            fn extend(_: &mut State, _: &mut Response<Body>) {}
+        }
+
+        fn file_handler(mut state: State) -> (State, Response<Body>) {
+            let params = state.take::<FileParams>();
+            (state, Response::new(params.path.into()))
+        }
+
+        let router = |case| {
+            build_simple_router(|route| {
+                route.set_path_case(case);
+
+                route.get("/signup").to(welcome::literal);
+                route.post("/signup").to(welcome::literal);
+                route
+                    .get("/users/:name")
+                    .with_path_extractor::<SalutationParams>()
+                    .to(welcome::hello);
+                route
+                    .get("/files/*")
+                    .with_path_extractor::<FileParams>()
+                    .to(file_handler);
+            })
+        };
+
+        let call = |router: Router, method: Method, path: &str| {
+            let new_service = GothamService::new(router);
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            let mut request = Request::new(Body::empty());
+            *request.method_mut() = method;
+            *request.uri_mut() = path.parse().unwrap();
+
+            let response = service.call(request).wait().unwrap();
+            let status = response.status();
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .map(|location| location.to_str().unwrap().to_owned())
+                .unwrap_or_default();
+            let response_bytes = response.into_body().concat2().wait().unwrap().to_vec();
+            (status, String::from_utf8(response_bytes).unwrap(), location)
+        };
+
+        let cases = vec![
+            (PathCase::Sensitive, Method::GET, "/signup", 201, "", ""),
+            (PathCase::Sensitive, Method::GET, "/Signup", 404, "", ""),
+            (PathCase::Sensitive, Method::GET, "/Users/Ann", 404, "", ""),
+            (PathCase::Insensitive, Method::GET, "/Signup", 201, "", ""),
+            (PathCase::Insensitive, Method::POST, "/SIGNUP", 201, "", ""),
+            (
+                PathCase::Insensitive,
+                Method::GET,
+                "/Users/Ann",
+                200,
+                "Hello, Ann!",
+                "",
+            ),
+            (
+                PathCase::Insensitive,
+                Method::GET,
+                "/FILES/Docs/ReadMe.md",
+                200,
+                "Docs/ReadMe.md",
+                "",
+            ),
+            (PathCase::Insensitive, Method::GET, "/Signin", 404, "", ""),
+            (PathCase::Redirect, Method::GET, "/signup", 201, "", ""),
+            (
+                PathCase::Redirect,
+                Method::GET,
+                "/Signup?ref=print",
+                301,
+                "",
+                "/signup?ref=print",
+            ),
+            (
+                PathCase::Redirect,
+                Method::HEAD,
+                "/SignUp",
+                301,
+                "",
+                "/signup",
+            ),
+            (PathCase::Redirect, Method::POST, "/SignUp", 201, "", ""),
+            (
+                PathCase::Redirect,
+                Method::GET,
+                "/Users/Ann/",
+                301,
+                "",
+                "/users/Ann/",
+            ),
+            (
+                PathCase::Redirect,
+                Method::GET,
+                "/users/Ann",
+                200,
+                "Hello, Ann!",
+                "",
+            ),
+            (
+                PathCase::Redirect,
+                Method::GET,
+                "/Files/My%20Docs/ReadMe.md",
+                301,
+                "",
+                "/files/My%20Docs/ReadMe.md",
+            ),
+            (
+                PathCase::Redirect,
+                Method::GET,
+                "/files/Docs/ReadMe.md",
+                200,
+                "Docs/ReadMe.md",
+                "",
+            ),
+        ];
+
+        for (case, method, path, status, body, location) in cases {
+            let description = format!("{:?} {} {}", case, method, path);
+            let (actual_status, actual_body, actual_location) = call(router(case), method, path);
+
+            assert_eq!(actual_status.as_u16(), status, "{}", description);
+            assert_eq!(actual_body, body, "{}", description);
+            assert_eq!(actual_location, location, "{}", description);
+        }
+    }
+
+    #[test]
+    fn route_extensions_test() {
+        let (chain, pipelines) = single_pipeline(new_pipeline().add(PermissionMiddleware).build());
+
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/").to(welcome::index);
+
+            route
+                .get("/admin")
+                .with_extension(Permission("admin"))
+                .to(welcome::literal);
+        });
+
+        let new_service = GothamService::new(router);
+
+        let call = move |req| {
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            service.call(req).wait().unwrap()
+        };
+
+        let response = call(Request::get("/").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = call(Request::get("/admin").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = call(
+            Request::get("/admin")
+                .header("x-role", "admin")
+                .body(Body::empty())
+                .unwrap(),
+        );
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    fn set_body(body: &'static str) -> impl Fn(&mut State, &mut Response<Body>) + Send + Sync {
+        move |_state: &mut State, res: &mut Response<Body>| {
+            *res.body_mut() = body.into();
+        }
+    }
+
+    fn not_found_handler(state: State) -> (State, Response<Body>) {
+        let response = create_response(&state, StatusCode::NOT_FOUND, None);
+        (state, response)
+    }
+
+    #[test]
+    fn scoped_response_extenders_test() {
+        let docs_router = build_simple_router(|route| {
+            route.get("/").to(welcome::index);
+        });
+
+        let router = build_simple_router(|route| {
+            route.add_response_extender(StatusCode::NOT_FOUND, set_body("page"));
+            route.get("/").to(welcome::index);
+
+            route.scope("/api", |route| {
+                route.add_response_extender(StatusCode::NOT_FOUND, set_body("json"));
+                route.get("/users/:id").to(not_found_handler);
+                route
+                    .get("/teams/:id")
+                    .add_response_extender(StatusCode::NOT_FOUND, set_body("team"))
+                    .to(not_found_handler);
+                route.delegate("/docs").to_router(docs_router);
+
+                route.scope("/v2", |route| {
+                    route.add_response_extender(StatusClass::ClientError, set_body("v2"));
+                });
+            });
+        });
+
+        let cases = vec![
+            ("/missing", "page"),
+            ("/apis", "page"),
+            ("/api/missing", "json"),
+            ("/api/users/1", "json"),
+            ("/api/teams/1", "team"),
+            ("/api/docs/missing", "json"),
+            ("/api/v2/missing", "v2"),
+        ];
+
+        for (path, body) in cases {
+            assert_eq!(
+                call_path(router.clone(), path),
+                (StatusCode::NOT_FOUND, body.to_owned())
+            );
+        }
+
+        assert_eq!(call_path(router, "/api/docs").0, StatusCode::OK);
+    }
+
+    #[test]
+    fn build_router_result_test() {
+        fn build<F>(f: F) -> Result<Router, RouterBuildError>
+        where
+            F: FnOnce(&mut RouterBuilder<(), ()>),
+        {
+            build_router_result((), finalize_pipeline_set(new_pipeline_set()), f)
+        }
+
+        fn assert_invalid_pattern(
+            result: Result<Router, RouterBuildError>,
+            expected_path: &str,
+            expected_segment: usize,
+        ) {
+            match result.err() {
+                Some(RouterBuildError::InvalidPattern { path, segment, .. }) => {
+                    assert_eq!(path, expected_path);
+                    assert_eq!(segment, expected_segment);
+                }
+                e => panic!(
+                    "expected an invalid pattern in `{}`: {:?}",
+                    expected_path, e
+                ),
+            }
+        }
+
+        fn assert_invalid_matcher(result: Result<Router, RouterBuildError>, expected_path: &str) {
+            match result.err() {
+                Some(RouterBuildError::InvalidMatcher { path, .. }) => {
+                    assert_eq!(path, expected_path)
+                }
+                e => panic!(
+                    "expected an invalid matcher in `{}`: {:?}",
+                    expected_path, e
+                ),
+            }
+        }
+
+        assert_invalid_pattern(
+            build(|route| route.get("/files/*/raw").to(welcome::index)),
+            "/files/*/raw",
+            1,
+        );
+        assert_invalid_pattern(
+            build(|route| route.get("/archive/:year?/:month").to(welcome::index)),
+            "/archive/:year?/:month",
+            2,
+        );
+        assert_invalid_pattern(
+            build(|route| route.get("/users/:id:[0-9").to(welcome::index)),
+            "/users/:id:[0-9",
+            1,
+        );
+        assert_invalid_pattern(
+            build(|route| route.scope("/api/:version?", |_| {})),
+            "/api/:version?",
+            1,
+        );
+        assert_invalid_pattern(
+            build(|route| route.get("/static/assets").to_dir("resources")),
+            "/static/assets",
+            1,
+        );
+
+        assert_invalid_matcher(
+            build(|route| {
+                route
+                    .get("/users/:id")
+                    .with_segment_regex("user_id", "[0-9]+")
+                    .to(welcome::index)
+            }),
+            "/users/:id",
+        );
+        assert_invalid_matcher(
+            build(|route| {
+                route
+                    .get("/users/:id")
+                    .with_segment_regex("id", "[0-9")
+                    .to(welcome::index)
+            }),
+            "/users/:id",
+        );
+
+        let result = build(|route| {
+            route.get("/users/:id").to(welcome::index);
+            route.get("/users/:user_id").to(welcome::index);
+        });
+        assert_eq!(
+            result.err(),
+            Some(RouterBuildError::Conflict {
+                route: "GET /users/:user_id".to_owned(),
+                existing: "GET /users/:id".to_owned(),
+            })
+        );
+
+        // A delegated router which fails to build doesn't affect the outer router, and the
+        // problems of one build aren't reported by the next.
+        let result = build(|route| {
+            let inner = build(|route| route.get("/*/raw").to(welcome::index));
+            assert!(inner.is_err());
+            route.get("/").to(welcome::index);
+        });
+        assert!(result.is_ok());
+        assert!(build(|route| route.get("/").to(welcome::index)).is_ok());
+    }
 }
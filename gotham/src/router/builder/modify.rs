@@ -103,8 +103,17 @@ where
             matcher: AndRouteMatcher::<M, NRM>::new(self.matcher, matcher),
             phantom: self.phantom,
             node_builder: self.node_builder,
+            path: self.path,
+            segment_constraints: self.segment_constraints,
+            automatic_head: self.automatic_head,
+            priority: self.priority,
+            fallback: self.fallback,
+            not_found: self.not_found,
+            response_finalizer_builder: self.response_finalizer_builder,
             pipeline_chain: self.pipeline_chain,
             pipelines: self.pipelines,
+            extensions: self.extensions,
+            query_string_options: self.query_string_options,
         }
     }
 }
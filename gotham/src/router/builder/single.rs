@@ -1,15 +1,39 @@
+use std::any::Any;
 use std::panic::RefUnwindSafe;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
-use extractor::{PathExtractor, QueryStringExtractor};
-use handler::{Handler, NewHandler};
+use extractor::{PathExtractor, QueryStringExtractor, QueryStringOptions};
+use handler::controller::ControllerHandler;
+use handler::dir::{DirHandler, DirOptions, DirPath};
+use handler::redirect::{RedirectHandler, RedirectKind, RedirectOptions, RedirectParams};
+use handler::{Handler, IntoHandlerFuture, NewHandler};
 use hyper::Body;
+use mime::Mime;
 use pipeline::chain::PipelineHandleChain;
+use router::builder::draw::{descend_with_constraints, has_trailing_slash};
+use router::builder::error::report;
 use router::builder::{
-    ExtendRouteMatcher, ReplacePathExtractor, ReplaceQueryStringExtractor, SingleRouteBuilder,
+    ExtendRouteMatcher, ReplacePathExtractor, ReplaceQueryStringExtractor, RouterBuildError,
+    SingleRouteBuilder, TimeoutRouteBuilder,
 };
+use router::response::extender::ResponseExtender;
+use router::response::finalizer::StatusMatcher;
 use router::route::dispatch::DispatcherImpl;
-use router::route::matcher::RouteMatcher;
+use router::route::matcher::content_type::ContentTypeHeaderRouteMatcher;
+use router::route::matcher::query::QueryStringRouteMatcher;
+use router::route::matcher::{
+    AcceptHeaderRouteMatcher, HostRouteMatcher, MatcherSummary, MethodOnlyRouteMatcher,
+    RouteMatcher,
+};
 use router::route::{Delegation, Extractors, RouteImpl};
+use router::table::RouteRecord;
+use router::tree::regex::ConstrainedSegmentRegex;
+use router::tree::segment::SegmentType;
+use router::tree::typed::TypedSegmentParser;
+use state::{State, StateData};
 
 /// Describes the API for defining a single route, after determining which request paths will be
 /// dispatched here. The API here uses chained function calls to build and add the route into the
@@ -168,6 +192,184 @@ pub trait DefineSingleRoute {
     where
         NH: NewHandler + 'static;
 
+    /// Directs the route to a `DirHandler`, serving the files beneath the directory `root`. The
+    /// path of the route must end with an anonymous glob segment (`*`, or `**` to also serve the
+    /// index file of `root` itself), which is extracted as the path of the file beneath `root`.
+    ///
+    /// A `HEAD` request is answered as for `GET`, without the body, unless
+    /// `without_automatic_head` is used. A request for a path which doesn't name a file beneath
+    /// `root`, or which contains a `.` or `..` segment, receives a `404 Not Found` response. See
+    /// `DirOptions` for the default index file and cache headers, and `to_dir_with` to change
+    /// them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use std::fs;
+    /// # use hyper::StatusCode;
+    /// # use hyper::header::CONTENT_TYPE;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn router(public: &str) -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.get("/static/*").to_dir(public);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let public = std::env::temp_dir().join("gotham-to-dir-doctest");
+    /// #   fs::create_dir_all(public.join("css")).unwrap();
+    /// #   fs::write(public.join("css/site.css"), "body {}").unwrap();
+    /// #
+    /// #   let test_server = TestServer::new(router(public.to_str().unwrap())).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/static/css/site.css")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #   assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "text/css; charset=utf-8");
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "body {}");
+    /// # }
+    /// ```
+    fn to_dir<R>(self, root: R)
+    where
+        R: Into<PathBuf>,
+        Self: Sized,
+    {
+        self.to_dir_with(DirOptions::new(root))
+    }
+
+    /// Directs the route to a `DirHandler` with the given `DirOptions`, in the same way as
+    /// `to_dir`.
+    fn to_dir_with(self, options: DirOptions);
+
+    /// Directs the route to a `RedirectHandler`, redirecting requests to `target` with the given
+    /// kind of redirect. Placeholders in `target`, such as `:slug` or `*`, are replaced by the
+    /// percent-encoded values matched by the dynamic segments of the same name, and the query
+    /// string of the request is appended. See `RedirectOptions` for details, and
+    /// `redirect_to_with` to drop the query string.
+    ///
+    /// Each placeholder must name a dynamic segment of the route path, or the `Router` can't be
+    /// built.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::StatusCode;
+    /// # use hyper::header::LOCATION;
+    /// # use gotham::handler::redirect::RedirectKind;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route
+    ///             .get("/old-blog/:slug")
+    ///             .redirect_to("/blog/:slug", RedirectKind::Permanent);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/old-blog/hello-world?page=2")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    /// #   assert_eq!(response.headers()[LOCATION], "/blog/hello-world?page=2");
+    /// # }
+    /// ```
+    fn redirect_to(self, target: &str, kind: RedirectKind)
+    where
+        Self: Sized,
+    {
+        self.redirect_to_with(RedirectOptions::new(target, kind))
+    }
+
+    /// Directs the route to a `RedirectHandler` with the given `RedirectOptions`, in the same way
+    /// as `redirect_to`.
+    fn redirect_to_with(self, options: RedirectOptions);
+
+    /// Directs the route to a method of a controller, which is shared via `Arc` with the other
+    /// routes directed to it. This allows related handlers to be grouped as methods of a struct
+    /// which holds their dependencies, constructed once before the `Router` is built. See
+    /// `ControllerHandler` for details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use std::sync::Arc;
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::response::create_text_response;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// struct UsersController {
+    ///     users: Vec<String>,
+    /// }
+    ///
+    /// impl UsersController {
+    ///     fn index(&self, state: State) -> (State, Response<Body>) {
+    ///         let res = create_text_response(&state, StatusCode::OK, self.users.join(", "));
+    ///         (state, res)
+    ///     }
+    ///
+    ///     fn count(&self, state: State) -> (State, Response<Body>) {
+    ///         let count = self.users.len().to_string();
+    ///         let res = create_text_response(&state, StatusCode::OK, count);
+    ///         (state, res)
+    ///     }
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     let users = Arc::new(UsersController {
+    ///         users: vec!["alice".to_owned(), "bob".to_owned()],
+    ///     });
+    ///
+    ///     build_simple_router(|route| {
+    ///         route.get("/users").to_controller(users.clone(), UsersController::index);
+    ///         route.get("/users/count").to_controller(users, UsersController::count);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/users")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "alice, bob");
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/users/count")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "2");
+    /// # }
+    /// ```
+    fn to_controller<C, R>(self, controller: Arc<C>, method: fn(&C, State) -> R)
+    where
+        C: Send + Sync + RefUnwindSafe + 'static,
+        R: IntoHandlerFuture + 'static,
+        Self: Sized,
+    {
+        self.to_new_handler(ControllerHandler::new(controller, method))
+    }
+
     /// Applies a `PathExtractor` type to the current route, to extract path parameters into
     /// `State` with the given type.
     ///
@@ -307,6 +509,62 @@ pub trait DefineSingleRoute {
         Self: ReplaceQueryStringExtractor<NQSE>,
         Self::Output: DefineSingleRoute;
 
+    /// Sets the options used to parse the query string before it is deserialized into the
+    /// `QueryStringExtractor` of the current route.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// # extern crate hyper;
+    /// # extern crate serde;
+    /// # #[macro_use]
+    /// # extern crate serde_derive;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::extractor::QueryStringOptions;
+    /// # use gotham::state::{State, FromState};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// #[derive(StateData, Deserialize, StaticResponseExtender)]
+    /// struct TagParams {
+    ///     #[serde(default)]
+    ///     tag: Vec<String>,
+    /// }
+    ///
+    /// fn my_handler(state: State) -> (State, Response<Body>) {
+    ///     let tags = &TagParams::borrow_from(&state).tag;
+    ///
+    ///     // Handler implementation elided.
+    /// #   assert_eq!(tags, &vec!["a".to_owned(), "b".to_owned()]);
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route
+    ///             .get("/posts")
+    ///             .with_query_string_extractor::<TagParams>()
+    ///             .with_query_string_options(QueryStringOptions::new().with_bracket_suffix(true))
+    ///             .to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/posts?tag[]=a&tag[]=b")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// # }
+    /// ```
+    fn with_query_string_options(self, options: QueryStringOptions) -> Self;
+
     /// Adds additional `RouteMatcher` requirements to the current route.
     ///
     /// ```
@@ -358,53 +616,851 @@ pub trait DefineSingleRoute {
         NRM: RouteMatcher + Send + Sync + 'static,
         Self: ExtendRouteMatcher<NRM>,
         Self::Output: DefineSingleRoute;
-}
 
-impl<'a, M, C, P, PE, QSE> DefineSingleRoute for SingleRouteBuilder<'a, M, C, P, PE, QSE>
-where
-    M: RouteMatcher + Send + Sync + 'static,
-    C: PipelineHandleChain<P> + Send + Sync + 'static,
-    P: RefUnwindSafe + Send + Sync + 'static,
-    PE: PathExtractor<Body> + Send + Sync + 'static,
-    QSE: QueryStringExtractor<Body> + Send + Sync + 'static,
-{
-    fn to<H>(self, handler: H)
+    /// Restricts the route to requests which accept one of `media_types`, by adding an
+    /// `AcceptHeaderRouteMatcher`. Requests which don't are offered to the other routes for the
+    /// request path, and receive `406 Not Acceptable` when no route matches.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use hyper::header::ACCEPT;
+    /// # use gotham::helpers::http::response::{create_html_response, create_response};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn html(state: State) -> (State, Response<Body>) {
+    ///     let res = create_html_response(&state, StatusCode::OK, "<p>Hello</p>");
+    ///     (state, res)
+    /// }
+    ///
+    /// fn json(state: State) -> (State, Response<Body>) {
+    ///     let body = (b"{\"hello\":true}".to_vec(), mime::APPLICATION_JSON);
+    ///     let res = create_response(&state, StatusCode::OK, Some(body));
+    ///     (state, res)
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.get("/hello").with_accept(vec![mime::TEXT_HTML]).to(html);
+    ///         route.get("/hello").with_accept(vec![mime::APPLICATION_JSON]).to(json);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/hello")
+    /// #       .with_header(ACCEPT, "application/json".parse().unwrap())
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "{\"hello\":true}");
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/hello")
+    /// #       .with_header(ACCEPT, "image/png".parse().unwrap())
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+    /// # }
+    /// ```
+    fn with_accept(
+        self,
+        media_types: Vec<Mime>,
+    ) -> <Self as ExtendRouteMatcher<AcceptHeaderRouteMatcher>>::Output
     where
-        H: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+        Self: ExtendRouteMatcher<AcceptHeaderRouteMatcher> + Sized,
+        <Self as ExtendRouteMatcher<AcceptHeaderRouteMatcher>>::Output: DefineSingleRoute,
     {
-        self.to_new_handler(move || Ok(handler))
+        self.add_route_matcher(AcceptHeaderRouteMatcher::new(media_types))
     }
 
-    fn to_new_handler<NH>(self, new_handler: NH)
+    /// Restricts the route to requests with a body of one of `media_types`, by adding a
+    /// `ContentTypeHeaderRouteMatcher`. Requests which don't match are offered to the other routes
+    /// for the request path, and receive `415 Unsupported Media Type` when no route matches.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn json_upload(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::CREATED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn form_upload(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route
+    ///             .post("/upload")
+    ///             .with_content_type(vec![mime::APPLICATION_JSON])
+    ///             .to(json_upload);
+    ///         route
+    ///             .post("/upload")
+    ///             .with_content_type(vec![mime::MULTIPART_FORM_DATA])
+    ///             .to(form_upload);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let multipart = "multipart/form-data; boundary=X".parse().unwrap();
+    /// #   let response = test_server.client()
+    /// #       .post("https://example.com/upload", "", multipart)
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .post("https://example.com/upload", "text", mime::TEXT_PLAIN)
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    /// # }
+    /// ```
+    fn with_content_type(
+        self,
+        media_types: Vec<Mime>,
+    ) -> <Self as ExtendRouteMatcher<ContentTypeHeaderRouteMatcher>>::Output
     where
-        NH: NewHandler + 'static,
+        Self: ExtendRouteMatcher<ContentTypeHeaderRouteMatcher> + Sized,
+        <Self as ExtendRouteMatcher<ContentTypeHeaderRouteMatcher>>::Output: DefineSingleRoute,
     {
-        let dispatcher = DispatcherImpl::new(new_handler, self.pipeline_chain, self.pipelines);
-        let route: RouteImpl<M, PE, QSE> = RouteImpl::new(
-            self.matcher,
-            Box::new(dispatcher),
-            Extractors::new(),
-            Delegation::Internal,
-        );
-        self.node_builder.add_route(Box::new(route));
+        self.add_route_matcher(ContentTypeHeaderRouteMatcher::new(media_types))
     }
 
-    fn with_path_extractor<NPE>(self) -> <Self as ReplacePathExtractor<NPE>>::Output
+    /// Restricts the route to requests for hosts which match `pattern`, by adding a
+    /// `HostRouteMatcher`. The pattern is an exact host, or a leading wildcard such as
+    /// `*.example.com`. Requests for other hosts are offered to the other routes for the request
+    /// path, so the route for a host should be defined before any host-agnostic route for the same
+    /// path.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use hyper::header::HOST;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn api_index(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn site_index(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::OK).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.get("/").with_host("api.example.com").to(api_index);
+    ///         route.get("/").to(site_index);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("http://localhost/")
+    /// #       .with_header(HOST, "api.example.com".parse().unwrap())
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("http://localhost/")
+    /// #       .with_header(HOST, "www.example.com".parse().unwrap())
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// # }
+    /// ```
+    fn with_host(self, pattern: &str) -> <Self as ExtendRouteMatcher<HostRouteMatcher>>::Output
     where
-        NPE: PathExtractor<Body> + Send + Sync + 'static,
+        Self: ExtendRouteMatcher<HostRouteMatcher> + Sized,
+        <Self as ExtendRouteMatcher<HostRouteMatcher>>::Output: DefineSingleRoute,
     {
-        self.replace_path_extractor()
+        self.add_route_matcher(HostRouteMatcher::new(pattern))
     }
 
-    fn with_query_string_extractor<NQSE>(
+    /// Restricts the route to requests whose query string contains `key`, with any value, by
+    /// adding a `QueryStringRouteMatcher`. Requests which don't match are offered to the other
+    /// routes for the request path, so this route should be defined before any route for the same
+    /// path without a query string condition.
+    ///
+    /// See `with_query_param` for an example.
+    fn with_query_key(
         self,
-    ) -> <Self as ReplaceQueryStringExtractor<NQSE>>::Output
+        key: &str,
+    ) -> <Self as ExtendRouteMatcher<QueryStringRouteMatcher>>::Output
+    where
+        Self: ExtendRouteMatcher<QueryStringRouteMatcher> + Sized,
+        <Self as ExtendRouteMatcher<QueryStringRouteMatcher>>::Output: DefineSingleRoute,
+    {
+        self.add_route_matcher(QueryStringRouteMatcher::with_key(key))
+    }
+
+    /// Restricts the route to requests whose query string provides `value` for `key`, by adding a
+    /// `QueryStringRouteMatcher`. Requests which don't match are offered to the other routes for
+    /// the request path. A predicate over the `QueryParams` can be used instead via
+    /// `add_route_matcher(QueryStringRouteMatcher::with_predicate(..))`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn ping(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::NO_CONTENT).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn event(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route
+    ///             .post("/webhook")
+    ///             .with_query_param("type", "ping")
+    ///             .to(ping);
+    ///         route.post("/webhook").with_query_key("type").to(event);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .post("https://example.com/webhook?type=ping", "", mime::APPLICATION_JSON)
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .post("https://example.com/webhook?type=push", "", mime::APPLICATION_JSON)
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .post("https://example.com/webhook", "", mime::APPLICATION_JSON)
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    /// # }
+    /// ```
+    fn with_query_param(
+        self,
+        key: &str,
+        value: &str,
+    ) -> <Self as ExtendRouteMatcher<QueryStringRouteMatcher>>::Output
+    where
+        Self: ExtendRouteMatcher<QueryStringRouteMatcher> + Sized,
+        <Self as ExtendRouteMatcher<QueryStringRouteMatcher>>::Output: DefineSingleRoute,
+    {
+        self.add_route_matcher(QueryStringRouteMatcher::with_value(key, value))
+    }
+
+    /// Constrains the dynamic segment `:name` of the route path to values which match `regex`.
+    /// This is equivalent to writing the segment as `:name:regex` in the path.
+    ///
+    /// Constraints are evaluated while the `Router` walks the tree of routes, before any
+    /// `PathExtractor` runs. A request path segment which doesn't match is not an error: the
+    /// `Router` moves on to other routes which may match the segment, in the order given by
+    /// `Node::match_node`. Static segments are always tried first, then constrained segments
+    /// (regex, then typed), then unconstrained dynamic segments, then globs. When no route
+    /// matches, the response is `404 Not Found`.
+    ///
+    /// The regex must match the entire segment, and is evaluated against the percent-decoded
+    /// segment value.
+    ///
+    /// # Panics
+    ///
+    /// If the route path has no dynamic segment `:name`, or if `regex` is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn show_order(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn export_orders(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::OK).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route
+    ///             .get("/orders/:id")
+    ///             .with_segment_regex("id", r"\d+")
+    ///             .to(show_order);
+    ///
+    ///         route.get("/orders/export").to(export_orders);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let status = |path: &str| {
+    /// #       test_server.client()
+    /// #           .get(&format!("https://example.com{}", path))
+    /// #           .perform()
+    /// #           .unwrap()
+    /// #           .status()
+    /// #   };
+    /// #   assert_eq!(status("/orders/42"), StatusCode::ACCEPTED);
+    /// #   assert_eq!(status("/orders/export"), StatusCode::OK);
+    /// #   assert_eq!(status("/orders/pending"), StatusCode::NOT_FOUND);
+    /// # }
+    /// ```
+    fn with_segment_regex(self, name: &str, regex: &str) -> Self;
+
+    /// Constrains the dynamic segment `:name` of the route path to values which can be parsed as
+    /// `T`, via `FromStr`. The constraint is evaluated in the same way as those added by
+    /// `with_segment_regex`, so a segment which can't be parsed falls through to other routes
+    /// rather than failing the `PathExtractor` with `400 Bad Request`.
+    ///
+    /// # Panics
+    ///
+    /// If the route path has no dynamic segment `:name`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn show_order(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route
+    ///             .get("/orders/:id")
+    ///             .with_segment_type::<u64>("id")
+    ///             .to(show_order);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let status = |path: &str| {
+    /// #       test_server.client()
+    /// #           .get(&format!("https://example.com{}", path))
+    /// #           .perform()
+    /// #           .unwrap()
+    /// #           .status()
+    /// #   };
+    /// #   assert_eq!(status("/orders/42"), StatusCode::ACCEPTED);
+    /// #   assert_eq!(status("/orders/-1"), StatusCode::NOT_FOUND);
+    /// # }
+    /// ```
+    fn with_segment_type<T>(self, name: &str) -> Self
+    where
+        T: FromStr + 'static;
+
+    /// Prevents the `Router` from dispatching `HEAD` requests to this route when they match no
+    /// other route, as it does by default for routes which match `GET` requests (see
+    /// `RouterBuilder::set_automatic_head`). This is useful when the `Handler` has side effects,
+    /// or is expensive to run only for the headers.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn export(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.get("/export").without_automatic_head().to(export);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .head("https://example.com/export")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    /// # }
+    /// ```
+    fn without_automatic_head(self) -> Self;
+
+    /// Overrides the precedence of this route over other routes which match the same request.
+    /// The default priority is `0`, and routes with a higher priority are tried first.
+    ///
+    /// Without a priority, the `Router` matches a request path against the path segments of each
+    /// route in a fixed order of precedence:
+    ///
+    /// 1. Static segments (`/users/me`);
+    /// 2. Dynamic segments constrained by a regex (`/users/:id:[0-9]+`);
+    /// 3. Dynamic segments constrained by a type (`with_segment_type`);
+    /// 4. Dynamic segments (`/users/:name`);
+    /// 5. Globs (`/files/*path`), then optional globs (`/files/**path`).
+    ///
+    /// Segments of the same kind are tried in the order their routes were defined, and so are
+    /// routes for the same path, such as routes restricted by different `RouteMatcher` values.
+    ///
+    /// A route with a higher priority is tried before routes of a lower priority for the same
+    /// path, and each of its segments is tried before the sibling segments which only lead to
+    /// routes of a lower priority, whatever their kind. This doesn't apply across routers
+    /// added with `DrawRoutes::delegate`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn archive(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn page(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::OK).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.get("/:page").to(page);
+    ///
+    ///         // Matched before `/:page`, although a glob is less specific.
+    ///         route.get("/*archive").with_priority(1).to(archive);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/about")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// # }
+    /// ```
+    fn with_priority(self, priority: i32) -> Self;
+
+    /// Adds a `ResponseExtender` for requests dispatched to this route, which is used in
+    /// preference to those of any scope containing the route, and those added with
+    /// `RouterBuilder::add_response_extender`. See `ScopeBuilder::add_response_extender` for the
+    /// order in which extenders are resolved.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::response::create_response;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn login(state: State) -> (State, Response<Body>) {
+    ///     let response = create_response(&state, StatusCode::UNAUTHORIZED, None);
+    ///     (state, response)
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route
+    ///             .post("/login")
+    ///             .add_response_extender(
+    ///                 StatusCode::UNAUTHORIZED,
+    ///                 |_state: &mut State, res: &mut Response<Body>| {
+    ///                     *res.body_mut() = "Invalid username or password".into();
+    ///                 },
+    ///             )
+    ///             .to(login);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .post("https://example.com/login", "", mime::TEXT_PLAIN)
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "Invalid username or password");
+    /// # }
+    /// ```
+    fn add_response_extender<S, E>(self, statuses: S, extender: E) -> Self
+    where
+        S: Into<StatusMatcher>,
+        E: ResponseExtender<Body> + Send + Sync + 'static;
+
+    /// Attaches a static value to this route, which is put into `State` before any `Pipeline` is
+    /// invoked for a request dispatched here. This allows `Middleware` and the `Handler` to read
+    /// per-route metadata, such as a required permission, via `FromState`. Routes without a given
+    /// extension do not populate it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// #[derive(Clone, StateData)]
+    /// struct OperationId(&'static str);
+    ///
+    /// fn my_handler(state: State) -> (State, Response<Body>) {
+    ///     let operation = OperationId::borrow_from(&state).0;
+    ///
+    ///     // Handler implementation elided.
+    /// #   assert_eq!(operation, "listUsers");
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route
+    ///             .get("/users")
+    ///             .with_extension(OperationId("listUsers"))
+    ///             .to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/users")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// # }
+    /// ```
+    fn with_extension<T>(self, extension: T) -> Self
+    where
+        T: StateData + Clone + Sync + RefUnwindSafe;
+
+    /// Enforces a deadline on the `Handler` for this route. When the `Handler` does not complete
+    /// within `timeout`, its future is dropped and the client receives a `503 Service
+    /// Unavailable` response (see `TimeoutRouteBuilder::with_timeout_status` to change it).
+    ///
+    /// This must be the final modification to the route before it is directed to a `Handler`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use std::time::Duration;
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn report(state: State) -> (State, Response<Body>) {
+    ///     // Handler implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route
+    ///             .get("/report")
+    ///             .with_timeout(Duration::from_secs(30))
+    ///             .with_timeout_status(StatusCode::GATEWAY_TIMEOUT)
+    ///             .to(report);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/report")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// # }
+    /// ```
+    fn with_timeout(self, timeout: Duration) -> TimeoutRouteBuilder<Self>
+    where
+        Self: Sized,
+    {
+        TimeoutRouteBuilder::new(self, timeout)
+    }
+}
+
+impl<'a, M, C, P, PE, QSE> DefineSingleRoute for SingleRouteBuilder<'a, M, C, P, PE, QSE>
+where
+    M: RouteMatcher + Send + Sync + 'static,
+    C: PipelineHandleChain<P> + Send + Sync + 'static,
+    P: RefUnwindSafe + Send + Sync + 'static,
+    PE: PathExtractor<Body> + Send + Sync + 'static,
+    QSE: QueryStringExtractor<Body> + Send + Sync + 'static,
+{
+    fn to<H>(self, handler: H)
+    where
+        H: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+    {
+        self.to_new_handler(move || Ok(handler))
+    }
+
+    fn to_new_handler<NH>(self, new_handler: NH)
+    where
+        NH: NewHandler + 'static,
+    {
+        // Only routes which are restricted solely by method can be compared for duplicates, as
+        // other matchers are opaque.
+        let methods = {
+            let matcher: &Any = &self.matcher;
+            matcher
+                .downcast_ref::<MethodOnlyRouteMatcher>()
+                .map(|matcher| matcher.methods().to_vec())
+        };
+
+        let mut summary = MatcherSummary::new();
+        self.matcher.summarize(&mut summary);
+        let record = RouteRecord::Route {
+            summary,
+            priority: self.priority,
+            fallback: self.fallback,
+        };
+
+        let dispatcher = DispatcherImpl::new(new_handler, self.pipeline_chain, self.pipelines)
+            .with_extensions(self.extensions);
+        let mut route: RouteImpl<M, PE, QSE> = RouteImpl::new(
+            self.matcher,
+            Box::new(dispatcher),
+            Extractors::new().with_query_string_options(self.query_string_options),
+            Delegation::Internal,
+        )
+        .with_automatic_head(self.automatic_head)
+        .with_priority(self.priority);
+
+        if !self.response_finalizer_builder.is_empty() {
+            route = route.with_response_finalizer(self.response_finalizer_builder.finalize());
+        }
+
+        if self.fallback {
+            self.node_builder.record_route(record);
+            self.node_builder
+                .add_fallback(Box::new(route), self.not_found);
+            return;
+        }
+
+        let node =
+            descend_with_constraints(self.node_builder, &self.path, &self.segment_constraints);
+        if let Some(methods) = methods {
+            node.add_method_route(&methods, self.priority);
+        }
+        if has_trailing_slash(&self.path) {
+            node.set_trailing_slash();
+        }
+        node.record_route(record);
+        node.add_route(Box::new(route));
+    }
+
+    fn to_dir_with(self, options: DirOptions) {
+        let anonymous_glob = match self.path.trim_right_matches('/').rsplit('/').next() {
+            Some("*") | Some("**") => true,
+            _ => false,
+        };
+
+        if self.fallback || !anonymous_glob {
+            report(RouterBuildError::InvalidPattern {
+                segment: self.path.trim_matches('/').split('/').count() - 1,
+                message: format!(
+                    "route path `{}` directed to a directory must end with `*` or `**`",
+                    self.path
+                ),
+                path: self.path,
+            });
+            return;
+        }
+
+        self.with_path_extractor::<DirPath>()
+            .to_new_handler(DirHandler::new(options));
+    }
+
+    fn redirect_to_with(self, options: RedirectOptions) {
+        if let Some((segment, placeholder)) = options.missing_placeholder(&self.path) {
+            report(RouterBuildError::InvalidPattern {
+                path: options.target().to_owned(),
+                segment,
+                message: format!(
+                    "redirect target `{}` uses `{}`, which isn't a dynamic segment of route \
+                     path `{}`",
+                    options.target(),
+                    placeholder,
+                    self.path
+                ),
+            });
+            return;
+        }
+
+        self.with_path_extractor::<RedirectParams>()
+            .to_new_handler(RedirectHandler::new(options));
+    }
+
+    fn with_priority(self, priority: i32) -> Self {
+        SingleRouteBuilder { priority, ..self }
+    }
+
+    fn without_automatic_head(self) -> Self {
+        SingleRouteBuilder {
+            automatic_head: false,
+            ..self
+        }
+    }
+
+    fn with_segment_regex(self, name: &str, regex: &str) -> Self {
+        match ConstrainedSegmentRegex::try_new(regex) {
+            Ok(regex) => self.constrain_segment(name, SegmentType::Constrained { regex }),
+            Err(e) => {
+                report(RouterBuildError::InvalidMatcher {
+                    message: format!(
+                        "invalid regex for segment `:{}` in route path `{}`: {}",
+                        name, self.path, e
+                    ),
+                    path: self.path.clone(),
+                });
+                self
+            }
+        }
+    }
+
+    fn with_segment_type<T>(self, name: &str) -> Self
+    where
+        T: FromStr + 'static,
+    {
+        let parser = TypedSegmentParser::new::<T>();
+        self.constrain_segment(name, SegmentType::Typed { parser })
+    }
+
+    fn with_extension<T>(mut self, extension: T) -> Self
+    where
+        T: StateData + Clone + Sync + RefUnwindSafe,
+    {
+        self.extensions.insert(extension);
+        self
+    }
+
+    fn add_response_extender<S, E>(mut self, statuses: S, extender: E) -> Self
+    where
+        S: Into<StatusMatcher>,
+        E: ResponseExtender<Body> + Send + Sync + 'static,
+    {
+        self.response_finalizer_builder
+            .add_matching(statuses.into(), Box::new(extender));
+        self
+    }
+
+    fn with_path_extractor<NPE>(self) -> <Self as ReplacePathExtractor<NPE>>::Output
+    where
+        NPE: PathExtractor<Body> + Send + Sync + 'static,
+    {
+        self.replace_path_extractor()
+    }
+
+    fn with_query_string_extractor<NQSE>(
+        self,
+    ) -> <Self as ReplaceQueryStringExtractor<NQSE>>::Output
     where
         NQSE: QueryStringExtractor<Body> + Send + Sync + 'static,
     {
         self.replace_query_string_extractor()
     }
 
+    fn with_query_string_options(self, query_string_options: QueryStringOptions) -> Self {
+        SingleRouteBuilder {
+            query_string_options,
+            ..self
+        }
+    }
+
     fn add_route_matcher<NRM>(self, matcher: NRM) -> <Self as ExtendRouteMatcher<NRM>>::Output
     where
         NRM: RouteMatcher + Send + Sync + 'static,
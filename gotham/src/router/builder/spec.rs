@@ -0,0 +1,605 @@
+//! Defines `RouterSpec`, a description of routes which can be deserialized from configuration,
+//! and `build_router_from_spec`, which builds a `Router` from it.
+
+use std::collections::HashMap;
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::{Method, StatusCode};
+
+use error::Result;
+use handler::redirect::{RedirectKind, RedirectOptions};
+use handler::{Handler, HandlerFuture, NewHandler};
+use pipeline::chain::PipelineHandleChain;
+use pipeline::set::PipelineSet;
+use router::builder::error::report;
+use router::builder::{
+    build_router_result, DefineSingleRoute, DrawRoutes, RouterBuildError, RouterBuilder,
+};
+use router::Router;
+use state::{State, StateData};
+
+/// A description of the routes of a `Router`, usually deserialized from a configuration file.
+/// `build_router_from_spec` builds the `Router`, finding the handler for each route by name in a
+/// `HandlerRegistry`.
+///
+/// In JSON, a `RouterSpec` looks like this:
+///
+/// ```json
+/// {
+///   "routes": [
+///     { "path": "/users/:id", "methods": ["GET", "HEAD"], "handler": "users.show" },
+///     { "path": "/reports/*", "handler": "reports", "timeout_ms": 5000, "timeout_status": 504 },
+///     { "path": "/admin", "handler": "admin", "extensions": { "permission": "admin" } },
+///     {
+///       "path": "/old-blog/:slug",
+///       "redirect": { "target": "/blog/:slug", "kind": "permanent" }
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouterSpec {
+    /// The routes, in the order they're added to the `Router`.
+    pub routes: Vec<RouteSpec>,
+}
+
+/// A description of a single route within a `RouterSpec`. Each route is directed either to a
+/// named handler or to a redirect.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteSpec {
+    /// The route path, in the form accepted by `DrawRoutes`, e.g. `/users/:id` or `/files/*`.
+    pub path: String,
+
+    /// The methods matched by the route, e.g. `GET`. Defaults to `GET` alone.
+    #[serde(default = "default_methods")]
+    pub methods: Vec<String>,
+
+    /// The name of the handler in the `HandlerRegistry`, for a route which isn't a redirect.
+    #[serde(default)]
+    pub handler: Option<String>,
+
+    /// Where the route redirects to, for a route which isn't directed to a handler.
+    #[serde(default)]
+    pub redirect: Option<RedirectSpec>,
+
+    /// A deadline for the handler, in milliseconds. See `DefineSingleRoute::with_timeout`.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+
+    /// The status of the response sent when the deadline passes, rather than `503 Service
+    /// Unavailable`.
+    #[serde(default)]
+    pub timeout_status: Option<u16>,
+
+    /// Values attached to the route, which are available to `Middleware` and the handler as
+    /// `RouteSpecExtensions` in `State`.
+    #[serde(default)]
+    pub extensions: HashMap<String, String>,
+}
+
+/// Where a route within a `RouterSpec` redirects to. See `RedirectOptions` for the placeholders
+/// which can be used in the target.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedirectSpec {
+    /// The location which requests are redirected to, e.g. `/blog/:slug`.
+    pub target: String,
+
+    /// The kind of redirect, e.g. `permanent` or `found`.
+    pub kind: RedirectKind,
+
+    /// Whether the query string of the request is appended to the location. Defaults to `true`.
+    #[serde(default = "default_query_string")]
+    pub query_string: bool,
+}
+
+fn default_methods() -> Vec<String> {
+    vec!["GET".to_owned()]
+}
+
+fn default_query_string() -> bool {
+    true
+}
+
+/// The `extensions` of a `RouteSpec`, which are put into `State` when the route is dispatched.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RouteSpecExtensions {
+    values: HashMap<String, String>,
+}
+
+impl RouteSpecExtensions {
+    /// The value of the extension with the given name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+}
+
+impl StateData for RouteSpecExtensions {}
+
+/// The handlers available to the routes of a `RouterSpec`, by name.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response};
+/// # use gotham::router::builder::HandlerRegistry;
+/// # use gotham::state::State;
+/// #
+/// fn show_user(state: State) -> (State, Response<Body>) {
+///     // Handler implementation elided.
+/// #   (state, Response::new(Body::empty()))
+/// }
+///
+/// # fn main() {
+/// let registry = HandlerRegistry::new().with_handler("users.show", || Ok(show_user));
+/// # assert!(registry.contains("users.show"));
+/// # }
+/// ```
+#[derive(Default)]
+pub struct HandlerRegistry {
+    handlers: HashMap<String, BoxedNewHandler>,
+}
+
+impl HandlerRegistry {
+    /// Creates an empty `HandlerRegistry`.
+    pub fn new() -> HandlerRegistry {
+        HandlerRegistry::default()
+    }
+
+    /// Adds a handler with the given name, replacing any handler which already has the name.
+    pub fn register<NH>(&mut self, name: &str, new_handler: NH)
+    where
+        NH: NewHandler + 'static,
+    {
+        let new_handler = BoxedNewHandler {
+            inner: Arc::new(new_handler),
+        };
+        self.handlers.insert(name.to_owned(), new_handler);
+    }
+
+    /// Adds a handler with the given name, as `register` does, and returns the registry.
+    pub fn with_handler<NH>(mut self, name: &str, new_handler: NH) -> HandlerRegistry
+    where
+        NH: NewHandler + 'static,
+    {
+        self.register(name, new_handler);
+        self
+    }
+
+    /// Determines if a handler with the given name has been added.
+    pub fn contains(&self, name: &str) -> bool {
+        self.handlers.contains_key(name)
+    }
+}
+
+/// Builds a `Router` from the routes described by a `RouterSpec`, directing each route to the
+/// handler of the same name in `registry`. The routes are defined with the builder, as
+/// `build_router_result` does, so an invalid route path is reported in the same way, as is a
+/// route directed to a handler which isn't in `registry`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::collections::HashMap;
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::pipeline::set::{finalize_pipeline_set, new_pipeline_set};
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// # fn show_user(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+/// # }
+/// #
+/// # fn main() {
+/// // Usually deserialized from a configuration file.
+/// let spec = RouterSpec {
+///     routes: vec![RouteSpec {
+///         path: "/users/:id".to_owned(),
+///         methods: vec!["GET".to_owned()],
+///         handler: Some("users.show".to_owned()),
+///         redirect: None,
+///         timeout_ms: None,
+///         timeout_status: None,
+///         extensions: HashMap::new(),
+///     }],
+/// };
+///
+/// let registry = HandlerRegistry::new().with_handler("users.show", || Ok(show_user));
+/// let pipelines = finalize_pipeline_set(new_pipeline_set());
+/// let router = build_router_from_spec((), pipelines, &spec, &registry).unwrap();
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/users/42")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::ACCEPTED);
+///
+/// let registry = HandlerRegistry::new();
+/// let pipelines = finalize_pipeline_set(new_pipeline_set());
+/// match build_router_from_spec((), pipelines, &spec, &registry) {
+///     Err(RouterBuildError::UnknownHandler { route, handler }) => {
+///         assert_eq!(route, "GET /users/:id");
+///         assert_eq!(handler, "users.show");
+///     }
+///     _ => panic!("expected an unknown handler"),
+/// }
+/// # }
+/// ```
+pub fn build_router_from_spec<C, P>(
+    pipeline_chain: C,
+    pipelines: PipelineSet<P>,
+    spec: &RouterSpec,
+    registry: &HandlerRegistry,
+) -> ::std::result::Result<Router, RouterBuildError>
+where
+    C: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
+    P: RefUnwindSafe + Send + Sync + 'static,
+{
+    build_router_result(pipeline_chain, pipelines, |route| {
+        for route_spec in &spec.routes {
+            draw_route(route, route_spec, registry);
+        }
+    })
+}
+
+/// Defines the route described by `spec`, reporting any problem with it.
+fn draw_route<C, P>(route: &mut RouterBuilder<C, P>, spec: &RouteSpec, registry: &HandlerRegistry)
+where
+    C: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
+    P: RefUnwindSafe + Send + Sync + 'static,
+{
+    let invalid = |message: String| {
+        report(RouterBuildError::InvalidMatcher {
+            path: spec.path.clone(),
+            message,
+        })
+    };
+
+    let mut methods = Vec::new();
+    for method in &spec.methods {
+        match Method::from_bytes(method.to_uppercase().as_bytes()) {
+            Ok(method) => methods.push(method),
+            Err(_) => {
+                return invalid(format!(
+                    "invalid method `{}` for route path `{}`",
+                    method, spec.path
+                ))
+            }
+        }
+    }
+
+    if methods.is_empty() {
+        return invalid(format!("no methods for route path `{}`", spec.path));
+    }
+
+    let description = format!(
+        "{} {}",
+        methods
+            .iter()
+            .map(Method::as_str)
+            .collect::<Vec<_>>()
+            .join(", "),
+        spec.path
+    );
+
+    let timeout_status = match spec.timeout_status.map(StatusCode::from_u16) {
+        Some(Ok(status)) => status,
+        Some(Err(_)) => {
+            return invalid(format!(
+                "invalid timeout status for route `{}`",
+                description
+            ))
+        }
+        None => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    let builder = route
+        .request(methods, &spec.path)
+        .with_extension(RouteSpecExtensions {
+            values: spec.extensions.clone(),
+        });
+
+    match (&spec.handler, &spec.redirect) {
+        (&Some(ref name), &None) => {
+            let new_handler = match registry.handlers.get(name) {
+                Some(new_handler) => new_handler.clone(),
+                None => {
+                    return report(RouterBuildError::UnknownHandler {
+                        route: description,
+                        handler: name.clone(),
+                    })
+                }
+            };
+
+            match spec.timeout_ms {
+                Some(timeout) => builder
+                    .with_timeout(Duration::from_millis(timeout))
+                    .with_timeout_status(timeout_status)
+                    .to_new_handler(new_handler),
+                None => builder.to_new_handler(new_handler),
+            }
+        }
+        (&None, &Some(ref redirect)) => {
+            if spec.timeout_ms.is_some() {
+                return invalid(format!(
+                    "redirect route `{}` can't have a timeout",
+                    description
+                ));
+            }
+
+            let options = RedirectOptions::new(redirect.target.clone(), redirect.kind)
+                .with_query_string(redirect.query_string);
+            builder.redirect_to_with(options);
+        }
+        _ => invalid(format!(
+            "route `{}` must have either a handler or a redirect",
+            description
+        )),
+    }
+}
+
+/// A `NewHandler` from a `HandlerRegistry`, which creates `BoxedHandler` values so that handlers
+/// of different types can be stored together.
+#[derive(Clone)]
+struct BoxedNewHandler {
+    inner: Arc<NewBoxedHandler>,
+}
+
+impl NewHandler for BoxedNewHandler {
+    type Instance = BoxedHandler;
+
+    fn new_handler(&self) -> Result<BoxedHandler> {
+        self.inner.new_boxed_handler()
+    }
+}
+
+/// The object safe form of `NewHandler`, as used by `BoxedNewHandler`.
+trait NewBoxedHandler: Send + Sync + RefUnwindSafe {
+    fn new_boxed_handler(&self) -> Result<BoxedHandler>;
+}
+
+impl<NH> NewBoxedHandler for NH
+where
+    NH: NewHandler + 'static,
+{
+    fn new_boxed_handler(&self) -> Result<BoxedHandler> {
+        let handler = self.new_handler()?;
+        Ok(BoxedHandler {
+            inner: Box::new(handler),
+        })
+    }
+}
+
+/// A `Handler` created by a `BoxedNewHandler`.
+struct BoxedHandler {
+    inner: Box<HandleBoxed>,
+}
+
+impl Handler for BoxedHandler {
+    fn handle(self, state: State) -> Box<HandlerFuture> {
+        self.inner.handle_boxed(state)
+    }
+}
+
+/// The object safe form of `Handler`, as used by `BoxedHandler`.
+trait HandleBoxed: Send {
+    fn handle_boxed(self: Box<Self>, state: State) -> Box<HandlerFuture>;
+}
+
+impl<H> HandleBoxed for H
+where
+    H: Handler,
+{
+    fn handle_boxed(self: Box<Self>, state: State) -> Box<HandlerFuture> {
+        (*self).handle(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::future;
+    use hyper::header::LOCATION;
+    use hyper::{Body, Response};
+
+    use helpers::http::response::create_text_response;
+    use pipeline::set::{finalize_pipeline_set, new_pipeline_set};
+    use state::FromState;
+    use test::TestServer;
+
+    fn route(path: &str, handler: &str) -> RouteSpec {
+        RouteSpec {
+            path: path.to_owned(),
+            methods: default_methods(),
+            handler: Some(handler.to_owned()),
+            redirect: None,
+            timeout_ms: None,
+            timeout_status: None,
+            extensions: HashMap::new(),
+        }
+    }
+
+    fn build(routes: Vec<RouteSpec>) -> ::std::result::Result<Router, RouterBuildError> {
+        let registry = HandlerRegistry::new().with_handler("ok", || Ok(ok));
+        let pipelines = finalize_pipeline_set(new_pipeline_set());
+        build_router_from_spec((), pipelines, &RouterSpec { routes }, &registry)
+    }
+
+    fn ok(state: State) -> (State, Response<Body>) {
+        let res = create_text_response(&state, StatusCode::OK, "ok");
+        (state, res)
+    }
+
+    #[test]
+    fn reports_invalid_routes() {
+        match build(vec![
+            route("/users/:id", "ok"),
+            route("/users/:id", "missing"),
+        ]) {
+            Err(RouterBuildError::UnknownHandler { route, handler }) => {
+                assert_eq!(route, "GET /users/:id");
+                assert_eq!(handler, "missing");
+            }
+            _ => panic!("expected an unknown handler"),
+        }
+
+        match build(vec![route("/files/*/raw", "ok")]) {
+            Err(RouterBuildError::InvalidPattern { path, segment, .. }) => {
+                assert_eq!(path, "/files/*/raw");
+                assert_eq!(segment, 1);
+            }
+            _ => panic!("expected an invalid pattern"),
+        }
+
+        let invalid = |spec: RouteSpec| match build(vec![spec]) {
+            Err(RouterBuildError::InvalidMatcher { message, .. }) => message,
+            _ => panic!("expected an invalid route"),
+        };
+
+        let mut spec = route("/users", "ok");
+        spec.methods = vec!["G E T".to_owned()];
+        assert_eq!(
+            invalid(spec),
+            "invalid method `G E T` for route path `/users`"
+        );
+
+        let mut spec = route("/users", "ok");
+        spec.redirect = Some(RedirectSpec {
+            target: "/people".to_owned(),
+            kind: RedirectKind::Permanent,
+            query_string: true,
+        });
+        assert_eq!(
+            invalid(spec),
+            "route `GET /users` must have either a handler or a redirect"
+        );
+
+        let mut spec = route("/users", "ok");
+        spec.timeout_status = Some(1000);
+        assert_eq!(
+            invalid(spec),
+            "invalid timeout status for route `GET /users`"
+        );
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn builds_router_from_fixture() {
+        use serde_json;
+
+        const FIXTURE: &'static str = include_str!("fixtures/routes.json");
+
+        fn show_user(state: State) -> (State, Response<Body>) {
+            let body = {
+                let uri = ::hyper::Uri::borrow_from(&state);
+                format!("user {}", uri.path().rsplit('/').next().unwrap())
+            };
+            let res = create_text_response(&state, StatusCode::OK, body);
+            (state, res)
+        }
+
+        fn create_user(state: State) -> (State, Response<Body>) {
+            let res = create_text_response(&state, StatusCode::CREATED, "created");
+            (state, res)
+        }
+
+        fn file(state: State) -> (State, Response<Body>) {
+            let body = ::hyper::Uri::borrow_from(&state).path().to_owned();
+            let res = create_text_response(&state, StatusCode::OK, body);
+            (state, res)
+        }
+
+        fn admin(state: State) -> (State, Response<Body>) {
+            let permission = RouteSpecExtensions::borrow_from(&state)
+                .get("permission")
+                .unwrap_or("none")
+                .to_owned();
+            let res = create_text_response(&state, StatusCode::OK, permission);
+            (state, res)
+        }
+
+        fn pending(_state: State) -> Box<HandlerFuture> {
+            Box::new(future::empty())
+        }
+
+        let spec: RouterSpec = serde_json::from_str(FIXTURE).unwrap();
+        let registry = HandlerRegistry::new()
+            .with_handler("users.show", || Ok(show_user))
+            .with_handler("users.create", || Ok(create_user))
+            .with_handler("files", || Ok(file))
+            .with_handler("admin", || Ok(admin))
+            .with_handler("reports", || Ok(pending));
+
+        let pipelines = finalize_pipeline_set(new_pipeline_set());
+        let router = build_router_from_spec((), pipelines, &spec, &registry).unwrap();
+        let test_server = TestServer::new(router).unwrap();
+
+        let get = |path: &str| {
+            let response = test_server
+                .client()
+                .get(&format!("http://localhost{}", path))
+                .perform()
+                .unwrap();
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .map(|location| location.to_str().unwrap().to_owned());
+            (
+                response.status(),
+                location,
+                response.read_utf8_body().unwrap(),
+            )
+        };
+
+        assert_eq!(
+            get("/users/42"),
+            (StatusCode::OK, None, "user 42".to_owned())
+        );
+        assert_eq!(
+            get("/files/css/site.css"),
+            (StatusCode::OK, None, "/files/css/site.css".to_owned())
+        );
+        assert_eq!(get("/admin"), (StatusCode::OK, None, "admin".to_owned()));
+        assert_eq!(get("/reports/daily").0, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(
+            get("/old-blog/hello?page=2"),
+            (
+                StatusCode::PERMANENT_REDIRECT,
+                Some("/blog/hello?page=2".to_owned()),
+                String::new()
+            )
+        );
+        assert_eq!(
+            get("/search?q=gotham").1,
+            Some("https://search.example.com/".to_owned())
+        );
+
+        let response = test_server
+            .client()
+            .post("http://localhost/users", "", ::mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = test_server
+            .client()
+            .delete("http://localhost/users/42")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
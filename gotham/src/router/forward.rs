@@ -0,0 +1,450 @@
+//! Defines `forward_to`, which re-dispatches a request to the route of another path within the
+//! same application, without an HTTP redirect.
+
+use std::io;
+
+use futures::future;
+use hyper::Uri;
+
+use handler::{HandlerFuture, IntoHandlerError};
+use helpers::http::request::path::put_request_path_segments;
+use helpers::http::request::QueryParams;
+use router::Router;
+use state::{request_id, FromState, State, StateData};
+
+/// The number of times a single request may be forwarded by `forward_to`. A request which is
+/// forwarded again after this fails with `500 Internal Server Error`, preventing a loop of
+/// forwards from running indefinitely.
+pub const MAX_FORWARDS: usize = 8;
+
+/// Describes a request which has been forwarded by `forward_to`, available via
+/// `Forwarded::try_borrow_from(&state)`.
+#[derive(Clone, Debug)]
+pub struct Forwarded {
+    original_uri: Uri,
+    count: usize,
+}
+
+impl Forwarded {
+    /// The `Uri` of the request before it was first forwarded. The `Uri` in `State` is that of
+    /// the most recent forward.
+    pub fn original_uri(&self) -> &Uri {
+        &self.original_uri
+    }
+
+    /// The number of times the request has been forwarded.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl StateData for Forwarded {}
+
+/// The `Router` which `forward_to` dispatches requests with, stored by the outermost `Router`.
+pub(crate) struct ForwardTarget {
+    router: Router,
+}
+
+impl ForwardTarget {
+    pub(crate) fn new(router: Router) -> ForwardTarget {
+        ForwardTarget { router }
+    }
+}
+
+impl StateData for ForwardTarget {}
+
+/// Forwards the request to the route for `path`, such as `/users/42`, as though the request had
+/// been made for that path. This allows a handler to have a request processed by another route
+/// (e.g. to support a legacy URL) without sending the client a redirect.
+///
+/// The `Uri` in `State` is replaced, and the request is matched against the routes of the
+/// outermost `Router` of the application, so `path` is absolute even when the handler belongs to
+/// a `Router` which requests are delegated to. The query string is replaced when `path` has one,
+/// and kept otherwise. The new path is normalized according to the `PathOptions` of that
+/// `Router`, and the extractors and pipelines of the matched route then run as for any other
+/// request. However, pipelines which already ran for the original route (such as those given to
+/// `build_router`) are skipped, and the `Middleware` and `Handler` values wrapping the `Router`
+/// (such as those given to `gotham::start`) don't run again.
+///
+/// The response of the forwarded route is finalized once, by the `Router` which dispatched the
+/// original request, so the response extenders of the original route apply to it. The original
+/// `Uri` is available from `Forwarded`, which is placed into `State`. Since forwards may chain,
+/// a request can be forwarded at most `MAX_FORWARDS` times, after which `forward_to` fails with
+/// `500 Internal Server Error`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # #[macro_use]
+/// # extern crate gotham_derive;
+/// # extern crate hyper;
+/// # extern crate serde;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::handler::HandlerFuture;
+/// # use gotham::helpers::http::response::create_text_response;
+/// # use gotham::router::Router;
+/// # use gotham::router::builder::*;
+/// # use gotham::router::forward::forward_to;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// #[derive(Deserialize, StateData, StaticResponseExtender)]
+/// struct UserPath {
+///     id: u32,
+/// }
+///
+/// fn legacy_profile(mut state: State) -> Box<HandlerFuture> {
+///     let UserPath { id } = UserPath::take_from(&mut state);
+///     forward_to(state, &format!("/users/{}", id))
+/// }
+///
+/// fn show_user(state: State) -> (State, Response<Body>) {
+///     let id = UserPath::borrow_from(&state).id;
+///     let res = create_text_response(&state, StatusCode::OK, format!("user {}", id));
+///     (state, res)
+/// }
+///
+/// fn router() -> Router {
+///     build_simple_router(|route| {
+///         route
+///             .get("/profile/:id")
+///             .with_path_extractor::<UserPath>()
+///             .to(legacy_profile);
+///         route
+///             .get("/users/:id")
+///             .with_path_extractor::<UserPath>()
+///             .to(show_user);
+///     })
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server.client()
+/// #       .get("https://example.com/profile/42")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "user 42");
+/// # }
+/// ```
+pub fn forward_to(mut state: State, path: &str) -> Box<HandlerFuture> {
+    let router = match ForwardTarget::try_borrow_from(&state) {
+        Some(target) => target.router.clone(),
+        None => return forward_error(state, "request was not dispatched by a Router"),
+    };
+
+    let (original_uri, count) = match Forwarded::try_borrow_from(&state) {
+        Some(forwarded) => (forwarded.original_uri.clone(), forwarded.count),
+        None => (Uri::borrow_from(&state).clone(), 0),
+    };
+
+    if count >= MAX_FORWARDS {
+        error!(
+            "[{}] request forwarded more than {} times, from {}",
+            request_id(&state),
+            MAX_FORWARDS,
+            original_uri
+        );
+        return forward_error(state, "request forwarded too many times");
+    }
+
+    let uri = match target_uri(Uri::borrow_from(&state), path) {
+        Some(uri) => uri,
+        None => return forward_error(state, "invalid forward path"),
+    };
+
+    trace!("[{}] forwarding to {}", request_id(&state), uri);
+
+    state.put(Forwarded {
+        original_uri,
+        count: count + 1,
+    });
+    state.put(uri);
+    put_request_path_segments(&mut state);
+    // Any `QueryParams` stored for the original `Uri` are parsed again from the target.
+    state.try_take::<QueryParams>();
+
+    router.forward(state)
+}
+
+/// Creates the `Uri` of a forwarded request for `path`, keeping the query string of `current`
+/// when `path` has none.
+fn target_uri(current: &Uri, path: &str) -> Option<Uri> {
+    if !path.starts_with('/') {
+        return None;
+    }
+
+    let target = match current.query() {
+        Some(query) if !path.contains('?') => format!("{}?{}", path, query),
+        _ => path.to_owned(),
+    };
+
+    let mut parts = current.clone().into_parts();
+    parts.path_and_query = Some(target.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+fn forward_error(state: State, message: &str) -> Box<HandlerFuture> {
+    let err = io::Error::new(io::ErrorKind::Other, message.to_owned());
+    Box::new(future::err((state, err.into_handler_error())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use hyper::{Body, Response, StatusCode};
+
+    use helpers::http::request::path::EncodedSlashes;
+    use helpers::http::response::create_text_response;
+    use middleware::{Middleware, NewMiddleware};
+    use pipeline::new_pipeline;
+    use pipeline::set::{finalize_pipeline_set, new_pipeline_set};
+    use router::builder::*;
+    use router::response::extender::StaticResponseExtender;
+    use test::TestServer;
+
+    #[derive(Deserialize)]
+    struct UserPath {
+        id: u32,
+    }
+
+    impl StateData for UserPath {}
+
+    impl StaticResponseExtender for UserPath {
+        type ResBody = Body;
+        fn extend(_: &mut State, res: &mut Response<Body>) {
+            *res.status_mut() = StatusCode::BAD_REQUEST;
+        }
+    }
+
+    fn legacy(mut state: State) -> Box<HandlerFuture> {
+        let UserPath { id } = state.take();
+        forward_to(state, &format!("/users/{}", id))
+    }
+
+    fn show_user(state: State) -> (State, Response<Body>) {
+        let body = {
+            let forwarded = state.borrow::<Forwarded>();
+            let uri = Uri::borrow_from(&state);
+            format!(
+                "user {} from {} ({}), query {}",
+                state.borrow::<UserPath>().id,
+                forwarded.original_uri().path(),
+                forwarded.count(),
+                uri.query().unwrap_or("none")
+            )
+        };
+        let res = create_text_response(&state, StatusCode::OK, body);
+        (state, res)
+    }
+
+    fn ping(state: State) -> Box<HandlerFuture> {
+        forward_to(state, "/pong")
+    }
+
+    fn pong(state: State) -> Box<HandlerFuture> {
+        forward_to(state, "/ping")
+    }
+
+    #[test]
+    fn forwards_to_another_route() {
+        let api_router = build_simple_router(|route| {
+            route
+                .get("/legacy/:id")
+                .with_path_extractor::<UserPath>()
+                .to(legacy);
+        });
+
+        let router = build_simple_router(|route| {
+            route
+                .get("/profile/:id")
+                .with_path_extractor::<UserPath>()
+                .to(legacy);
+            route
+                .get("/users/:id")
+                .with_path_extractor::<UserPath>()
+                .to(show_user);
+            route.delegate("/api").to_router(api_router);
+            route.get("/ping").to(ping);
+            route.get("/pong").to(pong);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let get = |path: &str| {
+            let response = test_server
+                .client()
+                .get(&format!("http://localhost{}", path))
+                .perform()
+                .unwrap();
+            let status = response.status().as_u16();
+            (status, response.read_utf8_body().unwrap())
+        };
+
+        assert_eq!(
+            get("/profile/42?tab=posts"),
+            (
+                200,
+                "user 42 from /profile/42 (1), query tab=posts".to_owned()
+            )
+        );
+        assert_eq!(
+            get("/api/legacy/7"),
+            (200, "user 7 from /api/legacy/7 (1), query none".to_owned())
+        );
+
+        assert_eq!(get("/ping").0, 500);
+    }
+
+    #[test]
+    fn target_uri_keeps_query() {
+        let current: Uri = "http://example.com/old?page=2".parse().unwrap();
+        assert_eq!(
+            target_uri(&current, "/new").unwrap(),
+            "http://example.com/new?page=2"
+        );
+        assert_eq!(
+            target_uri(&current, "/new?page=3").unwrap(),
+            "http://example.com/new?page=3"
+        );
+        assert!(target_uri(&current, "new").is_none());
+
+        let current: Uri = "/old".parse().unwrap();
+        assert_eq!(target_uri(&current, "/new").unwrap(), "/new");
+    }
+
+    #[derive(Clone)]
+    struct CountingMiddleware {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl NewMiddleware for CountingMiddleware {
+        type Instance = CountingMiddleware;
+
+        fn new_middleware(&self) -> io::Result<CountingMiddleware> {
+            Ok(self.clone())
+        }
+    }
+
+    impl Middleware for CountingMiddleware {
+        fn call<Chain>(self, state: State, chain: Chain) -> Box<HandlerFuture>
+        where
+            Chain: FnOnce(State) -> Box<HandlerFuture> + Send + 'static,
+            Self: Sized,
+        {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            chain(state)
+        }
+    }
+
+    #[test]
+    fn pipelines_of_the_original_route_are_not_repeated() {
+        let global = Arc::new(AtomicUsize::new(0));
+        let users = Arc::new(AtomicUsize::new(0));
+
+        let pipelines = new_pipeline_set();
+        let (pipelines, global_pipeline) = pipelines.add(
+            new_pipeline()
+                .add(CountingMiddleware {
+                    calls: global.clone(),
+                })
+                .build(),
+        );
+        let (pipelines, users_pipeline) = pipelines.add(
+            new_pipeline()
+                .add(CountingMiddleware {
+                    calls: users.clone(),
+                })
+                .build(),
+        );
+        let pipelines = finalize_pipeline_set(pipelines);
+
+        let router = build_router((global_pipeline, ()), pipelines, |route| {
+            route
+                .get("/profile/:id")
+                .with_path_extractor::<UserPath>()
+                .to(legacy);
+            route.with_pipeline_chain((users_pipeline, (global_pipeline, ())), |route| {
+                route
+                    .get("/users/:id")
+                    .with_path_extractor::<UserPath>()
+                    .to(show_user);
+            });
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/profile/42")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(global.load(Ordering::SeqCst), 1);
+        assert_eq!(users.load(Ordering::SeqCst), 1);
+    }
+
+    #[derive(Deserialize)]
+    struct FilePath {
+        name: String,
+    }
+
+    impl StateData for FilePath {}
+
+    impl StaticResponseExtender for FilePath {
+        type ResBody = Body;
+        fn extend(_: &mut State, res: &mut Response<Body>) {
+            *res.status_mut() = StatusCode::BAD_REQUEST;
+        }
+    }
+
+    fn old_file(state: State) -> Box<HandlerFuture> {
+        forward_to(state, "/files/a%2Fb")
+    }
+
+    fn show_file(state: State) -> (State, Response<Body>) {
+        let body = format!("file {}", state.borrow::<FilePath>().name);
+        let res = create_text_response(&state, StatusCode::OK, body);
+        (state, res)
+    }
+
+    fn split_file(state: State) -> (State, Response<Body>) {
+        let res = create_text_response(&state, StatusCode::OK, "split");
+        (state, res)
+    }
+
+    #[test]
+    fn forwarded_paths_follow_encoded_slashes_policy() {
+        let get = |encoded_slashes| {
+            let router = build_simple_router(|route| {
+                route.set_encoded_slashes(encoded_slashes);
+                route.get("/old").to(old_file);
+                route
+                    .get("/files/:name")
+                    .with_path_extractor::<FilePath>()
+                    .to(show_file);
+                route.get("/files/a/b").to(split_file);
+            });
+
+            let test_server = TestServer::new(router).unwrap();
+            let response = test_server
+                .client()
+                .get("http://localhost/old")
+                .perform()
+                .unwrap();
+            let status = response.status().as_u16();
+            (status, response.read_utf8_body().unwrap())
+        };
+
+        assert_eq!(get(EncodedSlashes::Reject).0, 400);
+        assert_eq!(get(EncodedSlashes::Preserve), (200, "file a/b".to_owned()));
+        assert_eq!(get(EncodedSlashes::Split), (200, "split".to_owned()));
+    }
+}
@@ -0,0 +1,157 @@
+//! Defines `MatchedRoute`, which describes the route that a request was dispatched to.
+
+use state::StateData;
+
+/// Describes the route which the `Router` dispatched a request to, available via
+/// `MatchedRoute::try_borrow_from(&state)`. It's placed into `State` once the route is selected,
+/// before the pipelines and `Handler` of the route run.
+///
+/// The pattern of the route is the path used to define it, such as `/users/:id` for a request to
+/// `/users/42`, so it identifies the route rather than the request (e.g. for labelling metrics).
+/// For a request delegated to another `Router`, the pattern includes the path of the delegation,
+/// such as `/api/users/:id`, and for a fallback route it's the path of its scope.
+///
+/// `MatchedRoute` is absent when no route is selected, such as for a `404 Not Found` response.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::response::create_text_response;
+/// # use gotham::router::builder::*;
+/// # use gotham::router::matched::MatchedRoute;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let pattern = MatchedRoute::borrow_from(&state).pattern().to_owned();
+///     let res = create_text_response(&state, StatusCode::OK, pattern);
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route.get("/users/:id").to(handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/users/42")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.read_utf8_body().unwrap(), "/users/:id");
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchedRoute {
+    pattern: String,
+}
+
+impl MatchedRoute {
+    /// Creates the `MatchedRoute` for a route with the given pattern. When the request was
+    /// delegated to the `Router` of the route, `delegation` is the `MatchedRoute` of the
+    /// delegation, whose pattern is prepended.
+    pub(crate) fn new(delegation: Option<&MatchedRoute>, pattern: &str) -> MatchedRoute {
+        let pattern = match delegation {
+            Some(delegation) if delegation.pattern == "/" => pattern.to_owned(),
+            Some(delegation) if pattern == "/" => delegation.pattern.clone(),
+            Some(delegation) => format!("{}{}", delegation.pattern, pattern),
+            None => pattern.to_owned(),
+        };
+
+        MatchedRoute { pattern }
+    }
+
+    /// The path of the route, in the form used to define it, such as `/users/:id`.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+}
+
+impl StateData for MatchedRoute {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::Future;
+    use hyper::{Body, Response, StatusCode};
+
+    use handler::{Handler, HandlerFuture};
+    use helpers::http::response::create_text_response;
+    use router::builder::*;
+    use router::Router;
+    use state::{FromState, State};
+    use test::TestServer;
+
+    fn pattern(state: &State) -> String {
+        MatchedRoute::try_borrow_from(state)
+            .map(|matched| matched.pattern().to_owned())
+            .unwrap_or_else(|| "none".to_owned())
+    }
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let res = create_text_response(&state, StatusCode::OK, pattern(&state));
+        (state, res)
+    }
+
+    /// Wraps a `Router`, adding the pattern of the `MatchedRoute` left in `State` to the response.
+    struct Observer(Router);
+
+    impl Handler for Observer {
+        fn handle(self, state: State) -> Box<HandlerFuture> {
+            Box::new(self.0.handle(state).map(|(state, mut res)| {
+                let value = pattern(&state).parse().unwrap();
+                res.headers_mut().insert("x-matched-route", value);
+                (state, res)
+            }))
+        }
+    }
+
+    #[test]
+    fn matched_route_tests() {
+        let api_router = build_simple_router(|route| {
+            route.get("/").to(handler);
+            route.get("/items/:item").to(handler);
+        });
+
+        let router = build_simple_router(|route| {
+            route.get("/users/:id").to(handler);
+            route.get("/users/:id/posts/*").to(handler);
+            route.delegate("/api/:version").to_router(api_router);
+        });
+
+        let test_server = TestServer::new(move || Ok(Observer(router.clone()))).unwrap();
+        let get = |path: &str| {
+            let response = test_server
+                .client()
+                .get(&format!("http://localhost{}", path))
+                .perform()
+                .unwrap();
+            let status = response.status();
+            let header = response.headers()["x-matched-route"]
+                .to_str()
+                .unwrap()
+                .to_owned();
+            let body = response.read_utf8_body().unwrap();
+            (status, header, body)
+        };
+
+        let ok = |pattern: &str| (StatusCode::OK, pattern.to_owned(), pattern.to_owned());
+
+        assert_eq!(get("/users/42"), ok("/users/:id"));
+        assert_eq!(get("/users/42/posts/a/b"), ok("/users/:id/posts/*"));
+        assert_eq!(get("/api/v1"), ok("/api/:version"));
+        assert_eq!(get("/api/v1/items/7"), ok("/api/:version/items/:item"));
+
+        let (status, header, _) = get("/missing");
+        assert_eq!((status, header.as_str()), (StatusCode::NOT_FOUND, "none"));
+
+        let (status, header, _) = get("/api/v1/missing");
+        assert_eq!((status, header.as_str()), (StatusCode::NOT_FOUND, "none"));
+    }
+}
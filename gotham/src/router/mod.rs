@@ -1,41 +1,86 @@
 //! Defines the Gotham `Router` and supporting types.
 
 pub mod builder;
+pub mod forward;
+pub mod matched;
 pub mod non_match;
 pub mod response;
 pub mod route;
+pub mod table;
 pub mod tree;
 
 use std::sync::Arc;
 
 use futures::{future, Future};
-use hyper::header::ALLOW;
-use hyper::{Body, Response, StatusCode};
+use hyper::body::Payload;
+use hyper::header::{HeaderMap, HeaderValue, ALLOW, CONTENT_LENGTH, LOCATION};
+use hyper::{Body, Method, Response, StatusCode, Uri};
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
 
 use error::*;
+use extractor::ExtractionFailure;
 use handler::{Handler, HandlerFuture, IntoResponse, NewHandler};
-use helpers::http::request::path::RequestPathSegments;
+use helpers::http::request::path::{
+    PathCase, PathOptions, RequestPathSegments, StaticSegments, TrailingSlash,
+};
 use helpers::http::response::create_response;
+use router::forward::ForwardTarget;
+use router::matched::MatchedRoute;
+use router::response::error::HandlerErrorMapper;
+use router::response::extraction::ExtractionFailureMapper;
 use router::response::finalizer::ResponseFinalizer;
 use router::route::{Delegation, Route};
+use router::table::RouteTable;
+use router::tree::node::Node;
 use router::tree::segment::SegmentMapping;
 use router::tree::Tree;
-use state::{request_id, State};
+use state::{request_id, FromState, State, StateData};
 
 struct RouterData {
     tree: Tree,
+    routes: RouteTable,
     response_finalizer: ResponseFinalizer,
+    error_mapper: Option<Box<HandlerErrorMapper + Send + Sync>>,
+    extraction_failure_mapper: Option<Box<ExtractionFailureMapper + Send + Sync>>,
+    path_options: PathOptions,
+    automatic_head: bool,
+    automatic_options: bool,
 }
 
 impl RouterData {
-    fn new(tree: Tree, response_finalizer: ResponseFinalizer) -> RouterData {
+    fn new(
+        mut tree: Tree,
+        response_finalizer: ResponseFinalizer,
+        error_mapper: Option<Box<HandlerErrorMapper + Send + Sync>>,
+        extraction_failure_mapper: Option<Box<ExtractionFailureMapper + Send + Sync>>,
+        path_options: PathOptions,
+        automatic_head: bool,
+        automatic_options: bool,
+    ) -> RouterData {
+        tree.compute_patterns();
+
         RouterData {
+            routes: tree.route_table(),
             tree,
             response_finalizer,
+            error_mapper,
+            extraction_failure_mapper,
+            path_options,
+            automatic_head,
+            automatic_options,
         }
     }
 }
 
+/// The routers which delegated a request and have a not-found route for its path, outermost
+/// first, so that a delegated `Router` without one of its own falls back to the nearest of them.
+#[derive(Default)]
+struct EnclosingNotFound {
+    routers: Vec<(Router, RequestPathSegments, Option<MatchedRoute>)>,
+}
+
+impl StateData for EnclosingNotFound {}
+
 /// Responsible for dispatching HTTP requests to defined routes, and responding with appropriate
 /// error codes when a valid `Route` is unable to be determined or the dispatch cannot be
 /// performed.
@@ -48,6 +93,63 @@ impl RouterData {
 /// within a single binary that have clear boundaries established via Rust module separation.
 /// Please see the documentation for `DrawRoutes::delegate` within `gotham::router::builder` in
 /// order to delegate to other `Router` instances.
+///
+/// Static segments of routes are matched byte for byte against the request path as it was
+/// received, so a request for `/dotted%20name` matches a route defined as `/dotted%20name`, but a
+/// request for `/%75sers` doesn't match `/users`. They can instead be matched after
+/// percent-decoding with `RouterBuilder::set_static_segments`; see `StaticSegments`. Dynamic
+/// segment values are provided to the `PathExtractor` in their decoded form, so `/files/:name`
+/// provides a `name` of `dotted name` for `/files/dotted%20name`.
+///
+/// A request path which isn't valid UTF-8 after decoding receives a `400 Bad Request` response.
+/// So does a request path containing an encoded slash (`%2F`), unless
+/// `RouterBuilder::set_encoded_slashes` sets another `EncodedSlashes` policy, which either splits
+/// the segment at the slash or retains the slash within the decoded segment. Before any of these
+/// checks, a request path which exceeds the `PathLimits` set with `RouterBuilder::set_path_limits`
+/// receives a `414 URI Too Long` response.
+///
+/// The decoded path is then normalized according to the options set on `RouterBuilder`:
+///
+/// * Duplicate slashes are collapsed (`//users///42` matches `/users/42`), unless disabled with
+///   `RouterBuilder::set_collapse_slashes`, in which case such a request receives `404 Not Found`;
+/// * `.` and `..` segments are resolved when enabled with
+///   `RouterBuilder::set_resolve_dot_segments`, and a request which would move above the root
+///   receives `400 Bad Request`. Otherwise they're matched as literal segments;
+/// * The path is lowercased when enabled with `RouterBuilder::set_lowercase_path`, for
+///   case-insensitive routing. Routes should then be defined in lowercase.
+///
+/// Static segments of routes can instead be matched regardless of case with
+/// `RouterBuilder::set_path_case`, which leaves the request path as it is, so that dynamic
+/// segment values keep their original case. See `PathCase`.
+///
+/// When a route exists for the request path but none of its routes matched, the status of the
+/// response is determined by the route matchers. For `405 Method Not Allowed`, the `Allow` header
+/// lists each method for which a route would have matched the request, so a method is omitted
+/// when its routes were also rejected by another matcher (e.g. on the `Accept` header). `HEAD`
+/// is listed whenever a `HEAD` request would succeed, including through automatic `HEAD`
+/// handling.
+///
+/// A `HEAD` request which matches no route is dispatched to the route it would match as a `GET`
+/// request, and the body of the `Response` is dropped without being polled. Routes which match
+/// the `HEAD` request take precedence. This can be disabled with
+/// `RouterBuilder::set_automatic_head`, or for a single route with
+/// `DefineSingleRoute::without_automatic_head`.
+///
+/// When enabled with `RouterBuilder::set_automatic_options`, an `OPTIONS` request which matches
+/// no route receives `204 No Content` with the same `Allow` header as a `405 Method Not Allowed`
+/// response, which then also lists `OPTIONS`. Routes which match the `OPTIONS` request (such as
+/// those answering CORS preflight requests) take precedence. A server-wide `OPTIONS *` request
+/// receives `204 No Content` without an `Allow` header.
+///
+/// A trailing slash is ignored by default, so `/users/` matches the same route as `/users`
+/// (whichever of the two was used to define it). `RouterBuilder::set_trailing_slash` can instead
+/// restrict matching to the canonical form, or redirect to it; see `TrailingSlash`.
+///
+/// The normalized path is used for matching and for the `PathExtractor`. The `Uri` in `State` is
+/// not modified, so the original path remains available (e.g. for logging).
+///
+/// Once a route is selected, the path used to define it (e.g. `/users/:id`) is placed into
+/// `State` as a `MatchedRoute`, before the pipelines and `Handler` of the route run.
 #[derive(Clone)]
 pub struct Router {
     data: Arc<RouterData>,
@@ -69,52 +171,17 @@ impl Handler for Router {
     fn handle(self, mut state: State) -> Box<HandlerFuture> {
         trace!("[{}] starting", request_id(&state));
 
-        let future = match state.try_take::<RequestPathSegments>() {
-            Some(rps) => {
-                if let Some((node, params, processed)) = self.data.tree.traverse(&rps.segments()) {
-                    match node.select_route(&state) {
-                        Ok(route) => match route.delegation() {
-                            Delegation::External => {
-                                trace!("[{}] delegating to secondary router", request_id(&state));
+        // Requests are forwarded by the outermost `Router`, since forwarded paths are absolute.
+        if !state.has::<ForwardTarget>() {
+            state.put(ForwardTarget::new(self.clone()));
+        }
 
-                                state.put(rps.into_subsegments(processed));
-                                route.dispatch(state)
-                            }
-                            Delegation::Internal => {
-                                trace!("[{}] dispatching to route", request_id(&state));
-                                self.dispatch(state, params, route)
-                            }
-                        },
-                        Err(non_match) => {
-                            let (status, allow) = non_match.deconstruct();
-
-                            trace!("[{}] responding with error status", request_id(&state));
-                            let mut res = create_response(&state, status, None);
-                            if let StatusCode::METHOD_NOT_ALLOWED = status {
-                                for allowed in allow {
-                                    res.headers_mut().append(
-                                        ALLOW,
-                                        allowed.as_str().to_string().parse().unwrap(),
-                                    );
-                                }
-                            }
-                            Box::new(future::ok((state, res)))
-                        }
-                    }
-                } else {
-                    trace!("[{}] did not find routable node", request_id(&state));
-                    let res = create_response(&state, StatusCode::NOT_FOUND, None);
-                    Box::new(future::ok((state, res)))
-                }
-            }
-            None => {
-                trace!("[{}] invalid request path segments", request_id(&state));
-                let res = create_response(&state, StatusCode::INTERNAL_SERVER_ERROR, None);
-                Box::new(future::ok((state, res)))
-            }
-        };
+        // A request delegated to this `Router` holds the `MatchedRoute` of the delegation.
+        let delegation = state.try_take::<MatchedRoute>();
 
-        self.finalize_response(future)
+        let mut finalizers = Vec::new();
+        let future = self.route_request(state, delegation.as_ref(), &mut finalizers);
+        self.finalize_response(future, finalizers)
     }
 }
 
@@ -124,23 +191,443 @@ impl Router {
         since = "0.2.0", note = "use the new `gotham::router::builder` API to construct a Router"
     )]
     pub fn new(tree: Tree, response_finalizer: ResponseFinalizer) -> Router {
-        Router::internal_new(tree, response_finalizer)
+        Router::internal_new(
+            tree,
+            response_finalizer,
+            None,
+            None,
+            PathOptions::default(),
+            true,
+            false,
+        )
     }
 
     /// Same as `new`, but private and not deprecated.
-    fn internal_new(tree: Tree, response_finalizer: ResponseFinalizer) -> Router {
-        let router_data = RouterData::new(tree, response_finalizer);
+    fn internal_new(
+        tree: Tree,
+        response_finalizer: ResponseFinalizer,
+        error_mapper: Option<Box<HandlerErrorMapper + Send + Sync>>,
+        extraction_failure_mapper: Option<Box<ExtractionFailureMapper + Send + Sync>>,
+        path_options: PathOptions,
+        automatic_head: bool,
+        automatic_options: bool,
+    ) -> Router {
+        let router_data = RouterData::new(
+            tree,
+            response_finalizer,
+            error_mapper,
+            extraction_failure_mapper,
+            path_options,
+            automatic_head,
+            automatic_options,
+        );
         Router {
             data: Arc::new(router_data),
         }
     }
 
+    /// Describes the routes of the `Router`, in the order they're tried for a request. See
+    /// `RouteTable`.
+    pub fn routes(&self) -> &RouteTable {
+        &self.data.routes
+    }
+
+    /// Routes a request forwarded by `forward_to`. The response isn't finalized here, since it's
+    /// finalized by the `Router` which dispatched the original request.
+    pub(crate) fn forward(&self, mut state: State) -> Box<HandlerFuture> {
+        state.try_take::<MatchedRoute>();
+        state.try_take::<EnclosingNotFound>();
+
+        let mut finalizers = Vec::new();
+        self.route_request(state, None, &mut finalizers)
+    }
+
+    /// Normalizes the request path, and routes the request. The `ResponseFinalizer` values of the
+    /// scopes and `Route` which the request is dispatched to are stored in `finalizers`.
+    ///
+    /// When the request was delegated to this `Router`, `delegation` describes the route which
+    /// delegated it, so that the `MatchedRoute` of the request includes its path.
+    fn route_request(
+        &self,
+        mut state: State,
+        delegation: Option<&MatchedRoute>,
+        finalizers: &mut Vec<ResponseFinalizer>,
+    ) -> Box<HandlerFuture> {
+        match state.try_take::<RequestPathSegments>() {
+            Some(rps) => match rps.normalize(&self.data.path_options) {
+                Err(rejection) => {
+                    if rejection.exceeds_limits() {
+                        debug!("[{}] {}", request_id(&state), rejection);
+                    } else {
+                        trace!("[{}] {}", request_id(&state), rejection);
+                    }
+                    let res = create_response(&state, rejection.status(), None);
+                    Box::new(future::ok((state, res)))
+                }
+                Ok(rps) => {
+                    *finalizers = self
+                        .data
+                        .tree
+                        .scope_finalizers(rps.segments(), self.ignores_case());
+                    self.route(state, rps, delegation, finalizers)
+                }
+            },
+            None => {
+                trace!("[{}] invalid request path segments", request_id(&state));
+                let res = create_response(&state, StatusCode::INTERNAL_SERVER_ERROR, None);
+                Box::new(future::ok((state, res)))
+            }
+        }
+    }
+
+    /// Matches the normalized request path against the `Tree`, and dispatches to the `Route` or
+    /// responds with an error status.
+    ///
+    /// The `ResponseFinalizer` of the `Route` which the request is dispatched to, if any, is added
+    /// to the front of `finalizers`.
+    fn route(
+        &self,
+        mut state: State,
+        rps: RequestPathSegments,
+        delegation: Option<&MatchedRoute>,
+        finalizers: &mut Vec<ResponseFinalizer>,
+    ) -> Box<HandlerFuture> {
+        if self.data.automatic_options
+            && *Method::borrow_from(&state) == Method::OPTIONS
+            && Uri::borrow_from(&state).path() == "*"
+        {
+            trace!("[{}] responding to server-wide OPTIONS", request_id(&state));
+            let res = create_response(&state, StatusCode::NO_CONTENT, None);
+            return Box::new(future::ok((state, res)));
+        }
+
+        let matched = if self.ignores_case() {
+            self.data.tree.traverse_ignoring_case(&rps.segments())
+        } else {
+            self.data
+                .tree
+                .traverse(&rps.segments())
+                .map(|(node, params, processed)| (node, params, processed, Vec::new()))
+        };
+
+        if let Some((node, params, processed, folded)) = matched {
+            if let Some(res) = self.case_redirect_response(&state, &rps, &folded) {
+                trace!("[{}] non-canonical path case", request_id(&state));
+                return Box::new(future::ok((state, res)));
+            }
+
+            if let Some(res) = self.trailing_slash_response(&state, &rps, node) {
+                trace!("[{}] non-canonical trailing slash", request_id(&state));
+                return Box::new(future::ok((state, res)));
+            }
+
+            let matched = MatchedRoute::new(delegation, node.route_pattern());
+
+            match node.select_route(&state) {
+                Ok(route) => match route.delegation() {
+                    Delegation::External => {
+                        trace!("[{}] delegating to secondary router", request_id(&state));
+
+                        if self.has_not_found_route(&state, &rps) {
+                            let enclosing = (self.clone(), rps.clone(), delegation.cloned());
+                            state
+                                .get_or_default::<EnclosingNotFound>()
+                                .routers
+                                .push(enclosing);
+                        }
+
+                        state.put(rps.into_subsegments(processed));
+                        state.put(matched);
+                        route.dispatch(state)
+                    }
+                    Delegation::Internal => {
+                        trace!("[{}] dispatching to route", request_id(&state));
+                        self.dispatch(state, params, route, matched, finalizers)
+                    }
+                },
+                Err(mut non_match) => {
+                    let headers = non_match.take_headers();
+                    let (status, mut allow) = non_match.deconstruct();
+
+                    if let StatusCode::METHOD_NOT_ALLOWED = status {
+                        if let Some(route) = self.automatic_head_route(node, &mut state) {
+                            if *Method::borrow_from(&state) == Method::HEAD {
+                                trace!("[{}] dispatching HEAD to GET route", request_id(&state));
+                                let f = self
+                                    .dispatch(state, params, route, matched, finalizers)
+                                    .map(|(state, res)| (state, strip_body(res)));
+                                return Box::new(f);
+                            }
+
+                            if !allow.contains(&Method::HEAD) {
+                                allow.push(Method::HEAD);
+                            }
+                        }
+
+                        if self.data.automatic_options {
+                            if !allow.contains(&Method::OPTIONS) {
+                                allow.push(Method::OPTIONS);
+                            }
+
+                            if *Method::borrow_from(&state) == Method::OPTIONS {
+                                trace!("[{}] responding to OPTIONS", request_id(&state));
+                                let mut res = create_response(&state, StatusCode::NO_CONTENT, None);
+                                res.headers_mut().insert(ALLOW, allow_header(allow));
+                                return Box::new(future::ok((state, res)));
+                            }
+                        }
+                    }
+
+                    if let StatusCode::NOT_FOUND = status {
+                        return self.not_found(state, &rps, delegation, headers, finalizers);
+                    }
+
+                    trace!("[{}] responding with error status", request_id(&state));
+                    let mut res = create_response(&state, status, None);
+                    if let StatusCode::METHOD_NOT_ALLOWED = status {
+                        res.headers_mut().insert(ALLOW, allow_header(allow));
+                    }
+                    res.headers_mut().extend(headers);
+                    Box::new(future::ok((state, res)))
+                }
+            }
+        } else {
+            trace!("[{}] did not find routable node", request_id(&state));
+            self.not_found(state, &rps, delegation, HeaderMap::new(), finalizers)
+        }
+    }
+
+    /// Dispatches a request which no route matched to a fallback route for its path. When there
+    /// is none, a request delegated to this `Router` is dispatched to the not-found route of the
+    /// nearest `Router` which delegated it, and any other request receives `404 Not Found`.
+    fn not_found(
+        &self,
+        state: State,
+        rps: &RequestPathSegments,
+        delegation: Option<&MatchedRoute>,
+        headers: HeaderMap,
+        finalizers: &mut Vec<ResponseFinalizer>,
+    ) -> Box<HandlerFuture> {
+        let mut state = match self.fallback(state, rps, delegation, false, finalizers) {
+            Ok(future) => return future,
+            Err(state) => state,
+        };
+
+        while let Some((router, rps, delegation)) = state
+            .try_borrow_mut::<EnclosingNotFound>()
+            .and_then(|enclosing| enclosing.routers.pop())
+        {
+            trace!(
+                "[{}] using not-found route of enclosing router",
+                request_id(&state)
+            );
+            state = match router.fallback(state, &rps, delegation.as_ref(), true, finalizers) {
+                Ok(future) => return future,
+                Err(state) => state,
+            };
+        }
+
+        let mut res = create_response(&state, StatusCode::NOT_FOUND, None);
+        res.headers_mut().extend(headers);
+        Box::new(future::ok((state, res)))
+    }
+
+    /// Dispatches a request to the fallback route for its path, or returns the `State` when there
+    /// is none. When `not_found_only` is set, only not-found routes are considered.
+    fn fallback(
+        &self,
+        state: State,
+        rps: &RequestPathSegments,
+        delegation: Option<&MatchedRoute>,
+        not_found_only: bool,
+        finalizers: &mut Vec<ResponseFinalizer>,
+    ) -> ::std::result::Result<Box<HandlerFuture>, State> {
+        let fallback = self.data.tree.select_fallback(
+            rps.segments(),
+            &state,
+            self.ignores_case(),
+            not_found_only,
+        );
+
+        match fallback {
+            Some((route, params, pattern)) => {
+                trace!("[{}] dispatching to fallback route", request_id(&state));
+                let matched = MatchedRoute::new(delegation, pattern);
+                Ok(self.dispatch(state, params, route, matched, finalizers))
+            }
+            None => Err(state),
+        }
+    }
+
+    /// Determines if a not-found route exists for the request path, which a `Router` delegated
+    /// to falls back to when it has none of its own.
+    fn has_not_found_route(&self, state: &State, rps: &RequestPathSegments) -> bool {
+        self.data
+            .tree
+            .select_fallback(rps.segments(), state, self.ignores_case(), true)
+            .is_some()
+    }
+
+    /// Selects the `Route` which a `HEAD` request is dispatched to when no route matches it, by
+    /// matching the request as though it were a `GET` request.
+    fn automatic_head_route<'n>(
+        &self,
+        node: &'n Node,
+        state: &mut State,
+    ) -> Option<&'n Box<Route<ResBody = Body> + Send + Sync>> {
+        if !self.data.automatic_head {
+            return None;
+        }
+
+        let method = state.take::<Method>();
+        state.put(Method::GET);
+
+        let route = match node.select_route(state) {
+            Ok(route) if route.automatic_head() && route.delegation() == Delegation::Internal => {
+                Some(route)
+            }
+            _ => None,
+        };
+
+        state.put(method);
+        route
+    }
+
+    /// Determines if static segments of routes match request path segments regardless of case.
+    fn ignores_case(&self) -> bool {
+        self.data.path_options.case != PathCase::Sensitive
+    }
+
+    /// Creates the redirect for a request path which only matched a route by ignoring the case of
+    /// the static segments in `folded`, when enabled with `PathCase::Redirect`. When `None`, the
+    /// request is routed as normal.
+    fn case_redirect_response(
+        &self,
+        state: &State,
+        rps: &RequestPathSegments,
+        folded: &[(usize, &str)],
+    ) -> Option<Response<Body>> {
+        if folded.is_empty() || self.data.path_options.case != PathCase::Redirect {
+            return None;
+        }
+
+        let method = Method::borrow_from(state);
+        if *method != Method::GET && *method != Method::HEAD {
+            return None;
+        }
+
+        let mut location = String::new();
+        let encoded = self.data.path_options.static_segments == StaticSegments::Encoded;
+        for (i, segment) in rps.segments().iter().enumerate() {
+            location.push('/');
+
+            match folded.iter().find(|&&(index, _)| index == i) {
+                // The static segments of routes are already encoded, unless matched after
+                // decoding.
+                Some(&(_, canonical)) if encoded => location.push_str(canonical),
+                Some(&(_, canonical)) => {
+                    location.extend(utf8_percent_encode(canonical, PATH_SEGMENT_ENCODE_SET))
+                }
+                None => location.extend(utf8_percent_encode(
+                    segment.as_ref(),
+                    PATH_SEGMENT_ENCODE_SET,
+                )),
+            }
+        }
+
+        if location.is_empty() || rps.trailing_slash() == Some(true) {
+            location.push('/');
+        }
+
+        if let Some(query) = Uri::borrow_from(state).query() {
+            location.push('?');
+            location.push_str(query);
+        }
+
+        let mut res = create_response(state, StatusCode::MOVED_PERMANENTLY, None);
+        res.headers_mut()
+            .insert(LOCATION, location.parse().unwrap());
+        Some(res)
+    }
+
+    /// Creates the response for a request path without the canonical trailing slash, according
+    /// to the `TrailingSlash` option. When `None`, the request is routed as normal.
+    ///
+    /// The canonical form ends with a slash when enabled with
+    /// `RouterBuilder::set_canonical_trailing_slash`, or when a route at `node` was defined with
+    /// one.
+    fn trailing_slash_response(
+        &self,
+        state: &State,
+        rps: &RequestPathSegments,
+        node: &Node,
+    ) -> Option<Response<Body>> {
+        let options = &self.data.path_options;
+        let canonical = options.canonical_trailing_slash || node.trailing_slash();
+
+        let trailing_slash = match rps.trailing_slash() {
+            Some(trailing_slash) if trailing_slash != canonical => trailing_slash,
+            _ => return None,
+        };
+
+        let status = match options.trailing_slash {
+            TrailingSlash::Normalize => return None,
+            TrailingSlash::Strict => {
+                return Some(create_response(state, StatusCode::NOT_FOUND, None));
+            }
+            TrailingSlash::Redirect => {
+                let method = Method::borrow_from(state);
+                if *method == Method::GET || *method == Method::HEAD {
+                    StatusCode::MOVED_PERMANENTLY
+                } else if options.redirect_all_methods {
+                    StatusCode::PERMANENT_REDIRECT
+                } else {
+                    return None;
+                }
+            }
+        };
+
+        // Empty segments are collapsed (a path containing them is only routed when collapsing
+        // slashes), so that a path such as `//example.com/` doesn't become the network-path
+        // reference `//example.com`, which a client would follow to another host.
+        let uri = Uri::borrow_from(state);
+        let mut location = String::with_capacity(uri.path().len() + 1);
+        for segment in uri.path().split('/').filter(|segment| !segment.is_empty()) {
+            location.push('/');
+            location.push_str(segment);
+        }
+
+        if !trailing_slash {
+            location.push('/');
+        }
+
+        if let Some(query) = uri.query() {
+            location.push('?');
+            location.push_str(query);
+        }
+
+        let mut res = create_response(state, status, None);
+        res.headers_mut()
+            .insert(LOCATION, location.parse().unwrap());
+        Some(res)
+    }
+
+    /// Extracts the request data for the `Route`, and dispatches to it. The `ResponseFinalizer`
+    /// of the `Route`, if any, is added to the front of `finalizers`.
     fn dispatch<'a>(
         &self,
         mut state: State,
         params: SegmentMapping<'a>,
         route: &Box<Route<ResBody = Body> + Send + Sync>,
+        matched: MatchedRoute,
+        finalizers: &mut Vec<ResponseFinalizer>,
     ) -> Box<HandlerFuture> {
+        state.put(matched);
+
+        if let Some(finalizer) = route.response_finalizer() {
+            finalizers.insert(0, finalizer.clone());
+        }
+
         match route.extract_request_path(&mut state, params) {
             Ok(()) => {
                 trace!("[{}] extracted request path", request_id(&state));
@@ -154,8 +641,14 @@ impl Router {
                         error!("[{}] the server cannot or will not process the request due to a client error within the query string",
                                request_id(&state));
 
-                        let mut res = Response::new(Body::empty());
-                        route.extend_response_on_query_string_error(&mut state, &mut res);
+                        let res = match self.map_extraction_failure(&state) {
+                            Some(res) => res,
+                            None => {
+                                let mut res = Response::new(Body::empty());
+                                route.extend_response_on_query_string_error(&mut state, &mut res);
+                                res
+                            }
+                        };
                         Box::new(future::ok((state, res)))
                     }
                 }
@@ -165,53 +658,118 @@ impl Router {
                     "[{}] the server cannot or will not process the request due to a client error on the request path",
                     request_id(&state)
                 );
-                let mut res = Response::new(Body::empty());
-                route.extend_response_on_path_error(&mut state, &mut res);
+                let res = match self.map_extraction_failure(&state) {
+                    Some(res) => res,
+                    None => {
+                        let mut res = Response::new(Body::empty());
+                        route.extend_response_on_path_error(&mut state, &mut res);
+                        res
+                    }
+                };
                 Box::new(future::ok((state, res)))
             }
         }
     }
 
-    fn finalize_response(&self, result: Box<HandlerFuture>) -> Box<HandlerFuture> {
+    /// Creates the `Response` for a failed extractor using the `ExtractionFailureMapper`, or
+    /// returns `None` when no mapper is set.
+    fn map_extraction_failure(&self, state: &State) -> Option<Response<Body>> {
+        let mapper = match self.data.extraction_failure_mapper {
+            Some(ref mapper) => mapper,
+            None => return None,
+        };
+
+        ExtractionFailure::try_borrow_from(state).map(|failure| mapper.map_failure(state, failure))
+    }
+
+    /// Converts an error from the `Handler` into a `Response`, and then applies the response
+    /// extenders of the first of `finalizers` which has one for the status code, or else those
+    /// of the `Router`.
+    fn finalize_response(
+        &self,
+        result: Box<HandlerFuture>,
+        finalizers: Vec<ResponseFinalizer>,
+    ) -> Box<HandlerFuture> {
         let response_finalizer = self.data.response_finalizer.clone();
+        let data = self.data.clone();
         let f = result
-            .or_else(|(state, err)| {
+            .or_else(move |(state, err)| {
                 trace!(
                     "[{}] converting error into http response \
                      during finalization: {:?}",
                     request_id(&state),
                     err
                 );
-                let response = err.into_response(&state);
+
+                let mapped = match data.error_mapper {
+                    Some(ref mapper) => mapper.map_error(&state, &err),
+                    None => None,
+                };
+
+                let response = match mapped {
+                    Some(response) => response,
+                    None => err.into_response(&state),
+                };
                 future::ok((state, response))
             })
             .and_then(move |(state, res)| {
                 trace!("[{}] handler complete", request_id(&state));
-                response_finalizer.finalize(state, res)
+                response_finalizer.finalize_within(&finalizers, state, res)
             });
 
         Box::new(f)
     }
 }
 
+/// Creates the value of the `Allow` header, listing the methods in alphabetical order.
+fn allow_header(mut allow: Vec<Method>) -> HeaderValue {
+    allow.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+    let allow = allow
+        .iter()
+        .map(Method::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+    allow.parse().unwrap()
+}
+
+/// Drops the body of a `Response` to a `HEAD` request without polling it, retaining its length as
+/// the `Content-Length` header when known.
+fn strip_body(res: Response<Body>) -> Response<Body> {
+    let (mut parts, body) = res.into_parts();
+
+    if !parts.headers.contains_key(CONTENT_LENGTH) {
+        if let Some(len) = body.content_length() {
+            parts.headers.insert(CONTENT_LENGTH, HeaderValue::from(len));
+        }
+    }
+
+    Response::from_parts(parts, Body::empty())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use hyper::header::{HeaderMap, CONTENT_LENGTH};
+    use futures::stream;
+    use hyper::header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_LENGTH, CONTENT_TYPE};
     use hyper::{Body, Method, Uri};
+    use mime;
+    use std::io;
     use std::str::FromStr;
 
     use extractor::{NoopPathExtractor, NoopQueryStringExtractor};
     use handler::HandlerError;
+    use helpers::http::response::create_text_response;
     use pipeline::set::*;
+    use router::builder::*;
     use router::response::finalizer::ResponseFinalizerBuilder;
     use router::route::dispatch::DispatcherImpl;
-    use router::route::matcher::MethodOnlyRouteMatcher;
+    use router::route::matcher::{AcceptHeaderRouteMatcher, MethodOnlyRouteMatcher};
     use router::route::{Extractors, RouteImpl};
     use router::tree::node::Node;
     use router::tree::segment::SegmentType;
     use router::tree::Tree;
     use state::set_request_id;
+    use test::TestServer;
 
     fn handler(state: State) -> (State, Response<Body>) {
         (state, Response::new(Body::empty()))
@@ -402,4 +960,252 @@ mod tests {
             Err(_) => panic!("Router should have correctly handled request"),
         };
     }
+
+    #[test]
+    fn allow_header_lists_matching_methods() {
+        let router = build_simple_router(|route| {
+            route.get("/users").to(handler);
+            route.delete("/users").to(handler);
+
+            route
+                .get_or_head("/reports")
+                .add_route_matcher(AcceptHeaderRouteMatcher::new(vec![mime::APPLICATION_JSON]))
+                .to(handler);
+            route.post("/reports").to(handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .put("http://localhost/users", "", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers().get_all(ALLOW).iter().count(), 1);
+        assert_eq!(response.headers().get(ALLOW).unwrap(), "DELETE, GET, HEAD");
+
+        let response = test_server
+            .client()
+            .delete("http://localhost/reports")
+            .with_header(ACCEPT, HeaderValue::from_static("application/json"))
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers().get(ALLOW).unwrap(), "GET, HEAD, POST");
+    }
+
+    fn text_handler(state: State) -> (State, Response<Body>) {
+        let res = create_text_response(&state, StatusCode::OK, "hello");
+        (state, res)
+    }
+
+    fn explicit_head_handler(state: State) -> (State, Response<Body>) {
+        let mut res = create_response(&state, StatusCode::NO_CONTENT, None);
+        res.headers_mut()
+            .insert("x-head", HeaderValue::from_static("explicit"));
+        (state, res)
+    }
+
+    fn streaming_handler(state: State) -> (State, Response<Body>) {
+        let chunks = vec![Ok::<_, io::Error>("hello")];
+        let res = Response::new(Body::wrap_stream(stream::iter_result(chunks)));
+        (state, res)
+    }
+
+    #[test]
+    fn automatic_head_dispatches_to_get_routes() {
+        let router = |automatic_head| {
+            build_simple_router(|route| {
+                route.set_automatic_head(automatic_head);
+
+                route.get("/text").to(text_handler);
+                route.get("/stream").to(streaming_handler);
+                route.get("/explicit").to(text_handler);
+                route.head("/explicit").to(explicit_head_handler);
+                route
+                    .get("/opt-out")
+                    .without_automatic_head()
+                    .to(text_handler);
+            })
+        };
+
+        let test_server = TestServer::new(router(true)).unwrap();
+        let head = |path: &str| {
+            test_server
+                .client()
+                .head(&format!("http://localhost{}", path))
+                .perform()
+                .unwrap()
+        };
+
+        let response = head("/text");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(CONTENT_LENGTH).unwrap(), "5");
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "text/plain; charset=utf-8"
+        );
+        assert!(response.read_body().unwrap().is_empty());
+
+        let response = head("/stream");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.read_body().unwrap().is_empty());
+
+        let response = head("/explicit");
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers().get("x-head").unwrap(), "explicit");
+
+        let response = head("/opt-out");
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers().get(ALLOW).unwrap(), "GET");
+
+        let response = test_server
+            .client()
+            .delete("http://localhost/text")
+            .perform()
+            .unwrap();
+        assert_eq!(response.headers().get(ALLOW).unwrap(), "GET, HEAD");
+
+        let test_server = TestServer::new(router(false)).unwrap();
+        let response = test_server
+            .client()
+            .head("http://localhost/text")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[test]
+    fn multiple_methods_share_one_route() {
+        let router = build_simple_router(|route| {
+            route.set_automatic_options(true);
+            route
+                .request(vec![Method::GET, Method::POST, Method::PUT], "/form")
+                .to(text_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let call = |method: Method| {
+            test_server
+                .client()
+                .build_request(method, "http://localhost/form")
+                .perform()
+                .unwrap()
+        };
+
+        for method in vec![Method::GET, Method::POST, Method::PUT] {
+            let response = call(method);
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.read_utf8_body().unwrap(), "hello");
+        }
+
+        let response = call(Method::HEAD);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "");
+
+        let response = call(Method::DELETE);
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(
+            response.headers().get(ALLOW).unwrap(),
+            "GET, HEAD, OPTIONS, POST, PUT"
+        );
+
+        let response = call(Method::OPTIONS);
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            response.headers().get(ALLOW).unwrap(),
+            "GET, HEAD, OPTIONS, POST, PUT"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`GET /form` is already defined by `GET, POST /form`")]
+    fn shadowed_methods_are_rejected() {
+        build_simple_router(|route| {
+            route
+                .request(vec![Method::POST, Method::GET], "/form")
+                .to(text_handler);
+            route.get("/form").to(text_handler);
+        });
+    }
+
+    #[test]
+    fn automatic_options_lists_allowed_methods() {
+        let router = |automatic_options| {
+            build_simple_router(|route| {
+                route.set_automatic_options(automatic_options);
+
+                route.get("/users").to(handler);
+                route.post("/users").to(handler);
+                route.get("/preflight").to(handler);
+                route.options("/preflight").to(explicit_head_handler);
+            })
+        };
+
+        let test_server = TestServer::new(router(true)).unwrap();
+        let options = |path: &str| {
+            test_server
+                .client()
+                .build_request(Method::OPTIONS, &format!("http://localhost{}", path))
+                .perform()
+                .unwrap()
+        };
+
+        let response = options("/users");
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            response.headers().get(ALLOW).unwrap(),
+            "GET, HEAD, OPTIONS, POST"
+        );
+
+        let response = options("/preflight");
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers().get("x-head").unwrap(), "explicit");
+        assert!(response.headers().get(ALLOW).is_none());
+
+        let response = options("/missing");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = test_server
+            .client()
+            .delete("http://localhost/users")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(
+            response.headers().get(ALLOW).unwrap(),
+            "GET, HEAD, OPTIONS, POST"
+        );
+
+        match send_request(router(true), Method::OPTIONS, "*") {
+            Ok((_state, res)) => {
+                assert_eq!(res.status(), StatusCode::NO_CONTENT);
+                assert!(res.headers().get(ALLOW).is_none());
+            }
+            Err(_) => panic!("Router should have handled request"),
+        };
+
+        let test_server = TestServer::new(router(false)).unwrap();
+        let response = test_server
+            .client()
+            .build_request(Method::OPTIONS, "http://localhost/users")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers().get(ALLOW).unwrap(), "GET, HEAD, POST");
+    }
+
+    #[test]
+    fn strip_body_keeps_known_length() {
+        let res = strip_body(Response::new(Body::from("hello")));
+        assert_eq!(res.headers().get(CONTENT_LENGTH).unwrap(), "5");
+        assert!(res.body().is_end_stream());
+
+        let chunks = vec![Ok::<_, io::Error>("hello")];
+        let body = Body::wrap_stream(stream::iter_result(chunks));
+        let res = strip_body(Response::new(body));
+        assert!(res.headers().get(CONTENT_LENGTH).is_none());
+        assert!(res.body().is_end_stream());
+    }
 }
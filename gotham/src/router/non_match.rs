@@ -1,7 +1,9 @@
 //! Defines the types used to indicate a non-matching route, and associated metadata.
 
 use std::collections::HashSet;
+use std::mem;
 
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
 use hyper::{Method, StatusCode};
 
 /// The error type used for a non-matching route, as returned by `RouteMatcher::is_match`. Multiple
@@ -9,6 +11,16 @@ use hyper::{Method, StatusCode};
 /// `intersection` / `union` methods.  The data within is used by the `Router` to create a
 /// `Response` when no routes were successfully matched.
 ///
+/// When two values are combined, the status of the result is the one of higher precedence:
+/// `404 Not Found` is overruled by any other status, then `405 Method Not Allowed`, then `406 Not
+/// Acceptable`. Between two other statuses, the status of the value which `intersection` or
+/// `union` is called on is kept. The `Router` combines the values of a node's routes in the order
+/// the routes were added, so the status of the first of them wins such a tie.
+///
+/// The headers of the result are those added via `with_header` to the values whose status was
+/// kept, with the values of the receiver before those of the argument. A header advertised by
+/// several routes therefore lists their values in the order the routes were added.
+///
 /// ```rust
 /// # extern crate gotham;
 /// # extern crate hyper;
@@ -44,6 +56,7 @@ use hyper::{Method, StatusCode};
 pub struct RouteNonMatch {
     status: StatusCode,
     allow: MethodSet,
+    headers: HeaderMap,
 }
 
 impl RouteNonMatch {
@@ -52,6 +65,7 @@ impl RouteNonMatch {
         RouteNonMatch {
             status,
             allow: MethodSet::default(),
+            headers: HeaderMap::new(),
         }
     }
 
@@ -65,6 +79,22 @@ impl RouteNonMatch {
         }
     }
 
+    /// Adds a header to the `Response` which the `Router` sends when no route matches the request,
+    /// such as one advertising the values of a request header which would have been accepted.
+    ///
+    /// When `RouteNonMatch` values are combined, the headers of those whose status is chosen are
+    /// kept, so a header is only sent along with the status it was added for. A header added by
+    /// several of them is sent with each of their distinct values.
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> RouteNonMatch {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// The headers added via `with_header`.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
     /// Takes the intersection of two `RouteNonMatch` values, producing a single result.  This is
     /// intended for use in cases where two `RouteMatcher` instances with a logical **AND**
     /// connection have both indicated a non-match, and their results need to be aggregated.
@@ -75,7 +105,16 @@ impl RouteNonMatch {
     pub fn intersection(self, other: RouteNonMatch) -> RouteNonMatch {
         let status = higher_precedence_status(self.status, other.status);
         let allow = self.allow.intersection(other.allow);
-        RouteNonMatch { status, allow }
+        let headers = combine_headers(
+            status,
+            (self.status, self.headers),
+            (other.status, other.headers),
+        );
+        RouteNonMatch {
+            status,
+            allow,
+            headers,
+        }
     }
 
     /// Takes the union of two `RouteNonMatch` values, producing a single result. This is intended
@@ -88,12 +127,25 @@ impl RouteNonMatch {
     pub fn union(self, other: RouteNonMatch) -> RouteNonMatch {
         let status = higher_precedence_status(self.status, other.status);
         let allow = self.allow.union(other.allow);
-        RouteNonMatch { status, allow }
+        let headers = combine_headers(
+            status,
+            (self.status, self.headers),
+            (other.status, other.headers),
+        );
+        RouteNonMatch {
+            status,
+            allow,
+            headers,
+        }
     }
 
     pub(super) fn deconstruct(self) -> (StatusCode, Vec<Method>) {
         (self.status, self.allow.into())
     }
+
+    pub(super) fn take_headers(&mut self) -> HeaderMap {
+        mem::replace(&mut self.headers, HeaderMap::new())
+    }
 }
 
 impl From<RouteNonMatch> for StatusCode {
@@ -102,6 +154,34 @@ impl From<RouteNonMatch> for StatusCode {
     }
 }
 
+/// Keeps the headers of each `RouteNonMatch` whose status was chosen when combining them, without
+/// repeating a value which both of them have for the same header.
+fn combine_headers(
+    status: StatusCode,
+    lhs: (StatusCode, HeaderMap),
+    rhs: (StatusCode, HeaderMap),
+) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    for (side_status, side_headers) in vec![lhs, rhs] {
+        if side_status != status {
+            continue;
+        }
+
+        for (name, value) in side_headers.iter() {
+            if !headers
+                .get_all(name)
+                .iter()
+                .any(|existing| existing == value)
+            {
+                headers.append(name.clone(), value.clone());
+            }
+        }
+    }
+
+    headers
+}
+
 fn higher_precedence_status(lhs: StatusCode, rhs: StatusCode) -> StatusCode {
     match (lhs, rhs) {
         // For 404, prefer routes that indicated *some* kind of match.
@@ -368,6 +448,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn header_tests() {
+        let name = HeaderName::from_static("x-supported");
+        let value = |v: &'static str| HeaderValue::from_static(v);
+        let non_match = |status, v| RouteNonMatch::new(status).with_header(name.clone(), value(v));
+        let values = |non_match: RouteNonMatch| {
+            non_match
+                .headers()
+                .get_all(&name)
+                .iter()
+                .map(|v| v.to_str().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let bad_request = StatusCode::BAD_REQUEST;
+        let combined = non_match(bad_request, "1")
+            .union(non_match(bad_request, "2"))
+            .union(non_match(bad_request, "1"));
+        assert_eq!(values(combined), vec!["1", "2"]);
+
+        let combined = non_match(StatusCode::NOT_FOUND, "1").union(non_match(bad_request, "2"));
+        assert_eq!(values(combined), vec!["2"]);
+
+        // Between two statuses of equal precedence, the receiver's status and headers are kept.
+        let unsupported = StatusCode::UNSUPPORTED_MEDIA_TYPE;
+        let combined = non_match(bad_request, "1").intersection(RouteNonMatch::new(unsupported));
+        assert_eq!(StatusCode::from(combined.clone()), bad_request);
+        assert_eq!(values(combined), vec!["1"]);
+
+        let combined = RouteNonMatch::new(unsupported).intersection(non_match(bad_request, "1"));
+        assert_eq!(StatusCode::from(combined.clone()), unsupported);
+        assert!(values(combined).is_empty());
+
+        let mut combined = non_match(bad_request, "1").intersection(non_match(bad_request, "2"));
+        assert_eq!(combined.take_headers().len(), 2);
+        assert!(combined.headers().is_empty());
+    }
+
     #[test]
     fn deconstruct_tests() {
         let (_, allow_list) = RouteNonMatch::new(StatusCode::NOT_FOUND)
@@ -0,0 +1,377 @@
+//! Defines `ResponseExtender` implementations which attach a body to responses generated by the
+//! framework, such as the `404 Not Found` produced when no route matches a request.
+//!
+//! These extenders are registered per status code via `RouterBuilder::add_response_extender`:
+//!
+//! ```rust
+//! # extern crate gotham;
+//! # extern crate hyper;
+//! #
+//! # use hyper::StatusCode;
+//! # use hyper::header::{ACCEPT, CONTENT_TYPE};
+//! # use gotham::router::Router;
+//! # use gotham::router::builder::*;
+//! # use gotham::router::response::body::{JsonBodyExtender, NegotiatedBodyExtender};
+//! # use gotham::test::TestServer;
+//! #
+//! fn router() -> Router {
+//!     build_simple_router(|route| {
+//!         route.add_response_extender(StatusCode::NOT_FOUND, NegotiatedBodyExtender::new());
+//!         route.add_response_extender(
+//!             StatusCode::INTERNAL_SERVER_ERROR,
+//!             JsonBodyExtender::new().with_message("Something went wrong"),
+//!         );
+//!     })
+//! }
+//! #
+//! # fn main() {
+//! #   let test_server = TestServer::new(router()).unwrap();
+//! #   let response = test_server.client()
+//! #       .get("https://example.com/missing")
+//! #       .with_header(ACCEPT, "application/json".parse().unwrap())
+//! #       .perform()
+//! #       .unwrap();
+//! #   assert_eq!(response.status(), StatusCode::NOT_FOUND);
+//! #   assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "application/json");
+//! # }
+//! ```
+
+use hyper::header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{Body, Method, Response, StatusCode};
+use mime::{self, Mime};
+
+use helpers::http::accept::negotiate_header;
+use helpers::http::response::check_charset;
+use router::response::extender::ResponseExtender;
+use state::{request_id, FromState, State};
+
+/// Attaches a minimal HTML page to the `Response`, containing the status code, its canonical
+/// reason (or a configured message) and the request ID.
+#[derive(Clone, Default)]
+pub struct HtmlBodyExtender {
+    message: Option<String>,
+}
+
+impl HtmlBodyExtender {
+    /// Creates a new `HtmlBodyExtender` which describes the response using the canonical reason
+    /// of the status code.
+    pub fn new() -> Self {
+        HtmlBodyExtender { message: None }
+    }
+
+    /// Replaces the canonical reason of the status code with the given message.
+    pub fn with_message<M: Into<String>>(self, message: M) -> Self {
+        HtmlBodyExtender {
+            message: Some(message.into()),
+        }
+    }
+
+    fn render(&self, state: &State, status: StatusCode) -> Vec<u8> {
+        let title = format!(
+            "{} {}",
+            status.as_u16(),
+            escape_html(status.canonical_reason().unwrap_or("Unknown Status"))
+        );
+
+        let message = self.message.as_ref().map(|m| escape_html(m));
+
+        format!(
+            "<!DOCTYPE html>\n\
+             <html>\n\
+             <head><title>{title}</title></head>\n\
+             <body>\n\
+             <h1>{title}</h1>\n\
+             {message}\
+             <p>Request ID: <code>{request_id}</code></p>\n\
+             </body>\n\
+             </html>\n",
+            title = title,
+            message = message
+                .map(|m| format!("<p>{}</p>\n", m))
+                .unwrap_or_else(String::new),
+            request_id = escape_html(request_id(state)),
+        ).into_bytes()
+    }
+}
+
+impl ResponseExtender<Body> for HtmlBodyExtender {
+    fn extend(&self, state: &mut State, res: &mut Response<Body>) {
+        trace!(
+            "[{}] attaching html body to {} response",
+            request_id(state),
+            res.status()
+        );
+
+        let body = self.render(state, res.status());
+        set_body(state, res, mime::TEXT_HTML_UTF_8, body);
+    }
+}
+
+/// Attaches a JSON document to the `Response`, of the form:
+///
+/// ```json
+/// {"status":404,"message":"Not Found","request_id":"..."}
+/// ```
+///
+/// The `message` is the canonical reason of the status code, unless a message has been configured.
+#[derive(Clone, Default)]
+pub struct JsonBodyExtender {
+    message: Option<String>,
+}
+
+impl JsonBodyExtender {
+    /// Creates a new `JsonBodyExtender` which describes the response using the canonical reason
+    /// of the status code.
+    pub fn new() -> Self {
+        JsonBodyExtender { message: None }
+    }
+
+    /// Replaces the canonical reason of the status code with the given message.
+    pub fn with_message<M: Into<String>>(self, message: M) -> Self {
+        JsonBodyExtender {
+            message: Some(message.into()),
+        }
+    }
+
+    fn render(&self, state: &State, status: StatusCode) -> Vec<u8> {
+        let message = match self.message {
+            Some(ref message) => message.as_str(),
+            None => status.canonical_reason().unwrap_or("Unknown Status"),
+        };
+
+        format!(
+            "{{\"status\":{},\"message\":\"{}\",\"request_id\":\"{}\"}}",
+            status.as_u16(),
+            escape_json(message),
+            escape_json(request_id(state)),
+        ).into_bytes()
+    }
+}
+
+impl ResponseExtender<Body> for JsonBodyExtender {
+    fn extend(&self, state: &mut State, res: &mut Response<Body>) {
+        trace!(
+            "[{}] attaching json body to {} response",
+            request_id(state),
+            res.status()
+        );
+
+        let body = self.render(state, res.status());
+        set_body(state, res, mime::APPLICATION_JSON, body);
+    }
+}
+
+/// Chooses between a `HtmlBodyExtender` and a `JsonBodyExtender` based on the `Accept` header of
+/// the request.
+///
+/// The JSON body is used only when the client prefers `application/json` over `text/html`, taking
+/// quality values into account. A missing or unparseable `Accept` header results in the HTML body.
+#[derive(Clone, Default)]
+pub struct NegotiatedBodyExtender {
+    html: HtmlBodyExtender,
+    json: JsonBodyExtender,
+}
+
+impl NegotiatedBodyExtender {
+    /// Creates a new `NegotiatedBodyExtender` using the default HTML and JSON extenders.
+    pub fn new() -> Self {
+        NegotiatedBodyExtender::with_extenders(HtmlBodyExtender::new(), JsonBodyExtender::new())
+    }
+
+    /// Creates a new `NegotiatedBodyExtender` using the provided HTML and JSON extenders.
+    pub fn with_extenders(html: HtmlBodyExtender, json: JsonBodyExtender) -> Self {
+        NegotiatedBodyExtender { html, json }
+    }
+}
+
+impl ResponseExtender<Body> for NegotiatedBodyExtender {
+    fn extend(&self, state: &mut State, res: &mut Response<Body>) {
+        let use_json = HeaderMap::try_borrow_from(state)
+            .and_then(|headers| headers.get(ACCEPT))
+            .map(prefers_json)
+            .unwrap_or(false);
+
+        if use_json {
+            self.json.extend(state, res)
+        } else {
+            self.html.extend(state, res)
+        }
+    }
+}
+
+/// Determines whether the `Accept` header value ranks `application/json` strictly higher than
+/// `text/html`.
+fn prefers_json(accept: &HeaderValue) -> bool {
+    let supported = [mime::TEXT_HTML, mime::APPLICATION_JSON];
+    negotiate_header(Some(accept), &supported) == Some(mime::APPLICATION_JSON)
+}
+
+fn set_body(state: &State, res: &mut Response<Body>, mime: Mime, body: Vec<u8>) {
+    check_charset(state, &mime, &body);
+
+    {
+        let headers = res.headers_mut();
+        headers.insert(CONTENT_TYPE, mime.as_ref().parse().unwrap());
+        headers.insert(CONTENT_LENGTH, body.len().to_string().parse().unwrap());
+    }
+
+    let is_head = Method::try_borrow_from(state)
+        .map(|method| *method == Method::HEAD)
+        .unwrap_or(false);
+
+    if !is_head {
+        *res.body_mut() = body.into();
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+pub(crate) fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, Response, StatusCode};
+
+    use helpers::http::header::X_REQUEST_ID;
+    use router::builder::*;
+    use router::Router;
+    use state::request_id::RequestIdConfig;
+    use state::set_request_id_with;
+    use test::TestServer;
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new(Body::empty()))
+    }
+
+    fn router() -> Router {
+        build_simple_router(|route| {
+            route.add_response_extender(StatusCode::NOT_FOUND, NegotiatedBodyExtender::new());
+            route.add_response_extender(
+                StatusCode::METHOD_NOT_ALLOWED,
+                JsonBodyExtender::new().with_message("Use \"GET\" instead"),
+            );
+
+            route.get("/").to(handler);
+        })
+    }
+
+    #[test]
+    fn json_body_for_missing_route_when_accepted() {
+        let test_server = TestServer::new(router()).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/missing")
+            .with_header(X_REQUEST_ID, HeaderValue::from_static("missing-id"))
+            .with_header(ACCEPT, HeaderValue::from_static("text/html;q=0.5, application/json"))
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            "{\"status\":404,\"message\":\"Not Found\",\"request_id\":\"missing-id\"}"
+        );
+    }
+
+    #[test]
+    fn html_body_for_missing_route_by_default() {
+        let test_server = TestServer::new(router()).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/missing")
+            .with_header(X_REQUEST_ID, HeaderValue::from_static("missing-id"))
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+
+        let body = response.read_utf8_body().unwrap();
+        assert!(body.contains("<h1>404 Not Found</h1>"));
+        assert!(body.contains("<code>missing-id</code>"));
+    }
+
+    #[test]
+    fn html_body_escapes_request_id() {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+
+        let config = RequestIdConfig::new().with_generator(|| "<generated-id>".to_owned());
+        set_request_id_with(&mut state, &config);
+
+        let body = HtmlBodyExtender::new().render(&state, StatusCode::NOT_FOUND);
+        let body = String::from_utf8(body).unwrap();
+        assert!(body.contains("<code>&lt;generated-id&gt;</code>"));
+    }
+
+    #[test]
+    fn configured_body_for_wrong_method() {
+        let test_server = TestServer::new(router()).unwrap();
+        let response = test_server
+            .client()
+            .delete("http://localhost/")
+            .with_header(X_REQUEST_ID, HeaderValue::from_static("method-id"))
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            "{\"status\":405,\"message\":\"Use \\\"GET\\\" instead\",\"request_id\":\"method-id\"}"
+        );
+    }
+
+    #[test]
+    fn accept_negotiation() {
+        let check = |accept: &'static str| prefers_json(&HeaderValue::from_static(accept));
+
+        assert!(check("application/json"));
+        assert!(check("application/*"));
+        assert!(check("text/html;q=0.1, application/json;q=0.2"));
+        assert!(!check("*/*"));
+        assert!(!check("text/html, application/json"));
+        assert!(!check("text/*;q=0.9, application/json;q=0.9"));
+        assert!(!check("application/json;q=0.2, */*;q=0.8"));
+        assert!(!check("not a mime type"));
+    }
+}
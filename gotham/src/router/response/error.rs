@@ -0,0 +1,34 @@
+//! Defines functionality for overriding the `Response` generated from a `HandlerError`.
+
+use std::panic::RefUnwindSafe;
+
+use hyper::{Body, Response};
+
+use handler::HandlerError;
+use state::{request_id, State};
+
+/// Overrides the `Response` which is generated when a `HandlerError` reaches the `Router`.
+///
+/// The mapper is invoked for every `HandlerError` produced by a `Handler` or by any `Middleware`
+/// in the pipelines of the `Router`. Returning `None` falls back to the default conversion
+/// provided by `HandlerError::into_response`.
+///
+/// The request ID is available via `gotham::state::request_id` as usual, so the mapper is able to
+/// include it in the generated `Response`.
+pub trait HandlerErrorMapper: RefUnwindSafe {
+    /// Creates a `Response` for the given `HandlerError`, or `None` to use the default.
+    fn map_error(&self, state: &State, err: &HandlerError) -> Option<Response<Body>>;
+}
+
+impl<F> HandlerErrorMapper for F
+where
+    F: Fn(&State, &HandlerError) -> Option<Response<Body>> + Send + Sync + RefUnwindSafe,
+{
+    fn map_error(&self, state: &State, err: &HandlerError) -> Option<Response<Body>> {
+        trace!(
+            "[{}] running closure based handler error mapper",
+            request_id(state)
+        );
+        self(state, err)
+    }
+}
@@ -0,0 +1,240 @@
+//! Defines functionality for overriding the `Response` generated when an extractor fails.
+
+use std::panic::RefUnwindSafe;
+
+use hyper::{Body, Response, StatusCode};
+use mime;
+
+use extractor::{ExtractionFailure, ExtractorKind};
+use helpers::http::response::create_response;
+use router::response::body::escape_json;
+use state::{request_id, State};
+
+/// Creates the `Response` for a request which matched a route, but whose path or query string
+/// couldn't be extracted by the extractors of that route.
+///
+/// When set on the `Router`, the mapper is used for every extraction failure in place of the
+/// `StaticResponseExtender` of the extractor. Without a mapper, the `Response` is created by the
+/// `StaticResponseExtender` alone, which only sets `400 Bad Request` when derived.
+pub trait ExtractionFailureMapper: RefUnwindSafe {
+    /// Creates a `Response` for the given `ExtractionFailure`.
+    fn map_failure(&self, state: &State, failure: &ExtractionFailure) -> Response<Body>;
+}
+
+impl<F> ExtractionFailureMapper for F
+where
+    F: Fn(&State, &ExtractionFailure) -> Response<Body> + Send + Sync + RefUnwindSafe,
+{
+    fn map_failure(&self, state: &State, failure: &ExtractionFailure) -> Response<Body> {
+        trace!(
+            "[{}] running closure based extraction failure mapper",
+            request_id(state)
+        );
+        self(state, failure)
+    }
+}
+
+/// An `ExtractionFailureMapper` which responds with `400 Bad Request` and a JSON body describing
+/// the failure, such as (without the whitespace):
+///
+/// ```json
+/// {
+///   "error": "invalid parameter",
+///   "source": "path",
+///   "field": "id",
+///   "message": "invalid digit found in string"
+/// }
+/// ```
+///
+/// The `source` is either `path` or `query_string`, and the `field` is `null` when the failure
+/// isn't specific to one field.
+pub fn json_extraction_failure(state: &State, failure: &ExtractionFailure) -> Response<Body> {
+    let source = match failure.kind() {
+        ExtractorKind::Path => "path",
+        ExtractorKind::QueryString => "query_string",
+    };
+
+    let field = match failure.field() {
+        Some(field) => format!(r#""{}""#, escape_json(field)),
+        None => "null".to_owned(),
+    };
+
+    let body = format!(
+        r#"{{"error":"invalid parameter","source":"{}","field":{},"message":"{}"}}"#,
+        source,
+        field,
+        escape_json(failure.message())
+    );
+
+    create_response(
+        state,
+        StatusCode::BAD_REQUEST,
+        Some((body.into_bytes(), mime::APPLICATION_JSON)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::CONTENT_TYPE;
+
+    use router::builder::*;
+    use router::response::extender::StaticResponseExtender;
+    use router::Router;
+    use state::{FromState, StateData};
+    use test::TestServer;
+
+    #[derive(Deserialize)]
+    struct UserPath {
+        id: u32,
+    }
+
+    impl StateData for UserPath {}
+
+    impl StaticResponseExtender for UserPath {
+        type ResBody = Body;
+        fn extend(_: &mut State, res: &mut Response<Body>) {
+            *res.status_mut() = StatusCode::BAD_REQUEST;
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct PageQuery {
+        page: u32,
+    }
+
+    impl StateData for PageQuery {}
+
+    impl StaticResponseExtender for PageQuery {
+        type ResBody = Body;
+        fn extend(state: &mut State, res: &mut Response<Body>) {
+            let field = ExtractionFailure::borrow_from(state)
+                .field()
+                .unwrap_or("")
+                .to_owned();
+            *res = create_response(
+                state,
+                StatusCode::BAD_REQUEST,
+                Some((field.into_bytes(), mime::TEXT_PLAIN)),
+            );
+        }
+    }
+
+    fn handler(mut state: State) -> (State, Response<Body>) {
+        let UserPath { id } = state.take();
+        let PageQuery { page } = state.take();
+        let body = format!("user {} page {}", id, page);
+        let res = create_response(
+            &state,
+            StatusCode::OK,
+            Some((body.into_bytes(), mime::TEXT_PLAIN)),
+        );
+        (state, res)
+    }
+
+    fn router(json: bool) -> Router {
+        build_simple_router(|route| {
+            if json {
+                route.set_extraction_failure_mapper(json_extraction_failure);
+            }
+
+            route
+                .get("/users/:id")
+                .with_path_extractor::<UserPath>()
+                .with_query_string_extractor::<PageQuery>()
+                .to(handler);
+        })
+    }
+
+    fn get(test_server: &TestServer, path: &str) -> (StatusCode, String) {
+        let response = test_server
+            .client()
+            .get(&format!("http://localhost{}", path))
+            .perform()
+            .unwrap();
+
+        (response.status(), response.read_utf8_body().unwrap())
+    }
+
+    #[test]
+    fn json_extraction_failure_names_field() {
+        let test_server = TestServer::new(router(true)).unwrap();
+
+        let (status, body) = get(&test_server, "/users/42?page=2");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "user 42 page 2");
+
+        let (status, body) = get(&test_server, "/users/abc?page=2");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            r#"{"error":"invalid parameter","source":"path","field":"id","#.to_owned()
+                + r#""message":"invalid digit found in string"}"#
+        );
+
+        let (status, body) = get(&test_server, "/users/42?page=last");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            r#"{"error":"invalid parameter","source":"query_string","field":"page","#.to_owned()
+                + r#""message":"invalid digit found in string"}"#
+        );
+
+        let response = test_server
+            .client()
+            .get("http://localhost/users/42")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        let body = response.read_utf8_body().unwrap();
+        assert!(body.contains(r#""source":"query_string","field":"page""#));
+    }
+
+    #[test]
+    fn extenders_are_used_without_mapper() {
+        let test_server = TestServer::new(router(false)).unwrap();
+
+        let (status, body) = get(&test_server, "/users/abc?page=2");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, "");
+
+        let (status, body) = get(&test_server, "/users/42?page=last");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, "page");
+    }
+
+    #[test]
+    fn closure_mapper() {
+        let router = build_simple_router(|route| {
+            route.set_extraction_failure_mapper(|state: &State, failure: &ExtractionFailure| {
+                let body = format!("{:?} {}", failure.kind(), failure.field().unwrap());
+                create_response(
+                    state,
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Some((body.into_bytes(), mime::TEXT_PLAIN)),
+                )
+            });
+
+            route
+                .get("/users/:id")
+                .with_path_extractor::<UserPath>()
+                .with_query_string_extractor::<PageQuery>()
+                .to(handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+
+        let (status, body) = get(&test_server, "/users/abc?page=2");
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body, "Path id");
+
+        let (status, body) = get(&test_server, "/users/42?page=last");
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body, "QueryString page");
+    }
+}
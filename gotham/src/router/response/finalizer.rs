@@ -1,10 +1,14 @@
 //! Defines functionality for finalizing a `Response` after all pipelines, middlewares, handlers
 //! and internal extenders have completed.
 
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::iter;
+use std::panic::RefUnwindSafe;
 use std::sync::Arc;
 
 use futures::future;
+use hyper::body::Payload;
 use hyper::{Body, Response, StatusCode};
 
 use handler::HandlerFuture;
@@ -12,6 +16,127 @@ use state::{request_id, State};
 
 use router::response::extender::ResponseExtender;
 
+/// A class of status codes, identified by the first digit of the code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StatusClass {
+    /// `1xx` status codes.
+    Informational,
+    /// `2xx` status codes.
+    Success,
+    /// `3xx` status codes.
+    Redirection,
+    /// `4xx` status codes.
+    ClientError,
+    /// `5xx` status codes.
+    ServerError,
+}
+
+impl StatusClass {
+    /// Determines if the status code belongs to this class.
+    pub fn contains(&self, status: StatusCode) -> bool {
+        let first_digit = match *self {
+            StatusClass::Informational => 1,
+            StatusClass::Success => 2,
+            StatusClass::Redirection => 3,
+            StatusClass::ClientError => 4,
+            StatusClass::ServerError => 5,
+        };
+
+        status.as_u16() / 100 == first_digit
+    }
+}
+
+/// Describes the status codes for which a `ResponseExtender` is invoked.
+///
+/// When more than one registration matches a `Response`, the most specific is used: a single
+/// status code, then a set of codes (smaller sets first), then a `StatusClass`, then a
+/// predicate. Of equally specific registrations, the most recent is used.
+///
+/// A `StatusCode` or `StatusClass` can be converted into a `StatusMatcher` with `into()`, which
+/// allows either to be passed to `RouterBuilder::add_response_extender`.
+pub struct StatusMatcher {
+    kind: MatcherKind,
+}
+
+enum MatcherKind {
+    Code(StatusCode),
+    Codes(Vec<StatusCode>),
+    Class(StatusClass),
+    Predicate(Box<Fn(StatusCode) -> bool + Send + Sync + RefUnwindSafe>),
+}
+
+impl StatusMatcher {
+    /// Matches a single status code.
+    pub fn code(status: StatusCode) -> StatusMatcher {
+        StatusMatcher {
+            kind: MatcherKind::Code(status),
+        }
+    }
+
+    /// Matches any of the given status codes.
+    pub fn codes<I>(statuses: I) -> StatusMatcher
+    where
+        I: IntoIterator<Item = StatusCode>,
+    {
+        let mut statuses: Vec<StatusCode> = statuses.into_iter().collect();
+        statuses.sort_by_key(StatusCode::as_u16);
+        statuses.dedup();
+
+        StatusMatcher {
+            kind: MatcherKind::Codes(statuses),
+        }
+    }
+
+    /// Matches every status code in the class.
+    pub fn class(class: StatusClass) -> StatusMatcher {
+        StatusMatcher {
+            kind: MatcherKind::Class(class),
+        }
+    }
+
+    /// Matches the status codes for which `predicate` returns `true`.
+    pub fn predicate<F>(predicate: F) -> StatusMatcher
+    where
+        F: Fn(StatusCode) -> bool + Send + Sync + RefUnwindSafe + 'static,
+    {
+        StatusMatcher {
+            kind: MatcherKind::Predicate(Box::new(predicate)),
+        }
+    }
+
+    /// Determines if the status code is matched.
+    pub fn matches(&self, status: StatusCode) -> bool {
+        match self.kind {
+            MatcherKind::Code(code) => code == status,
+            MatcherKind::Codes(ref codes) => codes.contains(&status),
+            MatcherKind::Class(class) => class.contains(status),
+            MatcherKind::Predicate(ref predicate) => predicate(status),
+        }
+    }
+
+    /// Orders matchers from the most specific to the least.
+    fn specificity(&self) -> (u8, usize) {
+        match self.kind {
+            MatcherKind::Code(_) => (0, 1),
+            MatcherKind::Codes(ref codes) => (1, codes.len()),
+            MatcherKind::Class(_) => (2, 0),
+            MatcherKind::Predicate(_) => (3, 0),
+        }
+    }
+}
+
+impl From<StatusCode> for StatusMatcher {
+    fn from(status: StatusCode) -> StatusMatcher {
+        StatusMatcher::code(status)
+    }
+}
+
+impl From<StatusClass> for StatusMatcher {
+    fn from(class: StatusClass) -> StatusMatcher {
+        StatusMatcher::class(class)
+    }
+}
+
 /// Holds an immutable collection of `ResponseExtender` values, as configured using
 /// `ResponseFinalizerBuilder::add`. This type is constructed automatically when using the
 /// `gotham::router::builder` API. See `RouterBuilder::add_response_extender` for details on
@@ -19,11 +144,13 @@ use router::response::extender::ResponseExtender;
 #[derive(Clone)]
 pub struct ResponseFinalizer {
     data: Arc<HashMap<StatusCode, Box<ResponseExtender<Body> + Send + Sync>>>,
+    matchers: Arc<Vec<(StatusMatcher, Box<ResponseExtender<Body> + Send + Sync>)>>,
 }
 
 /// Builds an immutable `ResponseFinalizer`.
 pub struct ResponseFinalizerBuilder {
     data: HashMap<StatusCode, Box<ResponseExtender<Body> + Send + Sync>>,
+    matchers: Vec<(StatusMatcher, Box<ResponseExtender<Body> + Send + Sync>)>,
 }
 
 impl ResponseFinalizerBuilder {
@@ -38,7 +165,10 @@ impl ResponseFinalizerBuilder {
 
     pub(in router) fn internal_new() -> Self {
         let handlers = HashMap::new();
-        ResponseFinalizerBuilder { data: handlers }
+        ResponseFinalizerBuilder {
+            data: handlers,
+            matchers: Vec::new(),
+        }
     }
 
     /// Add an Finalizer for responses that have been assigned this status_code.
@@ -51,10 +181,32 @@ impl ResponseFinalizerBuilder {
         self.data.insert(status_code, extender);
     }
 
+    /// Add an Finalizer for responses that have been assigned any status code matched by
+    /// `matcher`.
+    pub fn add_matching(
+        &mut self,
+        matcher: StatusMatcher,
+        extender: Box<ResponseExtender<Body> + Send + Sync>,
+    ) {
+        match matcher.kind {
+            MatcherKind::Code(status_code) => self.add(status_code, extender),
+            _ => {
+                trace!(" adding response extender for matched status codes");
+                self.matchers.push((matcher, extender));
+            }
+        }
+    }
+
+    /// Determines if no extenders have been added.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty() && self.matchers.is_empty()
+    }
+
     /// Finalize population of error handlers for the application, ready for use by a `Router`
     pub fn finalize(self) -> ResponseFinalizer {
         ResponseFinalizer {
             data: Arc::new(self.data),
+            matchers: Arc::new(self.matchers),
         }
     }
 }
@@ -62,25 +214,201 @@ impl ResponseFinalizerBuilder {
 impl ResponseFinalizer {
     /// Finalize the `Response` if a `ResponseFinalizer` has been supplied for the
     /// status code assigned to the `Response`.
-    pub fn finalize(&self, mut state: State, mut res: Response<Body>) -> Box<HandlerFuture> {
-        match self.data.get(&res.status()) {
-            Some(extender) => {
-                trace!(
-                    "[{}] invoking {} response extender",
-                    request_id(&state),
-                    res.status()
-                );
-                extender.extend(&mut state, &mut res);
-            }
-            None => {
-                trace!(
-                    "[{}] no response extender for {}",
-                    request_id(&state),
-                    res.status()
-                );
+    ///
+    /// Extenders are only invoked when the `Response` has no body, so that a body provided by a
+    /// `Handler` is never replaced.
+    pub fn finalize(&self, state: State, res: Response<Body>) -> Box<HandlerFuture> {
+        self.finalize_within(&[], state, res)
+    }
+
+    /// Finalize the `Response` as `finalize` does, but using an extender from the first of
+    /// `scoped` which has one for the status code, in preference to those of this
+    /// `ResponseFinalizer`. This allows the extenders registered for a route or scope to override
+    /// those of the `Router`.
+    pub(crate) fn finalize_within(
+        &self,
+        scoped: &[ResponseFinalizer],
+        mut state: State,
+        mut res: Response<Body>,
+    ) -> Box<HandlerFuture> {
+        if !res.body().is_end_stream() {
+            trace!(
+                "[{}] {} response has a body, skipping response extender",
+                request_id(&state),
+                res.status()
+            );
+        } else {
+            let status = res.status();
+            let extender = scoped
+                .iter()
+                .chain(iter::once(self))
+                .filter_map(|finalizer| finalizer.extender(status))
+                .next();
+
+            match extender {
+                Some(extender) => {
+                    trace!(
+                        "[{}] invoking {} response extender",
+                        request_id(&state),
+                        res.status()
+                    );
+                    extender.extend(&mut state, &mut res);
+                }
+                None => {
+                    trace!(
+                        "[{}] no response extender for {}",
+                        request_id(&state),
+                        res.status()
+                    );
+                }
             }
         }
 
         Box::new(future::ok((state, res)))
     }
+
+    /// Finds the most specific extender registered for the status code.
+    fn extender(&self, status: StatusCode) -> Option<&(ResponseExtender<Body> + Send + Sync)> {
+        if let Some(extender) = self.data.get(&status) {
+            return Some(&**extender);
+        }
+
+        self.matchers
+            .iter()
+            .enumerate()
+            .filter(|&(_, &(ref matcher, _))| matcher.matches(status))
+            .min_by_key(|&(i, &(ref matcher, _))| (matcher.specificity(), Reverse(i)))
+            .map(|(_, &(_, ref extender))| &**extender)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::WARNING;
+    use hyper::Uri;
+    use mime;
+
+    use helpers::http::response::create_response;
+    use router::builder::*;
+    use router::Router;
+    use state::FromState;
+    use test::TestServer;
+
+    fn warn(text: &'static str) -> impl Fn(&mut State, &mut Response<Body>) + Send + Sync {
+        move |_state: &mut State, res: &mut Response<Body>| {
+            res.headers_mut()
+                .insert(WARNING, format!("199 - \"{}\"", text).parse().unwrap());
+        }
+    }
+
+    fn respond(state: State) -> (State, Response<Body>) {
+        let res = {
+            let status = Uri::borrow_from(&state)
+                .path()
+                .trim_left_matches('/')
+                .parse()
+                .ok()
+                .and_then(|code| StatusCode::from_u16(code).ok())
+                .unwrap_or(StatusCode::OK);
+
+            create_response(&state, status, None)
+        };
+
+        (state, res)
+    }
+
+    fn respond_with_body(state: State) -> (State, Response<Body>) {
+        let res = create_response(
+            &state,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Some((b"handled".to_vec(), mime::TEXT_PLAIN)),
+        );
+        (state, res)
+    }
+
+    fn router() -> Router {
+        build_simple_router(|route| {
+            route.add_response_extender(StatusClass::ServerError, warn("server error"));
+            route.add_response_extender(StatusCode::SERVICE_UNAVAILABLE, warn("unavailable"));
+            route.add_response_extender(
+                StatusMatcher::codes(vec![StatusCode::BAD_GATEWAY, StatusCode::GATEWAY_TIMEOUT]),
+                warn("gateway"),
+            );
+            route.add_response_extender(
+                StatusMatcher::predicate(|status: StatusCode| status.as_u16() >= 400),
+                warn("error"),
+            );
+
+            route.get("/:status").to(respond);
+            route.get("/body/500").to(respond_with_body);
+        })
+    }
+
+    fn warning(test_server: &TestServer, path: &str) -> Option<String> {
+        let response = test_server
+            .client()
+            .get(&format!("http://localhost{}", path))
+            .perform()
+            .unwrap();
+
+        response
+            .headers()
+            .get(WARNING)
+            .map(|warning| warning.to_str().unwrap().to_owned())
+    }
+
+    #[test]
+    fn most_specific_extender_is_used() {
+        let test_server = TestServer::new(router()).unwrap();
+
+        let cases = vec![
+            ("/503", Some("199 - \"unavailable\"")),
+            ("/500", Some("199 - \"server error\"")),
+            ("/502", Some("199 - \"gateway\"")),
+            ("/504", Some("199 - \"gateway\"")),
+            ("/404", Some("199 - \"error\"")),
+            ("/200", None),
+        ];
+
+        for (path, expected) in cases {
+            assert_eq!(
+                warning(&test_server, path),
+                expected.map(str::to_owned),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn extenders_skip_responses_with_body() {
+        let test_server = TestServer::new(router()).unwrap();
+        assert_eq!(warning(&test_server, "/body/500"), None);
+    }
+
+    #[test]
+    fn later_registrations_win_ties() {
+        let router = build_simple_router(|route| {
+            route.add_response_extender(StatusClass::ClientError, warn("first"));
+            route.add_response_extender(StatusClass::ClientError, warn("second"));
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        assert_eq!(
+            warning(&test_server, "/missing"),
+            Some("199 - \"second\"".to_owned())
+        );
+    }
+
+    #[test]
+    fn status_classes() {
+        assert!(StatusClass::Informational.contains(StatusCode::CONTINUE));
+        assert!(StatusClass::Success.contains(StatusCode::NO_CONTENT));
+        assert!(StatusClass::Redirection.contains(StatusCode::FOUND));
+        assert!(StatusClass::ClientError.contains(StatusCode::NOT_FOUND));
+        assert!(StatusClass::ServerError.contains(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!StatusClass::ServerError.contains(StatusCode::NOT_FOUND));
+    }
 }
@@ -1,4 +1,7 @@
 //! Defines `Router` functionality which acts on the `Response`
 
+pub mod body;
+pub mod error;
 pub mod extender;
+pub mod extraction;
 pub mod finalizer;
@@ -1,11 +1,13 @@
 //! Defines the route `Dispatcher` and supporting types.
 
-use futures::future;
+use futures::{future, Future};
 use std::panic::RefUnwindSafe;
 
 use handler::{Handler, HandlerFuture, IntoHandlerError, NewHandler};
 use pipeline::chain::PipelineHandleChain;
 use pipeline::set::PipelineSet;
+use router::route::extensions::RouteExtensions;
+use state::times::{mark_handler_entered, mark_response_started};
 use state::{request_id, State};
 
 /// Used by `Router` to dispatch requests via pipelines and finally into the configured `Handler`.
@@ -24,6 +26,7 @@ where
     new_handler: H,
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
+    extensions: RouteExtensions,
 }
 
 impl<H, C, P> DispatcherImpl<H, C, P>
@@ -45,8 +48,15 @@ where
             new_handler,
             pipeline_chain,
             pipelines,
+            extensions: RouteExtensions::new(),
         }
     }
+
+    /// Sets the `RouteExtensions` which are put into `State` before the `pipeline_chain` is
+    /// invoked.
+    pub fn with_extensions(self, extensions: RouteExtensions) -> Self {
+        DispatcherImpl { extensions, ..self }
+    }
 }
 
 impl<H, C, P> Dispatcher for DispatcherImpl<H, C, P>
@@ -56,12 +66,26 @@ where
     C: PipelineHandleChain<P>,
     P: RefUnwindSafe,
 {
-    fn dispatch(&self, state: State) -> Box<HandlerFuture> {
+    fn dispatch(&self, mut state: State) -> Box<HandlerFuture> {
+        self.extensions.put_into(&mut state);
+
         match self.new_handler.new_handler() {
             Ok(h) => {
                 trace!("[{}] cloning handler", request_id(&state));
                 self.pipeline_chain
-                    .call(&self.pipelines, state, move |state| h.handle(state))
+                    .call(&self.pipelines, state, move |mut state| {
+                        mark_handler_entered(&mut state);
+                        Box::new(h.handle(state).then(|result| match result {
+                            Ok((mut state, res)) => {
+                                mark_response_started(&mut state);
+                                Ok((state, res))
+                            }
+                            Err((mut state, err)) => {
+                                mark_response_started(&mut state);
+                                Err((state, err))
+                            }
+                        }))
+                    })
             }
             Err(e) => {
                 trace!("[{}] error cloning handler", request_id(&state));
@@ -0,0 +1,72 @@
+//! Defines static values attached to a route, and inserted into `State` when it is dispatched.
+
+use std::panic::RefUnwindSafe;
+
+use state::{State, StateData};
+
+type Insert = Fn(&mut State) + Send + Sync + RefUnwindSafe;
+
+/// Values attached to a route via `DefineSingleRoute::with_extension`, such as a required
+/// permission or cache policy.
+///
+/// When the route is dispatched, a copy of each value is put into `State` before any `Pipeline`
+/// is invoked, so that `Middleware` can read it via `FromState`. Only one value of each type is
+/// retained, with a later value replacing an earlier value of the same type.
+#[derive(Default)]
+pub struct RouteExtensions {
+    inserts: Vec<Box<Insert>>,
+}
+
+impl RouteExtensions {
+    /// Creates an empty `RouteExtensions`.
+    pub fn new() -> RouteExtensions {
+        RouteExtensions::default()
+    }
+
+    /// Adds a value, which is copied into `State` for each request dispatched to the route.
+    pub fn insert<T>(&mut self, value: T)
+    where
+        T: StateData + Clone + Sync + RefUnwindSafe,
+    {
+        self.inserts
+            .push(Box::new(move |state: &mut State| state.put(value.clone())));
+    }
+
+    /// Puts a copy of each value into `State`, in the order they were added.
+    pub(crate) fn put_into(&self, state: &mut State) {
+        for insert in &self.inserts {
+            insert(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use state::FromState;
+
+    #[derive(Clone)]
+    struct Permission(&'static str);
+
+    impl StateData for Permission {}
+
+    #[derive(Clone)]
+    struct OperationId(&'static str);
+
+    impl StateData for OperationId {}
+
+    #[test]
+    fn later_values_replace_earlier_values() {
+        let mut extensions = RouteExtensions::new();
+        extensions.insert(Permission("read"));
+        extensions.insert(OperationId("listUsers"));
+        extensions.insert(Permission("admin"));
+
+        let mut state = State::new();
+        extensions.put_into(&mut state);
+
+        assert_eq!(Permission::borrow_from(&state).0, "admin");
+        assert_eq!(OperationId::borrow_from(&state).0, "listUsers");
+    }
+}
@@ -1,19 +1,24 @@
 //! Defines the `AcceptHeaderRouterMatcher`.
 
-use hyper::header::{HeaderMap, HeaderValue, ACCEPT};
 use hyper::StatusCode;
 use mime;
 
-use error;
+use helpers::http::accept::negotiate;
 use router::non_match::RouteNonMatch;
+use router::route::matcher::MatcherSummary;
 use router::route::RouteMatcher;
-use state::{request_id, FromState, State};
+use state::{request_id, State};
 
 /// A `RouteMatcher` that succeeds when the `Request` has been made with an `Accept` header that
-/// includes one or more supported media types. A missing `Accept` header, or the value of `*/*`
-/// will also positvely match.
+/// includes one or more supported media types. A missing `Accept` header matches any route.
 ///
-/// Quality values within `Accept` header values are not considered by this matcher.
+/// Quality values and wildcards are handled as they are by `gotham::helpers::http::accept`, so a
+/// supported type matches when the most specific media range which includes it (e.g.
+/// `application/json`, then `application/*`, then `*/*`) has a non-zero quality value. When a route
+/// doesn't match, the `Router` tries the other routes defined for the request path, and responds
+/// with `406 Not Acceptable` when none of them match. Routes are tried in the order they were
+/// defined, and the first which matches is chosen even if the client prefers the media type of a
+/// later route, so routes should be defined in the order of the server's preference.
 ///
 /// # Examples
 ///
@@ -59,12 +64,18 @@ use state::{request_id, FromState, State};
 /// headers.insert(ACCEPT, "application/json".parse().unwrap());
 /// state.put(headers);
 /// assert!(matcher.is_match(&state).is_ok());
-
+///
 /// // Accept header of `image/*`
 /// let mut headers = HeaderMap::new();
 /// headers.insert(ACCEPT, "image/*".parse().unwrap());
 /// state.put(headers);
 /// assert!(matcher.is_match(&state).is_ok());
+///
+/// // Supported media types excluded via a quality value of zero
+/// let mut headers = HeaderMap::new();
+/// headers.insert(ACCEPT, "*/*, application/json;q=0, image/*;q=0".parse().unwrap());
+/// state.put(headers);
+/// assert!(matcher.is_match(&state).is_err());
 /// #
 /// #   });
 /// # }
@@ -85,37 +96,115 @@ impl AcceptHeaderRouteMatcher {
 
 impl RouteMatcher for AcceptHeaderRouteMatcher {
     /// Determines if the `Request` was made using an `Accept` header that includes one or more
-    /// supported media types. A missing `Accept` header, or the value of `*/*` will also positvely
-    /// match.
-    ///
-    /// Quality values within `Accept` header values are not considered by the matcher, as the
-    /// matcher is only able to indicate whether a successful match has been found.
+    /// supported media types with a non-zero quality value. A missing `Accept` header will also
+    /// positively match.
     fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
-        // Request method is valid, ensure valid Accept header
-        match HeaderMap::borrow_from(state).get(ACCEPT) {
-            // The client has not specified an `Accept` header.
-            None => Ok(()),
-
-            // Or the header is any type, so it's fine.
-            Some(header) if header == "*/*" => Ok(()),
-
-            // Otherwise we have to validate the header is a match.
-            Some(mime_header) => parse_mime_type(mime_header)
-                .map_err(|_| RouteNonMatch::new(StatusCode::NOT_ACCEPTABLE))
-                .and_then(|mime_type| {
-                    if self.supported_media_types.contains(&mime_type) {
-                        return Ok(());
-                    }
-                    trace!(
-                        "[{}] did not provide an Accept with media types supported by this Route",
-                        request_id(&state)
-                    );
-                    Err(RouteNonMatch::new(StatusCode::NOT_ACCEPTABLE))
-                }),
+        match negotiate(state, &self.supported_media_types) {
+            Some(_) => Ok(()),
+            None => {
+                trace!(
+                    "[{}] did not provide an Accept with media types supported by this Route",
+                    request_id(&state)
+                );
+                Err(RouteNonMatch::new(StatusCode::NOT_ACCEPTABLE))
+            }
         }
     }
+
+    fn summarize(&self, summary: &mut MatcherSummary) {
+        let media_types: Vec<&str> = self
+            .supported_media_types
+            .iter()
+            .map(AsRef::as_ref)
+            .collect();
+        summary.add_condition(format!("accept: {}", media_types.join(", ")));
+    }
 }
 
-fn parse_mime_type(hv: &HeaderValue) -> error::Result<mime::Mime> {
-    Ok(hv.to_str()?.parse()?)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::{HeaderMap, HeaderValue, ACCEPT};
+    use hyper::{Body, Response};
+
+    use router::builder::*;
+    use test::TestServer;
+
+    fn is_match(accept: Option<&str>) -> bool {
+        let matcher = AcceptHeaderRouteMatcher::new(vec![mime::APPLICATION_JSON]);
+
+        let mut headers = HeaderMap::new();
+        if let Some(accept) = accept {
+            headers.insert(ACCEPT, accept.parse().unwrap());
+        }
+
+        let mut state = State::new();
+        state.put(headers);
+        matcher.is_match(&state).is_ok()
+    }
+
+    #[test]
+    fn quality_values_and_wildcards() {
+        assert!(is_match(None));
+        assert!(is_match(Some("*/*")));
+        assert!(is_match(Some("application/*")));
+        assert!(is_match(Some("text/html, application/json;q=0.1")));
+        assert!(is_match(Some("text/*, */*;q=0.5")));
+
+        assert!(!is_match(Some("text/html")));
+        assert!(!is_match(Some("application/json;q=0")));
+        assert!(!is_match(Some("*/*, application/json;q=0")));
+        assert!(!is_match(Some("application/*;q=0, text/html")));
+    }
+
+    fn json_handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new("json".into()))
+    }
+
+    fn html_handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new("html".into()))
+    }
+
+    #[test]
+    fn router_selects_route_by_accept_header() {
+        // Browsers accept `*/*` with a lower quality value, so the HTML route is defined first.
+        let router = build_simple_router(|route| {
+            route
+                .get("/report")
+                .with_accept(vec![mime::TEXT_HTML])
+                .to(html_handler);
+            route
+                .get("/report")
+                .with_accept(vec![mime::APPLICATION_JSON])
+                .to(json_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let get = |accept: Option<&'static str>| {
+            let mut req = test_server.client().get("http://localhost/report");
+            if let Some(accept) = accept {
+                req = req.with_header(ACCEPT, HeaderValue::from_static(accept));
+            }
+            req.perform().unwrap()
+        };
+
+        let response = get(Some("application/json"));
+        assert_eq!(response.read_utf8_body().unwrap(), "json");
+
+        let response = get(Some("text/html,application/xhtml+xml;q=0.9,*/*;q=0.8"));
+        assert_eq!(response.read_utf8_body().unwrap(), "html");
+
+        let response = get(Some("text/html;q=0, */*"));
+        assert_eq!(response.read_utf8_body().unwrap(), "json");
+
+        let response = get(Some("*/*"));
+        assert_eq!(response.read_utf8_body().unwrap(), "html");
+
+        let response = get(None);
+        assert_eq!(response.read_utf8_body().unwrap(), "html");
+
+        let response = get(Some("image/png"));
+        assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+    }
 }
@@ -1,10 +1,17 @@
 //! Defines the type `AndRouteMatcher`
 
 use router::non_match::RouteNonMatch;
+use router::route::matcher::MatcherSummary;
 use router::route::RouteMatcher;
 use state::State;
 
-/// Allows multiple `RouteMatcher` values to be combined when accessing a request.
+/// Allows multiple `RouteMatcher` values to be combined when accessing a request. The request
+/// must be accepted by both matchers.
+///
+/// This is how `DefineSingleRoute::add_route_matcher` combines a new matcher with those already
+/// present on a route. When both matchers fail, their `RouteNonMatch` values are combined with
+/// `RouteNonMatch::intersection`. See `OrRouteMatcher` and `NotRouteMatcher` for the other
+/// combinators.
 ///
 /// # Examples
 ///
@@ -42,7 +49,7 @@ use state::State;
 ///   // Request that fails accept header
 ///   state.put(Method::GET);
 ///   let mut headers = HeaderMap::new();
-///   headers.insert(ACCEPT, mime::TEXT.to_string().parse().unwrap());
+///   headers.insert(ACCEPT, mime::TEXT_PLAIN.to_string().parse().unwrap());
 ///   state.put(headers);
 ///   assert!(matcher.is_match(&state).is_err());
 /// #
@@ -83,4 +90,9 @@ where
             (Err(e), Err(e1)) => Err(e.intersection(e1)),
         }
     }
+
+    fn summarize(&self, summary: &mut MatcherSummary) {
+        self.t.summarize(summary);
+        self.u.summarize(summary);
+    }
 }
@@ -1,6 +1,7 @@
 //! Defines the type `AnyRouteMatcher`
 
 use router::non_match::RouteNonMatch;
+use router::route::matcher::MatcherSummary;
 use router::route::RouteMatcher;
 use state::State;
 
@@ -39,4 +40,6 @@ impl RouteMatcher for AnyRouteMatcher {
     fn is_match(&self, _state: &State) -> Result<(), RouteNonMatch> {
         Ok(())
     }
+
+    fn summarize(&self, _summary: &mut MatcherSummary) {}
 }
@@ -1,16 +1,27 @@
 //! Defines the `ContentTypeHeaderRouteMatcher`.
 
-use hyper::header::{HeaderMap, CONTENT_TYPE};
+use hyper::header::{HeaderMap, CONTENT_LENGTH, TRANSFER_ENCODING};
 use hyper::StatusCode;
 use mime;
 
+use helpers::http::request::RequestContentType;
 use router::non_match::RouteNonMatch;
+use router::route::matcher::MatcherSummary;
 use router::route::RouteMatcher;
 use state::{request_id, FromState, State};
 
 /// A `RouteMatcher` that succeeds when the `Request` has been made with a `Content-Type` header
 /// that includes a supported media type. The matcher will fail if the Content-Type
-/// header is missing.
+/// header is missing or cannot be parsed.
+///
+/// Parameters of the request media type, such as `charset` or `boundary`, are ignored unless the
+/// supported media type declares them, and a supported type of `type/*` matches any subtype. When
+/// a route doesn't match, the `Router` tries the other routes defined for the request path, and
+/// responds with `415 Unsupported Media Type` when none of them match.
+///
+/// A request without a body (one with neither a non-zero `Content-Length` nor a
+/// `Transfer-Encoding` header) is matched in the same way, unless `match_empty_body` is used to
+/// match such requests regardless of their `Content-Type`.
 ///
 /// # Examples
 ///
@@ -19,7 +30,7 @@ use state::{request_id, FromState, State};
 /// # extern crate hyper;
 /// # extern crate mime;
 /// # fn main() {
-/// #   use hyper::header::{HeaderMap, CONTENT_TYPE};
+/// #   use hyper::header::{HeaderMap, CONTENT_LENGTH, CONTENT_TYPE};
 /// #   use gotham::state::State;
 /// #   use gotham::router::route::matcher::RouteMatcher;
 /// #   use gotham::router::route::matcher::content_type::ContentTypeHeaderRouteMatcher;
@@ -39,6 +50,12 @@ use state::{request_id, FromState, State};
 /// state.put(headers);
 /// assert!(matcher.is_match(&state).is_ok());
 ///
+/// // Content type header with parameters
+/// let mut headers = HeaderMap::new();
+/// headers.insert(CONTENT_TYPE, "application/json; charset=utf-8".parse().unwrap());
+/// state.put(headers);
+/// assert!(matcher.is_match(&state).is_ok());
+///
 /// // Not a valid Content-Type header
 /// let mut headers = HeaderMap::new();
 /// headers.insert(CONTENT_TYPE, "text/plain".parse().unwrap());
@@ -51,6 +68,13 @@ use state::{request_id, FromState, State};
 /// headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
 /// state.put(headers);
 /// assert!(matcher.is_match(&state).is_ok());
+///
+/// // No body, when empty bodies are allowed
+/// let matcher = matcher.match_empty_body(true);
+/// let mut headers = HeaderMap::new();
+/// headers.insert(CONTENT_LENGTH, "0".parse().unwrap());
+/// state.put(headers);
+/// assert!(matcher.is_match(&state).is_ok());
 /// #
 /// #   });
 /// # }
@@ -58,29 +82,60 @@ use state::{request_id, FromState, State};
 #[derive(Clone)]
 pub struct ContentTypeHeaderRouteMatcher {
     supported_media_types: Vec<mime::Mime>,
+    match_empty_body: bool,
 }
 impl ContentTypeHeaderRouteMatcher {
     /// Creates a new `ContentTypeHeaderRouteMatcher`
     pub fn new(supported_media_types: Vec<mime::Mime>) -> Self {
         ContentTypeHeaderRouteMatcher {
             supported_media_types,
+            match_empty_body: false,
+        }
+    }
+
+    /// Sets whether a request without a body matches regardless of its `Content-Type` header.
+    /// This is disabled by default, so such a request must still provide a supported
+    /// `Content-Type`.
+    pub fn match_empty_body(self, match_empty_body: bool) -> Self {
+        ContentTypeHeaderRouteMatcher {
+            match_empty_body,
+            ..self
         }
     }
 }
 
 impl RouteMatcher for ContentTypeHeaderRouteMatcher {
     /// Determines if the `Request` was made using a `Content-Type` header that includes a
-    /// supported media type. A missing `Content-Type` header will not match.
+    /// supported media type. A missing `Content-Type` header will not match, unless the request
+    /// has no body and `match_empty_body` is set.
     fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
-        match HeaderMap::borrow_from(state).get(CONTENT_TYPE) {
-            // The client has not specified a `Content-Type` header.
+        let headers = HeaderMap::borrow_from(state);
+        if self.match_empty_body && !has_body(headers) {
+            return Ok(());
+        }
+
+        // The `RequestContentType` is present for every request handled by `GothamService`, but
+        // is parsed here otherwise (e.g. when the matcher is used directly).
+        let parsed;
+        let content_type = match RequestContentType::try_borrow_from(state) {
+            Some(content_type) => content_type,
+            None => {
+                parsed = RequestContentType::from_headers(headers);
+                &parsed
+            }
+        };
+
+        match content_type.mime() {
+            // The client has not specified a valid `Content-Type` header.
             None => Err(RouteNonMatch::new(StatusCode::UNSUPPORTED_MEDIA_TYPE)),
 
             // Header was provided.
-            Some(content_type) => {
-                let mime = content_type.to_str().unwrap().parse().unwrap();
-
-                if self.supported_media_types.contains(&mime) {
+            Some(mime) => {
+                if self
+                    .supported_media_types
+                    .iter()
+                    .any(|supported| is_compatible(supported, mime))
+                {
                     return Ok(());
                 }
 
@@ -93,4 +148,175 @@ impl RouteMatcher for ContentTypeHeaderRouteMatcher {
             }
         }
     }
+
+    fn summarize(&self, summary: &mut MatcherSummary) {
+        let media_types: Vec<&str> = self
+            .supported_media_types
+            .iter()
+            .map(AsRef::as_ref)
+            .collect();
+        summary.add_condition(format!("content-type: {}", media_types.join(", ")));
+    }
+}
+
+/// Determines if the request media type is one of the types described by `supported`, ignoring
+/// any parameters which `supported` doesn't declare.
+fn is_compatible(supported: &mime::Mime, requested: &mime::Mime) -> bool {
+    if supported.type_() != requested.type_() {
+        return false;
+    }
+
+    if supported.subtype() != mime::STAR && supported.subtype() != requested.subtype() {
+        return false;
+    }
+
+    supported.params().all(|(name, value)| {
+        requested
+            .get_param(name.as_str())
+            .map(|v| v.as_str() == value.as_str())
+            .unwrap_or(false)
+    })
+}
+
+/// Determines if the request has a body, based on the `Content-Length` and `Transfer-Encoding`
+/// headers.
+fn has_body(headers: &HeaderMap) -> bool {
+    match headers.get(CONTENT_LENGTH) {
+        Some(len) => len != "0",
+        None => headers.contains_key(TRANSFER_ENCODING),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, Method, Response};
+
+    use router::builder::*;
+    use test::TestServer;
+
+    fn is_match(matcher: &ContentTypeHeaderRouteMatcher, headers: &[(&'static str, &str)]) -> bool {
+        let mut map = HeaderMap::new();
+        for &(name, value) in headers {
+            map.insert(name, value.parse().unwrap());
+        }
+
+        let mut state = State::new();
+        state.put(map);
+        matcher.is_match(&state).is_ok()
+    }
+
+    #[test]
+    fn parameters_and_wildcards() {
+        let matcher = ContentTypeHeaderRouteMatcher::new(vec![
+            mime::APPLICATION_JSON,
+            mime::TEXT_STAR,
+            "application/x-custom; version=2".parse().unwrap(),
+        ]);
+        let content_type = |value| is_match(&matcher, &[("content-type", value)]);
+
+        assert!(content_type("application/json; charset=utf-8"));
+        assert!(content_type("text/csv"));
+        assert!(content_type("text/plain; charset=utf-8"));
+        assert!(content_type(
+            "application/x-custom; version=2; charset=utf-8"
+        ));
+
+        assert!(!content_type("application/xml"));
+        assert!(!content_type("application/x-custom"));
+        assert!(!content_type("application/x-custom; version=1"));
+        assert!(!content_type("multipart/form-data; boundary=abc"));
+    }
+
+    #[test]
+    fn empty_body_policy() {
+        let matcher = ContentTypeHeaderRouteMatcher::new(vec![mime::APPLICATION_JSON]);
+        assert!(!is_match(&matcher, &[]));
+        assert!(!is_match(&matcher, &[("content-length", "0")]));
+
+        let matcher = matcher.match_empty_body(true);
+        assert!(is_match(&matcher, &[]));
+        assert!(is_match(&matcher, &[("content-length", "0")]));
+        assert!(is_match(
+            &matcher,
+            &[("content-length", "0"), ("content-type", "text/plain")]
+        ));
+
+        assert!(!is_match(&matcher, &[("content-length", "2")]));
+        assert!(!is_match(&matcher, &[("transfer-encoding", "chunked")]));
+        assert!(is_match(
+            &matcher,
+            &[
+                ("transfer-encoding", "chunked"),
+                ("content-type", "application/json")
+            ]
+        ));
+    }
+
+    fn json_handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new("json".into()))
+    }
+
+    fn multipart_handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new("multipart".into()))
+    }
+
+    #[test]
+    fn router_selects_route_by_content_type() {
+        let router = build_simple_router(|route| {
+            route
+                .post("/upload")
+                .with_content_type(vec![mime::APPLICATION_JSON])
+                .to(json_handler);
+            route
+                .post("/upload")
+                .with_content_type(vec![mime::MULTIPART_FORM_DATA])
+                .to(multipart_handler);
+
+            route
+                .post("/ping")
+                .add_route_matcher(
+                    ContentTypeHeaderRouteMatcher::new(vec![mime::APPLICATION_JSON])
+                        .match_empty_body(true),
+                )
+                .to(json_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let post = |content_type: &str| {
+            test_server
+                .client()
+                .post(
+                    "http://localhost/upload",
+                    "{}",
+                    content_type.parse().unwrap(),
+                )
+                .perform()
+                .unwrap()
+        };
+
+        let response = post("application/json; charset=utf-8");
+        assert_eq!(response.read_utf8_body().unwrap(), "json");
+
+        let response = post("multipart/form-data; boundary=X-BOUNDARY");
+        assert_eq!(response.read_utf8_body().unwrap(), "multipart");
+
+        let response = post("text/plain");
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let response = test_server
+            .client()
+            .build_request(Method::POST, "http://localhost/upload")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let response = test_server
+            .client()
+            .build_request(Method::POST, "http://localhost/ping")
+            .perform()
+            .unwrap();
+        assert_eq!(response.read_utf8_body().unwrap(), "json");
+    }
 }
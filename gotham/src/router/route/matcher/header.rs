@@ -0,0 +1,345 @@
+//! Defines the `HeaderRouteMatcher`.
+
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::StatusCode;
+
+use router::non_match::RouteNonMatch;
+use router::route::matcher::MatcherSummary;
+use router::route::RouteMatcher;
+use state::{request_id, FromState, State};
+
+/// A `RouteMatcher` that succeeds when a header of the `Request` has a given value, one of a set
+/// of values, or a value accepted by a predicate. This allows the `Router` to select between
+/// implementations of a route by a request header, such as an `X-Api-Version` header.
+///
+/// When no value of the header is accepted (including when the header is missing), the matcher
+/// fails with `404 Not Found` by default, so the `Router` tries the other routes for the request
+/// path. A route without a header condition, defined after those with one, can then serve as the
+/// default. With `with_status`, the matcher instead fails with a status such as `400 Bad Request`
+/// or `406 Not Acceptable`, which the `Router` responds with when no route matches.
+///
+/// With `advertise_as`, the response sent when no route matches includes a header listing the
+/// values accepted by the matcher. When several routes for the request path advertise the same
+/// header, it's sent with the values of each of them.
+///
+/// Values are compared exactly, and each occurrence of a header which is sent more than once is
+/// tried in turn. The matcher is attached to a route with `DefineSingleRoute::add_route_matcher`,
+/// and may be composed with other matchers via `AndRouteMatcher`, `OrRouteMatcher` and
+/// `NotRouteMatcher`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::header::HeaderName;
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::router::builder::*;
+/// # use gotham::router::route::matcher::header::HeaderRouteMatcher;
+/// # use gotham::router::Router;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn users_v1(state: State) -> (State, Response<Body>) {
+///     // Handler implementation elided.
+/// #   (state, Response::builder().status(StatusCode::OK).body(Body::empty()).unwrap())
+/// }
+///
+/// fn users_v2(state: State) -> (State, Response<Body>) {
+///     // Handler implementation elided.
+/// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+/// }
+///
+/// fn version(value: &str) -> HeaderRouteMatcher {
+///     HeaderRouteMatcher::with_value(HeaderName::from_static("x-api-version"), value)
+///         .with_status(StatusCode::BAD_REQUEST)
+///         .advertise_as(HeaderName::from_static("x-api-supported-versions"))
+/// }
+///
+/// fn router() -> Router {
+///     build_simple_router(|route| {
+///         route.get("/users").add_route_matcher(version("1")).to(users_v1);
+///         route.get("/users").add_route_matcher(version("2")).to(users_v2);
+///     })
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server.client()
+/// #       .get("https://example.com/users")
+/// #       .with_header("x-api-version", "2".parse().unwrap())
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+/// #
+/// #   let response = test_server.client()
+/// #       .get("https://example.com/users")
+/// #       .with_header("x-api-version", "3".parse().unwrap())
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct HeaderRouteMatcher {
+    name: HeaderName,
+    condition: Condition,
+    status: StatusCode,
+    advertise: Option<HeaderName>,
+}
+
+#[derive(Clone)]
+enum Condition {
+    Values(Vec<String>),
+    Predicate(Arc<Fn(&HeaderValue) -> bool + Send + Sync + RefUnwindSafe>),
+}
+
+impl HeaderRouteMatcher {
+    /// Creates a `HeaderRouteMatcher` which matches when the header `name` is `value`.
+    pub fn with_value(name: HeaderName, value: &str) -> Self {
+        HeaderRouteMatcher::with_values(name, vec![value])
+    }
+
+    /// Creates a `HeaderRouteMatcher` which matches when the header `name` is any of `values`.
+    pub fn with_values<I, S>(name: HeaderName, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        HeaderRouteMatcher::new(
+            name,
+            Condition::Values(values.into_iter().map(Into::into).collect()),
+        )
+    }
+
+    /// Creates a `HeaderRouteMatcher` which matches when `predicate` returns `true` for a value
+    /// of the header `name`. The predicate receives the raw `HeaderValue`, which may not be valid
+    /// UTF-8.
+    pub fn with_predicate<F>(name: HeaderName, predicate: F) -> Self
+    where
+        F: Fn(&HeaderValue) -> bool + Send + Sync + RefUnwindSafe + 'static,
+    {
+        HeaderRouteMatcher::new(name, Condition::Predicate(Arc::new(predicate)))
+    }
+
+    fn new(name: HeaderName, condition: Condition) -> Self {
+        HeaderRouteMatcher {
+            name,
+            condition,
+            status: StatusCode::NOT_FOUND,
+            advertise: None,
+        }
+    }
+
+    /// Sets the status of the `RouteNonMatch` returned when the header has no accepted value.
+    pub fn with_status(self, status: StatusCode) -> Self {
+        HeaderRouteMatcher { status, ..self }
+    }
+
+    /// Advertises the accepted values, separated by `, `, in the header `name` of the response
+    /// sent when no route matches the request. A matcher created with `with_predicate` has no
+    /// values to advertise, so this has no effect on it.
+    pub fn advertise_as(self, name: HeaderName) -> Self {
+        HeaderRouteMatcher {
+            advertise: Some(name),
+            ..self
+        }
+    }
+
+    fn matches(&self, value: &HeaderValue) -> bool {
+        match self.condition {
+            Condition::Values(ref values) => {
+                values.iter().any(|v| v.as_bytes() == value.as_bytes())
+            }
+            Condition::Predicate(ref predicate) => predicate(value),
+        }
+    }
+}
+
+impl RouteMatcher for HeaderRouteMatcher {
+    /// Determines if a value of the header meets the condition.
+    fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+        let headers = HeaderMap::borrow_from(state);
+        if headers.get_all(&self.name).iter().any(|v| self.matches(v)) {
+            return Ok(());
+        }
+
+        trace!(
+            "[{}] did not provide a {} header matched by this Route",
+            request_id(&state),
+            self.name
+        );
+
+        let non_match = RouteNonMatch::new(self.status);
+        match (&self.advertise, &self.condition) {
+            (&Some(ref advertise), &Condition::Values(ref values)) => {
+                match HeaderValue::from_str(&values.join(", ")) {
+                    Ok(value) => Err(non_match.with_header(advertise.clone(), value)),
+                    Err(_) => Err(non_match),
+                }
+            }
+            _ => Err(non_match),
+        }
+    }
+
+    fn summarize(&self, summary: &mut MatcherSummary) {
+        let condition = match self.condition {
+            Condition::Values(ref values) if values.len() == 1 => {
+                format!("header: {}={}", self.name, values[0])
+            }
+            Condition::Values(ref values) => {
+                format!("header: {} in {}", self.name, values.join(", "))
+            }
+            Condition::Predicate(_) => format!("header: {} predicate", self.name),
+        };
+        summary.add_condition(condition);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, Method, Response};
+
+    use router::builder::*;
+    use router::route::matcher::{MethodOnlyRouteMatcher, NotRouteMatcher, OrRouteMatcher};
+    use test::TestServer;
+
+    const VERSION: &str = "x-api-version";
+    const SUPPORTED: &str = "x-api-supported-versions";
+
+    fn is_match(matcher: &HeaderRouteMatcher, values: &[&'static str]) -> bool {
+        let mut state = State::new();
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(VERSION, HeaderValue::from_static(*value));
+        }
+        state.put(headers);
+        matcher.is_match(&state).is_ok()
+    }
+
+    #[test]
+    fn header_conditions() {
+        let name = HeaderName::from_static(VERSION);
+
+        let matcher = HeaderRouteMatcher::with_value(name.clone(), "2");
+        assert!(is_match(&matcher, &["2"]));
+        assert!(is_match(&matcher, &["1", "2"]));
+        assert!(!is_match(&matcher, &["2.0"]));
+        assert!(!is_match(&matcher, &[]));
+
+        let matcher = HeaderRouteMatcher::with_values(name.clone(), vec!["2", "2.1"]);
+        assert!(is_match(&matcher, &["2.1"]));
+        assert!(!is_match(&matcher, &["3"]));
+
+        let matcher = HeaderRouteMatcher::with_predicate(name.clone(), |value| {
+            value.as_bytes().starts_with(b"2.")
+        });
+        assert!(is_match(&matcher, &["2.7"]));
+        assert!(!is_match(&matcher, &["1.7"]));
+
+        let composed = OrRouteMatcher::new(
+            NotRouteMatcher::new(HeaderRouteMatcher::with_value(name.clone(), "1")),
+            MethodOnlyRouteMatcher::new(vec![Method::GET]),
+        );
+        assert!(is_match_composed(&composed, "2"));
+        assert!(!is_match_composed(&composed, "1"));
+    }
+
+    fn is_match_composed<M: RouteMatcher>(matcher: &M, value: &'static str) -> bool {
+        let mut state = State::new();
+        let mut headers = HeaderMap::new();
+        headers.insert(VERSION, HeaderValue::from_static(value));
+        state.put(headers);
+        state.put(Method::POST);
+        matcher.is_match(&state).is_ok()
+    }
+
+    #[test]
+    fn header_summary() {
+        let name = HeaderName::from_static(VERSION);
+        let summarize = |matcher: HeaderRouteMatcher| {
+            let mut summary = MatcherSummary::new();
+            matcher.summarize(&mut summary);
+            summary.conditions().to_vec()
+        };
+
+        assert_eq!(
+            summarize(HeaderRouteMatcher::with_value(name.clone(), "1")),
+            vec!["header: x-api-version=1".to_owned()]
+        );
+        assert_eq!(
+            summarize(HeaderRouteMatcher::with_values(
+                name.clone(),
+                vec!["1", "2"]
+            )),
+            vec!["header: x-api-version in 1, 2".to_owned()]
+        );
+        assert_eq!(
+            summarize(HeaderRouteMatcher::with_predicate(name, |_| true)),
+            vec!["header: x-api-version predicate".to_owned()]
+        );
+    }
+
+    fn users_v1(state: State) -> (State, Response<Body>) {
+        (state, Response::new("v1".into()))
+    }
+
+    fn users_v2(state: State) -> (State, Response<Body>) {
+        (state, Response::new("v2".into()))
+    }
+
+    fn version(values: Vec<&str>, status: StatusCode) -> HeaderRouteMatcher {
+        HeaderRouteMatcher::with_values(HeaderName::from_static(VERSION), values)
+            .with_status(status)
+            .advertise_as(HeaderName::from_static(SUPPORTED))
+    }
+
+    #[test]
+    fn selects_route_by_header() {
+        let router = build_simple_router(|route| {
+            let v1 = version(vec!["1"], StatusCode::BAD_REQUEST);
+            let v2 = version(vec!["2", "2.1"], StatusCode::BAD_REQUEST);
+            route.get("/users").add_route_matcher(v1).to(users_v1);
+            route.get("/users").add_route_matcher(v2).to(users_v2);
+
+            let v1 = version(vec!["1"], StatusCode::NOT_FOUND);
+            route.get("/posts").add_route_matcher(v1).to(users_v1);
+            route.get("/posts").to(users_v2);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let get = |path: &str, version: Option<&str>| {
+            let mut request = test_server
+                .client()
+                .get(&format!("http://localhost{}", path));
+            if let Some(version) = version {
+                request = request.with_header(VERSION, version.parse().unwrap());
+            }
+            request.perform().unwrap()
+        };
+
+        assert_eq!(get("/users", Some("1")).read_utf8_body().unwrap(), "v1");
+        assert_eq!(get("/users", Some("2.1")).read_utf8_body().unwrap(), "v2");
+
+        for version in &[Some("3"), None] {
+            let response = get("/users", *version);
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let supported = response
+                .headers()
+                .get_all(SUPPORTED)
+                .iter()
+                .map(|v| v.to_str().unwrap().to_owned())
+                .collect::<Vec<_>>();
+            assert_eq!(supported, vec!["1", "2, 2.1"]);
+        }
+
+        assert_eq!(get("/posts", Some("1")).read_utf8_body().unwrap(), "v1");
+        assert_eq!(get("/posts", Some("3")).read_utf8_body().unwrap(), "v2");
+    }
+}
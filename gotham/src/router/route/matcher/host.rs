@@ -0,0 +1,249 @@
+//! Defines the `HostRouteMatcher`.
+
+use hyper::header::{HeaderMap, HOST};
+use hyper::{StatusCode, Uri};
+
+use router::non_match::RouteNonMatch;
+use router::route::matcher::MatcherSummary;
+use router::route::RouteMatcher;
+use state::{request_id, FromState, State};
+
+/// A `RouteMatcher` that succeeds when the `Request` was made for a given host, as determined by
+/// the `Host` header (or the authority of the request URI when the header is missing).
+///
+/// The pattern is either an exact host (`api.example.com`), or a leading wildcard
+/// (`*.example.com`) which matches any subdomain of `example.com`, but not `example.com` itself.
+/// Hosts are compared case-insensitively, and the port of the request host is ignored. A request
+/// without a host doesn't match. `Forwarded` and `X-Forwarded-Host` headers are not considered.
+///
+/// When a route doesn't match, the `Router` tries the other routes defined for the request path,
+/// and responds with `404 Not Found` when none of them match. Routes are tried in the order they
+/// were defined, so routes for a host should be defined before any host-agnostic route for the
+/// same path.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # fn main() {
+/// #   use hyper::header::{HeaderMap, HOST};
+/// #   use hyper::Uri;
+/// #   use gotham::state::State;
+/// #   use gotham::router::route::matcher::{HostRouteMatcher, RouteMatcher};
+/// #
+/// #   State::with_new(|state| {
+/// #
+/// let matcher = HostRouteMatcher::new("*.example.com");
+/// state.put(Uri::from_static("/"));
+///
+/// // Host header of a subdomain, with a port
+/// let mut headers = HeaderMap::new();
+/// headers.insert(HOST, "API.example.com:8080".parse().unwrap());
+/// state.put(headers);
+/// assert!(matcher.is_match(&state).is_ok());
+///
+/// // Host header of another domain
+/// let mut headers = HeaderMap::new();
+/// headers.insert(HOST, "example.org".parse().unwrap());
+/// state.put(headers);
+/// assert!(matcher.is_match(&state).is_err());
+///
+/// // No Host header
+/// state.put(HeaderMap::new());
+/// assert!(matcher.is_match(&state).is_err());
+/// #
+/// #   });
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct HostRouteMatcher {
+    host: String,
+    wildcard: bool,
+}
+
+impl HostRouteMatcher {
+    /// Creates a new `HostRouteMatcher` for the given host pattern.
+    pub fn new(pattern: &str) -> Self {
+        let pattern = normalize(strip_port(pattern));
+
+        if pattern.starts_with("*.") {
+            HostRouteMatcher {
+                host: pattern[1..].to_owned(),
+                wildcard: true,
+            }
+        } else {
+            HostRouteMatcher {
+                host: pattern,
+                wildcard: false,
+            }
+        }
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        if self.wildcard {
+            host.len() > self.host.len() && host.ends_with(&self.host)
+        } else {
+            host == self.host
+        }
+    }
+}
+
+impl RouteMatcher for HostRouteMatcher {
+    /// Determines if the `Request` was made for a host which matches the pattern.
+    fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+        let host = HeaderMap::try_borrow_from(state)
+            .and_then(|headers| headers.get(HOST))
+            .and_then(|host| host.to_str().ok())
+            .map(str::to_owned)
+            .or_else(|| {
+                Uri::try_borrow_from(state)
+                    .and_then(|uri| uri.host())
+                    .map(str::to_owned)
+            });
+
+        match host {
+            Some(ref host) if self.matches(&normalize(strip_port(host))) => Ok(()),
+            _ => {
+                trace!(
+                    "[{}] did not request a host matched by this Route",
+                    request_id(&state)
+                );
+                Err(RouteNonMatch::new(StatusCode::NOT_FOUND))
+            }
+        }
+    }
+
+    fn summarize(&self, summary: &mut MatcherSummary) {
+        let wildcard = if self.wildcard { "*" } else { "" };
+        summary.add_condition(format!("host: {}{}", wildcard, self.host));
+    }
+}
+
+/// Lowercases the host, and removes a trailing dot from a fully qualified domain name.
+fn normalize(host: &str) -> String {
+    host.trim().trim_right_matches('.').to_lowercase()
+}
+
+/// Removes the port from a `Host` header value, including one following an IPv6 address.
+fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        match host.find(']') {
+            Some(end) => &host[..end + 1],
+            None => host,
+        }
+    } else {
+        match host.rfind(':') {
+            Some(colon) => &host[..colon],
+            None => host,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::HeaderValue;
+    use hyper::{Body, Response};
+
+    use router::builder::*;
+    use test::TestServer;
+
+    fn is_match(pattern: &str, host: Option<&str>) -> bool {
+        let mut headers = HeaderMap::new();
+        if let Some(host) = host {
+            headers.insert(HOST, host.parse().unwrap());
+        }
+
+        let mut state = State::new();
+        state.put(Uri::from_static("/"));
+        state.put(headers);
+        HostRouteMatcher::new(pattern).is_match(&state).is_ok()
+    }
+
+    #[test]
+    fn host_patterns() {
+        assert!(is_match("api.example.com", Some("api.example.com")));
+        assert!(is_match("api.example.com", Some("API.Example.COM:8443")));
+        assert!(is_match("API.example.com", Some("api.example.com.")));
+        assert!(!is_match("api.example.com", Some("www.example.com")));
+        assert!(!is_match("api.example.com", Some("api.example.com.evil")));
+        assert!(!is_match("api.example.com", None));
+
+        assert!(is_match("*.example.com", Some("api.example.com")));
+        assert!(is_match("*.example.com", Some("eu.api.example.com:80")));
+        assert!(!is_match("*.example.com", Some("example.com")));
+        assert!(!is_match("*.example.com", Some("badexample.com")));
+
+        assert!(is_match("[::1]", Some("[::1]:7878")));
+        assert!(is_match("127.0.0.1", Some("127.0.0.1:7878")));
+    }
+
+    #[test]
+    fn uri_authority_without_host_header() {
+        let mut state = State::new();
+        state.put(Uri::from_static("http://API.example.com:8080/"));
+        state.put(HeaderMap::new());
+
+        let matcher = HostRouteMatcher::new("api.example.com");
+        assert!(matcher.is_match(&state).is_ok());
+    }
+
+    fn api_handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new("api".into()))
+    }
+
+    fn tenant_handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new("tenant".into()))
+    }
+
+    fn www_handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new("www".into()))
+    }
+
+    #[test]
+    fn router_selects_route_by_host() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/status")
+                .with_host("api.example.com")
+                .to(api_handler);
+            route
+                .get("/status")
+                .with_host("*.tenants.example.com")
+                .to(tenant_handler);
+            route.get("/status").to(www_handler);
+
+            route
+                .get("/internal")
+                .with_host("api.example.com")
+                .to(api_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let get = |path: &str, host: &'static str| {
+            test_server
+                .client()
+                .get(&format!("http://localhost{}", path))
+                .with_header(HOST, HeaderValue::from_static(host))
+                .perform()
+                .unwrap()
+        };
+
+        let response = get("/status", "api.example.com:8080");
+        assert_eq!(response.read_utf8_body().unwrap(), "api");
+
+        let response = get("/status", "Acme.Tenants.Example.com");
+        assert_eq!(response.read_utf8_body().unwrap(), "tenant");
+
+        let response = get("/status", "www.example.com");
+        assert_eq!(response.read_utf8_body().unwrap(), "www");
+
+        let response = get("/internal", "api.example.com");
+        assert_eq!(response.read_utf8_body().unwrap(), "api");
+
+        let response = get("/internal", "www.example.com");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -4,10 +4,19 @@ pub mod accept;
 pub mod and;
 pub mod any;
 pub mod content_type;
+pub mod header;
+pub mod host;
+pub mod not;
+pub mod or;
+pub mod query;
 
 pub use self::accept::AcceptHeaderRouteMatcher;
 pub use self::and::AndRouteMatcher;
 pub use self::any::AnyRouteMatcher;
+pub use self::header::HeaderRouteMatcher;
+pub use self::host::HostRouteMatcher;
+pub use self::not::NotRouteMatcher;
+pub use self::or::OrRouteMatcher;
 
 use std::panic::RefUnwindSafe;
 
@@ -18,9 +27,160 @@ use state::{request_id, FromState, State};
 
 /// Determines if conditions required for the associated `Route` to be invoked by the `Router` have
 /// been met.
+///
+/// Applications can implement this trait for their own routing conditions, and attach them to a
+/// route with `DefineSingleRoute::add_route_matcher`. The route then matches only when the
+/// new matcher and those already present (such as the method matcher added by `DrawRoutes::get`,
+/// or one added by `DefineSingleRoute::with_accept`) all succeed.
+///
+/// When a matcher fails, the `Router` tries the other routes defined for the request path. If no
+/// route matches, the response status is taken from the `RouteNonMatch` values returned by the
+/// matchers, preferring the status which indicates the closest match: a status such as `415
+/// Unsupported Media Type` takes precedence over `406 Not Acceptable`, which takes precedence over
+/// `405 Method Not Allowed`, which takes precedence over `404 Not Found`. A matcher which should
+/// make the route invisible to non-matching requests therefore returns `404 Not Found`.
+///
+/// Matchers can be composed with `AndRouteMatcher`, `OrRouteMatcher` and `NotRouteMatcher` before
+/// being attached to a route, e.g. to match requests which either accept JSON or ask for it in
+/// the query string.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::header::HeaderMap;
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::router::builder::*;
+/// # use gotham::router::non_match::RouteNonMatch;
+/// # use gotham::router::route::matcher::RouteMatcher;
+/// # use gotham::router::Router;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// /// Matches requests which opt in to a preview of the API via the `X-Api-Preview` header.
+/// #[derive(Clone)]
+/// struct PreviewMatcher;
+///
+/// impl RouteMatcher for PreviewMatcher {
+///     fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+///         match HeaderMap::borrow_from(state).get("x-api-preview") {
+///             Some(value) if value == "enabled" => Ok(()),
+///             _ => Err(RouteNonMatch::new(StatusCode::NOT_FOUND)),
+///         }
+///     }
+/// }
+///
+/// fn preview(state: State) -> (State, Response<Body>) {
+///     // Handler implementation elided.
+/// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+/// }
+///
+/// fn stable(state: State) -> (State, Response<Body>) {
+///     // Handler implementation elided.
+/// #   (state, Response::builder().status(StatusCode::OK).body(Body::empty()).unwrap())
+/// }
+///
+/// fn router() -> Router {
+///     build_simple_router(|route| {
+///         route.get("/users").add_route_matcher(PreviewMatcher).to(preview);
+///         route.get("/users").to(stable);
+///     })
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server.client()
+/// #       .get("https://example.com/users")
+/// #       .with_header("x-api-preview", "enabled".parse().unwrap())
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+/// #
+/// #   let response = test_server.client()
+/// #       .get("https://example.com/users")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
 pub trait RouteMatcher: RefUnwindSafe + Clone {
     /// Determines if the `Request` meets pre-defined conditions.
     fn is_match(&self, state: &State) -> Result<(), RouteNonMatch>;
+
+    /// Adds the conditions of this matcher to `summary`, which describes the route in the
+    /// `RouteTable` of the `Router`. This is called once, when the route is defined.
+    ///
+    /// The default adds a `custom` condition, since the conditions of the matcher are unknown.
+    fn summarize(&self, summary: &mut MatcherSummary) {
+        summary.add_condition("custom");
+    }
+}
+
+/// Describes the conditions of the `RouteMatcher` values of a route, as provided by
+/// `RouteMatcher::summarize`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MatcherSummary {
+    methods: Option<Vec<Method>>,
+    conditions: Vec<String>,
+}
+
+impl MatcherSummary {
+    /// Creates an empty `MatcherSummary`, which describes a route matching every request.
+    pub fn new() -> MatcherSummary {
+        MatcherSummary::default()
+    }
+
+    /// Restricts the methods which the route matches. When the methods are already restricted,
+    /// only those given both times are retained.
+    pub fn restrict_methods(&mut self, methods: &[Method]) {
+        let methods = match self.methods.take() {
+            Some(current) => methods
+                .iter()
+                .filter(|method| current.contains(method))
+                .cloned()
+                .collect(),
+            None => methods.to_vec(),
+        };
+
+        self.methods = Some(methods);
+    }
+
+    /// Adds a condition other than the request method, such as `host: api.example.com`.
+    pub fn add_condition<S>(&mut self, condition: S)
+    where
+        S: Into<String>,
+    {
+        self.conditions.push(condition.into());
+    }
+
+    /// The methods which the route matches, or `None` when it matches any method.
+    pub fn methods(&self) -> Option<&[Method]> {
+        self.methods.as_ref().map(Vec::as_slice)
+    }
+
+    /// The conditions other than the request method, in the order they were added.
+    pub fn conditions(&self) -> &[String] {
+        &self.conditions
+    }
+
+    /// Describes the methods and conditions as a single condition, for a matcher which combines
+    /// other matchers other than by **AND**. Returns `None` when every request matches.
+    fn describe(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(ref methods) = self.methods {
+            let methods: Vec<&str> = methods.iter().map(Method::as_str).collect();
+            parts.push(format!("method: {}", methods.join(", ")));
+        }
+        parts.extend(self.conditions.iter().cloned());
+
+        match parts.len() {
+            0 => None,
+            1 => parts.pop(),
+            _ => Some(format!("({})", parts.join(" and "))),
+        }
+    }
 }
 
 /// Allow various types to represent themselves as a `RouteMatcher`
@@ -87,6 +247,11 @@ impl MethodOnlyRouteMatcher {
     pub fn new(methods: Vec<Method>) -> Self {
         MethodOnlyRouteMatcher { methods }
     }
+
+    /// The methods which this matcher accepts.
+    pub(crate) fn methods(&self) -> &[Method] {
+        &self.methods
+    }
 }
 
 impl RouteMatcher for MethodOnlyRouteMatcher {
@@ -110,4 +275,117 @@ impl RouteMatcher for MethodOnlyRouteMatcher {
                 .with_allow_list(self.methods.as_slice()))
         }
     }
+
+    fn summarize(&self, summary: &mut MatcherSummary) {
+        summary.restrict_methods(&self.methods);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+    use hyper::{Body, Response};
+    use mime;
+
+    use router::builder::*;
+    use test::TestServer;
+
+    /// Matches requests with an `X-Tenant` header naming one of a fixed set of tenants, and marks
+    /// other requests as forbidden.
+    #[derive(Clone)]
+    struct TenantMatcher {
+        tenants: Vec<&'static str>,
+    }
+
+    impl RouteMatcher for TenantMatcher {
+        fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+            let tenant = HeaderMap::borrow_from(state)
+                .get("x-tenant")
+                .and_then(|value| value.to_str().ok());
+
+            match tenant {
+                Some(tenant) if self.tenants.contains(&tenant) => Ok(()),
+                Some(_) => Err(RouteNonMatch::new(StatusCode::FORBIDDEN)),
+                None => Err(RouteNonMatch::new(StatusCode::NOT_FOUND)),
+            }
+        }
+    }
+
+    fn tenant_handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new("tenant".into()))
+    }
+
+    fn public_handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new("public".into()))
+    }
+
+    #[test]
+    fn custom_matchers_combine_with_builtin_matchers() {
+        let router = build_simple_router(|route| {
+            route
+                .post("/reports")
+                .with_content_type(vec![mime::APPLICATION_JSON])
+                .add_route_matcher(TenantMatcher {
+                    tenants: vec!["acme"],
+                })
+                .to(tenant_handler);
+
+            route
+                .get("/status")
+                .add_route_matcher(TenantMatcher {
+                    tenants: vec!["acme"],
+                })
+                .to(tenant_handler);
+            route.get("/status").to(public_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let post = |content_type: mime::Mime, tenant: Option<&'static str>| {
+            let mut req = test_server
+                .client()
+                .post("http://localhost/reports", "{}", content_type);
+            if let Some(tenant) = tenant {
+                req = req.with_header("x-tenant", HeaderValue::from_static(tenant));
+            }
+            req.perform().unwrap()
+        };
+
+        let response = post(mime::APPLICATION_JSON, Some("acme"));
+        assert_eq!(response.read_utf8_body().unwrap(), "tenant");
+
+        let response = post(mime::APPLICATION_JSON, Some("globex"));
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = post(mime::APPLICATION_JSON, None);
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = post(mime::TEXT_PLAIN, Some("acme"));
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let response = test_server
+            .client()
+            .get("http://localhost/reports")
+            .with_header("x-tenant", HeaderValue::from_static("acme"))
+            .with_header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let response = test_server
+            .client()
+            .get("http://localhost/status")
+            .with_header("x-tenant", HeaderValue::from_static("acme"))
+            .perform()
+            .unwrap();
+        assert_eq!(response.read_utf8_body().unwrap(), "tenant");
+
+        let response = test_server
+            .client()
+            .get("http://localhost/status")
+            .perform()
+            .unwrap();
+        assert_eq!(response.read_utf8_body().unwrap(), "public");
+    }
 }
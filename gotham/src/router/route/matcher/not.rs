@@ -0,0 +1,189 @@
+//! Defines the type `NotRouteMatcher`
+
+use hyper::StatusCode;
+
+use router::non_match::RouteNonMatch;
+use router::route::matcher::MatcherSummary;
+use router::route::RouteMatcher;
+use state::{request_id, State};
+
+/// Inverts a `RouteMatcher`, so that a route matches a request only when the wrapped matcher
+/// rejects it.
+///
+/// When the wrapped matcher accepts the request, the `NotRouteMatcher` fails with `404 Not
+/// Found`, so the route is invisible to the request (see `RouteMatcher` for the precedence of
+/// statuses between routes). A different status can be set with `NotRouteMatcher::with_status`.
+/// Wrapping a matcher which restricts the request method isn't reflected in the `Allow` header.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # fn main() {
+/// #   use hyper::{StatusCode, Uri};
+/// #   use gotham::state::State;
+/// #   use gotham::router::route::matcher::query::QueryStringRouteMatcher;
+/// #   use gotham::router::route::matcher::{NotRouteMatcher, RouteMatcher};
+/// #
+/// #   State::with_new(|state| {
+/// #
+/// let matcher = NotRouteMatcher::new(QueryStringRouteMatcher::with_key("legacy"));
+///
+/// state.put(Uri::from_static("/users"));
+/// assert!(matcher.is_match(&state).is_ok());
+///
+/// state.put(Uri::from_static("/users?legacy=1"));
+/// assert!(matcher.is_match(&state).is_err());
+///
+/// let matcher = matcher.with_status(StatusCode::GONE);
+/// let status: StatusCode = matcher.is_match(&state).err().unwrap().into();
+/// assert_eq!(status, StatusCode::GONE);
+/// #
+/// #   });
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct NotRouteMatcher<T>
+where
+    T: RouteMatcher,
+{
+    t: T,
+    status: StatusCode,
+}
+
+impl<T> NotRouteMatcher<T>
+where
+    T: RouteMatcher,
+{
+    /// Creates a new `NotRouteMatcher`
+    pub fn new(t: T) -> Self {
+        NotRouteMatcher {
+            t,
+            status: StatusCode::NOT_FOUND,
+        }
+    }
+
+    /// Sets the status of the `RouteNonMatch` returned when the wrapped matcher accepts the
+    /// request.
+    pub fn with_status(self, status: StatusCode) -> Self {
+        NotRouteMatcher { status, ..self }
+    }
+}
+
+impl<T> RouteMatcher for NotRouteMatcher<T>
+where
+    T: RouteMatcher,
+{
+    fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+        match self.t.is_match(state) {
+            Ok(_) => {
+                trace!(
+                    "[{}] matched a condition excluded by this Route",
+                    request_id(&state)
+                );
+                Err(RouteNonMatch::new(self.status))
+            }
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn summarize(&self, summary: &mut MatcherSummary) {
+        let mut t = MatcherSummary::new();
+        self.t.summarize(&mut t);
+
+        match t.describe() {
+            Some(condition) => summary.add_condition(format!("not {}", condition)),
+            None => summary.add_condition("never"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::Method;
+
+    use router::route::matcher::OrRouteMatcher;
+    use router::route::matcher::{AndRouteMatcher, AnyRouteMatcher, MethodOnlyRouteMatcher};
+
+    /// Matches, or fails with the given status, regardless of the request.
+    #[derive(Clone)]
+    struct Fixed(Option<StatusCode>);
+
+    impl RouteMatcher for Fixed {
+        fn is_match(&self, _state: &State) -> Result<(), RouteNonMatch> {
+            match self.0 {
+                None => Ok(()),
+                Some(status) => Err(RouteNonMatch::new(status)),
+            }
+        }
+    }
+
+    #[test]
+    fn not_truth_table() {
+        let pass = Fixed(None);
+        let fail = Fixed(Some(StatusCode::NOT_ACCEPTABLE));
+
+        State::with_new(|state| {
+            let state = &*state;
+            let result = |matcher: &NotRouteMatcher<Fixed>| {
+                matcher.is_match(state).map_err(StatusCode::from)
+            };
+
+            assert_eq!(
+                result(&NotRouteMatcher::new(pass.clone())),
+                Err(StatusCode::NOT_FOUND)
+            );
+            assert!(result(&NotRouteMatcher::new(fail.clone())).is_ok());
+            assert_eq!(
+                result(&NotRouteMatcher::new(pass.clone()).with_status(StatusCode::FORBIDDEN)),
+                Err(StatusCode::FORBIDDEN)
+            );
+        });
+    }
+
+    #[test]
+    fn composed_truth_table() {
+        // (a AND NOT b) OR c
+        let fixed = |pass: bool| {
+            Fixed(if pass {
+                None
+            } else {
+                Some(StatusCode::NOT_FOUND)
+            })
+        };
+        let matcher = |a: bool, b: bool, c: bool| {
+            OrRouteMatcher::new(
+                AndRouteMatcher::new(fixed(a), NotRouteMatcher::new(fixed(b))),
+                fixed(c),
+            )
+        };
+
+        State::with_new(|state| {
+            for &a in &[false, true] {
+                for &b in &[false, true] {
+                    for &c in &[false, true] {
+                        let expected = (a && !b) || c;
+                        let actual = matcher(a, b, c).is_match(state).is_ok();
+                        assert_eq!(actual, expected, "a={} b={} c={}", a, b, c);
+                    }
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn not_summary() {
+        let mut summary = MatcherSummary::new();
+        NotRouteMatcher::new(MethodOnlyRouteMatcher::new(vec![Method::GET, Method::HEAD]))
+            .summarize(&mut summary);
+        assert_eq!(summary.methods(), None);
+        assert_eq!(summary.conditions(), &["not method: GET, HEAD".to_owned()]);
+
+        let mut summary = MatcherSummary::new();
+        NotRouteMatcher::new(AnyRouteMatcher::new()).summarize(&mut summary);
+        assert_eq!(summary.conditions(), &["never".to_owned()]);
+    }
+}
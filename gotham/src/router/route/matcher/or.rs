@@ -0,0 +1,288 @@
+//! Defines the type `OrRouteMatcher`
+
+use router::non_match::RouteNonMatch;
+use router::route::matcher::MatcherSummary;
+use router::route::RouteMatcher;
+use state::State;
+
+/// Allows a route to match a request when either of two `RouteMatcher` values accepts it. The
+/// second matcher is only consulted when the first fails.
+///
+/// When both matchers fail, their `RouteNonMatch` values are combined with
+/// `RouteNonMatch::union`. The status is chosen with the same precedence used between the routes
+/// of a request path, so the status of the branch which came closest to matching is used: e.g.
+/// when the first branch fails with `406 Not Acceptable` and the second with `404 Not Found`, the
+/// status is `406 Not Acceptable`. The `Allow` list includes the methods allowed by either branch.
+///
+/// The matcher is attached to a route with `DefineSingleRoute::add_route_matcher`, and may be
+/// composed with `AndRouteMatcher`, `NotRouteMatcher` and further `OrRouteMatcher` values.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::header::ACCEPT;
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::router::builder::*;
+/// # use gotham::router::route::matcher::query::QueryStringRouteMatcher;
+/// # use gotham::router::route::matcher::{AcceptHeaderRouteMatcher, OrRouteMatcher};
+/// # use gotham::router::Router;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn json_report(state: State) -> (State, Response<Body>) {
+///     // Handler implementation elided.
+/// #   (state, Response::builder().status(StatusCode::OK).body(Body::empty()).unwrap())
+/// }
+///
+/// fn router() -> Router {
+///     build_simple_router(|route| {
+///         route
+///             .get("/report")
+///             .add_route_matcher(OrRouteMatcher::new(
+///                 AcceptHeaderRouteMatcher::new(vec![mime::APPLICATION_JSON]),
+///                 QueryStringRouteMatcher::with_value("format", "json"),
+///             ))
+///             .to(json_report);
+///     })
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server.client()
+/// #       .get("https://example.com/report?format=json")
+/// #       .with_header(ACCEPT, "text/html".parse().unwrap())
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #
+/// #   let response = test_server.client()
+/// #       .get("https://example.com/report")
+/// #       .with_header(ACCEPT, "text/html".parse().unwrap())
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct OrRouteMatcher<T, U>
+where
+    T: RouteMatcher,
+    U: RouteMatcher,
+{
+    t: T,
+    u: U,
+}
+
+impl<T, U> OrRouteMatcher<T, U>
+where
+    T: RouteMatcher,
+    U: RouteMatcher,
+{
+    /// Creates a new `OrRouteMatcher`
+    pub fn new(t: T, u: U) -> Self {
+        OrRouteMatcher { t, u }
+    }
+}
+
+impl<T, U> RouteMatcher for OrRouteMatcher<T, U>
+where
+    T: RouteMatcher,
+    U: RouteMatcher,
+{
+    fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+        match self.t.is_match(state) {
+            Ok(_) => Ok(()),
+            Err(e) => self.u.is_match(state).map_err(|e1| e.union(e1)),
+        }
+    }
+
+    fn summarize(&self, summary: &mut MatcherSummary) {
+        let mut t = MatcherSummary::new();
+        let mut u = MatcherSummary::new();
+        self.t.summarize(&mut t);
+        self.u.summarize(&mut u);
+
+        // When only methods are restricted, the route matches the methods of either side.
+        if t.conditions.is_empty() && u.conditions.is_empty() {
+            if let (Some(t), Some(u)) = (t.methods(), u.methods()) {
+                let mut methods = t.to_vec();
+                for method in u {
+                    if !methods.contains(method) {
+                        methods.push(method.clone());
+                    }
+                }
+                summary.restrict_methods(&methods);
+            }
+            return;
+        }
+
+        if let (Some(t), Some(u)) = (t.describe(), u.describe()) {
+            summary.add_condition(format!("{} or {}", t, u));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::ACCEPT;
+    use hyper::{Body, Method, Response, StatusCode};
+    use mime;
+
+    use router::builder::*;
+    use router::route::matcher::query::QueryStringRouteMatcher;
+    use router::route::matcher::{AcceptHeaderRouteMatcher, AnyRouteMatcher};
+    use router::route::matcher::{MethodOnlyRouteMatcher, NotRouteMatcher};
+    use test::TestServer;
+
+    /// Matches, or fails with the given status, regardless of the request.
+    #[derive(Clone)]
+    struct Fixed(Option<StatusCode>);
+
+    impl RouteMatcher for Fixed {
+        fn is_match(&self, _state: &State) -> Result<(), RouteNonMatch> {
+            match self.0 {
+                None => Ok(()),
+                Some(status) => Err(RouteNonMatch::new(status)),
+            }
+        }
+    }
+
+    #[test]
+    fn or_truth_table() {
+        let pass = Fixed(None);
+        let not_found = Fixed(Some(StatusCode::NOT_FOUND));
+        let not_acceptable = Fixed(Some(StatusCode::NOT_ACCEPTABLE));
+
+        State::with_new(|state| {
+            let state = &*state;
+            let result = |t: &Fixed, u: &Fixed| {
+                OrRouteMatcher::new(t.clone(), u.clone())
+                    .is_match(state)
+                    .map_err(StatusCode::from)
+            };
+
+            assert!(result(&pass, &pass).is_ok());
+            assert!(result(&pass, &not_found).is_ok());
+            assert!(result(&not_found, &pass).is_ok());
+            assert_eq!(result(&not_found, &not_found), Err(StatusCode::NOT_FOUND));
+            assert_eq!(
+                result(&not_found, &not_acceptable),
+                Err(StatusCode::NOT_ACCEPTABLE)
+            );
+            assert_eq!(
+                result(&not_acceptable, &not_found),
+                Err(StatusCode::NOT_ACCEPTABLE)
+            );
+        });
+    }
+
+    #[test]
+    fn or_unions_allow_lists() {
+        State::with_new(|state| {
+            state.put(Method::DELETE);
+
+            let matcher = OrRouteMatcher::new(
+                MethodOnlyRouteMatcher::new(vec![Method::GET]),
+                MethodOnlyRouteMatcher::new(vec![Method::POST]),
+            );
+            let (status, allow) = matcher.is_match(state).err().unwrap().deconstruct();
+            assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(allow, vec![Method::GET, Method::POST]);
+
+            state.put(Method::POST);
+            assert!(matcher.is_match(state).is_ok());
+        });
+    }
+
+    fn summarize<M: RouteMatcher>(matcher: &M) -> MatcherSummary {
+        let mut summary = MatcherSummary::new();
+        matcher.summarize(&mut summary);
+        summary
+    }
+
+    #[test]
+    fn or_summary() {
+        let summary = summarize(&OrRouteMatcher::new(
+            MethodOnlyRouteMatcher::new(vec![Method::GET]),
+            MethodOnlyRouteMatcher::new(vec![Method::POST, Method::GET]),
+        ));
+        assert_eq!(summary.methods(), Some(&[Method::GET, Method::POST][..]));
+
+        let summary = summarize(&OrRouteMatcher::new(
+            AcceptHeaderRouteMatcher::new(vec![mime::APPLICATION_JSON]),
+            NotRouteMatcher::new(QueryStringRouteMatcher::with_key("html")),
+        ));
+        assert_eq!(
+            summary.conditions(),
+            &["accept: application/json or not query: html".to_owned()]
+        );
+
+        let summary = summarize(&OrRouteMatcher::new(
+            QueryStringRouteMatcher::with_key("json"),
+            AnyRouteMatcher::new(),
+        ));
+        assert_eq!(summary, MatcherSummary::new());
+    }
+
+    fn json_report(state: State) -> (State, Response<Body>) {
+        (state, Response::new("json".into()))
+    }
+
+    fn html_report(state: State) -> (State, Response<Body>) {
+        (state, Response::new("html".into()))
+    }
+
+    fn json_matcher() -> OrRouteMatcher<AcceptHeaderRouteMatcher, QueryStringRouteMatcher> {
+        OrRouteMatcher::new(
+            AcceptHeaderRouteMatcher::new(vec![mime::APPLICATION_JSON]),
+            QueryStringRouteMatcher::with_value("format", "json"),
+        )
+    }
+
+    #[test]
+    fn or_route_matcher_in_router() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/report")
+                .add_route_matcher(json_matcher())
+                .to(json_report);
+            route.get("/report").to(html_report);
+
+            route
+                .get("/export")
+                .add_route_matcher(json_matcher())
+                .to(json_report);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let get = |path: &str, accept: &str| {
+            let response = test_server
+                .client()
+                .get(&format!("http://localhost{}", path))
+                .with_header(ACCEPT, accept.parse().unwrap())
+                .perform()
+                .unwrap();
+            let status = response.status().as_u16();
+            (status, response.read_utf8_body().unwrap())
+        };
+
+        assert_eq!(get("/report", "application/json"), (200, "json".to_owned()));
+        assert_eq!(
+            get("/report?format=json", "text/html"),
+            (200, "json".to_owned())
+        );
+        assert_eq!(
+            get("/report?format=xml", "text/html"),
+            (200, "html".to_owned())
+        );
+
+        assert_eq!(get("/export?format=json", "text/html").0, 200);
+        assert_eq!(get("/export", "text/html").0, 406);
+    }
+}
@@ -0,0 +1,255 @@
+//! Defines the `QueryStringRouteMatcher`.
+
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+
+use hyper::{StatusCode, Uri};
+
+use helpers::http::request::QueryParams;
+use router::non_match::RouteNonMatch;
+use router::route::matcher::MatcherSummary;
+use router::route::RouteMatcher;
+use state::{request_id, FromState, State};
+
+/// A `RouteMatcher` that succeeds when the query string of the `Request` meets a condition: the
+/// presence of a key, a key with a given value, or a predicate over the `QueryParams`.
+///
+/// The query string is parsed for the matcher unless the `QueryParams` are already stored in
+/// `State`. When a route doesn't match, the `Router` tries
+/// the other routes defined for the request path, and responds with `404 Not Found` when none of
+/// them match (or `405 Method Not Allowed`, when only the method of another route didn't match).
+/// Routes are tried in the order they were defined, so a route without a query string condition
+/// should be defined after the routes with one.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # fn main() {
+/// #   use hyper::Uri;
+/// #   use gotham::state::State;
+/// #   use gotham::router::route::matcher::RouteMatcher;
+/// #   use gotham::router::route::matcher::query::QueryStringRouteMatcher;
+/// #
+/// #   State::with_new(|state| {
+/// #
+/// let matcher = QueryStringRouteMatcher::with_value("type", "ping");
+///
+/// state.put(Uri::from_static("/webhook?type=ping"));
+/// assert!(matcher.is_match(&state).is_ok());
+///
+/// state.put(Uri::from_static("/webhook?type=push"));
+/// assert!(matcher.is_match(&state).is_err());
+///
+/// let matcher = QueryStringRouteMatcher::with_predicate(|params| {
+///     params.get_all("id").len() > 1
+/// });
+///
+/// state.put(Uri::from_static("/items?id=1&id=2"));
+/// assert!(matcher.is_match(&state).is_ok());
+/// #
+/// #   });
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct QueryStringRouteMatcher {
+    condition: Condition,
+}
+
+#[derive(Clone)]
+enum Condition {
+    Key(String),
+    Value(String, String),
+    Predicate(Arc<Fn(&QueryParams) -> bool + Send + Sync + RefUnwindSafe>),
+}
+
+impl QueryStringRouteMatcher {
+    /// Creates a `QueryStringRouteMatcher` which matches when the query string contains `key`,
+    /// with any value.
+    pub fn with_key(key: &str) -> Self {
+        QueryStringRouteMatcher {
+            condition: Condition::Key(key.to_owned()),
+        }
+    }
+
+    /// Creates a `QueryStringRouteMatcher` which matches when any value provided for `key` in the
+    /// query string is `value`.
+    pub fn with_value(key: &str, value: &str) -> Self {
+        QueryStringRouteMatcher {
+            condition: Condition::Value(key.to_owned(), value.to_owned()),
+        }
+    }
+
+    /// Creates a `QueryStringRouteMatcher` which matches when `predicate` returns `true` for the
+    /// `QueryParams` of the request.
+    pub fn with_predicate<F>(predicate: F) -> Self
+    where
+        F: Fn(&QueryParams) -> bool + Send + Sync + RefUnwindSafe + 'static,
+    {
+        QueryStringRouteMatcher {
+            condition: Condition::Predicate(Arc::new(predicate)),
+        }
+    }
+
+    fn matches(&self, params: &QueryParams) -> bool {
+        match self.condition {
+            Condition::Key(ref key) => params.contains_key(key),
+            Condition::Value(ref key, ref value) => params.get_all(key).contains(&value.as_str()),
+            Condition::Predicate(ref predicate) => predicate(params),
+        }
+    }
+}
+
+impl RouteMatcher for QueryStringRouteMatcher {
+    /// Determines if the query string of the `Request` meets the condition.
+    fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+        // The `QueryParams` are only stored once the route has been matched (or when a request
+        // is forwarded after they were stored), so they're usually parsed here.
+        let parsed;
+        let params = match QueryParams::try_borrow_from(state) {
+            Some(params) => params,
+            None => {
+                parsed = QueryParams::parse(Uri::borrow_from(state).query());
+                &parsed
+            }
+        };
+
+        if self.matches(params) {
+            return Ok(());
+        }
+
+        trace!(
+            "[{}] did not provide a query string matched by this Route",
+            request_id(&state)
+        );
+        Err(RouteNonMatch::new(StatusCode::NOT_FOUND))
+    }
+
+    fn summarize(&self, summary: &mut MatcherSummary) {
+        let condition = match self.condition {
+            Condition::Key(ref key) => format!("query: {}", key),
+            Condition::Value(ref key, ref value) => format!("query: {}={}", key, value),
+            Condition::Predicate(_) => "query: predicate".to_owned(),
+        };
+        summary.add_condition(condition);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, Response};
+    use mime;
+
+    use router::builder::*;
+    use test::TestServer;
+
+    fn is_match(matcher: &QueryStringRouteMatcher, uri: &'static str) -> bool {
+        let mut state = State::new();
+        state.put(Uri::from_static(uri));
+        matcher.is_match(&state).is_ok()
+    }
+
+    #[test]
+    fn query_string_conditions() {
+        let matcher = QueryStringRouteMatcher::with_key("debug");
+        assert!(is_match(&matcher, "/?debug=1"));
+        assert!(is_match(&matcher, "/?a=b&debug="));
+        assert!(is_match(&matcher, "/?a=b&debug"));
+        assert!(!is_match(&matcher, "/?a=b"));
+        assert!(!is_match(&matcher, "/"));
+
+        let matcher = QueryStringRouteMatcher::with_value("type", "pull request");
+        assert!(is_match(&matcher, "/?type=pull+request"));
+        assert!(is_match(&matcher, "/?type=push&type=pull%20request"));
+        assert!(!is_match(&matcher, "/?type=push"));
+        assert!(!is_match(&matcher, "/?kind=pull+request"));
+
+        let matcher = QueryStringRouteMatcher::with_predicate(|params| {
+            params
+                .get("page")
+                .and_then(|page| page.parse::<u32>().ok())
+                .is_some()
+        });
+        assert!(is_match(&matcher, "/?page=2"));
+        assert!(!is_match(&matcher, "/?page=last"));
+    }
+
+    #[test]
+    fn stored_query_params_are_used() {
+        let mut state = State::new();
+        state.put(Uri::from_static("/?type=push"));
+        state.put(QueryParams::parse(Some("type=ping")));
+
+        let matcher = QueryStringRouteMatcher::with_value("type", "ping");
+        assert!(matcher.is_match(&state).is_ok());
+    }
+
+    fn ping_handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new("ping".into()))
+    }
+
+    fn push_handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new("push".into()))
+    }
+
+    fn event_handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new("event".into()))
+    }
+
+    #[test]
+    fn router_selects_route_by_query_string() {
+        let router = build_simple_router(|route| {
+            route
+                .post("/webhook")
+                .with_query_param("type", "ping")
+                .to(ping_handler);
+            route
+                .post("/webhook")
+                .with_query_param("type", "push")
+                .to(push_handler);
+            route
+                .post("/webhook")
+                .with_query_key("type")
+                .to(event_handler);
+
+            route
+                .get("/events")
+                .with_query_key("since")
+                .to(event_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let post = |uri: &str| {
+            test_server
+                .client()
+                .post(uri, "", mime::APPLICATION_JSON)
+                .perform()
+                .unwrap()
+        };
+
+        let response = post("http://localhost/webhook?type=ping");
+        assert_eq!(response.read_utf8_body().unwrap(), "ping");
+
+        let response = post("http://localhost/webhook?type=push");
+        assert_eq!(response.read_utf8_body().unwrap(), "push");
+
+        let response = post("http://localhost/webhook?type=release");
+        assert_eq!(response.read_utf8_body().unwrap(), "event");
+
+        let response = post("http://localhost/webhook");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = post("http://localhost/events?since=1");
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let response = test_server
+            .client()
+            .get("http://localhost/events?until=1")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -5,21 +5,27 @@
 //! be dispatched to the first `Route` which matches.
 
 pub mod dispatch;
+pub mod extensions;
 pub mod matcher;
 
 use std::marker::PhantomData;
 use std::panic::RefUnwindSafe;
 
-use hyper::{Body, Response, Uri};
+use hyper::{Body, Response, StatusCode};
 
-use extractor::{self, PathExtractor, QueryStringExtractor};
+use extractor::internal::ExtractorError;
+use extractor::{
+    self, ExtractionFailure, ExtractorKind, PathExtractor, QueryStringExtractor, QueryStringOptions,
+};
 use handler::HandlerFuture;
-use helpers::http::request::query_string;
+use helpers::http::request::query_string::{self, QueryParams};
+use helpers::http::response::create_text_response;
 use router::non_match::RouteNonMatch;
+use router::response::finalizer::ResponseFinalizer;
 use router::route::dispatch::Dispatcher;
 use router::route::matcher::RouteMatcher;
 use router::tree::segment::SegmentMapping;
-use state::{request_id, State};
+use state::{request_id, FromState, State, StateData};
 
 #[derive(Clone, Copy, PartialEq)]
 /// Indicates whether this `Route` will dispatch the request to an inner `Router` instance. To
@@ -58,6 +64,18 @@ pub trait Route: RefUnwindSafe {
     /// Determines if this `Route` intends to delegate requests to a secondary `Router` instance.
     fn delegation(&self) -> Delegation;
 
+    /// Determines if the `Router` may dispatch a `HEAD` request to this `Route` when it matches
+    /// the request as a `GET` request, and no other `Route` matches the `HEAD` request.
+    fn automatic_head(&self) -> bool;
+
+    /// The priority of this `Route` relative to other routes which match the same request path.
+    /// Routes with a higher priority are evaluated first. The default priority is `0`.
+    fn priority(&self) -> i32;
+
+    /// The response extenders registered for this `Route`, which take precedence over those of
+    /// any scope containing it, and those of the `Router`.
+    fn response_finalizer(&self) -> Option<&ResponseFinalizer>;
+
     /// Extracts dynamic components of the `Request` path and stores the `PathExtractor` in `State`.
     fn extract_request_path<'a>(
         &self,
@@ -84,9 +102,16 @@ pub trait Route: RefUnwindSafe {
 }
 
 /// Returned in the `Err` variant from `extract_query_string` or `extract_request_path`, this
-/// signals that the extractor has failed and the request should not proceed.
+/// signals that the extractor has failed and the request should not proceed. An
+/// `ExtractionFailure` describing the problem is placed into `State` beforehand.
 pub struct ExtractorFailed;
 
+/// The query string keys rejected by `QueryStringOptions::deny_unknown_keys`, which are held in
+/// `State` until the response to the failed request is created.
+struct UnknownQueryStringKeys(Vec<String>);
+
+impl StateData for UnknownQueryStringKeys {}
+
 /// Concrete type for a route in a Gotham web application. Values of this type are created by the
 /// `gotham::router::builder` API and held internally in the `Router` for dispatching requests.
 pub struct RouteImpl<RM, PE, QSE>
@@ -97,8 +122,11 @@ where
 {
     matcher: RM,
     dispatcher: Box<Dispatcher + Send + Sync>,
-    _extractors: Extractors<PE, QSE>,
+    extractors: Extractors<PE, QSE>,
     delegation: Delegation,
+    automatic_head: bool,
+    priority: i32,
+    response_finalizer: Option<ResponseFinalizer>,
 }
 
 /// Extractors used by `RouteImpl` to acquire request data and change into a type safe form
@@ -110,6 +138,7 @@ where
 {
     rpe_phantom: PhantomData<PE>,
     qse_phantom: PhantomData<QSE>,
+    query_string_options: QueryStringOptions,
 }
 
 impl<RM, PE, QSE> RouteImpl<RM, PE, QSE>
@@ -122,14 +151,39 @@ where
     pub fn new(
         matcher: RM,
         dispatcher: Box<Dispatcher + Send + Sync>,
-        _extractors: Extractors<PE, QSE>,
+        extractors: Extractors<PE, QSE>,
         delegation: Delegation,
     ) -> Self {
         RouteImpl {
             matcher,
             dispatcher,
-            _extractors,
+            extractors,
             delegation,
+            automatic_head: true,
+            priority: 0,
+            response_finalizer: None,
+        }
+    }
+
+    /// Sets whether `HEAD` requests may be dispatched to this `Route` when they would match as
+    /// `GET` requests. This is enabled by default. See `Route::automatic_head`.
+    pub fn with_automatic_head(self, automatic_head: bool) -> Self {
+        RouteImpl {
+            automatic_head,
+            ..self
+        }
+    }
+
+    /// Sets the priority of this `Route`. See `Route::priority`.
+    pub fn with_priority(self, priority: i32) -> Self {
+        RouteImpl { priority, ..self }
+    }
+
+    /// Sets the response extenders of this `Route`. See `Route::response_finalizer`.
+    pub fn with_response_finalizer(self, response_finalizer: ResponseFinalizer) -> Self {
+        RouteImpl {
+            response_finalizer: Some(response_finalizer),
+            ..self
         }
     }
 }
@@ -144,6 +198,16 @@ where
         Extractors {
             rpe_phantom: PhantomData,
             qse_phantom: PhantomData,
+            query_string_options: QueryStringOptions::default(),
+        }
+    }
+
+    /// Sets the options used to parse the query string before extracting the
+    /// `QueryStringExtractor`.
+    pub fn with_query_string_options(self, query_string_options: QueryStringOptions) -> Self {
+        Extractors {
+            query_string_options,
+            ..self
         }
     }
 }
@@ -164,6 +228,18 @@ where
         self.delegation
     }
 
+    fn automatic_head(&self) -> bool {
+        self.automatic_head
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn response_finalizer(&self) -> Option<&ResponseFinalizer> {
+        self.response_finalizer.as_ref()
+    }
+
     fn dispatch(&self, state: State) -> Box<HandlerFuture> {
         self.dispatcher.dispatch(state)
     }
@@ -177,6 +253,7 @@ where
             Ok(val) => Ok(state.put(val)),
             Err(e) => {
                 debug!("[{}] path extractor failed: {}", request_id(&state), e);
+                state.put(ExtractionFailure::new(ExtractorKind::Path, &e));
                 Err(ExtractorFailed)
             }
         }
@@ -188,9 +265,10 @@ where
 
     fn extract_query_string(&self, state: &mut State) -> Result<(), ExtractorFailed> {
         let result: Result<QSE, _> = {
-            let uri = state.borrow::<Uri>();
-            let query_string_mapping = query_string::split(uri.query());
-            extractor::internal::from_query_string_mapping(&query_string_mapping)
+            let options = self.extractors.query_string_options;
+            query_string::put_query_params(state);
+            let query_string_mapping = QueryParams::borrow_from(state).mapping(options);
+            extractor::internal::from_query_string_mapping(&query_string_mapping, options)
         };
 
         match result {
@@ -201,6 +279,13 @@ where
                     request_id(&state),
                     e
                 );
+
+                state.put(ExtractionFailure::new(ExtractorKind::QueryString, &e));
+
+                if let ExtractorError::UnknownKeys(keys) = e {
+                    state.put(UnknownQueryStringKeys(keys));
+                }
+
                 Err(ExtractorFailed)
             }
         }
@@ -211,6 +296,11 @@ where
         state: &mut State,
         res: &mut Response<Self::ResBody>,
     ) {
+        if let Some(UnknownQueryStringKeys(keys)) = state.try_take() {
+            let body = format!("unknown query string keys: {}", keys.join(", "));
+            *res = create_text_response(state, StatusCode::BAD_REQUEST, body);
+        }
+
         QSE::extend(state, res)
     }
 }
@@ -228,9 +318,11 @@ mod tests {
     use helpers::http::response::create_response;
     use pipeline::set::*;
     use router::builder::*;
+    use router::response::extender::StaticResponseExtender;
     use router::route::dispatch::DispatcherImpl;
     use router::route::matcher::MethodOnlyRouteMatcher;
     use state::set_request_id;
+    use test::TestServer;
 
     #[test]
     fn internal_route_tests() {
@@ -293,4 +385,47 @@ mod tests {
             Err((_state, e)) => panic!("error polling future: {}", e),
         }
     }
+
+    #[derive(Deserialize)]
+    struct SearchQuery {
+        q: String,
+    }
+
+    impl StateData for SearchQuery {}
+
+    impl StaticResponseExtender for SearchQuery {
+        type ResBody = Body;
+        fn extend(_: &mut State, _: &mut Response<Body>) {}
+    }
+
+    #[test]
+    fn unknown_query_string_keys_tests() {
+        fn handler(mut state: State) -> (State, Response<Body>) {
+            let query = state.take::<SearchQuery>();
+            let res = create_text_response(&state, StatusCode::OK, query.q);
+            (state, res)
+        }
+
+        let router = build_simple_router(|route| {
+            route
+                .get("/search")
+                .with_query_string_extractor::<SearchQuery>()
+                .with_query_string_options(QueryStringOptions::new().with_deny_unknown_keys(true))
+                .to(handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let get = |uri: &str| test_server.client().get(uri).perform().unwrap();
+
+        let response = get("http://localhost/search?q=rust");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "rust");
+
+        let response = get("http://localhost/search?q=rust&utm_source=feed");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            "unknown query string keys: utm_source"
+        );
+    }
 }
@@ -0,0 +1,409 @@
+//! Defines the `RouteTable`, which describes the routes of a `Router`.
+
+use std::fmt::{self, Display, Formatter};
+
+use hyper::Method;
+
+use router::route::matcher::MatcherSummary;
+
+/// Describes the routes of a `Router`, in the order they're tried for a request. The table is
+/// collected while the `Router` is built, and is available via `Router::routes`.
+///
+/// For a given request path, the routes of the path are tried in the order listed. Routes of a
+/// `Router` which requests are delegated to are included beneath the path of the delegation, and
+/// fallback routes are listed after the routes beneath their scope.
+///
+/// The `Display` implementation renders the table with one route per line, for logging when the
+/// application starts. Each line holds the methods of the route (or `*` for any method), its
+/// path, and any other conditions of its matchers, followed by its priority when not `0`, and
+/// whether it's a fallback or delegated route:
+///
+/// ```plain
+/// GET   /users/:id   accept: application/json
+/// POST  /users
+/// GET   /api/status  delegated
+/// ```
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Method, Response, StatusCode};
+/// # use gotham::router::builder::*;
+/// # use gotham::router::table::SegmentKind;
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::builder().status(StatusCode::OK).body(Body::empty()).unwrap())
+/// # }
+/// #
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route.get("/users/:id").to(handler);
+///     route.post("/users").to(handler);
+/// });
+///
+/// let entries = router.routes().entries();
+/// assert_eq!(entries.len(), 2);
+/// assert_eq!(entries[0].path(), "/users");
+/// assert_eq!(entries[1].path(), "/users/:id");
+/// assert_eq!(entries[1].methods(), Some(&[Method::GET][..]));
+/// assert_eq!(entries[1].segments()[1].kind(), SegmentKind::Dynamic);
+/// assert_eq!(entries[1].segments()[1].name(), "id");
+///
+/// println!("{}", router.routes());
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RouteTable {
+    entries: Vec<RouteEntry>,
+}
+
+impl RouteTable {
+    pub(crate) fn new(entries: Vec<RouteEntry>) -> RouteTable {
+        RouteTable { entries }
+    }
+
+    /// The routes, in the order they're tried.
+    pub fn entries(&self) -> &[RouteEntry] {
+        &self.entries
+    }
+}
+
+impl Display for RouteTable {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let rows: Vec<(String, String, String)> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let methods = match entry.methods {
+                    Some(ref methods) => {
+                        let methods: Vec<&str> = methods.iter().map(Method::as_str).collect();
+                        methods.join(", ")
+                    }
+                    None => "*".to_owned(),
+                };
+
+                let mut details = entry.conditions.clone();
+                if entry.priority != 0 {
+                    details.push(format!("priority {}", entry.priority));
+                }
+                if entry.fallback {
+                    details.push("fallback".to_owned());
+                }
+                if entry.delegated {
+                    details.push("delegated".to_owned());
+                }
+
+                (methods, entry.path(), details.join(", "))
+            })
+            .collect();
+
+        let methods_width = rows.iter().map(|row| row.0.len()).max().unwrap_or(0);
+        let path_width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0);
+
+        for &(ref methods, ref path, ref details) in &rows {
+            let line = if details.is_empty() {
+                format!("{:2$}  {}", methods, path, methods_width)
+            } else {
+                format!(
+                    "{:3$}  {:4$}  {}",
+                    methods, path, details, methods_width, path_width
+                )
+            };
+
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Describes a single route of a `Router`, as an entry of its `RouteTable`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteEntry {
+    segments: Vec<RouteSegment>,
+    methods: Option<Vec<Method>>,
+    conditions: Vec<String>,
+    priority: i32,
+    fallback: bool,
+    delegated: bool,
+}
+
+impl RouteEntry {
+    pub(crate) fn new(
+        segments: Vec<RouteSegment>,
+        summary: &MatcherSummary,
+        priority: i32,
+        fallback: bool,
+    ) -> RouteEntry {
+        RouteEntry {
+            segments,
+            methods: summary.methods().map(<[Method]>::to_vec),
+            conditions: summary.conditions().to_vec(),
+            priority,
+            fallback,
+            delegated: false,
+        }
+    }
+
+    /// Places a route of a delegated `Router` beneath the path of the delegation.
+    pub(crate) fn delegated_from(&self, prefix: &[RouteSegment]) -> RouteEntry {
+        let mut segments = prefix.to_vec();
+        segments.extend(self.segments.iter().cloned());
+
+        RouteEntry {
+            segments,
+            delegated: true,
+            ..self.clone()
+        }
+    }
+
+    /// The path of the route, in the form used to define it, such as `/users/:id`. For a
+    /// fallback route, this is the path of its scope.
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        for segment in &self.segments {
+            path.push('/');
+            path.push_str(&segment.pattern);
+        }
+
+        if path.is_empty() {
+            path.push('/');
+        }
+
+        path
+    }
+
+    /// The segments of the path of the route.
+    pub fn segments(&self) -> &[RouteSegment] {
+        &self.segments
+    }
+
+    /// The methods which the route matches, or `None` when it matches any method.
+    pub fn methods(&self) -> Option<&[Method]> {
+        self.methods.as_ref().map(Vec::as_slice)
+    }
+
+    /// The conditions of the matchers of the route other than the request method, as provided
+    /// by `RouteMatcher::summarize`.
+    pub fn conditions(&self) -> &[String] {
+        &self.conditions
+    }
+
+    /// The priority of the route, as set by `DefineSingleRoute::with_priority`.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Determines if the route is a fallback route, which is only tried when no other route
+    /// matches a request beneath its scope.
+    pub fn is_fallback(&self) -> bool {
+        self.fallback
+    }
+
+    /// Determines if the route belongs to a `Router` which requests are delegated to.
+    pub fn is_delegated(&self) -> bool {
+        self.delegated
+    }
+}
+
+/// Describes a segment of the path of a route.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteSegment {
+    name: String,
+    kind: SegmentKind,
+    optional: bool,
+    pattern: String,
+}
+
+impl RouteSegment {
+    pub(crate) fn new(name: &str, kind: SegmentKind, optional: bool, pattern: String) -> Self {
+        RouteSegment {
+            name: name.to_owned(),
+            kind,
+            optional,
+            pattern,
+        }
+    }
+
+    /// The text of a static segment, or the name of any other segment, under which its value is
+    /// provided to the `PathExtractor`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The kind of the segment.
+    pub fn kind(&self) -> SegmentKind {
+        self.kind
+    }
+
+    /// Determines if the segment may be omitted from the end of a request path (`:name?`).
+    pub fn is_optional(&self) -> bool {
+        self.optional
+    }
+
+    /// The segment in the form used to define it, such as `:id:[0-9]+`.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+}
+
+/// The kind of a segment of the path of a route.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SegmentKind {
+    /// A segment which matches its text exactly, such as `users`.
+    Static,
+
+    /// A segment which matches any value, such as `:id`.
+    Dynamic,
+
+    /// A segment which matches values matching a regular expression, such as `:id:[0-9]+`.
+    Constrained,
+
+    /// A segment which matches values which parse as a type, as set by
+    /// `DefineSingleRoute::with_segment_type`.
+    Typed,
+
+    /// A segment which matches one or more segments, such as `*path`.
+    Glob,
+
+    /// A segment which matches zero or more segments, such as `**path`.
+    OptionalGlob,
+}
+
+/// The routes recorded at a `Node` of the `Tree` while the `Router` is built.
+pub(crate) enum RouteRecord {
+    /// A route defined with the given matchers and priority.
+    Route {
+        summary: MatcherSummary,
+        priority: i32,
+        fallback: bool,
+    },
+
+    /// A delegation to a `Router` with the given routes.
+    Delegated(RouteTable),
+}
+
+impl RouteRecord {
+    /// The priority which orders the record amongst the other routes of the same `Node`.
+    pub(crate) fn priority(&self) -> i32 {
+        match *self {
+            RouteRecord::Route { priority, .. } => priority,
+            RouteRecord::Delegated(_) => 0,
+        }
+    }
+
+    /// Determines if the record is of a fallback route.
+    pub(crate) fn is_fallback(&self) -> bool {
+        match *self {
+            RouteRecord::Route { fallback, .. } => fallback,
+            RouteRecord::Delegated(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, Response};
+    use mime;
+
+    use router::builder::*;
+    use router::route::matcher::AnyRouteMatcher;
+    use state::State;
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        (state, Response::new(Body::empty()))
+    }
+
+    #[test]
+    fn route_table_dump() {
+        let admin_router = build_simple_router(|route| {
+            route.get("/").to(handler);
+            route.post("/users/:id:[0-9]+").to(handler);
+        });
+
+        let router = build_simple_router(|route| {
+            route.get("/").to(handler);
+            route
+                .get("/users/:id")
+                .with_accept(vec![mime::APPLICATION_JSON])
+                .to(handler);
+            route
+                .get("/users/:id")
+                .with_host("api.example.com")
+                .with_priority(1)
+                .to(handler);
+            route.post("/users").to(handler);
+            route.get("/archive/:year/:month?").to(handler);
+            route.get("/files/*path").to(handler);
+
+            route.scope("/api", |route| {
+                route.get("/status").with_query_key("verbose").to(handler);
+                route.fallback(AnyRouteMatcher::new()).to(handler);
+            });
+
+            route.delegate("/admin").to_router(admin_router);
+        });
+
+        let expected = vec![
+            "GET   /",
+            "POST  /users",
+            "GET   /users/:id               host: api.example.com, priority 1",
+            "GET   /users/:id               accept: application/json",
+            "GET   /archive/:year/:month?",
+            "GET   /files/*path",
+            "GET   /api/status              query: verbose",
+            "*     /api                     fallback",
+            "GET   /admin                   delegated",
+            "POST  /admin/users/:id:[0-9]+  delegated",
+        ];
+        assert_eq!(router.routes().to_string(), expected.join("\n") + "\n");
+
+        let entries = router.routes().entries();
+        assert_eq!(entries.len(), 10);
+
+        let kinds = |entry: &RouteEntry| -> Vec<SegmentKind> {
+            entry.segments().iter().map(RouteSegment::kind).collect()
+        };
+        assert!(entries[0].segments().is_empty());
+        assert_eq!(
+            kinds(&entries[4]),
+            vec![
+                SegmentKind::Static,
+                SegmentKind::Dynamic,
+                SegmentKind::Dynamic
+            ]
+        );
+        assert!(entries[4].segments()[2].is_optional());
+        assert_eq!(entries[4].segments()[2].name(), "month");
+        assert_eq!(
+            kinds(&entries[5]),
+            vec![SegmentKind::Static, SegmentKind::Glob]
+        );
+        assert_eq!(entries[5].segments()[1].name(), "path");
+        assert_eq!(
+            kinds(&entries[9]),
+            vec![
+                SegmentKind::Static,
+                SegmentKind::Static,
+                SegmentKind::Constrained
+            ]
+        );
+
+        assert_eq!(entries[1].methods(), Some(&[Method::POST][..]));
+        assert_eq!(entries[7].methods(), None);
+        assert!(entries[7].is_fallback());
+        assert_eq!(
+            entries[2].conditions(),
+            &["host: api.example.com".to_owned()]
+        );
+        assert_eq!(entries[2].priority(), 1);
+        assert!(entries[9].is_delegated());
+        assert!(!entries[3].is_delegated());
+    }
+}
@@ -2,13 +2,18 @@
 
 use helpers::http::PercentDecoded;
 use hyper::Body;
+use router::builder::RouterBuildError;
+use router::response::finalizer::ResponseFinalizer;
 use router::route::Route;
+use router::table::RouteTable;
 use router::tree::node::Node;
 use router::tree::segment::{SegmentMapping, SegmentType};
+use state::State;
 
 pub mod node;
 pub mod regex;
 pub mod segment;
+pub mod typed;
 
 /// A hierarchical structure that provides a root `Node` and subtrees of linked nodes
 /// that represent valid `Request` paths.
@@ -43,6 +48,64 @@ impl Tree {
         &mut self.root
     }
 
+    /// Orders the nodes of the `Tree` according to the priority of their routes. See
+    /// `Node::sort_by_priority`.
+    pub(crate) fn sort_by_priority(&mut self) {
+        self.root.sort_by_priority();
+    }
+
+    /// Computes the numbers of request path segments which each node can be followed by, so that
+    /// traversal can skip nodes which can't match. See `Node::compute_depths`.
+    pub(crate) fn compute_depths(&mut self) {
+        self.root.compute_depths();
+    }
+
+    /// Sets the path of the routes at each node, for `MatchedRoute`. See
+    /// `Node::compute_patterns`.
+    pub(crate) fn compute_patterns(&mut self) {
+        self.root.compute_patterns();
+    }
+
+    /// Selects the fallback `Route` for a request path which no other route matched. See
+    /// `Node::select_fallback`.
+    pub(crate) fn select_fallback<'a>(
+        &'a self,
+        req_path_segments: &'a [PercentDecoded],
+        state: &State,
+        ignore_case: bool,
+        not_found_only: bool,
+    ) -> Option<(
+        &'a Box<Route<ResBody = Body> + Send + Sync>,
+        SegmentMapping<'a>,
+        &'a str,
+    )> {
+        self.root
+            .select_fallback(req_path_segments, state, ignore_case, not_found_only)
+    }
+
+    /// Collects the `ResponseFinalizer` values of the scopes which contain a request path. See
+    /// `Node::scope_finalizers`.
+    pub(crate) fn scope_finalizers(
+        &self,
+        req_path_segments: &[PercentDecoded],
+        ignore_case: bool,
+    ) -> Vec<ResponseFinalizer> {
+        self.root.scope_finalizers(req_path_segments, ignore_case)
+    }
+
+    /// Describes the routes of the `Tree`, in the order they're tried. See
+    /// `Node::collect_route_table`.
+    pub(crate) fn route_table(&self) -> RouteTable {
+        let mut entries = Vec::new();
+        self.root.collect_route_table(&mut Vec::new(), &mut entries);
+        RouteTable::new(entries)
+    }
+
+    /// Checks the `Tree` for routes which could never be matched. See `Node::check_conflicts`.
+    pub(crate) fn check_conflicts(&self) -> Vec<RouterBuildError> {
+        self.root.check_conflicts()
+    }
+
     /// Determines if a child `Node` representing the exact segment provided exists at the root of
     /// the `Tree`.
     ///
@@ -59,6 +122,16 @@ impl Tree {
         trace!(" starting tree traversal");
         self.root.match_node(req_path_segments)
     }
+
+    /// Same as `traverse`, but static segments match regardless of case. See
+    /// `Node::match_node_ignoring_case`.
+    pub(crate) fn traverse_ignoring_case<'a>(
+        &'a self,
+        req_path_segments: &'a [PercentDecoded],
+    ) -> Option<(&Node, SegmentMapping<'a>, usize, Vec<(usize, &'a str)>)> {
+        trace!(" starting case-insensitive tree traversal");
+        self.root.match_node_ignoring_case(req_path_segments)
+    }
 }
 
 #[cfg(test)]
@@ -103,7 +176,14 @@ mod tests {
         activate_node_builder.add_child(thing_node_builder);
         tree.add_child(activate_node_builder);
 
+        // Static segments are matched against the path as received, before decoding.
         let request_path_segments = RequestPathSegments::new("/%61ctiv%61te/workflow5");
+        assert!(
+            tree.traverse(request_path_segments.segments().as_slice())
+                .is_none()
+        );
+
+        let request_path_segments = RequestPathSegments::new("/activate/workflow%35");
         match tree.traverse(request_path_segments.segments().as_slice()) {
             Some((node, params, processed)) => {
                 assert!(node.is_routable());
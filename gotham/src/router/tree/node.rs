@@ -1,14 +1,17 @@
 //! Defines `Node` for `Tree`.
 
-use hyper::{Body, StatusCode};
+use hyper::{Body, Method, StatusCode};
 
 use helpers::http::PercentDecoded;
+use router::builder::RouterBuildError;
 use router::non_match::RouteNonMatch;
+use router::response::finalizer::ResponseFinalizer;
 use router::route::{Delegation, Route};
+use router::table::{RouteEntry, RouteRecord, RouteSegment, SegmentKind};
 use router::tree::segment::{SegmentMapping, SegmentType};
 use state::{request_id, State};
 
-use std::cmp::Ordering;
+use std::cmp::{self, Ordering, Reverse};
 use std::collections::HashMap;
 
 /// A recursive member of `Tree`, representative of segment(s) in a request path.
@@ -21,6 +24,25 @@ pub struct Node {
     segment_type: SegmentType,
     routes: Vec<Box<Route<ResBody = Body> + Send + Sync>>,
     children: Vec<Node>,
+    // The highest priority of the routes at or beneath this node, set by `sort_by_priority`.
+    priority: i32,
+    // The methods and priority of each route which is restricted only by request method.
+    method_routes: Vec<(Vec<Method>, i32)>,
+    // Routes for requests beneath this node which no other route matches, and whether each was
+    // defined as a not-found route.
+    fallbacks: Vec<(Box<Route<ResBody = Body> + Send + Sync>, bool)>,
+    // The response extenders of each scope defined at this node, in the order they were defined.
+    response_finalizers: Vec<ResponseFinalizer>,
+    // Whether the segment may be omitted from the end of a request path (`:name?`).
+    optional: bool,
+    // The routes defined at this node, as described in the `RouteTable` of the `Router`.
+    route_records: Vec<RouteRecord>,
+    // The numbers of request path segments which can follow this node, set by `compute_depths`.
+    depths: Option<Depths>,
+    // The path of the routes at this node, as written to define them, set by `compute_patterns`.
+    route_pattern: String,
+    // Whether a route at this node was defined with a trailing slash, such as `/teams/`.
+    trailing_slash: bool,
 }
 
 impl Node {
@@ -31,30 +53,355 @@ impl Node {
             segment: segment.to_string(),
             routes: vec![],
             children: vec![],
+            priority: i32::min_value(),
+            method_routes: vec![],
+            fallbacks: vec![],
+            response_finalizers: vec![],
+            optional: false,
+            route_records: vec![],
+            depths: None,
+            route_pattern: String::new(),
+            trailing_slash: false,
         }
     }
 
-    /// Adds a new child `Node` instance to this `Node`.
+    /// Adds a new child `Node` instance to this `Node`. Children are kept in the order of their
+    /// `SegmentType` (see `match_node`), and then in the order they were added.
     pub fn add_child(&mut self, node: Node) -> &mut Self {
+        self.depths = None;
         self.children.push(node);
-        self.children.sort();
+        self.children
+            .sort_by_key(|child| child.segment_type.precedence());
         self
     }
 
-    /// Adds a `Route` to this `Node`, to be potentially evaluated by the `Router`.
+    /// Adds a `Route` to this `Node`, to be potentially evaluated by the `Router`. Routes are
+    /// kept in order of descending `Route::priority`, and then in the order they were added.
     pub fn add_route(&mut self, route: Box<Route<ResBody = Body> + Send + Sync>) -> &mut Self {
-        self.routes.push(route);
+        let index = self
+            .routes
+            .iter()
+            .position(|r| r.priority() < route.priority())
+            .unwrap_or(self.routes.len());
+
+        self.depths = None;
+        self.routes.insert(index, route);
         self
     }
 
-    /// Borrows a child `Node` based on the defined segment bounds.
+    /// Adds a fallback `Route` to this `Node`, to be evaluated by the `Router` for requests to this
+    /// node, or any path beneath it, which no other route matches (see `select_fallback`). A
+    /// `not_found` route also applies to requests delegated to another `Router` beneath it.
+    pub(crate) fn add_fallback(
+        &mut self,
+        route: Box<Route<ResBody = Body> + Send + Sync>,
+        not_found: bool,
+    ) {
+        self.fallbacks.push((route, not_found));
+    }
+
+    /// Adds the response extenders of a scope defined at this `Node`, which take precedence over
+    /// those of the `Router` for requests to this node, or any path beneath it (see
+    /// `scope_finalizers`).
+    pub(crate) fn add_response_finalizer(&mut self, finalizer: ResponseFinalizer) {
+        self.response_finalizers.push(finalizer);
+    }
+
+    /// Records a route which is restricted only by request method, so that `check_conflicts` can
+    /// find any such route which could never be matched.
+    pub(crate) fn add_method_route(&mut self, methods: &[Method], priority: i32) {
+        let mut methods = methods.to_vec();
+        methods.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        methods.dedup();
+
+        self.method_routes.push((methods, priority));
+    }
+
+    /// Records the description of a route defined at this `Node`, for the `RouteTable` of the
+    /// `Router` (see `collect_route_table`).
+    pub(crate) fn record_route(&mut self, record: RouteRecord) {
+        self.route_records.push(record);
+    }
+
+    /// Collects the entries of the `RouteTable` for the routes at and beneath this `Node`, in the
+    /// order they're tried: the routes of this node by priority, then those beneath each child in
+    /// turn, and then the fallback routes of this node. This is called once the `Tree` is
+    /// complete, after `sort_by_priority`. The segments leading to this node are in `prefix`.
+    pub(crate) fn collect_route_table(
+        &self,
+        prefix: &mut Vec<RouteSegment>,
+        entries: &mut Vec<RouteEntry>,
+    ) {
+        let mut records: Vec<&RouteRecord> = self
+            .route_records
+            .iter()
+            .filter(|record| !record.is_fallback())
+            .collect();
+        records.sort_by_key(|record| Reverse(record.priority()));
+
+        let fallbacks = self
+            .route_records
+            .iter()
+            .filter(|record| record.is_fallback());
+
+        for record in records {
+            self.collect_record(record, prefix, entries);
+        }
+
+        for child in &self.children {
+            prefix.push(child.route_segment());
+            child.collect_route_table(prefix, entries);
+            prefix.pop();
+        }
+
+        for record in fallbacks {
+            self.collect_record(record, prefix, entries);
+        }
+    }
+
+    /// Adds the entries of the `RouteTable` for a single `RouteRecord` of this `Node`.
+    fn collect_record(
+        &self,
+        record: &RouteRecord,
+        prefix: &[RouteSegment],
+        entries: &mut Vec<RouteEntry>,
+    ) {
+        match *record {
+            RouteRecord::Route {
+                ref summary,
+                priority,
+                fallback,
+            } => entries.push(RouteEntry::new(
+                prefix.to_vec(),
+                summary,
+                priority,
+                fallback,
+            )),
+            RouteRecord::Delegated(ref table) => entries.extend(
+                table
+                    .entries()
+                    .iter()
+                    .map(|entry| entry.delegated_from(prefix)),
+            ),
+        }
+    }
+
+    /// Describes the segment of a route path which this `Node` represents, for the `RouteTable`.
+    fn route_segment(&self) -> RouteSegment {
+        let kind = match self.segment_type {
+            SegmentType::Static => SegmentKind::Static,
+            SegmentType::Constrained { .. } => SegmentKind::Constrained,
+            SegmentType::Typed { .. } => SegmentKind::Typed,
+            SegmentType::Dynamic => SegmentKind::Dynamic,
+            SegmentType::Glob => SegmentKind::Glob,
+            SegmentType::OptionalGlob => SegmentKind::OptionalGlob,
+        };
+
+        RouteSegment::new(&self.segment, kind, self.optional, self.pattern())
+    }
+
+    /// Checks the routes beneath this `Node` for a route which could never be matched, because
+    /// it's restricted only by request method, and an earlier route of the same priority, which
+    /// is also restricted only by method, matches the same paths and all of its methods. Paths
+    /// match the same requests when their segments are of the same types, regardless of the
+    /// names of any dynamic segments (so `/users/:id` conflicts with `/users/:user_id`).
+    ///
+    /// Routes which use any other `RouteMatcher` are never considered to conflict, as they may be
+    /// differentiated by the request.
+    ///
+    /// Each conflicting route is returned as a `RouterBuildError::Conflict`, naming it and the
+    /// route which prevents it from being matched. This is called once the `Tree` is complete,
+    /// after `sort_by_priority`, so that the routes are checked in the order they would be
+    /// matched.
+    pub(crate) fn check_conflicts(&self) -> Vec<RouterBuildError> {
+        let mut routes = Vec::new();
+        self.collect_method_routes(&mut Vec::new(), &mut routes);
+
+        routes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, route)| {
+                routes[..i]
+                    .iter()
+                    .find(|earlier| earlier.shadows(route))
+                    .map(|earlier| RouterBuildError::Conflict {
+                        route: route.describe(),
+                        existing: earlier.describe(),
+                    })
+            })
+            .collect()
+    }
+
+    /// Collects the routes recorded by `add_method_route` at and beneath this `Node`, in the
+    /// order they would be matched.
+    fn collect_method_routes<'a>(
+        &'a self,
+        nodes: &mut Vec<&'a Node>,
+        routes: &mut Vec<MethodRoute<'a>>,
+    ) {
+        for &(ref methods, priority) in &self.method_routes {
+            routes.push(MethodRoute {
+                nodes: nodes.clone(),
+                methods,
+                priority,
+            });
+        }
+
+        for child in &self.children {
+            nodes.push(child);
+            child.collect_method_routes(nodes, routes);
+            nodes.pop();
+        }
+    }
+
+    /// Determines if this `Node` matches the same request path segments as another, which is the
+    /// case when they have the same `SegmentType`, and the same segment when it's static.
+    fn is_equivalent(&self, other: &Node) -> bool {
+        self.segment_type == other.segment_type
+            && self.optional == other.optional
+            && (self.segment_type != SegmentType::Static || self.segment == other.segment)
+    }
+
+    /// Describes the segment of a route path which this `Node` represents, as it would be
+    /// written when defining the route.
+    fn pattern(&self) -> String {
+        if self.optional {
+            return match self.segment_type {
+                SegmentType::Constrained { ref regex } => {
+                    let regex = regex.as_str();
+                    format!(":{}?:{}", self.segment, &regex[1..regex.len() - 1])
+                }
+                _ => format!(":{}?", self.segment),
+            };
+        }
+
+        match self.segment_type {
+            SegmentType::Static => self.segment.clone(),
+            SegmentType::Constrained { ref regex } => {
+                // Remove the anchors added by `ConstrainedSegmentRegex::new`.
+                let regex = regex.as_str();
+                format!(":{}:{}", self.segment, &regex[1..regex.len() - 1])
+            }
+            SegmentType::Typed { .. } | SegmentType::Dynamic => format!(":{}", self.segment),
+            SegmentType::Glob if self.segment == "*" => "*".to_owned(),
+            SegmentType::Glob => format!("*{}", self.segment),
+            SegmentType::OptionalGlob if self.segment == "*" => "**".to_owned(),
+            SegmentType::OptionalGlob => format!("**{}", self.segment),
+        }
+    }
+
+    /// Orders the children of this `Node`, and all nodes beneath it, so that a child leading to a
+    /// route with a higher `Route::priority` is tried before its siblings, regardless of their
+    /// `SegmentType`. Children leading to routes of the same priority remain in the order given
+    /// by `add_child`.
+    pub(crate) fn sort_by_priority(&mut self) {
+        let mut priority = self
+            .routes
+            .iter()
+            .map(|route| route.priority())
+            .max()
+            .unwrap_or(i32::min_value());
+
+        for child in &mut self.children {
+            child.sort_by_priority();
+            priority = cmp::max(priority, child.priority);
+        }
+
+        self.children
+            .sort_by_key(|child| (Reverse(child.priority), child.segment_type.precedence()));
+        self.priority = priority;
+    }
+
+    /// Computes the numbers of request path segments which can follow this `Node`, and each node
+    /// beneath it, while still leading to a routable node. `match_node` uses them to skip a child
+    /// whose routes are all shallower or deeper than the remainder of the request path, without
+    /// visiting the nodes beneath it. This is called once the `Tree` is complete, and a node
+    /// which changes afterwards is no longer skipped.
+    pub(crate) fn compute_depths(&mut self) {
+        let mut depths = if self.is_routable() {
+            Depths::exactly(0)
+        } else {
+            Depths::none()
+        };
+
+        // A delegated `Router` matches whatever follows its node.
+        if let Some(route) = self.routes.first() {
+            if route.delegation() == Delegation::External {
+                depths = depths.union(Depths::at_least(1));
+            }
+        }
+
+        for child in &mut self.children {
+            child.compute_depths();
+            let child_depths = child.depths.unwrap_or_else(|| Depths::at_least(0));
+
+            // Optional segments and optional globs may be omitted from the end of the path.
+            let omitted = (child.optional && child_depths.contains(0))
+                || (child.segment_type == SegmentType::OptionalGlob && child.is_routable());
+            if omitted {
+                depths = depths.union(Depths::exactly(0));
+            }
+
+            depths = depths.union(child_depths.deeper());
+        }
+
+        // A glob consumes any number of further segments.
+        if self.segment_type.is_glob() {
+            depths = depths.unbounded();
+        }
+
+        self.depths = Some(depths);
+    }
+
+    /// Sets the path of the routes at this `Node`, and each node beneath it, in the form used to
+    /// define them, such as `/users/:id`. This is called on the root `Node` once the `Tree` is
+    /// complete.
+    pub(crate) fn compute_patterns(&mut self) {
+        self.route_pattern = "/".to_owned();
+        self.compute_child_patterns("");
+    }
+
+    fn compute_child_patterns(&mut self, prefix: &str) {
+        for child in &mut self.children {
+            let pattern = format!("{}/{}", prefix, child.pattern());
+            child.compute_child_patterns(&pattern);
+            child.route_pattern = pattern;
+        }
+    }
+
+    /// The path of the routes at this `Node`, as set by `compute_patterns`.
+    pub(crate) fn route_pattern(&self) -> &str {
+        &self.route_pattern
+    }
+
+    /// Records that a route at this `Node` was defined with a trailing slash, which makes that
+    /// the canonical form of its path for the `TrailingSlash` modes which distinguish the two.
+    pub(crate) fn set_trailing_slash(&mut self) {
+        self.trailing_slash = true;
+    }
+
+    /// Determines if a route at this `Node` was defined with a trailing slash.
+    pub(crate) fn trailing_slash(&self) -> bool {
+        self.trailing_slash
+    }
+
+    /// Determines if `remaining` request path segments could follow this `Node`. This is always
+    /// true before `compute_depths` has been called.
+    fn may_match(&self, remaining: usize) -> bool {
+        self.depths
+            .map_or(true, |depths| depths.contains(remaining))
+    }
+
+    /// Borrows a child `Node` based on the defined segment bounds. Children for optional segments
+    /// are excluded (see `optional_child_mut`).
     pub fn borrow_child(&self, segment: &str, segment_type: SegmentType) -> Option<&Node> {
         self.children
             .iter()
-            .find(|n| n.segment_type == segment_type && n.segment == segment)
+            .find(|n| !n.optional && n.segment_type == segment_type && n.segment == segment)
     }
 
-    /// Borrows a mutable child `Node` based on the defined segment bounds.
+    /// Borrows a mutable child `Node` based on the defined segment bounds. Children for optional
+    /// segments are excluded (see `optional_child_mut`).
     pub fn borrow_child_mut(
         &mut self,
         segment: &str,
@@ -62,7 +409,33 @@ impl Node {
     ) -> Option<&mut Node> {
         self.children
             .iter_mut()
-            .find(|n| n.segment_type == segment_type && n.segment == segment)
+            .find(|n| !n.optional && n.segment_type == segment_type && n.segment == segment)
+    }
+
+    /// Borrows the child `Node` for an optional segment (`:name?`), adding it when it doesn't
+    /// exist. Optional segments are kept apart from the required segments of the same name and
+    /// type, so that the routes of a required segment are never matched by a shorter path.
+    pub(crate) fn optional_child_mut(
+        &mut self,
+        segment: &str,
+        segment_type: SegmentType,
+    ) -> &mut Node {
+        let position = {
+            let is_child =
+                |n: &Node| n.optional && n.segment_type == segment_type && n.segment == segment;
+
+            match self.children.iter().position(&is_child) {
+                Some(position) => position,
+                None => {
+                    let mut node = Node::new(segment, segment_type.clone());
+                    node.optional = true;
+                    self.add_child(node);
+                    self.children.iter().position(&is_child).unwrap()
+                }
+            }
+        };
+
+        &mut self.children[position]
     }
 
     /// Determines if a child exists based on the defined segment bounds.
@@ -87,8 +460,21 @@ impl Node {
     ///
     /// 1. Static
     /// 2. Constrained
-    /// 3. Dynamic
-    /// 4. Glob
+    /// 3. Typed
+    /// 4. Dynamic
+    /// 5. Glob
+    /// 6. OptionalGlob
+    ///
+    /// Children of the same `SegmentType` are searched in the order they were added, unless
+    /// `sort_by_priority` has moved a child leading to a route of higher priority ahead of them.
+    ///
+    /// When the remainder of the path cannot be matched beneath a child, the search backtracks
+    /// and continues with the next child. So, given routes for `/static/:file` and
+    /// `/static/*path`, a request for `/static/a` is matched by `:file` while a request for
+    /// `/static/a/b` is matched by `*path`. Similarly, given routes for `/users/new` and
+    /// `/users/:id/edit`, a request for `/users/new/edit` is matched by `/users/:id/edit`, as
+    /// `new` has no child matching `edit`. A glob consumes segments only once its own children
+    /// cannot match the remainder of the path.
     ///
     /// This method is a wrapping of an internal recursive implementation to mask the required
     /// types needed for the recursion.
@@ -99,12 +485,30 @@ impl Node {
         // accumulators for recursion
         let mut params = HashMap::new();
         let mut processed = 0;
+        let mut folded = Vec::new();
 
         // process and map the results through to the required form
-        self.inner_match_node(segments, &mut params, &mut processed)
+        self.inner_match_node(segments, &mut params, &mut processed, false, &mut folded)
             .map(|node| (node, params, processed))
     }
 
+    /// Same as `match_node`, but static segments match request path segments regardless of case.
+    ///
+    /// The index of each request path segment which matched a static segment spelled with a
+    /// different case is returned, along with that spelling, so that the `Router` can redirect to
+    /// the canonical form of the path.
+    pub(crate) fn match_node_ignoring_case<'a>(
+        &'a self,
+        segments: &'a [PercentDecoded],
+    ) -> Option<(&'a Node, SegmentMapping<'a>, usize, Vec<(usize, &'a str)>)> {
+        let mut params = HashMap::new();
+        let mut processed = 0;
+        let mut folded = Vec::new();
+
+        self.inner_match_node(segments, &mut params, &mut processed, true, &mut folded)
+            .map(|node| (node, params, processed, folded))
+    }
+
     /// Retrieves a reference to the contained segment value.
     ///
     /// This is required for lifetime related annotations.
@@ -116,10 +520,11 @@ impl Node {
     /// request.
     ///
     /// Where multiple `Route` instances could possibly handle the `Request` only the first, ordered
-    /// per creation, is invoked.
+    /// by priority and then per creation, is invoked.
     ///
     /// Where no `Route` instances will accept the `Request` the resulting Error will be the
-    /// union of the `RouteNonMatch` values returned from each `Route`.
+    /// union of the `RouteNonMatch` values returned from each `Route`, taken in the order the
+    /// routes were added.
     ///
     /// In the situation where all these avenues are exhausted an InternalServerError will be
     /// provided.
@@ -127,7 +532,7 @@ impl Node {
         &self,
         state: &State,
     ) -> Result<&Box<Route<ResBody = Body> + Send + Sync>, RouteNonMatch> {
-        let mut err = Ok(());
+        let mut err: Result<(), RouteNonMatch> = Ok(());
 
         // check for matching routes
         for r in self.routes.iter() {
@@ -139,7 +544,7 @@ impl Node {
                 Err(e) => {
                     // concat errors
                     err = match err {
-                        Err(e0) => Err(e.union(e0)),
+                        Err(e0) => Err(e0.union(e)),
                         Ok(()) => Err(e),
                     }
                 }
@@ -164,8 +569,125 @@ impl Node {
         Err(RouteNonMatch::new(StatusCode::INTERNAL_SERVER_ERROR))
     }
 
+    /// Selects the fallback `Route` for a request path which no other route matched. Fallback
+    /// routes are evaluated from the deepest `Node` along the path which has any, so that those of
+    /// a scope take precedence over those of its parent. When no fallback route of a node accepts
+    /// the request (e.g. one which is restricted to `GET` requests), those of its parent are
+    /// evaluated next.
+    ///
+    /// The `SegmentMapping` holds the values of any dynamic segments leading to the node with the
+    /// selected fallback route, which is returned along with the path of that node (see
+    /// `compute_patterns`). Glob segments don't lead to fallback routes. When `not_found_only` is
+    /// set, only the routes defined as not-found routes are evaluated.
+    pub(crate) fn select_fallback<'a>(
+        &'a self,
+        segments: &'a [PercentDecoded],
+        state: &State,
+        ignore_case: bool,
+        not_found_only: bool,
+    ) -> Option<(
+        &'a Box<Route<ResBody = Body> + Send + Sync>,
+        SegmentMapping<'a>,
+        &'a str,
+    )> {
+        let mut params = HashMap::new();
+        self.inner_select_fallback(segments, state, ignore_case, not_found_only, &mut params)
+            .map(|(node, route)| (route, params, node.route_pattern()))
+    }
+
+    /// Recursive implementation of `select_fallback`.
+    fn inner_select_fallback<'a>(
+        &'a self,
+        segments: &'a [PercentDecoded],
+        state: &State,
+        ignore_case: bool,
+        not_found_only: bool,
+        params: &mut SegmentMapping<'a>,
+    ) -> Option<(&'a Node, &'a Box<Route<ResBody = Body> + Send + Sync>)> {
+        if let Some((segment, remaining)) = segments.split_first() {
+            for child in &self.children {
+                if !child.matches_segment(segment, ignore_case) {
+                    continue;
+                }
+
+                let mut child_params = params.clone();
+                if child.segment_type != SegmentType::Static {
+                    child_params.insert(&child.segment, vec![&segment]);
+                }
+
+                if let Some(selected) = child.inner_select_fallback(
+                    remaining,
+                    state,
+                    ignore_case,
+                    not_found_only,
+                    &mut child_params,
+                ) {
+                    *params = child_params;
+                    return Some(selected);
+                }
+            }
+        }
+
+        self.fallbacks
+            .iter()
+            .filter(|&&(_, not_found)| not_found || !not_found_only)
+            .find(|&&(ref route, _)| route.is_match(state).is_ok())
+            .map(|&(ref route, _)| (self, route))
+    }
+
+    /// Collects the `ResponseFinalizer` values of the scopes which contain a request path, from
+    /// the innermost scope to the outermost. The scopes are found along the first path of nodes
+    /// which matches the request path (or a prefix of it) and leads to any scope with response
+    /// extenders, so a request for a missing path beneath a scope still uses its extenders.
+    pub(crate) fn scope_finalizers(
+        &self,
+        segments: &[PercentDecoded],
+        ignore_case: bool,
+    ) -> Vec<ResponseFinalizer> {
+        let mut finalizers = match segments.split_first() {
+            Some((segment, remaining)) => self
+                .children
+                .iter()
+                .filter(|child| child.matches_segment(segment, ignore_case))
+                .map(|child| child.scope_finalizers(remaining, ignore_case))
+                .find(|finalizers| !finalizers.is_empty())
+                .unwrap_or_else(Vec::new),
+            None => Vec::new(),
+        };
+
+        finalizers.extend(self.response_finalizers.iter().rev().cloned());
+        finalizers
+    }
+
+    /// Determines if a single request path segment matches this `Node`. Glob segments never
+    /// match, as they only lead to routes, and not to fallback routes or scopes.
+    fn matches_segment(&self, segment: &PercentDecoded, ignore_case: bool) -> bool {
+        match self.segment_type {
+            SegmentType::Glob | SegmentType::OptionalGlob => false,
+            SegmentType::Static => self.matches_static(segment.static_form(), ignore_case),
+            SegmentType::Constrained { ref regex } => regex.is_match(segment.as_ref()),
+            SegmentType::Typed { ref parser } => parser.is_match(segment.as_ref()),
+            SegmentType::Dynamic => true,
+        }
+    }
+
+    /// Determines if a request path segment matches the segment of this static `Node`.
+    fn matches_static(&self, segment: &str, ignore_case: bool) -> bool {
+        if self.segment == segment {
+            return true;
+        }
+
+        ignore_case
+            && self
+                .segment
+                .chars()
+                .flat_map(char::to_lowercase)
+                .eq(segment.chars().flat_map(char::to_lowercase))
+    }
+
     /// Recursive implementation of `match_route` to populate parameters and keep
-    /// track of the number of visited nodes.
+    /// track of the number of visited nodes. Static segments which matched a request path
+    /// segment only by ignoring case are added to `folded`.
     ///
     /// There's space for optimizations in here (perhaps), but it seems to perform
     /// faster than the previous implementation of the router, so all is well for now.
@@ -174,15 +696,34 @@ impl Node {
         segments: &'a [PercentDecoded],
         params: &mut SegmentMapping<'a>,
         processed: &mut usize,
+        ignore_case: bool,
+        folded: &mut Vec<(usize, &'a str)>,
     ) -> Option<&'a Node> {
         let next_segment = segments.split_first();
 
         // stop if we're done
         if let None = next_segment {
-            if !self.is_routable() {
-                return None;
+            if self.is_routable() {
+                return Some(self);
+            }
+
+            // Optional segments can be omitted from the end of the path, leaving them out of the
+            // parameters, and an optional glob can match the end of the path, leaving its
+            // parameters empty.
+            for child in &self.children {
+                if child.optional {
+                    if let Some(node) =
+                        child.inner_match_node(segments, params, processed, ignore_case, folded)
+                    {
+                        return Some(node);
+                    }
+                } else if child.segment_type == SegmentType::OptionalGlob && child.is_routable() {
+                    params.entry(&child.segment).or_insert_with(|| vec![]);
+                    return Some(child);
+                }
             }
-            return Some(self);
+
+            return None;
         }
 
         // check for external delegates, and stop
@@ -194,73 +735,240 @@ impl Node {
 
         let (segment, remaining) = next_segment.unwrap();
 
-        *processed += 1;
-
         // check all children first
         for child in &self.children {
-            match child.segment_type {
-                // Globbing matches everything, so we append the segment value
-                // to the parameters against the child segment name.
-                SegmentType::Glob => {
-                    params
-                        .entry(&child.segment)
-                        .or_insert_with(|| vec![])
-                        .push(&segment);
-                }
+            // Skip children whose routes can't be reached with the number of segments left.
+            if !child.may_match(remaining.len()) {
+                continue;
+            }
+
+            // Whether the child is a static segment matched only by ignoring case.
+            let folds = match child.segment_type {
+                // Globbing matches everything.
+                SegmentType::Glob | SegmentType::OptionalGlob => false,
 
                 // Static matches based on a raw string match, so we simply
                 // compare the value of the current segment with that of the
                 // child node we're currently iterating.
                 SegmentType::Static => {
-                    // check for raw string match
-                    if child.segment != segment.as_ref() {
+                    if child.segment == segment.static_form() {
+                        false
+                    } else if child.matches_static(segment.static_form(), ignore_case) {
+                        true
+                    } else {
                         continue;
                     }
                 }
 
                 // Constrained matches are based on a contained pattern the
-                // segment value must match. If the segment matches, we need
-                // to make sure to store the value inside the parameters map.
+                // segment value must match.
                 SegmentType::Constrained { ref regex } => {
-                    // check for regex matching
                     if !regex.is_match(&segment.as_ref()) {
                         continue;
                     }
-                    // if there's a match, store the value
-                    params.insert(&child.segment, vec![&segment]);
+                    false
+                }
+
+                // Typed matches are based on whether the segment value can be
+                // parsed as the target type.
+                SegmentType::Typed { ref parser } => {
+                    if !parser.is_match(segment.as_ref()) {
+                        continue;
+                    }
+                    false
+                }
+
+                // Dynamic matches match every value.
+                SegmentType::Dynamic => false,
+            };
+
+            // Parameters are only retained when the child leads to a matching node, so that a
+            // branch which is abandoned leaves no trace. They're copied only once the child is
+            // known to match this segment.
+            let mut child_params = params.clone();
+            let mut child_folded = folded.clone();
+
+            match child.segment_type {
+                // Globbing matches everything, so we append the segment value
+                // to the parameters against the child segment name.
+                SegmentType::Glob | SegmentType::OptionalGlob => {
+                    child_params
+                        .entry(&child.segment)
+                        .or_insert_with(|| vec![])
+                        .push(&segment);
+                }
+
+                SegmentType::Static => {
+                    if folds {
+                        child_folded.push((*processed, child.segment.as_str()));
+                    }
                 }
 
-                // Dynamic matches match every value, so we just attach the
-                // segment value to the parameters list (just like with the
-                // constrained type).
-                SegmentType::Dynamic => {
-                    // if there's a match, store the value
-                    params.insert(&child.segment, vec![&segment]);
+                // The other segment types store the value inside the parameters map.
+                SegmentType::Constrained { .. }
+                | SegmentType::Typed { .. }
+                | SegmentType::Dynamic => {
+                    child_params.insert(&child.segment, vec![&segment]);
                 }
             };
 
-            // If we hit this point, we've determined that the child node is
-            // the correct node to delegate to, so we continue the recursion
-            // on the child node, passing in the same parameters.
-            return child.inner_match_node(remaining, params, processed);
+            // If we hit this point, the child node matches this segment, so we
+            // continue the recursion on the child node. If the remainder of the
+            // path can't be matched from there, we move on to the next child.
+            let mut child_processed = *processed + 1;
+            if let Some(node) = child.inner_match_node(
+                remaining,
+                &mut child_params,
+                &mut child_processed,
+                ignore_case,
+                &mut child_folded,
+            ) {
+                *params = child_params;
+                *processed = child_processed;
+                *folded = child_folded;
+                return Some(node);
+            }
         }
 
-        // If there are no children, but this is a globbing node, then we can
+        // If none of the children match, but this is a globbing node, then we can
         // continue the nesting by just shifting the path segments and calling
         // `inner_match_node` on ourself again (to simulate wildcards).
-        if let SegmentType::Glob = self.segment_type {
+        if self.segment_type.is_glob() {
             // push the segment to the parameters of the glob
             if let Some(path) = params.get_mut(self.segment()) {
                 path.push(&segment);
             }
             // call again, but after shifting the segments to the next
-            return self.inner_match_node(remaining, params, processed);
+            *processed += 1;
+            return self.inner_match_node(remaining, params, processed, ignore_case, folded);
         }
 
         None
     }
 }
 
+/// A set of numbers of request path segments, as computed by `Node::compute_depths`. The set is
+/// approximated by its bounds, so it may contain numbers which can't actually be matched, but
+/// never omits one which can.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Depths {
+    min: usize,
+    // `None` when there is no upper bound.
+    max: Option<usize>,
+}
+
+impl Depths {
+    /// The empty set.
+    fn none() -> Depths {
+        Depths {
+            min: usize::max_value(),
+            max: Some(0),
+        }
+    }
+
+    fn exactly(n: usize) -> Depths {
+        Depths {
+            min: n,
+            max: Some(n),
+        }
+    }
+
+    fn at_least(n: usize) -> Depths {
+        Depths { min: n, max: None }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.max.map_or(false, |max| self.min > max)
+    }
+
+    fn contains(&self, n: usize) -> bool {
+        n >= self.min && self.max.map_or(true, |max| n <= max)
+    }
+
+    fn union(self, other: Depths) -> Depths {
+        if self.is_empty() {
+            return other;
+        }
+
+        if other.is_empty() {
+            return self;
+        }
+
+        Depths {
+            min: cmp::min(self.min, other.min),
+            max: match (self.max, other.max) {
+                (Some(a), Some(b)) => Some(cmp::max(a, b)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Each number in the set, plus one segment.
+    fn deeper(self) -> Depths {
+        if self.is_empty() {
+            return self;
+        }
+
+        Depths {
+            min: self.min + 1,
+            max: self.max.map(|max| max + 1),
+        }
+    }
+
+    /// Each number in the set, and every number above it.
+    fn unbounded(self) -> Depths {
+        if self.is_empty() {
+            return self;
+        }
+
+        Depths::at_least(self.min)
+    }
+}
+
+/// A route restricted only by request method, as collected by `Node::check_conflicts`.
+struct MethodRoute<'a> {
+    // The nodes leading to the route from the root of the `Tree`.
+    nodes: Vec<&'a Node>,
+    methods: &'a [Method],
+    priority: i32,
+}
+
+impl<'a> MethodRoute<'a> {
+    /// Determines if this route prevents a later route from ever being matched.
+    fn shadows(&self, later: &MethodRoute) -> bool {
+        self.priority == later.priority
+            && self.nodes.len() == later.nodes.len()
+            && self
+                .nodes
+                .iter()
+                .zip(&later.nodes)
+                .all(|(node, other)| node.is_equivalent(other))
+            && later
+                .methods
+                .iter()
+                .all(|method| self.methods.contains(method))
+    }
+
+    /// Describes the route for a duplicate route panic, e.g. `GET, HEAD /users/:id`.
+    fn describe(&self) -> String {
+        let methods = self
+            .methods
+            .iter()
+            .map(Method::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let path = self
+            .nodes
+            .iter()
+            .map(|node| node.pattern())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        format!("{} /{}", methods, path)
+    }
+}
+
 impl Eq for Node {}
 impl PartialEq for Node {
     /// Compares two `Node` values for equality based on the segments they represent.
@@ -533,6 +1241,206 @@ mod tests {
         }
     }
 
+    #[test]
+    fn computed_depths_tests() {
+        let paths = [
+            "/seg1",
+            "/seg1/extra",
+            "/seg3/seg4",
+            "/seg3/seg4/seg5",
+            "/seg3",
+            "/seg5/seg6",
+            "/seg5/someval/seg7",
+            "/seg5/someval/seg7/extra",
+            "/some/path/seg9/another/branch",
+            "/seg9/branch",
+            "/resource/5001",
+            "/resource/5001/extra",
+            "/",
+        ];
+
+        let matches = |root: &Node| {
+            paths
+                .iter()
+                .map(|path| {
+                    let rs = RequestPathSegments::new(path);
+                    root.match_node(&rs.segments())
+                        .map(|(node, params, processed)| {
+                            let mut params = params
+                                .iter()
+                                .map(|(k, v)| (k.to_string(), v.len()))
+                                .collect::<Vec<_>>();
+                            params.sort();
+                            (node.segment.clone(), params, processed)
+                        })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut root = test_structure();
+        let expected = matches(&root);
+        root.compute_depths();
+        assert_eq!(matches(&root), expected);
+
+        let depths = |path: &[&str]| {
+            let mut node = &root;
+            for segment in path {
+                node = node
+                    .children
+                    .iter()
+                    .find(|n| n.segment == *segment)
+                    .unwrap();
+            }
+            node.depths.unwrap()
+        };
+
+        assert_eq!(depths(&["seg1"]), Depths::exactly(0));
+        assert_eq!(depths(&["seg3"]), Depths::exactly(1));
+        assert_eq!(
+            depths(&["seg5"]),
+            Depths {
+                min: 1,
+                max: Some(2)
+            }
+        );
+        assert_eq!(depths(&["seg8"]), Depths::at_least(2));
+        assert_eq!(depths(&[]), Depths::at_least(1));
+
+        // A node which changes after `compute_depths` is no longer skipped.
+        root.add_child(Node::new("seg11", SegmentType::Static));
+        assert!(root.may_match(0));
+    }
+
+    #[test]
+    fn segment_precedence_tests() {
+        let paths = [
+            "/users/new",
+            "/users/new/preview",
+            "/users/:id",
+            "/users/:id/edit",
+            "/users/*",
+        ];
+
+        // Builds the nodes for each path, as the router builder would, with `/users/:id` as a
+        // dynamic segment named `id` and `*` as a glob.
+        fn build(paths: &[&str]) -> Node {
+            let pipeline_set = finalize_pipeline_set(new_pipeline_set());
+            let mut root = Node::new("/", SegmentType::Static);
+
+            for path in paths {
+                let mut node = &mut root;
+                for segment in path[1..].split('/') {
+                    let (segment, segment_type) = match segment.chars().next() {
+                        Some(':') => (&segment[1..], SegmentType::Dynamic),
+                        Some('*') => (segment, SegmentType::Glob),
+                        _ => (segment, SegmentType::Static),
+                    };
+
+                    let current = node;
+                    if !current.has_child(segment, segment_type.clone()) {
+                        current.add_child(Node::new(segment, segment_type.clone()));
+                    }
+                    node = current.borrow_child_mut(segment, segment_type).unwrap();
+                }
+                node.add_route(get_route(pipeline_set.clone()));
+            }
+
+            root.sort_by_priority();
+            root.compute_depths();
+            root
+        }
+
+        // The segment of the matched node, and the parameters collected on the way to it.
+        fn matched(root: &Node, path: &str) -> Option<(String, Vec<(String, String)>)> {
+            let rs = RequestPathSegments::new(path);
+            let result = root.match_node(&rs.segments()).map(|(node, params, _)| {
+                let mut params = params
+                    .iter()
+                    .map(|(name, values)| {
+                        let values = values
+                            .iter()
+                            .map(|value| value.as_ref().to_owned())
+                            .collect::<Vec<_>>();
+                        (name.to_string(), values.join("/"))
+                    })
+                    .collect::<Vec<_>>();
+                params.sort();
+                (node.segment.clone(), params)
+            });
+            result
+        }
+
+        fn expected(
+            segment: &str,
+            params: &[(&str, &str)],
+        ) -> Option<(String, Vec<(String, String)>)> {
+            let params = params
+                .iter()
+                .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+                .collect();
+            Some((segment.to_owned(), params))
+        }
+
+        let cases = vec![
+            // A static segment is preferred to a dynamic one at the same depth.
+            ("/users/new", expected("new", &[])),
+            ("/users/new/preview", expected("preview", &[])),
+            ("/users/42", expected("id", &[("id", "42")])),
+            ("/users/42/edit", expected("edit", &[("id", "42")])),
+            // When the static branch can't match the rest of the path, the dynamic one is tried.
+            ("/users/new/edit", expected("edit", &[("id", "new")])),
+            // A glob is only tried once no other segment type leads to a match.
+            ("/users/new/other", expected("*", &[("*", "new/other")])),
+            (
+                "/users/42/edit/more",
+                expected("*", &[("*", "42/edit/more")]),
+            ),
+            ("/users", None),
+        ];
+
+        // The result is the same regardless of the order the paths are added in.
+        let mut reversed = paths.to_vec();
+        reversed.reverse();
+        let mut rotated = paths.to_vec();
+        rotated.rotate_left(2);
+
+        for order in &[paths.to_vec(), reversed, rotated] {
+            let root = build(&order[..]);
+            for &(path, ref expected) in &cases {
+                assert_eq!(
+                    &matched(&root, path),
+                    expected,
+                    "{} with paths added as {:?}",
+                    path,
+                    order
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn depths_tests() {
+        let empty = Depths::none();
+        assert!(empty.is_empty());
+        assert!(!empty.contains(0));
+        assert_eq!(empty.deeper(), empty);
+        assert_eq!(empty.unbounded(), empty);
+        assert_eq!(empty.union(Depths::exactly(3)), Depths::exactly(3));
+
+        let depths = Depths::exactly(1).union(Depths::exactly(3));
+        assert!(depths.contains(1) && depths.contains(3));
+        assert!(!depths.contains(0) && !depths.contains(4));
+        assert_eq!(
+            depths.deeper(),
+            Depths {
+                min: 2,
+                max: Some(4)
+            }
+        );
+        assert_eq!(depths.unbounded(), Depths::at_least(1));
+        assert_eq!(depths.union(Depths::at_least(5)), Depths::at_least(1));
+    }
+
     #[test]
     fn node_traversal_tests() {
         let pipeline_set = finalize_pipeline_set(new_pipeline_set());
@@ -1,6 +1,6 @@
 //! Defines the wrapping type for a segment-matching regex.
 
-use regex::Regex;
+use regex::{self, Regex};
 
 use std::cmp::Ordering;
 use std::panic::AssertUnwindSafe;
@@ -21,9 +21,15 @@ impl ConstrainedSegmentRegex {
     /// It wraps the string in begin and end of line anchors to prevent it from matching more than
     /// intended.
     pub fn new(regex: &str) -> Self {
-        ConstrainedSegmentRegex {
-            regex: AssertUnwindSafe(Regex::new(&format!("^{}$", regex)).unwrap()),
-        }
+        ConstrainedSegmentRegex::try_new(regex).unwrap()
+    }
+
+    /// Creates a new ConstrainedSegmentRegex from a provided string, returning the error from
+    /// `regex::Regex::new` if the pattern is invalid.
+    pub(crate) fn try_new(regex: &str) -> Result<Self, regex::Error> {
+        Regex::new(&format!("^{}$", regex)).map(|regex| ConstrainedSegmentRegex {
+            regex: AssertUnwindSafe(regex),
+        })
     }
 
     /// Returns the pattern backing this regex as a `&str`.
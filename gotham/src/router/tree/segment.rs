@@ -3,6 +3,7 @@ use std::collections::HashMap;
 
 use helpers::http::PercentDecoded;
 use router::tree::regex::ConstrainedSegmentRegex;
+use router::tree::typed::TypedSegmentParser;
 
 /// Mapping of segment names into the collection of values for that segment.
 pub type SegmentMapping<'r> = HashMap<&'r str, Vec<&'r PercentDecoded>>;
@@ -22,10 +23,43 @@ pub enum SegmentType {
         regex: ConstrainedSegmentRegex,
     },
 
+    /// Matches incoming request path segments which can be parsed as a given type.
+    Typed {
+        /// Parser used to match against a single segment of a request path.
+        parser: TypedSegmentParser,
+    },
+
     /// Matches any corresponding segment for incoming request paths.
     Dynamic,
 
     /// Matches multiple path segments until the end of the request path or until a child
-    /// segment of the above defined types is found.
+    /// segment of the above defined types is found. At least one segment must be matched.
     Glob,
+
+    /// Matches in the same way as `Glob`, but also matches when no segments remain in the
+    /// request path.
+    OptionalGlob,
+}
+
+impl SegmentType {
+    /// Determines if this is a `Glob` or `OptionalGlob`.
+    pub fn is_glob(&self) -> bool {
+        match *self {
+            SegmentType::Glob | SegmentType::OptionalGlob => true,
+            _ => false,
+        }
+    }
+
+    /// Ranks the `SegmentType` for matching, where nodes with a lower rank are tried first: a
+    /// static segment is the most specific, and an optional glob the least.
+    pub(crate) fn precedence(&self) -> u8 {
+        match *self {
+            SegmentType::Static => 0,
+            SegmentType::Constrained { .. } => 1,
+            SegmentType::Typed { .. } => 2,
+            SegmentType::Dynamic => 3,
+            SegmentType::Glob => 4,
+            SegmentType::OptionalGlob => 5,
+        }
+    }
 }
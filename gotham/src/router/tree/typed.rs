@@ -0,0 +1,79 @@
+//! Defines the wrapping type for a segment-matching `FromStr` implementation.
+
+use std::any::TypeId;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// Matches a single segment of a request path when it can be parsed as the type `T` given to
+/// `TypedSegmentParser::new`, via `FromStr`. The parsed value is discarded; it's parsed again
+/// by the `PathExtractor` of the route.
+///
+/// `PartialEq`, `Eq`, `PartialOrd` and `Ord` compare the `TypeId` of the target type, so two
+/// parsers for the same type are considered equal.
+#[derive(Clone, Copy)]
+pub struct TypedSegmentParser {
+    type_id: TypeId,
+    accepts: fn(&str) -> bool,
+}
+
+impl TypedSegmentParser {
+    /// Creates a new `TypedSegmentParser` which accepts segments that `T` can be parsed from.
+    pub fn new<T>() -> Self
+    where
+        T: FromStr + 'static,
+    {
+        TypedSegmentParser {
+            type_id: TypeId::of::<T>(),
+            accepts: accepts::<T>,
+        }
+    }
+
+    /// Returns true if and only if the string given can be parsed as the target type.
+    #[inline]
+    pub(crate) fn is_match(&self, s: &str) -> bool {
+        (self.accepts)(s)
+    }
+}
+
+fn accepts<T>(s: &str) -> bool
+where
+    T: FromStr,
+{
+    s.parse::<T>().is_ok()
+}
+
+impl PartialEq for TypedSegmentParser {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_id == other.type_id
+    }
+}
+
+impl Eq for TypedSegmentParser {}
+
+impl PartialOrd for TypedSegmentParser {
+    fn partial_cmp(&self, other: &TypedSegmentParser) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TypedSegmentParser {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.type_id.cmp(&other.type_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_segment_parser_tests() {
+        let parser = TypedSegmentParser::new::<u64>();
+        assert!(parser.is_match("42"));
+        assert!(!parser.is_match("-1"));
+        assert!(!parser.is_match("export"));
+
+        assert!(parser == TypedSegmentParser::new::<u64>());
+        assert!(parser != TypedSegmentParser::new::<i64>());
+    }
+}
@@ -0,0 +1,758 @@
+//! Defines `ServerBuilder`, which configures and starts a Gotham application, and
+//! `ServerBuildError`, which describes a configuration that can't be started.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+#[cfg(unix)]
+use std::fs;
+use std::net::{self, SocketAddr, ToSocketAddrs};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use futures::Future;
+
+use handler::NewHandler;
+use keep_alive::KeepAliveConfig;
+use limits::ConnectionLimits;
+use listeners::{ListenerKind, Listeners};
+use middleware::validation::RequestValidation;
+use read_timeout::ReadTimeouts;
+use service::GothamService;
+use shutdown::{shutdown_handle, ShutdownHandle};
+use state::request_id::RequestIdConfig;
+#[cfg(feature = "tls")]
+use tls::TlsConfig;
+use workers::{run_per_worker, ListenerLayout, WorkerConfig};
+
+/// Configures a Gotham application, which is started by `run` or `spawn` once all of its listeners
+/// and options have been given.
+///
+/// Each listener is bound as it's added, so that an address which can't be bound is reported
+/// before the application starts. Problems with the configuration are reported as a
+/// `ServerBuildError` by `run` or `spawn`, rather than when the option is given, so that options
+/// can be chained; the first problem found is the one reported.
+///
+/// # Examples
+///
+/// Serving HTTP on a single address, with four threads, closing connections which are idle for
+/// thirty seconds:
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::time::Duration;
+/// # use hyper::{Body, Response};
+/// # use gotham::state::State;
+/// # use gotham::ServerBuilder;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// ServerBuilder::new(|| Ok(handler))
+///     .bind("0.0.0.0:7878")
+///     .threads(4)
+///     .keep_alive(Duration::from_secs(30))
+///     .run()
+///     .expect("unable to start server");
+/// # }
+/// ```
+///
+/// See `spawn` for an application which is stopped gracefully, and `bind_tls` for one which
+/// accepts connections over TLS.
+pub struct ServerBuilder<NH>
+where
+    NH: NewHandler + 'static,
+{
+    new_handler: NH,
+    listeners: Listeners,
+    error: Option<ServerBuildError>,
+    // The Unix domain sockets given to `bind_unix`, which are bound by `build`.
+    #[cfg(unix)]
+    unix_sockets: Vec<(PathBuf, Option<u32>)>,
+    threads: usize,
+    listener_layout: ListenerLayout,
+    keep_alive: KeepAliveConfig,
+    limits: ConnectionLimits,
+    read_timeouts: ReadTimeouts,
+    request_ids: RequestIdConfig,
+    validation: Option<RequestValidation>,
+    #[cfg(feature = "tls")]
+    tls_handshake_timeout: Option<Duration>,
+}
+
+impl<NH> ServerBuilder<NH>
+where
+    NH: NewHandler + 'static,
+{
+    /// Begins configuring an application which serves requests via `new_handler`, typically a
+    /// `Router`, with the default number of threads, which is the number of logical CPUs.
+    pub fn new(new_handler: NH) -> ServerBuilder<NH> {
+        ServerBuilder {
+            new_handler,
+            listeners: Listeners::new(),
+            error: None,
+            #[cfg(unix)]
+            unix_sockets: Vec::new(),
+            threads: ::num_cpus::get(),
+            listener_layout: ListenerLayout::Shared,
+            keep_alive: KeepAliveConfig::new(),
+            limits: ConnectionLimits::new(),
+            read_timeouts: ReadTimeouts::new(),
+            request_ids: RequestIdConfig::new(),
+            validation: None,
+            #[cfg(feature = "tls")]
+            tls_handshake_timeout: None,
+        }
+    }
+
+    /// Binds a TCP listener to `addr`. Port 0 leaves the operating system to choose the port,
+    /// which is available via `ShutdownHandle::addrs` once the application is spawned.
+    pub fn bind<A>(self, addr: A) -> ServerBuilder<NH>
+    where
+        A: ToSocketAddrs,
+    {
+        self.bind_with(addr, ListenerKind::Tcp)
+    }
+
+    /// Adds a TCP listener which is already bound, as `gotham::start_on_listener` accepts.
+    pub fn listener(mut self, listener: net::TcpListener) -> ServerBuilder<NH> {
+        self.listeners.push(None, ListenerKind::Tcp(listener));
+        self
+    }
+
+    /// Binds a TCP listener to `addr`, accepting connections over TLS with the certificate chain
+    /// and private key of the `TlsConfig`. Requires the `tls` feature.
+    ///
+    /// # Examples
+    ///
+    /// Serving HTTPS alongside HTTP, e.g. so that the HTTP listener can redirect to HTTPS:
+    ///
+    /// ```rust,no_run
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use std::time::Duration;
+    /// # use hyper::{Body, Response};
+    /// # use gotham::state::State;
+    /// # use gotham::tls::TlsConfig;
+    /// # use gotham::ServerBuilder;
+    /// #
+    /// # fn handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::new(Body::empty()))
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let tls_config = TlsConfig::from_pem_files("cert.pem", "key.pem")
+    ///     .expect("unable to load certificate");
+    ///
+    /// ServerBuilder::new(|| Ok(handler))
+    ///     .bind("0.0.0.0:80")
+    ///     .bind_tls("0.0.0.0:443", tls_config)
+    ///     .tls_handshake_timeout(Duration::from_secs(10))
+    ///     .run()
+    ///     .expect("unable to start server");
+    /// # }
+    /// ```
+    #[cfg(feature = "tls")]
+    pub fn bind_tls<A>(self, addr: A, tls_config: TlsConfig) -> ServerBuilder<NH>
+    where
+        A: ToSocketAddrs,
+    {
+        self.bind_with(addr, move |listener| {
+            ListenerKind::Tls(listener, tls_config)
+        })
+    }
+
+    /// Binds a Unix domain socket at `path`, as `gotham::start_unix_with_mode` does when `mode`
+    /// is given, or `gotham::start_unix` otherwise. The socket file is removed once an
+    /// application started via `spawn` has stopped. Unix only.
+    ///
+    /// Unlike a TCP listener, the socket is bound by `run` or `spawn` once the rest of the
+    /// configuration has been checked, so that a configuration which can't be started doesn't
+    /// leave a socket file behind. It's added after the other listeners.
+    #[cfg(unix)]
+    pub fn bind_unix<P>(mut self, path: P, mode: Option<u32>) -> ServerBuilder<NH>
+    where
+        P: AsRef<Path>,
+    {
+        self.unix_sockets.push((path.as_ref().to_path_buf(), mode));
+        self
+    }
+
+    /// Adds each of the named `Listeners`, after the listeners already added. The name of the
+    /// listener which a request arrived on is available via `ConnectionInfo::listener`.
+    pub fn listeners(mut self, listeners: Listeners) -> ServerBuilder<NH> {
+        self.listeners.append(listeners);
+        self
+    }
+
+    /// Sets the number of threads which serve requests, which must be at least one.
+    pub fn threads(self, threads: usize) -> ServerBuilder<NH> {
+        ServerBuilder { threads, ..self }
+    }
+
+    /// Sets the number of threads which serve requests, and how the listeners are shared between
+    /// them, as described by the `WorkerConfig`. See `ListenerLayout` for the trade-offs between
+    /// the layouts.
+    ///
+    /// `ListenerLayout::PerWorker` is only supported by `run`, and not with Unix domain sockets.
+    pub fn workers(self, workers: WorkerConfig) -> ServerBuilder<NH> {
+        ServerBuilder {
+            threads: workers.threads(),
+            listener_layout: workers.listener_layout(),
+            ..self
+        }
+    }
+
+    /// Closes a connection when no new request starts within `idle_timeout` of the previous
+    /// response, as `KeepAliveConfig::with_idle_timeout` does.
+    pub fn keep_alive(self, idle_timeout: Duration) -> ServerBuilder<NH> {
+        let keep_alive = self.keep_alive.clone().with_idle_timeout(idle_timeout);
+        ServerBuilder { keep_alive, ..self }
+    }
+
+    /// Keeps connections open between requests as described by the `KeepAliveConfig`,
+    /// replacing an idle timeout given to `keep_alive`.
+    pub fn keep_alive_config(self, keep_alive: KeepAliveConfig) -> ServerBuilder<NH> {
+        ServerBuilder { keep_alive, ..self }
+    }
+
+    /// Limits the connections served at once as described by the `ConnectionLimits`.
+    pub fn connection_limits(self, limits: ConnectionLimits) -> ServerBuilder<NH> {
+        ServerBuilder { limits, ..self }
+    }
+
+    /// Bounds the time connections wait for each request as described by the `ReadTimeouts`.
+    pub fn read_timeouts(self, read_timeouts: ReadTimeouts) -> ServerBuilder<NH> {
+        ServerBuilder {
+            read_timeouts,
+            ..self
+        }
+    }
+
+    /// Assigns request identifiers as described by the `RequestIdConfig`.
+    pub fn request_ids(self, request_ids: RequestIdConfig) -> ServerBuilder<NH> {
+        ServerBuilder {
+            request_ids,
+            ..self
+        }
+    }
+
+    /// Rejects requests which fail the `RequestValidation` before any `Router`, `Pipeline` or
+    /// `Middleware` is invoked.
+    pub fn request_validation(self, validation: RequestValidation) -> ServerBuilder<NH> {
+        ServerBuilder {
+            validation: Some(validation),
+            ..self
+        }
+    }
+
+    /// Closes a TLS connection which hasn't completed its handshake within `timeout` of being
+    /// accepted. Requires the `tls` feature, and at least one listener added by `bind_tls`.
+    #[cfg(feature = "tls")]
+    pub fn tls_handshake_timeout(self, timeout: Duration) -> ServerBuilder<NH> {
+        ServerBuilder {
+            tls_handshake_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Starts the application, serving requests until the process exits.
+    pub fn run(self) -> Result<(), ServerBuildError> {
+        let listener_layout = self.listener_layout;
+        let (listeners, service, threads) = self.build()?;
+
+        match listener_layout {
+            ListenerLayout::Shared => {
+                let runtime = ::new_runtime(threads);
+                listeners.spawn(service, &runtime.executor());
+                runtime.shutdown_on_idle().wait().unwrap();
+            }
+            ListenerLayout::PerWorker => run_per_worker(listeners, service, threads),
+        }
+
+        Ok(())
+    }
+
+    /// Starts the application in the background, returning a `ShutdownHandle` which reports the
+    /// addresses it's listening on, and stops it, allowing the requests in flight to complete.
+    ///
+    /// # Examples
+    ///
+    /// Serving the public API and an internal port, and stopping gracefully:
+    ///
+    /// ```rust,no_run
+    /// # extern crate futures;
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use std::time::Duration;
+    /// # use futures::Future;
+    /// # use hyper::{Body, Response};
+    /// # use gotham::listeners::Listeners;
+    /// # use gotham::state::State;
+    /// # use gotham::ServerBuilder;
+    /// #
+    /// # fn handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::new(Body::empty()))
+    /// # }
+    /// #
+    /// # fn wait_for_signal() {}
+    /// #
+    /// # fn main() {
+    /// let internal = Listeners::new()
+    ///     .bind("internal", "127.0.0.1:9090")
+    ///     .expect("unable to bind internal listener");
+    ///
+    /// let server = ServerBuilder::new(|| Ok(handler))
+    ///     .bind("0.0.0.0:8080")
+    ///     .listeners(internal)
+    ///     .keep_alive(Duration::from_secs(30))
+    ///     .spawn()
+    ///     .expect("unable to start server");
+    ///
+    /// println!("listening on {:?}", server.addrs());
+    ///
+    /// wait_for_signal();
+    /// server.shutdown(Duration::from_secs(30)).wait().unwrap();
+    /// # }
+    /// ```
+    pub fn spawn(self) -> Result<ShutdownHandle, ServerBuildError> {
+        if self.error.is_none() && self.listener_layout == ListenerLayout::PerWorker {
+            return Err(ServerBuildError::PerWorkerUnsupported {
+                message: "by `spawn`",
+            });
+        }
+
+        let (listeners, service, threads) = self.build()?;
+
+        let runtime = ::new_runtime(threads);
+        let (handle, signal) = shutdown_handle(listeners.addrs());
+        let handle = remove_on_shutdown(handle, &listeners);
+        listeners.spawn_until_shutdown(service, &runtime.executor(), signal);
+
+        Ok(handle.with_runtime(runtime))
+    }
+
+    /// Checks the configuration and binds the Unix domain sockets, returning the listeners, the
+    /// service which they serve, and the number of threads.
+    fn build(self) -> Result<(Listeners, GothamService<NH>, usize), ServerBuildError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        if self.listeners.is_empty() && !self.has_unix_sockets() {
+            return Err(ServerBuildError::NoListeners);
+        }
+
+        if self.threads == 0 {
+            return Err(ServerBuildError::NoThreads);
+        }
+
+        if self.listener_layout == ListenerLayout::PerWorker
+            && (self.listeners.has_unix() || self.has_unix_sockets())
+        {
+            return Err(ServerBuildError::PerWorkerUnsupported {
+                message: "with Unix domain sockets",
+            });
+        }
+
+        let mut service = GothamService::with_request_ids(self.new_handler, self.request_ids)
+            .with_keep_alive(self.keep_alive)
+            .with_connection_limits(self.limits)
+            .with_read_timeouts(self.read_timeouts);
+
+        if let Some(validation) = self.validation {
+            service = service.validate_requests(validation);
+        }
+
+        #[cfg(feature = "tls")]
+        let service = match self.tls_handshake_timeout {
+            Some(_) if !self.listeners.has_tls() => {
+                return Err(ServerBuildError::TlsOptionWithoutTlsListener {
+                    option: "tls_handshake_timeout",
+                })
+            }
+            Some(timeout) => service.with_tls_handshake_timeout(timeout),
+            None => service,
+        };
+
+        #[cfg(unix)]
+        let listeners = bind_unix_sockets(self.listeners, self.unix_sockets)?;
+        #[cfg(not(unix))]
+        let listeners = self.listeners;
+
+        Ok((listeners, service, self.threads))
+    }
+
+    /// Determines if any Unix domain socket was given to `bind_unix`.
+    #[cfg(unix)]
+    fn has_unix_sockets(&self) -> bool {
+        !self.unix_sockets.is_empty()
+    }
+
+    #[cfg(not(unix))]
+    fn has_unix_sockets(&self) -> bool {
+        false
+    }
+
+    /// Binds a TCP listener to `addr`, adding the kind of listener returned by `kind`.
+    fn bind_with<A, F>(mut self, addr: A, kind: F) -> ServerBuilder<NH>
+    where
+        A: ToSocketAddrs,
+        F: FnOnce(net::TcpListener) -> ListenerKind,
+    {
+        if self.error.is_some() {
+            return self;
+        }
+
+        match tcp_listener(addr) {
+            Ok(listener) => self.listeners.push(None, kind(listener)),
+            Err(error) => self.error = Some(error),
+        }
+
+        self
+    }
+}
+
+/// A configuration given to a `ServerBuilder` which can't be started, as returned by
+/// `ServerBuilder::run` and `ServerBuilder::spawn`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ServerBuildError {
+    /// No listener was added, so the application would never receive a request.
+    NoListeners,
+
+    /// The number of threads was set to zero.
+    NoThreads,
+
+    /// An address given to the builder which doesn't resolve to a socket address.
+    InvalidAddress {
+        /// Describes the problem.
+        message: String,
+    },
+
+    /// A listener which couldn't be bound, e.g. because another application is listening on its
+    /// address.
+    Bind {
+        /// The address of the listener, e.g. `127.0.0.1:7878` or `unix:/run/myapp/http.sock`.
+        listener: String,
+        /// Describes the problem.
+        message: String,
+    },
+
+    /// An option which only applies to connections accepted over TLS, given without any listener
+    /// added by `bind_tls`.
+    TlsOptionWithoutTlsListener {
+        /// The builder method which set the option, e.g. `tls_handshake_timeout`.
+        option: &'static str,
+    },
+
+    /// `ListenerLayout::PerWorker` was given for an application which can't accept connections
+    /// on each worker thread: one started by `spawn`, or one listening on a Unix domain socket.
+    PerWorkerUnsupported {
+        /// Describes the problem.
+        message: &'static str,
+    },
+}
+
+impl Display for ServerBuildError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            ServerBuildError::NoListeners => write!(f, "at least one listener is required"),
+            ServerBuildError::NoThreads => write!(f, "at least one thread is required"),
+            ServerBuildError::InvalidAddress { ref message } => {
+                write!(f, "invalid listener address: {}", message)
+            }
+            ServerBuildError::Bind {
+                ref listener,
+                ref message,
+            } => write!(f, "unable to listen on {}: {}", listener, message),
+            ServerBuildError::TlsOptionWithoutTlsListener { option } => write!(
+                f,
+                "`{}` was given, but no listener accepts connections over TLS",
+                option
+            ),
+            ServerBuildError::PerWorkerUnsupported { message } => {
+                write!(f, "a listener per worker isn't supported {}", message)
+            }
+        }
+    }
+}
+
+impl Error for ServerBuildError {
+    fn description(&self) -> &str {
+        match *self {
+            ServerBuildError::NoListeners => "no listeners",
+            ServerBuildError::NoThreads => "no threads",
+            ServerBuildError::InvalidAddress { .. } => "invalid listener address",
+            ServerBuildError::Bind { .. } => "unable to bind listener",
+            ServerBuildError::TlsOptionWithoutTlsListener { .. } => {
+                "TLS option without TLS listener"
+            }
+            ServerBuildError::PerWorkerUnsupported { .. } => "listener per worker unsupported",
+        }
+    }
+}
+
+/// Resolves `addr`, binding a TCP listener to the first of its socket addresses which can be
+/// bound.
+fn tcp_listener<A>(addr: A) -> Result<net::TcpListener, ServerBuildError>
+where
+    A: ToSocketAddrs,
+{
+    let addrs: Vec<SocketAddr> = addr
+        .to_socket_addrs()
+        .map_err(|e| ServerBuildError::InvalidAddress {
+            message: e.to_string(),
+        })?
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(ServerBuildError::InvalidAddress {
+            message: "no socket addresses were resolved".to_owned(),
+        });
+    }
+
+    net::TcpListener::bind(&addrs[..]).map_err(|e| ServerBuildError::Bind {
+        listener: addrs[0].to_string(),
+        message: e.to_string(),
+    })
+}
+
+/// Binds a Unix domain socket at each path given to `ServerBuilder::bind_unix`, adding them to
+/// `listeners`. When one can't be bound, the socket files of those already bound are removed.
+#[cfg(unix)]
+fn bind_unix_sockets(
+    mut listeners: Listeners,
+    unix_sockets: Vec<(PathBuf, Option<u32>)>,
+) -> Result<Listeners, ServerBuildError> {
+    let mut bound = Vec::new();
+
+    for (path, mode) in unix_sockets {
+        match ::unix_listener(&path, mode) {
+            Ok(listener) => {
+                bound.push(path.clone());
+                listeners.push(None, ListenerKind::Unix(listener, path));
+            }
+            Err(e) => {
+                for path in bound {
+                    let _ = fs::remove_file(path);
+                }
+
+                return Err(ServerBuildError::Bind {
+                    listener: format!("unix:{}", path.display()),
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(listeners)
+}
+
+/// Removes the socket files of the Unix domain sockets once the application has stopped.
+#[cfg(unix)]
+fn remove_on_shutdown(handle: ShutdownHandle, listeners: &Listeners) -> ShutdownHandle {
+    listeners
+        .socket_paths()
+        .into_iter()
+        .fold(handle, ShutdownHandle::with_socket_path)
+}
+
+#[cfg(not(unix))]
+fn remove_on_shutdown(handle: ShutdownHandle, _listeners: &Listeners) -> ShutdownHandle {
+    handle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+
+    use hyper::{Body, Response, StatusCode};
+
+    use helpers::http::response::create_text_response;
+    use state::State;
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let res = create_text_response(&state, StatusCode::OK, "served");
+        (state, res)
+    }
+
+    fn get(addr: SocketAddr) -> String {
+        let mut stream = net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn requires_a_listener() {
+        let result = ServerBuilder::new(|| Ok(handler)).spawn();
+        assert_eq!(result.err(), Some(ServerBuildError::NoListeners));
+    }
+
+    #[test]
+    fn requires_a_thread() {
+        let result = ServerBuilder::new(|| Ok(handler))
+            .bind("127.0.0.1:0")
+            .threads(0)
+            .spawn();
+        assert_eq!(result.err(), Some(ServerBuildError::NoThreads));
+    }
+
+    #[test]
+    fn reports_invalid_address() {
+        let result = ServerBuilder::new(|| Ok(handler))
+            .bind("not an address")
+            .spawn();
+
+        match result.err() {
+            Some(ServerBuildError::InvalidAddress { .. }) => (),
+            e => panic!("unexpected result: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn reports_first_bind_failure() {
+        let taken = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let taken_addr = taken.local_addr().unwrap();
+
+        // The later problem isn't reported, as the first problem found is returned.
+        let result = ServerBuilder::new(|| Ok(handler))
+            .bind(taken_addr)
+            .threads(0)
+            .spawn();
+
+        match result.err() {
+            Some(ServerBuildError::Bind { ref listener, .. }) => {
+                assert_eq!(listener, &taken_addr.to_string())
+            }
+            e => panic!("unexpected result: {:?}", e),
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn rejects_tls_option_without_tls_listener() {
+        let result = ServerBuilder::new(|| Ok(handler))
+            .bind("127.0.0.1:0")
+            .tls_handshake_timeout(Duration::from_secs(1))
+            .spawn();
+
+        let expected = ServerBuildError::TlsOptionWithoutTlsListener {
+            option: "tls_handshake_timeout",
+        };
+        assert_eq!(result.err(), Some(expected));
+    }
+
+    #[test]
+    fn rejects_listener_per_worker_with_spawn() {
+        let workers = WorkerConfig::new().with_listener_layout(ListenerLayout::PerWorker);
+        let result = ServerBuilder::new(|| Ok(handler))
+            .bind("127.0.0.1:0")
+            .workers(workers)
+            .spawn();
+
+        let expected = ServerBuildError::PerWorkerUnsupported {
+            message: "by `spawn`",
+        };
+        assert_eq!(result.err(), Some(expected));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn binds_unix_sockets_once_checked() {
+        let path =
+            ::std::env::temp_dir().join(format!("gotham-builder-{}.sock", ::std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let result = ServerBuilder::new(|| Ok(handler))
+            .bind_unix(&path, None)
+            .threads(0)
+            .spawn();
+        assert_eq!(result.err(), Some(ServerBuildError::NoThreads));
+        assert!(!path.exists());
+
+        let workers = WorkerConfig::new().with_listener_layout(ListenerLayout::PerWorker);
+        let result = ServerBuilder::new(|| Ok(handler))
+            .bind_unix(&path, None)
+            .workers(workers)
+            .run();
+        let expected = ServerBuildError::PerWorkerUnsupported {
+            message: "with Unix domain sockets",
+        };
+        assert_eq!(result.err(), Some(expected));
+        assert!(!path.exists());
+
+        let server = ServerBuilder::new(|| Ok(handler))
+            .bind_unix(&path, None)
+            .threads(1)
+            .spawn()
+            .unwrap();
+        assert!(path.exists());
+
+        server.shutdown(Duration::from_secs(1)).wait().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn serves_with_listener_per_worker() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let workers = WorkerConfig::new()
+            .with_threads(2)
+            .with_listener_layout(ListenerLayout::PerWorker);
+
+        let internal = Listeners::new().bind("internal", "127.0.0.1:0").unwrap();
+        let internal_addr = internal.addrs()[0];
+
+        ::std::thread::spawn(move || {
+            ServerBuilder::new(|| Ok(handler))
+                .listener(listener)
+                .listeners(internal)
+                .workers(workers)
+                .run()
+                .unwrap()
+        });
+
+        for addr in &[addr, internal_addr] {
+            let response = get(*addr);
+            assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+            assert!(response.ends_with("served"), response);
+        }
+    }
+
+    #[test]
+    fn serves_every_listener_until_shutdown() {
+        let server = ServerBuilder::new(|| Ok(handler))
+            .bind("127.0.0.1:0")
+            .bind("127.0.0.1:0")
+            .threads(1)
+            .keep_alive(Duration::from_secs(5))
+            .spawn()
+            .unwrap();
+
+        let addrs = server.addrs().to_vec();
+        assert_eq!(addrs.len(), 2);
+        assert_eq!(server.addr(), Some(addrs[0]));
+
+        for addr in &addrs {
+            let response = get(*addr);
+            assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+            assert!(response.ends_with("served"), response);
+        }
+
+        server.shutdown(Duration::from_secs(1)).wait().unwrap();
+        assert!(net::TcpStream::connect(addrs[0]).is_err());
+    }
+}
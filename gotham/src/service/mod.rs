@@ -5,22 +5,43 @@ use std::net::SocketAddr;
 use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
 use std::thread;
+#[cfg(feature = "tls")]
+use std::time::Duration;
+use std::time::Instant;
 
 use failure;
 
-use futures::Future;
+use futures::{future, Future};
 use http::request;
+use hyper::header::{HeaderValue, CONNECTION};
+use hyper::server::conn::Http;
 use hyper::service::Service;
-use hyper::{Body, Request, Response};
+use hyper::{Body, Request, Response, StatusCode};
 
 use handler::NewHandler;
-use helpers::http::request::path::RequestPathSegments;
+use helpers::http::request::content_type::put_request_content_type;
+use helpers::http::request::path::put_request_path_segments;
+use helpers::http::response::create_response;
+use keep_alive::{Activity, IdleTimeout, KeepAliveConfig};
+use limits::{ConnectionGuard, ConnectionLimits, LimitedIncoming};
+use middleware::validation::RequestValidation;
+use read_timeout::{BodyTimeout, HeadTimeout, ReadTimeouts};
 use state::client_addr::put_client_addr;
-use state::{set_request_id, State};
+use state::connection::put_connection_info;
+#[cfg(unix)]
+use state::connection::PeerCredentials;
+use state::executor::put_executor;
+use state::request_id::{RequestIdConfig, RequestIdScope};
+use state::request_info::put_request_info;
+use state::times::put_request_times;
+use state::{set_request_id_with, State};
 
 mod timing;
 mod trap;
 
+type ResponseFuture =
+    Box<Future<Item = Response<Body>, Error = failure::Compat<failure::Error>> + Send>;
+
 /// Wraps a `NewHandler` which will be used to serve requests. Used in `gotham::os::*` to bind
 /// incoming connections to `ConnectedGothamService` values.
 pub(crate) struct GothamService<T>
@@ -28,6 +49,34 @@ where
     T: NewHandler + 'static,
 {
     handler: Arc<T>,
+    request_ids: RequestIdConfig,
+    validation: Option<Arc<RequestValidation>>,
+    listener: Option<Arc<str>>,
+    keep_alive: KeepAliveConfig,
+    limits: ConnectionLimits,
+    read_timeouts: ReadTimeouts,
+    #[cfg(feature = "tls")]
+    tls_handshake_timeout: Option<Duration>,
+}
+
+// Implemented manually, as `T` needn't be `Clone` itself.
+impl<T> Clone for GothamService<T>
+where
+    T: NewHandler + 'static,
+{
+    fn clone(&self) -> Self {
+        GothamService {
+            handler: self.handler.clone(),
+            request_ids: self.request_ids.clone(),
+            validation: self.validation.clone(),
+            listener: self.listener.clone(),
+            keep_alive: self.keep_alive.clone(),
+            limits: self.limits.clone(),
+            read_timeouts: self.read_timeouts.clone(),
+            #[cfg(feature = "tls")]
+            tls_handshake_timeout: self.tls_handshake_timeout,
+        }
+    }
 }
 
 impl<T> GothamService<T>
@@ -35,27 +84,242 @@ where
     T: NewHandler + 'static,
 {
     pub(crate) fn new(handler: T) -> GothamService<T> {
+        GothamService::with_request_ids(handler, RequestIdConfig::new())
+    }
+
+    pub(crate) fn with_request_ids(handler: T, request_ids: RequestIdConfig) -> GothamService<T> {
         GothamService {
             handler: Arc::new(handler),
+            request_ids,
+            validation: None,
+            listener: None,
+            keep_alive: KeepAliveConfig::new(),
+            limits: ConnectionLimits::new(),
+            read_timeouts: ReadTimeouts::new(),
+            #[cfg(feature = "tls")]
+            tls_handshake_timeout: None,
         }
     }
 
+    /// Rejects requests which fail the `RequestValidation` before the handler is invoked.
+    pub(crate) fn validate_requests(self, validation: RequestValidation) -> GothamService<T> {
+        GothamService {
+            validation: Some(Arc::new(validation)),
+            ..self
+        }
+    }
+
+    /// Names the listener which connections are accepted from, for `ConnectionInfo`.
+    pub(crate) fn with_listener(self, name: &str) -> GothamService<T> {
+        GothamService {
+            listener: Some(Arc::from(name)),
+            ..self
+        }
+    }
+
+    /// Applies the `KeepAliveConfig` to each connection.
+    pub(crate) fn with_keep_alive(self, keep_alive: KeepAliveConfig) -> GothamService<T> {
+        GothamService { keep_alive, ..self }
+    }
+
+    /// Applies the `ConnectionLimits` to connections, and to the requests of each connection.
+    pub(crate) fn with_connection_limits(self, limits: ConnectionLimits) -> GothamService<T> {
+        GothamService { limits, ..self }
+    }
+
+    /// Applies the `ReadTimeouts` to the requests of each connection.
+    pub(crate) fn with_read_timeouts(self, read_timeouts: ReadTimeouts) -> GothamService<T> {
+        GothamService {
+            read_timeouts,
+            ..self
+        }
+    }
+
+    /// Bounds the time for each TLS connection to complete its handshake.
+    #[cfg(feature = "tls")]
+    pub(crate) fn with_tls_handshake_timeout(self, timeout: Duration) -> GothamService<T> {
+        GothamService {
+            tls_handshake_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// The time for each TLS connection to complete its handshake, if bounded.
+    #[cfg(feature = "tls")]
+    pub(crate) fn tls_handshake_timeout(&self) -> Option<Duration> {
+        self.tls_handshake_timeout
+    }
+
+    /// Limits the connections accepted from `incoming`. Each connection should be connected
+    /// along with its `ConnectionGuard`, via `ConnectedGothamService::with_guard`.
+    pub(crate) fn limit<S>(&self, incoming: S) -> LimitedIncoming<S> {
+        self.limits.limit(incoming)
+    }
+
+    /// The `Http` protocol settings which connections are served with.
+    ///
+    /// Connections are served over HTTP/1.1, unless the client opens the connection with the
+    /// HTTP/2 preface, i.e. with prior knowledge of HTTP/2 support (h2c), in which case Hyper
+    /// switches to HTTP/2. Over TLS, HTTP/2 is also negotiated via ALPN.
+    ///
+    /// Upgrading an HTTP/1.1 connection via `Upgrade: h2c` isn't supported: Hyper has no way to
+    /// hand the upgrading request to its HTTP/2 server as stream 1, which the upgrade requires.
+    /// The header is ignored and the request is served over HTTP/1.1 without `101 Switching
+    /// Protocols`, so clients fall back to HTTP/1.1 and need prior knowledge to use h2c.
+    pub(crate) fn protocol(&self) -> Http {
+        let mut protocol = Http::new();
+        protocol.keep_alive(self.keep_alive.enabled());
+        protocol
+    }
+
     pub(crate) fn connect(&self, client_addr: SocketAddr) -> ConnectedGothamService<T> {
         ConnectedGothamService {
-            client_addr,
+            client_addr: Some(client_addr),
             handler: self.handler.clone(),
+            request_ids: self.request_ids.clone(),
+            validation: self.validation.clone(),
+            local_addr: None,
+            tls: false,
+            listener: self.listener.clone(),
+            keep_alive: self.keep_alive.clone(),
+            activity: Arc::new(Activity::new()),
+            requests: 0,
+            max_concurrent_requests: self.limits.max_concurrent_requests(),
+            read_timeouts: self.read_timeouts.clone(),
+            _guard: None,
+            #[cfg(unix)]
+            peer_credentials: None,
+        }
+    }
+
+    /// Connects a client over a Unix domain socket, which has no client address. The credentials
+    /// of the peer process are recorded instead, when they're available.
+    #[cfg(unix)]
+    pub(crate) fn connect_unix(
+        &self,
+        peer_credentials: Option<PeerCredentials>,
+    ) -> ConnectedGothamService<T> {
+        ConnectedGothamService {
+            client_addr: None,
+            handler: self.handler.clone(),
+            request_ids: self.request_ids.clone(),
+            validation: self.validation.clone(),
+            local_addr: None,
+            tls: false,
+            listener: self.listener.clone(),
+            keep_alive: self.keep_alive.clone(),
+            activity: Arc::new(Activity::new()),
+            requests: 0,
+            max_concurrent_requests: self.limits.max_concurrent_requests(),
+            read_timeouts: self.read_timeouts.clone(),
+            _guard: None,
+            peer_credentials,
         }
     }
 }
 
 /// A `GothamService` which has been connected to a client. The major difference is that a
-/// `client_addr` has been assigned (as this isn't available from Hyper).
+/// `client_addr` has been assigned (as this isn't available from Hyper), unless the client is
+/// connected over a Unix domain socket.
 pub(crate) struct ConnectedGothamService<T>
 where
     T: NewHandler + 'static,
 {
     handler: Arc<T>,
-    client_addr: SocketAddr,
+    request_ids: RequestIdConfig,
+    validation: Option<Arc<RequestValidation>>,
+    client_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+    tls: bool,
+    listener: Option<Arc<str>>,
+    keep_alive: KeepAliveConfig,
+    activity: Arc<Activity>,
+    requests: usize,
+    max_concurrent_requests: Option<usize>,
+    read_timeouts: ReadTimeouts,
+    _guard: Option<ConnectionGuard>,
+    #[cfg(unix)]
+    peer_credentials: Option<PeerCredentials>,
+}
+
+impl<T> ConnectedGothamService<T>
+where
+    T: NewHandler + 'static,
+{
+    /// Records the local address which the connection was accepted on, for `ConnectionInfo`.
+    pub(crate) fn with_local_addr(self, local_addr: Option<SocketAddr>) -> Self {
+        ConnectedGothamService { local_addr, ..self }
+    }
+
+    /// Holds the place of the connection within the `ConnectionLimits`, until it's closed.
+    pub(crate) fn with_guard(self, guard: ConnectionGuard) -> Self {
+        ConnectedGothamService {
+            _guard: Some(guard),
+            ..self
+        }
+    }
+
+    /// Wraps the I/O of the connection, so that it's closed once idle for longer than the idle
+    /// timeout of the `KeepAliveConfig`.
+    pub(crate) fn watch_idle<I>(&self, io: I) -> IdleTimeout<I> {
+        IdleTimeout::new(io, self.keep_alive.idle_timeout(), self.activity.clone())
+    }
+
+    /// Wraps the I/O of the connection, so that it's closed when a request head isn't received
+    /// within the head timeout of the `ReadTimeouts`.
+    pub(crate) fn watch_head<I>(&self, io: I) -> HeadTimeout<I> {
+        HeadTimeout::new(io, self.read_timeouts.clone(), self.activity.clone())
+    }
+
+    /// Wraps the body of a request, so that it fails when it isn't received within the body
+    /// timeout of the `ReadTimeouts`. Returns the deadline for the body along with it.
+    fn watch_body(&self, body: Body) -> (Body, Option<Instant>) {
+        match self.read_timeouts.body_timeout() {
+            Some(body_timeout) => {
+                let deadline = Instant::now() + body_timeout;
+                let timeouts = self.read_timeouts.clone();
+                let body = BodyTimeout::new(body, deadline, timeouts, self.activity.clone());
+                (Body::wrap_stream(body), Some(deadline))
+            }
+            None => (body, None),
+        }
+    }
+
+    /// Records that a request has completed, and marks its response as the last one on the
+    /// connection when `last_request` is set, or the connection has been marked to be closed.
+    fn finish(&self, response: ResponseFuture, last_request: bool) -> ResponseFuture {
+        let activity = self.activity.clone();
+
+        Box::new(response.then(move |result| {
+            activity.end();
+
+            result.map(|mut response| {
+                if last_request || activity.closing() {
+                    response
+                        .headers_mut()
+                        .insert(CONNECTION, HeaderValue::from_static("close"));
+                }
+                response
+            })
+        }))
+    }
+
+    /// Stores the `PeerCredentials` of a client connected over a Unix domain socket.
+    #[cfg(unix)]
+    fn put_peer_credentials(&self, state: &mut State) {
+        if let Some(peer_credentials) = self.peer_credentials {
+            state.put(peer_credentials);
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn put_peer_credentials(&self, _state: &mut State) {}
+
+    /// Records that the connection was accepted over TLS, for `ConnectionInfo`.
+    #[cfg(feature = "tls")]
+    pub(crate) fn with_tls(self, tls: bool) -> Self {
+        ConnectedGothamService { tls, ..self }
+    }
 }
 
 impl<T> Service for ConnectedGothamService<T>
@@ -65,12 +329,24 @@ where
     type ReqBody = Body; // required by hyper::server::conn::Http::serve_connection()
     type ResBody = Body; // has to impl Payload...
     type Error = failure::Compat<failure::Error>; // :Into<Box<StdError + Send + Sync>>
-    type Future = Box<Future<Item = Response<Self::ResBody>, Error = Self::Error> + Send>;
+    type Future = ResponseFuture;
 
     fn call(&mut self, req: Request<Self::ReqBody>) -> Self::Future {
         let mut state = State::new();
 
-        put_client_addr(&mut state, self.client_addr);
+        self.requests += 1;
+        self.activity.begin();
+        let last_request = self
+            .keep_alive
+            .max_requests()
+            .map_or(false, |max_requests| self.requests >= max_requests);
+
+        put_request_times(&mut state);
+        put_executor(&mut state);
+        if let Some(client_addr) = self.client_addr {
+            put_client_addr(&mut state, client_addr);
+        }
+        self.put_peer_credentials(&mut state);
 
         let (
             request::Parts {
@@ -84,23 +360,60 @@ where
             body,
         ) = req.into_parts();
 
-        state.put(RequestPathSegments::new(uri.path()));
         state.put(method);
         state.put(uri);
+        put_request_path_segments(&mut state);
         state.put(version);
         state.put(headers);
+
+        let (body, body_deadline) = self.watch_body(body);
         state.put(body);
+        put_request_info(&mut state);
+        put_connection_info(
+            &mut state,
+            self.local_addr,
+            self.tls,
+            self.listener.clone(),
+            &self.keep_alive,
+            last_request,
+            body_deadline,
+        );
 
-        {
-            let request_id = set_request_id(&mut state);
+        let request_id = {
+            let request_id = set_request_id_with(&mut state, &self.request_ids);
             debug!(
                 "[DEBUG][{}][Thread][{:?}]",
                 request_id,
                 thread::current().id(),
             );
+            request_id.to_owned()
         };
 
-        trap::call_handler(&*self.handler, AssertUnwindSafe(state))
+        // Logs the request ID when the `Content-Type` is malformed, so it must follow the ID.
+        put_request_content_type(&mut state);
+
+        let over_limit = self
+            .max_concurrent_requests
+            .map_or(false, |max| self.activity.in_flight() > max);
+
+        if over_limit {
+            let response = create_response(&state, StatusCode::SERVICE_UNAVAILABLE, None);
+            return self.finish(Box::new(future::ok(response)), last_request);
+        }
+
+        if let Some(ref validation) = self.validation {
+            if let Err(rule) = validation.check(&state) {
+                let response = validation.reject(&state, rule);
+                return self.finish(Box::new(future::ok(response)), last_request);
+            }
+        }
+
+        let handler = &*self.handler;
+        let response = Box::new(RequestIdScope::new(request_id, move || {
+            trap::call_handler(handler, AssertUnwindSafe(state))
+        }));
+
+        self.finish(response, last_request)
     }
 }
 
@@ -108,11 +421,23 @@ where
 mod tests {
     use super::*;
 
+    use std::io;
+    use std::panic::RefUnwindSafe;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    use futures::future::{self, Either};
+    use futures::Stream;
     use hyper::{Body, StatusCode};
+    use mime;
+    use tokio::runtime::Runtime;
+    use tokio::timer::Delay;
 
+    use handler::{HandlerFuture, IntoHandlerError};
+    use helpers::http::header::X_REQUEST_ID;
     use helpers::http::response::create_response;
     use router::builder::*;
-    use state::State;
+    use state::{current_request_id, State};
 
     fn handler(state: State) -> (State, Response<Body>) {
         let res = create_response(&state, StatusCode::ACCEPTED, None);
@@ -150,4 +475,187 @@ mod tests {
         let response = f.wait().unwrap();
         assert_eq!(response.status(), StatusCode::ACCEPTED);
     }
+
+    #[test]
+    fn configured_request_id_generator() {
+        let config = RequestIdConfig::new().with_generator(|| "generated-id".to_owned());
+        let service = GothamService::with_request_ids(|| Ok(handler), config);
+
+        let req = Request::get("http://localhost/")
+            .body(Body::empty())
+            .unwrap();
+        let f = service
+            .connect("127.0.0.1:10000".parse().unwrap())
+            .call(req);
+        let response = f.wait().unwrap();
+        assert_eq!(
+            response.headers().get(X_REQUEST_ID).unwrap(),
+            "generated-id"
+        );
+    }
+
+    #[test]
+    fn request_validation() {
+        let validation = RequestValidation::new()
+            .require_host(true)
+            .max_uri_length(32);
+        let service = GothamService::new(|| Ok(handler)).validate_requests(validation);
+        let mut connected = service.connect("127.0.0.1:10000".parse().unwrap());
+
+        let cases = vec![
+            ("/", Some("localhost"), StatusCode::ACCEPTED),
+            ("/", None, StatusCode::BAD_REQUEST),
+            (
+                "/0123456789/0123456789/0123456789",
+                Some("localhost"),
+                StatusCode::URI_TOO_LONG,
+            ),
+        ];
+
+        for (uri, host, status) in cases {
+            let mut req = Request::get(uri);
+            if let Some(host) = host {
+                req.header("host", host);
+            }
+
+            let response = connected
+                .call(req.body(Body::empty()).unwrap())
+                .wait()
+                .unwrap();
+            assert_eq!(response.status(), status, "{}", uri);
+            assert!(response.headers().get(X_REQUEST_ID).is_some());
+        }
+    }
+
+    fn counting_service<F>(
+        hooks: Arc<AtomicUsize>,
+        f: F,
+    ) -> ConnectedGothamService<impl NewHandler + 'static>
+    where
+        F: Fn(State) -> Box<HandlerFuture> + Copy + Send + Sync + RefUnwindSafe + 'static,
+    {
+        let new_handler = move || {
+            let hooks = hooks.clone();
+            Ok(move |mut state: State| {
+                state.on_drop(move || {
+                    hooks.fetch_add(1, Ordering::SeqCst);
+                });
+                f(state)
+            })
+        };
+
+        GothamService::new(new_handler).connect("127.0.0.1:10000".parse().unwrap())
+    }
+
+    #[test]
+    fn drop_hooks_run_once_after_response() {
+        let hooks = Arc::new(AtomicUsize::new(0));
+        let req = Request::get("http://localhost/")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = counting_service(hooks.clone(), |state| {
+            let res = create_response(&state, StatusCode::OK, None);
+            Box::new(future::ok((state, res)))
+        }).call(req)
+            .wait()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(hooks.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn drop_hooks_run_once_after_error() {
+        let hooks = Arc::new(AtomicUsize::new(0));
+        let req = Request::get("http://localhost/")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = counting_service(hooks.clone(), |state| {
+            let err = io::Error::new(io::ErrorKind::Other, "failed").into_handler_error();
+            Box::new(future::err((state, err)))
+        }).call(req)
+            .wait()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(hooks.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn drop_hooks_run_once_when_request_abandoned() {
+        let hooks = Arc::new(AtomicUsize::new(0));
+        let req = Request::get("http://localhost/")
+            .body(Body::empty())
+            .unwrap();
+
+        // Hyper drops the response future when the client disconnects mid-request, which drops
+        // the `State` held by the pending handler.
+        let f = counting_service(hooks.clone(), |state| {
+            Box::new(future::empty().map(move |()| (state, Response::new(Body::empty()))))
+        }).call(req)
+            .select2(Delay::new(Instant::now() + Duration::from_millis(50)));
+
+        let mut runtime = Runtime::new().unwrap();
+        match runtime.block_on(f) {
+            Ok(Either::B((_, pending))) => {
+                assert_eq!(hooks.load(Ordering::SeqCst), 0);
+                drop(pending);
+            }
+            _ => panic!("request should still be pending"),
+        }
+
+        assert_eq!(hooks.load(Ordering::SeqCst), 1);
+    }
+
+    fn slow_reporting_handler(state: State) -> Box<HandlerFuture> {
+        let before = current_request_id();
+
+        let f = Delay::new(Instant::now() + Duration::from_millis(50)).then(move |_| {
+            let body = format!("{} {}", before.unwrap(), current_request_id().unwrap());
+            let res = create_response(
+                &state,
+                StatusCode::OK,
+                Some((body.into_bytes(), mime::TEXT_PLAIN)),
+            );
+            future::ok((state, res))
+        });
+
+        Box::new(f)
+    }
+
+    #[test]
+    fn current_request_id_does_not_bleed_between_requests() {
+        let service = GothamService::new(|| Ok(slow_reporting_handler));
+
+        let call = |id: &'static str| {
+            let req = Request::get("http://localhost/")
+                .header(X_REQUEST_ID, id)
+                .body(Body::empty())
+                .unwrap();
+
+            service
+                .connect("127.0.0.1:10000".parse().unwrap())
+                .call(req)
+                .and_then(|response| {
+                    response
+                        .into_body()
+                        .concat2()
+                        .map_err(|e| failure::Error::from(e).compat())
+                })
+                .map(|body| String::from_utf8(body.to_vec()).unwrap())
+        };
+
+        // Both requests are pending at the same time, and are polled alternately on the same
+        // thread.
+        let f = call("first-id").join(call("second-id"));
+
+        let mut runtime = Runtime::new().unwrap();
+        let (first, second) = runtime.block_on(f).unwrap();
+
+        assert_eq!(first, "first-id first-id");
+        assert_eq!(second, "second-id second-id");
+        assert_eq!(current_request_id(), None);
+    }
 }
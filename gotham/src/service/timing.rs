@@ -1,25 +1,31 @@
 //! Defines types for timing requests and emitting timing information into logs and responses.
 
 use std::fmt::{self, Display, Formatter};
+use std::time::Instant;
 
-use chrono::prelude::*;
 use hyper::Response;
 
 use helpers::http::header::X_RUNTIME_MICROSECONDS;
-use state::{request_id, State};
+use state::times::RequestTimes;
+use state::{request_id, FromState, State};
 
 /// Used by `GothamService` to time requests. The `elapsed` function returns the elapsed time
 /// in a way that can be used for logging and adding the `X-Runtime-Microseconds` header to
 /// responses.
 #[derive(Clone, Copy)]
 pub(super) struct Timer {
-    start: DateTime<Utc>,
+    start: Instant,
 }
 
 impl Timer {
-    /// Begins measuring from the current time.
-    pub(super) fn new() -> Timer {
-        Timer { start: Utc::now() }
+    /// Begins measuring from the arrival time recorded in `RequestTimes`, or from the current time
+    /// if it is not present.
+    pub(super) fn new(state: &State) -> Timer {
+        let start = RequestTimes::try_borrow_from(state)
+            .map(|times| times.arrived())
+            .unwrap_or_else(Instant::now);
+
+        Timer { start }
     }
 
     /// Finishes measuring, and returns the elapsed time as a `Timing` value.
@@ -28,7 +34,7 @@ impl Timer {
 
         if let Timing::Invalid = timing {
             error!(
-                "[{}] Unable to measure timing of request, num_microseconds was None",
+                "[{}] Unable to measure timing of request, elapsed microseconds overflowed",
                 request_id(state)
             );
         }
@@ -38,9 +44,15 @@ impl Timer {
 
     pub(super) fn elapsed_no_logging(self) -> Timing {
         let Timer { start } = self;
-        match Utc::now().signed_duration_since(start).num_microseconds() {
-            Some(dur) => Timing::Microseconds(dur),
-            None => Timing::Invalid,
+        let dur = start.elapsed();
+        let micros = dur
+            .as_secs()
+            .checked_mul(1_000_000)
+            .and_then(|us| us.checked_add(u64::from(dur.subsec_micros())));
+
+        match micros {
+            Some(us) if us <= i64::max_value() as u64 => Timing::Microseconds(us as i64),
+            _ => Timing::Invalid,
         }
     }
 }
@@ -3,6 +3,8 @@
 
 use std::any::Any;
 use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::net::SocketAddr;
 use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::{io, mem};
 
@@ -12,8 +14,10 @@ use futures::Async;
 use hyper::{Body, Response, StatusCode};
 
 use handler::{Handler, HandlerError, IntoResponse, NewHandler};
+use router::matched::MatchedRoute;
 use service::timing::Timer;
-use state::{request_id, State};
+use state::request_info::RequestInfo;
+use state::{client_addr, request_id, FromState, State};
 
 type CompatError = failure::Compat<failure::Error>;
 
@@ -23,6 +27,9 @@ type CompatError = failure::Compat<failure::Error>;
 ///
 /// Timing information is recorded and logged, except in the case of a panic where the timer is
 /// moved and cannot be recovered.
+///
+/// The `State` is owned by the `Handler` and is lost when a panic occurs, so identifying details
+/// of the request are captured beforehand and included when the panic is logged.
 pub(super) fn call_handler<'a, T>(
     t: &T,
     state: AssertUnwindSafe<State>,
@@ -30,7 +37,8 @@ pub(super) fn call_handler<'a, T>(
 where
     T: NewHandler + 'a,
 {
-    let timer = Timer::new();
+    let timer = Timer::new(&state);
+    let context = RequestContext::capture(&state);
 
     let res = catch_unwind(move || {
         // Hyper doesn't allow us to present an affine-typed `Handler` interface directly. We have
@@ -49,15 +57,76 @@ where
             })
     });
 
-    if let Ok(f) = res {
-        return Box::new(
+    match res {
+        // must be Future<Item = impl Payload>
+        Ok(f) => Box::new(
             UnwindSafeFuture::new(f)
                 .catch_unwind()
-                .then(finalize_catch_unwind_response), // must be Future<Item = impl Payload>
-        );
+                .then(move |r| finalize_catch_unwind_response(&context, r)),
+        ),
+        Err(payload) => Box::new(finalize_panic_response(timer, &context, &*payload)),
+    }
+}
+
+/// Identifying details of a request, used as the prefix of each log entry. This is captured
+/// before the `State` is handed to the `Handler` so that it remains available for logging after a
+/// panic. The query string is omitted, since it may carry credentials or personal data.
+///
+/// Once the `Handler` has finished, the context is captured again, and then includes the pattern
+/// of the route which the request was dispatched to (see `MatchedRoute`).
+struct RequestContext {
+    request_id: String,
+    info: Option<RequestInfo>,
+    client_addr: Option<SocketAddr>,
+    route: Option<String>,
+}
+
+impl RequestContext {
+    fn capture(state: &State) -> RequestContext {
+        RequestContext {
+            request_id: request_id(state).to_owned(),
+            info: RequestInfo::try_borrow_from(state).cloned(),
+            client_addr: client_addr(state),
+            route: MatchedRoute::try_borrow_from(state).map(|matched| matched.pattern().to_owned()),
+        }
+    }
+}
+
+impl Display for RequestContext {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "[{}]", self.request_id)?;
+
+        if let Some(ref info) = self.info {
+            write!(
+                f,
+                "[{} {} {:?} ({} headers, {} bytes)]",
+                info.method(),
+                info.path(),
+                info.version(),
+                info.header_count(),
+                info.header_bytes()
+            )?;
+        }
+
+        if let Some(addr) = self.client_addr {
+            write!(f, "[{}]", addr)?;
+        }
+
+        if let Some(ref route) = self.route {
+            write!(f, "[route {}]", route)?;
+        }
+
+        Ok(())
     }
+}
 
-    Box::new(finalize_panic_response(timer))
+/// Extracts the message from a panic payload, when it was created by `panic!` or similar.
+fn panic_message(payload: &(Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&'static str>()
+        .cloned()
+        .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+        .unwrap_or("(no message)")
 }
 
 fn finalize_success_response(
@@ -68,9 +137,8 @@ fn finalize_success_response(
     let timing = timer.elapsed(&state);
 
     info!(
-        "[RESPONSE][{}][{:?}][{}][{}]",
-        request_id(&state),
-        response.version(),
+        "[RESPONSE]{}[{}][{}]",
+        RequestContext::capture(&state),
         response.status(),
         timing
     );
@@ -94,8 +162,8 @@ fn finalize_error_response(
             .unwrap_or(err.description());
 
         error!(
-            "[ERROR][{}][Error: {}][{}]",
-            request_id(&state),
+            "[ERROR]{}[Error: {}][{}]",
+            RequestContext::capture(&state),
             err_description,
             timing
         );
@@ -104,11 +172,17 @@ fn finalize_error_response(
     future::ok(err.into_response(&state))
 }
 
-fn finalize_panic_response(timer: Timer) -> FutureResult<Response<Body>, CompatError> {
+fn finalize_panic_response(
+    timer: Timer,
+    context: &RequestContext,
+    payload: &(Any + Send),
+) -> FutureResult<Response<Body>, CompatError> {
     let timing = timer.elapsed_no_logging();
 
     error!(
-        "[PANIC][A panic occurred while invoking the handler][{}]",
+        "[PANIC]{}[A panic occurred while invoking the handler: {}][{}]",
+        context,
+        panic_message(payload),
         timing
     );
 
@@ -121,19 +195,26 @@ fn finalize_panic_response(timer: Timer) -> FutureResult<Response<Body>, CompatE
 }
 
 fn finalize_catch_unwind_response(
+    context: &RequestContext,
     result: Result<Result<Response<Body>, CompatError>, Box<Any + Send>>,
 ) -> FutureResult<Response<Body>, CompatError> {
     let response = result
-        .unwrap_or_else(|_| {
+        .unwrap_or_else(|payload| {
             let e = io::Error::new(
                 io::ErrorKind::Other,
-                "Attempting to poll the future caused a panic",
+                format!(
+                    "Attempting to poll the future caused a panic: {}",
+                    panic_message(&*payload)
+                ),
             );
 
             Err(failure::Error::from(e).compat())
         })
-        .unwrap_or_else(|_| {
-            error!("[PANIC][A panic occurred while polling the future]");
+        .unwrap_or_else(|e| {
+            error!(
+                "[PANIC]{}[A panic occurred while polling the future][{}]",
+                context, e
+            );
             Response::builder()
                 .status(StatusCode::INTERNAL_SERVER_ERROR)
                 .body(Body::default())
@@ -197,14 +278,57 @@ where
 mod tests {
     use super::*;
 
+    use std::cell::RefCell;
     use std::io;
+    use std::sync::Once;
 
-    use hyper::{HeaderMap, Method, StatusCode};
+    use hyper::{HeaderMap, Method, StatusCode, Uri, Version};
+    use log::{self, LevelFilter, Log, Metadata, Record};
 
     use handler::{HandlerFuture, IntoHandlerError};
     use helpers::http::response::create_response;
+    use state::client_addr::put_client_addr;
+    use state::request_info::put_request_info;
     use state::set_request_id;
 
+    thread_local! {
+        static CAPTURED: RefCell<Vec<String>> = RefCell::new(Vec::new());
+    }
+
+    /// Captures error logs emitted on the current thread, so that tests running concurrently do
+    /// not observe each other's logs.
+    struct CaptureLogger;
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= log::Level::Error
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                let line = format!("{}", record.args());
+                CAPTURED.with(|captured| captured.borrow_mut().push(line));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CaptureLogger = CaptureLogger;
+    static INIT_LOGGER: Once = Once::new();
+
+    fn captured_logs() -> Vec<String> {
+        // Only one logger can be installed per process, so it is shared by every test which
+        // captures logs, and left in place if another logger was installed first.
+        INIT_LOGGER.call_once(|| {
+            if log::set_logger(&LOGGER).is_ok() {
+                log::set_max_level(LevelFilter::Error);
+            }
+        });
+
+        CAPTURED.with(|captured| captured.borrow().clone())
+    }
+
     #[test]
     fn success() {
         let new_handler = || {
@@ -332,4 +456,79 @@ mod tests {
         let response = r.wait().unwrap();
         assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
     }
+
+    #[test]
+    fn panic_report_includes_request_context() {
+        captured_logs();
+
+        let new_handler = || {
+            Ok(|_| {
+                let val: Option<Box<HandlerFuture>> = None;
+                val.expect("test panic")
+            })
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Request-ID", "panic-report-id".parse().unwrap());
+
+        let mut state = State::new();
+        state.put(headers);
+        state.put(Method::GET);
+        state.put("http://localhost/panicking?step=1".parse::<Uri>().unwrap());
+        state.put(Version::HTTP_11);
+        put_client_addr(&mut state, "127.0.0.1:10000".parse().unwrap());
+        put_request_info(&mut state);
+        set_request_id(&mut state);
+
+        let r = call_handler(&new_handler, AssertUnwindSafe(state));
+        let response = r.wait().unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let logs = captured_logs();
+        let report = logs
+            .iter()
+            .find(|line| line.contains("panic-report-id"))
+            .expect("panic should be logged with the request id");
+
+        assert!(report.starts_with("[PANIC][panic-report-id]"));
+        assert!(report.contains("[GET /panicking HTTP/1.1 (1 headers, 27 bytes)]"));
+        assert!(!report.contains("step=1"));
+        assert!(report.contains("[127.0.0.1:10000]"));
+        assert!(report.contains("test panic"));
+    }
+
+    #[test]
+    fn error_report_includes_matched_route() {
+        captured_logs();
+
+        let new_handler = || {
+            Ok(|mut state: State| {
+                state.put(MatchedRoute::new(None, "/users/:id"));
+                Box::new(future::err((
+                    state,
+                    io::Error::new(io::ErrorKind::Other, "test error").into_handler_error(),
+                ))) as Box<HandlerFuture>
+            })
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Request-ID", "route-report-id".parse().unwrap());
+
+        let mut state = State::new();
+        state.put(headers);
+        state.put(Method::GET);
+        set_request_id(&mut state);
+
+        let r = call_handler(&new_handler, AssertUnwindSafe(state));
+        let response = r.wait().unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let logs = captured_logs();
+        let report = logs
+            .iter()
+            .find(|line| line.contains("route-report-id"))
+            .expect("error should be logged with the request id");
+
+        assert!(report.starts_with("[ERROR][route-report-id][route /users/:id]"));
+    }
 }
@@ -0,0 +1,438 @@
+//! Defines `ShutdownHandle`, which stops a Gotham application started via
+//! `gotham::start_with_shutdown` or `ServerBuilder::spawn`, allowing the requests in flight to
+//! complete.
+
+use std::fs;
+use std::mem;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::Shared;
+use futures::sync::{mpsc, oneshot};
+use futures::{Async, Future, Poll, Stream};
+use hyper::server::conn::Connection;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::runtime::Runtime;
+use tokio::timer::Delay;
+
+use handler::NewHandler;
+use service::ConnectedGothamService;
+
+type Completion = Box<Future<Item = (), Error = ()> + Send>;
+
+/// Stops a Gotham application started via `gotham::start_with_shutdown` or `ServerBuilder::spawn`
+/// (or `gotham::start_unix_with_shutdown`, which also removes the socket file once stopped).
+///
+/// Once `shutdown` is called, the application stops accepting connections, and each open
+/// connection is closed once its request in flight (if any) has completed. Connections which are
+/// still open when the deadline passes are dropped, along with their requests. The `Shutdown`
+/// future resolves once every connection has closed, and the threads of the application have
+/// stopped.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # extern crate futures;
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::time::Duration;
+/// # use futures::Future;
+/// # use hyper::{Body, Response};
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn wait_for_signal() {}
+/// #
+/// # fn main() {
+/// let server = gotham::start_with_shutdown("127.0.0.1:7878", || Ok(handler));
+///
+/// wait_for_signal();
+/// server.shutdown(Duration::from_secs(30)).wait().unwrap();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    addrs: Vec<SocketAddr>,
+    ready: Shared<oneshot::Receiver<()>>,
+    socket_paths: Mutex<Vec<PathBuf>>,
+    trigger: Mutex<Option<oneshot::Sender<Instant>>>,
+    completion: Shared<Completion>,
+    runtime: Mutex<Option<Runtime>>,
+}
+
+impl ShutdownHandle {
+    /// The address which the application is listening on, or `None` for an application listening
+    /// on a Unix domain socket. For an application with several TCP listeners, this is the address
+    /// of the first.
+    pub fn addr(&self) -> Option<SocketAddr> {
+        self.inner.addrs.first().cloned()
+    }
+
+    /// The addresses of every TCP listener of the application, in the order they were added.
+    pub fn addrs(&self) -> &[SocketAddr] {
+        &self.inner.addrs
+    }
+
+    /// A `Future` which resolves once the application has begun accepting connections, or fails
+    /// if the application stops before then.
+    ///
+    /// The listener is bound before `gotham::start_with_shutdown` returns, so connections made
+    /// before then are queued rather than refused, but may wait for the application to start.
+    pub fn ready(&self) -> Ready {
+        Ready {
+            ready: self.inner.ready.clone(),
+        }
+    }
+
+    /// Begins shutting down the application, allowing its requests in flight until `deadline`
+    /// passes to complete. Calling `shutdown` again has no further effect, other than returning
+    /// another `Shutdown` future.
+    ///
+    /// The `Shutdown` future should be waited on outside of the application, e.g. in `main`, as
+    /// it stops the threads of the application as it resolves.
+    pub fn shutdown(&self, deadline: Duration) -> Shutdown {
+        if let Some(trigger) = self.inner.trigger.lock().unwrap().take() {
+            info!(
+                target: "gotham::shutdown",
+                " Gotham shutting down, allowing up to {:?} for requests in flight",
+                deadline
+            );
+
+            // The receivers are only dropped once the application has stopped.
+            let _ = trigger.send(Instant::now() + deadline);
+        }
+
+        Shutdown {
+            completion: self.inner.completion.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Sets the `Runtime` which the application runs on, which is shut down once every
+    /// connection has closed.
+    pub(crate) fn with_runtime(self, runtime: Runtime) -> ShutdownHandle {
+        *self.inner.runtime.lock().unwrap() = Some(runtime);
+        self
+    }
+
+    /// Adds the path of a Unix domain socket which the application is listening on, which is
+    /// removed once the application has stopped.
+    #[cfg(unix)]
+    pub(crate) fn with_socket_path(self, socket_path: PathBuf) -> ShutdownHandle {
+        self.inner.socket_paths.lock().unwrap().push(socket_path);
+        self
+    }
+}
+
+/// A `Future` which resolves once an application has begun accepting connections, as returned by
+/// `ShutdownHandle::ready`.
+pub struct Ready {
+    ready: Shared<oneshot::Receiver<()>>,
+}
+
+impl Future for Ready {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        match self.ready.poll() {
+            Ok(Async::Ready(_)) => Ok(Async::Ready(())),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(_) => Err(()),
+        }
+    }
+}
+
+/// A `Future` which resolves once an application has stopped, as returned by
+/// `ShutdownHandle::shutdown`.
+pub struct Shutdown {
+    completion: Shared<Completion>,
+    inner: Arc<Inner>,
+}
+
+impl Future for Shutdown {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        if let Ok(Async::NotReady) = self.completion.poll() {
+            return Ok(Async::NotReady);
+        }
+
+        // Dropping the `Runtime` waits for its threads to stop.
+        let runtime = self.inner.runtime.lock().unwrap().take();
+        drop(runtime);
+
+        let socket_paths = mem::replace(&mut *self.inner.socket_paths.lock().unwrap(), Vec::new());
+        for socket_path in socket_paths {
+            if let Err(e) = fs::remove_file(&socket_path) {
+                warn!("unable to remove socket file {:?}: {}", socket_path, e);
+            }
+        }
+
+        info!(target: "gotham::shutdown", " Gotham stopped");
+        Ok(Async::Ready(()))
+    }
+}
+
+/// The receiving side of a `ShutdownHandle`, held by the task accepting connections and by each
+/// connection. The `Shutdown` future resolves once every `ShutdownSignal` has been dropped.
+#[derive(Clone)]
+pub(crate) struct ShutdownSignal {
+    deadline: Shared<oneshot::Receiver<Instant>>,
+    ready: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    _open: mpsc::Sender<()>,
+}
+
+/// Creates a `ShutdownHandle` for an application listening on `addrs`, along with the
+/// `ShutdownSignal` which the application observes.
+pub(crate) fn shutdown_handle(addrs: Vec<SocketAddr>) -> (ShutdownHandle, ShutdownSignal) {
+    let (trigger, deadline) = oneshot::channel();
+    let (ready_tx, ready_rx) = oneshot::channel();
+    let (open, closed) = mpsc::channel(0);
+
+    // The stream ends once every sender has been dropped. No value is ever sent.
+    let completion: Completion = Box::new(closed.for_each(|()| Ok(())));
+
+    let handle = ShutdownHandle {
+        inner: Arc::new(Inner {
+            addrs,
+            ready: ready_rx.shared(),
+            socket_paths: Mutex::new(Vec::new()),
+            trigger: Mutex::new(Some(trigger)),
+            completion: completion.shared(),
+            runtime: Mutex::new(None),
+        }),
+    };
+
+    let signal = ShutdownSignal {
+        deadline: deadline.shared(),
+        ready: Arc::new(Mutex::new(Some(ready_tx))),
+        _open: open,
+    };
+
+    (handle, signal)
+}
+
+impl ShutdownSignal {
+    /// Records that the application has begun accepting connections, resolving `Ready`.
+    pub(crate) fn ready(&self) {
+        if let Some(ready) = self.ready.lock().unwrap().take() {
+            let _ = ready.send(());
+        }
+    }
+
+    /// A `Future` which resolves with the deadline once shutdown begins. When every
+    /// `ShutdownHandle` is dropped without shutting down, it never resolves.
+    pub(crate) fn triggered(&self) -> Triggered {
+        Triggered {
+            deadline: self.deadline.clone(),
+        }
+    }
+
+    /// Serves `connection` until it completes, or until shutdown begins and the connection has
+    /// finished its request in flight, or the deadline has passed.
+    pub(crate) fn watch<I, NH>(
+        &self,
+        connection: Connection<I, ConnectedGothamService<NH>>,
+    ) -> GracefulConnection<I, NH>
+    where
+        I: AsyncRead + AsyncWrite + 'static,
+        NH: NewHandler + 'static,
+    {
+        GracefulConnection {
+            connection,
+            triggered: self.triggered(),
+            deadline: None,
+            _signal: self.clone(),
+        }
+    }
+}
+
+/// A `Future` which resolves once shutdown begins, as returned by `ShutdownSignal::triggered`.
+pub(crate) struct Triggered {
+    deadline: Shared<oneshot::Receiver<Instant>>,
+}
+
+impl Future for Triggered {
+    type Item = Instant;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Instant, ()> {
+        match self.deadline.poll() {
+            Ok(Async::Ready(deadline)) => Ok(Async::Ready(*deadline)),
+            // A canceled trigger means that shutdown will never begin.
+            Ok(Async::NotReady) | Err(_) => Ok(Async::NotReady),
+        }
+    }
+}
+
+/// A connection which closes gracefully when shutdown begins, as returned by
+/// `ShutdownSignal::watch`.
+pub(crate) struct GracefulConnection<I, NH>
+where
+    NH: NewHandler + 'static,
+{
+    connection: Connection<I, ConnectedGothamService<NH>>,
+    triggered: Triggered,
+    deadline: Option<Delay>,
+    _signal: ShutdownSignal,
+}
+
+impl<I, NH> Future for GracefulConnection<I, NH>
+where
+    I: AsyncRead + AsyncWrite + 'static,
+    NH: NewHandler + 'static,
+{
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        if self.deadline.is_none() {
+            if let Ok(Async::Ready(deadline)) = self.triggered.poll() {
+                self.connection.graceful_shutdown();
+                self.deadline = Some(Delay::new(deadline));
+            }
+        }
+
+        if let Some(ref mut deadline) = self.deadline {
+            match deadline.poll() {
+                Ok(Async::NotReady) => (),
+                _ => {
+                    debug!("dropping connection at shutdown deadline");
+                    return Ok(Async::Ready(()));
+                }
+            }
+        }
+
+        match self.connection.poll() {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Ok(Async::Ready(())) => Ok(Async::Ready(())),
+            Err(e) => {
+                debug!("connection closed with error: {}", e);
+                Ok(Async::Ready(()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+    use std::net;
+    use std::thread;
+
+    use futures::future;
+    use hyper::{Body, Response, StatusCode};
+
+    use handler::HandlerFuture;
+    use helpers::http::response::create_text_response;
+    use state::State;
+
+    fn sleep(ms: u64) {
+        thread::sleep(Duration::from_millis(ms));
+    }
+
+    fn slow(state: State) -> Box<HandlerFuture> {
+        let f = Delay::new(Instant::now() + Duration::from_millis(500)).then(move |_| {
+            let res = create_text_response(&state, StatusCode::OK, "done");
+            future::ok((state, res))
+        });
+
+        Box::new(f)
+    }
+
+    fn never(_state: State) -> Box<HandlerFuture> {
+        Box::new(future::empty())
+    }
+
+    /// Sends a request to the application, without waiting for the response.
+    fn begin_request(addr: SocketAddr) -> net::TcpStream {
+        let mut stream = net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        stream
+    }
+
+    /// Waits for the application to stop accepting connections.
+    fn assert_refused(addr: SocketAddr) {
+        for _ in 0..100 {
+            if net::TcpStream::connect(addr).is_err() {
+                return;
+            }
+            sleep(10);
+        }
+
+        panic!("connections are still accepted after shutdown");
+    }
+
+    #[test]
+    fn completes_requests_in_flight() {
+        let server = ::start_with_shutdown("127.0.0.1:0", || Ok(slow));
+        let addr = server.addr().unwrap();
+
+        let mut stream = begin_request(addr);
+        sleep(100);
+
+        let shutdown = server.shutdown(Duration::from_secs(5));
+        assert_refused(addr);
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+        assert!(response.ends_with("done"), response);
+
+        shutdown.wait().unwrap();
+    }
+
+    #[test]
+    fn resolves_ready_once_accepting() {
+        let server = ::start_with_shutdown("127.0.0.1:0", || Ok(slow));
+        server.ready().wait().unwrap();
+
+        // Connections which haven't been accepted when shutdown begins are refused.
+        let mut stream = begin_request(server.addr().unwrap());
+        sleep(100);
+
+        let mut response = String::new();
+        let shutdown = server.shutdown(Duration::from_secs(5));
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.ends_with("done"), response);
+
+        shutdown.wait().unwrap();
+
+        // Once stopped, the application remains ready.
+        server.ready().wait().unwrap();
+    }
+
+    #[test]
+    fn drops_requests_at_deadline() {
+        let server = ::start_with_shutdown("127.0.0.1:0", || Ok(never));
+        let addr = server.addr().unwrap();
+
+        let mut stream = begin_request(addr);
+        sleep(100);
+
+        let started = Instant::now();
+        server.shutdown(Duration::from_millis(100)).wait().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_refused(addr);
+
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response);
+        assert!(response.is_empty());
+    }
+}
@@ -1,21 +1,22 @@
 //! Defines storage for the remote address of the client
 
-use state::{FromState, State, StateData};
+use state::{FromState, State};
 use std::net::SocketAddr;
 
-struct ClientAddr {
-    addr: SocketAddr,
-}
-
-impl StateData for ClientAddr {}
-
+/// Stores the client `SocketAddr` in `State`, before any `Middleware` or `Handler` is invoked.
+///
+/// The address is also available via `SocketAddr::borrow_from(&state)` (or `try_borrow_from`),
+/// as an alternative to `client_addr`.
 pub(crate) fn put_client_addr(state: &mut State, addr: SocketAddr) {
-    state.put(ClientAddr { addr })
+    state.put(addr)
 }
 
 /// Returns the client `SocketAddr` as reported by hyper, if one was present. Certain connections
 /// do not report a client address, in which case this will return `None`.
 ///
+/// This is the address of the immediate peer of the connection, which may be a proxy rather than
+/// the originating client.
+///
 /// # Examples
 ///
 /// ```rust
@@ -56,5 +57,35 @@ pub(crate) fn put_client_addr(state: &mut State, addr: SocketAddr) {
 /// #   assert_eq!(buf[..10], b"127.0.0.1:9816"[0..10]);
 /// # }
 pub fn client_addr(state: &State) -> Option<SocketAddr> {
-    ClientAddr::try_borrow_from(&state).map(|c| c.addr)
+    SocketAddr::try_borrow_from(&state).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, Response, StatusCode};
+
+    use test::TestServer;
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let addr = SocketAddr::borrow_from(&state);
+        assert_eq!(Some(*addr), client_addr(&state));
+
+        let body = format!("{}", addr.ip());
+        (state, Response::new(body.into()))
+    }
+
+    #[test]
+    fn client_addr_present_in_state() {
+        let test_server = TestServer::new(|| Ok(handler)).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "127.0.0.1");
+    }
 }
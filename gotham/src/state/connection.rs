@@ -0,0 +1,268 @@
+//! Defines information about the connection on which the request was received.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use hyper::header::{HeaderMap, CONNECTION};
+use hyper::Version;
+
+use keep_alive::KeepAliveConfig;
+use state::{FromState, State};
+
+/// Describes the connection on which the request was received, available via
+/// `ConnectionInfo::borrow_from(&state)`.
+///
+/// This is stored by `GothamService` before any `Router`, `Pipeline` or `Middleware` is invoked.
+/// The HTTP version of the request is available separately, via `Version::borrow_from(&state)`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode, Version};
+/// # use gotham::helpers::http::response::create_text_response;
+/// # use gotham::state::connection::ConnectionInfo;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let message = {
+///         let connection = ConnectionInfo::borrow_from(&state);
+///         format!(
+///             "{:?} keep-alive={} tls={}",
+///             Version::borrow_from(&state),
+///             connection.keep_alive(),
+///             connection.tls()
+///         )
+///     };
+///
+///     let res = create_text_response(&state, StatusCode::OK, message);
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://localhost/")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(
+/// #       response.read_utf8_body().unwrap(),
+/// #       "HTTP/1.1 keep-alive=true tls=false"
+/// #   );
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConnectionInfo {
+    keep_alive: bool,
+    tls: bool,
+    local_addr: Option<SocketAddr>,
+    listener: Option<Arc<str>>,
+    idle_timeout: Option<Duration>,
+    max_requests: Option<usize>,
+    body_deadline: Option<Instant>,
+}
+
+impl ConnectionInfo {
+    /// Determines if the connection will be kept open after the response.
+    ///
+    /// This is the default for HTTP/1.1 unless the request has a `Connection: close` header, and
+    /// is only the case for HTTP/1.0 when the request has a `Connection: keep-alive` header. It's
+    /// never the case when the `KeepAliveConfig` of the application disables keep-alive, or for
+    /// the last request the connection is allowed to serve.
+    pub fn keep_alive(&self) -> bool {
+        self.keep_alive
+    }
+
+    /// Determines if the request was received over TLS, i.e. by an application started via
+    /// `gotham::start_with_tls`. This doesn't reflect TLS terminated by a proxy in front of the
+    /// application, which is described by its `Forwarded` or `X-Forwarded-Proto` headers.
+    pub fn tls(&self) -> bool {
+        self.tls
+    }
+
+    /// The local address which the connection was accepted on, if known.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// The name of the listener which the connection was accepted from, for an application
+    /// started via `gotham::start_on_listeners`. This allows middleware to treat listeners
+    /// differently, e.g. only exposing admin routes on an internal listener.
+    pub fn listener(&self) -> Option<&str> {
+        self.listener.as_ref().map(|name| &**name)
+    }
+
+    /// The time the connection is kept open without a new request, if limited by the
+    /// `KeepAliveConfig` of the application.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+
+    /// The number of requests the connection serves before it's closed, if limited by the
+    /// `KeepAliveConfig` of the application.
+    pub fn max_requests(&self) -> Option<usize> {
+        self.max_requests
+    }
+
+    /// The time by which the request body must have been received, if limited by the
+    /// `ReadTimeouts` of the application. Reading the body fails once it has passed.
+    pub fn body_deadline(&self) -> Option<Instant> {
+        self.body_deadline
+    }
+}
+
+/// The credentials of the process at the other end of a Unix domain socket, available via
+/// `PeerCredentials::try_borrow_from(&state)` for a request received by an application started
+/// via `gotham::start_unix`. Such a request has no client address, so `client_addr` returns
+/// `None` for it.
+#[cfg(unix)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerCredentials {
+    uid: u32,
+    gid: u32,
+}
+
+#[cfg(unix)]
+impl PeerCredentials {
+    pub(crate) fn new(uid: u32, gid: u32) -> PeerCredentials {
+        PeerCredentials { uid, gid }
+    }
+
+    /// The effective user ID of the peer process.
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// The effective group ID of the peer process.
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+}
+
+/// Stores a `ConnectionInfo` derived from the request `Version` and headers already placed in
+/// `State`, and from the connection the request was received on.
+pub(crate) fn put_connection_info(
+    state: &mut State,
+    local_addr: Option<SocketAddr>,
+    tls: bool,
+    listener: Option<Arc<str>>,
+    keep_alive: &KeepAliveConfig,
+    last_request: bool,
+    body_deadline: Option<Instant>,
+) {
+    let requested = {
+        let version = Version::try_borrow_from(state)
+            .cloned()
+            .unwrap_or(Version::HTTP_11);
+
+        let headers = HeaderMap::try_borrow_from(state);
+        let has_token = |token: &str| {
+            headers
+                .map(|headers| {
+                    headers
+                        .get_all(CONNECTION)
+                        .iter()
+                        .filter_map(|value| value.to_str().ok())
+                        .flat_map(|value| value.split(','))
+                        .any(|t| t.trim().eq_ignore_ascii_case(token))
+                })
+                .unwrap_or(false)
+        };
+
+        match version {
+            Version::HTTP_09 => false,
+            Version::HTTP_10 => has_token("keep-alive"),
+            Version::HTTP_11 => !has_token("close"),
+            _ => true,
+        }
+    };
+
+    state.put(ConnectionInfo {
+        keep_alive: requested && keep_alive.enabled() && !last_request,
+        tls,
+        local_addr,
+        listener,
+        idle_timeout: keep_alive.idle_timeout(),
+        max_requests: keep_alive.max_requests(),
+        body_deadline,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::HeaderValue;
+    use hyper::{Body, Response};
+
+    use test::TestServer;
+
+    fn connection_info(version: Version, connection: &[&'static str]) -> ConnectionInfo {
+        let mut headers = HeaderMap::new();
+        for value in connection {
+            headers.append(CONNECTION, value.parse().unwrap());
+        }
+
+        let mut state = State::new();
+        state.put(version);
+        state.put(headers);
+        put_connection_info(
+            &mut state,
+            "127.0.0.1:7878".parse().ok(),
+            false,
+            None,
+            &KeepAliveConfig::new(),
+            false,
+            None,
+        );
+
+        ConnectionInfo::borrow_from(&state).clone()
+    }
+
+    #[test]
+    fn keep_alive_defaults() {
+        assert!(connection_info(Version::HTTP_11, &[]).keep_alive());
+        assert!(!connection_info(Version::HTTP_11, &["Close"]).keep_alive());
+        assert!(!connection_info(Version::HTTP_11, &["upgrade", "te, close"]).keep_alive());
+
+        assert!(!connection_info(Version::HTTP_10, &[]).keep_alive());
+        assert!(connection_info(Version::HTTP_10, &["Keep-Alive"]).keep_alive());
+
+        assert!(connection_info(Version::HTTP_2, &[]).keep_alive());
+    }
+
+    #[test]
+    fn http_10_request() {
+        fn handler(state: State) -> (State, Response<Body>) {
+            let body = format!(
+                "{:?} {}",
+                Version::borrow_from(&state),
+                ConnectionInfo::borrow_from(&state).keep_alive()
+            );
+            (state, Response::new(body.into()))
+        }
+
+        let test_server = TestServer::new(|| Ok(handler)).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .with_version(Version::HTTP_10)
+            .with_header(CONNECTION, HeaderValue::from_static("close"))
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.read_utf8_body().unwrap(), "HTTP/1.0 false");
+    }
+
+    #[test]
+    fn local_addr() {
+        let info = connection_info(Version::HTTP_11, &[]);
+        assert_eq!(info.local_addr(), "127.0.0.1:7878".parse().ok());
+        assert!(!info.tls());
+    }
+}
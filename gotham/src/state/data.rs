@@ -1,9 +1,21 @@
 use std::any::Any;
+use std::net::SocketAddr;
 
 use hyper::{Body, HeaderMap, Method, Uri, Version};
 
+use helpers::http::request::body::BufferedBody;
+use helpers::http::request::content_type::RequestContentType;
+use helpers::http::request::forwarded::TrustedProxies;
 use helpers::http::request::path::RequestPathSegments;
+use helpers::http::request::query_string::QueryParams;
+use state::connection::ConnectionInfo;
+#[cfg(unix)]
+use state::connection::PeerCredentials;
+use state::deadline::Deadline;
+use state::executor::Executor;
 use state::request_id::RequestId;
+use state::request_info::RequestInfo;
+use state::times::RequestTimes;
 
 /// A marker trait for types that can be stored in `State`.
 ///
@@ -35,6 +47,18 @@ impl StateData for Method {}
 impl StateData for Uri {}
 impl StateData for Version {}
 impl StateData for HeaderMap {}
+impl StateData for SocketAddr {}
 
 impl StateData for RequestPathSegments {}
+impl StateData for BufferedBody {}
+impl StateData for TrustedProxies {}
+impl StateData for RequestContentType {}
+impl StateData for QueryParams {}
 impl StateData for RequestId {}
+impl StateData for RequestInfo {}
+impl StateData for ConnectionInfo {}
+#[cfg(unix)]
+impl StateData for PeerCredentials {}
+impl StateData for Deadline {}
+impl StateData for Executor {}
+impl StateData for RequestTimes {}
@@ -0,0 +1,127 @@
+//! Defines the time by which a response is required, as set by the active timeout.
+
+use std::time::{Duration, Instant};
+
+use state::{FromState, State};
+
+/// The time by which the response to the current request is required, so that downstream work
+/// (e.g. database queries and upstream HTTP requests) can be bounded by the time that remains.
+///
+/// This is set by each layer which enforces a timeout, such as a route configured via
+/// `DefineSingleRoute::with_timeout`. Layers only ever tighten the `Deadline` via
+/// `Deadline::tighten`, so an inner layer with a longer timeout has no effect when an outer layer
+/// has already set a tighter one.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::time::Duration;
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::router::builder::*;
+/// # use gotham::state::deadline::Deadline;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let budget = Deadline::try_borrow_from(&state)
+///         .map(|deadline| deadline.remaining())
+///         .unwrap_or_else(|| Duration::from_secs(30));
+///
+///     // Pass `budget` to the database client, omitted for brevity.
+///     assert!(budget <= Duration::from_secs(2));
+///
+///     (state, Response::new(Body::empty()))
+/// }
+/// #
+/// # fn main() {
+/// #   let router = build_simple_router(|route| {
+/// #       route.get("/").with_timeout(Duration::from_secs(2)).to(handler);
+/// #   });
+/// #   let test_server = TestServer::new(router).unwrap();
+/// #   let response = test_server.client().get("http://localhost/").perform().unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Deadline {
+    at: Instant,
+}
+
+impl Deadline {
+    /// Sets the `Deadline` for the request to `at`, unless an earlier `Deadline` is already
+    /// present. Returns the `Deadline` which is in effect.
+    pub fn tighten(state: &mut State, at: Instant) -> Deadline {
+        let deadline = match Deadline::try_borrow_from(state) {
+            Some(existing) if existing.at <= at => *existing,
+            _ => Deadline { at },
+        };
+
+        state.put(deadline);
+        deadline
+    }
+
+    /// The time by which the response is required.
+    pub fn at(&self) -> Instant {
+        self.at
+    }
+
+    /// The time remaining until the `Deadline`, which is zero once it has passed.
+    pub fn remaining(&self) -> Duration {
+        let now = Instant::now();
+        if now >= self.at {
+            Duration::from_secs(0)
+        } else {
+            self.at - now
+        }
+    }
+
+    /// Determines if the `Deadline` has passed.
+    pub fn expired(&self) -> bool {
+        Instant::now() >= self.at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tighten_sets_initial_deadline() {
+        let mut state = State::new();
+        let at = Instant::now() + Duration::from_secs(10);
+
+        assert_eq!(Deadline::tighten(&mut state, at).at(), at);
+        assert_eq!(Deadline::borrow_from(&state).at(), at);
+    }
+
+    #[test]
+    fn tighten_never_loosens() {
+        let mut state = State::new();
+        let now = Instant::now();
+        let outer = now + Duration::from_secs(5);
+
+        Deadline::tighten(&mut state, outer);
+
+        let later = Deadline::tighten(&mut state, now + Duration::from_secs(10));
+        assert_eq!(later.at(), outer);
+
+        let inner = now + Duration::from_secs(1);
+        assert_eq!(Deadline::tighten(&mut state, inner).at(), inner);
+        assert_eq!(Deadline::borrow_from(&state).at(), inner);
+    }
+
+    #[test]
+    fn remaining_and_expired() {
+        let mut state = State::new();
+        let deadline = Deadline::tighten(&mut state, Instant::now() + Duration::from_secs(60));
+        assert!(!deadline.expired());
+        assert!(deadline.remaining() > Duration::from_secs(30));
+
+        let deadline = Deadline::tighten(&mut state, Instant::now());
+        assert!(deadline.expired());
+        assert_eq!(deadline.remaining(), Duration::from_secs(0));
+    }
+}
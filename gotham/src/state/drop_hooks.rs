@@ -0,0 +1,42 @@
+//! Defines the callbacks registered via `State::on_drop`.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Cleanup callbacks which are invoked when the owning `State` is dropped.
+///
+/// Callbacks run in reverse registration order, so a resource acquired later (which may depend on
+/// one acquired earlier) is released first. A panic within a callback is trapped and logged, and
+/// the remaining callbacks still run.
+pub(super) struct DropHooks {
+    hooks: Vec<Box<FnMut() + Send>>,
+}
+
+impl DropHooks {
+    pub(super) fn new() -> DropHooks {
+        DropHooks { hooks: Vec::new() }
+    }
+
+    pub(super) fn push<F>(&mut self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // Boxed `FnOnce` values cannot be invoked directly, so the callback is wrapped in an
+        // `Option` which is emptied when it runs.
+        let mut f = Some(f);
+        self.hooks.push(Box::new(move || {
+            if let Some(f) = f.take() {
+                f()
+            }
+        }));
+    }
+}
+
+impl Drop for DropHooks {
+    fn drop(&mut self) {
+        while let Some(mut hook) = self.hooks.pop() {
+            if catch_unwind(AssertUnwindSafe(|| hook())).is_err() {
+                error!("[PANIC][A panic occurred while running a State drop hook]");
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Defines `Executor`, a handle for spawning background work from a `Handler` or `Middleware`.
+
+use futures::Future;
+use tokio::executor::{self, DefaultExecutor};
+
+use state::State;
+
+/// A handle to the executor which is running the current request, stored in `State` for every
+/// request, including those served by a `TestServer`.
+///
+/// Work spawned via `Executor::spawn` is not tied to the lifetime of the request: it continues
+/// after the response has been sent, and its outcome does not affect the response. The `State`
+/// itself cannot be moved into spawned work, so values it needs are copied with a `StateSnapshot`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate futures;
+/// # extern crate gotham;
+/// # #[macro_use]
+/// # extern crate gotham_derive;
+/// # extern crate hyper;
+/// #
+/// # use futures::future;
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::state::executor::Executor;
+/// # use gotham::state::snapshot::StateSnapshot;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// #[derive(Clone, StateData)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// fn handler(mut state: State) -> (State, Response<Body>) {
+///     state.put(User { name: "alice".to_owned() });
+///
+///     let snapshot = StateSnapshot::builder(&state).with::<User>().build();
+///     Executor::borrow_from(&state).spawn(future::lazy(move || {
+///         let user = User::borrow_from(snapshot.state());
+///         println!("[{}] audit: {} logged in", snapshot.request_id(), user.name);
+///         Ok(())
+///     }));
+///
+///     (state, Response::new(Body::empty()))
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #   let response = test_server.client().get("http://localhost/").perform().unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Executor {
+    _priv: (),
+}
+
+impl Executor {
+    /// Spawns `future` onto the executor, to run in the background.
+    ///
+    /// If the executor has shut down, the future is dropped without being run, and an error is
+    /// logged.
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        let mut executor = DefaultExecutor::current();
+        if let Err(e) = executor::Executor::spawn(&mut executor, Box::new(future)) {
+            error!("unable to spawn background work: {:?}", e);
+        }
+    }
+}
+
+/// Stores a handle to the executor running the current request in `State`.
+pub(crate) fn put_executor(state: &mut State) {
+    state.put(Executor { _priv: () });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use futures::future;
+    use hyper::{Body, Response, StatusCode};
+
+    use state::FromState;
+    use test::TestServer;
+
+    #[test]
+    fn handler_spawns_background_work() {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+
+        let test_server = TestServer::new(move || {
+            let tx = tx.lock().unwrap().clone();
+            Ok(move |state: State| {
+                Executor::borrow_from(&state).spawn(future::lazy(move || {
+                    tx.send("spawned").unwrap();
+                    Ok(())
+                }));
+
+                (state, Response::new(Body::empty()))
+            })
+        }).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let received = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(received, "spawned");
+    }
+}
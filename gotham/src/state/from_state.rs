@@ -32,6 +32,47 @@ pub trait FromState: StateData + Sized {
     /// # });
     /// # }
     /// ```
+    ///
+    /// This allows handlers to make use of data which is only present in some requests, such as
+    /// the claims placed into `State` by authentication middleware:
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response};
+    /// # use gotham::state::{FromState, State};
+    /// #
+    /// #[derive(StateData)]
+    /// struct Claims {
+    ///     user: String,
+    /// }
+    ///
+    /// fn greeting(state: &State) -> String {
+    ///     if let Some(claims) = Claims::try_borrow_from(state) {
+    ///         format!("Welcome back, {}!", claims.user)
+    ///     } else {
+    ///         "Welcome, guest!".to_owned()
+    ///     }
+    /// }
+    ///
+    /// fn handler(state: State) -> (State, Response<Body>) {
+    ///     let body = greeting(&state);
+    ///     (state, Response::new(body.into()))
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   State::with_new(|state| {
+    /// assert_eq!(greeting(state), "Welcome, guest!");
+    ///
+    /// state.put(Claims { user: "alice".to_owned() });
+    /// assert_eq!(greeting(state), "Welcome back, alice!");
+    /// #   });
+    /// #   let _ = handler;
+    /// # }
+    /// ```
     fn try_borrow_from(&State) -> Option<&Self>;
 
     /// Borrows a value from the `State` storage.
@@ -189,6 +230,91 @@ pub trait FromState: StateData + Sized {
     /// # }
     /// ```
     fn take_from(&mut State) -> Self;
+
+    /// Mutably borrows a value from the `State` storage, first storing the value returned by `f`
+    /// if it is not present. See `State::get_or_insert_with`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// #
+    /// # use gotham::state::{FromState, State};
+    /// #
+    /// # fn main() {
+    /// #[derive(StateData)]
+    /// struct Locale {
+    ///     tag: String,
+    /// }
+    ///
+    /// # State::with_new(|state| {
+    /// let locale = Locale::get_or_insert_with(state, || Locale { tag: "en-AU".to_owned() });
+    /// assert_eq!(locale.tag, "en-AU");
+    /// # });
+    /// # }
+    /// ```
+    fn get_or_insert_with<F>(&mut State, F) -> &mut Self
+    where
+        F: FnOnce() -> Self;
+
+    /// Mutably borrows a value from the `State` storage, first storing `Self::default()` if it is
+    /// not present. See `State::get_or_default`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// #
+    /// # use gotham::state::{FromState, State};
+    /// #
+    /// # fn main() {
+    /// #[derive(StateData, Default)]
+    /// struct Counter {
+    ///     hits: u32,
+    /// }
+    ///
+    /// # State::with_new(|state| {
+    /// Counter::get_or_default(state).hits += 1;
+    /// assert_eq!(Counter::borrow_from(state).hits, 1);
+    /// # });
+    /// # }
+    /// ```
+    fn get_or_default(&mut State) -> &mut Self
+    where
+        Self: Default;
+
+    /// Mutably borrows a value from the `State` storage, first storing `Self::default()` if it is
+    /// not present. See `State::borrow_mut_or_default`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// #
+    /// # use gotham::state::{FromState, State};
+    /// #
+    /// # fn main() {
+    /// #[derive(StateData, Default)]
+    /// struct Timings {
+    ///     spans: Vec<&'static str>,
+    /// }
+    ///
+    /// # State::with_new(|state| {
+    /// Timings::borrow_mut_or_default(state).spans.push("session");
+    /// Timings::borrow_mut_or_default(state).spans.push("handler");
+    /// assert_eq!(Timings::borrow_from(state).spans.len(), 2);
+    /// # });
+    /// # }
+    /// ```
+    fn borrow_mut_or_default(&mut State) -> &mut Self
+    where
+        Self: Default;
 }
 
 impl<T> FromState for T
@@ -218,4 +344,170 @@ where
     fn take_from(state: &mut State) -> Self {
         state.take()
     }
+
+    fn get_or_insert_with<F>(state: &mut State, f: F) -> &mut Self
+    where
+        F: FnOnce() -> Self,
+    {
+        state.get_or_insert_with(f)
+    }
+
+    fn get_or_default(state: &mut State) -> &mut Self
+    where
+        Self: Default,
+    {
+        state.get_or_default()
+    }
+
+    fn borrow_mut_or_default(state: &mut State) -> &mut Self
+    where
+        Self: Default,
+    {
+        state.borrow_mut_or_default()
+    }
+}
+
+/// A trait for accessing several values stored in `State` at once, implemented for tuples of up
+/// to six `StateData` types.
+///
+/// This avoids a separate `borrow_from` call for each value which a `Handler` requires.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, HeaderMap, Method, Response, StatusCode, Uri};
+/// # use gotham::state::{FromStateTuple, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let body = {
+///         let (method, uri, headers) = <(Method, Uri, HeaderMap)>::borrow_from(&state);
+///         format!("{} {} with {} headers", method, uri.path(), headers.len())
+///     };
+///
+///     (state, Response::new(body.into()))
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #   let response = test_server.client().get("http://localhost/path").perform().unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert!(response.read_utf8_body().unwrap().starts_with("GET /path with "));
+/// # }
+/// ```
+pub trait FromStateTuple<'a>: Sized {
+    /// The tuple of references returned when borrowing, e.g. `(&'a A, &'a B)` for `(A, B)`.
+    type Borrowed: 'a;
+
+    /// Tries to borrow each value from the `State` storage, returning `None` if any are absent.
+    fn try_borrow_from(&'a State) -> Option<Self::Borrowed>;
+
+    /// Borrows each value from the `State` storage.
+    ///
+    /// # Panics
+    ///
+    /// If any of the values are not present in `State`.
+    fn borrow_from(&'a State) -> Self::Borrowed;
+
+    /// Tries to move each value out of the `State` storage. If any are absent, `None` is returned
+    /// and `State` is left unchanged.
+    fn try_take_from(&mut State) -> Option<Self>;
+
+    /// Moves each value out of the `State` storage and returns ownership.
+    ///
+    /// # Panics
+    ///
+    /// If any of the values are not present in `State`.
+    fn take_from(&mut State) -> Self;
+}
+
+macro_rules! from_state_tuple {
+    ($($t:ident),+) => {
+        impl<'a, $($t),+> FromStateTuple<'a> for ($($t,)+)
+        where
+            $($t: StateData,)+
+        {
+            type Borrowed = ($(&'a $t,)+);
+
+            fn try_borrow_from(state: &'a State) -> Option<Self::Borrowed> {
+                Some(($(state.try_borrow::<$t>()?,)+))
+            }
+
+            fn borrow_from(state: &'a State) -> Self::Borrowed {
+                ($(state.borrow::<$t>(),)+)
+            }
+
+            fn try_take_from(state: &mut State) -> Option<Self> {
+                if $(state.has::<$t>())&&+ {
+                    Some(($(state.take::<$t>(),)+))
+                } else {
+                    None
+                }
+            }
+
+            fn take_from(state: &mut State) -> Self {
+                ($(state.take::<$t>(),)+)
+            }
+        }
+    };
+}
+
+from_state_tuple!(A, B);
+from_state_tuple!(A, B, C);
+from_state_tuple!(A, B, C, D);
+from_state_tuple!(A, B, C, D, E);
+from_state_tuple!(A, B, C, D, E, F);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct First(u8);
+    impl StateData for First {}
+
+    struct Second(&'static str);
+    impl StateData for Second {}
+
+    struct Third;
+    impl StateData for Third {}
+
+    fn state() -> State {
+        let mut state = State::new();
+        state.put(First(1));
+        state.put(Second("two"));
+        state
+    }
+
+    #[test]
+    fn borrows_tuple() {
+        let state = state();
+        let (first, second) = <(First, Second)>::borrow_from(&state);
+        assert_eq!(first.0, 1);
+        assert_eq!(second.0, "two");
+
+        assert!(<(First, Second, Third)>::try_borrow_from(&state).is_none());
+    }
+
+    #[test]
+    fn takes_tuple() {
+        let mut state = state();
+        assert!(<(First, Third)>::try_take_from(&mut state).is_none());
+        assert!(state.has::<First>());
+
+        let (first, second) = <(First, Second)>::take_from(&mut state);
+        assert_eq!((first.0, second.0), (1, "two"));
+        assert!(!state.has::<First>() && !state.has::<Second>());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "required type gotham::state::from_state::tests::Third is not present"
+    )]
+    fn borrow_panics_naming_missing_type() {
+        let state = state();
+        <(First, Third)>::borrow_from(&state);
+    }
 }
@@ -1,19 +1,39 @@
 //! Defines types for passing request state through `Middleware` and `Handler` implementations
 
 pub(crate) mod client_addr;
+pub mod connection;
 mod data;
+pub mod deadline;
+mod drop_hooks;
+pub mod executor;
 mod from_state;
 pub mod request_id;
+pub mod request_info;
+pub mod snapshot;
+mod storage;
+pub mod times;
 
-use std::any::{Any, TypeId};
-use std::collections::HashMap;
+use std::any::{type_name, TypeId};
+use std::net::SocketAddr;
+
+use hyper::header::HeaderMap;
+use hyper::{Method, Uri, Version};
+
+use state::deadline::Deadline;
+use state::drop_hooks::DropHooks;
+use state::request_id::RequestId;
+use state::request_info::RequestInfo;
+use state::storage::Storage;
+use state::times::RequestTimes;
 
 pub use state::client_addr::client_addr;
 pub use state::data::StateData;
-pub use state::from_state::FromState;
-pub use state::request_id::request_id;
+pub use state::from_state::{FromState, FromStateTuple};
+pub use state::request_id::{current_request_id, request_id, with_current_request_id};
 
+#[cfg(test)]
 pub(crate) use state::request_id::set_request_id;
+pub(crate) use state::request_id::set_request_id_with;
 
 /// Provides storage for request state, and stores one item of each type. The types used for
 /// storage must implement the `gotham::state::StateData` trait to allow its storage. The
@@ -43,7 +63,9 @@ pub(crate) use state::request_id::set_request_id;
 /// # }
 /// ```
 pub struct State {
-    data: HashMap<TypeId, Box<Any + Send>>,
+    // Declared first, so that callbacks run before any other data in `State` is dropped.
+    drop_hooks: DropHooks,
+    data: Storage,
 }
 
 impl State {
@@ -52,7 +74,39 @@ impl State {
     /// incorrectly discard important internal data.
     pub(crate) fn new() -> State {
         State {
-            data: HashMap::new(),
+            drop_hooks: DropHooks::new(),
+            data: Storage::new(),
+        }
+    }
+
+    /// Creates a new `State` container holding copies of the request data stored by Gotham: the
+    /// method, URI, version, headers, request ID, request info, client address, request times and
+    /// deadline.
+    ///
+    /// This allows a `Response` to be generated after the original `State` is no longer available,
+    /// such as when a `Handler` future which owns the `State` has been dropped.
+    pub(crate) fn request_snapshot(&self) -> State {
+        let mut snapshot = State::new();
+
+        self.copy_into::<Method>(&mut snapshot);
+        self.copy_into::<Uri>(&mut snapshot);
+        self.copy_into::<Version>(&mut snapshot);
+        self.copy_into::<HeaderMap>(&mut snapshot);
+        self.copy_into::<RequestId>(&mut snapshot);
+        self.copy_into::<RequestInfo>(&mut snapshot);
+        self.copy_into::<SocketAddr>(&mut snapshot);
+        self.copy_into::<RequestTimes>(&mut snapshot);
+        self.copy_into::<Deadline>(&mut snapshot);
+
+        snapshot
+    }
+
+    fn copy_into<T>(&self, other: &mut State)
+    where
+        T: StateData + Clone,
+    {
+        if let Some(t) = self.try_borrow::<T>() {
+            other.put(t.clone());
         }
     }
 
@@ -150,7 +204,7 @@ impl State {
         T: StateData,
     {
         let type_id = TypeId::of::<T>();
-        self.data.get(&type_id).is_some()
+        self.data.contains(&type_id)
     }
 
     /// Tries to borrow a value from the `State` storage.
@@ -227,8 +281,7 @@ impl State {
     where
         T: StateData,
     {
-        self.try_borrow()
-            .expect("required type is not present in State container")
+        self.try_borrow().unwrap_or_else(|| missing::<T>())
     }
 
     /// Tries to mutably borrow a value from the `State` storage.
@@ -320,8 +373,122 @@ impl State {
     where
         T: StateData,
     {
-        self.try_borrow_mut()
-            .expect("required type is not present in State container")
+        self.try_borrow_mut().unwrap_or_else(|| missing::<T>())
+    }
+
+    /// Mutably borrows a value from the `State` storage, first storing the value returned by `f`
+    /// if no value of type `T` is present. `f` is only invoked when the value is absent.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// #
+    /// # use gotham::state::State;
+    /// #
+    /// # #[derive(StateData)]
+    /// # struct Locale {
+    /// #     tag: &'static str
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   State::with_new(|state| {
+    /// #
+    /// assert_eq!(state.get_or_insert_with(|| Locale { tag: "en-AU" }).tag, "en-AU");
+    ///
+    /// // The stored value is returned from now on.
+    /// assert_eq!(state.get_or_insert_with(|| Locale { tag: "fr-FR" }).tag, "en-AU");
+    /// #
+    /// #   });
+    /// # }
+    /// ```
+    pub fn get_or_insert_with<T, F>(&mut self, f: F) -> &mut T
+    where
+        T: StateData,
+        F: FnOnce() -> T,
+    {
+        let type_id = TypeId::of::<T>();
+        trace!(
+            " mutably borrowing or inserting state data for type_id `{:?}`",
+            type_id
+        );
+        self.data
+            .get_or_insert_with(type_id, || Box::new(f()))
+            .downcast_mut::<T>()
+            .unwrap_or_else(|| missing::<T>())
+    }
+
+    /// Mutably borrows a value from the `State` storage, first storing `T::default()` if no value
+    /// of type `T` is present.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// #
+    /// # use gotham::state::State;
+    /// #
+    /// # #[derive(StateData, Default)]
+    /// # struct Counter {
+    /// #     hits: u32
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   State::with_new(|state| {
+    /// #
+    /// state.get_or_default::<Counter>().hits += 1;
+    /// state.get_or_default::<Counter>().hits += 1;
+    ///
+    /// assert_eq!(state.borrow::<Counter>().hits, 2);
+    /// #
+    /// #   });
+    /// # }
+    /// ```
+    pub fn get_or_default<T>(&mut self) -> &mut T
+    where
+        T: StateData + Default,
+    {
+        self.get_or_insert_with(T::default)
+    }
+
+    /// Mutably borrows a value from the `State` storage, first storing `T::default()` if no value
+    /// of type `T` is present. This allows cooperating middleware to accumulate into a shared
+    /// value without knowing whether an earlier middleware has already created it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate gotham_derive;
+    /// #
+    /// # use gotham::state::State;
+    /// #
+    /// # #[derive(StateData, Default)]
+    /// # struct Timings {
+    /// #     spans: Vec<&'static str>
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   State::with_new(|state| {
+    /// #
+    /// state.borrow_mut_or_default::<Timings>().spans.push("session");
+    /// state.borrow_mut_or_default::<Timings>().spans.push("handler");
+    ///
+    /// assert_eq!(state.borrow::<Timings>().spans, vec!["session", "handler"]);
+    /// #
+    /// #   });
+    /// # }
+    /// ```
+    pub fn borrow_mut_or_default<T>(&mut self) -> &mut T
+    where
+        T: StateData + Default,
+    {
+        self.get_or_insert_with(T::default)
     }
 
     /// Tries to move a value out of the `State` storage and return ownership.
@@ -411,7 +578,165 @@ impl State {
     where
         T: StateData,
     {
-        self.try_take()
-            .expect("required type is not present in State container")
+        self.try_take().unwrap_or_else(|| missing::<T>())
+    }
+
+    /// Registers a callback to be invoked when this `State` is dropped, which happens exactly once
+    /// for each request: after the response has been produced, after an error or panic, or when the
+    /// request is abandoned (e.g. the client disconnected, or a timeout dropped the `Handler`).
+    ///
+    /// This is the reliable place to release resources checked out by `Middleware`, since code
+    /// chained after the `Handler` future does not run when the future is dropped.
+    ///
+    /// Callbacks are invoked in the reverse order of registration, before any data stored in
+    /// `State` is dropped. A panic within one callback is trapped and logged, and the remaining
+    /// callbacks are still invoked. Callbacks should nonetheless avoid panicking, as a panic which
+    /// occurs while `State` is being dropped due to an earlier panic aborts the process.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// #
+    /// # use std::sync::{Arc, Mutex};
+    /// # use gotham::state::State;
+    /// #
+    /// # fn main() {
+    /// let pool = Arc::new(Mutex::new(vec!["connection"]));
+    ///
+    /// # State::with_new(|state| {
+    /// let conn = pool.lock().unwrap().pop().unwrap();
+    ///
+    /// let checkin = pool.clone();
+    /// state.on_drop(move || checkin.lock().unwrap().push(conn));
+    /// # });
+    ///
+    /// // Once the request's `State` has been dropped, the connection is back in the pool.
+    /// assert_eq!(*pool.lock().unwrap(), vec!["connection"]);
+    /// # }
+    /// ```
+    pub fn on_drop<F>(&mut self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        trace!(" registering drop hook");
+        self.drop_hooks.push(f);
+    }
+}
+
+/// Panics with a message naming the type which was expected to be present.
+fn missing<T>() -> ! {
+    panic!(
+        "required type {} is not present in State container",
+        type_name::<T>()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    struct Present;
+
+    impl StateData for Present {}
+
+    struct Absent;
+
+    impl StateData for Absent {}
+
+    #[test]
+    #[should_panic(expected = "required type gotham::state::tests::Absent is not present")]
+    fn borrow_panic_names_missing_type() {
+        let mut state = State::new();
+        state.put(Present);
+        state.borrow::<Absent>();
+    }
+
+    #[test]
+    fn get_or_insert_with_initializes_once() {
+        #[derive(Default)]
+        struct Lazy(u32);
+
+        impl StateData for Lazy {}
+
+        let mut state = State::new();
+        let mut calls = 0;
+
+        for _ in 0..3 {
+            let lazy = state.get_or_insert_with(|| {
+                calls += 1;
+                Lazy(0)
+            });
+            lazy.0 += 1;
+        }
+
+        assert_eq!(calls, 1);
+        assert_eq!(state.borrow::<Lazy>().0, 3);
+
+        state.take::<Lazy>();
+        assert_eq!(state.get_or_default::<Lazy>().0, 0);
+        state.get_or_default::<Lazy>().0 += 5;
+        assert_eq!(state.get_or_default::<Lazy>().0, 5);
+    }
+
+    #[test]
+    fn borrow_mut_or_default_keeps_existing_value() {
+        #[derive(Default)]
+        struct Lazy(u32);
+
+        impl StateData for Lazy {}
+
+        let mut state = State::new();
+        state.put(Lazy(7));
+
+        state.borrow_mut_or_default::<Lazy>().0 += 1;
+        assert_eq!(state.borrow::<Lazy>().0, 8);
+
+        state.take::<Lazy>();
+        state.borrow_mut_or_default::<Lazy>().0 += 1;
+        assert_eq!(state.borrow::<Lazy>().0, 1);
+    }
+
+    #[test]
+    fn drop_hooks_run_in_reverse_order() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        {
+            let mut state = State::new();
+            for i in 0..3 {
+                let order = order.clone();
+                state.on_drop(move || order.lock().unwrap().push(i));
+            }
+            assert!(order.lock().unwrap().is_empty());
+        }
+
+        assert_eq!(*order.lock().unwrap(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn drop_hooks_survive_panicking_hook() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        {
+            let mut state = State::new();
+
+            let first = order.clone();
+            state.on_drop(move || first.lock().unwrap().push("first"));
+            state.on_drop(|| panic!("drop hook failure"));
+
+            let last = order.clone();
+            state.on_drop(move || last.lock().unwrap().push("last"));
+        }
+
+        assert_eq!(*order.lock().unwrap(), vec!["last", "first"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "required type gotham::state::tests::Absent is not present")]
+    fn take_panic_names_missing_type() {
+        let mut state = State::new();
+        state.take::<Absent>();
     }
 }
@@ -1,11 +1,200 @@
 //! Defines a unique id per `Request` that should be output with all logging.
 
-use hyper::header::HeaderMap;
+use std::cell::RefCell;
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+
+use futures::{Future, Poll};
+use hyper::header::{HeaderMap, HeaderName};
 use uuid::Uuid;
 
+use helpers::http::header::X_REQUEST_ID;
+
 use state::{FromState, State};
 
+/// Creates the unique identifier for a request which did not provide an acceptable one via the
+/// request ID header.
+///
+/// A single generator is shared by every worker thread, so `generate` must be cheap and safe to
+/// call concurrently. This is implemented for closures, so a custom format can be provided
+/// directly:
+///
+/// ```rust
+/// # extern crate gotham;
+/// #
+/// # use std::sync::atomic::{AtomicUsize, Ordering};
+/// # use gotham::state::request_id::{RequestIdConfig, RequestIdGenerator};
+/// #
+/// # fn main() {
+/// let counter = AtomicUsize::new(0);
+/// let generator = move || format!("req-{}", counter.fetch_add(1, Ordering::Relaxed));
+///
+/// assert_eq!(generator.generate(), "req-0");
+/// assert_eq!(generator.generate(), "req-1");
+///
+/// let config = RequestIdConfig::new().with_generator(generator);
+/// # let _ = config;
+/// # }
+/// ```
+pub trait RequestIdGenerator: Send + Sync + RefUnwindSafe {
+    /// Creates a new request identifier.
+    fn generate(&self) -> String;
+}
+
+impl<F> RequestIdGenerator for F
+where
+    F: Fn() -> String + Send + Sync + RefUnwindSafe,
+{
+    fn generate(&self) -> String {
+        self()
+    }
+}
+
+/// Generates request identifiers as hyphenated UUID v4 values, e.g.
+/// `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+///
+/// This is the default `RequestIdGenerator`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UuidRequestIdGenerator;
+
+impl RequestIdGenerator for UuidRequestIdGenerator {
+    fn generate(&self) -> String {
+        Uuid::new_v4().hyphenated().to_string()
+    }
+}
+
+/// The default maximum length, in bytes, of a request ID accepted from a request header.
+pub const DEFAULT_MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// Configures how request identifiers are assigned by Gotham, before the `Router` or any
+/// `Middleware` is invoked, so every log line for the request carries the same identifier.
+///
+/// The identifier is taken from the request ID header (`X-Request-ID` by default) when the header
+/// is trusted and its value passes validation. Otherwise, the identifier is created by the
+/// configured `RequestIdGenerator`.
+///
+/// Since the identifier is written into every log line, a value supplied by the client is only
+/// accepted when it is:
+///
+/// * between 1 and `max_length` bytes long (`DEFAULT_MAX_REQUEST_ID_LENGTH` by default); and
+/// * made up entirely of ASCII letters, digits, `-`, `_`, `.` and `:`.
+///
+/// Rejected values are ignored, and never logged. When requests do not arrive via a proxy which
+/// assigns the identifier, the header should not be trusted at all, via `trust_header(false)`.
+///
+/// This is provided when starting the server, via `gotham::start_with_request_ids`.
+///
+/// ```rust
+/// # extern crate gotham;
+/// #
+/// # use gotham::state::request_id::RequestIdConfig;
+/// #
+/// # fn main() {
+/// // Adopt the correlation ID assigned by the edge proxy.
+/// let behind_proxy = RequestIdConfig::new()
+///     .with_header("x-correlation-id")
+///     .with_max_length(64);
+///
+/// // Always generate a fresh ID, regardless of the request headers.
+/// let exposed = RequestIdConfig::new().trust_header(false);
+/// # let _ = (behind_proxy, exposed);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RequestIdConfig {
+    generator: Arc<RequestIdGenerator>,
+    header: HeaderName,
+    trust_header: bool,
+    max_length: usize,
+}
+
+impl RequestIdConfig {
+    /// Creates the default configuration, which trusts a valid `X-Request-ID` header and
+    /// otherwise generates identifiers using `UuidRequestIdGenerator`.
+    pub fn new() -> Self {
+        RequestIdConfig {
+            generator: Arc::new(UuidRequestIdGenerator),
+            header: HeaderName::from_static(X_REQUEST_ID),
+            trust_header: true,
+            max_length: DEFAULT_MAX_REQUEST_ID_LENGTH,
+        }
+    }
+
+    /// Sets the `RequestIdGenerator` used to create identifiers for requests.
+    pub fn with_generator<G>(self, generator: G) -> Self
+    where
+        G: RequestIdGenerator + 'static,
+    {
+        RequestIdConfig {
+            generator: Arc::new(generator),
+            ..self
+        }
+    }
+
+    /// Sets the name of the header which is read to obtain a request ID.
+    ///
+    /// # Panics
+    ///
+    /// If `header` is not a valid, lowercase header name.
+    pub fn with_header(self, header: &'static str) -> Self {
+        RequestIdConfig {
+            header: HeaderName::from_static(header),
+            ..self
+        }
+    }
+
+    /// Sets whether the request ID header is trusted. When `false`, every request is assigned an
+    /// identifier by the `RequestIdGenerator`.
+    pub fn trust_header(self, trust_header: bool) -> Self {
+        RequestIdConfig {
+            trust_header,
+            ..self
+        }
+    }
+
+    /// Sets the maximum length, in bytes, of a request ID accepted from the request header.
+    pub fn with_max_length(self, max_length: usize) -> Self {
+        RequestIdConfig { max_length, ..self }
+    }
+
+    /// Determines if the value of the request ID header can be used as the request ID.
+    fn accepts(&self, value: &[u8]) -> bool {
+        !value.is_empty()
+            && value.len() <= self.max_length
+            && value
+                .iter()
+                .all(|&b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b))
+    }
+
+    fn trusted_header_value(&self, state: &State) -> Option<String> {
+        if !self.trust_header {
+            return None;
+        }
+
+        let value = HeaderMap::try_borrow_from(state)?.get(&self.header)?;
+
+        if self.accepts(value.as_bytes()) {
+            // Validated as ASCII, so this conversion cannot fail.
+            value.to_str().ok().map(|v| v.to_owned())
+        } else {
+            debug!(
+                "ignoring invalid {} header ({} bytes)",
+                self.header,
+                value.len()
+            );
+            None
+        }
+    }
+}
+
+impl Default for RequestIdConfig {
+    fn default() -> Self {
+        RequestIdConfig::new()
+    }
+}
+
 /// A container type for the value returned by `request_id`.
+#[derive(Clone)]
 pub(super) struct RequestId {
     val: String,
 }
@@ -14,24 +203,34 @@ pub(super) struct RequestId {
 ///
 /// The unique identifier chosen depends on the the request headers:
 ///
-/// 1. If the header `X-Request-ID` is provided this value is used as-is;
+/// 1. If the header `X-Request-ID` is provided with a valid value, this value is used as-is;
 /// 2. Alternatively creates and stores a UUID v4 value.
+#[cfg(test)]
+pub(crate) fn set_request_id<'a>(state: &'a mut State) -> &'a str {
+    set_request_id_with(state, &RequestIdConfig::new())
+}
+
+/// Sets a unique identifier for the request if it has not already been stored, according to the
+/// `RequestIdConfig`.
 ///
 /// This function is invoked by `GothamService` before handing control to its `Router`, to ensure
 /// that a value for `RequestId` is always available.
-pub(crate) fn set_request_id<'a>(state: &'a mut State) -> &'a str {
+pub(crate) fn set_request_id_with<'a>(
+    state: &'a mut State,
+    config: &RequestIdConfig,
+) -> &'a str {
     if !state.has::<RequestId>() {
-        let request_id = match HeaderMap::borrow_from(state).get("X-Request-ID") {
-            Some(ex_req_id) => {
-                let id = String::from_utf8(ex_req_id.as_bytes().into()).unwrap();
+        let request_id = match config.trusted_header_value(state) {
+            Some(id) => {
                 trace!(
-                    "[{}] RequestId set from external source via X-Request-ID header",
-                    id
+                    "[{}] RequestId set from external source via {} header",
+                    id,
+                    config.header
                 );
                 RequestId { val: id }
             }
             None => {
-                let val = Uuid::new_v4().hyphenated().to_string();
+                let val = config.generator.generate();
                 trace!("[{}] RequestId generated internally", val);
                 RequestId { val }
             }
@@ -57,6 +256,89 @@ pub fn request_id(state: &State) -> &str {
     }
 }
 
+thread_local! {
+    static CURRENT_REQUEST_ID: RefCell<Option<Arc<str>>> = RefCell::new(None);
+}
+
+/// Returns the ID of the request which is being processed on the current thread, if any.
+///
+/// This allows code which has no access to `State` (e.g. a logging adapter, or library code called
+/// by a `Handler`) to correlate its output with the request. The ID is available while Gotham is
+/// invoking the `Router` and polling the response future, but not within work which has been
+/// spawned separately onto the executor.
+///
+/// The ID is copied, since it cannot be borrowed beyond the current poll. Use
+/// `with_current_request_id` to avoid the allocation.
+pub fn current_request_id() -> Option<String> {
+    with_current_request_id(|id| id.map(|id| id.to_owned()))
+}
+
+/// Invokes `f` with the ID of the request which is being processed on the current thread, if any.
+/// See `current_request_id` for details.
+pub fn with_current_request_id<F, R>(f: F) -> R
+where
+    F: FnOnce(Option<&str>) -> R,
+{
+    CURRENT_REQUEST_ID.with(|current| f(current.borrow().as_ref().map(|id| &**id)))
+}
+
+/// Runs `f` with `id` as the current request ID, restoring the previous value afterwards (even when
+/// `f` panics). The ID is shared rather than copied, so that no allocation occurs on each poll.
+fn scope_request_id<F, R>(id: &Arc<str>, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    struct Restore(Option<Arc<str>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CURRENT_REQUEST_ID.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let previous = CURRENT_REQUEST_ID.with(|current| current.replace(Some(id.clone())));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// A future which sets the current request ID for the duration of each poll of the wrapped
+/// future. Many requests are interleaved on each worker thread, so the ID is scoped to the poll
+/// rather than the thread.
+pub(crate) struct RequestIdScope<F> {
+    id: Arc<str>,
+    inner: F,
+}
+
+impl<F> RequestIdScope<F>
+where
+    F: Future,
+{
+    /// Invokes `f` with `id` as the current request ID, and wraps the returned future so that the
+    /// ID is also current whenever it is polled.
+    pub(crate) fn new<N>(id: String, f: N) -> RequestIdScope<F>
+    where
+        N: FnOnce() -> F,
+    {
+        let id: Arc<str> = Arc::from(id);
+        let inner = scope_request_id(&id, f);
+        RequestIdScope { id, inner }
+    }
+}
+
+impl<F> Future for RequestIdScope<F>
+where
+    F: Future,
+{
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, F::Error> {
+        let RequestIdScope { ref id, ref mut inner } = *self;
+        scope_request_id(id, || inner.poll())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -100,6 +382,71 @@ mod tests {
         );
     }
 
+    #[test]
+    fn uses_the_configured_generator() {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+
+        let config = RequestIdConfig::new().with_generator(|| "custom-id".to_owned());
+        set_request_id_with(&mut state, &config);
+        assert_eq!("custom-id", request_id(&state));
+    }
+
+    fn state_with_header(name: &'static str, value: &str) -> State {
+        let mut state = State::new();
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        state.put(headers);
+        state
+    }
+
+    #[test]
+    fn ignores_invalid_external_request_ids() {
+        let config = RequestIdConfig::new().with_max_length(8);
+
+        for value in &["bad id", "<script>", "id;forged", "123456789", ""] {
+            let mut state = state_with_header("X-Request-ID", value);
+            set_request_id_with(&mut state, &config);
+            assert_eq!(
+                4,
+                Uuid::parse_str(request_id(&state))
+                    .unwrap()
+                    .get_version_num()
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_untrusted_header() {
+        let mut state = state_with_header("X-Request-ID", "1-2-3-4");
+        let config = RequestIdConfig::new().trust_header(false);
+
+        set_request_id_with(&mut state, &config);
+        assert_ne!("1-2-3-4", request_id(&state));
+    }
+
+    #[test]
+    fn uses_a_configured_header() {
+        let mut state = state_with_header("X-Correlation-ID", "edge.42:a_b");
+        let config = RequestIdConfig::new().with_header("x-correlation-id");
+
+        set_request_id_with(&mut state, &config);
+        assert_eq!("edge.42:a_b", request_id(&state));
+    }
+
+    #[test]
+    fn current_request_id_is_scoped() {
+        assert_eq!(current_request_id(), None);
+
+        let outer = scope_request_id(&Arc::from("outer"), || {
+            let inner = scope_request_id(&Arc::from("inner"), current_request_id);
+            (inner, current_request_id())
+        });
+
+        assert_eq!(outer, (Some("inner".to_owned()), Some("outer".to_owned())));
+        assert_eq!(current_request_id(), None);
+    }
+
     #[test]
     fn does_not_overwrite_existant_request_id() {
         let mut state = State::new();
@@ -0,0 +1,173 @@
+//! Defines a summary of the request line and headers, for use in logging and error reporting.
+
+use std::fmt::{self, Display, Formatter};
+
+use hyper::header::HeaderMap;
+use hyper::{Method, Uri, Version};
+
+use state::{FromState, State};
+
+/// A summary of the incoming request, available via `RequestInfo::borrow_from(&state)`.
+///
+/// This is stored by `GothamService` before any `Router`, `Pipeline` or `Middleware` is invoked,
+/// so it is present for every request, including those which the `Router` responds to with
+/// `404 Not Found`. Its `Display` implementation renders a concise single line, such as
+/// `GET /users?page=2 HTTP/1.1 (3 headers, 92 bytes)`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Method, Response, StatusCode};
+/// # use gotham::state::request_info::RequestInfo;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     {
+///         let info = RequestInfo::borrow_from(&state);
+///         assert_eq!(*info.method(), Method::GET);
+///         assert_eq!(info.path(), "/users");
+///         assert_eq!(info.query(), Some("page=2"));
+///         println!("handling {}", info);
+///     }
+///
+///     (state, Response::new(Body::empty()))
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://localhost/users?page=2")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RequestInfo {
+    method: Method,
+    uri: Uri,
+    version: Version,
+    header_count: usize,
+    header_bytes: usize,
+}
+
+impl RequestInfo {
+    /// The request method.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// The request URI, as received.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// The path component of the request URI.
+    pub fn path(&self) -> &str {
+        self.uri.path()
+    }
+
+    /// The query string of the request URI, if one was present.
+    pub fn query(&self) -> Option<&str> {
+        self.uri.query()
+    }
+
+    /// The HTTP version of the request.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// The number of request headers.
+    pub fn header_count(&self) -> usize {
+        self.header_count
+    }
+
+    /// The combined length in bytes of all request header names and values.
+    pub fn header_bytes(&self) -> usize {
+        self.header_bytes
+    }
+}
+
+impl Display for RequestInfo {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path())?;
+
+        if let Some(query) = self.query() {
+            write!(f, "?{}", query)?;
+        }
+
+        write!(
+            f,
+            " {:?} ({} headers, {} bytes)",
+            self.version, self.header_count, self.header_bytes
+        )
+    }
+}
+
+/// Stores a `RequestInfo` summarizing the request data which has already been placed in `State`.
+pub(crate) fn put_request_info(state: &mut State) {
+    let info = {
+        let headers = HeaderMap::borrow_from(state);
+
+        RequestInfo {
+            method: Method::borrow_from(state).clone(),
+            uri: Uri::borrow_from(state).clone(),
+            version: *Version::borrow_from(state),
+            header_count: headers.len(),
+            header_bytes: headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum(),
+        }
+    };
+
+    state.put(info);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info_state(uri: &str) -> State {
+        let mut headers = HeaderMap::new();
+        headers.insert("accept", "text/plain".parse().unwrap());
+        headers.insert("host", "localhost".parse().unwrap());
+
+        let mut state = State::new();
+        state.put(Method::POST);
+        state.put(uri.parse::<Uri>().unwrap());
+        state.put(Version::HTTP_11);
+        state.put(headers);
+        put_request_info(&mut state);
+        state
+    }
+
+    #[test]
+    fn summarizes_request() {
+        let state = info_state("/users?page=2");
+        let info = RequestInfo::borrow_from(&state);
+
+        assert_eq!(info.path(), "/users");
+        assert_eq!(info.query(), Some("page=2"));
+        assert_eq!(info.header_count(), 2);
+        assert_eq!(info.header_bytes(), 6 + 10 + 4 + 9);
+        assert_eq!(
+            info.to_string(),
+            "POST /users?page=2 HTTP/1.1 (2 headers, 29 bytes)"
+        );
+    }
+
+    #[test]
+    fn display_omits_absent_query() {
+        let state = info_state("/users");
+        assert_eq!(
+            RequestInfo::borrow_from(&state).to_string(),
+            "POST /users HTTP/1.1 (2 headers, 29 bytes)"
+        );
+    }
+}
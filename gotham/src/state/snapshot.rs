@@ -0,0 +1,196 @@
+//! Defines a copy of selected `State` data which can be moved into background work.
+
+use futures::{future, IntoFuture};
+use tokio::executor;
+
+use state::request_id::RequestId;
+use state::{request_id, State, StateData};
+
+/// A copy of selected values from `State`, for use by work which continues independently of the
+/// request, such as a fire-and-forget audit log write.
+///
+/// The `State` itself must be returned by the `Handler`, so it cannot be moved into spawned work.
+/// A `StateSnapshot` owns copies of the values it was built with, and the request ID is always
+/// included. Values are accessed via `StateSnapshot::state`, using the same `FromState` API as the
+/// original `State`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # #[macro_use]
+/// # extern crate gotham_derive;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::state::snapshot::{spawn_with_snapshot, StateSnapshot};
+/// # use gotham::state::{request_id, FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// #[derive(Clone, StateData)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// fn handler(mut state: State) -> (State, Response<Body>) {
+///     state.put(User { name: "alice".to_owned() });
+///
+///     let snapshot = StateSnapshot::builder(&state).with::<User>().build();
+///     spawn_with_snapshot(snapshot, |snapshot| {
+///         let user = User::borrow_from(snapshot.state());
+///         println!("[{}] audit: {} logged in", request_id(snapshot.state()), user.name);
+///         Ok(())
+///     });
+///
+///     (state, Response::new(Body::empty()))
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #   let response = test_server.client().get("http://localhost/").perform().unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+pub struct StateSnapshot {
+    state: State,
+}
+
+impl StateSnapshot {
+    /// Begins building a `StateSnapshot` of the given `State`.
+    pub fn builder(state: &State) -> StateSnapshotBuilder {
+        let mut snapshot = State::new();
+        state.copy_into::<RequestId>(&mut snapshot);
+
+        StateSnapshotBuilder {
+            source: state,
+            snapshot,
+        }
+    }
+
+    /// The copied values, which are accessed via `FromState` in the same way as the original
+    /// `State`.
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// Returns the ID of the request which this snapshot was taken from.
+    pub fn request_id(&self) -> &str {
+        request_id(&self.state)
+    }
+}
+
+/// Selects the values which are copied into a `StateSnapshot`.
+pub struct StateSnapshotBuilder<'a> {
+    source: &'a State,
+    snapshot: State,
+}
+
+impl<'a> StateSnapshotBuilder<'a> {
+    /// Copies the value of type `T` into the snapshot, if it is present in `State`.
+    pub fn with<T>(mut self) -> Self
+    where
+        T: StateData + Clone,
+    {
+        self.source.copy_into::<T>(&mut self.snapshot);
+        self
+    }
+
+    /// Finishes building the `StateSnapshot`.
+    pub fn build(self) -> StateSnapshot {
+        StateSnapshot {
+            state: self.snapshot,
+        }
+    }
+}
+
+/// Spawns the future returned by `f` onto the executor which is running the current request,
+/// passing it the `StateSnapshot`.
+///
+/// The spawned future runs independently of the request, and its outcome does not affect the
+/// response. Other background work can be spawned via the `Executor` in `State`.
+///
+/// # Panics
+///
+/// If called outside of a running Gotham server (or `TestServer`).
+pub fn spawn_with_snapshot<F, R>(snapshot: StateSnapshot, f: F)
+where
+    F: FnOnce(StateSnapshot) -> R + Send + 'static,
+    R: IntoFuture<Item = (), Error = ()> + 'static,
+    R::Future: Send + 'static,
+{
+    trace!("[{}] spawning background work", snapshot.request_id());
+    executor::spawn(future::lazy(move || f(snapshot)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use hyper::{Body, Response, StatusCode};
+
+    use helpers::http::header::X_REQUEST_ID;
+    use state::{set_request_id, FromState};
+    use test::TestServer;
+
+    #[derive(Clone)]
+    struct Audit(&'static str);
+
+    impl StateData for Audit {}
+
+    struct Uncopied;
+
+    impl StateData for Uncopied {}
+
+    #[test]
+    fn snapshot_copies_selected_values() {
+        let mut state = State::new();
+        state.put(Audit("login"));
+        state.put(Uncopied);
+        set_request_id(&mut state);
+
+        let snapshot = StateSnapshot::builder(&state).with::<Audit>().build();
+        assert_eq!(snapshot.request_id(), request_id(&state));
+        assert_eq!(Audit::borrow_from(snapshot.state()).0, "login");
+        assert!(!snapshot.state().has::<Uncopied>());
+
+        // The original `State` is unaffected.
+        assert!(state.has::<Audit>());
+    }
+
+    #[test]
+    fn spawned_work_receives_snapshot() {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+
+        let test_server = TestServer::new(move || {
+            let tx = tx.lock().unwrap().clone();
+            Ok(move |mut state: State| {
+                state.put(Audit("logout"));
+
+                let snapshot = StateSnapshot::builder(&state).with::<Audit>().build();
+                spawn_with_snapshot(snapshot, move |snapshot| {
+                    let audit = Audit::borrow_from(snapshot.state()).0;
+                    tx.send((snapshot.request_id().to_owned(), audit)).unwrap();
+                    Ok(())
+                });
+
+                (state, Response::new(Body::empty()))
+            })
+        }).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .with_header(X_REQUEST_ID, "snapshot-id".parse().unwrap())
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let received = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(received, ("snapshot-id".to_owned(), "logout"));
+    }
+}
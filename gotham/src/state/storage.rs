@@ -0,0 +1,184 @@
+//! Defines the type-keyed storage behind `State`.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+type Value = Box<Any + Send>;
+
+/// The number of values which are stored inline and found via a linear scan. Beyond this, values
+/// are moved into a `HashMap`.
+///
+/// Gotham itself stores around ten values for each request, and applications typically add a
+/// handful more.
+const INLINE_CAPACITY: usize = 16;
+
+/// Storage for at most one value per `TypeId`.
+///
+/// Most requests store few enough values that a linear scan of a `Vec` is cheaper than hashing,
+/// and the `Vec` is allocated once with enough capacity for a typical request.
+pub(super) enum Storage {
+    Inline(Vec<(TypeId, Value)>),
+    Map(HashMap<TypeId, Value>),
+}
+
+impl Storage {
+    pub(super) fn new() -> Storage {
+        Storage::Inline(Vec::with_capacity(INLINE_CAPACITY))
+    }
+
+    /// Stores the value, replacing any existing value of the same type.
+    pub(super) fn insert(&mut self, type_id: TypeId, value: Value) {
+        let spill = match *self {
+            Storage::Map(ref mut map) => {
+                map.insert(type_id, value);
+                return;
+            }
+            Storage::Inline(ref mut entries) => {
+                if let Some(entry) = entries.iter_mut().find(|entry| entry.0 == type_id) {
+                    entry.1 = value;
+                    return;
+                }
+
+                if entries.len() < INLINE_CAPACITY {
+                    entries.push((type_id, value));
+                    return;
+                }
+
+                let mut map: HashMap<TypeId, Value> = entries.drain(..).collect();
+                map.insert(type_id, value);
+                map
+            }
+        };
+
+        *self = Storage::Map(spill);
+    }
+
+    pub(super) fn contains(&self, type_id: &TypeId) -> bool {
+        self.get(type_id).is_some()
+    }
+
+    pub(super) fn get(&self, type_id: &TypeId) -> Option<&Value> {
+        match *self {
+            Storage::Inline(ref entries) => entries
+                .iter()
+                .find(|entry| entry.0 == *type_id)
+                .map(|entry| &entry.1),
+            Storage::Map(ref map) => map.get(type_id),
+        }
+    }
+
+    pub(super) fn get_mut(&mut self, type_id: &TypeId) -> Option<&mut Value> {
+        match *self {
+            Storage::Inline(ref mut entries) => entries
+                .iter_mut()
+                .find(|entry| entry.0 == *type_id)
+                .map(|entry| &mut entry.1),
+            Storage::Map(ref mut map) => map.get_mut(type_id),
+        }
+    }
+
+    pub(super) fn remove(&mut self, type_id: &TypeId) -> Option<Value> {
+        match *self {
+            Storage::Inline(ref mut entries) => entries
+                .iter()
+                .position(|entry| entry.0 == *type_id)
+                .map(|i| entries.swap_remove(i).1),
+            Storage::Map(ref mut map) => map.remove(type_id),
+        }
+    }
+
+    /// Returns the value for `type_id`, first storing the value returned by `f` if absent.
+    pub(super) fn get_or_insert_with<F>(&mut self, type_id: TypeId, f: F) -> &mut Value
+    where
+        F: FnOnce() -> Value,
+    {
+        if !self.contains(&type_id) {
+            self.insert(type_id, f());
+        }
+
+        self.get_mut(&type_id)
+            .expect("value was inserted before borrowing")
+    }
+
+    #[cfg(test)]
+    fn is_inline(&self) -> bool {
+        match *self {
+            Storage::Inline(_) => true,
+            Storage::Map(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id<T: 'static>() -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn value(v: usize) -> Value {
+        Box::new(v)
+    }
+
+    fn read(storage: &Storage, type_id: &TypeId) -> Option<usize> {
+        storage
+            .get(type_id)
+            .and_then(|v| v.downcast_ref::<usize>())
+            .cloned()
+    }
+
+    #[test]
+    fn last_insert_wins() {
+        let mut storage = Storage::new();
+        storage.insert(id::<u8>(), value(1));
+        storage.insert(id::<u8>(), value(2));
+
+        assert_eq!(read(&storage, &id::<u8>()), Some(2));
+        assert_eq!(read(&storage, &id::<u16>()), None);
+    }
+
+    #[test]
+    fn spills_into_map_beyond_inline_capacity() {
+        macro_rules! insert_all {
+            ($storage:ident, $($t:ty),+) => {{
+                let mut n = 0;
+                $(
+                    n += 1;
+                    $storage.insert(id::<$t>(), value(n));
+                )+
+                n
+            }};
+        }
+
+        let mut storage = Storage::new();
+        let count = insert_all!(
+            storage, u8, u16, u32, u64, i8, i16, i32, i64, f32, f64, bool, char, String, (),
+            usize, isize, u128
+        );
+
+        assert!(count > INLINE_CAPACITY);
+        assert!(!storage.is_inline());
+        assert_eq!(read(&storage, &id::<u8>()), Some(1));
+        assert_eq!(read(&storage, &id::<u128>()), Some(count));
+
+        storage.insert(id::<u8>(), value(100));
+        assert_eq!(read(&storage, &id::<u8>()), Some(100));
+    }
+
+    #[test]
+    fn remove_and_get_or_insert_with() {
+        let mut storage = Storage::new();
+        storage.insert(id::<u8>(), value(1));
+        storage.insert(id::<u16>(), value(2));
+
+        assert!(storage.remove(&id::<u8>()).is_some());
+        assert!(storage.remove(&id::<u8>()).is_none());
+        assert!(!storage.contains(&id::<u8>()));
+        assert_eq!(read(&storage, &id::<u16>()), Some(2));
+
+        storage.get_or_insert_with(id::<u8>(), || value(3));
+        storage.get_or_insert_with(id::<u8>(), || value(4));
+        assert_eq!(read(&storage, &id::<u8>()), Some(3));
+    }
+}
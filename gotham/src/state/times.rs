@@ -0,0 +1,166 @@
+//! Defines the timestamps recorded by Gotham as a request is processed.
+
+use std::time::{Duration, Instant};
+
+use state::{FromState, State};
+
+/// Timestamps recorded by Gotham while processing a request, available via
+/// `RequestTimes::borrow_from(&state)`.
+///
+/// The arrival time is recorded by `GothamService` as soon as the request is received, before any
+/// `Router`, `Pipeline` or `Middleware` is invoked. The remaining markers are recorded by the
+/// route `Dispatcher` around the `Handler`, and are `None` until that point (or when the request
+/// is not dispatched by a `Router`).
+///
+/// Anything which reports on request durations should use these values rather than measuring
+/// independently, so that all reported durations agree.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::state::{FromState, State};
+/// # use gotham::state::times::RequestTimes;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let body = {
+///         let times = RequestTimes::borrow_from(&state);
+///         format!("{:?} since arrival", times.elapsed())
+///     };
+///
+///     (state, Response::new(body.into()))
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #   let response = test_server.client().get("http://localhost/").perform().unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct RequestTimes {
+    arrived: Instant,
+    handler_entered: Option<Instant>,
+    response_started: Option<Instant>,
+}
+
+impl RequestTimes {
+    /// The time at which the request was received.
+    pub fn arrived(&self) -> Instant {
+        self.arrived
+    }
+
+    /// The time at which the `Handler` was invoked, after all `Middleware` in the pipelines had
+    /// passed the request along.
+    pub fn handler_entered(&self) -> Option<Instant> {
+        self.handler_entered
+    }
+
+    /// The time at which the `Handler` completed and the response began its way back out through
+    /// the pipelines.
+    pub fn response_started(&self) -> Option<Instant> {
+        self.response_started
+    }
+
+    /// The time elapsed since the request was received.
+    pub fn elapsed(&self) -> Duration {
+        self.arrived.elapsed()
+    }
+}
+
+/// Records the arrival of a request. This is invoked by `GothamService` before control is handed
+/// to its `Router`.
+pub(crate) fn put_request_times(state: &mut State) {
+    state.put(RequestTimes {
+        arrived: Instant::now(),
+        handler_entered: None,
+        response_started: None,
+    })
+}
+
+/// Records that the `Handler` has been invoked for the request.
+pub(crate) fn mark_handler_entered(state: &mut State) {
+    if let Some(times) = RequestTimes::try_borrow_mut_from(state) {
+        times.handler_entered = Some(Instant::now());
+    }
+}
+
+/// Records that the `Handler` has completed, and the response has started.
+pub(crate) fn mark_response_started(state: &mut State) {
+    if let Some(times) = RequestTimes::try_borrow_mut_from(state) {
+        times.response_started = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::future;
+    use hyper::{Body, Response, StatusCode};
+
+    use handler::HandlerFuture;
+    use router::builder::*;
+    use test::TestServer;
+
+    #[test]
+    fn markers_are_recorded_in_order() {
+        let mut state = State::new();
+        put_request_times(&mut state);
+        assert!(RequestTimes::borrow_from(&state).handler_entered().is_none());
+
+        mark_handler_entered(&mut state);
+        mark_response_started(&mut state);
+
+        let times = RequestTimes::borrow_from(&state);
+        let entered = times.handler_entered().unwrap();
+        let started = times.response_started().unwrap();
+
+        assert!(times.arrived() <= entered);
+        assert!(entered <= started);
+        assert!(times.elapsed() >= started - times.arrived());
+    }
+
+    #[test]
+    fn markers_without_times_are_ignored() {
+        let mut state = State::new();
+        mark_handler_entered(&mut state);
+        mark_response_started(&mut state);
+        assert!(!state.has::<RequestTimes>());
+    }
+
+    #[test]
+    fn handler_sees_arrival_and_entry() {
+        fn handler(state: State) -> Box<HandlerFuture> {
+            let status = {
+                let times = RequestTimes::borrow_from(&state);
+                match (times.handler_entered(), times.response_started()) {
+                    (Some(entered), None) if times.arrived() <= entered => StatusCode::OK,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                }
+            };
+
+            let mut res = Response::new(Body::empty());
+            *res.status_mut() = status;
+            Box::new(future::ok((state, res)))
+        }
+
+        let router = build_simple_router(|route| {
+            route.get("/").to(handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,196 @@
+//! Defines a `TemplateEngine` backed by the `handlebars` crate. Available when the `handlebars`
+//! feature is enabled.
+//!
+//! ```rust
+//! # extern crate gotham;
+//! # extern crate handlebars;
+//! # extern crate hyper;
+//! # #[macro_use]
+//! # extern crate serde_derive;
+//! #
+//! # use handlebars::Handlebars;
+//! # use hyper::StatusCode;
+//! # use gotham::middleware::state::StateMiddleware;
+//! # use gotham::pipeline::new_pipeline;
+//! # use gotham::pipeline::single::single_pipeline;
+//! # use gotham::router::Router;
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::template::Template;
+//! # use gotham::template::handlebars::{HandlebarsContext, HandlebarsEngine};
+//! # use gotham::test::TestServer;
+//! #
+//! #[derive(Serialize)]
+//! struct Index {
+//!     name: &'static str,
+//!     crew: Vec<&'static str>,
+//! }
+//!
+//! fn index(state: State) -> (State, Template<HandlebarsEngine>) {
+//!     let context = HandlebarsContext::new(&Index {
+//!         name: "Gotham",
+//!         crew: vec!["Alfred", "Lucius"],
+//!     });
+//!
+//!     (state, Template::new("index", context))
+//! }
+//!
+//! fn router() -> Router {
+//!     let mut registry = Handlebars::new();
+//!     registry
+//!         .register_template_string(
+//!             "index",
+//!             "<h1>Hello, {{name}}!</h1>{{#each crew}}<p>{{this}}</p>{{/each}}",
+//!         )
+//!         .unwrap();
+//!
+//!     let (chain, pipelines) = single_pipeline(
+//!         new_pipeline()
+//!             .add(StateMiddleware::new(HandlebarsEngine::new(registry)))
+//!             .build(),
+//!     );
+//!
+//!     build_router(chain, pipelines, |route| {
+//!         route.get("/").to(index);
+//!     })
+//! }
+//! #
+//! # fn main() {
+//! #   let test_server = TestServer::new(router()).unwrap();
+//! #   let response = test_server.client().get("http://localhost/").perform().unwrap();
+//! #   assert_eq!(response.status(), StatusCode::OK);
+//! #   assert_eq!(
+//! #       response.read_utf8_body().unwrap(),
+//! #       "<h1>Hello, Gotham!</h1><p>Alfred</p><p>Lucius</p>"
+//! #   );
+//! # }
+//! ```
+
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use failure::err_msg;
+use handlebars::{Context, Handlebars, RenderError};
+use mime::{self, Mime};
+use serde::Serialize;
+
+use error::Result;
+use state::StateData;
+use template::TemplateEngine;
+
+/// The context provided to templates rendered by a `HandlebarsEngine`, created from any value
+/// which implements `Serialize`, so that templates can iterate over and branch on structured data.
+pub struct HandlebarsContext {
+    context: ::std::result::Result<Context, RenderError>,
+}
+
+impl HandlebarsContext {
+    /// Creates a `HandlebarsContext` from `data`. If `data` can't be serialized, rendering the
+    /// template fails, producing a `500 Internal Server Error`.
+    pub fn new<T>(data: &T) -> HandlebarsContext
+    where
+        T: Serialize,
+    {
+        HandlebarsContext {
+            context: Context::wraps(data),
+        }
+    }
+}
+
+/// A `TemplateEngine` which renders templates from a compiled `Handlebars` registry, producing
+/// `text/html` response bodies.
+///
+/// The registry is shared between all clones of the engine, so it is only compiled once.
+#[derive(Clone)]
+pub struct HandlebarsEngine {
+    registry: Arc<AssertUnwindSafe<Handlebars>>,
+}
+
+impl HandlebarsEngine {
+    /// Creates a new `HandlebarsEngine` from a registry with all templates registered.
+    pub fn new(registry: Handlebars) -> Self {
+        HandlebarsEngine {
+            registry: Arc::new(AssertUnwindSafe(registry)),
+        }
+    }
+}
+
+impl StateData for HandlebarsEngine {}
+
+impl TemplateEngine for HandlebarsEngine {
+    type Context = HandlebarsContext;
+
+    fn render(&self, name: &str, context: &Self::Context) -> Result<(Mime, Vec<u8>)> {
+        let context = match context.context {
+            Ok(ref context) => context,
+            Err(ref e) => {
+                return Err(err_msg(format!(
+                    "unable to serialize the context of {:?}: {}",
+                    name, e
+                )))
+            }
+        };
+
+        let rendered = self.registry.render(name, context.data())?;
+        Ok((mime::TEXT_HTML_UTF_8, rendered.into_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    struct Greeting {
+        name: &'static str,
+        admin: bool,
+        roles: Vec<&'static str>,
+    }
+
+    #[test]
+    fn renders_registered_template() {
+        let mut registry = Handlebars::new();
+        registry
+            .register_template_string("greeting", "Hello, {{name}}!")
+            .unwrap();
+
+        let engine = HandlebarsEngine::new(registry);
+        let mut data = BTreeMap::new();
+        data.insert("name", "<Gotham>");
+
+        let context = HandlebarsContext::new(&data);
+        let (mime, body) = engine.render("greeting", &context).unwrap();
+        assert_eq!(mime, mime::TEXT_HTML_UTF_8);
+        assert_eq!(body, b"Hello, &lt;Gotham&gt;!".to_vec());
+    }
+
+    #[test]
+    fn renders_structured_context() {
+        let mut registry = Handlebars::new();
+        registry
+            .register_template_string(
+                "greeting",
+                "{{name}}{{#if admin}} (admin){{/if}}:{{#each roles}} {{this}}{{/each}}",
+            )
+            .unwrap();
+
+        let engine = HandlebarsEngine::new(registry);
+        let context = HandlebarsContext::new(&Greeting {
+            name: "alice",
+            admin: true,
+            roles: vec!["editor", "reviewer"],
+        });
+
+        let (_, body) = engine.render("greeting", &context).unwrap();
+        assert_eq!(body, b"alice (admin): editor reviewer".to_vec());
+    }
+
+    #[test]
+    fn unknown_template_is_error() {
+        let engine = HandlebarsEngine::new(Handlebars::new());
+        let context = HandlebarsContext::new(&BTreeMap::<String, String>::new());
+        assert!(engine.render("missing", &context).is_err());
+    }
+}
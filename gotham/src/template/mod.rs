@@ -0,0 +1,283 @@
+//! Defines an engine-agnostic integration point for returning rendered templates from a `Handler`.
+//!
+//! A `TemplateEngine` is placed into `State` (typically via `StateMiddleware`), and handlers
+//! return a `Template` which names the template and carries its context. The `Template` is
+//! rendered when it is converted into a `Response`, producing a `200 OK` with the rendered body,
+//! or a `500 Internal Server Error` if rendering fails.
+//!
+//! ```rust
+//! # extern crate gotham;
+//! # extern crate hyper;
+//! # extern crate mime;
+//! #
+//! # use mime::Mime;
+//! # use hyper::StatusCode;
+//! # use gotham::error::Result;
+//! # use gotham::middleware::state::StateMiddleware;
+//! # use gotham::pipeline::new_pipeline;
+//! # use gotham::pipeline::single::single_pipeline;
+//! # use gotham::router::Router;
+//! # use gotham::router::builder::*;
+//! # use gotham::state::{State, StateData};
+//! # use gotham::template::{Template, TemplateEngine};
+//! # use gotham::test::TestServer;
+//! #
+//! #[derive(Clone)]
+//! struct Greeter;
+//!
+//! impl StateData for Greeter {}
+//!
+//! impl TemplateEngine for Greeter {
+//!     type Context = String;
+//!
+//!     fn render(&self, name: &str, context: &String) -> Result<(Mime, Vec<u8>)> {
+//!         let body = format!("<p>{}: Hello, {}!</p>", name, context);
+//!         Ok((mime::TEXT_HTML, body.into_bytes()))
+//!     }
+//! }
+//!
+//! fn handler(state: State) -> (State, Template<Greeter>) {
+//!     (state, Template::new("index", "Gotham".to_owned()))
+//! }
+//!
+//! fn router() -> Router {
+//!     let (chain, pipelines) = single_pipeline(
+//!         new_pipeline().add(StateMiddleware::new(Greeter)).build()
+//!     );
+//!
+//!     build_router(chain, pipelines, |route| {
+//!         route.get("/").to(handler);
+//!     })
+//! }
+//! #
+//! # fn main() {
+//! #   let test_server = TestServer::new(router()).unwrap();
+//! #   let response = test_server.client().get("http://localhost/").perform().unwrap();
+//! #   assert_eq!(response.status(), StatusCode::OK);
+//! #   let body = response.read_utf8_body().unwrap();
+//! #   assert_eq!(body, "<p>index: Hello, Gotham!</p>");
+//! # }
+//! ```
+//!
+//! Engines which compile templates into types (such as `askama`) can instead implement `Render`
+//! directly and be returned from handlers wrapped in `Rendered`.
+
+#[cfg(feature = "handlebars")]
+pub mod handlebars;
+
+use std::borrow::Cow;
+
+use failure::err_msg;
+use hyper::{Body, Response, StatusCode};
+use mime::Mime;
+
+use error::Result;
+use handler::{IntoHandlerError, IntoResponse};
+use helpers::http::response::create_response;
+use state::{request_id, FromState, State, StateData};
+
+/// A value which can be rendered into a response body, based on the current `State`.
+pub trait Render {
+    /// Renders the value, returning the media type and content of the response body.
+    fn render(&self, state: &State) -> Result<(Mime, Vec<u8>)>;
+}
+
+/// A template engine which renders named templates, and is stored in `State` for use by
+/// `Template` values.
+///
+/// Expensive resources (such as a compiled template registry) should be wrapped in an `Arc`, as
+/// the engine will be cloned for each request when attached via `StateMiddleware`.
+pub trait TemplateEngine: StateData {
+    /// The type of context which is provided to templates rendered by this engine.
+    type Context;
+
+    /// Renders the named template with the given context, returning the media type and content of
+    /// the response body.
+    fn render(&self, name: &str, context: &Self::Context) -> Result<(Mime, Vec<u8>)>;
+}
+
+/// A named template and its context, which is rendered by the `TemplateEngine` `E` found in
+/// `State` when converted into a `Response`.
+pub struct Template<E>
+where
+    E: TemplateEngine,
+{
+    name: Cow<'static, str>,
+    context: E::Context,
+}
+
+impl<E> Template<E>
+where
+    E: TemplateEngine,
+{
+    /// Creates a new `Template` which renders the named template with the given context.
+    pub fn new<N>(name: N, context: E::Context) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Template {
+            name: name.into(),
+            context,
+        }
+    }
+}
+
+impl<E> Render for Template<E>
+where
+    E: TemplateEngine,
+{
+    fn render(&self, state: &State) -> Result<(Mime, Vec<u8>)> {
+        match E::try_borrow_from(state) {
+            Some(engine) => engine.render(&self.name, &self.context),
+            None => Err(err_msg(format!(
+                "no template engine present in State to render {:?}",
+                self.name
+            ))),
+        }
+    }
+}
+
+impl<E> IntoResponse<Body> for Template<E>
+where
+    E: TemplateEngine,
+{
+    fn into_response(self, state: &State) -> Response<Body> {
+        render_response(&self, state)
+    }
+}
+
+/// Wraps a `Render` value, allowing it to be converted into a `Response`.
+pub struct Rendered<R>(pub R)
+where
+    R: Render;
+
+impl<R> IntoResponse<Body> for Rendered<R>
+where
+    R: Render,
+{
+    fn into_response(self, state: &State) -> Response<Body> {
+        render_response(&self.0, state)
+    }
+}
+
+fn render_response<R>(r: &R, state: &State) -> Response<Body>
+where
+    R: Render,
+{
+    match r.render(state) {
+        Ok((mime, body)) => create_response(state, StatusCode::OK, Some((body, mime))),
+        Err(e) => {
+            error!("[{}] failed to render template: {}", request_id(state), e);
+            e.compat().into_handler_error().into_response(state)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use middleware::state::StateMiddleware;
+    use pipeline::new_pipeline;
+    use pipeline::single::single_pipeline;
+    use router::builder::*;
+    use router::Router;
+    use test::TestServer;
+
+    #[derive(Clone)]
+    struct Engine;
+
+    impl StateData for Engine {}
+
+    impl TemplateEngine for Engine {
+        type Context = Option<&'static str>;
+
+        fn render(&self, name: &str, context: &Self::Context) -> Result<(Mime, Vec<u8>)> {
+            match *context {
+                Some(value) => Ok((mime::TEXT_PLAIN, format!("{}={}", name, value).into_bytes())),
+                None => Err(err_msg("missing value")),
+            }
+        }
+    }
+
+    struct Precompiled;
+
+    impl Render for Precompiled {
+        fn render(&self, _state: &State) -> Result<(Mime, Vec<u8>)> {
+            Ok((mime::TEXT_HTML, b"<p>precompiled</p>".to_vec()))
+        }
+    }
+
+    fn good(state: State) -> (State, Template<Engine>) {
+        (state, Template::new("good", Some("value")))
+    }
+
+    fn bad(state: State) -> (State, Template<Engine>) {
+        (state, Template::new("bad", None))
+    }
+
+    fn precompiled(state: State) -> (State, Rendered<Precompiled>) {
+        (state, Rendered(Precompiled))
+    }
+
+    fn router() -> Router {
+        let (chain, pipelines) =
+            single_pipeline(new_pipeline().add(StateMiddleware::new(Engine)).build());
+
+        build_router(chain, pipelines, |route| {
+            route.get("/good").to(good);
+            route.get("/bad").to(bad);
+            route.get("/precompiled").to(precompiled);
+        })
+    }
+
+    #[test]
+    fn renders_template() {
+        let test_server = TestServer::new(router()).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/good")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "good=value");
+    }
+
+    #[test]
+    fn render_failure_is_server_error() {
+        let test_server = TestServer::new(router()).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/bad")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn missing_engine_is_server_error() {
+        let test_server = TestServer::new(|| Ok(good)).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn renders_render_implementation() {
+        let test_server = TestServer::new(router()).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/precompiled")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "<p>precompiled</p>");
+    }
+}
@@ -23,6 +23,7 @@ use tokio::runtime::Runtime;
 use tokio::timer::Delay;
 
 use handler::NewHandler;
+use service::GothamService;
 
 use error::*;
 
@@ -93,7 +94,7 @@ impl TestServer {
         let listener = TcpListener::bind(&"127.0.0.1:0".parse()?)?;
         let addr = listener.local_addr()?;
 
-        let service_stream = super::bind_server(listener, new_handler);
+        let service_stream = super::serve(listener, GothamService::new(new_handler));
         runtime.spawn(service_stream);
 
         let data = TestServerData {
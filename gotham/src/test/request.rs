@@ -1,5 +1,5 @@
 use hyper::header::{HeaderValue, IntoHeaderName};
-use hyper::{Body, Method, Request, Uri};
+use hyper::{Body, Method, Request, Uri, Version};
 
 use test::{TestClient, TestResponse};
 
@@ -39,6 +39,17 @@ impl RequestBuilder {
         RequestBuilder { request, ..self }
     }
 
+    /// Sets the HTTP version of the underlying `Request`, which is HTTP/1.1 by default.
+    pub fn with_version(self, version: Version) -> RequestBuilder {
+        let mut request = self.request;
+
+        if let Ok(ref mut req) = request {
+            *req.version_mut() = version;
+        }
+
+        RequestBuilder { request, ..self }
+    }
+
     /// Adds the given body into the underlying `Request`, replacing any existing body.
     pub fn with_body<T>(self, body: T) -> RequestBuilder
     where
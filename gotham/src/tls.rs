@@ -0,0 +1,237 @@
+//! Defines `TlsConfig`, the certificate chain and private key which `gotham::start_with_tls`
+//! presents to clients. Requires the `tls` feature.
+
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+use failure::err_msg;
+use tokio_rustls::rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
+use tokio_rustls::rustls::{NoClientAuth, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+use error::Result;
+
+/// The TLS configuration of an application started via `gotham::start_with_tls`.
+///
+/// Each connection completes a TLS handshake before any request is read from it. A connection
+/// whose handshake fails is logged and closed, without affecting any other connection. Requests
+/// received over TLS are marked as such in `ConnectionInfo`, so that `ConnectionInfo::tls` is
+/// `true`.
+///
+/// A `TlsConfig` created from PEM offers HTTP/2 and HTTP/1.1 via ALPN, preferring HTTP/2. The
+/// version negotiated for a request is available via `Version::borrow_from(&state)`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response};
+/// # use gotham::state::State;
+/// # use gotham::tls::TlsConfig;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let tls_config = TlsConfig::from_pem_files("certs/fullchain.pem", "certs/privkey.pem")
+///     .expect("unable to load the certificate and private key");
+///
+/// gotham::start_with_tls("0.0.0.0:443", || Ok(handler), tls_config);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct TlsConfig {
+    server_config: Arc<ServerConfig>,
+}
+
+impl TlsConfig {
+    /// Creates a `TlsConfig` from a PEM encoded certificate chain, starting with the certificate
+    /// of the server, and a PEM encoded private key in PKCS #8 or PKCS #1 (RSA) form.
+    pub fn from_pem(cert_chain: &[u8], private_key: &[u8]) -> Result<TlsConfig> {
+        let cert_chain = certs(&mut BufReader::new(cert_chain))
+            .map_err(|()| err_msg("invalid PEM in certificate chain"))?;
+
+        if cert_chain.is_empty() {
+            return Err(err_msg("no certificates in certificate chain"));
+        }
+
+        let mut keys = pkcs8_private_keys(&mut BufReader::new(private_key))
+            .map_err(|()| err_msg("invalid PEM in private key"))?;
+
+        if keys.is_empty() {
+            keys = rsa_private_keys(&mut BufReader::new(private_key))
+                .map_err(|()| err_msg("invalid PEM in private key"))?;
+        }
+
+        let private_key = keys
+            .into_iter()
+            .next()
+            .ok_or_else(|| err_msg("no private key found"))?;
+
+        let mut server_config = ServerConfig::new(NoClientAuth::new());
+        server_config
+            .set_single_cert(cert_chain, private_key)
+            .map_err(|e| err_msg(format!("invalid certificate or private key: {}", e)))?;
+        server_config.set_protocols(&["h2".to_owned(), "http/1.1".to_owned()]);
+
+        Ok(TlsConfig::from_server_config(server_config))
+    }
+
+    /// Creates a `TlsConfig` from files containing a PEM encoded certificate chain and private
+    /// key, as `from_pem` does.
+    pub fn from_pem_files<C, K>(cert_chain: C, private_key: K) -> Result<TlsConfig>
+    where
+        C: AsRef<Path>,
+        K: AsRef<Path>,
+    {
+        let cert_chain = fs::read(cert_chain)?;
+        let private_key = fs::read(private_key)?;
+
+        TlsConfig::from_pem(&cert_chain, &private_key)
+    }
+
+    /// Creates a `TlsConfig` from a rustls `ServerConfig`, for settings which `from_pem` doesn't
+    /// provide, such as client certificate authentication. Connections are only served over
+    /// HTTP/2 when the `ServerConfig` offers `h2` via ALPN, or when the client sends the HTTP/2
+    /// preface without negotiating a protocol.
+    pub fn from_server_config(server_config: ServerConfig) -> TlsConfig {
+        TlsConfig {
+            server_config: Arc::new(server_config),
+        }
+    }
+
+    pub(crate) fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.server_config.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+    use std::net::{self, SocketAddr};
+
+    use hyper::{Body, Response, StatusCode};
+    use tokio::net::TcpListener;
+    use tokio::runtime::Runtime;
+    use tokio_rustls::rustls::{ClientConfig, ClientSession, Session, Stream};
+    use tokio_rustls::webpki::DNSNameRef;
+
+    use helpers::http::response::create_text_response;
+    use service::GothamService;
+    use state::connection::ConnectionInfo;
+    use state::{FromState, State};
+
+    // A certificate for `localhost`, issued by the `Gotham Test CA` in `ca.pem`, which are only
+    // used by these tests.
+    const CA: &'static [u8] = include_bytes!("fixtures/tls/ca.pem");
+    const CERT: &'static [u8] = include_bytes!("fixtures/tls/cert.pem");
+    const KEY: &'static [u8] = include_bytes!("fixtures/tls/key.pem");
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let tls = ConnectionInfo::borrow_from(&state).tls();
+        let res = create_text_response(&state, StatusCode::OK, format!("tls={}", tls));
+        (state, res)
+    }
+
+    fn start(runtime: &mut Runtime) -> SocketAddr {
+        let config = TlsConfig::from_pem(CERT, KEY).unwrap();
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let service = GothamService::new(|| Ok(handler));
+        runtime.spawn(::serve_tls(listener, service, config.acceptor()));
+        addr
+    }
+
+    fn client_session(protocols: &[String]) -> ClientSession {
+        let mut client_config = ClientConfig::new();
+        client_config
+            .root_store
+            .add_pem_file(&mut BufReader::new(CA))
+            .unwrap();
+        client_config.set_protocols(protocols);
+
+        let name = DNSNameRef::try_from_ascii_str("localhost").unwrap();
+        ClientSession::new(&Arc::new(client_config), name)
+    }
+
+    fn https_get(addr: SocketAddr) -> String {
+        let mut session = client_session(&[]);
+        let mut socket = net::TcpStream::connect(addr).unwrap();
+
+        let mut stream = Stream::new(&mut session, &mut socket);
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+
+        // The server closes the connection after the response, without a TLS `close_notify`.
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response);
+        String::from_utf8(response).unwrap()
+    }
+
+    #[test]
+    fn serves_requests_over_tls() {
+        let mut runtime = Runtime::new().unwrap();
+        let addr = start(&mut runtime);
+
+        let response = https_get(addr);
+        assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+        assert!(response.ends_with("tls=true"), response);
+    }
+
+    #[test]
+    fn failed_handshake_is_isolated() {
+        let mut runtime = Runtime::new().unwrap();
+        let addr = start(&mut runtime);
+
+        let mut plain = net::TcpStream::connect(addr).unwrap();
+        plain
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+
+        let mut response = Vec::new();
+        let _ = plain.read_to_end(&mut response);
+        assert!(!String::from_utf8_lossy(&response).contains("tls="));
+
+        let response = https_get(addr);
+        assert!(response.ends_with("tls=true"), response);
+    }
+
+    #[test]
+    fn negotiates_http2_via_alpn() {
+        let mut runtime = Runtime::new().unwrap();
+        let addr = start(&mut runtime);
+
+        let mut session = client_session(&["h2".to_owned(), "http/1.1".to_owned()]);
+        let mut socket = net::TcpStream::connect(addr).unwrap();
+        let mut stream = Stream::new(&mut session, &mut socket);
+
+        // The HTTP/2 connection preface, followed by an empty SETTINGS frame.
+        stream
+            .write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\0\0\0\x04\0\0\0\0\0")
+            .unwrap();
+
+        // The server responds with a SETTINGS frame of its own.
+        let mut frame_header = [0; 9];
+        stream.read_exact(&mut frame_header).unwrap();
+        assert_eq!(frame_header[3], 0x4);
+        drop(stream);
+
+        assert_eq!(session.get_alpn_protocol(), Some("h2"));
+    }
+
+    #[test]
+    fn rejects_invalid_pem() {
+        assert!(TlsConfig::from_pem(b"", KEY).is_err());
+        assert!(TlsConfig::from_pem(CERT, b"").is_err());
+        assert!(TlsConfig::from_pem(CERT, CERT).is_err());
+    }
+}
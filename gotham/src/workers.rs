@@ -0,0 +1,273 @@
+//! Defines `WorkerConfig`, which determines how many worker threads an application started via
+//! `ServerBuilder::workers` or `gotham::start_with_workers` runs, and how connections are
+//! accepted across them.
+
+use std::net;
+use std::thread;
+
+use tokio::net::TcpListener;
+use tokio::reactor::Handle;
+use tokio::runtime::current_thread;
+
+use handler::NewHandler;
+use listeners::Listeners;
+use service::GothamService;
+
+/// How the listening socket of an application is shared between its worker threads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListenerLayout {
+    /// A single task accepts every connection, on a pool of worker threads which share one
+    /// reactor. Connections are spread across the workers as they become busy, so a slow request
+    /// doesn't hold up the other connections of the worker which accepted it. This is the layout
+    /// used by `gotham::start` and `gotham::start_with_num_threads`.
+    Shared,
+
+    /// Each worker thread runs its own reactor, and accepts connections from its own duplicate
+    /// of the listening socket. A connection stays on the worker which accepted it, which avoids
+    /// handing work between threads, but leaves its requests waiting while that worker is busy,
+    /// even while other workers are idle. Suited to many short requests which don't block.
+    PerWorker,
+}
+
+/// The number of worker threads which an application runs, and the `ListenerLayout` of the
+/// listening socket between them.
+///
+/// By default, there's one worker thread for each logical CPU, sharing a single listener. In a
+/// small container, a single worker thread is often enough, and avoids the overhead of moving
+/// work between threads.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response};
+/// # use gotham::state::State;
+/// # use gotham::workers::{ListenerLayout, WorkerConfig};
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #   (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let workers = WorkerConfig::new()
+///     .with_threads(4)
+///     .with_listener_layout(ListenerLayout::PerWorker);
+///
+/// gotham::start_with_workers("127.0.0.1:7878", || Ok(handler), workers);
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WorkerConfig {
+    threads: usize,
+    listener_layout: ListenerLayout,
+}
+
+impl WorkerConfig {
+    /// Creates a `WorkerConfig` with one worker thread for each logical CPU, and a
+    /// `ListenerLayout::Shared` listener.
+    pub fn new() -> WorkerConfig {
+        WorkerConfig {
+            threads: ::num_cpus::get(),
+            listener_layout: ListenerLayout::Shared,
+        }
+    }
+
+    /// Sets the number of worker threads, which must be at least one.
+    pub fn with_threads(self, threads: usize) -> WorkerConfig {
+        assert!(threads > 0, "at least one worker thread is required");
+        WorkerConfig { threads, ..self }
+    }
+
+    /// Sets how the listening socket is shared between the worker threads.
+    pub fn with_listener_layout(self, listener_layout: ListenerLayout) -> WorkerConfig {
+        WorkerConfig {
+            listener_layout,
+            ..self
+        }
+    }
+
+    /// The number of worker threads.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// How the listening socket is shared between the worker threads.
+    pub fn listener_layout(&self) -> ListenerLayout {
+        self.listener_layout
+    }
+}
+
+impl Default for WorkerConfig {
+    fn default() -> WorkerConfig {
+        WorkerConfig::new()
+    }
+}
+
+/// Serves connections accepted by `listeners` on `threads` worker threads, each of which accepts
+/// from its own duplicate of the listeners as described by `ListenerLayout::PerWorker`, until
+/// every worker has stopped.
+pub(crate) fn run_per_worker<NH>(listeners: Listeners, service: GothamService<NH>, threads: usize)
+where
+    NH: NewHandler + 'static,
+{
+    let workers: Vec<_> = (0..threads)
+        .map(|i| {
+            let listeners = listeners
+                .try_clone()
+                .expect("unable to duplicate TCP listener");
+            let service = service.clone();
+
+            thread::Builder::new()
+                .name(format!("gotham-worker-{}", i))
+                .spawn(move || run_worker(listeners, service, i == 0))
+                .expect("unable to start worker thread")
+        })
+        .collect();
+
+    // The original listeners are closed once each worker holds its own duplicate.
+    drop(listeners);
+
+    for worker in workers {
+        if worker.join().is_err() {
+            error!("worker thread panicked");
+        }
+    }
+}
+
+/// Runs a single worker of a `ListenerLayout::PerWorker` application, on the current thread.
+/// The first worker logs the listeners as they start.
+fn run_worker<NH>(listeners: Listeners, service: GothamService<NH>, announce: bool)
+where
+    NH: NewHandler + 'static,
+{
+    let mut runtime = current_thread::Runtime::new().expect("unable to start worker reactor");
+
+    // The listeners are registered within the runtime, so that they use the reactor of this
+    // thread.
+    for accept in listeners.serve(service, announce) {
+        runtime.spawn(accept);
+    }
+
+    runtime.run().unwrap();
+}
+
+/// Registers `listener` with the reactor of the current runtime, switching it to non-blocking mode
+/// first, as a listener bound outside of Gotham is usually in blocking mode.
+pub(crate) fn from_std(listener: net::TcpListener) -> TcpListener {
+    register(listener, &Handle::default())
+}
+
+/// Registers `listener` with the reactor of `handle`, as `from_std` does for the current runtime.
+pub(crate) fn register(listener: net::TcpListener, handle: &Handle) -> TcpListener {
+    listener
+        .set_nonblocking(true)
+        .expect("unable to set TCP listener to non-blocking");
+
+    TcpListener::from_std(listener, handle).expect("unable to open TCP listener")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+    use std::net::SocketAddr;
+
+    use hyper::{Body, Response, StatusCode};
+
+    use helpers::http::response::create_text_response;
+    use server::ServerBuilder;
+    use state::State;
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let res = create_text_response(&state, StatusCode::OK, "served");
+        (state, res)
+    }
+
+    /// Starts an application in the background, returning the address it's listening on.
+    fn start(config: WorkerConfig) -> SocketAddr {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            ServerBuilder::new(|| Ok(handler))
+                .listener(listener)
+                .workers(config)
+                .run()
+                .unwrap()
+        });
+        addr
+    }
+
+    fn get(addr: SocketAddr) -> String {
+        let mut stream = net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn assert_serves(config: WorkerConfig) {
+        let addr = start(config);
+
+        // More connections than workers, so that each worker is likely to accept one.
+        let clients: Vec<_> = (0..config.threads() * 2)
+            .map(|_| thread::spawn(move || get(addr)))
+            .collect();
+
+        for client in clients {
+            let response = client.join().unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+            assert!(response.ends_with("served"), response);
+        }
+    }
+
+    #[test]
+    fn defaults_to_logical_cpus() {
+        let config = WorkerConfig::default();
+        assert_eq!(config.threads(), ::num_cpus::get());
+        assert_eq!(config.listener_layout(), ListenerLayout::Shared);
+    }
+
+    #[test]
+    fn serves_with_shared_listener() {
+        for &threads in &[1, 4] {
+            assert_serves(WorkerConfig::new().with_threads(threads));
+        }
+    }
+
+    #[test]
+    fn serves_with_listener_per_worker() {
+        for &threads in &[1, 4] {
+            let config = WorkerConfig::new()
+                .with_threads(threads)
+                .with_listener_layout(ListenerLayout::PerWorker);
+
+            assert_serves(config);
+        }
+    }
+
+    #[test]
+    fn starts_on_bound_listener() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(port != 0);
+
+        thread::spawn(move || ::start_on_listener(listener, || Ok(handler)));
+
+        let response = get(SocketAddr::from(([127, 0, 0, 1], port)));
+        assert!(response.starts_with("HTTP/1.1 200 OK"), response);
+        assert!(response.ends_with("served"), response);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one worker thread is required")]
+    fn rejects_zero_threads() {
+        WorkerConfig::new().with_threads(0);
+    }
+}